@@ -511,6 +511,11 @@ fn parse_uri_filename(uri: &str) -> Result<(String, bool, bool), ()> {
                     }
                     // "private" is also valid but is the default behavior
                 }
+                // SQLite's memdb VFS: names starting with '/' are shared.
+                "vfs" if value == "memdb" => {
+                    is_memory = true;
+                    cache_shared |= path.starts_with('/');
+                }
                 _ => {}
             }
         }
@@ -38,6 +38,7 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 pub mod connection;
 pub mod params;
+pub mod query_builder;
 mod rows;
 pub mod transaction;
 pub mod value;
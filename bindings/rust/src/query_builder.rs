@@ -0,0 +1,951 @@
+//! A minimal typed query builder.
+//!
+//! Tables and their columns are declared once with the [`table!`](crate::table) macro,
+//! which [`generate_tables`] writes for every table of an existing schema.
+//! Each column is a typed constant tied to its table, so a filter, ordering or
+//! assignment that mixes columns of different tables, or binds a value of the
+//! wrong type, is rejected at compile time. Built queries render to plain SQL
+//! with positional `?` placeholders and carry their bound parameters.
+//!
+//! This is intentionally not an ORM: there are no joins, relations or result
+//! mapping. Rows come back as regular [`Rows`].
+//!
+//! ```rust,no_run
+//! # async fn run(conn: turso::Connection) -> turso::Result<()> {
+//! use turso::query_builder::{delete_from, insert_into, select, update};
+//!
+//! turso::table! {
+//!     pub struct Users as "users" {
+//!         id: i64,
+//!         email: String,
+//!         age: Option<i64>,
+//!     }
+//! }
+//!
+//! insert_into::<Users>()
+//!     .value(Users::email, "alice@example.org")
+//!     .value(Users::age, 30i64)
+//!     .build()
+//!     .execute(&conn)
+//!     .await?;
+//!
+//! let mut rows = select((Users::id, Users::email))
+//!     .filter(Users::age.gt(18i64).and(Users::email.like("%@example.org")))
+//!     .order_by(Users::id.desc())
+//!     .limit(10)
+//!     .build()
+//!     .query(&conn)
+//!     .await?;
+//!
+//! update::<Users>()
+//!     .set(Users::age, None)
+//!     .filter(Users::id.eq(1))
+//!     .build()?
+//!     .execute(&conn)
+//!     .await?;
+//!
+//! delete_from::<Users>()
+//!     .filter(Users::age.is_null())
+//!     .build()
+//!     .execute(&conn)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+use crate::{Connection, Error, Result, Rows, Value};
+
+/// A table declared with [`table!`](crate::table).
+pub trait Table {
+    /// The table name as it appears in the schema.
+    const NAME: &'static str;
+    /// The declared column names, in declaration order.
+    const COLUMNS: &'static [&'static str];
+}
+
+/// A column of table `T` holding values of type `V`.
+pub struct Column<T, V> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> (T, V)>,
+}
+
+impl<T, V> Clone for Column<T, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, V> Copy for Column<T, V> {}
+
+impl<T, V> std::fmt::Debug for Column<T, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Column").field(&self.name).finish()
+    }
+}
+
+impl<T: Table, V: Into<Value>> Column<T, V> {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The column name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// `column = value`, or `column IS NULL` when `value` is NULL.
+    pub fn eq(self, value: impl Into<V>) -> Expr<T> {
+        self.compare_or_null_check("=", " IS NULL", value)
+    }
+
+    /// `column != value`, or `column IS NOT NULL` when `value` is NULL.
+    pub fn ne(self, value: impl Into<V>) -> Expr<T> {
+        self.compare_or_null_check("!=", " IS NOT NULL", value)
+    }
+
+    pub fn lt(self, value: impl Into<V>) -> Expr<T> {
+        self.compare("<", bind::<V>(value))
+    }
+
+    pub fn le(self, value: impl Into<V>) -> Expr<T> {
+        self.compare("<=", bind::<V>(value))
+    }
+
+    pub fn gt(self, value: impl Into<V>) -> Expr<T> {
+        self.compare(">", bind::<V>(value))
+    }
+
+    pub fn ge(self, value: impl Into<V>) -> Expr<T> {
+        self.compare(">=", bind::<V>(value))
+    }
+
+    /// `column LIKE pattern`. The pattern is always bound as text.
+    pub fn like(self, pattern: impl Into<String>) -> Expr<T> {
+        let mut sql = String::new();
+        push_ident(&mut sql, self.name);
+        sql.push_str(" LIKE ?");
+        Expr::new(sql, vec![Value::Text(pattern.into())])
+    }
+
+    pub fn is_null(self) -> Expr<T> {
+        self.null_check(" IS NULL")
+    }
+
+    pub fn is_not_null(self) -> Expr<T> {
+        self.null_check(" IS NOT NULL")
+    }
+
+    /// `column IN (...)`. An empty list renders as an always-false expression,
+    /// matching SQLite's semantics for `x IN ()`.
+    pub fn in_list<I>(self, values: I) -> Expr<T>
+    where
+        I: IntoIterator,
+        I::Item: Into<V>,
+    {
+        let params: Vec<Value> = values
+            .into_iter()
+            .map(|v| Into::<V>::into(v).into())
+            .collect();
+        if params.is_empty() {
+            return Expr::new("0".to_string(), params);
+        }
+        let mut sql = String::new();
+        push_ident(&mut sql, self.name);
+        sql.push_str(" IN (");
+        push_placeholders(&mut sql, params.len());
+        sql.push(')');
+        Expr::new(sql, params)
+    }
+
+    pub fn asc(self) -> Ordering<T> {
+        Ordering {
+            column: self.name,
+            descending: false,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn desc(self) -> Ordering<T> {
+        Ordering {
+            column: self.name,
+            descending: true,
+            _marker: PhantomData,
+        }
+    }
+
+    fn compare(self, op: &str, value: Value) -> Expr<T> {
+        let mut sql = String::new();
+        push_ident(&mut sql, self.name);
+        write!(sql, " {op} ?").unwrap();
+        Expr::new(sql, vec![value])
+    }
+
+    /// Compare with `op`, unless `value` is NULL: `x = NULL` is never true, so
+    /// the comparison becomes `null_check` instead.
+    fn compare_or_null_check(self, op: &str, null_check: &str, value: impl Into<V>) -> Expr<T> {
+        match bind::<V>(value) {
+            Value::Null => self.null_check(null_check),
+            value => self.compare(op, value),
+        }
+    }
+
+    fn null_check(self, check: &str) -> Expr<T> {
+        let mut sql = String::new();
+        push_ident(&mut sql, self.name);
+        sql.push_str(check);
+        Expr::new(sql, Vec::new())
+    }
+}
+
+fn bind<V: Into<Value>>(value: impl Into<V>) -> Value {
+    Into::<V>::into(value).into()
+}
+
+/// A boolean expression over the columns of table `T`.
+#[derive(Debug, Clone)]
+pub struct Expr<T> {
+    sql: String,
+    params: Vec<Value>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Expr<T> {
+    fn new(sql: String, params: Vec<Value>) -> Self {
+        Self {
+            sql,
+            params,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn and(self, other: Expr<T>) -> Expr<T> {
+        self.combine("AND", other)
+    }
+
+    pub fn or(self, other: Expr<T>) -> Expr<T> {
+        self.combine("OR", other)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Expr<T> {
+        Expr::new(format!("NOT ({})", self.sql), self.params)
+    }
+
+    fn combine(mut self, op: &str, other: Expr<T>) -> Expr<T> {
+        // Parenthesize both sides so the caller's nesting is preserved
+        // regardless of SQL operator precedence.
+        let sql = format!("({}) {op} ({})", self.sql, other.sql);
+        self.params.extend(other.params);
+        Expr::new(sql, self.params)
+    }
+}
+
+/// An `ORDER BY` term over a column of table `T`.
+#[derive(Debug, Clone, Copy)]
+pub struct Ordering<T> {
+    column: &'static str,
+    descending: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// The set of result columns of a `SELECT` over table `T`.
+///
+/// Implemented for a single [`Column`], tuples of columns of the same table
+/// and [`All`].
+pub trait Selection<T> {
+    #[doc(hidden)]
+    fn write_columns(&self, out: &mut String);
+}
+
+/// Selects every column of the table, i.e. `SELECT *`.
+pub struct All<T>(PhantomData<fn() -> T>);
+
+impl<T> All<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for All<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Selection<T> for All<T> {
+    fn write_columns(&self, out: &mut String) {
+        out.push('*');
+    }
+}
+
+impl<T, V> Selection<T> for Column<T, V> {
+    fn write_columns(&self, out: &mut String) {
+        push_ident(out, self.name);
+    }
+}
+
+macro_rules! tuple_selection {
+    ($(($field:tt $vtype:ident)),* $(,)?) => {
+        impl<T, $($vtype,)*> Selection<T> for ($(Column<T, $vtype>,)*) {
+            fn write_columns(&self, out: &mut String) {
+                let names = [$(self.$field.name,)*];
+                for (i, name) in names.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    push_ident(out, name);
+                }
+            }
+        }
+    };
+}
+
+tuple_selection!((0 A));
+tuple_selection!((0 A), (1 B));
+tuple_selection!((0 A), (1 B), (2 C));
+tuple_selection!((0 A), (1 B), (2 C), (3 D));
+tuple_selection!((0 A), (1 B), (2 C), (3 D), (4 E));
+tuple_selection!((0 A), (1 B), (2 C), (3 D), (4 E), (5 F));
+tuple_selection!((0 A), (1 B), (2 C), (3 D), (4 E), (5 F), (6 G));
+tuple_selection!((0 A), (1 B), (2 C), (3 D), (4 E), (5 F), (6 G), (7 H));
+
+/// A rendered statement together with its bound parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    sql: String,
+    params: Vec<Value>,
+}
+
+impl Query {
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn params(&self) -> &[Value] {
+        &self.params
+    }
+
+    pub fn into_parts(self) -> (String, Vec<Value>) {
+        (self.sql, self.params)
+    }
+
+    /// Run the statement and return its rows.
+    pub async fn query(self, conn: &Connection) -> Result<Rows> {
+        conn.query(self.sql, self.params).await
+    }
+
+    /// Run the statement and return the number of changed rows.
+    pub async fn execute(self, conn: &Connection) -> Result<u64> {
+        conn.execute(self.sql, self.params).await
+    }
+}
+
+/// Start a `SELECT` of `columns` from their table.
+pub fn select<T: Table, S: Selection<T>>(columns: S) -> Select<T, S> {
+    Select {
+        columns,
+        distinct: false,
+        filter: None,
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+    }
+}
+
+/// Start a `SELECT *` from table `T`.
+pub fn select_all<T: Table>() -> Select<T, All<T>> {
+    select(All::new())
+}
+
+pub struct Select<T, S> {
+    columns: S,
+    distinct: bool,
+    filter: Option<Expr<T>>,
+    order_by: Vec<Ordering<T>>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl<T: Table, S: Selection<T>> Select<T, S> {
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    /// Add a `WHERE` condition. Repeated calls are combined with `AND`.
+    pub fn filter(mut self, expr: Expr<T>) -> Self {
+        self.filter = and_filter(self.filter.take(), expr);
+        self
+    }
+
+    pub fn order_by(mut self, ordering: Ordering<T>) -> Self {
+        self.order_by.push(ordering);
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: i64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut sql = String::from("SELECT ");
+        if self.distinct {
+            sql.push_str("DISTINCT ");
+        }
+        self.columns.write_columns(&mut sql);
+        sql.push_str(" FROM ");
+        push_ident(&mut sql, T::NAME);
+        let mut params = Vec::new();
+        push_where(&mut sql, &mut params, self.filter);
+        for (i, ordering) in self.order_by.iter().enumerate() {
+            sql.push_str(if i == 0 { " ORDER BY " } else { ", " });
+            push_ident(&mut sql, ordering.column);
+            if ordering.descending {
+                sql.push_str(" DESC");
+            }
+        }
+        // OFFSET is only valid after LIMIT; a negative LIMIT means "no limit".
+        if self.limit.is_some() || self.offset.is_some() {
+            sql.push_str(" LIMIT ?");
+            params.push(Value::Integer(self.limit.unwrap_or(-1)));
+        }
+        if let Some(offset) = self.offset {
+            sql.push_str(" OFFSET ?");
+            params.push(Value::Integer(offset));
+        }
+        Query { sql, params }
+    }
+}
+
+/// Start an `INSERT` into table `T`.
+pub fn insert_into<T: Table>() -> Insert<T> {
+    Insert {
+        columns: Vec::new(),
+        params: Vec::new(),
+        _marker: PhantomData,
+    }
+}
+
+pub struct Insert<T> {
+    columns: Vec<&'static str>,
+    params: Vec<Value>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Table> Insert<T> {
+    pub fn value<V: Into<Value>>(mut self, column: Column<T, V>, value: impl Into<V>) -> Self {
+        self.columns.push(column.name);
+        self.params.push(Into::<V>::into(value).into());
+        self
+    }
+
+    /// Render the statement. Without any values this is `DEFAULT VALUES`.
+    pub fn build(self) -> Query {
+        let mut sql = String::from("INSERT INTO ");
+        push_ident(&mut sql, T::NAME);
+        if self.columns.is_empty() {
+            sql.push_str(" DEFAULT VALUES");
+        } else {
+            sql.push_str(" (");
+            push_ident_list(&mut sql, &self.columns);
+            sql.push_str(") VALUES (");
+            push_placeholders(&mut sql, self.params.len());
+            sql.push(')');
+        }
+        Query {
+            sql,
+            params: self.params,
+        }
+    }
+}
+
+/// Start an `UPDATE` of table `T`.
+pub fn update<T: Table>() -> Update<T> {
+    Update {
+        columns: Vec::new(),
+        params: Vec::new(),
+        filter: None,
+    }
+}
+
+pub struct Update<T> {
+    columns: Vec<&'static str>,
+    params: Vec<Value>,
+    filter: Option<Expr<T>>,
+}
+
+impl<T: Table> Update<T> {
+    pub fn set<V: Into<Value>>(mut self, column: Column<T, V>, value: impl Into<V>) -> Self {
+        self.columns.push(column.name);
+        self.params.push(Into::<V>::into(value).into());
+        self
+    }
+
+    /// Add a `WHERE` condition. Repeated calls are combined with `AND`.
+    pub fn filter(mut self, expr: Expr<T>) -> Self {
+        self.filter = and_filter(self.filter.take(), expr);
+        self
+    }
+
+    /// Render the statement. Fails if no column was [`set`](Self::set).
+    pub fn build(self) -> Result<Query> {
+        if self.columns.is_empty() {
+            return Err(Error::Misuse(format!(
+                "UPDATE of {} must set at least one column",
+                T::NAME
+            )));
+        }
+        let mut sql = String::from("UPDATE ");
+        push_ident(&mut sql, T::NAME);
+        sql.push_str(" SET ");
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                sql.push_str(", ");
+            }
+            push_ident(&mut sql, column);
+            sql.push_str(" = ?");
+        }
+        let mut params = self.params;
+        push_where(&mut sql, &mut params, self.filter);
+        Ok(Query { sql, params })
+    }
+}
+
+/// Start a `DELETE` from table `T`.
+pub fn delete_from<T: Table>() -> Delete<T> {
+    Delete { filter: None }
+}
+
+pub struct Delete<T> {
+    filter: Option<Expr<T>>,
+}
+
+impl<T: Table> Delete<T> {
+    /// Add a `WHERE` condition. Repeated calls are combined with `AND`.
+    pub fn filter(mut self, expr: Expr<T>) -> Self {
+        self.filter = and_filter(self.filter.take(), expr);
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut sql = String::from("DELETE FROM ");
+        push_ident(&mut sql, T::NAME);
+        let mut params = Vec::new();
+        push_where(&mut sql, &mut params, self.filter);
+        Query { sql, params }
+    }
+}
+
+fn and_filter<T>(existing: Option<Expr<T>>, expr: Expr<T>) -> Option<Expr<T>> {
+    Some(match existing {
+        Some(existing) => existing.and(expr),
+        None => expr,
+    })
+}
+
+fn push_where<T>(sql: &mut String, params: &mut Vec<Value>, filter: Option<Expr<T>>) {
+    if let Some(filter) = filter {
+        sql.push_str(" WHERE ");
+        sql.push_str(&filter.sql);
+        params.extend(filter.params);
+    }
+}
+
+/// Append `name` as a double-quoted SQL identifier.
+fn push_ident(out: &mut String, name: &str) {
+    out.push('"');
+    for c in name.chars() {
+        if c == '"' {
+            out.push('"');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+fn push_ident_list(out: &mut String, names: &[&str]) {
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        push_ident(out, name);
+    }
+}
+
+fn push_placeholders(out: &mut String, count: usize) {
+    for i in 0..count {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push('?');
+    }
+}
+
+/// Write a [`table!`](crate::table) declaration for every table in the schema
+/// of `conn`, to be saved as a source file of the application.
+///
+/// Each struct is named after its table in `UpperCamelCase`. Column types follow
+/// the affinity of the declared type: `i64`, `String`, `Vec<u8>` or `f64`, and
+/// [`Value`] for NUMERIC columns and columns without a type. Columns that can hold
+/// NULL are `Option`s. Columns whose name isn't a Rust identifier are renamed.
+pub async fn generate_tables(conn: &Connection) -> Result<String> {
+    let mut tables = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT name FROM sqlite_schema WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            (),
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        if let Value::Text(name) = row.get_value(0)? {
+            tables.push(name);
+        }
+    }
+
+    let mut out = String::new();
+    for table in tables {
+        let mut sql = String::from("PRAGMA table_info(");
+        push_ident(&mut sql, &table);
+        sql.push(')');
+        // name, declared type, NOT NULL, position in the primary key
+        let mut columns = Vec::new();
+        let mut rows = conn.query(sql, ()).await?;
+        while let Some(row) = rows.next().await? {
+            let Value::Text(name) = row.get_value(1)? else {
+                continue;
+            };
+            let declared_type = match row.get_value(2)? {
+                Value::Text(declared_type) => declared_type,
+                _ => String::new(),
+            };
+            let not_null = row.get_value(3)? == Value::Integer(1);
+            let pk = row.get_value(5)? != Value::Integer(0);
+            columns.push((name, declared_type, not_null, pk));
+        }
+        let pk_columns = columns.iter().filter(|(.., pk)| *pk).count();
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        writeln!(out, "turso::table! {{").unwrap();
+        writeln!(
+            out,
+            "    pub struct {} as {table:?} {{",
+            struct_name(&table)
+        )
+        .unwrap();
+        for (name, declared_type, not_null, pk) in &columns {
+            // an INTEGER PRIMARY KEY is the rowid, which is never NULL
+            let rowid = *pk && pk_columns == 1 && declared_type.eq_ignore_ascii_case("INTEGER");
+            let ident = column_ident(name);
+            write!(out, "        {ident}").unwrap();
+            if ident != *name {
+                write!(out, " as {name:?}").unwrap();
+            }
+            writeln!(
+                out,
+                ": {},",
+                column_type(declared_type, !not_null && !rowid)
+            )
+            .unwrap();
+        }
+        writeln!(out, "    }}\n}}").unwrap();
+    }
+    Ok(out)
+}
+
+/// `table` in `UpperCamelCase`, as the name of its struct.
+fn struct_name(table: &str) -> String {
+    let mut name = String::new();
+    for word in table
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let mut chars = word.chars();
+        name.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        name.push_str(chars.as_str());
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, 'T');
+    }
+    name
+}
+
+/// `column` as a Rust identifier, renamed when it isn't one.
+fn column_ident(column: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+        "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+        "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv",
+        "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "try",
+        "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    let mut ident: String = column
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        ident.insert(0, '_');
+    }
+    if ident == "_" || KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+/// The Rust type of a column declared as `declared_type`, following SQLite's
+/// affinity rules.
+fn column_type(declared_type: &str, nullable: bool) -> String {
+    let declared_type = declared_type.to_ascii_uppercase();
+    let has = |names: &[&str]| names.iter().any(|name| declared_type.contains(name));
+    let ty = if has(&["INT"]) {
+        "i64"
+    } else if has(&["CHAR", "CLOB", "TEXT"]) {
+        "String"
+    } else if has(&["BLOB"]) {
+        "Vec<u8>"
+    } else if has(&["REAL", "FLOA", "DOUB"]) {
+        "f64"
+    } else {
+        // any value at all, so it's already nullable
+        return "turso::Value".to_string();
+    };
+    if nullable {
+        format!("Option<{ty}>")
+    } else {
+        ty.to_string()
+    }
+}
+
+/// Declare a table for the [`query_builder`](crate::query_builder).
+///
+/// Generates a unit struct implementing [`Table`](crate::query_builder::Table)
+/// with one associated [`Column`](crate::query_builder::Column) constant per
+/// declared column. A column whose name isn't a Rust identifier is declared
+/// as `ident as "name"`. [`generate_tables`](crate::query_builder::generate_tables)
+/// writes these declarations from an existing schema.
+///
+/// ```rust
+/// turso::table! {
+///     pub struct Users as "users" {
+///         id: i64,
+///         email: String,
+///     }
+/// }
+///
+/// let query = turso::query_builder::select(Users::email)
+///     .filter(Users::id.eq(1))
+///     .build();
+/// assert_eq!(query.sql(), r#"SELECT "email" FROM "users" WHERE "id" = ?"#);
+/// ```
+#[macro_export]
+macro_rules! table {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident as $table:literal {
+            $($column:ident $(as $column_name:literal)? : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $vis struct $name;
+
+        impl $crate::query_builder::Table for $name {
+            const NAME: &'static str = $table;
+            const COLUMNS: &'static [&'static str] =
+                &[$($crate::__table_column_name!($column $(, $column_name)?)),*];
+        }
+
+        #[allow(non_upper_case_globals)]
+        impl $name {
+            $(
+                pub const $column: $crate::query_builder::Column<$name, $ty> =
+                    $crate::query_builder::Column::new(
+                        $crate::__table_column_name!($column $(, $column_name)?),
+                    );
+            )*
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __table_column_name {
+    ($column:ident) => {
+        stringify!($column)
+    };
+    ($column:ident, $column_name:literal) => {
+        $column_name
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::table! {
+        struct Users as "users" {
+            id: i64,
+            email: String,
+            age: Option<i64>,
+        }
+    }
+
+    crate::table! {
+        struct Quoted as "odd\"name" {
+            value: i64,
+            type_ as "type": String,
+        }
+    }
+
+    #[test]
+    fn test_table_metadata() {
+        assert_eq!(Users::NAME, "users");
+        assert_eq!(Users::COLUMNS, &["id", "email", "age"]);
+        assert_eq!(Users::email.name(), "email");
+    }
+
+    #[test]
+    fn test_select_renders_filters_and_params_in_order() {
+        let query = select((Users::id, Users::email))
+            .filter(Users::age.ge(18i64))
+            .filter(Users::email.like("%@example.org").or(Users::id.eq(7)))
+            .order_by(Users::age.desc())
+            .order_by(Users::id.asc())
+            .limit(10)
+            .offset(5)
+            .build();
+        assert_eq!(
+            query.sql(),
+            r#"SELECT "id", "email" FROM "users" WHERE ("age" >= ?) AND (("email" LIKE ?) OR ("id" = ?)) ORDER BY "age" DESC, "id" LIMIT ? OFFSET ?"#
+        );
+        assert_eq!(
+            query.params(),
+            &[
+                Value::Integer(18),
+                Value::Text("%@example.org".to_string()),
+                Value::Integer(7),
+                Value::Integer(10),
+                Value::Integer(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_all_offset_without_limit() {
+        let query = select_all::<Users>().distinct().offset(3).build();
+        assert_eq!(
+            query.sql(),
+            r#"SELECT DISTINCT * FROM "users" LIMIT ? OFFSET ?"#
+        );
+        assert_eq!(query.params(), &[Value::Integer(-1), Value::Integer(3)]);
+    }
+
+    #[test]
+    fn test_in_list_and_null_checks() {
+        let query = select(Users::id)
+            .filter(
+                Users::id
+                    .in_list([1, 2, 3])
+                    .and(Users::age.is_not_null().not()),
+            )
+            .build();
+        assert_eq!(
+            query.sql(),
+            r#"SELECT "id" FROM "users" WHERE ("id" IN (?, ?, ?)) AND (NOT ("age" IS NOT NULL))"#
+        );
+        assert_eq!(query.params().len(), 3);
+
+        let empty = select(Users::id)
+            .filter(Users::id.in_list(Vec::<i64>::new()))
+            .build();
+        assert_eq!(empty.sql(), r#"SELECT "id" FROM "users" WHERE 0"#);
+        assert!(empty.params().is_empty());
+    }
+
+    #[test]
+    fn test_insert_update_delete() {
+        let insert = insert_into::<Users>()
+            .value(Users::email, "a@b.c")
+            .value(Users::age, None)
+            .build();
+        assert_eq!(
+            insert.sql(),
+            r#"INSERT INTO "users" ("email", "age") VALUES (?, ?)"#
+        );
+        assert_eq!(
+            insert.params(),
+            &[Value::Text("a@b.c".to_string()), Value::Null]
+        );
+
+        let default = insert_into::<Users>().build();
+        assert_eq!(default.sql(), r#"INSERT INTO "users" DEFAULT VALUES"#);
+
+        let update = update::<Users>()
+            .set(Users::age, 41i64)
+            .filter(Users::id.eq(1))
+            .build()
+            .unwrap();
+        assert_eq!(
+            update.sql(),
+            r#"UPDATE "users" SET "age" = ? WHERE "id" = ?"#
+        );
+        assert_eq!(update.params(), &[Value::Integer(41), Value::Integer(1)]);
+        assert!(matches!(
+            update::<Users>().filter(Users::id.eq(1)).build(),
+            Err(Error::Misuse(_))
+        ));
+
+        let delete = delete_from::<Users>().filter(Users::age.lt(3i64)).build();
+        assert_eq!(delete.sql(), r#"DELETE FROM "users" WHERE "age" < ?"#);
+    }
+
+    #[test]
+    fn test_comparisons_with_null_check_for_null() {
+        let query = select(Users::id)
+            .filter(Users::age.eq(None).or(Users::age.ne(None)))
+            .filter(Users::age.eq(Some(3i64)))
+            .build();
+        assert_eq!(
+            query.sql(),
+            r#"SELECT "id" FROM "users" WHERE (("age" IS NULL) OR ("age" IS NOT NULL)) AND ("age" = ?)"#
+        );
+        assert_eq!(query.params(), &[Value::Integer(3)]);
+    }
+
+    #[test]
+    fn test_identifiers_are_quoted() {
+        let query = select((Quoted::value, Quoted::type_)).build();
+        assert_eq!(query.sql(), r#"SELECT "value", "type" FROM "odd""name""#);
+        assert_eq!(Quoted::COLUMNS, &["value", "type"]);
+    }
+
+    #[test]
+    fn test_generated_names_and_types() {
+        assert_eq!(struct_name("user_accounts"), "UserAccounts");
+        assert_eq!(struct_name("2fa codes"), "T2faCodes");
+        assert_eq!(column_ident("email"), "email");
+        assert_eq!(column_ident("type"), "type_");
+        assert_eq!(column_ident("first name"), "first_name");
+        assert_eq!(column_ident("1st"), "_1st");
+        assert_eq!(column_type("BIGINT", false), "i64");
+        assert_eq!(column_type("varchar(20)", true), "Option<String>");
+        assert_eq!(column_type("DOUBLE PRECISION", true), "Option<f64>");
+        assert_eq!(column_type("blob", false), "Vec<u8>");
+        assert_eq!(column_type("", true), "turso::Value");
+        assert_eq!(column_type("NUMERIC", false), "turso::Value");
+    }
+}
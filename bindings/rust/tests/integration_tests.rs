@@ -1969,3 +1969,110 @@ async fn test_typed_numeric_row_conversions() {
     assert_eq!(row.get::<f64>(4).unwrap(), -1.0);
     assert_eq!(row.get::<f64>(9).unwrap(), 9_007_199_254_740_993_i64 as f64);
 }
+
+#[tokio::test]
+async fn test_query_builder_round_trip() {
+    use turso::query_builder::{delete_from, insert_into, select, update};
+
+    turso::table! {
+        struct Users as "users" {
+            id: i64,
+            email: String,
+            age: Option<i64>,
+        }
+    }
+
+    let db = Builder::new_local(":memory:").build().await.unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT, age INTEGER)",
+        (),
+    )
+    .await
+    .unwrap();
+
+    for (email, age) in [
+        ("a@x.org", Some(17)),
+        ("b@x.org", Some(30)),
+        ("c@y.org", None),
+    ] {
+        let changed = insert_into::<Users>()
+            .value(Users::email, email)
+            .value(Users::age, age)
+            .build()
+            .execute(&conn)
+            .await
+            .unwrap();
+        assert_eq!(changed, 1);
+    }
+
+    let changed = update::<Users>()
+        .set(Users::age, 31i64)
+        .filter(Users::email.eq("b@x.org"))
+        .build()
+        .unwrap()
+        .execute(&conn)
+        .await
+        .unwrap();
+    assert_eq!(changed, 1);
+
+    let mut rows = select((Users::email, Users::age))
+        .filter(Users::age.gt(18i64).or(Users::age.is_null()))
+        .order_by(Users::id.desc())
+        .build()
+        .query(&conn)
+        .await
+        .unwrap();
+    let row = rows.next().await.unwrap().unwrap();
+    assert_eq!(row.get_value(0).unwrap(), Value::Text("c@y.org".into()));
+    assert_eq!(row.get_value(1).unwrap(), Value::Null);
+    let row = rows.next().await.unwrap().unwrap();
+    assert_eq!(row.get_value(0).unwrap(), Value::Text("b@x.org".into()));
+    assert_eq!(row.get_value(1).unwrap(), Value::Integer(31));
+    assert!(rows.next().await.unwrap().is_none());
+
+    let changed = delete_from::<Users>()
+        .filter(Users::email.like("%@x.org"))
+        .build()
+        .execute(&conn)
+        .await
+        .unwrap();
+    assert_eq!(changed, 2);
+}
+
+#[tokio::test]
+async fn test_query_builder_generates_tables_from_the_schema() {
+    let db = Builder::new_local(":memory:").build().await.unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute(
+        "CREATE TABLE user_accounts (id INTEGER PRIMARY KEY, email TEXT NOT NULL, age INT, \"type\" BLOB, score REAL, extra)",
+        (),
+    )
+    .await
+    .unwrap();
+    conn.execute("CREATE TABLE audit (at TEXT, PRIMARY KEY (at))", ())
+        .await
+        .unwrap();
+
+    let generated = turso::query_builder::generate_tables(&conn).await.unwrap();
+    assert_eq!(
+        generated,
+        r#"turso::table! {
+    pub struct Audit as "audit" {
+        at: Option<String>,
+    }
+}
+
+turso::table! {
+    pub struct UserAccounts as "user_accounts" {
+        id: i64,
+        email: String,
+        age: Option<i64>,
+        type_ as "type": Option<Vec<u8>>,
+        score: Option<f64>,
+        extra: turso::Value,
+    }
+}
+"#
+    );
+}
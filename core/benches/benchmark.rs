@@ -508,6 +508,79 @@ fn bench_execute_select_rows(criterion: &mut Criterion) {
     group.finish();
 }
 
+#[turso_macros::codspeed_criterion_benchmark]
+fn bench_execute_select_wide_row_columns(criterion: &mut Criterion) {
+    // The rusqlite benchmark crashes on Mac M1 when using the flamegraph features
+    let enable_rusqlite = std::env::var("DISABLE_RUSQLITE_BENCHMARK").is_err();
+
+    // Columns late in a wide row are where walking the record header for
+    // every Column opcode shows up.
+    const COLUMNS: usize = 50;
+    let columns = (0..COLUMNS)
+        .map(|i| format!("c{i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let create = format!("CREATE TABLE wide({columns})");
+    let values = (0..COLUMNS)
+        .map(|i| format!("value * {i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert = format!("INSERT INTO wide SELECT {values} FROM generate_series(1, 1000)");
+    let query = format!("SELECT c{}, c0, c{} FROM wide", COLUMNS - 1, COLUMNS / 2);
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("bench.db");
+    #[allow(clippy::arc_with_non_send_sync)]
+    let io = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file(io, db_path.to_str().unwrap(), Arc::new(SqliteDialect)).unwrap();
+    let limbo_conn = db.connect().unwrap();
+    limbo_conn.execute(&create).unwrap();
+    limbo_conn.execute(&insert).unwrap();
+
+    let mut group = criterion.benchmark_group("Execute `SELECT` of columns of a wide row");
+
+    group.bench_function("limbo_execute_select_wide_row_columns", |b| {
+        let mut stmt = limbo_conn.prepare(&query).unwrap();
+        b.iter(|| {
+            loop {
+                match stmt.step().unwrap() {
+                    turso_core::StepResult::Row => {
+                        black_box(stmt.row());
+                    }
+                    turso_core::StepResult::IO | turso_core::StepResult::Yield => {
+                        db.io.step().unwrap();
+                    }
+                    turso_core::StepResult::Done => {
+                        break;
+                    }
+                    turso_core::StepResult::Interrupt | turso_core::StepResult::Busy => {
+                        unreachable!();
+                    }
+                }
+            }
+            stmt.reset().unwrap();
+        });
+    });
+
+    if enable_rusqlite {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let sqlite_conn = setup_rusqlite(&temp_dir, &create);
+        sqlite_conn.execute(&insert, []).unwrap();
+
+        group.bench_function("sqlite_execute_select_wide_row_columns", |b| {
+            let mut stmt = sqlite_conn.prepare(&query).unwrap();
+            b.iter(|| {
+                let mut rows = stmt.raw_query();
+                while let Some(row) = rows.next().unwrap() {
+                    black_box(row);
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
 #[turso_macros::codspeed_criterion_benchmark]
 fn bench_execute_select_1(criterion: &mut Criterion) {
     // https://github.com/tursodatabase/turso/issues/174
@@ -1256,14 +1329,14 @@ fn bench_insert_randomblob(criterion: &mut Criterion) {
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = bench_open, bench_alter, bench_prepare_query, bench_execute_select_1, bench_execute_select_rows, bench_execute_select_count, bench_execute_group_by, bench_insert_rows, bench_concurrent_writes, bench_insert_randomblob
+    targets = bench_open, bench_alter, bench_prepare_query, bench_execute_select_1, bench_execute_select_rows, bench_execute_select_wide_row_columns, bench_execute_select_count, bench_execute_group_by, bench_insert_rows, bench_concurrent_writes, bench_insert_randomblob
 }
 
 #[cfg(feature = "codspeed")]
 criterion_group! {
     name = benches;
     config = Criterion::default();
-    targets = bench_open, bench_alter, bench_prepare_query, bench_execute_select_1, bench_execute_select_rows, bench_execute_select_count, bench_execute_group_by, bench_insert_rows, bench_concurrent_writes, bench_insert_randomblob
+    targets = bench_open, bench_alter, bench_prepare_query, bench_execute_select_1, bench_execute_select_rows, bench_execute_select_wide_row_columns, bench_execute_select_count, bench_execute_group_by, bench_insert_rows, bench_concurrent_writes, bench_insert_randomblob
 }
 
 criterion_main!(benches);
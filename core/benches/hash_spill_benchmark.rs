@@ -9,6 +9,7 @@ use codspeed_criterion_compat::{
 };
 
 use std::sync::Arc;
+use turso_core::io::TempDirectory;
 use turso_core::types::Value;
 use turso_core::vdbe::hash_table::{HashTable, HashTableConfig};
 use turso_core::vdbe::CollationSeq;
@@ -27,6 +28,7 @@ fn create_hash_table(mem_budget: usize) -> HashTable {
         num_keys: 1,
         collations: turso_core::alloc::vec![CollationSeq::Binary],
         temp_store: turso_core::TempStore::Default,
+        temp_dir: TempDirectory::System,
         track_matched: false,
         partition_count: None,
     };
@@ -249,6 +251,7 @@ fn bench_text_key_hashing(c: &mut Criterion) {
                         num_keys: 1,
                         collations: turso_core::alloc::vec![CollationSeq::Binary],
                         temp_store: turso_core::TempStore::Default,
+                        temp_dir: TempDirectory::System,
                         track_matched: false,
                         partition_count: None,
                     };
@@ -273,6 +276,7 @@ fn bench_text_key_hashing(c: &mut Criterion) {
                         num_keys: 1,
                         collations: turso_core::alloc::vec![CollationSeq::NoCase],
                         temp_store: turso_core::TempStore::Default,
+                        temp_dir: TempDirectory::System,
                         track_matched: false,
                         partition_count: None,
                     };
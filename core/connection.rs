@@ -3,7 +3,7 @@ use crate::error::io_error;
 #[cfg(any(test, injected_yields))]
 use crate::mvcc::yield_points::{FailureInjector, YieldInjector};
 use crate::statement::StatementOrigin;
-use crate::storage::{journal_mode, pager::SavepointResult};
+use crate::storage::{journal_mode, page_cache::PageCacheStats, pager::SavepointResult};
 use crate::sync::{
     atomic::{
         AtomicBool, AtomicI32, AtomicI64, AtomicIsize, AtomicU16, AtomicU64, AtomicU8, Ordering,
@@ -13,7 +13,7 @@ use crate::sync::{
 #[cfg(all(feature = "fs", feature = "conn_raw_api"))]
 use crate::types::{WalFrameInfo, WalState};
 #[cfg(feature = "fs")]
-use crate::util::{OpenMode, OpenOptions};
+use crate::util::{CacheMode, OpenMode, OpenOptions};
 #[cfg(all(feature = "fs", feature = "conn_raw_api"))]
 use crate::Page;
 use crate::{
@@ -28,7 +28,7 @@ use crate::{
     Completion, ConnectionMetrics, Database, DatabaseCatalog, DatabaseOpts, Duration,
     EncryptionKey, EncryptionOpts, IOResult, IndexMethod, LimboError, MvStore, OpenFlags, PageSize,
     Pager, Program, QueryMode, QueryRunner, Result, Schema, Statement, SyncMode, TransactionMode,
-    Trigger, Value, VirtualTable, WalAutoActions,
+    Trigger, Value, VirtualTable, WalAutoActions, WorkloadCapture,
 };
 use crate::{is_memory_like, turso_assert};
 use crate::{MAIN_DB_ID, TEMP_DB_ID};
@@ -40,8 +40,6 @@ use std::fmt::Display;
 use std::ops::Deref;
 #[cfg(feature = "simulator")]
 use std::path::Path;
-#[cfg(not(target_family = "wasm"))]
-use tempfile::TempDir;
 use tracing::{instrument, Level};
 use turso_macros::{turso_assert_ne, AtomicEnum};
 
@@ -81,7 +79,7 @@ pub(crate) struct TempDatabase {
     pub(crate) db: Arc<Database>,
     pub(crate) pager: Arc<Pager>,
     #[cfg(not(target_family = "wasm"))]
-    _temp_dir: Option<TempDir>,
+    _temp_path: Option<crate::io::TempPath>,
 }
 
 /// All of the connection-local state needed to manage the `TEMP` schema.
@@ -393,7 +391,11 @@ pub struct Connection {
     /// Attached databases
     pub(super) attached_databases: RwLock<DatabaseCatalog>,
     pub(super) query_only: AtomicBool,
+    /// Set by `BEGIN READONLY`; only meaningful while `auto_commit` is false.
+    pub(crate) read_only_tx: AtomicBool,
     pub(super) vdbe_trace: AtomicBool,
+    /// Debugging aid: poison temporary registers once codegen releases them.
+    pub(super) poison_temp_registers: AtomicBool,
     /// If enabled, the UPDATE/DELETE statements must have a WHERE clause
     pub(super) dml_require_where: AtomicBool,
     /// SQLite DQS misfeature: when ON (default), unresolved double-quoted identifiers
@@ -449,6 +451,10 @@ pub struct Connection {
     pub(super) encryption_cipher_mode: AtomicCipherMode,
     pub(super) sync_mode: AtomicSyncMode,
     pub(super) temp_store: AtomicTempStore,
+    /// Directory set with `PRAGMA temp_store_directory`, see [Connection::temp_directory].
+    pub(super) temp_store_directory: RwLock<Option<String>>,
+    /// Where executed statements are recorded, see [Connection::set_workload_capture].
+    pub(super) workload_capture: RwLock<Option<Arc<WorkloadCapture>>>,
     pub(super) data_sync_retry: AtomicBool,
     /// Busy handler for lock contention
     /// Default is BusyHandler::None (return SQLITE_BUSY immediately)
@@ -458,6 +464,8 @@ pub struct Connection {
     /// Maximum execution time for a single statement on this connection.
     /// `Duration::ZERO` means disabled.
     pub(super) query_timeout_ms: AtomicU64,
+    /// Most bytes a single statement's sorters may hold at once. 0 means unlimited.
+    pub(super) statement_memory_limit: AtomicU64,
     /// True when sqlite3_interrupt()-style cancellation is pending for active root statements.
     pub(super) interrupt_requested: AtomicBool,
     /// Whether this is an internal connection used for MVCC bootstrap
@@ -527,10 +535,10 @@ impl Drop for Connection {
             let pager = self.pager.load();
             if let Some(wal) = &pager.wal {
                 if wal.holds_write_lock() {
-                    wal.end_write_tx();
+                    pager.end_write_tx();
                 }
                 if wal.holds_read_lock() {
-                    wal.end_read_tx();
+                    pager.end_read_tx();
                 }
             }
 
@@ -539,10 +547,10 @@ impl Drop for Connection {
                 for (_, attached_pager) in attached_pagers {
                     if let Some(wal) = &attached_pager.wal {
                         if wal.holds_write_lock() {
-                            wal.end_write_tx();
+                            attached_pager.end_write_tx();
                         }
                         if wal.holds_read_lock() {
-                            wal.end_read_tx();
+                            attached_pager.end_read_tx();
                         }
                     }
                 }
@@ -591,6 +599,7 @@ impl Connection {
             .with_vacuum(self.db.experimental_vacuum_enabled())
             .with_generated_columns(self.db.experimental_generated_columns_enabled())
             .with_without_rowid(self.db.experimental_without_rowid_enabled())
+            .with_page_cache_policy(self.db.page_cache_policy())
     }
 
     fn effective_temp_store(&self) -> crate::TempStore {
@@ -628,26 +637,22 @@ impl Connection {
                 db,
                 pager,
                 #[cfg(not(target_family = "wasm"))]
-                _temp_dir: None,
+                _temp_path: None,
             });
         }
 
         #[cfg(not(target_family = "wasm"))]
         {
-            let temp_dir = self.create_tempdir()?;
-            let temp_path = temp_dir.path().join("tursodb-temp.db");
-            let temp_path_str = temp_path.to_str().ok_or_else(|| {
-                LimboError::InternalError("temp db path is not valid UTF-8".into())
-            })?;
             // Always create a fresh IO for the temp file. Cloning the
             // main db's IO is wrong when the main db uses a mock /
             // simulated backend (e.g. the deterministic simulator
             // with `--io-backend=memory`) that can't access real
-            // filesystem paths produced by `tempfile::tempdir()`.
-            let io = Database::io_for_path(temp_path_str)?;
+            // filesystem paths in the temp directory.
+            let io: Arc<dyn IO> = Arc::new(crate::PlatformIO::new()?);
+            let (_, temp_path) = self.temp_directory().create_file(&io, "tursodb-temp")?;
             let db = Database::open_file_with_flags(
                 io,
-                temp_path_str,
+                temp_path.as_str(),
                 OpenFlags::Create,
                 db_opts,
                 None,
@@ -658,7 +663,7 @@ impl Connection {
             Ok(TempDatabase {
                 db,
                 pager,
-                _temp_dir: Some(temp_dir),
+                _temp_path: Some(temp_path),
             })
         }
 
@@ -696,7 +701,7 @@ impl Connection {
             db,
             pager,
             #[cfg(not(target_family = "wasm"))]
-            _temp_dir: None,
+            _temp_path: None,
         })
     }
 
@@ -1037,6 +1042,23 @@ impl Connection {
         self.prepare_stmt_with_input_and_origin(stmt, input, StatementOrigin::Root)
     }
 
+    /// Suggest indexes for a workload of SQL statements.
+    ///
+    /// Each SELECT, UPDATE and DELETE in `workload` is planned against the current schema
+    /// and then once per candidate index, with the candidate added as a hypothetical index
+    /// that is never created. Candidates come from the columns the statements constrain in
+    /// WHERE, join and ORDER BY clauses. Those that lower the estimated cost of the whole
+    /// workload are returned, largest improvement first. Other statements are ignored.
+    pub fn suggest_indexes(
+        self: &Arc<Connection>,
+        workload: &[impl AsRef<str>],
+    ) -> Result<Vec<crate::IndexSuggestion>> {
+        if self.is_closed() {
+            return Err(LimboError::InternalError("Connection closed".to_string()));
+        }
+        translate::advisor::suggest_indexes(self, workload)
+    }
+
     #[turso_macros::trace_stack]
     fn prepare_stmt_with_input_and_origin(
         self: &Arc<Connection>,
@@ -1148,6 +1170,11 @@ impl Connection {
         if db_schema_version == on_disk_schema_version {
             return Ok(());
         }
+        if let Some(schema) = crate::schema_cache::lookup(&self.db, on_disk_schema_version) {
+            self.db.update_schema_if_newer(schema);
+            self.maybe_update_schema();
+            return Ok(());
+        }
 
         // start read transaction manually, because we will read schema cookie once again and
         // we must be sure that it will consistent with schema content
@@ -1176,6 +1203,7 @@ impl Connection {
         reparse_result?;
 
         let schema = self.schema.read().clone();
+        crate::schema_cache::publish(&self.db, &schema);
         self.db.update_schema_if_newer(schema);
         Ok(())
     }
@@ -1741,7 +1769,15 @@ impl Connection {
         use crate::util::MEMORY_PATH;
         let opts = OpenOptions::parse(uri)?;
         let flags = opts.get_flags()?;
-        if opts.path == MEMORY_PATH || matches!(opts.mode, OpenMode::Memory) {
+        let memdb = opts.vfs.as_deref() == Some("memdb");
+        if opts.path == MEMORY_PATH || matches!(opts.mode, OpenMode::Memory) || memdb {
+            // As in SQLite, `cache=shared` (or a memdb name starting with
+            // `/`) makes every connection in the process that opens the same
+            // name see the same in-memory database.
+            if opts.cache == CacheMode::Shared || (memdb && opts.path.starts_with('/')) {
+                let db = Database::open_shared_memory(&opts.path, dialect)?;
+                return Ok((db.io.clone(), db.connect()?));
+            }
             let io = Arc::new(MemoryIO::new());
             let db = Database::open_file_with_flags(
                 io.clone(),
@@ -2167,6 +2203,7 @@ impl Connection {
         self.set_tx_state(TransactionState::Write {
             schema_did_change: false,
         });
+        self.read_only_tx.store(false, Ordering::SeqCst);
         self.auto_commit.store(false, Ordering::SeqCst);
 
         Ok(())
@@ -2181,11 +2218,11 @@ impl Connection {
         {
             let pager = self.pager.load();
 
-            let Some(wal) = pager.wal.as_ref() else {
+            if pager.wal.is_none() {
                 return Err(LimboError::InternalError(
                     "wal_insert_end called without a wal".to_string(),
                 ));
-            };
+            }
 
             let commit_err = if force_commit {
                 pager
@@ -2206,8 +2243,8 @@ impl Connection {
 
             self.auto_commit.store(true, Ordering::SeqCst);
             self.set_tx_state(TransactionState::None);
-            wal.end_write_tx();
-            wal.end_read_tx();
+            pager.end_write_tx();
+            pager.end_read_tx();
 
             if !force_commit {
                 // remove all non-commited changes in case if WAL session left some suffix without commit frame
@@ -2237,6 +2274,60 @@ impl Connection {
         pager.io.block(|| pager.cacheflush())
     }
 
+    /// Return the image of the main database as it would look checkpointed
+    /// into a single file, like `sqlite3_serialize`. Frames still in the WAL
+    /// are included and, inside a transaction, so are its uncommitted changes.
+    /// A database that was never written serializes to an empty image.
+    ///
+    /// The image can be opened again with [`Database::deserialize`].
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        if self.is_closed() {
+            return Err(LimboError::InternalError("Connection closed".to_string()));
+        }
+        // MVCC keeps committed rows in the log until they are checkpointed,
+        // so the pages alone are not the whole database.
+        if self.mv_store().is_some() {
+            return Err(LimboError::InvalidArgument(
+                "serialize is not supported on MVCC databases".to_string(),
+            ));
+        }
+        let pager = self.pager.load();
+        if !pager.db_initialized() {
+            return Ok(Vec::new());
+        }
+        let own_read_tx = matches!(self.get_tx_state(), TransactionState::None);
+        if own_read_tx {
+            pager.begin_read_tx()?;
+        }
+        let image = Self::read_database_image(&pager);
+        if own_read_tx {
+            pager.end_read_tx();
+        }
+        image
+    }
+
+    fn read_database_image(pager: &Pager) -> Result<Vec<u8>> {
+        let (page_size, database_size) = pager.io.block(|| {
+            pager
+                .with_header(|header| (header.page_size.get() as usize, header.database_size.get()))
+        })?;
+        let pending_page = pager.pending_byte_page_id();
+        let mut image = Vec::with_capacity(page_size * database_size as usize);
+        for page_idx in 1..=database_size {
+            // The pending byte page is never written, it reads as zeros.
+            if pending_page == Some(page_idx) {
+                image.resize(image.len() + page_size, 0);
+                continue;
+            }
+            let (page_ref, completion) = pager.io.block(|| pager.read_page(page_idx as i64))?;
+            if let Some(c) = completion {
+                pager.io.wait_for_completion(c)?;
+            }
+            image.extend_from_slice(page_ref.get_contents().as_ptr());
+        }
+        Ok(image)
+    }
+
     pub fn checkpoint(self: &Arc<Self>, mode: CheckpointMode) -> Result<CheckpointResult> {
         use crate::mvcc::database::CheckpointStateMachine;
         use crate::state_machine::{StateTransition, TransitionResult};
@@ -2581,6 +2672,11 @@ impl Connection {
         self.bump_prepare_context_generation();
     }
 
+    /// Hits, misses and evictions of this connection's page cache.
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        self.get_pager().page_cache_stats()
+    }
+
     pub fn get_capture_data_changes_info(
         &self,
     ) -> crate::sync::RwLockReadGuard<'_, Option<CaptureDataChangesInfo>> {
@@ -2671,6 +2767,14 @@ impl Connection {
         Database::open_with_vfs(&self.db, path, vfs, dialect)
     }
 
+    /// Reads, writes and other operations performed so far through the
+    /// extension VFS called `vfs`, across every database opened with it.
+    /// `None` if no such VFS is registered.
+    #[cfg(feature = "fs")]
+    pub fn vfs_stats(&self, vfs: &str) -> Option<crate::VfsStats> {
+        crate::ext::vfs_stats(vfs)
+    }
+
     pub fn list_vfs(&self) -> Vec<String> {
         #[allow(unused_mut)]
         let mut all_vfs = vec![String::from("memory")];
@@ -3365,7 +3469,8 @@ impl Connection {
                         .with_index_method(self.db.experimental_index_method_enabled())
                         .with_vacuum(self.db.experimental_vacuum_enabled())
                         .with_generated_columns(self.db.experimental_generated_columns_enabled())
-                        .with_without_rowid(self.db.experimental_without_rowid_enabled());
+                        .with_without_rowid(self.db.experimental_without_rowid_enabled())
+                        .with_page_cache_policy(self.db.page_cache_policy());
                     let is_memory_db = is_memory_like(path);
                     let io: Arc<dyn IO> = if is_memory_db {
                         Arc::new(MemoryIO::new())
@@ -3721,6 +3826,56 @@ impl Connection {
         }
     }
 
+    /// Branch database `source` (`main` or an attached schema name) into a new
+    /// database file at `branch_path` and attach it as `alias`, the equivalent
+    /// of `ATTACH branch_of(source) AS alias`. Both databases then evolve
+    /// independently. Requires an IO backend that supports
+    /// [IO::branch_file], such as [crate::ContentAddressedIO], and must be
+    /// called outside of an explicit transaction.
+    pub fn attach_branch(
+        self: &Arc<Connection>,
+        source: &str,
+        branch_path: &str,
+        alias: &str,
+    ) -> Result<()> {
+        if !self.get_auto_commit() {
+            return Err(LimboError::InvalidArgument(
+                "cannot branch a database inside a transaction".to_string(),
+            ));
+        }
+        let db = if source.eq_ignore_ascii_case("main") {
+            self.db.clone()
+        } else {
+            let attached = self.attached_databases.read();
+            attached
+                .name_to_index
+                .get(source)
+                .and_then(|index| attached.index_to_data.get(index))
+                .map(|(db, _pager)| db.clone())
+                .ok_or_else(|| LimboError::InvalidArgument(format!("no such database: {source}")))?
+        };
+        if db.is_in_memory_db() {
+            return Err(LimboError::InvalidArgument(format!(
+                "cannot branch in-memory database {source}"
+            )));
+        }
+        db.io.branch_file(&db.path, branch_path)?;
+        // A missing WAL just means every change has been checkpointed.
+        let branch_wal = format!("{branch_path}-wal");
+        match db.io.branch_file(&db.wal_path, &branch_wal) {
+            Err(LimboError::CompletionError(crate::error::CompletionError::IOError(
+                std::io::ErrorKind::NotFound,
+                _,
+            ))) => db.io.remove_file(&branch_wal)?,
+            result => result?,
+        }
+        self.execute(format!(
+            "ATTACH '{}' AS {}",
+            branch_path.replace('\'', "''"),
+            crate::util::quote_identifier(alias)
+        ))
+    }
+
     /// List all databases (main + attached) with their sequence numbers, names, and file paths
     /// Returns a vector of tuples: (seq_number, name, file_path)
     pub fn list_all_databases(&self) -> Vec<(usize, String, String)> {
@@ -3767,6 +3922,17 @@ impl Connection {
         self.bump_prepare_context_generation();
     }
 
+    /// Whether the current explicit transaction was opened with `BEGIN READONLY`.
+    pub fn is_read_only_tx(&self) -> bool {
+        !self.get_auto_commit() && self.read_only_tx.load(Ordering::SeqCst)
+    }
+
+    /// Whether writes are rejected on this connection, either by
+    /// `PRAGMA query_only` or by a `BEGIN READONLY` transaction.
+    pub fn is_write_protected(&self) -> bool {
+        self.is_query_only() || self.is_read_only_tx()
+    }
+
     pub fn set_vdbe_trace(&self, value: bool) {
         self.vdbe_trace.store(value, Ordering::SeqCst);
     }
@@ -3775,6 +3941,17 @@ impl Connection {
         self.vdbe_trace.load(Ordering::SeqCst)
     }
 
+    /// Make statements prepared from now on poison the temporary registers that
+    /// codegen releases, so that reading one before it is written again panics
+    /// instead of silently seeing a stale or reused value. Meant for tests.
+    pub fn set_poison_temp_registers(&self, value: bool) {
+        self.poison_temp_registers.store(value, Ordering::SeqCst);
+    }
+
+    pub fn get_poison_temp_registers(&self) -> bool {
+        self.poison_temp_registers.load(Ordering::SeqCst)
+    }
+
     pub fn get_dml_require_where(&self) -> bool {
         self.dml_require_where.load(Ordering::SeqCst)
     }
@@ -3832,6 +4009,66 @@ impl Connection {
         self.bump_prepare_context_generation();
     }
 
+    pub fn get_temp_store_directory(&self) -> Option<String> {
+        self.temp_store_directory.read().clone()
+    }
+
+    /// Set the directory this connection creates temp files in, or reset it
+    /// to the default with `None`. The directory must already exist and be
+    /// writable.
+    pub fn set_temp_store_directory(&self, dir: Option<String>) -> Result<()> {
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(dir) = &dir {
+            let path = std::path::Path::new(dir);
+            // Creating (and dropping) a temp file in it is the only portable
+            // way to tell that temp files can be created there later.
+            if !path.is_dir()
+                || crate::io::TempDirectory::Explicit(path.into())
+                    .create_file(&self.db.io, crate::io::TEMP_FILE_NAME)
+                    .is_err()
+            {
+                return Err(LimboError::InvalidArgument(format!(
+                    "not a writable directory: {dir}"
+                )));
+            }
+        }
+        *self.temp_store_directory.write() = dir;
+        Ok(())
+    }
+
+    /// Record every statement this connection executes, with its bound
+    /// parameters, into `capture`. `None` stops capturing. Statements run
+    /// internally by the engine (schema refreshes, triggers) are not recorded.
+    pub fn set_workload_capture(&self, capture: Option<Arc<WorkloadCapture>>) {
+        *self.workload_capture.write() = capture;
+    }
+
+    pub(crate) fn workload_capture(&self) -> Option<Arc<WorkloadCapture>> {
+        self.workload_capture.read().clone()
+    }
+
+    /// Where this connection creates temp files, in priority order:
+    /// `PRAGMA temp_store_directory`, `TURSO_TMPDIR`, `SQLITE_TMPDIR`, the
+    /// directory of the main database file, and finally the OS default for
+    /// in-memory databases.
+    pub(crate) fn temp_directory(&self) -> crate::io::TempDirectory {
+        use crate::io::TempDirectory;
+        if let Some(dir) = self.temp_store_directory.read().as_ref() {
+            return TempDirectory::Explicit(dir.into());
+        }
+        if let Some(dir) = TempDirectory::from_env() {
+            return dir;
+        }
+        if is_memory_like(&self.db.path) {
+            return TempDirectory::System;
+        }
+        match std::path::Path::new(&self.db.path).parent() {
+            Some(parent) if parent.as_os_str().is_empty() => TempDirectory::DatabaseDir(".".into()),
+            Some(parent) => TempDirectory::DatabaseDir(parent.to_path_buf()),
+            None => TempDirectory::System,
+        }
+    }
+
     /// Find a sequence by name, supporting optional schema qualification.
     ///
     /// - `"my_seq"` → searches main database only
@@ -4308,24 +4545,6 @@ impl Connection {
         &self.db.path
     }
 
-    /// Create a `TempDir` honoring `TURSO_TMPDIR` and `SQLITE_TMPDIR`,
-    /// falling back to the OS default (`env::temp_dir()`).
-    ///
-    /// `&self` is reserved for a future per-connection
-    /// `temp_store_directory` setting (e.g. `PRAGMA temp_store_directory`)
-    /// so call sites don't need to change when that lands.
-    #[cfg(not(target_family = "wasm"))]
-    pub(crate) fn create_tempdir(&self) -> Result<TempDir> {
-        let res = if let Some(d) = std::env::var_os("TURSO_TMPDIR") {
-            tempfile::tempdir_in(d)
-        } else if let Some(d) = std::env::var_os("SQLITE_TMPDIR") {
-            tempfile::tempdir_in(d)
-        } else {
-            tempfile::tempdir()
-        };
-        res.map_err(|e| io_error(e, "tempdir"))
-    }
-
     pub fn get_data_sync_retry(&self) -> bool {
         self.data_sync_retry
             .load(crate::sync::atomic::Ordering::SeqCst)
@@ -4491,6 +4710,62 @@ impl Connection {
         self.set_encryption_context()
     }
 
+    /// Re-encrypt the main database with `key`, keeping the cipher. The WAL is
+    /// checkpointed, every page is committed to it under the new key, and it
+    /// is checkpointed again, so no other connection may be open and no
+    /// transaction may be active. A crash leaves the database under either the
+    /// old key or the new one, see [Pager::rekey].
+    pub fn rekey(self: &Arc<Self>, key: EncryptionKey) -> Result<()> {
+        let pager = self.pager.load().clone();
+        let Some(cipher_mode) = self.get_encryption_cipher_mode() else {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey a database that is not encrypted".to_string(),
+            ));
+        };
+        if !pager.is_encryption_ctx_set() {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey before the current key is set".to_string(),
+            ));
+        }
+        if self.db.get_mv_store().is_some() {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey a database in MVCC mode".to_string(),
+            ));
+        }
+        if self.experimental_multiprocess_wal_enabled() {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey a database with experimental multiprocess WAL".to_string(),
+            ));
+        }
+        if !self.get_auto_commit() {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey inside a transaction".to_string(),
+            ));
+        }
+        if self.db.n_connections.load(Ordering::SeqCst) > 1 {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey while other connections to the database are open".to_string(),
+            ));
+        }
+        if pager.wal.is_some() {
+            let result = self.checkpoint(CheckpointMode::Truncate {
+                upper_bound_inclusive: None,
+            })?;
+            if !result.everything_backfilled() {
+                return Err(LimboError::Busy);
+            }
+        }
+        pager.rekey(cipher_mode, &key)?;
+        *self.encryption_key.write() = Some(key);
+        self.bump_prepare_context_generation();
+        // Until the pages are backfilled they are read from the WAL, which is
+        // just as durable, so this checkpoint may leave some behind.
+        self.checkpoint(CheckpointMode::Truncate {
+            upper_bound_inclusive: None,
+        })?;
+        Ok(())
+    }
+
     pub fn set_reserved_bytes(&self, reserved_bytes: u8) -> Result<()> {
         let pager = self.pager.load();
         pager.set_reserved_space_bytes(reserved_bytes);
@@ -4581,6 +4856,18 @@ impl Connection {
         Duration::from_millis(self.query_timeout_ms.load(Ordering::SeqCst))
     }
 
+    /// Sets the most bytes a single statement's sorters may hold in memory at
+    /// once. A statement that needs more fails with an out of memory error.
+    /// 0 removes the limit.
+    pub fn set_statement_memory_limit(&self, bytes: u64) {
+        self.statement_memory_limit.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Get the statement memory limit. 0 means unlimited.
+    pub fn get_statement_memory_limit(&self) -> u64 {
+        self.statement_memory_limit.load(Ordering::SeqCst)
+    }
+
     /// Get a reference to the busy handler.
     pub fn get_busy_handler(&self) -> crate::sync::RwLockReadGuard<'_, BusyHandler> {
         self.busy_handler.read()
@@ -4827,6 +5114,7 @@ impl Connection {
         pager: &Arc<Pager>,
         clear_attached_schemas: bool,
     ) {
+        let was_writing = matches!(self.get_tx_state(), TransactionState::Write { .. });
         if let Some(mv_store) = self.mv_store().as_ref() {
             if let Some(tx_id) = self.get_mv_tx_id() {
                 self.auto_commit.store(true, Ordering::SeqCst);
@@ -4845,6 +5133,9 @@ impl Connection {
         self.rollback_attached_wal_txns();
         self.set_tx_state(TransactionState::None);
         self.clear_tx_poison();
+        if was_writing {
+            self.run_rollback_hooks();
+        }
     }
 
     /// Roll back transaction state for helpers that start a manual `BEGIN`
@@ -4973,6 +5264,8 @@ pub struct SymbolTable {
     pub vtabs: HashMap<String, Arc<VirtualTable>>,
     pub vtab_modules: HashMap<String, Arc<crate::ext::VTabImpl>>,
     pub index_methods: HashMap<String, Arc<dyn IndexMethod>>,
+    pub commit_hooks: Vec<Arc<function::ExternalCommitHook>>,
+    pub rollback_hooks: Vec<Arc<function::ExternalRollbackHook>>,
 }
 
 impl std::fmt::Debug for SymbolTable {
@@ -5014,6 +5307,8 @@ impl SymbolTable {
             vtabs: HashMap::default(),
             vtab_modules: HashMap::default(),
             index_methods: HashMap::default(),
+            commit_hooks: Vec::new(),
+            rollback_hooks: Vec::new(),
         }
     }
     pub fn resolve_function(
@@ -5055,6 +5350,16 @@ impl SymbolTable {
         for (name, module) in &other.index_methods {
             self.index_methods.insert(name.clone(), module.clone());
         }
+        for hook in &other.commit_hooks {
+            if !self.commit_hooks.iter().any(|h| Arc::ptr_eq(h, hook)) {
+                self.commit_hooks.push(hook.clone());
+            }
+        }
+        for hook in &other.rollback_hooks {
+            if !self.rollback_hooks.iter().any(|h| Arc::ptr_eq(h, hook)) {
+                self.rollback_hooks.push(hook.clone());
+            }
+        }
     }
 }
 
@@ -5195,6 +5500,28 @@ mod tests {
         assert_eq!(text_value(&aux[2]), "");
     }
 
+    #[test]
+    fn test_attached_and_temp_databases_inherit_page_cache_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_path = temp_dir.path().join("main.db");
+        let opts = DatabaseOpts::new()
+            .with_attach(true)
+            .with_page_cache_policy(crate::PageCachePolicy::Arc);
+        let conn = open_connection_with_opts(&main_path, opts);
+
+        conn.execute("ATTACH ':memory:aux' AS aux").unwrap();
+        conn.execute("CREATE TEMP TABLE t(x)").unwrap();
+        for name in ["main", "aux", "temp"] {
+            let index = conn.get_database_id_by_name(name).unwrap();
+            let pager = conn.get_pager_from_database_index(&index).unwrap();
+            assert_eq!(
+                pager.page_cache_policy(),
+                crate::PageCachePolicy::Arc,
+                "{name}"
+            );
+        }
+    }
+
     #[test]
     fn test_named_memory_parent_can_attach_real_file_database() {
         let temp_dir = TempDir::new().unwrap();
@@ -490,6 +490,8 @@ pub fn resolve_builtin_function(name: &str, arg_count: usize) -> crate::Result<O
         "repeat" => Ok(Some(Func::Scalar(ScalarFunc::Repeat))),
         "lpad" => Ok(Some(Func::Scalar(ScalarFunc::Lpad))),
         "rpad" => Ok(Some(Func::Scalar(ScalarFunc::Rpad))),
+        "row_hash" => Ok(Some(Func::Scalar(ScalarFunc::RowHash))),
+        "hash_table" => Ok(Some(Func::Scalar(ScalarFunc::HashTable))),
         // Built-in type support functions
         "boolean_to_int" => Ok(Some(Func::Scalar(ScalarFunc::BooleanToInt))),
         "int_to_boolean" => Ok(Some(Func::Scalar(ScalarFunc::IntToBoolean))),
@@ -1,4 +1,5 @@
 use thiserror::Error;
+use turso_ext::{ErrorClass, ResultCode};
 
 use crate::storage::page_cache::CacheError;
 
@@ -119,6 +120,56 @@ pub enum LimboError {
     OutOfMemory,
 }
 
+impl LimboError {
+    /// The class of this error in the vocabulary shared with extensions.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::Corrupt(_) | Self::Page1NotAlloc => ErrorClass::Corrupt,
+            Self::NotADB => ErrorClass::NotADb,
+            Self::InternalError(_) | Self::NoSuchTransactionID(_) => ErrorClass::Internal,
+            Self::CacheError(_) => ErrorClass::Internal,
+            Self::DatabaseFull(_) => ErrorClass::Full,
+            Self::CompletionError(_) | Self::CheckpointFailed(_) => ErrorClass::IoErr,
+            Self::Constraint(_) | Self::ForeignKeyConstraint(_) | Self::Raise(..) => {
+                ErrorClass::Constraint
+            }
+            Self::TooBig | Self::InvalidBlobSize(_) => ErrorClass::TooBig,
+            Self::TableLocked => ErrorClass::Locked,
+            Self::ReadOnly => ErrorClass::ReadOnly,
+            // SQLite reports "SQL statements in progress" rejections as SQLITE_BUSY too.
+            Self::Busy | Self::BusySnapshot | Self::StatementsInProgress(_) => ErrorClass::Busy,
+            Self::Interrupt => ErrorClass::Interrupt,
+            Self::SchemaUpdated | Self::SchemaConflict => ErrorClass::Schema,
+            Self::BlobHandleExpired
+            | Self::TxTerminated
+            | Self::WriteWriteConflict
+            | Self::CommitDependencyAborted
+            | Self::Conflict(_) => ErrorClass::Abort,
+            Self::InvalidColumnType => ErrorClass::Mismatch,
+            Self::OutOfMemory => ErrorClass::NoMem,
+            Self::ParseError(_)
+            | Self::LexerError(_)
+            | Self::ConversionError(_)
+            | Self::EnvVarError(_)
+            | Self::TxError(_)
+            | Self::LockingError(_)
+            | Self::ParseIntError(_)
+            | Self::ParseFloatError(_)
+            | Self::InvalidDate(_)
+            | Self::InvalidTime(_)
+            | Self::InvalidModifier(_)
+            | Self::InvalidArgument(_)
+            | Self::InvalidFormatter(_)
+            | Self::RaiseIgnore
+            | Self::ExtensionError(_)
+            | Self::IntegerOverflow
+            | Self::NullValue
+            | Self::PlanningError(_)
+            | Self::UnsupportedEncoding(_) => ErrorClass::Error,
+        }
+    }
+}
+
 impl From<crate::alloc::AllocError> for LimboError {
     fn from(_: crate::alloc::AllocError) -> Self {
         Self::OutOfMemory
@@ -213,6 +264,8 @@ pub enum CompletionError {
     },
     #[error("tursodb not compiled with checksum feature")]
     ChecksumNotEnabled,
+    #[error("Compressed frame of page {page_idx} is corrupt")]
+    CorruptCompressedFrame { page_idx: usize },
 }
 
 /// Convert a `std::io::Error` into a `LimboError` with an operation label.
@@ -276,9 +329,21 @@ macro_rules! bail_constraint_error {
     };
 }
 
-impl From<turso_ext::ResultCode> for LimboError {
-    fn from(err: turso_ext::ResultCode) -> Self {
-        cold_return(LimboError::ExtensionError(err.to_string()))
+/// Maps an error code returned by an extension to the closest [LimboError], so
+/// that e.g. a constraint violation raised by a virtual table is reported like
+/// one raised by the core.
+impl From<ResultCode> for LimboError {
+    fn from(err: ResultCode) -> Self {
+        cold_return(match err {
+            ResultCode::OoM => LimboError::OutOfMemory,
+            ResultCode::Corrupt => LimboError::Corrupt(err.to_string()),
+            ResultCode::ReadOnly => LimboError::ReadOnly,
+            ResultCode::Interrupt => LimboError::Interrupt,
+            ResultCode::Busy => LimboError::Busy,
+            ResultCode::ConstraintViolation => LimboError::Constraint(err.to_string()),
+            ResultCode::Internal => LimboError::InternalError(err.to_string()),
+            _ => LimboError::ExtensionError(err.to_string()),
+        })
     }
 }
 
@@ -299,3 +364,31 @@ pub const SQLITE_CONSTRAINT_UNIQUE: usize = 2067;
 // op_sequence_commit_inner_tx.
 #[allow(dead_code)]
 pub const SQLITE_BUSY: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precise_extension_codes_keep_their_class() {
+        for rc in [
+            ResultCode::OoM,
+            ResultCode::Corrupt,
+            ResultCode::ReadOnly,
+            ResultCode::Interrupt,
+            ResultCode::Busy,
+            ResultCode::ConstraintViolation,
+            ResultCode::Internal,
+        ] {
+            assert_eq!(Some(LimboError::from(rc).class()), rc.error_class());
+        }
+        assert_eq!(
+            LimboError::from(ResultCode::NotFound).class(),
+            ErrorClass::Error
+        );
+        assert_eq!(
+            ErrorClass::from_sqlite_code(SQLITE_CONSTRAINT_UNIQUE as i32),
+            Some(ErrorClass::Constraint)
+        );
+    }
+}
@@ -1,14 +1,16 @@
 use crate::{
     ext::{
-        register_aggregate_function, register_scalar_function_with_options, register_vtab_module,
-        unregister_function,
+        register_aggregate_function, register_commit_hook, register_rollback_hook,
+        register_scalar_function_with_options, register_vtab_module, register_window_function,
+        unregister_function, ExtensionCapabilities,
     },
+    io::{VfsCounters, VfsStats},
     Connection, LimboError,
 };
 #[cfg(not(target_family = "wasm"))]
 use libloading::{Library, Symbol};
 use std::{
-    ffi::{c_char, CString},
+    ffi::{c_char, CStr, CString},
     sync::{Arc, Mutex, OnceLock},
 };
 use turso_ext::{ExtensionApi, ExtensionApiRef, ExtensionEntryPoint, ResultCode, VfsImpl};
@@ -30,21 +32,42 @@ static VFS_MODULES: OnceLock<Mutex<Vec<Vfs>>> = OnceLock::new();
 #[derive(Clone, Debug)]
 pub struct VfsMod {
     pub ctx: *const VfsImpl,
+    pub(crate) counters: crate::sync::Arc<VfsCounters>,
 }
 
 unsafe impl Send for VfsMod {}
 unsafe impl Sync for VfsMod {}
 crate::assert::assert_send_sync!(VfsMod);
 
+impl VfsMod {
+    pub fn new(ctx: *const VfsImpl) -> Self {
+        Self {
+            ctx,
+            counters: crate::sync::Arc::new(VfsCounters::default()),
+        }
+    }
+}
+
 impl Connection {
     #[cfg(not(target_family = "wasm"))]
     pub fn load_extension<P: AsRef<std::ffi::OsStr>>(
         self: &Arc<Connection>,
         path: P,
+    ) -> crate::Result<()> {
+        self.load_extension_with_capabilities(path, ExtensionCapabilities::all())
+    }
+
+    /// Load an extension that may only use the parts of the extension API allowed by
+    /// `capabilities`. Attempts to use anything else fail when the extension makes them.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn load_extension_with_capabilities<P: AsRef<std::ffi::OsStr>>(
+        self: &Arc<Connection>,
+        path: P,
+        capabilities: ExtensionCapabilities,
     ) -> crate::Result<()> {
         use turso_ext::ExtensionApiRef;
 
-        let api = Box::new(unsafe { self._build_turso_ext() });
+        let api = Box::new(unsafe { self._build_turso_ext_with_capabilities(capabilities) });
         let lib =
             unsafe { Library::new(path).map_err(|e| LimboError::ExtensionError(e.to_string()))? };
         let entry: Symbol<ExtensionEntryPoint> = unsafe {
@@ -90,10 +113,46 @@ pub(crate) unsafe extern "C" fn register_vfs(
         Ok(s) => s.to_string(),
         Err(_) => return ResultCode::Error,
     };
-    add_vfs_module(name_str, Arc::new(VfsMod { ctx: vfs }));
+    add_vfs_module(name_str, Arc::new(VfsMod::new(vfs)));
     ResultCode::OK
 }
 
+/// Look up a VFS for an extension that layers its own over it. Rust IO
+/// backends registered with `register_io` come first, as they do for
+/// [Database::io_for_vfs](crate::Database::io_for_vfs), then the VFSes of
+/// extensions, then the backends built into core.
+pub(crate) unsafe extern "C" fn find_vfs(name: *const c_char) -> *const VfsImpl {
+    if name.is_null() {
+        return std::ptr::null();
+    }
+    let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+        return std::ptr::null();
+    };
+    if crate::io::get_registered_io(name).is_none() {
+        let modules = VFS_MODULES
+            .get_or_init(|| Mutex::new(Vec::new()))
+            .lock()
+            .unwrap();
+        if let Some((_, vfs)) = modules.iter().find(|v| v.0 == name) {
+            return vfs.ctx;
+        }
+    }
+    super::io_vfs::export_io(name)
+}
+
+#[cfg(feature = "fs")]
+pub(crate) unsafe extern "C" fn register_vfs_denied(
+    name: *const c_char,
+    _vfs: *const VfsImpl,
+) -> ResultCode {
+    if !name.is_null() {
+        // register_vfs takes ownership of the name
+        let name = unsafe { CString::from_raw(name as *mut _) };
+        tracing::error!("extension is not allowed to register VFS {name:?}");
+    }
+    ResultCode::PermissionDenied
+}
+
 /// Get pointers to all the vfs extensions that need to be built in at compile time.
 /// any other types that are defined in the same extension will not be registered
 /// until the database file is opened and `register_builtins` is called.
@@ -110,10 +169,14 @@ pub fn add_builtin_vfs_extensions(
             ctx: std::ptr::null_mut(),
             register_scalar_function: register_scalar_function_with_options,
             register_aggregate_function,
+            register_window_function,
             unregister_function,
             register_vtab_module,
+            register_commit_hook,
+            register_rollback_hook,
             vfs_interface: VfsInterface {
                 register_vfs,
+                find_vfs,
                 builtin_vfs: vfslist.as_mut_ptr(),
                 builtin_vfs_count: 0,
             },
@@ -144,12 +207,7 @@ pub fn add_builtin_vfs_extensions(
                 })?
                 .to_string()
         };
-        vfslist.push((
-            name,
-            Arc::new(VfsMod {
-                ctx: vfsimpl as *const _,
-            }),
-        ));
+        vfslist.push((name, Arc::new(VfsMod::new(vfsimpl as *const _))));
     }
     Ok(vfslist)
 }
@@ -180,6 +238,17 @@ pub fn list_vfs_modules() -> Vec<String> {
         .collect()
 }
 
+/// Operations performed so far through the registered VFS called `name`.
+pub fn vfs_stats(name: &str) -> Option<VfsStats> {
+    VFS_MODULES
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|v| v.0 == name)
+        .map(|v| v.1.stats())
+}
+
 pub fn get_vfs_modules() -> Vec<Vfs> {
     VFS_MODULES
         .get_or_init(|| Mutex::new(Vec::new()))
@@ -0,0 +1,312 @@
+//! Core IO backends exported as VFSes, for VFS extensions that layer
+//! themselves over another VFS (see [turso_ext::find_vfs]). This is the
+//! reverse of [VfsMod](super::VfsMod), which exposes an extension VFS as an
+//! [IO].
+
+use crate::io::{get_registered_io, Buffer, Completion, File, FileSyncType, OpenFlags, IO};
+use crate::sync::Arc;
+use crate::{CompletionError, Database, LimboError};
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::sync::Mutex;
+use turso_ext::{BufferRef, IOCallback, LockLevel, ResultCode, ShmLockScope, VfsImpl};
+
+const EIO: i32 = 5;
+
+/// A `VfsImpl` handed out for an IO backend. Extensions keep pointers to it,
+/// so it is never freed.
+struct ExportedVfs {
+    io: Arc<dyn IO>,
+    vfs: *const VfsImpl,
+}
+
+unsafe impl Send for ExportedVfs {}
+
+static EXPORTED: Mutex<Option<HashMap<String, ExportedVfs>>> = Mutex::new(None);
+
+/// The IO backend `name` resolves to with [Database::io_for_vfs], as a
+/// `VfsImpl`, or null if there is none.
+pub(crate) fn export_io(name: &str) -> *const VfsImpl {
+    let mut exported = EXPORTED.lock().unwrap();
+    let exported = exported.get_or_insert_with(HashMap::new);
+    let registered = get_registered_io(name);
+    if let Some(vfs) = exported.get(name) {
+        match &registered {
+            Some(io) if !Arc::ptr_eq(io, &vfs.io) => {}
+            _ => return vfs.vfs,
+        }
+    }
+    let io = match registered {
+        Some(io) => io,
+        None => match Database::io_for_vfs(name) {
+            Ok(io) => io,
+            Err(e) => {
+                tracing::debug!("no IO backend to export as VFS {name}: {e}");
+                return std::ptr::null();
+            }
+        },
+    };
+    let ctx = Box::into_raw(Box::new(io.clone())) as *const c_void;
+    let vfs = Box::into_raw(Box::new(VfsImpl {
+        name: CString::new(name).unwrap_or_default().into_raw(),
+        vfs: ctx,
+        open,
+        remove,
+        close,
+        read,
+        write,
+        sync,
+        lock,
+        unlock,
+        lock_level,
+        unlock_level,
+        check_reserved_lock,
+        supports_shm,
+        shm_lock,
+        shm_unlock,
+        shm_set_len,
+        shm_map,
+        shm_unmap,
+        size,
+        run_once,
+        current_time,
+        gen_random_number,
+        truncate,
+        allocate,
+    })) as *const VfsImpl;
+    exported.insert(name.to_string(), ExportedVfs { io, vfs });
+    vfs
+}
+
+fn errno(err: &CompletionError) -> i32 {
+    match err {
+        #[cfg(target_family = "unix")]
+        CompletionError::RustixIOError(errno) => errno.raw_os_error(),
+        _ => EIO,
+    }
+}
+
+fn result_code(result: crate::Result<()>) -> ResultCode {
+    match result {
+        Ok(()) => ResultCode::OK,
+        Err(LimboError::Busy) => ResultCode::Busy,
+        Err(e) => {
+            tracing::debug!("exported IO backend failed: {e}");
+            ResultCode::Error
+        }
+    }
+}
+
+/// Calls back into the extension once, with the result of the operation.
+struct PendingCallback(Mutex<Option<IOCallback>>);
+
+impl PendingCallback {
+    fn new(cb: IOCallback) -> Self {
+        Self(Mutex::new(Some(cb)))
+    }
+
+    fn call(&self, result: i32) {
+        if let Some(cb) = self.0.lock().unwrap().take() {
+            unsafe { (cb.callback)(result, cb.ctx) };
+        }
+    }
+}
+
+unsafe fn io<'a>(ctx: *const c_void) -> &'a Arc<dyn IO> {
+    &*(ctx as *const Arc<dyn IO>)
+}
+
+unsafe fn file<'a>(file: *const c_void) -> &'a Arc<dyn File> {
+    &*(file as *const Arc<dyn File>)
+}
+
+unsafe extern "C" fn open(
+    ctx: *const c_void,
+    path: *const c_char,
+    flags: i32,
+    direct: bool,
+) -> *const c_void {
+    let vfs = &*(ctx as *const VfsImpl);
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return std::ptr::null();
+    };
+    match io(vfs.vfs).open_file(path, OpenFlags::from_bits_truncate(flags), direct) {
+        Ok(file) => Box::into_raw(Box::new(file)) as *const c_void,
+        Err(e) => {
+            tracing::debug!("failed to open {path} through exported IO backend: {e}");
+            std::ptr::null()
+        }
+    }
+}
+
+unsafe extern "C" fn remove(ctx: *const c_void, path: *const c_char) -> ResultCode {
+    let vfs = &*(ctx as *const VfsImpl);
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return ResultCode::InvalidArgs;
+    };
+    result_code(io(vfs.vfs).remove_file(path))
+}
+
+unsafe extern "C" fn close(file: *const c_void) -> ResultCode {
+    if file.is_null() {
+        return ResultCode::Error;
+    }
+    drop(Box::from_raw(file as *mut Arc<dyn File>));
+    ResultCode::OK
+}
+
+unsafe extern "C" fn read(
+    file_ptr: *const c_void,
+    mut buf: BufferRef,
+    offset: i64,
+    cb: IOCallback,
+) -> ResultCode {
+    let len = buf.len();
+    let dest = buf.as_mut_ptr() as usize;
+    let cb = PendingCallback::new(cb);
+    let c = Completion::new_read(Arc::new(Buffer::new_temporary(len)), move |res| {
+        match res {
+            Ok((data, n)) => {
+                let n = (n.max(0) as usize).min(len);
+                // SAFETY: the extension keeps `buf` alive until it is called back.
+                let dest = unsafe { std::slice::from_raw_parts_mut(dest as *mut u8, len) };
+                dest[..n].copy_from_slice(&data.as_slice()[..n]);
+                cb.call(n as i32);
+            }
+            Err(e) => cb.call(-errno(&e)),
+        }
+        None
+    });
+    result_code(file(file_ptr).pread(offset as u64, c).map(|_| ()))
+}
+
+unsafe extern "C" fn write(
+    file_ptr: *const c_void,
+    buf: BufferRef,
+    offset: i64,
+    cb: IOCallback,
+) -> ResultCode {
+    let buffer = Arc::new(Buffer::new(buf.as_slice().to_vec()));
+    let cb = PendingCallback::new(cb);
+    let c = Completion::new_write(move |res| match res {
+        Ok(n) => cb.call(n),
+        Err(e) => cb.call(-errno(&e)),
+    });
+    result_code(file(file_ptr).pwrite(offset as u64, buffer, c).map(|_| ()))
+}
+
+unsafe extern "C" fn sync(file_ptr: *const c_void, cb: IOCallback) -> ResultCode {
+    let cb = PendingCallback::new(cb);
+    let c = Completion::new_sync(move |res| match res {
+        Ok(n) => cb.call(n),
+        Err(e) => cb.call(-errno(&e)),
+    });
+    result_code(file(file_ptr).sync(c, FileSyncType::Fsync).map(|_| ()))
+}
+
+unsafe extern "C" fn truncate(file_ptr: *const c_void, len: i64, cb: IOCallback) -> ResultCode {
+    let cb = PendingCallback::new(cb);
+    let c = Completion::new_trunc(move |res| match res {
+        Ok(n) => cb.call(n),
+        Err(e) => cb.call(-errno(&e)),
+    });
+    result_code(file(file_ptr).truncate(len as u64, c).map(|_| ()))
+}
+
+unsafe extern "C" fn allocate(file_ptr: *const c_void, len: i64) -> ResultCode {
+    result_code(file(file_ptr).allocate(len as u64))
+}
+
+unsafe extern "C" fn lock(file_ptr: *const c_void, exclusive: bool) -> ResultCode {
+    result_code(file(file_ptr).lock_file(exclusive))
+}
+
+unsafe extern "C" fn unlock(file_ptr: *const c_void) -> ResultCode {
+    result_code(file(file_ptr).unlock_file())
+}
+
+unsafe extern "C" fn lock_level(file_ptr: *const c_void, level: LockLevel) -> ResultCode {
+    result_code(file(file_ptr).lock_level(level))
+}
+
+unsafe extern "C" fn unlock_level(file_ptr: *const c_void, level: LockLevel) -> ResultCode {
+    result_code(file(file_ptr).unlock_level(level))
+}
+
+unsafe extern "C" fn check_reserved_lock(
+    file_ptr: *const c_void,
+    reserved: *mut bool,
+) -> ResultCode {
+    match file(file_ptr).check_reserved_lock() {
+        Ok(held) => {
+            *reserved = held;
+            ResultCode::OK
+        }
+        Err(e) => result_code(Err(e)),
+    }
+}
+
+unsafe extern "C" fn supports_shm(_ctx: *const c_void) -> bool {
+    false
+}
+
+unsafe extern "C" fn shm_lock(
+    _file: *const c_void,
+    _offset: u64,
+    _exclusive: bool,
+    _blocking: bool,
+    _scope: ShmLockScope,
+    _acquired: *mut bool,
+) -> ResultCode {
+    ResultCode::Unimplemented
+}
+
+unsafe extern "C" fn shm_unlock(
+    _file: *const c_void,
+    _offset: u64,
+    _scope: ShmLockScope,
+) -> ResultCode {
+    ResultCode::Unimplemented
+}
+
+unsafe extern "C" fn shm_set_len(_file: *const c_void, _len: u64) -> ResultCode {
+    ResultCode::Unimplemented
+}
+
+unsafe extern "C" fn shm_map(
+    _file: *const c_void,
+    _offset: u64,
+    _len: usize,
+    _ptr: *mut *mut u8,
+) -> ResultCode {
+    ResultCode::Unimplemented
+}
+
+unsafe extern "C" fn shm_unmap(
+    _ctx: *const c_void,
+    _ptr: *mut u8,
+    _offset: u64,
+    _len: usize,
+) -> ResultCode {
+    ResultCode::Unimplemented
+}
+
+unsafe extern "C" fn size(file_ptr: *const c_void) -> i64 {
+    match file(file_ptr).size() {
+        Ok(size) => size as i64,
+        Err(_) => -1,
+    }
+}
+
+unsafe extern "C" fn run_once(ctx: *const c_void) -> ResultCode {
+    result_code(io(ctx).step())
+}
+
+unsafe extern "C" fn current_time() -> *const c_char {
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    CString::new(now).unwrap_or_default().into_raw()
+}
+
+unsafe extern "C" fn gen_random_number() -> i64 {
+    rand::random()
+}
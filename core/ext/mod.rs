@@ -1,5 +1,7 @@
 #[cfg(feature = "fs")]
 mod dynamic;
+#[cfg(feature = "fs")]
+mod io_vfs;
 mod vtab_xconnect;
 use crate::index_method::backing_btree::BackingBtreeIndexMethod;
 #[cfg(all(feature = "fts", not(target_family = "wasm")))]
@@ -15,23 +17,90 @@ use crate::sync::Mutex;
 use crate::UringIO;
 #[cfg(all(target_os = "windows", feature = "experimental_win_iocp", not(miri)))]
 use crate::WindowsIOCP;
+use bitflags::bitflags;
 
-use crate::{function::ExternalFunc, Connection, Database};
+use crate::function::{ExternalCommitHook, ExternalFunc, ExternalRollbackHook};
 use crate::{vtab::VirtualTable, SymbolTable};
+use crate::{Connection, Database};
 #[cfg(feature = "fs")]
 use crate::{LimboError, IO};
 #[cfg(feature = "fs")]
-pub use dynamic::{add_builtin_vfs_extensions, add_vfs_module, list_vfs_modules, VfsMod};
+pub use dynamic::{
+    add_builtin_vfs_extensions, add_vfs_module, list_vfs_modules, vfs_stats, VfsMod,
+};
 use std::{
     ffi::{c_char, c_void, CStr, CString},
     sync::Arc,
 };
 use turso_ext::{
-    ContextDestructor, ExtensionApi, InitAggFunction, ResultCode, ScalarFunction, VTabKind,
-    VTabModuleImpl, ValueDestructor,
+    CommitHook, ContextDestructor, ExtensionApi, InitAggFunction, InverseFunction, ResultCode,
+    RollbackHook, ScalarFunction, VTabKind, VTabModuleImpl, ValueDestructor, ValueFunction,
 };
 pub use turso_ext::{FinalizeFunction, StepFunction, Value as ExtValue, ValueType as ExtValueType};
-pub use vtab_xconnect::{execute, prepare_stmt};
+pub use vtab_xconnect::{
+    execute, execute_nested, execute_nested_read_only, execute_read_only, prepare_stmt,
+    prepare_stmt_nested, prepare_stmt_nested_read_only, prepare_stmt_read_only,
+};
+
+/// Entry point of an extension linked statically into the binary. This is the
+/// `register_extension_static` function generated by `register_extension!` when the
+/// extension is built with its `static` feature.
+pub type AutoExtension = unsafe extern "C" fn(api: &mut ExtensionApi) -> ResultCode;
+
+static AUTO_EXTENSIONS: std::sync::Mutex<Vec<AutoExtension>> = std::sync::Mutex::new(Vec::new());
+
+fn auto_extensions() -> std::sync::MutexGuard<'static, Vec<AutoExtension>> {
+    AUTO_EXTENSIONS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Register a statically linked extension to be loaded into every database opened
+/// afterwards, and therefore into every connection to it. Registering the same entry
+/// point twice is a no-op. Databases that are already open are not affected.
+pub fn register_auto_extension(entry: AutoExtension) {
+    let mut entries = auto_extensions();
+    if !entries.iter().any(|e| *e as usize == entry as usize) {
+        entries.push(entry);
+    }
+}
+
+/// Remove a previously registered auto extension, returning whether it was registered.
+pub fn cancel_auto_extension(entry: AutoExtension) -> bool {
+    let mut entries = auto_extensions();
+    let len = entries.len();
+    entries.retain(|e| *e as usize != entry as usize);
+    entries.len() != len
+}
+
+/// Remove every registered auto extension.
+pub fn reset_auto_extension() {
+    auto_extensions().clear();
+}
+
+/// Register statically linked extensions so that every database opened afterwards loads
+/// them, e.g. `turso_core::auto_extension!(limbo_crypto, limbo_regexp);`. Each crate must
+/// be built with its `static` feature so it exports `register_extension_static`.
+#[macro_export]
+macro_rules! auto_extension {
+    ($($ext:ident),+ $(,)?) => {
+        $( $crate::register_auto_extension($ext::register_extension_static); )+
+    };
+}
+
+bitflags! {
+    /// What an extension may do through the extension API. Extensions run in-process,
+    /// so this bounds what is reachable through the API, not what native code can do.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ExtensionCapabilities: u32 {
+        /// Register VFS implementations, which do file I/O on behalf of the database.
+        const FILESYSTEM = 1 << 0;
+        /// Run read-only statements through the connection handed to virtual tables.
+        const DATABASE_READ = 1 << 1;
+        /// Run statements that modify the database through that connection.
+        const DATABASE_WRITE = 1 << 2;
+    }
+}
 
 /// The context passed to extensions to register with Core
 /// along with the function pointers
@@ -42,6 +111,7 @@ pub struct ExtensionCtx {
     /// We must bump the prepare context generation so prepared statements
     /// know they need to be reprepared after extension registration.
     prepare_context_generation: *const AtomicU64,
+    pub(crate) capabilities: ExtensionCapabilities,
 }
 
 pub(crate) unsafe extern "C" fn register_vtab_module(
@@ -65,6 +135,7 @@ pub(crate) unsafe extern "C" fn register_vtab_module(
     let vmodule = VTabImpl {
         module_kind: kind,
         implementation: module,
+        capabilities: ext_ctx.capabilities,
     };
 
     unsafe {
@@ -91,10 +162,56 @@ pub(crate) unsafe extern "C" fn register_vtab_module(
     ResultCode::OK
 }
 
+pub(crate) unsafe extern "C" fn register_commit_hook(
+    ctx: *mut c_void,
+    context: usize,
+    hook: CommitHook,
+    context_destructor: Option<ContextDestructor>,
+) -> ResultCode {
+    if ctx.is_null() {
+        return ResultCode::InvalidArgs;
+    }
+    let ext_ctx = unsafe { &mut *(ctx as *mut ExtensionCtx) };
+    unsafe {
+        (*ext_ctx.syms)
+            .commit_hooks
+            .push(Arc::new(ExternalCommitHook::new(
+                context,
+                hook,
+                context_destructor,
+            )));
+    }
+    ResultCode::OK
+}
+
+pub(crate) unsafe extern "C" fn register_rollback_hook(
+    ctx: *mut c_void,
+    context: usize,
+    hook: RollbackHook,
+    context_destructor: Option<ContextDestructor>,
+) -> ResultCode {
+    if ctx.is_null() {
+        return ResultCode::InvalidArgs;
+    }
+    let ext_ctx = unsafe { &mut *(ctx as *mut ExtensionCtx) };
+    unsafe {
+        (*ext_ctx.syms)
+            .rollback_hooks
+            .push(Arc::new(ExternalRollbackHook::new(
+                context,
+                hook,
+                context_destructor,
+            )));
+    }
+    ResultCode::OK
+}
+
 #[derive(Clone)]
 pub struct VTabImpl {
     pub module_kind: VTabKind,
     pub implementation: Arc<VTabModuleImpl>,
+    /// Capabilities of the extension that registered the module.
+    pub capabilities: ExtensionCapabilities,
 }
 
 pub(crate) unsafe fn register_scalar_function(
@@ -105,6 +222,14 @@ pub(crate) unsafe fn register_scalar_function(
     unsafe { register_scalar_function_with_options(ctx, name, -1, false, 0, func, None, None) }
 }
 
+pub(crate) unsafe fn register_deterministic_scalar_function(
+    ctx: *mut c_void,
+    name: *const c_char,
+    func: ScalarFunction,
+) -> ResultCode {
+    unsafe { register_scalar_function_with_options(ctx, name, -1, true, 0, func, None, None) }
+}
+
 pub(crate) unsafe extern "C" fn register_scalar_function_with_options(
     ctx: *mut c_void,
     name: *const c_char,
@@ -179,6 +304,58 @@ pub(crate) unsafe extern "C" fn register_aggregate_function(
     context_destructor: Option<ContextDestructor>,
     aggregate_destructor: Option<ContextDestructor>,
     value_destructor: Option<ValueDestructor>,
+) -> ResultCode {
+    insert_aggregate_function(
+        ctx,
+        name,
+        args,
+        context,
+        (init_func, step_func, finalize_func),
+        None,
+        context_destructor,
+        aggregate_destructor,
+        value_destructor,
+    )
+}
+
+pub(crate) unsafe extern "C" fn register_window_function(
+    ctx: *mut c_void,
+    name: *const c_char,
+    args: i32,
+    context: usize,
+    init_func: InitAggFunction,
+    step_func: StepFunction,
+    finalize_func: FinalizeFunction,
+    value_func: ValueFunction,
+    inverse_func: InverseFunction,
+    context_destructor: Option<ContextDestructor>,
+    aggregate_destructor: Option<ContextDestructor>,
+    value_destructor: Option<ValueDestructor>,
+) -> ResultCode {
+    insert_aggregate_function(
+        ctx,
+        name,
+        args,
+        context,
+        (init_func, step_func, finalize_func),
+        Some((value_func, inverse_func)),
+        context_destructor,
+        aggregate_destructor,
+        value_destructor,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn insert_aggregate_function(
+    ctx: *mut c_void,
+    name: *const c_char,
+    args: i32,
+    context: usize,
+    funcs: (InitAggFunction, StepFunction, FinalizeFunction),
+    window: Option<(ValueFunction, InverseFunction)>,
+    context_destructor: Option<ContextDestructor>,
+    aggregate_destructor: Option<ContextDestructor>,
+    value_destructor: Option<ValueDestructor>,
 ) -> ResultCode {
     if ctx.is_null() || name.is_null() || args < -1 {
         return ResultCode::InvalidArgs;
@@ -196,7 +373,8 @@ pub(crate) unsafe extern "C" fn register_aggregate_function(
                 name_str,
                 args,
                 context,
-                (init_func, step_func, finalize_func),
+                funcs,
+                window,
                 context_destructor,
                 aggregate_destructor,
                 value_destructor,
@@ -266,17 +444,22 @@ impl Database {
             syms,
             schema: schema_mutex_ptr as *mut c_void,
             prepare_context_generation: std::ptr::null(),
+            capabilities: ExtensionCapabilities::all(),
         }));
         #[allow(unused)]
         let mut ext_api = ExtensionApi {
             ctx: ctx as *mut c_void,
             register_scalar_function: register_scalar_function_with_options,
             register_aggregate_function,
+            register_window_function,
             unregister_function,
             register_vtab_module,
+            register_commit_hook,
+            register_rollback_hook,
             #[cfg(feature = "fs")]
             vfs_interface: turso_ext::VfsInterface {
                 register_vfs: dynamic::register_vfs,
+                find_vfs: dynamic::find_vfs,
                 builtin_vfs: std::ptr::null_mut(),
                 builtin_vfs_count: 0,
             },
@@ -291,6 +474,14 @@ impl Database {
         #[cfg(feature = "percentile")]
         crate::percentile::register_extension(&mut ext_api);
         crate::regexp::register_extension(&mut ext_api);
+        let auto_extensions = auto_extensions().clone();
+        for entry in auto_extensions {
+            let rc = unsafe { entry(&mut ext_api) };
+            if !rc.is_ok() {
+                let _ = unsafe { Box::from_raw(ctx) };
+                return Err(format!("failed to register auto extension: {rc}"));
+            }
+        }
         #[cfg(feature = "fs")]
         {
             let vfslist = add_builtin_vfs_extensions(Some(ext_api)).map_err(|e| e.to_string())?;
@@ -335,23 +526,44 @@ impl Connection {
     /// }
     ///```
     pub unsafe fn _build_turso_ext(&self) -> ExtensionApi {
+        unsafe { self._build_turso_ext_with_capabilities(ExtensionCapabilities::all()) }
+    }
+
+    /// Like `_build_turso_ext`, for an extension that may only use the parts of the
+    /// extension API allowed by `capabilities`.
+    ///
+    /// # Safety
+    /// Same as `_build_turso_ext`.
+    pub unsafe fn _build_turso_ext_with_capabilities(
+        &self,
+        capabilities: ExtensionCapabilities,
+    ) -> ExtensionApi {
         let schema_mutex_ptr =
             &*self.db.schema as *const Mutex<Arc<Schema>> as *mut Mutex<Arc<Schema>>;
         let ctx = ExtensionCtx {
             syms: self.syms.data_ptr(),
             schema: schema_mutex_ptr as *mut c_void,
             prepare_context_generation: &self.prepare_context_generation as *const _,
+            capabilities,
         };
         let ctx = Box::into_raw(Box::new(ctx)) as *mut c_void;
         ExtensionApi {
             ctx,
             register_scalar_function: register_scalar_function_with_options,
             register_aggregate_function,
+            register_window_function,
             unregister_function,
             register_vtab_module,
+            register_commit_hook,
+            register_rollback_hook,
             #[cfg(feature = "fs")]
             vfs_interface: turso_ext::VfsInterface {
-                register_vfs: dynamic::register_vfs,
+                register_vfs: if capabilities.contains(ExtensionCapabilities::FILESYSTEM) {
+                    dynamic::register_vfs
+                } else {
+                    dynamic::register_vfs_denied
+                },
+                find_vfs: dynamic::find_vfs,
                 builtin_vfs: std::ptr::null_mut(),
                 builtin_vfs_count: 0,
             },
@@ -367,4 +579,32 @@ impl Connection {
         }
         let _ = unsafe { Box::from_raw(api.ctx as *mut ExtensionCtx) };
     }
+
+    /// Run the commit hooks registered by extensions before a write transaction
+    /// commits. The first hook that does not return OK vetoes the commit.
+    pub(crate) fn run_commit_hooks(&self) -> crate::Result<()> {
+        let hooks = self.syms.read().commit_hooks.clone();
+        for hook in hooks {
+            let rc = unsafe { (hook.callback)(hook.context) };
+            if !rc.is_ok() {
+                return Err(crate::LimboError::ExtensionError(format!(
+                    "commit hook vetoed the transaction: {rc}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the rollback hooks registered by extensions after a write transaction
+    /// was rolled back. Virtual tables that took part in the transaction are rolled
+    /// back first, if that did not already happen when the statement failed.
+    pub(crate) fn run_rollback_hooks(&self) {
+        if let Err(e) = crate::vdbe::execute::vtab_rollback_all(self) {
+            tracing::error!("failed to roll back virtual tables: {e}");
+        }
+        let hooks = self.syms.read().rollback_hooks.clone();
+        for hook in hooks {
+            unsafe { (hook.callback)(hook.context) };
+        }
+    }
 }
@@ -1,12 +1,13 @@
-use crate::{types::Value, Connection, LimboError, Statement};
+use crate::{sync::Arc, types::Value, Connection, LimboError, Statement};
 use std::{
     boxed::Box,
     ffi::{c_char, c_void, CStr, CString},
     num::NonZeroUsize,
     ptr,
-    sync::Weak,
+    sync::{atomic::Ordering, Weak},
 };
 use turso_ext::{Conn as ExtConn, ResultCode, Stmt, Value as ExtValue};
+use turso_parser::ast::Cmd;
 
 /// Wrapper around core Connection::execute with optional arguments to bind
 /// to the statment This function takes ownership of the optional turso_ext::Value array if provided
@@ -16,6 +17,84 @@ pub unsafe extern "C" fn execute(
     args: *mut ExtValue,
     arg_count: i32,
     last_insert_rowid: *mut i64,
+) -> ResultCode {
+    unsafe { execute_inner(ctx, sql, args, arg_count, last_insert_rowid, true, false) }
+}
+
+/// Same as [execute], for extensions without the `DATABASE_WRITE` capability:
+/// statements that would modify the database are rejected with `ResultCode::ReadOnly`.
+pub unsafe extern "C" fn execute_read_only(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+    args: *mut ExtValue,
+    arg_count: i32,
+    last_insert_rowid: *mut i64,
+) -> ResultCode {
+    unsafe { execute_inner(ctx, sql, args, arg_count, last_insert_rowid, false, false) }
+}
+
+/// Same as [execute], for connections handed to a virtual table while it runs inside a write
+/// statement of that connection: the query runs as a helper of that statement, in its
+/// transaction, e.g. to maintain the table's shadow tables.
+pub unsafe extern "C" fn execute_nested(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+    args: *mut ExtValue,
+    arg_count: i32,
+    last_insert_rowid: *mut i64,
+) -> ResultCode {
+    unsafe { execute_inner(ctx, sql, args, arg_count, last_insert_rowid, true, true) }
+}
+
+/// [execute_nested] for extensions without the `DATABASE_WRITE` capability.
+pub unsafe extern "C" fn execute_nested_read_only(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+    args: *mut ExtValue,
+    arg_count: i32,
+    last_insert_rowid: *mut i64,
+) -> ResultCode {
+    unsafe { execute_inner(ctx, sql, args, arg_count, last_insert_rowid, false, true) }
+}
+
+/// Prepares `sql` on `conn`. Nested statements are helpers of the statement currently running
+/// on the connection, so they share its transaction rather than opening a statement journal.
+fn prepare_for_extension(
+    conn: &Arc<Connection>,
+    sql: &str,
+    nested: bool,
+) -> crate::Result<Statement> {
+    if !nested {
+        return conn.prepare(sql);
+    }
+    let stmt = conn.prepare_internal(sql)?;
+    stmt.program
+        .prepared
+        .needs_stmt_subtransactions
+        .store(false, Ordering::Relaxed);
+    Ok(stmt)
+}
+
+/// Whether `sql` may be prepared for an extension without the `DATABASE_WRITE` capability.
+/// Judged from the syntax, since translating a pragma setter already applies it. Statements
+/// that fail to parse are left for the prepare to report.
+fn is_read_only_sql(conn: &Connection, sql: &str) -> bool {
+    match conn.parse_sql(sql) {
+        Ok((Some(Cmd::Stmt(stmt) | Cmd::Explain(stmt) | Cmd::ExplainQueryPlan(stmt)), _)) => {
+            crate::translate::stmt_is_read_only(&stmt)
+        }
+        Ok((None, _)) | Err(_) => true,
+    }
+}
+
+unsafe fn execute_inner(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+    args: *mut ExtValue,
+    arg_count: i32,
+    last_insert_rowid: *mut i64,
+    allow_writes: bool,
+    nested: bool,
 ) -> ResultCode {
     let c_str = unsafe { CStr::from_ptr(sql as *mut c_char) };
     let sql_str = match c_str.to_str() {
@@ -36,8 +115,16 @@ pub unsafe extern "C" fn execute(
     let weak_box = extcon._ctx as *const Weak<Connection>;
     let weak = unsafe { &*weak_box };
     if let Some(conn) = weak.upgrade() {
-        match conn.query(&sql_str) {
-            Ok(Some(mut stmt)) => {
+        if !allow_writes && !is_read_only_sql(&conn, &sql_str) {
+            tracing::error!("execute: extension is not allowed to write: {sql_str}");
+            return ResultCode::ReadOnly;
+        }
+        match prepare_for_extension(&conn, &sql_str, nested) {
+            Ok(mut stmt) => {
+                if !allow_writes && !stmt.program.is_readonly() {
+                    tracing::error!("execute: extension is not allowed to write: {sql_str}");
+                    return ResultCode::ReadOnly;
+                }
                 if arg_count > 0 {
                     let args_slice = &mut std::slice::from_raw_parts_mut(args, arg_count as usize);
                     for (i, val) in args_slice.iter_mut().enumerate() {
@@ -73,7 +160,6 @@ pub unsafe extern "C" fn execute(
                 };
                 return rc;
             }
-            Ok(None) => tracing::error!("query: no statement returned"),
             Err(e) => tracing::error!("query: failed to execute query: {:?}", e),
         };
     }
@@ -83,6 +169,37 @@ pub unsafe extern "C" fn execute(
 /// Wraps core Connection::prepare with a custom Stmt object with the necessary function pointers.
 /// This object is boxed/leaked and the caller is responsible for freeing the memory.
 pub unsafe extern "C" fn prepare_stmt(ctx: *mut ExtConn, sql: *const c_char) -> *mut Stmt {
+    unsafe { prepare_stmt_inner(ctx, sql, true, false) }
+}
+
+/// Same as [prepare_stmt], for extensions without the `DATABASE_WRITE` capability:
+/// statements that would modify the database fail to prepare.
+pub unsafe extern "C" fn prepare_stmt_read_only(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+) -> *mut Stmt {
+    unsafe { prepare_stmt_inner(ctx, sql, false, false) }
+}
+
+/// Same as [prepare_stmt], for the connections that [execute_nested] is used with.
+pub unsafe extern "C" fn prepare_stmt_nested(ctx: *mut ExtConn, sql: *const c_char) -> *mut Stmt {
+    unsafe { prepare_stmt_inner(ctx, sql, true, true) }
+}
+
+/// [prepare_stmt_nested] for extensions without the `DATABASE_WRITE` capability.
+pub unsafe extern "C" fn prepare_stmt_nested_read_only(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+) -> *mut Stmt {
+    unsafe { prepare_stmt_inner(ctx, sql, false, true) }
+}
+
+unsafe fn prepare_stmt_inner(
+    ctx: *mut ExtConn,
+    sql: *const c_char,
+    allow_writes: bool,
+    nested: bool,
+) -> *mut Stmt {
     let c_str = unsafe { CStr::from_ptr(sql as *mut c_char) };
     let sql_str = match c_str.to_str() {
         Ok(s) => s.to_string(),
@@ -102,7 +219,15 @@ pub unsafe extern "C" fn prepare_stmt(ctx: *mut ExtConn, sql: *const c_char) ->
     let weak_box = extcon._ctx as *const Weak<Connection>;
     let weak = unsafe { &*weak_box };
     if let Some(conn) = weak.upgrade() {
-        match conn.prepare(&sql_str) {
+        if !allow_writes && !is_read_only_sql(&conn, &sql_str) {
+            tracing::error!("prepare_stmt: extension is not allowed to write: {sql_str}");
+            return ptr::null_mut();
+        }
+        match prepare_for_extension(&conn, &sql_str, nested) {
+            Ok(stmt) if !allow_writes && !stmt.program.is_readonly() => {
+                tracing::error!("prepare_stmt: extension is not allowed to write: {sql_str}");
+                ptr::null_mut()
+            }
             Ok(stmt) => {
                 let raw_stmt = Box::into_raw(Box::new(stmt)) as *mut c_void;
                 Box::into_raw(Box::new(Stmt::new(
@@ -3,8 +3,8 @@ use std::fmt;
 use std::fmt::{Debug, Display};
 use strum::IntoEnumIterator;
 use turso_ext::{
-    ContextDestructor, FinalizeFunction, InitAggFunction, ScalarFunction, StepFunction,
-    ValueDestructor,
+    CommitHook, ContextDestructor, FinalizeFunction, InitAggFunction, InverseFunction,
+    RollbackHook, ScalarFunction, StepFunction, ValueDestructor, ValueFunction,
 };
 
 use crate::LimboError;
@@ -65,6 +65,43 @@ impl Debug for ExternalCollation {
     }
 }
 
+/// A transaction hook registered by an extension, together with the context it is
+/// called with.
+pub struct ExternalTxnHook<F> {
+    pub context: usize,
+    pub callback: F,
+    pub context_destructor: Option<ContextDestructor>,
+}
+
+pub type ExternalCommitHook = ExternalTxnHook<CommitHook>;
+pub type ExternalRollbackHook = ExternalTxnHook<RollbackHook>;
+
+impl<F> ExternalTxnHook<F> {
+    pub fn new(context: usize, callback: F, context_destructor: Option<ContextDestructor>) -> Self {
+        Self {
+            context,
+            callback,
+            context_destructor,
+        }
+    }
+}
+
+impl<F> Drop for ExternalTxnHook<F> {
+    fn drop(&mut self) {
+        if let Some(destructor) = self.context_destructor {
+            unsafe { destructor(self.context) };
+        }
+    }
+}
+
+impl<F> Debug for ExternalTxnHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalTxnHook")
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
 impl Deterministic for ExternalFunc {
     fn is_deterministic(&self) -> bool {
         match self.func {
@@ -90,6 +127,8 @@ pub enum ExtFunc {
         init: InitAggFunction,
         step: StepFunction,
         finalize: FinalizeFunction,
+        /// Set for aggregates that can be used as window functions.
+        window: Option<(ValueFunction, InverseFunction)>,
         context_destructor: Option<ContextDestructor>,
         aggregate_destructor: Option<ContextDestructor>,
         value_destructor: Option<ValueDestructor>,
@@ -115,6 +154,17 @@ impl ExtFunc {
         matches!(self, Self::Aggregate { .. })
     }
 
+    /// Whether this is an aggregate that can be used with an `OVER` clause.
+    pub fn is_window_aggregate(&self) -> bool {
+        matches!(
+            self,
+            Self::Aggregate {
+                window: Some(_),
+                ..
+            }
+        )
+    }
+
     pub fn with_aggregate_arg_count(&self, arg_count: usize) -> Self {
         match self {
             Self::Aggregate {
@@ -122,6 +172,7 @@ impl ExtFunc {
                 init,
                 step,
                 finalize,
+                window,
                 aggregate_destructor,
                 value_destructor,
                 ..
@@ -131,6 +182,7 @@ impl ExtFunc {
                 init: *init,
                 step: *step,
                 finalize: *finalize,
+                window: *window,
                 context_destructor: None,
                 aggregate_destructor: *aggregate_destructor,
                 value_destructor: *value_destructor,
@@ -168,6 +220,7 @@ impl ExternalFunc {
         argc: i32,
         context: usize,
         func: (InitAggFunction, StepFunction, FinalizeFunction),
+        window: Option<(ValueFunction, InverseFunction)>,
         context_destructor: Option<ContextDestructor>,
         aggregate_destructor: Option<ContextDestructor>,
         value_destructor: Option<ValueDestructor>,
@@ -180,6 +233,7 @@ impl ExternalFunc {
                 init: func.0,
                 step: func.1,
                 finalize: func.2,
+                window,
                 context_destructor,
                 aggregate_destructor,
                 value_destructor,
@@ -886,6 +940,9 @@ pub enum ScalarFunc {
     Repeat,
     Lpad,
     Rpad,
+    // Order-independent content digests
+    RowHash,
+    HashTable,
     // Built-in type support functions
     BooleanToInt,
     IntToBoolean,
@@ -1014,6 +1071,9 @@ impl Deterministic for ScalarFunc {
             | ScalarFunc::Repeat
             | ScalarFunc::Lpad
             | ScalarFunc::Rpad => true,
+            ScalarFunc::RowHash => true,
+            // reads the table, so it can't be hoisted out of a loop or a statement
+            ScalarFunc::HashTable => false,
             #[cfg(feature = "test_helper")]
             ScalarFunc::TestNondetCounter => false,
             ScalarFunc::BooleanToInt
@@ -1163,6 +1223,8 @@ impl Display for ScalarFunc {
             Self::Repeat => "repeat",
             Self::Lpad => "lpad",
             Self::Rpad => "rpad",
+            Self::RowHash => "row_hash",
+            Self::HashTable => "hash_table",
             Self::BooleanToInt => "boolean_to_int",
             Self::IntToBoolean => "int_to_boolean",
             Self::ValidateIpAddr => "validate_ipaddr",
@@ -1282,7 +1344,8 @@ impl ScalarFunc {
             | Self::UnixEpoch
             | Self::JulianDay
             | Self::StrfTime
-            | Self::Printf => &[-1],
+            | Self::Printf
+            | Self::RowHash => &[-1],
             #[cfg(feature = "fs")]
             #[cfg(not(target_family = "wasm"))]
             Self::LoadExtension => &[-1],
@@ -1348,6 +1411,8 @@ impl ScalarFunc {
             Self::UnionValueFunc => &[2],    // union_value('tag', value)
             Self::UnionTagFunc => &[1],      // union_tag(col)
             Self::UnionExtractFunc => &[2],  // union_extract(col, 'tag')
+            // Content digests
+            Self::HashTable => &[1], // hash_table('t')
             // Sequence functions
             Self::NextVal | Self::CurrVal => &[1],
             Self::SetVal => &[2, 3],
@@ -1,3 +1,4 @@
+use crate::io::clock::{Clock, WallClockInstant};
 use crate::numeric::Numeric;
 use crate::types::AsValueRef;
 use crate::types::Value;
@@ -6,7 +7,9 @@ use crate::{Result, ValueRef};
 // chrono isn't used more due to incompatibility with sqlite
 use chrono::{Local, Offset, TimeZone};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fmt::Write;
+use std::sync::Arc;
 
 const JD_TO_MS: i64 = 86_400_000;
 const MAX_JD: i64 = 464269060799999; // 9999-12-31 23:59:59.999
@@ -195,13 +198,36 @@ fn get_digits(z: &str, digits: usize, min_val: i32, max_val: i32) -> Option<(i32
     Some((val, &z[digits..]))
 }
 
+thread_local! {
+    /// The clock that `'now'` is read from, set while the VDBE runs a date and time function.
+    static CLOCK: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with `'now'` read from `clock` instead of the system time, so that the date and
+/// time functions follow the clock of the IO the statement runs on. The clock is only read
+/// if `f` evaluates `'now'`.
+pub(crate) fn with_clock<T>(clock: Arc<dyn Clock>, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Arc<dyn Clock>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CLOCK.with(|cell| cell.replace(self.0.take()));
+        }
+    }
+    let _restore = Restore(CLOCK.with(|cell| cell.replace(Some(clock))));
+    f()
+}
+
 fn set_to_current(p: &mut DateTime) {
-    let now = std::time::SystemTime::now();
-    let duration = now
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default();
+    let now = CLOCK
+        .with(|cell| {
+            cell.borrow()
+                .as_ref()
+                .map(|clock| clock.current_time_wall_clock())
+        })
+        .unwrap_or_else(WallClockInstant::now);
+    let unix_ms = now.secs * 1000 + (now.micros / 1000) as i64;
     const UNIX_EPOCH_IJD: i64 = 210866760000000;
-    p.i_jd = UNIX_EPOCH_IJD + duration.as_millis() as i64;
+    p.i_jd = UNIX_EPOCH_IJD + unix_ms;
     p.valid_jd = true;
     p.is_utc = true;
     p.is_local = false;
@@ -3206,4 +3232,55 @@ mod tests {
         ];
         let _ = exec_strftime(args2.iter());
     }
+
+    #[test]
+    fn test_now_follows_the_injected_clock() {
+        struct FixedClock {
+            now: WallClockInstant,
+            reads: std::cell::Cell<usize>,
+        }
+        impl Clock for FixedClock {
+            fn current_time_monotonic(&self) -> crate::io::clock::MonotonicInstant {
+                crate::io::clock::MonotonicInstant::now()
+            }
+            fn current_time_wall_clock(&self) -> WallClockInstant {
+                self.reads.set(self.reads.get() + 1);
+                self.now
+            }
+        }
+
+        let clock = Arc::new(FixedClock {
+            now: WallClockInstant {
+                secs: 1_700_000_000,
+                micros: 250_000,
+            },
+            reads: std::cell::Cell::new(0),
+        });
+        let args = &[Value::build_text("now")];
+        with_clock(clock.clone(), || {
+            assert_eq!(
+                exec_datetime_full(args.iter()),
+                Value::build_text("2023-11-14 22:13:20")
+            );
+            assert_eq!(exec_unixepoch(args.iter()), Value::from_i64(1_700_000_000));
+            assert_eq!(
+                exec_time::<&[_; 0], std::slice::Iter<'_, Value>, &Value>(&[]),
+                Value::build_text("22:13:20")
+            );
+        });
+        assert_eq!(clock.reads.get(), 3);
+        // A date that doesn't refer to 'now' doesn't read the clock
+        with_clock(clock.clone(), || {
+            assert_eq!(
+                exec_date([Value::build_text("2024-02-29")].iter()),
+                Value::build_text("2024-02-29")
+            );
+        });
+        assert_eq!(clock.reads.get(), 3);
+        // Outside of the scope 'now' is the system time again
+        let Value::Numeric(Numeric::Integer(epoch)) = exec_unixepoch(args.iter()) else {
+            panic!("unixepoch('now') should be an integer");
+        };
+        assert!(epoch > 1_700_000_000);
+    }
 }
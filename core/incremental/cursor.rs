@@ -403,6 +403,9 @@ mod tests {
                 enable_without_rowid: false,
                 enable_experimental_mvcc_passive_checkpoint: false,
                 unsafe_testing: false,
+                page_cache_policy: Default::default(),
+                disable_direct_io: false,
+                enable_page_checksums: false,
             },
             None,
             Arc::new(SqliteDialect),
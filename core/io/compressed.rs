@@ -0,0 +1,911 @@
+//! Page compression layer for database files.
+//!
+//! [CompressedFile] wraps the [File] of a database and stores every page as a
+//! variable-size frame appended to the underlying file: a small header (page
+//! number, codec, payload length, checksum) followed by the page as compressed
+//! by a [PageCodec]. An in-memory frame map translates the page-aligned offsets
+//! the pager reads and writes into those frames, and is rebuilt on open by
+//! scanning the file.
+//!
+//! Rewriting a page appends a new frame and leaves the superseded one in
+//! place, so the layer is meant for read-mostly, cold-storage databases; the
+//! space is only reclaimed by copying the database into a new file. Pages that
+//! don't shrink are stored as is.
+//!
+//! [CompressedIO] applies the layer to every database file opened through
+//! another [IO] backend, so it can be put in front of any VFS.
+
+use super::{
+    is_temp_file, Buffer, Clock, Completion, File, FileId, FileSyncType, LockLevel, OpenFlags, IO,
+};
+use crate::error::CompletionError;
+use crate::io::clock::{MonotonicInstant, WallClockInstant};
+use crate::sync::RwLock;
+use crate::{LimboError, Result};
+use std::hash::Hasher;
+use std::sync::Arc;
+
+/// A compression algorithm for page payloads.
+pub trait PageCodec: Send + Sync {
+    /// Identifies the codec in every frame it writes, so that a file is never
+    /// decoded with a different codec than the one that wrote it. 0 is
+    /// reserved for pages stored uncompressed.
+    fn id(&self) -> u8;
+    fn compress(&self, page: &[u8]) -> Vec<u8>;
+    /// Decompress `data` into `out`, which is exactly one page long.
+    fn decompress(&self, data: &[u8], out: &mut [u8]) -> Result<()>;
+}
+
+/// LZ4 block compression: fast enough that reads stay cheap.
+#[cfg(feature = "compression")]
+pub struct Lz4Codec;
+
+#[cfg(feature = "compression")]
+impl PageCodec for Lz4Codec {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn compress(&self, page: &[u8]) -> Vec<u8> {
+        lz4_flex::block::compress(page)
+    }
+
+    fn decompress(&self, data: &[u8], out: &mut [u8]) -> Result<()> {
+        match lz4_flex::block::decompress_into(data, out) {
+            Ok(n) if n == out.len() => Ok(()),
+            Ok(n) => Err(LimboError::Corrupt(format!(
+                "lz4 page decompressed to {n} bytes, expected {}",
+                out.len()
+            ))),
+            Err(e) => Err(LimboError::Corrupt(format!("lz4 page: {e}"))),
+        }
+    }
+}
+
+const FRAME_MAGIC: u32 = 0x7470_7a31;
+const FRAME_HEADER_SIZE: usize = 28;
+/// Bytes of the header covered by the checksum, i.e. all but the checksum.
+const FRAME_HEADER_CHECKSUMMED: usize = 20;
+const FRAME_KIND_PAGE: u8 = 0;
+/// Drops every page from `page_no` on.
+const FRAME_KIND_TRUNCATE: u8 = 1;
+const CODEC_STORED: u8 = 0;
+
+/// Frame header, little endian:
+/// magic (4) | kind (1) | codec (1) | unused (2) | page_no (4) | page_size (4) | len (4) | checksum (8)
+struct FrameHeader {
+    kind: u8,
+    codec: u8,
+    /// Zero-based page number, or the new page count of a truncate frame.
+    page_no: u32,
+    page_size: u32,
+    /// Payload length.
+    len: u32,
+    checksum: u64,
+}
+
+impl FrameHeader {
+    fn encode(&self) -> [u8; FRAME_HEADER_SIZE] {
+        let mut out = [0; FRAME_HEADER_SIZE];
+        out[0..4].copy_from_slice(&FRAME_MAGIC.to_le_bytes());
+        out[4] = self.kind;
+        out[5] = self.codec;
+        out[8..12].copy_from_slice(&self.page_no.to_le_bytes());
+        out[12..16].copy_from_slice(&self.page_size.to_le_bytes());
+        out[16..20].copy_from_slice(&self.len.to_le_bytes());
+        out[20..28].copy_from_slice(&self.checksum.to_le_bytes());
+        out
+    }
+
+    /// `None` if `bytes` doesn't start with a frame header.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < FRAME_HEADER_SIZE {
+            return None;
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        if u32_at(0) != FRAME_MAGIC {
+            return None;
+        }
+        Some(Self {
+            kind: bytes[4],
+            codec: bytes[5],
+            page_no: u32_at(8),
+            page_size: u32_at(12),
+            len: u32_at(16),
+            checksum: u64::from_le_bytes(bytes[20..28].try_into().unwrap()),
+        })
+    }
+
+    /// Build a whole frame, header and payload.
+    fn frame(kind: u8, codec: u8, page_no: u32, page_size: usize, payload: &[u8]) -> Vec<u8> {
+        let mut header = Self {
+            kind,
+            codec,
+            page_no,
+            page_size: page_size as u32,
+            len: payload.len() as u32,
+            checksum: 0,
+        }
+        .encode();
+        let checksum = frame_checksum(&header, payload);
+        header[20..28].copy_from_slice(&checksum.to_le_bytes());
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&header);
+        frame.extend_from_slice(payload);
+        frame
+    }
+}
+
+fn frame_checksum(header: &[u8], payload: &[u8]) -> u64 {
+    let mut hasher = twox_hash::XxHash64::with_seed(0);
+    hasher.write(&header[..FRAME_HEADER_CHECKSUMMED]);
+    hasher.write(payload);
+    hasher.finish()
+}
+
+fn is_valid_page_size(page_size: usize) -> bool {
+    (512..=65536).contains(&page_size) && page_size.is_power_of_two()
+}
+
+/// Decode the page stored in `frame`, a whole frame as read from disk.
+fn decode_page(codec: &dyn PageCodec, frame: &[u8], page_size: usize) -> Result<Vec<u8>> {
+    let header = FrameHeader::decode(frame)
+        .ok_or_else(|| LimboError::Corrupt("compressed frame has no header".into()))?;
+    let payload = &frame[FRAME_HEADER_SIZE..];
+    if header.kind != FRAME_KIND_PAGE
+        || header.page_size as usize != page_size
+        || header.len as usize != payload.len()
+        || header.checksum != frame_checksum(frame, payload)
+    {
+        return Err(LimboError::Corrupt(format!(
+            "compressed frame of page {} doesn't match its header",
+            header.page_no
+        )));
+    }
+    let mut page = vec![0; page_size];
+    match header.codec {
+        CODEC_STORED if payload.len() == page_size => page.copy_from_slice(payload),
+        id if id != CODEC_STORED && id == codec.id() => codec.decompress(payload, &mut page)?,
+        id => {
+            return Err(LimboError::Corrupt(format!(
+                "compressed frame of page {} uses codec {id}",
+                header.page_no
+            )))
+        }
+    }
+    Ok(page)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// Offset of the frame header in the underlying file.
+    offset: u64,
+    len: u32,
+}
+
+#[derive(Default)]
+struct FrameMap {
+    /// Fixed by the first page written.
+    page_size: Option<usize>,
+    /// Frame of every page, `None` for pages never written (they read as zeros).
+    pages: Vec<Option<Frame>>,
+    /// Where the next frame goes. Advanced when an append is issued, so
+    /// concurrent appends get disjoint ranges.
+    end: u64,
+    /// Set when an append failed after later ones were placed past it. The
+    /// open scan stops at the hole it left, so those frames would be lost
+    /// on reopen, and every further write is refused.
+    poisoned: bool,
+}
+
+impl FrameMap {
+    fn set(&mut self, page_no: usize, frame: Frame) {
+        if self.pages.len() <= page_no {
+            self.pages.resize(page_no + 1, None);
+        }
+        // Frames are placed in file order; the later one is the newer page
+        // even if its write completed first.
+        if self.pages[page_no].is_none_or(|old| old.offset < frame.offset) {
+            self.pages[page_no] = Some(frame);
+        }
+    }
+
+    /// Reserve `len` bytes at the end of the file for a frame.
+    fn reserve(&mut self, len: u64) -> Result<u64> {
+        if self.poisoned {
+            return Err(LimboError::InternalError(
+                "compressed file refuses writes after a failed append".to_string(),
+            ));
+        }
+        let offset = self.end;
+        self.end += len;
+        Ok(offset)
+    }
+
+    /// Give back the range of a failed append. Only the last one can be
+    /// rewound; anything placed after it would sit past a hole.
+    fn release(&mut self, offset: u64, len: u64) {
+        if self.end == offset + len {
+            self.end = offset;
+        } else {
+            self.poisoned = true;
+        }
+    }
+}
+
+/// Space used by a [CompressedFile], see [CompressedFile::stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedFileStats {
+    pub pages: usize,
+    /// Size the pages would take uncompressed.
+    pub logical_bytes: u64,
+    /// Size of the frames holding the current pages, headers included.
+    pub live_bytes: u64,
+    /// Size of the underlying file, superseded frames included.
+    pub file_bytes: u64,
+}
+
+pub struct CompressedFile {
+    file: Arc<dyn File>,
+    codec: Arc<dyn PageCodec>,
+    map: Arc<RwLock<FrameMap>>,
+}
+
+crate::assert::assert_sync!(CompressedFile);
+
+impl CompressedFile {
+    /// Wrap `file`, rebuilding the frame map from the frames it already
+    /// holds. The scan stops at the first frame that is torn or fails its
+    /// checksum, and new frames overwrite it.
+    pub fn open(io: &Arc<dyn IO>, file: Arc<dyn File>, codec: Arc<dyn PageCodec>) -> Result<Self> {
+        let file_size = file.size()?;
+        let read = |pos: u64, len: usize| -> Result<Arc<Buffer>> {
+            let buf = Arc::new(Buffer::new_temporary(len));
+            let c = file.pread(pos, Completion::new_read(buf.clone(), |_| None))?;
+            io.wait_for_completion(c)?;
+            Ok(buf)
+        };
+        let mut map = FrameMap::default();
+        while map.end + FRAME_HEADER_SIZE as u64 <= file_size {
+            let Some(header) = FrameHeader::decode(read(map.end, FRAME_HEADER_SIZE)?.as_slice())
+            else {
+                break;
+            };
+            let len = FRAME_HEADER_SIZE + header.len as usize;
+            let page_size = header.page_size as usize;
+            if map.end + len as u64 > file_size
+                || !is_valid_page_size(page_size)
+                || map.page_size.is_some_and(|s| s != page_size)
+            {
+                break;
+            }
+            let frame = read(map.end, len)?;
+            let frame = frame.as_slice();
+            if header.checksum != frame_checksum(frame, &frame[FRAME_HEADER_SIZE..]) {
+                break;
+            }
+            map.page_size = Some(page_size);
+            match header.kind {
+                FRAME_KIND_PAGE => map.set(
+                    header.page_no as usize,
+                    Frame {
+                        offset: map.end,
+                        len: len as u32,
+                    },
+                ),
+                FRAME_KIND_TRUNCATE => map.pages.truncate(header.page_no as usize),
+                _ => break,
+            }
+            map.end += len as u64;
+        }
+        tracing::debug!(
+            "CompressedFile::open: {} pages in {} of {} bytes",
+            map.pages.len(),
+            map.end,
+            file_size
+        );
+        Ok(Self {
+            file,
+            codec,
+            map: Arc::new(RwLock::new(map)),
+        })
+    }
+
+    pub fn stats(&self) -> CompressedFileStats {
+        let map = self.map.read();
+        let pages = map.pages.iter().flatten();
+        CompressedFileStats {
+            pages: pages.clone().count(),
+            logical_bytes: (pages.clone().count() * map.page_size.unwrap_or(0)) as u64,
+            live_bytes: pages.map(|frame| frame.len as u64).sum(),
+            file_bytes: map.end,
+        }
+    }
+
+    /// Append a frame per buffer, each a whole page starting at `pos`.
+    fn write_pages(&self, pos: u64, buffers: &[Arc<Buffer>], c: Completion) -> Result<Completion> {
+        if buffers.is_empty() {
+            c.complete(0);
+            return Ok(c);
+        }
+        let mut map = self.map.write();
+        let page_size = match map.page_size {
+            Some(page_size) => page_size,
+            None => {
+                let page_size = buffers[0].len();
+                if !is_valid_page_size(page_size) {
+                    return Err(LimboError::InternalError(format!(
+                        "compressed file page size must be a power of two between 512 and 65536, got {page_size}"
+                    )));
+                }
+                map.page_size = Some(page_size);
+                page_size
+            }
+        };
+        if pos % page_size as u64 != 0 || buffers.iter().any(|b| b.len() != page_size) {
+            return Err(LimboError::InternalError(format!(
+                "compressed file writes must be whole {page_size}-byte pages"
+            )));
+        }
+        let first_page = (pos / page_size as u64) as usize;
+        let mut data = Vec::new();
+        let mut frames = Vec::with_capacity(buffers.len());
+        for (i, buffer) in buffers.iter().enumerate() {
+            let page = buffer.as_slice();
+            let compressed = self.codec.compress(page);
+            let (codec, payload) = if compressed.len() < page_size {
+                (self.codec.id(), compressed.as_slice())
+            } else {
+                (CODEC_STORED, page)
+            };
+            let frame = FrameHeader::frame(
+                FRAME_KIND_PAGE,
+                codec,
+                (first_page + i) as u32,
+                page_size,
+                payload,
+            );
+            frames.push((
+                first_page + i,
+                Frame {
+                    offset: map.end + data.len() as u64,
+                    len: frame.len() as u32,
+                },
+            ));
+            data.extend_from_slice(&frame);
+        }
+        let offset = map.reserve(data.len() as u64)?;
+        drop(map);
+
+        let written = (buffers.len() * page_size) as i32;
+        self.append(
+            offset,
+            data,
+            move |map| {
+                for (page_no, frame) in &frames {
+                    map.set(*page_no, *frame);
+                }
+            },
+            c.clone(),
+            written,
+        )?;
+        Ok(c)
+    }
+
+    /// Write `frames` at `offset`, reserved with [FrameMap::reserve]. On
+    /// success `apply` updates the map and `c` completes with `result`; on
+    /// failure the range is released and `c` fails.
+    fn append(
+        &self,
+        offset: u64,
+        frames: Vec<u8>,
+        apply: impl Fn(&mut FrameMap) + Send + Sync + 'static,
+        c: Completion,
+        result: i32,
+    ) -> Result<()> {
+        let len = frames.len() as u64;
+        let frame_map = self.map.clone();
+        let append_c = Completion::new_write(move |res| match res {
+            Ok(n) if n as u64 == len => {
+                apply(&mut frame_map.write());
+                c.complete(result);
+            }
+            Ok(_) => {
+                frame_map.write().release(offset, len);
+                c.error(CompletionError::ShortWrite);
+            }
+            Err(err) => {
+                frame_map.write().release(offset, len);
+                c.error(err);
+            }
+        });
+        if let Err(err) = self
+            .file
+            .pwrite(offset, Arc::new(Buffer::new(frames)), append_c)
+        {
+            self.map.write().release(offset, len);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl File for CompressedFile {
+    fn lock_file(&self, exclusive: bool) -> Result<()> {
+        self.file.lock_file(exclusive)
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        self.file.unlock_file()
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        self.file.lock_level(level)
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        self.file.unlock_level(level)
+    }
+
+    fn check_reserved_lock(&self) -> Result<bool> {
+        self.file.check_reserved_lock()
+    }
+
+    /// Reads must stay within one page: the pager reads whole pages and the
+    /// database header.
+    fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        let map = self.map.read();
+        let read_len = c.as_read().buf().len();
+        let Some(page_size) = map.page_size else {
+            c.complete(0);
+            return Ok(c);
+        };
+        let page_no = (pos / page_size as u64) as usize;
+        let in_page = (pos % page_size as u64) as usize;
+        if in_page + read_len > page_size {
+            return Err(LimboError::InternalError(format!(
+                "compressed file reads must not cross a page boundary (pos={pos}, len={read_len})"
+            )));
+        }
+        let Some(slot) = map.pages.get(page_no) else {
+            c.complete(0);
+            return Ok(c);
+        };
+        let Some(frame) = *slot else {
+            c.as_read().buf().as_mut_slice().fill(0);
+            c.complete(read_len as i32);
+            return Ok(c);
+        };
+        drop(map);
+
+        let codec = self.codec.clone();
+        let original_c = c.clone();
+        let frame_buf = Arc::new(Buffer::new_temporary(frame.len as usize));
+        let decode_complete =
+            move |res: std::result::Result<(Arc<Buffer>, i32), CompletionError>| {
+                let (buf, bytes_read) = match res {
+                    Ok(res) => res,
+                    Err(err) => {
+                        original_c.error(err);
+                        return original_c.get_error();
+                    }
+                };
+                if bytes_read as usize != buf.len() {
+                    original_c.error(CompletionError::ShortRead {
+                        page_idx: page_no + 1,
+                        expected: buf.len(),
+                        actual: bytes_read.max(0) as usize,
+                    });
+                    return original_c.get_error();
+                }
+                match decode_page(&*codec, buf.as_slice(), page_size) {
+                    Ok(page) => {
+                        original_c
+                            .as_read()
+                            .buf()
+                            .as_mut_slice()
+                            .copy_from_slice(&page[in_page..in_page + read_len]);
+                        original_c.complete(read_len as i32);
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to decode compressed page {}: {e}", page_no + 1);
+                        original_c.error(CompletionError::CorruptCompressedFrame {
+                            page_idx: page_no + 1,
+                        });
+                    }
+                }
+                original_c.get_error()
+            };
+        self.file.pread(
+            frame.offset,
+            Completion::new_read(frame_buf, decode_complete),
+        )?;
+        Ok(c)
+    }
+
+    fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion> {
+        self.write_pages(pos, &[buffer], c)
+    }
+
+    fn pwritev(&self, pos: u64, buffers: Vec<Arc<Buffer>>, c: Completion) -> Result<Completion> {
+        self.write_pages(pos, &buffers, c)
+    }
+
+    fn sync(&self, c: Completion, sync_type: FileSyncType) -> Result<Completion> {
+        self.file.sync(c, sync_type)
+    }
+
+    fn size(&self) -> Result<u64> {
+        let map = self.map.read();
+        Ok((map.pages.len() * map.page_size.unwrap_or(0)) as u64)
+    }
+
+    /// Truncating to zero empties the underlying file; any other length
+    /// appends a frame dropping the pages past it.
+    fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
+        let mut map = self.map.write();
+        if len == 0 {
+            *map = FrameMap::default();
+            return self.file.truncate(0, c);
+        }
+        let Some(page_size) = map.page_size else {
+            c.complete(0);
+            return Ok(c);
+        };
+        if len % page_size as u64 != 0 {
+            return Err(LimboError::InternalError(format!(
+                "compressed file can only be truncated to whole {page_size}-byte pages"
+            )));
+        }
+        let page_count = (len / page_size as u64) as usize;
+        let frame = FrameHeader::frame(
+            FRAME_KIND_TRUNCATE,
+            CODEC_STORED,
+            page_count as u32,
+            page_size,
+            &[],
+        );
+        let offset = map.reserve(frame.len() as u64)?;
+        drop(map);
+
+        self.append(
+            offset,
+            frame,
+            move |map| map.pages.truncate(page_count),
+            c.clone(),
+            0,
+        )?;
+        Ok(c)
+    }
+}
+
+/// Suffixes of the files kept next to a database. They aren't made of whole
+/// pages, so [CompressedIO] leaves them alone.
+pub(super) const AUXILIARY_FILE_SUFFIXES: [&str; 5] = ["-wal", "-journal", "-log", "-shm", "-tshm"];
+
+/// [IO] backend storing the database files of another one as
+/// [CompressedFile]s. WAL, journal and other auxiliary files pass through
+/// unchanged: pages are compressed once they are checkpointed into the
+/// database file. So do temp files, which aren't written in whole pages.
+///
+/// Every open rebuilds the frame map of the file, so a database file must
+/// only be opened once at a time.
+pub struct CompressedIO {
+    inner: Arc<dyn IO>,
+    codec: Arc<dyn PageCodec>,
+}
+
+impl CompressedIO {
+    pub fn new(inner: Arc<dyn IO>, codec: Arc<dyn PageCodec>) -> Self {
+        tracing::debug!("Using IO backend 'compressed'");
+        Self { inner, codec }
+    }
+}
+
+impl Clock for CompressedIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        self.inner.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        self.inner.current_time_wall_clock()
+    }
+}
+
+impl IO for CompressedIO {
+    /// `direct` is only passed on for auxiliary files: frames aren't aligned.
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        if is_temp_file(path)
+            || AUXILIARY_FILE_SUFFIXES
+                .iter()
+                .any(|suffix| path.ends_with(suffix))
+        {
+            return self.inner.open_file(path, flags, direct);
+        }
+        let file = self.inner.open_file(path, flags, false)?;
+        Ok(Arc::new(CompressedFile::open(
+            &self.inner,
+            file,
+            self.codec.clone(),
+        )?))
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn step(&self) -> Result<()> {
+        self.inner.step()
+    }
+
+    fn generate_random_number(&self) -> i64 {
+        self.inner.generate_random_number()
+    }
+
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest)
+    }
+
+    fn get_memory_io(&self) -> Arc<super::MemoryIO> {
+        self.inner.get_memory_io()
+    }
+
+    fn yield_now(&self) {
+        self.inner.yield_now()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) {
+        self.inner.sleep(duration)
+    }
+
+    fn file_id(&self, path: &str) -> Result<FileId> {
+        self.inner.file_id(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemoryIO;
+    use crate::storage::database::DatabaseFile;
+    use crate::{CheckpointMode, Database, OpenOptions, SqliteDialect, Value};
+
+    /// Run-length encoding, as (count, byte) pairs.
+    struct RleCodec;
+
+    impl PageCodec for RleCodec {
+        fn id(&self) -> u8 {
+            0x7f
+        }
+
+        fn compress(&self, page: &[u8]) -> Vec<u8> {
+            let mut out = Vec::new();
+            for run in page.chunk_by(|a, b| a == b) {
+                for chunk in run.chunks(255) {
+                    out.extend_from_slice(&[chunk.len() as u8, chunk[0]]);
+                }
+            }
+            out
+        }
+
+        fn decompress(&self, data: &[u8], out: &mut [u8]) -> Result<()> {
+            let mut at = 0;
+            for pair in data.chunks_exact(2) {
+                let end = at + pair[0] as usize;
+                out.get_mut(at..end)
+                    .ok_or_else(|| LimboError::Corrupt("rle overflow".into()))?
+                    .fill(pair[1]);
+                at = end;
+            }
+            Ok(())
+        }
+    }
+
+    fn open_compressed(io: &Arc<dyn IO>, inner: &Arc<dyn File>) -> Arc<CompressedFile> {
+        Arc::new(CompressedFile::open(io, inner.clone(), Arc::new(RleCodec)).unwrap())
+    }
+
+    fn read(file: &CompressedFile, pos: u64, len: usize) -> Vec<u8> {
+        let buffer = Arc::new(Buffer::new_temporary(len));
+        let c = Completion::new_read(buffer.clone(), |_| None);
+        file.pread(pos, c).unwrap();
+        buffer.as_slice().to_vec()
+    }
+
+    fn write(file: &CompressedFile, pos: u64, data: Vec<u8>) {
+        let c = Completion::new_write(|_| {});
+        file.pwrite(pos, Arc::new(Buffer::new(data)), c).unwrap();
+    }
+
+    #[test]
+    fn test_frames_survive_reopen_and_torn_tail() {
+        let io: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let inner = io.open_file("cold.db", OpenFlags::Create, false).unwrap();
+        let file = open_compressed(&io, &inner);
+        write(&file, 0, vec![1; 4096]);
+        write(&file, 8192, (0..4096).map(|i| i as u8).collect());
+        write(&file, 0, vec![2; 4096]);
+        assert_eq!(file.size().unwrap(), 3 * 4096);
+        // Never written pages read as zeros.
+        assert_eq!(read(&file, 4096, 4), vec![0; 4]);
+        assert!(file
+            .pread(
+                4000,
+                Completion::new_read(Arc::new(Buffer::new_temporary(200)), |_| None)
+            )
+            .is_err());
+
+        let stats = file.stats();
+        assert_eq!(stats.pages, 2);
+        // The incompressible page is stored as is, the other in a few bytes.
+        assert!(stats.live_bytes < 4096 + 2 * FRAME_HEADER_SIZE as u64 + 64);
+        assert!(stats.file_bytes > stats.live_bytes);
+
+        // A torn frame at the end is ignored and overwritten.
+        let torn = FrameHeader::frame(FRAME_KIND_PAGE, CODEC_STORED, 0, 4096, &[9; 4096]);
+        let c = Completion::new_write(|_| {});
+        inner
+            .pwrite(
+                stats.file_bytes,
+                Arc::new(Buffer::new(torn[..100].to_vec())),
+                c,
+            )
+            .unwrap();
+        let file = open_compressed(&io, &inner);
+        assert_eq!(file.stats(), stats);
+        assert_eq!(read(&file, 0, 100), vec![2; 100]);
+        assert_eq!(read(&file, 8192 + 250, 3), vec![250, 251, 252]);
+
+        file.truncate(4096, Completion::new_trunc(|_| {})).unwrap();
+        let file = open_compressed(&io, &inner);
+        assert_eq!(file.size().unwrap(), 4096);
+    }
+
+    #[cfg(feature = "io_fault")]
+    #[test]
+    fn test_failed_append_is_rewound() {
+        use crate::io::{Fault, FaultIO, FaultOp, FaultSchedule};
+
+        let schedule = FaultSchedule::new()
+            .on(FaultOp::Write, 2, Fault::TornWrite(10))
+            .on(FaultOp::Write, 4, Fault::Error);
+        let io: Arc<dyn IO> = Arc::new(FaultIO::new(Arc::new(MemoryIO::new()), schedule));
+        let inner = io.open_file("cold.db", OpenFlags::Create, false).unwrap();
+        let file = open_compressed(&io, &inner);
+        let wait = |c: Result<Completion>| io.wait_for_completion(c.unwrap());
+        let page = |byte: u8| Arc::new(Buffer::new(vec![byte; 4096]));
+        write(&file, 0, vec![1; 4096]);
+        let end = file.stats().file_bytes;
+        assert!(wait(file.pwrite(4096, page(2), Completion::new_write(|_| {}))).is_err());
+        assert_eq!(file.stats().file_bytes, end);
+        write(&file, 8192, vec![3; 4096]);
+        assert!(wait(file.truncate(4096, Completion::new_trunc(|_| {}))).is_err());
+
+        // The frames after the failed append are found again on reopen.
+        let stats = file.stats();
+        let file = open_compressed(&io, &inner);
+        assert_eq!(file.stats(), stats);
+        assert_eq!(file.size().unwrap(), 3 * 4096);
+        assert_eq!(read(&file, 4096, 4), vec![0; 4]);
+        assert_eq!(read(&file, 8192, 4), vec![3; 4]);
+    }
+
+    #[test]
+    fn test_failed_append_before_another_poisons() {
+        let mut map = FrameMap::default();
+        let first = map.reserve(100).unwrap();
+        map.reserve(100).unwrap();
+        map.release(first, 100);
+        assert!(map.poisoned);
+        assert!(map.reserve(100).is_err());
+    }
+
+    #[test]
+    fn test_database_on_compressed_file() {
+        let io: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let inner = io.open_file("cold.db", OpenFlags::Create, false).unwrap();
+        let open = |file: Arc<CompressedFile>| {
+            Database::open(
+                io.clone(),
+                "cold.db",
+                OpenOptions::new(Arc::new(SqliteDialect))
+                    .storage(Arc::new(DatabaseFile::new(file))),
+            )
+            .unwrap()
+        };
+
+        let file = open_compressed(&io, &inner);
+        let db = open(file.clone());
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT zeroblob(500) FROM generate_series(1, 100)")
+            .unwrap();
+        conn.checkpoint(CheckpointMode::Truncate {
+            upper_bound_inclusive: None,
+        })
+        .unwrap();
+        let stats = file.stats();
+        assert!(stats.pages > 10);
+        assert!(stats.live_bytes * 4 < stats.logical_bytes);
+        conn.close().unwrap();
+        drop(db);
+
+        let db = open(open_compressed(&io, &inner));
+        let conn = db.connect().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT count(*), sum(length(x)) FROM t")
+            .unwrap();
+        assert_eq!(
+            stmt.run_collect_rows().unwrap(),
+            vec![vec![Value::from_i64(100), Value::from_i64(50_000)]]
+        );
+    }
+
+    #[test]
+    fn test_compressed_io_leaves_wal_alone() {
+        let inner: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let io: Arc<dyn IO> = Arc::new(CompressedIO::new(inner.clone(), Arc::new(RleCodec)));
+        let db = Database::open_file(io.clone(), "cold.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT zeroblob(500) FROM generate_series(1, 100)")
+            .unwrap();
+        // Uncompressed in the WAL until checkpointed.
+        let wal = inner
+            .open_file("cold.db-wal", OpenFlags::None, false)
+            .unwrap();
+        assert!(wal.size().unwrap() > 10 * 4096);
+        conn.checkpoint(CheckpointMode::Truncate {
+            upper_bound_inclusive: None,
+        })
+        .unwrap();
+        let raw = inner.open_file("cold.db", OpenFlags::None, false).unwrap();
+        let compressed = open_compressed(&inner, &raw);
+        let stats = compressed.stats();
+        assert!(stats.pages > 10);
+        assert!(stats.file_bytes * 4 < stats.logical_bytes);
+        drop(compressed);
+        conn.close().unwrap();
+        drop(db);
+
+        let db = Database::open_file(io, "cold.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT count(*), sum(length(x)) FROM t")
+            .unwrap();
+        assert_eq!(
+            stmt.run_collect_rows().unwrap(),
+            vec![vec![Value::from_i64(100), Value::from_i64(50_000)]]
+        );
+    }
+
+    #[test]
+    fn test_compressed_io_leaves_temp_files_alone() {
+        let inner: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let io: Arc<dyn IO> = Arc::new(CompressedIO::new(inner, Arc::new(RleCodec)));
+        let db = Database::open_file(io, "cold.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        // The sorter buffer is sized like the page cache; shrinking it to the
+        // minimum makes the ORDER BY below spill to a temp file.
+        conn.execute("PRAGMA cache_size = -1").unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 2000)")
+            .unwrap();
+        let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x").unwrap();
+        let rows = stmt.run_collect_rows().unwrap();
+        assert_eq!(rows.len(), 2000);
+        assert!(rows.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_lz4_round_trip() {
+        let page: Vec<u8> = (0..4096).map(|i| (i / 64) as u8).collect();
+        let compressed = Lz4Codec.compress(&page);
+        assert!(compressed.len() < page.len());
+        let mut out = vec![0; 4096];
+        Lz4Codec.decompress(&compressed, &mut out).unwrap();
+        assert_eq!(out, page);
+        assert!(Lz4Codec.decompress(&compressed[..10], &mut out).is_err());
+    }
+}
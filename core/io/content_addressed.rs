@@ -0,0 +1,483 @@
+//! Experimental in-memory IO backend that stores file contents content-addressed.
+//!
+//! Every file is a manifest: a list of fixed-size block hashes plus a length.
+//! Blocks live once in a shared, reference-counted store, so files holding the
+//! same pages share them. Branching a file copies only its manifest, which
+//! makes snapshots and branches of a database cost O(manifest) instead of
+//! O(file); writes to either side afterwards copy just the blocks they touch.
+
+use super::{Buffer, Clock, Completion, File, FileId, OpenFlags, IO};
+use crate::error::CompletionError;
+use crate::io::clock::{DefaultClock, MonotonicInstant, WallClockInstant};
+use crate::io::FileSyncType;
+use crate::sync::{Mutex, RwLock};
+use crate::{LimboError, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Granularity of deduplication. Matches the default page size, so that each
+/// database page of a default-configured database is one block.
+pub const CONTENT_ADDRESSED_BLOCK_SIZE: usize = 4096;
+
+type BlockHash = u128;
+
+struct StoredBlock {
+    data: Arc<[u8]>,
+    refs: usize,
+}
+
+/// Blocks shared by every file of a [ContentAddressedIO].
+#[derive(Default)]
+struct BlockStore {
+    blocks: Mutex<HashMap<BlockHash, StoredBlock>>,
+}
+
+impl BlockStore {
+    /// Store `data` (or take another reference to an identical block) and return its hash.
+    fn put(&self, data: &[u8]) -> Result<BlockHash> {
+        let hash = twox_hash::XxHash3_128::oneshot(data);
+        let mut blocks = self.blocks.lock();
+        match blocks.get_mut(&hash) {
+            Some(block) if *block.data == *data => block.refs += 1,
+            Some(_) => {
+                return Err(LimboError::InternalError(format!(
+                    "content-addressed block hash collision on {hash:032x}"
+                )))
+            }
+            None => {
+                blocks.insert(
+                    hash,
+                    StoredBlock {
+                        data: data.into(),
+                        refs: 1,
+                    },
+                );
+            }
+        }
+        Ok(hash)
+    }
+
+    fn get(&self, hash: BlockHash) -> Arc<[u8]> {
+        self.blocks
+            .lock()
+            .get(&hash)
+            .expect("manifest references a block missing from the store")
+            .data
+            .clone()
+    }
+
+    fn retain(&self, hashes: impl Iterator<Item = BlockHash>) {
+        let mut blocks = self.blocks.lock();
+        for hash in hashes {
+            blocks
+                .get_mut(&hash)
+                .expect("manifest references a block missing from the store")
+                .refs += 1;
+        }
+    }
+
+    fn release(&self, hashes: impl Iterator<Item = BlockHash>) {
+        let mut blocks = self.blocks.lock();
+        for hash in hashes {
+            let block = blocks
+                .get_mut(&hash)
+                .expect("manifest references a block missing from the store");
+            block.refs -= 1;
+            if block.refs == 0 {
+                blocks.remove(&hash);
+            }
+        }
+    }
+}
+
+/// A file's contents: block hashes in file order (`None` for never-written
+/// blocks, which read as zeros) and the file length.
+#[derive(Clone, Default)]
+struct Manifest {
+    blocks: Vec<Option<BlockHash>>,
+    size: u64,
+}
+
+impl Manifest {
+    fn hashes(&self) -> impl Iterator<Item = BlockHash> + '_ {
+        self.blocks.iter().flatten().copied()
+    }
+}
+
+/// Space used by a [ContentAddressedIO], see [ContentAddressedIO::stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentAddressedStats {
+    pub files: usize,
+    /// Blocks referenced by all manifests together.
+    pub logical_blocks: usize,
+    /// Distinct blocks actually stored.
+    pub stored_blocks: usize,
+}
+
+pub struct ContentAddressedIO {
+    store: Arc<BlockStore>,
+    files: Mutex<HashMap<String, Arc<ContentAddressedFile>>>,
+}
+
+impl ContentAddressedIO {
+    pub fn new() -> Self {
+        debug!("Using IO backend 'content_addressed'");
+        Self {
+            store: Arc::new(BlockStore::default()),
+            files: Mutex::new(HashMap::default()),
+        }
+    }
+
+    pub fn stats(&self) -> ContentAddressedStats {
+        let files = self.files.lock();
+        ContentAddressedStats {
+            files: files.len(),
+            logical_blocks: files
+                .values()
+                .map(|file| file.manifest.read().hashes().count())
+                .sum(),
+            stored_blocks: self.store.blocks.lock().len(),
+        }
+    }
+}
+
+impl Default for ContentAddressedIO {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ContentAddressedIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        DefaultClock.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        DefaultClock.current_time_wall_clock()
+    }
+}
+
+impl IO for ContentAddressedIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, _direct: bool) -> Result<Arc<dyn File>> {
+        let mut files = self.files.lock();
+        if let Some(file) = files.get(path) {
+            return Ok(file.clone());
+        }
+        if !flags.contains(OpenFlags::Create) {
+            return Err(CompletionError::IOError(std::io::ErrorKind::NotFound, "open").into());
+        }
+        let file = Arc::new(ContentAddressedFile {
+            path: path.to_string(),
+            store: self.store.clone(),
+            manifest: RwLock::new(Manifest::default()),
+        });
+        files.insert(path.to_string(), file.clone());
+        Ok(file)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        if let Some(file) = self.files.lock().remove(path) {
+            let manifest = std::mem::take(&mut *file.manifest.write());
+            self.store.release(manifest.hashes());
+        }
+        Ok(())
+    }
+
+    /// Point `target` at the current contents of `source` without copying any
+    /// blocks. Files already open on `target` observe the new contents.
+    fn branch_file(&self, source: &str, target: &str) -> Result<()> {
+        if source == target {
+            return Ok(());
+        }
+        let mut files = self.files.lock();
+        let Some(source) = files.get(source).cloned() else {
+            return Err(CompletionError::IOError(std::io::ErrorKind::NotFound, "branch").into());
+        };
+        let manifest = source.manifest.read().clone();
+        self.store.retain(manifest.hashes());
+        debug!(
+            "branch_file(source={}, target={}): {} blocks",
+            source.path,
+            target,
+            manifest.blocks.len()
+        );
+        match files.get(target) {
+            Some(existing) => {
+                let previous = std::mem::replace(&mut *existing.manifest.write(), manifest);
+                self.store.release(previous.hashes());
+            }
+            None => {
+                files.insert(
+                    target.to_string(),
+                    Arc::new(ContentAddressedFile {
+                        path: target.to_string(),
+                        store: self.store.clone(),
+                        manifest: RwLock::new(manifest),
+                    }),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn file_id(&self, path: &str) -> Result<FileId> {
+        Ok(FileId::from_path_hash(path))
+    }
+}
+
+pub struct ContentAddressedFile {
+    path: String,
+    store: Arc<BlockStore>,
+    manifest: RwLock<Manifest>,
+}
+
+crate::assert::assert_sync!(ContentAddressedFile);
+
+impl ContentAddressedFile {
+    fn read_into(&self, pos: u64, buf: &Buffer) -> i32 {
+        let manifest = self.manifest.read();
+        if pos >= manifest.size || buf.len() == 0 {
+            return 0;
+        }
+        let read_len = (buf.len() as u64).min(manifest.size - pos) as usize;
+        let dst = buf.as_mut_slice();
+        let mut offset = pos as usize;
+        let mut read = 0;
+        while read < read_len {
+            let block_no = offset / CONTENT_ADDRESSED_BLOCK_SIZE;
+            let block_offset = offset % CONTENT_ADDRESSED_BLOCK_SIZE;
+            let len = (read_len - read).min(CONTENT_ADDRESSED_BLOCK_SIZE - block_offset);
+            let out = &mut dst[read..read + len];
+            match manifest.blocks.get(block_no).copied().flatten() {
+                Some(hash) => {
+                    out.copy_from_slice(&self.store.get(hash)[block_offset..block_offset + len])
+                }
+                None => out.fill(0),
+            }
+            offset += len;
+            read += len;
+        }
+        read_len as i32
+    }
+
+    /// Replace `manifest.blocks[block_no]` with a copy patched by `patch`.
+    fn rewrite_block(
+        &self,
+        manifest: &mut Manifest,
+        block_no: usize,
+        patch: impl FnOnce(&mut [u8]),
+    ) -> Result<()> {
+        if manifest.blocks.len() <= block_no {
+            manifest.blocks.resize(block_no + 1, None);
+        }
+        let mut block = [0u8; CONTENT_ADDRESSED_BLOCK_SIZE];
+        let previous = manifest.blocks[block_no];
+        if let Some(hash) = previous {
+            block.copy_from_slice(&self.store.get(hash));
+        }
+        patch(&mut block);
+        manifest.blocks[block_no] = Some(self.store.put(&block)?);
+        self.store.release(previous.into_iter());
+        Ok(())
+    }
+
+    fn write_at(&self, manifest: &mut Manifest, pos: u64, data: &[u8]) -> Result<usize> {
+        let mut offset = pos as usize;
+        let mut written = 0;
+        while written < data.len() {
+            let block_no = offset / CONTENT_ADDRESSED_BLOCK_SIZE;
+            let block_offset = offset % CONTENT_ADDRESSED_BLOCK_SIZE;
+            let len = (data.len() - written).min(CONTENT_ADDRESSED_BLOCK_SIZE - block_offset);
+            self.rewrite_block(manifest, block_no, |block| {
+                block[block_offset..block_offset + len]
+                    .copy_from_slice(&data[written..written + len])
+            })?;
+            offset += len;
+            written += len;
+        }
+        manifest.size = manifest.size.max(pos + data.len() as u64);
+        Ok(written)
+    }
+
+    fn truncate_to(&self, len: u64) -> Result<()> {
+        let mut manifest = self.manifest.write();
+        if len < manifest.size {
+            let keep = (len as usize).div_ceil(CONTENT_ADDRESSED_BLOCK_SIZE);
+            if keep < manifest.blocks.len() {
+                let dropped = manifest.blocks.split_off(keep);
+                self.store.release(dropped.into_iter().flatten());
+            }
+            // Zero the tail of a partially kept block so that growing the file
+            // again doesn't resurrect truncated bytes.
+            let tail = len as usize % CONTENT_ADDRESSED_BLOCK_SIZE;
+            if tail != 0 && manifest.blocks.get(keep - 1).copied().flatten().is_some() {
+                self.rewrite_block(&mut manifest, keep - 1, |block| block[tail..].fill(0))?;
+            }
+        }
+        manifest.size = len;
+        Ok(())
+    }
+}
+
+impl File for ContentAddressedFile {
+    fn lock_file(&self, _exclusive: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        tracing::debug!("pread(path={}): pos={}", self.path, pos);
+        let n = self.read_into(pos, c.as_read().buf());
+        c.complete(n);
+        Ok(c)
+    }
+
+    fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion> {
+        tracing::debug!(
+            "pwrite(path={}): pos={}, size={}",
+            self.path,
+            pos,
+            buffer.len()
+        );
+        let n = self.write_at(&mut self.manifest.write(), pos, buffer.as_slice())?;
+        c.complete(n as i32);
+        Ok(c)
+    }
+
+    fn pwritev(&self, pos: u64, buffers: Vec<Arc<Buffer>>, c: Completion) -> Result<Completion> {
+        tracing::debug!(
+            "pwritev(path={}): pos={}, buffers={:?}",
+            self.path,
+            pos,
+            buffers.iter().map(|x| x.len()).collect::<Vec<_>>()
+        );
+        // Apply all buffers under one manifest lock so readers never see a partial write.
+        let mut manifest = self.manifest.write();
+        let mut offset = pos;
+        for buffer in &buffers {
+            offset += self.write_at(&mut manifest, offset, buffer.as_slice())? as u64;
+        }
+        c.complete((offset - pos) as i32);
+        Ok(c)
+    }
+
+    fn sync(&self, c: Completion, _sync_type: FileSyncType) -> Result<Completion> {
+        tracing::debug!("sync(path={})", self.path);
+        // no-op
+        c.complete(0);
+        Ok(c)
+    }
+
+    fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
+        tracing::debug!("truncate(path={}): len={}", self.path, len);
+        self.truncate_to(len)?;
+        c.complete(0);
+        Ok(c)
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.manifest.read().size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, SqliteDialect, Value};
+
+    fn read(file: &Arc<dyn File>, pos: u64, len: usize) -> Vec<u8> {
+        let buffer = Arc::new(Buffer::new_temporary(len));
+        let c = Completion::new_read(buffer.clone(), |_| None);
+        file.pread(pos, c).unwrap();
+        buffer.as_slice().to_vec()
+    }
+
+    fn write(file: &Arc<dyn File>, pos: u64, data: &[u8]) {
+        let c = Completion::new_write(|_| {});
+        file.pwrite(pos, Arc::new(Buffer::new(data.to_vec())), c)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_identical_blocks_are_stored_once() {
+        let io = ContentAddressedIO::new();
+        let a = io.open_file("a", OpenFlags::Create, false).unwrap();
+        let b = io.open_file("b", OpenFlags::Create, false).unwrap();
+        write(&a, 0, &[7; CONTENT_ADDRESSED_BLOCK_SIZE * 2]);
+        write(&b, 0, &[7; CONTENT_ADDRESSED_BLOCK_SIZE]);
+        assert_eq!(
+            io.stats(),
+            ContentAddressedStats {
+                files: 2,
+                logical_blocks: 3,
+                stored_blocks: 1,
+            }
+        );
+
+        // An unaligned write spanning two blocks only changes those blocks.
+        write(&a, CONTENT_ADDRESSED_BLOCK_SIZE as u64 - 2, &[1, 2, 3, 4]);
+        assert_eq!(
+            read(&a, CONTENT_ADDRESSED_BLOCK_SIZE as u64 - 3, 6),
+            vec![7, 1, 2, 3, 4, 7]
+        );
+        assert_eq!(io.stats().stored_blocks, 3);
+
+        io.remove_file("a").unwrap();
+        assert_eq!(io.stats().stored_blocks, 1);
+    }
+
+    #[test]
+    fn test_branch_is_isolated_from_its_source() {
+        let io = ContentAddressedIO::new();
+        let source = io.open_file("source", OpenFlags::Create, false).unwrap();
+        write(&source, 0, b"hello");
+        io.branch_file("source", "branch").unwrap();
+        let branch = io.open_file("branch", OpenFlags::None, false).unwrap();
+        assert_eq!(io.stats().stored_blocks, 1);
+
+        write(&branch, 0, b"jelly");
+        assert_eq!(read(&source, 0, 5), b"hello");
+        assert_eq!(read(&branch, 0, 5), b"jelly");
+
+        source.truncate(2, Completion::new_trunc(|_| {})).unwrap();
+        write(&source, 4, b"!");
+        assert_eq!(read(&source, 0, 5), b"he\0\0!");
+        assert!(io.branch_file("missing", "other").is_err());
+    }
+
+    #[test]
+    fn test_attach_branch_of_database() {
+        let io: Arc<dyn IO> = Arc::new(ContentAddressedIO::new());
+        let db = Database::open_file_with_flags(
+            io,
+            "main.db",
+            OpenFlags::default(),
+            crate::DatabaseOpts::new().with_attach(true),
+            None,
+            Arc::new(SqliteDialect),
+        )
+        .unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t VALUES (1), (2)").unwrap();
+
+        conn.attach_branch("main", "dev.db", "dev").unwrap();
+        conn.execute("INSERT INTO dev.t VALUES (3)").unwrap();
+        conn.execute("DELETE FROM main.t WHERE x = 1").unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT (SELECT group_concat(x) FROM main.t), (SELECT group_concat(x) FROM dev.t)",
+            )
+            .unwrap();
+        let rows = stmt.run_collect_rows().unwrap();
+        assert_eq!(
+            rows,
+            vec![vec![Value::build_text("2"), Value::build_text("1,2,3")]]
+        );
+    }
+}
@@ -0,0 +1,692 @@
+//! Scripted fault injection over any IO backend.
+//!
+//! [FaultIO] wraps another [IO] and counts the reads, writes, syncs and
+//! truncates made through it. A [FaultSchedule] names the operations to
+//! interfere with by kind and position (the 3rd write, the 1st read of the
+//! WAL, ...) and what to do to them: fail with EIO, return a short read, tear
+//! a write, hold the operation back for a while, or cut the power. Faults are
+//! tied to operation counts rather than to randomness, so a schedule that
+//! breaks something breaks it the same way on every run.
+//!
+//! To simulate power loss the layer remembers what every write overwrote
+//! until the file is synced. Cutting the power rolls back every unsynced
+//! write, except that the first `torn_bytes` bytes of the last one to each
+//! file make it to disk, as on a drive that tore the page. Truncates are
+//! treated as durable right away. Files opened before the power loss fail
+//! every operation afterwards and must be opened again, as after a restart.
+
+use super::{Buffer, Clock, Completion, File, FileId, FileSyncType, LockLevel, OpenFlags, IO};
+use crate::error::CompletionError;
+use crate::io::clock::{MonotonicInstant, WallClockInstant};
+use crate::sync::atomic::{AtomicI32, Ordering};
+use crate::sync::Mutex;
+use crate::{LimboError, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Kind of operation a fault is scheduled for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultOp {
+    Read,
+    Write,
+    Sync,
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with an I/O error without performing the operation.
+    Error,
+    /// Read at most this many bytes. Only valid for reads.
+    ShortRead(usize),
+    /// Write only the first this many bytes, then fail with an I/O error.
+    /// Only valid for writes.
+    TornWrite(usize),
+    /// Perform the operation once this much time has passed on the clock of
+    /// the wrapped IO, which is checked whenever the IO is stepped.
+    Latency(Duration),
+    /// Cut the power instead of performing the operation, see
+    /// [FaultIO::power_loss].
+    PowerLoss { torn_bytes: usize },
+}
+
+#[derive(Debug, Clone)]
+struct ScheduledFault {
+    /// Suffixes of the paths of the files counted, any file if empty.
+    file_suffixes: Vec<String>,
+    ops: Vec<FaultOp>,
+    nth: u64,
+    fault: Fault,
+    seen: u64,
+}
+
+/// The operations a [FaultIO] interferes with.
+#[derive(Debug, Clone, Default)]
+pub struct FaultSchedule {
+    faults: Vec<ScheduledFault>,
+}
+
+impl FaultSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inject `fault` into the `nth` operation of kind `op` on any file,
+    /// counting from 1.
+    pub fn on(self, op: FaultOp, nth: u64, fault: Fault) -> Self {
+        self.on_files(&[], &[op], nth, fault)
+    }
+
+    /// Like [FaultSchedule::on], counting only the operations on files whose
+    /// path ends with `suffix`, e.g. `"-wal"`.
+    pub fn on_file(self, suffix: &str, op: FaultOp, nth: u64, fault: Fault) -> Self {
+        self.on_files(&[suffix], &[op], nth, fault)
+    }
+
+    /// Like [FaultSchedule::on_file], counting the operations of every kind
+    /// in `ops` together, on every file whose path ends with one of
+    /// `suffixes`: e.g. the 3rd write or sync of a database or of its WAL.
+    pub fn on_files(mut self, suffixes: &[&str], ops: &[FaultOp], nth: u64, fault: Fault) -> Self {
+        assert!(nth > 0, "operations are counted from 1");
+        assert!(
+            ops.iter().all(|op| *op == FaultOp::Read) || !matches!(fault, Fault::ShortRead(_)),
+            "short reads only apply to reads"
+        );
+        assert!(
+            ops.iter().all(|op| *op == FaultOp::Write) || !matches!(fault, Fault::TornWrite(_)),
+            "torn writes only apply to writes"
+        );
+        self.faults.push(ScheduledFault {
+            file_suffixes: suffixes.iter().map(|suffix| suffix.to_string()).collect(),
+            ops: ops.to_vec(),
+            nth,
+            fault,
+            seen: 0,
+        });
+        self
+    }
+
+    /// Whether any of the operations the faults are scheduled for came.
+    pub fn fired(&self) -> bool {
+        self.faults
+            .iter()
+            .any(|scheduled| scheduled.seen >= scheduled.nth)
+    }
+
+    /// Count an operation and return the fault scheduled for it, if any. When
+    /// several are, the first one added wins.
+    fn next(&mut self, path: &str, op: FaultOp) -> Option<Fault> {
+        let mut fault = None;
+        for scheduled in &mut self.faults {
+            let counted = scheduled.ops.contains(&op)
+                && (scheduled.file_suffixes.is_empty()
+                    || scheduled
+                        .file_suffixes
+                        .iter()
+                        .any(|suffix| path.ends_with(suffix.as_str())));
+            if !counted {
+                continue;
+            }
+            scheduled.seen += 1;
+            if scheduled.seen == scheduled.nth && fault.is_none() {
+                fault = Some(scheduled.fault);
+            }
+        }
+        fault
+    }
+}
+
+/// What has to be undone to a file if the power is cut.
+struct Journal {
+    /// Handle used to roll the file back.
+    file: Arc<dyn File>,
+    /// Size of the file when it was last synced.
+    synced_size: u64,
+    /// Position and previous contents of every unsynced write, oldest first.
+    undo: Vec<(u64, Vec<u8>)>,
+    /// Position and data of the last unsynced write.
+    last: Option<(u64, Vec<u8>)>,
+}
+
+impl Journal {
+    fn roll_back(self, io: &dyn IO, torn_bytes: usize) -> Result<()> {
+        let Journal {
+            file,
+            synced_size,
+            undo,
+            last,
+        } = self;
+        let write = |pos: u64, data: Vec<u8>| -> Result<()> {
+            if data.is_empty() {
+                return Ok(());
+            }
+            let c = file.pwrite(
+                pos,
+                Arc::new(Buffer::new(data)),
+                Completion::new_write(|_| {}),
+            )?;
+            io.wait_for_completion(c)
+        };
+        for (pos, before) in undo.into_iter().rev() {
+            write(pos, before)?;
+        }
+        let mut size = synced_size;
+        if let Some((pos, mut data)) = last {
+            data.truncate(torn_bytes);
+            size = size.max(pos + data.len() as u64);
+            write(pos, data)?;
+        }
+        if file.size()? > size {
+            let c = file.truncate(size, Completion::new_trunc(|_| {}))?;
+            io.wait_for_completion(c)?;
+        }
+        Ok(())
+    }
+}
+
+type DelayedOp = Box<dyn FnOnce() + Send>;
+
+struct State {
+    schedule: FaultSchedule,
+    journals: HashMap<String, Journal>,
+    delayed: Vec<(MonotonicInstant, DelayedOp)>,
+    /// Bumped by every power loss. Files opened in an earlier epoch are dead.
+    epoch: u64,
+}
+
+struct Shared {
+    inner: Arc<dyn IO>,
+    state: Mutex<State>,
+}
+
+/// [IO] backend injecting the faults of a [FaultSchedule] into another one.
+pub struct FaultIO {
+    shared: Arc<Shared>,
+}
+
+impl FaultIO {
+    pub fn new(inner: Arc<dyn IO>, schedule: FaultSchedule) -> Self {
+        tracing::debug!("Using IO backend 'fault'");
+        Self {
+            shared: Arc::new(Shared {
+                inner,
+                state: Mutex::new(State {
+                    schedule,
+                    journals: HashMap::new(),
+                    delayed: Vec::new(),
+                    epoch: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Replace the schedule, restarting the operation counts, and return the
+    /// old one. Handy to set a database up before faults start.
+    pub fn set_schedule(&self, schedule: FaultSchedule) -> FaultSchedule {
+        std::mem::replace(&mut self.shared.state.lock().schedule, schedule)
+    }
+
+    /// Cut the power: roll back every write that wasn't synced, except for
+    /// the first `torn_bytes` bytes of the last one to each file. Operations
+    /// held back by [Fault::Latency] are lost and never complete.
+    pub fn power_loss(&self, torn_bytes: usize) -> Result<()> {
+        self.shared.power_loss(torn_bytes)
+    }
+}
+
+impl Shared {
+    fn power_loss(&self, torn_bytes: usize) -> Result<()> {
+        tracing::debug!("simulating power loss, torn_bytes={torn_bytes}");
+        let (journals, delayed) = {
+            let mut state = self.state.lock();
+            state.epoch += 1;
+            let journals: Vec<Journal> = state.journals.drain().map(|(_, j)| j).collect();
+            (journals, std::mem::take(&mut state.delayed))
+        };
+        drop(delayed);
+        for journal in journals {
+            journal.roll_back(&*self.inner, torn_bytes)?;
+        }
+        Ok(())
+    }
+}
+
+impl Clock for FaultIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        self.shared.inner.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        self.shared.inner.current_time_wall_clock()
+    }
+}
+
+impl IO for FaultIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        let file = self.shared.inner.open_file(path, flags, direct)?;
+        let size = file.size()?;
+        let epoch = {
+            let mut state = self.shared.state.lock();
+            state
+                .journals
+                .entry(path.to_string())
+                .or_insert_with(|| Journal {
+                    file: file.clone(),
+                    synced_size: size,
+                    undo: Vec::new(),
+                    last: None,
+                });
+            state.epoch
+        };
+        Ok(Arc::new(FaultFile {
+            path: path.to_string(),
+            file,
+            shared: self.shared.clone(),
+            epoch,
+        }))
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.shared.inner.remove_file(path)?;
+        self.shared.state.lock().journals.remove(path);
+        Ok(())
+    }
+
+    /// Starts the operations held back by [Fault::Latency] whose time has
+    /// come, then steps the wrapped IO.
+    fn step(&self) -> Result<()> {
+        let now = self.shared.inner.current_time_monotonic();
+        let ready: Vec<_> = {
+            let mut state = self.shared.state.lock();
+            let (ready, pending) = std::mem::take(&mut state.delayed)
+                .into_iter()
+                .partition(|(at, _)| *at <= now);
+            state.delayed = pending;
+            ready
+        };
+        for (_, op) in ready {
+            op();
+        }
+        self.shared.inner.step()
+    }
+
+    fn generate_random_number(&self) -> i64 {
+        self.shared.inner.generate_random_number()
+    }
+
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.shared.inner.fill_bytes(dest)
+    }
+
+    fn get_memory_io(&self) -> Arc<super::MemoryIO> {
+        self.shared.inner.get_memory_io()
+    }
+
+    fn yield_now(&self) {
+        self.shared.inner.yield_now()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) {
+        self.shared.inner.sleep(duration)
+    }
+
+    fn file_id(&self, path: &str) -> Result<FileId> {
+        self.shared.inner.file_id(path)
+    }
+}
+
+#[derive(Clone)]
+struct FaultFile {
+    path: String,
+    file: Arc<dyn File>,
+    shared: Arc<Shared>,
+    epoch: u64,
+}
+
+impl FaultFile {
+    /// Count `op` and return the fault scheduled for it.
+    fn next_fault(&self, op: FaultOp) -> Result<Option<Fault>> {
+        let mut state = self.shared.state.lock();
+        if state.epoch != self.epoch {
+            return Err(LimboError::InternalError(format!(
+                "{} was opened before a simulated power loss",
+                self.path
+            )));
+        }
+        Ok(state.schedule.next(&self.path, op))
+    }
+
+    /// Apply `fault` to an operation, which `run` performs.
+    fn apply<F>(&self, fault: Option<Fault>, c: Completion, run: F) -> Result<Completion>
+    where
+        F: FnOnce(&FaultFile, Completion) -> Result<Completion> + Send + 'static,
+    {
+        match fault {
+            None | Some(Fault::ShortRead(_)) | Some(Fault::TornWrite(_)) => run(self, c),
+            Some(Fault::Error) => {
+                tracing::debug!("injecting I/O error into {}", self.path);
+                c.error(CompletionError::IOError(
+                    std::io::ErrorKind::Other,
+                    "injected fault",
+                ));
+                Ok(c)
+            }
+            Some(Fault::Latency(delay)) => {
+                let at = self.shared.inner.current_time_monotonic() + delay;
+                let file = self.clone();
+                let delayed_c = c.clone();
+                let op: DelayedOp = Box::new(move || {
+                    if let Err(e) = run(&file, delayed_c.clone()) {
+                        tracing::debug!("delayed operation on {} failed: {e}", file.path);
+                        delayed_c.error(CompletionError::IOError(
+                            std::io::ErrorKind::Other,
+                            "delayed operation failed",
+                        ));
+                    }
+                });
+                self.shared.state.lock().delayed.push((at, op));
+                Ok(c)
+            }
+            Some(Fault::PowerLoss { torn_bytes }) => {
+                self.shared.power_loss(torn_bytes)?;
+                Err(LimboError::InternalError(
+                    "simulated power loss".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn short_read(&self, pos: u64, c: Completion, n: usize) -> Result<Completion> {
+        let n = n.min(c.as_read().buf().len());
+        tracing::debug!("injecting short read of {n} bytes into {}", self.path);
+        if n == 0 {
+            c.complete(0);
+            return Ok(c);
+        }
+        let original_c = c.clone();
+        let short_c = Completion::new_read(Arc::new(Buffer::new_temporary(n)), move |res| {
+            match res {
+                Ok((buf, read)) => {
+                    let read = read.max(0) as usize;
+                    original_c.as_read().buf().as_mut_slice()[..read]
+                        .copy_from_slice(&buf.as_slice()[..read]);
+                    original_c.complete(read as i32);
+                }
+                Err(e) => original_c.error(e),
+            }
+            None
+        });
+        self.file.pread(pos, short_c)?;
+        Ok(c)
+    }
+
+    fn torn_write(
+        &self,
+        pos: u64,
+        buffers: Vec<Arc<Buffer>>,
+        c: Completion,
+        n: usize,
+    ) -> Result<Completion> {
+        let mut data: Vec<u8> = buffers.iter().flat_map(|b| b.as_slice()).copied().collect();
+        data.truncate(n);
+        tracing::debug!(
+            "injecting torn write of {} bytes into {}",
+            data.len(),
+            self.path
+        );
+        let failed_c = c.clone();
+        let fail = move || {
+            failed_c.error(CompletionError::IOError(
+                std::io::ErrorKind::Other,
+                "injected fault",
+            ))
+        };
+        if data.is_empty() {
+            fail();
+            return Ok(c);
+        }
+        self.write(
+            pos,
+            vec![Arc::new(Buffer::new(data))],
+            Completion::new_write(move |_| fail()),
+        )?;
+        Ok(c)
+    }
+
+    /// Read `len` bytes at `pos` from the wrapped file, waiting for them.
+    fn read_now(&self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let buf = Arc::new(Buffer::new_temporary(len));
+        let read = Arc::new(AtomicI32::new(0));
+        let c = {
+            let read = read.clone();
+            Completion::new_read(buf.clone(), move |res| {
+                if let Ok((_, n)) = res {
+                    read.store(n, Ordering::Release);
+                }
+                None
+            })
+        };
+        let c = self.file.pread(pos, c)?;
+        self.shared.inner.wait_for_completion(c)?;
+        let n = read.load(Ordering::Acquire).max(0) as usize;
+        Ok(buf.as_slice()[..n].to_vec())
+    }
+
+    /// Write through to the wrapped file, remembering what gets overwritten.
+    fn write(&self, pos: u64, mut buffers: Vec<Arc<Buffer>>, c: Completion) -> Result<Completion> {
+        let data: Vec<u8> = buffers.iter().flat_map(|b| b.as_slice()).copied().collect();
+        let before = self.read_now(pos, data.len())?;
+        if let Some(journal) = self.shared.state.lock().journals.get_mut(&self.path) {
+            journal.undo.push((pos, before));
+            journal.last = Some((pos, data));
+        }
+        if buffers.len() == 1 {
+            self.file.pwrite(pos, buffers.pop().unwrap(), c)
+        } else {
+            self.file.pwritev(pos, buffers, c)
+        }
+    }
+
+    /// Everything written so far survives a power loss.
+    fn make_durable(&self, size: u64) {
+        if let Some(journal) = self.shared.state.lock().journals.get_mut(&self.path) {
+            journal.synced_size = size;
+            journal.undo.clear();
+            journal.last = None;
+        }
+    }
+}
+
+impl File for FaultFile {
+    fn lock_file(&self, exclusive: bool) -> Result<()> {
+        self.file.lock_file(exclusive)
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        self.file.unlock_file()
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        self.file.lock_level(level)
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        self.file.unlock_level(level)
+    }
+
+    fn check_reserved_lock(&self) -> Result<bool> {
+        self.file.check_reserved_lock()
+    }
+
+    fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        match self.next_fault(FaultOp::Read)? {
+            Some(Fault::ShortRead(n)) => self.short_read(pos, c, n),
+            fault => self.apply(fault, c, move |file, c| file.file.pread(pos, c)),
+        }
+    }
+
+    fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion> {
+        match self.next_fault(FaultOp::Write)? {
+            Some(Fault::TornWrite(n)) => self.torn_write(pos, vec![buffer], c, n),
+            fault => self.apply(fault, c, move |file, c| file.write(pos, vec![buffer], c)),
+        }
+    }
+
+    fn pwritev(&self, pos: u64, buffers: Vec<Arc<Buffer>>, c: Completion) -> Result<Completion> {
+        match self.next_fault(FaultOp::Write)? {
+            Some(Fault::TornWrite(n)) => self.torn_write(pos, buffers, c, n),
+            fault => self.apply(fault, c, move |file, c| file.write(pos, buffers, c)),
+        }
+    }
+
+    fn sync(&self, c: Completion, sync_type: FileSyncType) -> Result<Completion> {
+        let fault = self.next_fault(FaultOp::Sync)?;
+        self.apply(fault, c, move |file, c| {
+            file.make_durable(file.file.size()?);
+            file.file.sync(c, sync_type)
+        })
+    }
+
+    fn size(&self) -> Result<u64> {
+        self.file.size()
+    }
+
+    fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
+        let fault = self.next_fault(FaultOp::Truncate)?;
+        self.apply(fault, c, move |file, c| {
+            file.make_durable(len);
+            file.file.truncate(len, c)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemoryIO;
+
+    fn write(file: &Arc<dyn File>, io: &FaultIO, pos: u64, data: &[u8]) -> Result<()> {
+        let c = file.pwrite(
+            pos,
+            Arc::new(Buffer::new(data.to_vec())),
+            Completion::new_write(|_| {}),
+        )?;
+        io.wait_for_completion(c)
+    }
+
+    fn read(file: &Arc<dyn File>, io: &FaultIO, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let buf = Arc::new(Buffer::new_temporary(len));
+        let read = Arc::new(AtomicI32::new(-1));
+        let c = {
+            let read = read.clone();
+            Completion::new_read(buf.clone(), move |res| {
+                if let Ok((_, n)) = res {
+                    read.store(n, Ordering::SeqCst);
+                }
+                None
+            })
+        };
+        io.wait_for_completion(file.pread(pos, c)?)?;
+        let n = read.load(Ordering::SeqCst) as usize;
+        Ok(buf.as_slice()[..n].to_vec())
+    }
+
+    fn sync(file: &Arc<dyn File>, io: &FaultIO) -> Result<()> {
+        let c = file.sync(Completion::new_sync(|_| {}), FileSyncType::Fsync)?;
+        io.wait_for_completion(c)
+    }
+
+    #[test]
+    fn test_scheduled_faults() {
+        let schedule = FaultSchedule::new()
+            .on(FaultOp::Write, 2, Fault::Error)
+            .on(FaultOp::Read, 1, Fault::ShortRead(3))
+            .on(FaultOp::Read, 2, Fault::Latency(Duration::from_millis(5)));
+        let io = FaultIO::new(Arc::new(MemoryIO::new()), schedule);
+        let file = io.open_file("a.db", OpenFlags::Create, false).unwrap();
+
+        write(&file, &io, 0, &[1; 16]).unwrap();
+        assert!(write(&file, &io, 16, &[2; 16]).is_err());
+        write(&file, &io, 16, &[3; 16]).unwrap();
+
+        assert_eq!(read(&file, &io, 0, 16).unwrap(), vec![1; 3]);
+        let start = io.current_time_monotonic();
+        assert_eq!(read(&file, &io, 16, 16).unwrap(), vec![3; 16]);
+        assert!(io.current_time_monotonic().duration_since(start) >= Duration::from_millis(5));
+        assert_eq!(read(&file, &io, 0, 32).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_faults_counted_across_files() {
+        let schedule = FaultSchedule::new().on_files(
+            &["a.db", "a.db-wal"],
+            &[FaultOp::Write, FaultOp::Sync],
+            3,
+            Fault::TornWrite(4),
+        );
+        let io = FaultIO::new(Arc::new(MemoryIO::new()), schedule);
+        let db = io.open_file("a.db", OpenFlags::Create, false).unwrap();
+        let wal = io.open_file("a.db-wal", OpenFlags::Create, false).unwrap();
+        let other = io.open_file("b.db", OpenFlags::Create, false).unwrap();
+
+        write(&wal, &io, 0, &[1; 16]).unwrap();
+        write(&other, &io, 0, &[2; 16]).unwrap();
+        sync(&wal, &io).unwrap();
+        assert!(!io.set_schedule(FaultSchedule::new()).fired());
+
+        io.set_schedule(FaultSchedule::new().on_files(
+            &["a.db", "a.db-wal"],
+            &[FaultOp::Write, FaultOp::Sync],
+            2,
+            Fault::TornWrite(4),
+        ));
+        sync(&wal, &io).unwrap();
+        assert!(write(&db, &io, 0, &[3; 16]).is_err());
+        assert_eq!(read(&db, &io, 0, 16).unwrap(), vec![3; 4]);
+        assert!(io.set_schedule(FaultSchedule::new()).fired());
+    }
+
+    #[test]
+    fn test_power_loss_tears_last_write() {
+        let io = FaultIO::new(Arc::new(MemoryIO::new()), FaultSchedule::new());
+        let file = io.open_file("a.db", OpenFlags::Create, false).unwrap();
+        write(&file, &io, 0, &[1; 4096]).unwrap();
+        sync(&file, &io).unwrap();
+
+        write(&file, &io, 0, &[2; 4096]).unwrap();
+        write(&file, &io, 4096, &[3; 4096]).unwrap();
+        io.power_loss(512).unwrap();
+        assert!(read(&file, &io, 0, 16).is_err());
+
+        let file = io.open_file("a.db", OpenFlags::None, false).unwrap();
+        assert_eq!(file.size().unwrap(), 4096 + 512);
+        assert_eq!(read(&file, &io, 0, 4096).unwrap(), vec![1; 4096]);
+        assert_eq!(read(&file, &io, 4096, 4096).unwrap(), vec![3; 512]);
+    }
+
+    #[test]
+    fn test_scheduled_power_loss() {
+        let schedule = FaultSchedule::new().on_file(
+            "-wal",
+            FaultOp::Sync,
+            1,
+            Fault::PowerLoss { torn_bytes: 0 },
+        );
+        let io = FaultIO::new(Arc::new(MemoryIO::new()), schedule);
+        let db = io.open_file("a.db", OpenFlags::Create, false).unwrap();
+        let wal = io.open_file("a.db-wal", OpenFlags::Create, false).unwrap();
+        write(&db, &io, 0, &[1; 100]).unwrap();
+        sync(&db, &io).unwrap();
+        write(&wal, &io, 0, &[2; 100]).unwrap();
+        assert!(sync(&wal, &io).is_err());
+
+        let db = io.open_file("a.db", OpenFlags::None, false).unwrap();
+        let wal = io.open_file("a.db-wal", OpenFlags::None, false).unwrap();
+        assert_eq!(read(&db, &io, 0, 100).unwrap(), vec![1; 100]);
+        assert_eq!(wal.size().unwrap(), 0);
+    }
+}
@@ -0,0 +1,1298 @@
+//! Read-only IO backend for database files hosted over HTTP.
+//!
+//! Reads are served from fixed-size chunks of the remote file, fetched with
+//! `Range` requests through a [RangeClient] supplied by the embedder: native
+//! builds can plug in any HTTP stack and wasm builds can use `fetch`. Requests
+//! never block; they are polled and their reads completed from [IO::step].
+//! Fetched chunks stay in an LRU cache shared by every file of the backend, so
+//! pages that are read over and over (the schema, interior b-tree pages) are
+//! downloaded once.
+//!
+//! Files are looked up by URL, so a database is opened with its URL as the
+//! path and `OpenFlags::ReadOnly`. Opening a file sends no request of its own:
+//! the first chunk is requested right away, and its response tells the size of
+//! the file, which [File::size] waits for. A missing `-wal` answers 404, which
+//! the read-only open treats as "no WAL". Temp files (sorter and hash join
+//! spills) never go over the network: they are kept in memory, or on the IO of
+//! the local cache if there is one.
+//!
+//! Optionally, chunks are also persisted in a local cache file per remote file
+//! (see [HttpRangeIO::with_local_cache]), so they survive restarts. Every
+//! chunk slot is stamped with the generation of the remote file it was
+//! fetched from and a checksum of its data; slots from another generation,
+//! and slots torn by a crash in the middle of their write, are ignored. The
+//! local cache files are read and written without blocking: loads are
+//! collected from [IO::step] like the responses of range requests.
+//!
+//! Network hiccups are absorbed by [RetryingRangeClient], which [HttpRangeIO::new]
+//! puts in front of the embedder's client: transient failures are retried with
+//! exponential backoff and only surface as I/O errors once its [RetryPolicy]
+//! is exhausted.
+
+use super::{is_temp_file, Clock, Completion, File, FileId, MemoryIO, OpenFlags, IO};
+use crate::error::CompletionError;
+use crate::io::clock::{DefaultClock, MonotonicInstant, WallClockInstant};
+use crate::io::{Buffer, FileSyncType};
+use crate::sync::{Mutex, OnceLock};
+use crate::{LimboError, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Default size of a fetched chunk. A multiple of every page size, so a page
+/// never straddles two chunks.
+pub const HTTP_RANGE_DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Default number of chunks kept in the cache (16 MiB with the default chunk size).
+pub const HTTP_RANGE_DEFAULT_CACHE_CHUNKS: usize = 256;
+
+/// Marks a written slot of a local cache file; holes read back as zeroes.
+const LOCAL_SLOT_MAGIC: u32 = 0x7475_6369;
+/// Slot header: magic, data length (both u32), generation (u64) and the
+/// CRC32C of the data (u32, then 4 unused bytes), little-endian.
+const LOCAL_SLOT_HEADER: usize = 24;
+
+/// Transport used by [HttpRangeIO].
+pub trait RangeClient: Send + Sync {
+    /// Start fetching `len` bytes of `url` at `offset`, i.e. a `GET` with
+    /// `Range: bytes={offset}-{offset + len - 1}`.
+    fn get_range(&self, url: &str, offset: u64, len: usize) -> Result<Box<dyn RangeRequest>>;
+}
+
+/// A request started by [RangeClient::get_range].
+pub trait RangeRequest: Send {
+    /// Return the response if it has arrived, without blocking. A 404 must
+    /// be reported as `CompletionError::IOError(ErrorKind::NotFound, ..)`.
+    fn poll(&mut self) -> std::result::Result<Option<RangeResponse>, CompletionError>;
+}
+
+pub struct RangeResponse {
+    /// Bytes starting at the requested offset. Shorter than requested only
+    /// at the end of the file.
+    pub data: Vec<u8>,
+    /// Size of the whole remote file, from the `Content-Range` header.
+    pub file_size: u64,
+    /// Version of the remote file, e.g. a hash of its `ETag`. Chunks cached
+    /// locally for another generation are not reused.
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpRangeStats {
+    /// Range requests issued so far.
+    pub requests: u64,
+    /// Chunks currently cached in memory.
+    pub cached_chunks: usize,
+    /// Chunks loaded from the local cache files instead of the network.
+    pub local_hits: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ChunkKey {
+    file: u64,
+    chunk: u64,
+}
+
+struct ChunkCache {
+    capacity: usize,
+    chunks: HashMap<ChunkKey, (Arc<[u8]>, u64)>,
+    /// Last use of every cached chunk, oldest first.
+    recency: BTreeMap<u64, ChunkKey>,
+    clock: u64,
+}
+
+impl ChunkCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chunks: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn contains(&self, key: &ChunkKey) -> bool {
+        self.chunks.contains_key(key)
+    }
+
+    fn get(&mut self, key: &ChunkKey) -> Option<Arc<[u8]>> {
+        let (data, last_use) = self.chunks.get_mut(key)?;
+        self.recency.remove(last_use);
+        self.clock += 1;
+        *last_use = self.clock;
+        self.recency.insert(self.clock, *key);
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, key: ChunkKey, data: Arc<[u8]>) {
+        if let Some((_, last_use)) = self.chunks.remove(&key) {
+            self.recency.remove(&last_use);
+        }
+        while self.chunks.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.chunks.remove(&oldest);
+        }
+        self.clock += 1;
+        self.chunks.insert(key, (data, self.clock));
+        self.recency.insert(self.clock, key);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RemoteMeta {
+    size: u64,
+    generation: u64,
+}
+
+struct RemoteFile {
+    id: u64,
+    url: String,
+    /// Known once the first response for the file has arrived.
+    meta: OnceLock<RemoteMeta>,
+    local: Option<Arc<dyn File>>,
+}
+
+impl RemoteFile {
+    fn first_chunk(&self) -> ChunkKey {
+        ChunkKey {
+            file: self.id,
+            chunk: 0,
+        }
+    }
+}
+
+struct PendingRead {
+    file: Arc<RemoteFile>,
+    pos: u64,
+    c: Completion,
+}
+
+/// A read of a chunk slot from a local cache file.
+struct LocalLoad {
+    file: Arc<RemoteFile>,
+    buffer: Arc<Buffer>,
+    read: Arc<AtomicUsize>,
+    c: Completion,
+}
+
+struct RangeState {
+    client: Arc<dyn RangeClient>,
+    chunk_size: usize,
+    cache: ChunkCache,
+    /// IO and directory of the local cache files, if enabled.
+    local: Option<(Arc<dyn IO>, String)>,
+    inflight: HashMap<ChunkKey, (Arc<RemoteFile>, Box<dyn RangeRequest>)>,
+    /// Chunks being read from the local cache files.
+    local_loads: HashMap<ChunkKey, LocalLoad>,
+    /// Chunks whose request failed, reported to the next read that needs them.
+    failed: HashMap<ChunkKey, CompletionError>,
+    reads: Vec<PendingRead>,
+    next_file_id: u64,
+    requests: u64,
+    local_hits: u64,
+}
+
+impl RangeState {
+    fn start_request(&mut self, file: &Arc<RemoteFile>, key: ChunkKey) -> Result<()> {
+        let offset = key.chunk * self.chunk_size as u64;
+        debug!("get_range(url={}): offset={offset}", file.url);
+        let request = self.client.get_range(&file.url, offset, self.chunk_size)?;
+        self.requests += 1;
+        self.inflight.insert(key, (file.clone(), request));
+        Ok(())
+    }
+
+    /// Make sure the first chunk of `file`, whose response tells its size, is
+    /// being fetched, or return the error of its request.
+    fn fetch_meta(&mut self, file: &Arc<RemoteFile>) -> std::result::Result<(), CompletionError> {
+        let key = file.first_chunk();
+        if let Some(err) = self.failed.remove(&key) {
+            return Err(err);
+        }
+        if !self.inflight.contains_key(&key) {
+            self.start_request(file, key).map_err(request_error)?;
+        }
+        Ok(())
+    }
+
+    fn local_slot_size(&self) -> usize {
+        LOCAL_SLOT_HEADER + self.chunk_size
+    }
+
+    /// Start reading `key` from the file's local cache, if there is one, and
+    /// return whether the read was started.
+    fn start_local_load(&mut self, file: &Arc<RemoteFile>, key: ChunkKey) -> bool {
+        let Some(local_file) = &file.local else {
+            return false;
+        };
+        let slot_size = self.local_slot_size();
+        let buffer = Arc::new(Buffer::new_temporary(slot_size));
+        let read = Arc::new(AtomicUsize::new(0));
+        // The callback may run inside `pread`, under the state lock, so it
+        // only records how much was read.
+        let c = Completion::new_read(buffer.clone(), {
+            let read = read.clone();
+            move |res| {
+                if let Ok((_, n)) = res {
+                    read.store(n.max(0) as usize, Ordering::Release);
+                }
+                None
+            }
+        });
+        let Ok(c) = local_file.pread(key.chunk * slot_size as u64, c) else {
+            return false;
+        };
+        self.local_loads.insert(
+            key,
+            LocalLoad {
+                file: file.clone(),
+                buffer,
+                read,
+                c,
+            },
+        );
+        true
+    }
+
+    /// Move the chunks whose local read finished into the cache. Chunks that
+    /// were not in the local cache for the current generation, or whose slot
+    /// is torn, are fetched from the network instead.
+    fn finish_local_loads(&mut self) {
+        let finished: Vec<ChunkKey> = self
+            .local_loads
+            .iter()
+            .filter(|(_, load)| load.c.finished())
+            .map(|(key, _)| *key)
+            .collect();
+        for key in finished {
+            let load = self.local_loads.remove(&key).unwrap();
+            let data = if load.c.succeeded() {
+                let slot = &load.buffer.as_slice()[..load.read.load(Ordering::Acquire)];
+                parse_local_slot(slot, &load.file)
+            } else {
+                None
+            };
+            match data {
+                Some(data) => {
+                    self.local_hits += 1;
+                    self.cache.insert(key, data);
+                }
+                None => {
+                    if let Err(err) = self.start_request(&load.file, key) {
+                        self.failed.insert(key, request_error(err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Persist `chunk` in the file's local cache. The cache is best effort,
+    /// so failures are only logged, and the write is not waited for: a slot
+    /// torn by a crash fails its checksum when loaded.
+    fn store_local(&self, file: &RemoteFile, chunk: u64, data: &[u8]) {
+        let (Some(local_file), Some(meta)) = (&file.local, file.meta.get()) else {
+            return;
+        };
+        let slot_size = self.local_slot_size();
+        let mut slot = Vec::with_capacity(LOCAL_SLOT_HEADER + data.len());
+        slot.extend_from_slice(&LOCAL_SLOT_MAGIC.to_le_bytes());
+        slot.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        slot.extend_from_slice(&meta.generation.to_le_bytes());
+        slot.extend_from_slice(&crc32c::crc32c(data).to_le_bytes());
+        slot.extend_from_slice(&[0; 4]);
+        slot.extend_from_slice(data);
+        let url = file.url.clone();
+        let c = Completion::new_write(move |res| {
+            if let Err(err) = res {
+                warn!("failed to cache chunk {chunk} of {url} locally: {err}");
+            }
+        });
+        if let Err(err) =
+            local_file.pwrite(chunk * slot_size as u64, Arc::new(Buffer::new(slot)), c)
+        {
+            warn!(
+                "failed to cache chunk {chunk} of {} locally: {err}",
+                file.url
+            );
+        }
+    }
+
+    /// Copy the bytes at `pos` into `buf` if every chunk they span is cached,
+    /// otherwise make sure the missing chunks are being fetched and return `None`.
+    fn read_cached(
+        &mut self,
+        file: &Arc<RemoteFile>,
+        pos: u64,
+        buf: &mut [u8],
+    ) -> std::result::Result<Option<usize>, CompletionError> {
+        let Some(meta) = file.meta.get() else {
+            // Nothing can be read before the size of the file is known.
+            self.fetch_meta(file)?;
+            return Ok(None);
+        };
+        let end = (pos + buf.len() as u64).min(meta.size);
+        if pos >= end {
+            return Ok(Some(0));
+        }
+        let chunk_size = self.chunk_size as u64;
+        let chunks = pos / chunk_size..=(end - 1) / chunk_size;
+        let mut missing = false;
+        for chunk in chunks.clone() {
+            let key = ChunkKey {
+                file: file.id,
+                chunk,
+            };
+            if let Some(err) = self.failed.remove(&key) {
+                return Err(err);
+            }
+            if self.cache.contains(&key) {
+                continue;
+            }
+            if self.local_loads.contains_key(&key) || self.inflight.contains_key(&key) {
+                missing = true;
+                continue;
+            }
+            if self.start_local_load(file, key) {
+                // Local IO backends that complete reads synchronously have
+                // the chunk ready right away.
+                self.finish_local_loads();
+                missing |= !self.cache.contains(&key);
+                continue;
+            }
+            missing = true;
+            self.start_request(file, key).map_err(request_error)?;
+        }
+        if missing {
+            return Ok(None);
+        }
+        let mut copied = 0;
+        for chunk in chunks {
+            let key = ChunkKey {
+                file: file.id,
+                chunk,
+            };
+            let Some(data) = self.cache.get(&key) else {
+                // Evicted by a chunk loaded above: retry from `IO::step`.
+                return Ok(None);
+            };
+            let offset = (pos + copied as u64 - chunk * chunk_size) as usize;
+            let wanted = (end - pos) as usize - copied;
+            let n = wanted.min(data.len().saturating_sub(offset));
+            if n == 0 {
+                // The remote file shrank under us: report a short read.
+                break;
+            }
+            buf[copied..copied + n].copy_from_slice(&data[offset..offset + n]);
+            copied += n;
+        }
+        Ok(Some(copied))
+    }
+}
+
+fn request_error(err: LimboError) -> CompletionError {
+    match err {
+        LimboError::CompletionError(e) => e,
+        _ => CompletionError::IOError(std::io::ErrorKind::Other, "get_range"),
+    }
+}
+
+/// The data of a local cache slot, if it was stored for the current
+/// generation of `file` and was written in full.
+fn parse_local_slot(slot: &[u8], file: &RemoteFile) -> Option<Arc<[u8]>> {
+    let header = slot.get(..LOCAL_SLOT_HEADER)?;
+    let magic = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let len = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let generation = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let checksum = u32::from_le_bytes(header[16..20].try_into().unwrap());
+    if magic != LOCAL_SLOT_MAGIC || Some(generation) != file.meta.get().map(|m| m.generation) {
+        return None;
+    }
+    let data = slot.get(LOCAL_SLOT_HEADER..LOCAL_SLOT_HEADER + len)?;
+    if crc32c::crc32c(data) != checksum {
+        debug!("ignoring a torn slot in the local cache of {}", file.url);
+        return None;
+    }
+    Some(Arc::from(data))
+}
+
+/// How [RetryingRangeClient] retries failed range requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request, the first one included.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled after every further failure.
+    pub initial_backoff: Duration,
+    /// Upper bound of the wait between two attempts.
+    pub max_backoff: Duration,
+    /// An attempt that hasn't answered after this long is abandoned and
+    /// counts as a `TimedOut` failure.
+    pub attempt_timeout: Duration,
+    /// Overall time budget of a request, retries included. No retry is
+    /// scheduled past it.
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            attempt_timeout: Duration::from_secs(30),
+            deadline: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait after the `attempt`-th failed attempt (1-based).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(31);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether a failed request may succeed if issued again. Clients should report
+/// throttling and server errors (429, 5xx) with one of these kinds too.
+fn is_transient(err: &CompletionError) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        err,
+        CompletionError::IOError(
+            ErrorKind::TimedOut
+                | ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof,
+            _
+        )
+    )
+}
+
+/// [RangeClient] middleware that retries transient failures of another client
+/// with exponential backoff, according to a [RetryPolicy]. Nothing blocks:
+/// waiting for the next attempt is just another `Ok(None)` from
+/// [RangeRequest::poll]. Once the policy is exhausted the last error is
+/// returned, and permanent errors such as a 404 are returned right away.
+///
+/// [HttpRangeIO::new] wraps its client in one with the default policy.
+pub struct RetryingRangeClient {
+    inner: Arc<dyn RangeClient>,
+    policy: RetryPolicy,
+    clock: Arc<dyn Clock + Send + Sync>,
+}
+
+impl RetryingRangeClient {
+    pub fn new(inner: Arc<dyn RangeClient>, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            clock: Arc::new(DefaultClock),
+        }
+    }
+
+    /// Measure timeouts and backoff with `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock + Send + Sync>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl RangeClient for RetryingRangeClient {
+    fn get_range(&self, url: &str, offset: u64, len: usize) -> Result<Box<dyn RangeRequest>> {
+        let now = self.clock.current_time_monotonic();
+        let mut request = RetryingRequest {
+            client: self.inner.clone(),
+            policy: self.policy,
+            clock: self.clock.clone(),
+            url: url.to_string(),
+            offset,
+            len,
+            started: now,
+            attempts: 0,
+            attempt: Attempt::Backoff { until: now },
+        };
+        request.start_attempt(now)?;
+        Ok(Box::new(request))
+    }
+}
+
+enum Attempt {
+    Running {
+        request: Box<dyn RangeRequest>,
+        started: MonotonicInstant,
+    },
+    Backoff {
+        until: MonotonicInstant,
+    },
+}
+
+struct RetryingRequest {
+    client: Arc<dyn RangeClient>,
+    policy: RetryPolicy,
+    clock: Arc<dyn Clock + Send + Sync>,
+    url: String,
+    offset: u64,
+    len: usize,
+    /// Start of the first attempt, for the overall deadline.
+    started: MonotonicInstant,
+    attempts: u32,
+    attempt: Attempt,
+}
+
+impl RetryingRequest {
+    fn start_attempt(&mut self, now: MonotonicInstant) -> std::result::Result<(), CompletionError> {
+        self.attempts += 1;
+        match self.client.get_range(&self.url, self.offset, self.len) {
+            Ok(request) => {
+                self.attempt = Attempt::Running {
+                    request,
+                    started: now,
+                };
+                Ok(())
+            }
+            Err(LimboError::CompletionError(err)) => self.schedule_retry(err, now),
+            Err(err) => {
+                warn!("range request for {} failed: {err}", self.url);
+                Err(CompletionError::IOError(
+                    std::io::ErrorKind::Other,
+                    "get_range",
+                ))
+            }
+        }
+    }
+
+    /// Back off after a failed attempt, or give up with `err` if it is
+    /// permanent or the policy is exhausted.
+    fn schedule_retry(
+        &mut self,
+        err: CompletionError,
+        now: MonotonicInstant,
+    ) -> std::result::Result<(), CompletionError> {
+        if !is_transient(&err) || self.attempts >= self.policy.max_attempts {
+            return Err(err);
+        }
+        let until = now + self.policy.backoff(self.attempts);
+        if until.duration_since(self.started) >= self.policy.deadline {
+            return Err(err);
+        }
+        debug!(
+            "range request for {} at {} failed ({err}), attempt {} of {}",
+            self.url, self.offset, self.attempts, self.policy.max_attempts
+        );
+        self.attempt = Attempt::Backoff { until };
+        Ok(())
+    }
+}
+
+impl RangeRequest for RetryingRequest {
+    fn poll(&mut self) -> std::result::Result<Option<RangeResponse>, CompletionError> {
+        let now = self.clock.current_time_monotonic();
+        match &mut self.attempt {
+            Attempt::Backoff { until } => {
+                if now >= *until {
+                    self.start_attempt(now)?;
+                }
+            }
+            Attempt::Running { request, started } => {
+                let err = match request.poll() {
+                    Ok(Some(response)) => return Ok(Some(response)),
+                    Err(err) => err,
+                    Ok(None) if now.duration_since(*started) < self.policy.attempt_timeout => {
+                        return Ok(None)
+                    }
+                    Ok(None) => CompletionError::IOError(std::io::ErrorKind::TimedOut, "get_range"),
+                };
+                self.schedule_retry(err, now)?;
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Read-only [IO] backend over HTTP range requests. See the module docs.
+pub struct HttpRangeIO {
+    state: Arc<Mutex<RangeState>>,
+    files: Mutex<HashMap<String, Arc<HttpRangeFile>>>,
+    /// Where temp files are created.
+    temp: Arc<dyn IO>,
+}
+
+impl HttpRangeIO {
+    /// Fetch chunks through `client`, retrying transient failures with the
+    /// default [RetryPolicy].
+    pub fn new(client: Arc<dyn RangeClient>) -> Self {
+        Self::with_cache(
+            Arc::new(RetryingRangeClient::new(client, RetryPolicy::default())),
+            HTTP_RANGE_DEFAULT_CHUNK_SIZE,
+            HTTP_RANGE_DEFAULT_CACHE_CHUNKS,
+        )
+        .expect("default cache configuration is valid")
+    }
+
+    /// `client` is used as is; wrap it in a [RetryingRangeClient] to retry
+    /// failed requests.
+    ///
+    /// `chunk_size` must be a power of two of at least 512 bytes, so that no
+    /// page smaller than a chunk straddles two of them, and the cache must
+    /// hold at least two chunks.
+    pub fn with_cache(
+        client: Arc<dyn RangeClient>,
+        chunk_size: usize,
+        cache_chunks: usize,
+    ) -> Result<Self> {
+        if !chunk_size.is_power_of_two() || chunk_size < 512 {
+            return Err(LimboError::InvalidArgument(format!(
+                "HTTP range chunk size must be a power of two of at least 512 bytes, got {chunk_size}"
+            )));
+        }
+        if cache_chunks < 2 {
+            return Err(LimboError::InvalidArgument(
+                "HTTP range cache must hold at least two chunks".to_string(),
+            ));
+        }
+        debug!("Using IO backend 'http-range'");
+        Ok(Self {
+            state: Arc::new(Mutex::new(RangeState {
+                client,
+                chunk_size,
+                cache: ChunkCache::new(cache_chunks),
+                inflight: HashMap::new(),
+                local_loads: HashMap::new(),
+                failed: HashMap::new(),
+                reads: Vec::new(),
+                local: None,
+                next_file_id: 0,
+                requests: 0,
+                local_hits: 0,
+            })),
+            files: Mutex::new(HashMap::new()),
+            temp: Arc::new(MemoryIO::new()),
+        })
+    }
+
+    /// Also keep fetched chunks in files under `dir` of `io`, one per remote
+    /// file, so they outlive this backend. Temp files are created on `io` too.
+    pub fn with_local_cache(mut self, io: Arc<dyn IO>, dir: impl Into<String>) -> Self {
+        self.temp = io.clone();
+        self.state.lock().local = Some((io, dir.into()));
+        self
+    }
+
+    pub fn stats(&self) -> HttpRangeStats {
+        let state = self.state.lock();
+        HttpRangeStats {
+            requests: state.requests,
+            cached_chunks: state.cache.chunks.len(),
+            local_hits: state.local_hits,
+        }
+    }
+}
+
+impl Clock for HttpRangeIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        DefaultClock.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        DefaultClock.current_time_wall_clock()
+    }
+}
+
+impl IO for HttpRangeIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        if is_temp_file(path) {
+            return self.temp.open_file(path, flags, direct);
+        }
+        if !flags.contains(OpenFlags::ReadOnly) {
+            return Err(LimboError::ReadOnly);
+        }
+        if let Some(file) = self.files.lock().get(path) {
+            return Ok(file.clone());
+        }
+        let remote = {
+            let mut state = self.state.lock();
+            let id = state.next_file_id;
+            state.next_file_id += 1;
+            let local = match &state.local {
+                Some((io, dir)) => {
+                    let name = format!("{dir}/{:016x}.chunks", FileId::from_path_hash(path).ino);
+                    Some(io.open_file(&name, OpenFlags::Create, false)?)
+                }
+                None => None,
+            };
+            let remote = Arc::new(RemoteFile {
+                id,
+                url: path.to_string(),
+                meta: OnceLock::new(),
+                local,
+            });
+            // Only started here: the response is polled from `IO::step` like
+            // any other, and `File::size` waits for it.
+            state.fetch_meta(&remote)?;
+            remote
+        };
+        let file = Arc::new(HttpRangeFile {
+            remote,
+            state: self.state.clone(),
+        });
+        self.files.lock().insert(path.to_string(), file.clone());
+        Ok(file)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        if is_temp_file(path) {
+            return self.temp.remove_file(path);
+        }
+        Err(LimboError::ReadOnly)
+    }
+
+    fn step(&self) -> Result<()> {
+        // Also drives the local cache files, without holding the state lock.
+        self.temp.step()?;
+        step_requests(&self.state);
+        Ok(())
+    }
+
+    fn file_id(&self, path: &str) -> Result<FileId> {
+        Ok(FileId::from_path_hash(path))
+    }
+}
+
+/// Poll the requests in flight and the loads from the local cache, then
+/// complete the reads they were holding up.
+fn step_requests(state: &Mutex<RangeState>) {
+    let mut done = Vec::new();
+    {
+        let mut state = state.lock();
+        let state = &mut *state;
+        let mut fetched = Vec::new();
+        let failed = &mut state.failed;
+        state
+            .inflight
+            .retain(|key, (file, request)| match request.poll() {
+                Ok(None) => true,
+                Ok(Some(response)) => {
+                    fetched.push((*key, file.clone(), response));
+                    false
+                }
+                Err(err) => {
+                    failed.insert(*key, err);
+                    false
+                }
+            });
+        for (key, file, response) in fetched {
+            let _ = file.meta.set(RemoteMeta {
+                size: response.file_size,
+                generation: response.generation,
+            });
+            state.store_local(&file, key.chunk, &response.data);
+            state.cache.insert(key, response.data.into());
+        }
+        state.finish_local_loads();
+        for read in std::mem::take(&mut state.reads) {
+            if read.c.finished() {
+                continue;
+            }
+            let buf = read.c.as_read().buf().as_mut_slice();
+            match state.read_cached(&read.file, read.pos, buf) {
+                Ok(Some(n)) => done.push((read.c, Ok(n))),
+                Ok(None) => state.reads.push(read),
+                Err(err) => done.push((read.c, Err(err))),
+            }
+        }
+    }
+    // Completion callbacks may issue new reads, so run them unlocked.
+    for (c, result) in done {
+        match result {
+            Ok(n) => c.complete(n as i32),
+            Err(err) => c.error(err),
+        }
+    }
+}
+
+pub struct HttpRangeFile {
+    remote: Arc<RemoteFile>,
+    state: Arc<Mutex<RangeState>>,
+}
+
+impl File for HttpRangeFile {
+    fn lock_file(&self, _exclusive: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        tracing::debug!("pread(url={}): pos={}", self.remote.url, pos);
+        let buf = c.as_read().buf().as_mut_slice();
+        let mut state = self.state.lock();
+        match state.read_cached(&self.remote, pos, buf) {
+            Ok(Some(n)) => {
+                drop(state);
+                c.complete(n as i32);
+            }
+            Ok(None) => state.reads.push(PendingRead {
+                file: self.remote.clone(),
+                pos,
+                c: c.clone(),
+            }),
+            Err(err) => {
+                drop(state);
+                c.error(err);
+            }
+        }
+        Ok(c)
+    }
+
+    fn pwrite(&self, _pos: u64, _buffer: Arc<Buffer>, _c: Completion) -> Result<Completion> {
+        Err(LimboError::ReadOnly)
+    }
+
+    fn sync(&self, c: Completion, _sync_type: FileSyncType) -> Result<Completion> {
+        // nothing is ever written
+        c.complete(0);
+        Ok(c)
+    }
+
+    fn truncate(&self, _len: u64, _c: Completion) -> Result<Completion> {
+        Err(LimboError::ReadOnly)
+    }
+
+    /// Waits for the first response for the file if it hasn't arrived yet,
+    /// stepping the requests in flight like [IO::wait_for_completion] does.
+    fn size(&self) -> Result<u64> {
+        loop {
+            if let Some(meta) = self.remote.meta.get() {
+                return Ok(meta.size);
+            }
+            self.state.lock().fetch_meta(&self.remote)?;
+            step_requests(&self.state);
+            crate::thread::yield_now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, MemoryIO, OpenOptions, SqliteDialect, Value};
+
+    /// Serves files from memory, answering each request on its second poll.
+    struct FakeServer {
+        files: HashMap<String, Arc<Vec<u8>>>,
+        generation: u64,
+    }
+
+    impl FakeServer {
+        fn new(url: &str, data: Vec<u8>) -> Arc<Self> {
+            Self::with_generation(url, data, 1)
+        }
+
+        fn with_generation(url: &str, data: Vec<u8>, generation: u64) -> Arc<Self> {
+            Arc::new(Self {
+                files: HashMap::from([(url.to_string(), Arc::new(data))]),
+                generation,
+            })
+        }
+    }
+
+    struct FakeRequest {
+        file: Option<Arc<Vec<u8>>>,
+        offset: u64,
+        len: usize,
+        generation: u64,
+        polled: bool,
+    }
+
+    impl RangeClient for FakeServer {
+        fn get_range(&self, url: &str, offset: u64, len: usize) -> Result<Box<dyn RangeRequest>> {
+            Ok(Box::new(FakeRequest {
+                file: self.files.get(url).cloned(),
+                offset,
+                len,
+                generation: self.generation,
+                polled: false,
+            }))
+        }
+    }
+
+    impl RangeRequest for FakeRequest {
+        fn poll(&mut self) -> std::result::Result<Option<RangeResponse>, CompletionError> {
+            if !std::mem::replace(&mut self.polled, true) {
+                return Ok(None);
+            }
+            let Some(file) = &self.file else {
+                return Err(CompletionError::IOError(
+                    std::io::ErrorKind::NotFound,
+                    "get_range",
+                ));
+            };
+            let start = (self.offset as usize).min(file.len());
+            let end = (start + self.len).min(file.len());
+            Ok(Some(RangeResponse {
+                data: file[start..end].to_vec(),
+                file_size: file.len() as u64,
+                generation: self.generation,
+            }))
+        }
+    }
+
+    fn read(io: &HttpRangeIO, file: &Arc<dyn File>, pos: u64, len: usize) -> Vec<u8> {
+        let buffer = Arc::new(Buffer::new_temporary(len));
+        let c = Completion::new_read(buffer.clone(), |_| None);
+        let c = file.pread(pos, c).unwrap();
+        io.wait_for_completion(c).unwrap();
+        buffer.as_slice().to_vec()
+    }
+
+    #[test]
+    fn test_reads_are_served_from_cached_chunks() {
+        let data: Vec<u8> = (0..4096u32).map(|i| i as u8).collect();
+        let server = FakeServer::new("http://x/f", data.clone());
+        let io = HttpRangeIO::with_cache(server, 1024, 2).unwrap();
+        let file = io
+            .open_file("http://x/f", OpenFlags::ReadOnly, false)
+            .unwrap();
+        // Opening only starts the request for the first chunk, which tells the size.
+        assert_eq!(io.stats().requests, 1);
+        assert_eq!(file.size().unwrap(), 4096);
+        assert_eq!(io.stats().requests, 1);
+
+        // Within the first chunk, fetched for the size.
+        assert_eq!(read(&io, &file, 10, 4), data[10..14]);
+        assert_eq!(io.stats().requests, 1);
+        // Spanning chunks 1 and 2, then evicting chunk 0.
+        assert_eq!(read(&io, &file, 2000, 100), data[2000..2100]);
+        assert_eq!(
+            io.stats(),
+            HttpRangeStats {
+                requests: 3,
+                cached_chunks: 2,
+                local_hits: 0,
+            }
+        );
+        assert_eq!(read(&io, &file, 0, 4), data[0..4]);
+        assert_eq!(io.stats().requests, 4);
+
+        // Reads stop at the end of the file.
+        assert_eq!(read(&io, &file, 4090, 16)[..6], data[4090..]);
+
+        assert!(matches!(
+            io.open_file("http://x/f", OpenFlags::Create, false),
+            Err(LimboError::ReadOnly)
+        ));
+        let missing = io
+            .open_file("http://x/missing", OpenFlags::ReadOnly, false)
+            .unwrap();
+        assert!(matches!(
+            missing.size(),
+            Err(LimboError::CompletionError(CompletionError::IOError(
+                std::io::ErrorKind::NotFound,
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_local_cache_survives_restart() {
+        let local: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let data: Vec<u8> = (0..8192u32).map(|i| (i / 7) as u8).collect();
+        let open = |generation| {
+            let server = FakeServer::with_generation("http://x/f", data.clone(), generation);
+            let io = HttpRangeIO::with_cache(server, 1024, 8)
+                .unwrap()
+                .with_local_cache(local.clone(), "cache");
+            let file = io
+                .open_file("http://x/f", OpenFlags::ReadOnly, false)
+                .unwrap();
+            (io, file)
+        };
+
+        let (io, file) = open(1);
+        assert_eq!(read(&io, &file, 1000, 5000), data[1000..6000]);
+        assert_eq!(io.stats().requests, 6);
+        assert_eq!(io.stats().local_hits, 0);
+
+        // A new backend over the same local cache only asks for the first
+        // chunk, which tells it the generation is unchanged.
+        let (io, file) = open(1);
+        assert_eq!(read(&io, &file, 1000, 5000), data[1000..6000]);
+        assert_eq!(io.stats().requests, 1);
+        assert_eq!(io.stats().local_hits, 5);
+
+        // A slot torn by a crash fails its checksum and is fetched again.
+        let name = format!(
+            "cache/{:016x}.chunks",
+            FileId::from_path_hash("http://x/f").ino
+        );
+        let cache_file = local.open_file(&name, OpenFlags::None, false).unwrap();
+        let slot_size = (LOCAL_SLOT_HEADER + 1024) as u64;
+        let c = cache_file
+            .pwrite(
+                2 * slot_size + LOCAL_SLOT_HEADER as u64 + 10,
+                Arc::new(Buffer::new(vec![0xff; 4])),
+                Completion::new_write(|_| {}),
+            )
+            .unwrap();
+        local.wait_for_completion(c).unwrap();
+        let (io, file) = open(1);
+        assert_eq!(read(&io, &file, 1000, 5000), data[1000..6000]);
+        assert_eq!(io.stats().requests, 2);
+        assert_eq!(io.stats().local_hits, 4);
+
+        // The remote file changed: nothing cached locally is trusted.
+        let (io, file) = open(2);
+        assert_eq!(read(&io, &file, 1000, 5000), data[1000..6000]);
+        assert_eq!(io.stats().requests, 6);
+        assert_eq!(io.stats().local_hits, 0);
+    }
+
+    #[test]
+    fn test_query_remote_database() {
+        let local = Database::open_file(
+            Arc::new(MemoryIO::new()),
+            ":memory:",
+            Arc::new(SqliteDialect),
+        )
+        .unwrap();
+        let conn = local.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT value FROM generate_series(1, 5000)")
+            .unwrap();
+        let image = conn.serialize().unwrap();
+
+        let io = Arc::new(HttpRangeIO::new(FakeServer::new("https://cdn/db", image)));
+        let db = Database::open(
+            io.clone(),
+            "https://cdn/db",
+            OpenOptions::new(Arc::new(SqliteDialect)).flags(OpenFlags::ReadOnly),
+        )
+        .unwrap();
+        let conn = db.connect().unwrap();
+        let mut stmt = conn.prepare("SELECT count(*), sum(x) FROM t").unwrap();
+        assert_eq!(
+            stmt.run_collect_rows().unwrap(),
+            vec![vec![Value::from_i64(5000), Value::from_i64(12_502_500)]]
+        );
+        assert!(conn.execute("INSERT INTO t VALUES (1)").is_err());
+
+        // The sorter buffer is sized like the page cache; shrinking it to the
+        // minimum makes the ORDER BY below spill to a temp file, which must not
+        // go to the server.
+        conn.execute("PRAGMA cache_size = -1").unwrap();
+        let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x DESC").unwrap();
+        let rows = stmt.run_collect_rows().unwrap();
+        assert_eq!(rows.len(), 5000);
+        assert_eq!(rows[0], vec![Value::from_i64(5000)]);
+    }
+
+    /// Fails the first `failures` requests with `kind`, then defers to `server`.
+    struct FlakyClient {
+        server: Arc<FakeServer>,
+        failures: AtomicUsize,
+        kind: std::io::ErrorKind,
+        requests: AtomicUsize,
+    }
+
+    impl FlakyClient {
+        fn new(server: Arc<FakeServer>, failures: usize, kind: std::io::ErrorKind) -> Arc<Self> {
+            Arc::new(Self {
+                server,
+                failures: AtomicUsize::new(failures),
+                kind,
+                requests: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    struct FailedRequest(std::io::ErrorKind);
+
+    impl RangeRequest for FailedRequest {
+        fn poll(&mut self) -> std::result::Result<Option<RangeResponse>, CompletionError> {
+            Err(CompletionError::IOError(self.0, "get_range"))
+        }
+    }
+
+    impl RangeClient for FlakyClient {
+        fn get_range(&self, url: &str, offset: u64, len: usize) -> Result<Box<dyn RangeRequest>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let failing = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                return Ok(Box::new(FailedRequest(self.kind)));
+            }
+            self.server.get_range(url, offset, len)
+        }
+    }
+
+    /// Never answers.
+    struct SilentClient;
+
+    struct SilentRequest;
+
+    impl RangeRequest for SilentRequest {
+        fn poll(&mut self) -> std::result::Result<Option<RangeResponse>, CompletionError> {
+            Ok(None)
+        }
+    }
+
+    impl RangeClient for SilentClient {
+        fn get_range(&self, _: &str, _: u64, _: usize) -> Result<Box<dyn RangeRequest>> {
+            Ok(Box::new(SilentRequest))
+        }
+    }
+
+    #[derive(Default)]
+    struct ManualClock {
+        nanos: std::sync::atomic::AtomicU64,
+    }
+
+    impl ManualClock {
+        fn advance(&self, by: Duration) {
+            self.nanos.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn current_time_monotonic(&self) -> MonotonicInstant {
+            MonotonicInstant::from_nanos(self.nanos.load(Ordering::SeqCst) as u128)
+        }
+
+        fn current_time_wall_clock(&self) -> WallClockInstant {
+            WallClockInstant::now()
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(250),
+            attempt_timeout: Duration::from_secs(1),
+            deadline: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn test_retry_backs_off_exponentially() {
+        let server = FakeServer::new("http://x/f", vec![1; 100]);
+        let flaky = FlakyClient::new(server, 3, std::io::ErrorKind::ConnectionReset);
+        let clock = Arc::new(ManualClock::default());
+        let client = RetryingRangeClient::new(flaky.clone(), policy()).with_clock(clock.clone());
+
+        let mut request = client.get_range("http://x/f", 0, 10).unwrap();
+        // Each failure waits twice as long as the previous one, up to the cap.
+        for (attempt, backoff) in [(1, 100), (2, 200), (3, 250)] {
+            assert!(request.poll().unwrap().is_none());
+            clock.advance(Duration::from_millis(backoff - 1));
+            assert!(request.poll().unwrap().is_none());
+            assert_eq!(flaky.requests.load(Ordering::SeqCst), attempt);
+            clock.advance(Duration::from_millis(1));
+            assert!(request.poll().unwrap().is_none());
+            assert_eq!(flaky.requests.load(Ordering::SeqCst), attempt + 1);
+        }
+        // The fake server answers on its second poll.
+        assert!(request.poll().unwrap().is_none());
+        assert_eq!(request.poll().unwrap().unwrap().data, vec![1; 10]);
+    }
+
+    #[test]
+    fn test_retry_gives_up() {
+        let server = FakeServer::new("http://x/f", vec![1; 100]);
+        let clock = Arc::new(ManualClock::default());
+
+        // Permanent errors are returned right away.
+        let flaky = FlakyClient::new(server.clone(), 1, std::io::ErrorKind::NotFound);
+        let client = RetryingRangeClient::new(flaky.clone(), policy()).with_clock(clock.clone());
+        let mut request = client.get_range("http://x/f", 0, 10).unwrap();
+        assert!(matches!(
+            request.poll(),
+            Err(CompletionError::IOError(std::io::ErrorKind::NotFound, _))
+        ));
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 1);
+
+        // Transient ones once every attempt has failed.
+        let flaky = FlakyClient::new(server, 10, std::io::ErrorKind::TimedOut);
+        let client = RetryingRangeClient::new(flaky.clone(), policy()).with_clock(clock.clone());
+        let mut request = client.get_range("http://x/f", 0, 10).unwrap();
+        let err = loop {
+            match request.poll() {
+                Ok(None) => clock.advance(Duration::from_millis(50)),
+                Ok(Some(_)) => panic!("request should fail"),
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(
+            err,
+            CompletionError::IOError(std::io::ErrorKind::TimedOut, _)
+        ));
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 4);
+
+        // Attempts that never answer time out, and no retry starts past the deadline.
+        let client = RetryingRangeClient::new(
+            Arc::new(SilentClient),
+            RetryPolicy {
+                max_attempts: u32::MAX,
+                ..policy()
+            },
+        )
+        .with_clock(clock.clone());
+        let mut request = client.get_range("http://x/f", 0, 10).unwrap();
+        let mut waited = Duration::ZERO;
+        let err = loop {
+            match request.poll() {
+                Ok(None) => {
+                    clock.advance(Duration::from_millis(50));
+                    waited += Duration::from_millis(50);
+                }
+                Ok(Some(_)) => panic!("request should fail"),
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(
+            err,
+            CompletionError::IOError(std::io::ErrorKind::TimedOut, _)
+        ));
+        assert!(waited <= policy().deadline, "{waited:?}");
+    }
+
+    #[test]
+    fn test_io_retries_transient_failures() {
+        let data: Vec<u8> = (0..4096u32).map(|i| i as u8).collect();
+        let server = FakeServer::new("http://x/f", data.clone());
+        // The first request fails.
+        let flaky = FlakyClient::new(server, 1, std::io::ErrorKind::ConnectionReset);
+        let client = RetryingRangeClient::new(
+            flaky.clone(),
+            RetryPolicy {
+                initial_backoff: Duration::ZERO,
+                ..policy()
+            },
+        );
+        let io = HttpRangeIO::with_cache(Arc::new(client), 1024, 8).unwrap();
+        let file = io
+            .open_file("http://x/f", OpenFlags::ReadOnly, false)
+            .unwrap();
+        assert_eq!(file.size().unwrap(), 4096);
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 2);
+
+        flaky.failures.store(1, Ordering::SeqCst);
+        assert_eq!(read(&io, &file, 2100, 100), data[2100..2200]);
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 4);
+    }
+}
@@ -1,13 +1,15 @@
 #![allow(clippy::arc_with_non_send_sync)]
 
+use super::lock_level::{LockLevels, RangeLocks};
 use super::{
-    common, Completion, CompletionInner, File, OpenFlags, SharedWalLockKind, SharedWalMappedRegion,
-    IO,
+    common, Completion, CompletionInner, File, LockLevel, OpenFlags, SharedWalLockKind,
+    SharedWalMappedRegion, IO,
 };
 use crate::error::io_error;
 use crate::io::clock::{Clock, DefaultClock, MonotonicInstant, WallClockInstant};
 use crate::io::unix::{
-    unix_shared_wal_lock_byte, unix_shared_wal_map, unix_shared_wal_unlock_byte,
+    unix_lock_range, unix_shared_wal_lock_byte, unix_shared_wal_map, unix_shared_wal_unlock_byte,
+    unix_unlock_range,
 };
 use crate::storage::wal::CKPT_BATCH_PAGES;
 use crate::sync::Mutex;
@@ -191,14 +193,42 @@ impl IovecPool {
     }
 }
 
+/// Ring setup for [UringIO].
+#[derive(Debug, Clone, Copy)]
+pub struct UringOptions {
+    /// Size of the submission and completion queues.
+    pub entries: u32,
+    /// Idle timeout in milliseconds of the SQPOLL kernel thread, or `None` to
+    /// submit with `io_uring_enter` instead. If the kernel refuses SQPOLL
+    /// (e.g. missing privileges) the ring falls back to regular submission.
+    pub sqpoll_idle: Option<u32>,
+}
+
+impl Default for UringOptions {
+    fn default() -> Self {
+        Self {
+            entries: ENTRIES,
+            sqpoll_idle: Some(SQPOLL_IDLE),
+        }
+    }
+}
+
 impl UringIO {
     pub fn new() -> Result<Self> {
-        let ring = match io_uring::IoUring::builder()
-            .setup_sqpoll(SQPOLL_IDLE)
-            .build(ENTRIES)
-        {
-            Ok(ring) => ring,
-            Err(_) => io_uring::IoUring::new(ENTRIES).map_err(|e| io_error(e, "io_uring_setup"))?,
+        Self::with_options(UringOptions::default())
+    }
+
+    pub fn with_options(options: UringOptions) -> Result<Self> {
+        let sqpoll_ring = options.sqpoll_idle.and_then(|idle| {
+            io_uring::IoUring::builder()
+                .setup_sqpoll(idle)
+                .build(options.entries)
+                .ok()
+        });
+        let ring = match sqpoll_ring {
+            Some(ring) => ring,
+            None => io_uring::IoUring::new(options.entries)
+                .map_err(|e| io_error(e, "io_uring_setup"))?,
         };
         // RL_MEMLOCK cap is typically 8MB, the current design is to have one large arena
         // registered at startup and therefore we can simply use the zero index, falling back
@@ -459,6 +489,7 @@ impl IO for UringIO {
             state: self.state.clone(),
             caps: self.caps.clone(),
             file,
+            lock_levels: LockLevels::new(),
         });
         if std::env::var(common::ENV_DISABLE_FILE_LOCK).is_err()
             && !flags.intersects(OpenFlags::ReadOnly | OpenFlags::NoLock)
@@ -665,6 +696,7 @@ pub struct UringFile {
     state: Arc<Mutex<RingState>>,
     caps: Arc<UringCapabilities>,
     file: std::fs::File,
+    lock_levels: LockLevels,
 }
 
 impl Deref for UringFile {
@@ -678,6 +710,19 @@ unsafe impl Send for UringFile {}
 unsafe impl Sync for UringFile {}
 crate::assert::assert_send_sync!(UringFile);
 
+/// The same POSIX record locks as [super::unix::UnixFile].
+impl RangeLocks for UringFile {
+    const CONVERTS_IN_PLACE: bool = true;
+
+    fn lock_range(&self, start: u64, len: u64, exclusive: bool) -> Result<bool> {
+        unix_lock_range(self.file.as_raw_fd(), start, len, exclusive)
+    }
+
+    fn unlock_range(&self, start: u64, len: u64) -> Result<()> {
+        unix_unlock_range(self.file.as_raw_fd(), start, len)
+    }
+}
+
 impl File for UringFile {
     fn lock_file(&self, exclusive: bool) -> Result<()> {
         let fd = self.file.as_fd();
@@ -701,6 +746,7 @@ impl File for UringFile {
             };
             LimboError::LockingError(message)
         })?;
+        self.lock_levels.whole_file_locked();
 
         Ok(())
     }
@@ -713,7 +759,19 @@ impl File for UringFile {
                 std::io::Error::from(e)
             ))
         })?;
-        Ok(())
+        self.lock_levels.whole_file_unlocked(self)
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        self.lock_levels.lock(self, level)
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        self.lock_levels.unlock(self, level)
+    }
+
+    fn check_reserved_lock(&self) -> Result<bool> {
+        self.lock_levels.check_reserved(self)
     }
 
     fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
@@ -897,4 +955,14 @@ mod tests {
     fn test_multiple_processes_cannot_open_file() {
         common::tests::test_multiple_processes_cannot_open_file(UringIO::new);
     }
+
+    #[test]
+    fn test_multiple_processes_cannot_open_file_without_sqpoll() {
+        common::tests::test_multiple_processes_cannot_open_file(|| {
+            UringIO::with_options(UringOptions {
+                sqpoll_idle: None,
+                ..Default::default()
+            })
+        });
+    }
 }
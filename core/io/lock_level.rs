@@ -0,0 +1,307 @@
+//! SQLite's lock levels on top of byte-range locks, for the files of the
+//! built-in Unix and Windows backends.
+//!
+//! The levels are mapped onto the same bytes SQLite locks, so that Turso and
+//! SQLite exclude each other: one byte at `PENDING_BYTE` for PENDING, the next
+//! one for RESERVED, and the `SHARED_SIZE` bytes after them for SHARED
+//! (read-locked) and EXCLUSIVE (write-locked). These bytes are in the pending
+//! byte page, which never holds data, so the mandatory locks of Windows don't
+//! get in the way of I/O. Like SQLite's own locks on Unix, POSIX locks only
+//! exclude other processes.
+//!
+//! The whole-file lock taken when a file is opened already keeps every other
+//! process out. While it is held the levels are only recorded: unlocking their
+//! bytes would punch holes into it, as POSIX locks of a process merge. The
+//! levels only take byte-range locks for files opened with
+//! `OpenFlags::NoLock`.
+
+use crate::sync::Mutex;
+use crate::{LimboError, Result};
+use turso_ext::LockLevel;
+
+pub(crate) const PENDING_BYTE: u64 = 0x4000_0000;
+pub(crate) const RESERVED_BYTE: u64 = PENDING_BYTE + 1;
+pub(crate) const SHARED_FIRST: u64 = PENDING_BYTE + 2;
+pub(crate) const SHARED_SIZE: u64 = 510;
+
+/// Byte-range locks of one open file.
+pub(crate) trait RangeLocks {
+    /// Whether a read lock can be turned into a write lock, and back, without
+    /// releasing it first.
+    const CONVERTS_IN_PLACE: bool;
+
+    /// Lock `len` bytes at `start` without waiting. Returns `false` if another
+    /// process holds a conflicting lock.
+    fn lock_range(&self, start: u64, len: u64, exclusive: bool) -> Result<bool>;
+
+    fn unlock_range(&self, start: u64, len: u64) -> Result<()>;
+}
+
+fn acquired(result: Result<bool>) -> Result<()> {
+    match result? {
+        true => Ok(()),
+        false => Err(LimboError::Busy),
+    }
+}
+
+struct State {
+    level: LockLevel,
+    /// Whether the file holds the whole-file lock of `File::lock_file`.
+    whole_file: bool,
+}
+
+/// Lock level held by one open file.
+pub(crate) struct LockLevels {
+    state: Mutex<State>,
+}
+
+impl LockLevels {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                level: LockLevel::None,
+                whole_file: false,
+            }),
+        }
+    }
+
+    /// Record that the file took its whole-file lock, which covers the bytes
+    /// of every level.
+    pub(crate) fn whole_file_locked(&self) {
+        self.state.lock().whole_file = true;
+    }
+
+    /// Record that the file released its whole-file lock, and with it the
+    /// byte-range locks of every level, and take back the level it held.
+    pub(crate) fn whole_file_unlocked(&self, file: &impl RangeLocks) -> Result<()> {
+        let mut state = self.state.lock();
+        state.whole_file = false;
+        let level = std::mem::replace(&mut state.level, LockLevel::None);
+        Self::raise(&mut state.level, file, level)
+    }
+
+    /// Raise the lock to `level`, passing through the levels in between. On
+    /// failure the lock stays at the last level reached, as SQLite's does.
+    pub(crate) fn lock(&self, file: &impl RangeLocks, level: LockLevel) -> Result<()> {
+        let mut state = self.state.lock();
+        if state.whole_file {
+            state.level = state.level.max(level);
+            return Ok(());
+        }
+        Self::raise(&mut state.level, file, level)
+    }
+
+    fn raise(current: &mut LockLevel, file: &impl RangeLocks, level: LockLevel) -> Result<()> {
+        if *current >= level {
+            return Ok(());
+        }
+        if *current == LockLevel::None {
+            // Nobody gets a new SHARED lock while a writer holds PENDING.
+            acquired(file.lock_range(PENDING_BYTE, 1, false))?;
+            let shared = file.lock_range(SHARED_FIRST, SHARED_SIZE, false);
+            file.unlock_range(PENDING_BYTE, 1)?;
+            acquired(shared)?;
+            *current = LockLevel::Shared;
+        }
+        if level >= LockLevel::Reserved && *current < LockLevel::Reserved {
+            acquired(file.lock_range(RESERVED_BYTE, 1, true))?;
+            *current = LockLevel::Reserved;
+        }
+        if level >= LockLevel::Pending && *current < LockLevel::Pending {
+            acquired(file.lock_range(PENDING_BYTE, 1, true))?;
+            *current = LockLevel::Pending;
+        }
+        if level == LockLevel::Exclusive {
+            Self::convert_shared(file, true)?;
+            *current = LockLevel::Exclusive;
+        }
+        Ok(())
+    }
+
+    /// Lower the lock to `level`, `LockLevel::Shared` or `LockLevel::None`.
+    pub(crate) fn unlock(&self, file: &impl RangeLocks, level: LockLevel) -> Result<()> {
+        if level > LockLevel::Shared {
+            return Err(LimboError::InternalError(format!(
+                "can't unlock a file to {level:?}"
+            )));
+        }
+        let mut state = self.state.lock();
+        if state.whole_file {
+            state.level = state.level.min(level);
+            return Ok(());
+        }
+        let current = &mut state.level;
+        if *current <= level {
+            return Ok(());
+        }
+        if *current == LockLevel::Exclusive && level == LockLevel::Shared {
+            Self::convert_shared(file, false)?;
+        }
+        if *current >= LockLevel::Pending {
+            file.unlock_range(PENDING_BYTE, 1)?;
+        }
+        if *current >= LockLevel::Reserved {
+            file.unlock_range(RESERVED_BYTE, 1)?;
+        }
+        if level == LockLevel::None {
+            file.unlock_range(SHARED_FIRST, SHARED_SIZE)?;
+        }
+        *current = level;
+        Ok(())
+    }
+
+    /// Whether this file, or another process, holds a RESERVED lock or
+    /// stronger.
+    pub(crate) fn check_reserved(&self, file: &impl RangeLocks) -> Result<bool> {
+        let state = self.state.lock();
+        if state.level >= LockLevel::Reserved {
+            return Ok(true);
+        }
+        if state.whole_file {
+            // No other process can hold any lock on the file.
+            return Ok(false);
+        }
+        // Locks can't be queried portably: try to take it.
+        if file.lock_range(RESERVED_BYTE, 1, true)? {
+            file.unlock_range(RESERVED_BYTE, 1)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Turn the read lock on the SHARED bytes into a write lock, or back.
+    fn convert_shared<F: RangeLocks>(file: &F, exclusive: bool) -> Result<()> {
+        if !F::CONVERTS_IN_PLACE {
+            file.unlock_range(SHARED_FIRST, SHARED_SIZE)?;
+        }
+        let result = file.lock_range(SHARED_FIRST, SHARED_SIZE, exclusive);
+        if !F::CONVERTS_IN_PLACE && !matches!(result, Ok(true)) {
+            // Take the read lock back, as the lock stays at its level.
+            file.lock_range(SHARED_FIRST, SHARED_SIZE, false)?;
+        }
+        acquired(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Locks of two "processes" on the same file.
+    #[derive(Default)]
+    struct FakeLocks {
+        /// Lock of every byte held by each process, `true` if exclusive.
+        held: Mutex<[HashMap<u64, bool>; 2]>,
+    }
+
+    struct Process<'a> {
+        locks: &'a FakeLocks,
+        id: usize,
+    }
+
+    impl RangeLocks for Process<'_> {
+        const CONVERTS_IN_PLACE: bool = false;
+
+        fn lock_range(&self, start: u64, len: u64, exclusive: bool) -> Result<bool> {
+            let mut held = self.locks.held.lock();
+            let other = &held[1 - self.id];
+            if (start..start + len)
+                .any(|byte| other.get(&byte).is_some_and(|other| exclusive || *other))
+            {
+                return Ok(false);
+            }
+            for byte in start..start + len {
+                held[self.id].insert(byte, exclusive);
+            }
+            Ok(true)
+        }
+
+        fn unlock_range(&self, start: u64, len: u64) -> Result<()> {
+            let mut held = self.locks.held.lock();
+            for byte in start..start + len {
+                held[self.id].remove(&byte);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lock_levels_exclude_each_other() {
+        let locks = FakeLocks::default();
+        let (a, b) = (
+            Process {
+                locks: &locks,
+                id: 0,
+            },
+            Process {
+                locks: &locks,
+                id: 1,
+            },
+        );
+        let (levels_a, levels_b) = (LockLevels::new(), LockLevels::new());
+
+        levels_a.lock(&a, LockLevel::Shared).unwrap();
+        levels_b.lock(&b, LockLevel::Shared).unwrap();
+        levels_a.lock(&a, LockLevel::Reserved).unwrap();
+        assert!(levels_b.check_reserved(&b).unwrap());
+        assert!(matches!(
+            levels_b.lock(&b, LockLevel::Reserved),
+            Err(LimboError::Busy)
+        ));
+        // A reader is still there: A waits at PENDING, which keeps new
+        // readers out.
+        assert!(matches!(
+            levels_a.lock(&a, LockLevel::Exclusive),
+            Err(LimboError::Busy)
+        ));
+        levels_b.unlock(&b, LockLevel::None).unwrap();
+        assert!(matches!(
+            levels_b.lock(&b, LockLevel::Shared),
+            Err(LimboError::Busy)
+        ));
+        levels_a.lock(&a, LockLevel::Exclusive).unwrap();
+
+        levels_a.unlock(&a, LockLevel::Shared).unwrap();
+        assert!(!levels_b.check_reserved(&b).unwrap());
+        levels_b.lock(&b, LockLevel::Exclusive).unwrap_err();
+        levels_a.unlock(&a, LockLevel::None).unwrap();
+        // The failed attempt left B at PENDING, and A is gone now.
+        levels_b.lock(&b, LockLevel::Exclusive).unwrap();
+        assert!(levels_a.check_reserved(&a).unwrap());
+        assert!(levels_b.unlock(&b, LockLevel::Reserved).is_err());
+    }
+
+    #[test]
+    fn test_lock_levels_under_whole_file_lock_are_recorded() {
+        let locks = FakeLocks::default();
+        let (a, b) = (
+            Process {
+                locks: &locks,
+                id: 0,
+            },
+            Process {
+                locks: &locks,
+                id: 1,
+            },
+        );
+        let (levels_a, levels_b) = (LockLevels::new(), LockLevels::new());
+
+        levels_a.whole_file_locked();
+        levels_a.lock(&a, LockLevel::Reserved).unwrap();
+        assert!(levels_a.check_reserved(&a).unwrap());
+        levels_a.unlock(&a, LockLevel::Shared).unwrap();
+        assert!(!levels_a.check_reserved(&a).unwrap());
+        // Nothing was locked or unlocked under the whole-file lock.
+        assert!(locks.held.lock()[0].is_empty());
+
+        // Releasing it takes the level back on its own bytes.
+        levels_a.whole_file_unlocked(&a).unwrap();
+        assert!(matches!(
+            levels_b.lock(&b, LockLevel::Exclusive),
+            Err(LimboError::Busy)
+        ));
+        levels_a.unlock(&a, LockLevel::None).unwrap();
+        levels_b.lock(&b, LockLevel::Exclusive).unwrap();
+    }
+}
@@ -13,13 +13,14 @@ use std::fmt;
 use std::ptr::NonNull;
 use std::sync::LazyLock;
 use std::{fmt::Debug, pin::Pin};
+pub use turso_ext::LockLevel;
 use turso_macros::AtomicEnum;
 
 cfg_block! {
     #[cfg(all(target_os = "linux", feature = "io_uring", not(miri)))] {
         mod io_uring;
         #[cfg(feature = "fs")]
-        pub use io_uring::UringIO;
+        pub use io_uring::{UringIO, UringOptions};
     }
 
     #[cfg(all(target_family = "unix", not(miri)))] {
@@ -52,14 +53,45 @@ cfg_block! {
     }
 }
 
+mod compressed;
+mod content_addressed;
+#[cfg(feature = "io_fault")]
+mod fault;
+mod http_range;
+#[cfg(all(any(target_family = "unix", target_os = "windows"), not(miri)))]
+mod lock_level;
 mod memory;
 #[cfg(feature = "io_memory_yield")]
 mod memory_yield;
+mod object_store;
+mod quota;
 #[cfg(feature = "fs")]
 mod vfs;
+#[cfg(feature = "compression")]
+pub use compressed::Lz4Codec;
+pub use compressed::{CompressedFile, CompressedFileStats, CompressedIO, PageCodec};
+pub use content_addressed::{
+    ContentAddressedFile, ContentAddressedIO, ContentAddressedStats, CONTENT_ADDRESSED_BLOCK_SIZE,
+};
+#[cfg(feature = "io_fault")]
+pub use fault::{Fault, FaultIO, FaultOp, FaultSchedule};
+pub use http_range::{
+    HttpRangeFile, HttpRangeIO, HttpRangeStats, RangeClient, RangeRequest, RangeResponse,
+    RetryPolicy, RetryingRangeClient, HTTP_RANGE_DEFAULT_CACHE_CHUNKS,
+    HTTP_RANGE_DEFAULT_CHUNK_SIZE,
+};
 pub use memory::MemoryIO;
 #[cfg(feature = "io_memory_yield")]
 pub use memory_yield::MemoryYieldIO;
+pub use object_store::{
+    ObjectRequest, ObjectStore, ObjectStoreFile, ObjectStoreIO, OBJECT_STORE_DEFAULT_BLOCK_SIZE,
+    OBJECT_STORE_DEFAULT_PART_SIZE,
+};
+pub use quota::QuotaIO;
+#[cfg(feature = "fs")]
+pub(crate) use vfs::VfsCounters;
+#[cfg(feature = "fs")]
+pub use vfs::VfsStats;
 pub mod clock;
 mod common;
 mod completions;
@@ -87,6 +119,13 @@ impl FileId {
             ino: hasher.finish(),
         }
     }
+
+    /// Whether this identity may come from [Self::from_path_hash], and so only
+    /// tells paths apart rather than files. A real file on a device numbered 0
+    /// is reported as synthetic too.
+    pub const fn is_synthetic(&self) -> bool {
+        self.dev == 0
+    }
 }
 
 /// Return the OS-level file identity for a path.
@@ -153,6 +192,25 @@ pub trait SharedWalMappedRegion: Send + Sync {
 pub trait File: Send + Sync {
     fn lock_file(&self, exclusive: bool) -> Result<()>;
     fn unlock_file(&self) -> Result<()>;
+    /// Raise the SQLite lock level of the file to `level`, passing through the
+    /// levels in between, or fail with [crate::LimboError::Busy] if another
+    /// process is in the way. Does nothing if the lock is already at least
+    /// that strong. The pager calls it as transactions begin. The default
+    /// does nothing: backends without levels rely on the lock taken by
+    /// `lock_file` when the file is opened, if any.
+    fn lock_level(&self, _level: LockLevel) -> Result<()> {
+        Ok(())
+    }
+    /// Lower the lock level of the file to `level`, which is either
+    /// `LockLevel::Shared` or `LockLevel::None`.
+    fn unlock_level(&self, _level: LockLevel) -> Result<()> {
+        Ok(())
+    }
+    /// Whether this file, or another process, holds a RESERVED lock or
+    /// stronger on the file.
+    fn check_reserved_lock(&self) -> Result<bool> {
+        Ok(false)
+    }
     fn pread(&self, pos: u64, c: Completion) -> Result<Completion>;
     fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion>;
     /// Sync file data&metadata to disk.
@@ -215,6 +273,26 @@ pub trait File: Send + Sync {
         panic!("punch_hole is not supported for the given IO implementation")
     }
 
+    /// Serve reads of the first `limit` bytes of the file from a read-only
+    /// memory mapping instead of one system call per read. 0 removes the
+    /// mapping. Files or platforms that can't be mapped keep using regular
+    /// reads, so this is only a hint. Returns the limit now in effect.
+    fn set_mmap_limit(&self, _limit: u64) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// The limit set with [File::set_mmap_limit].
+    fn mmap_limit(&self) -> u64 {
+        0
+    }
+
+    /// Hint that the file is about to grow to `len` bytes, so that the space
+    /// can be reserved before the writes that need it. The size of the file
+    /// doesn't change.
+    fn allocate(&self, _len: u64) -> Result<()> {
+        Ok(())
+    }
+
     fn shared_wal_lock_byte(
         &self,
         _offset: u64,
@@ -289,26 +367,144 @@ pub trait File: Send + Sync {
     }
 }
 
+/// Where a connection creates its temp files (sorter and hash join spills,
+/// ephemeral tables, VACUUM output and the temp database).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TempDirectory {
+    /// The OS default temp directory.
+    #[default]
+    System,
+    /// A directory configured by the user (`PRAGMA temp_store_directory`,
+    /// `TURSO_TMPDIR` or `SQLITE_TMPDIR`). Failing to create temp files here
+    /// is an error.
+    Explicit(std::path::PathBuf),
+    /// The directory holding the main database file. Falls back to the OS
+    /// default when it is not writable (e.g. a read-only database).
+    DatabaseDir(std::path::PathBuf),
+}
+
+/// `TURSO_TMPDIR`, or else `SQLITE_TMPDIR`. Like SQLite, the environment is
+/// read once per process rather than every time a temp file is opened.
+static TEMP_DIRECTORY_FROM_ENV: LazyLock<Option<std::path::PathBuf>> = LazyLock::new(|| {
+    std::env::var_os("TURSO_TMPDIR")
+        .or_else(|| std::env::var_os("SQLITE_TMPDIR"))
+        .map(Into::into)
+});
+
+impl TempDirectory {
+    /// The directory configured through the environment, if any.
+    pub(crate) fn from_env() -> Option<Self> {
+        TEMP_DIRECTORY_FROM_ENV.clone().map(TempDirectory::Explicit)
+    }
+
+    /// Create a uniquely named file starting with `prefix` in this directory,
+    /// opened through `io` like any other database file. Returns the file and
+    /// its path, which removes it through `io` when dropped.
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn create_file(
+        &self,
+        io: &Arc<dyn IO>,
+        prefix: &str,
+    ) -> Result<(Arc<dyn File>, TempPath)> {
+        match self {
+            TempDirectory::System => TempPath::create(io, &std::env::temp_dir(), prefix),
+            TempDirectory::Explicit(dir) => TempPath::create(io, dir, prefix),
+            TempDirectory::DatabaseDir(dir) => TempPath::create(io, dir, prefix)
+                .or_else(|_| TempPath::create(io, &std::env::temp_dir(), prefix)),
+        }
+    }
+}
+
+/// Name prefix of the files [TempFile] creates.
+pub(crate) const TEMP_FILE_NAME: &str = "tursodb_temp_file";
+
+/// Whether `path` is a file created by [TempFile]. Spilled rows and ephemeral
+/// tables aren't part of the database, so IO backends that store database files
+/// in a special way open these as plain files of the IO they wrap.
+pub(crate) fn is_temp_file(path: &str) -> bool {
+    std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(TEMP_FILE_NAME))
+}
+
+/// Path of a temp file created by [TempDirectory::create_file]. Dropping it
+/// removes the file through the IO that created it, along with the WAL and
+/// journal files of a database opened on it.
+pub(crate) struct TempPath {
+    io: Arc<dyn IO>,
+    path: String,
+}
+
+impl TempPath {
+    #[cfg(not(target_family = "wasm"))]
+    fn create(
+        io: &Arc<dyn IO>,
+        dir: &std::path::Path,
+        prefix: &str,
+    ) -> Result<(Arc<dyn File>, Self)> {
+        let name = format!(
+            "{prefix}-{}-{:016x}",
+            std::process::id(),
+            io.generate_random_number() as u64
+        );
+        let path = dir.join(name).to_str().map(str::to_string).ok_or_else(|| {
+            crate::LimboError::InternalError("temp file path is not valid UTF-8".to_string())
+        })?;
+        let file = io.open_file(&path, OpenFlags::Create, false)?;
+        Ok((
+            file,
+            TempPath {
+                io: io.clone(),
+                path,
+            },
+        ))
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn as_str(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-tshm", "-log", "-journal"] {
+            let _ = self.io.remove_file(&format!("{}{suffix}", self.path));
+        }
+    }
+}
+
 pub struct TempFile {
-    /// When temp_dir is dropped the folder is deleted
-    /// set to None if tempfile allocated in memory (for example, in case of WASM target)
-    _temp_dir: Option<tempfile::TempDir>,
     pub(crate) file: Arc<dyn File>,
+    /// Removes the file once it is closed. `None` if the temp file is
+    /// allocated in memory (for example, in case of WASM target).
+    _path: Option<TempPath>,
 }
 
 impl TempFile {
     pub fn new(io: &Arc<dyn IO>) -> Result<Self> {
+        Self::new_in(io, &TempDirectory::System)
+    }
+
+    /// Creates a file-backed TempFile under `temp_dir`. The file is opened
+    /// through `io`, so it goes through the same VFS as the database itself.
+    pub fn new_in(io: &Arc<dyn IO>, temp_dir: &TempDirectory) -> Result<Self> {
         #[cfg(not(target_family = "wasm"))]
         {
-            let temp_dir = tempfile::tempdir().map_err(|e| crate::error::io_error(e, "tempdir"))?;
-            let chunk_file_path = temp_dir.as_ref().join("tursodb_temp_file");
-            let chunk_file_path_str = chunk_file_path.to_str().ok_or_else(|| {
-                crate::LimboError::InternalError("temp file path is not valid UTF-8".to_string())
-            })?;
-            let chunk_file = io.open_file(chunk_file_path_str, OpenFlags::Create, false)?;
+            let (file, path) = temp_dir.create_file(io, TEMP_FILE_NAME)?;
+            // Like SQLite's delete-on-close temp files: unlink the file while it
+            // is open, so that its space is reclaimed as soon as the handle goes
+            // away, even if the process crashes. Only files on the host
+            // filesystem can outlive their name; IO backends that keep files
+            // elsewhere remove them when the TempFile is dropped.
+            #[cfg(unix)]
+            if std::path::Path::new(path.as_str()).exists() {
+                let _ = io.remove_file(path.as_str());
+            }
             Ok(TempFile {
-                _temp_dir: Some(temp_dir),
-                file: chunk_file.clone(),
+                file,
+                _path: Some(path),
             })
         }
         // on WASM in browser we do not support temp files (as we pre-register db files in advance and can't easily create a new one)
@@ -317,31 +513,36 @@ impl TempFile {
         {
             use crate::MemoryIO;
 
+            let _ = (io, temp_dir);
             let memory_io = Arc::new(MemoryIO::new());
-            let memory_file = memory_io.open_file("tursodb_temp_file", OpenFlags::Create, false)?;
+            let memory_file = memory_io.open_file(TEMP_FILE_NAME, OpenFlags::Create, false)?;
             Ok(TempFile {
-                _temp_dir: None,
                 file: memory_file,
+                _path: None,
             })
         }
     }
 
     /// Creates a TempFile respecting the temp_store setting.
     /// When temp_store is Memory, uses in-memory storage.
-    /// When temp_store is Default or File, uses file-based storage when
-    /// available. In `no-fs` builds, temp storage always falls back to memory.
-    pub fn with_temp_store(io: &Arc<dyn IO>, temp_store: crate::TempStore) -> Result<Self> {
+    /// When temp_store is Default or File, uses file-based storage under
+    /// `temp_dir` when available. In `no-fs` builds, temp storage always
+    /// falls back to memory.
+    pub fn with_temp_store(
+        io: &Arc<dyn IO>,
+        temp_store: crate::TempStore,
+        temp_dir: &TempDirectory,
+    ) -> Result<Self> {
         #[cfg(not(target_family = "wasm"))]
         {
             #[cfg(not(feature = "fs"))]
             {
-                let _ = (io, temp_store);
+                let _ = (io, temp_store, temp_dir);
                 let memory_io = Arc::new(MemoryIO::new());
-                let memory_file =
-                    memory_io.open_file("tursodb_temp_file", OpenFlags::Create, false)?;
+                let memory_file = memory_io.open_file(TEMP_FILE_NAME, OpenFlags::Create, false)?;
                 Ok(TempFile {
-                    _temp_dir: None,
                     file: memory_file,
+                    _path: None,
                 })
             }
             #[cfg(feature = "fs")]
@@ -349,21 +550,21 @@ impl TempFile {
                 if matches!(temp_store, crate::TempStore::Memory) {
                     let memory_io = Arc::new(MemoryIO::new());
                     let memory_file =
-                        memory_io.open_file("tursodb_temp_file", OpenFlags::Create, false)?;
+                        memory_io.open_file(TEMP_FILE_NAME, OpenFlags::Create, false)?;
                     return Ok(TempFile {
-                        _temp_dir: None,
                         file: memory_file,
+                        _path: None,
                     });
                 }
                 // Fall through to file-based for Default and File modes
-                Self::new(io)
+                Self::new_in(io, temp_dir)
             }
         }
         #[cfg(target_family = "wasm")]
         {
             // WASM always uses memory, ignore temp_store setting
             let _ = temp_store;
-            Self::new(io)
+            Self::new_in(io, temp_dir)
         }
     }
 }
@@ -398,7 +599,22 @@ impl Default for OpenFlags {
     }
 }
 
+/// Largest logical block size direct I/O has to be aligned to.
+pub(crate) const DIRECT_IO_ALIGNMENT: usize = 4096;
+
 pub trait IO: Clock + Send + Sync {
+    /// Open the file at `path`.
+    ///
+    /// With `direct` the backend should bypass the OS page cache where it can:
+    /// `O_DIRECT` on Linux, `F_NOCACHE` on macOS. It is a hint, a filesystem that
+    /// refuses it (tmpfs on older kernels, for example) leaves the file buffered.
+    /// Once enabled on Linux, every read and write must start at an offset and
+    /// cover a length that are multiples of the device's logical block size
+    /// (512 or 4096 bytes), from memory aligned the same way, or it fails with
+    /// `EINVAL`. Whole pages read into buffers from the
+    /// [BufferPool](crate::storage::buffer_pool::BufferPool) arena meet that, as
+    /// do buffers from [Buffer::new_aligned], so it is only meant for database
+    /// files, never for WAL or journal files.
     fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>>;
 
     fn open_shared_wal_file(&self, path: &str) -> Result<Arc<dyn File>> {
@@ -408,6 +624,14 @@ pub trait IO: Clock + Send + Sync {
     // remove_file is used in the sync-engine
     fn remove_file(&self, path: &str) -> Result<()>;
 
+    /// Make `target` a copy of `source` that can diverge from it afterwards.
+    /// Only backends that can do this cheaply implement it, see [ContentAddressedIO].
+    fn branch_file(&self, _source: &str, _target: &str) -> Result<()> {
+        Err(crate::LimboError::InternalError(
+            "branching files is not supported by this IO backend".to_string(),
+        ))
+    }
+
     /// Whether this IO backend can back host-filesystem shared WAL coordination.
     fn supports_shared_wal_coordination(&self) -> bool {
         false
@@ -645,12 +869,20 @@ pub enum Buffer {
         start: usize,
     },
     Pooled(ArenaBuffer),
+    /// An over-allocated heap buffer exposing `data[start..start + len]`,
+    /// with `start` chosen so the exposed bytes are aligned for direct I/O.
+    Aligned {
+        data: BufferData,
+        start: usize,
+        len: usize,
+    },
 }
 
 impl Debug for Buffer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Pooled(p) => write!(f, "Pooled(len={})", p.logical_len()),
+            Self::Aligned { len, .. } => write!(f, "Aligned(len={len})"),
             Self::Heap(buf) => write!(f, "{buf:?}: {}", buf.len()),
             Self::Shared(buf) => write!(f, "Shared(len={})", buf.len()),
             Self::HeapView { data, start } => {
@@ -677,7 +909,7 @@ impl Drop for Buffer {
                     cache.return_buffer(buffer, underlying_len);
                 });
             }
-            Self::Pooled(_) | Self::Shared(_) => {}
+            Self::Pooled(_) | Self::Shared(_) | Self::Aligned { .. } => {}
         }
     }
 }
@@ -713,11 +945,25 @@ impl Buffer {
         }
     }
 
+    /// A zeroed heap buffer of `len` bytes whose start is aligned to `align`,
+    /// for writes to a file opened with direct I/O outside the buffer pool.
+    pub fn new_aligned(len: usize, align: usize) -> Self {
+        assert!(
+            align.is_power_of_two(),
+            "Buffer::new_aligned: align ({align}) is not a power of two"
+        );
+        let data = Pin::new(vec![0; len + align - 1].into_boxed_slice());
+        let start = data.as_ptr().align_offset(align);
+        Self::Aligned { data, start, len }
+    }
+
     /// Returns the index of the underlying `Arena` if it was registered with
     /// io_uring. Only for use with `UringIO` backend.
     pub fn fixed_id(&self) -> Option<u32> {
         match self {
-            Self::Heap(..) | Self::HeapView { .. } | Self::Shared(..) => None,
+            Self::Heap(..) | Self::HeapView { .. } | Self::Shared(..) | Self::Aligned { .. } => {
+                None
+            }
             Self::Pooled(buf) => buf.fixed_id(),
         }
     }
@@ -742,6 +988,7 @@ impl Buffer {
             Self::Shared(buf) => buf.len(),
             Self::HeapView { data, start } => data.len() - *start,
             Self::Pooled(buf) => buf.logical_len(),
+            Self::Aligned { len, .. } => *len,
         }
     }
 
@@ -764,6 +1011,7 @@ impl Buffer {
                 }
             }
             Self::Pooled(buf) => buf,
+            Self::Aligned { data, start, len } => &data[*start..*start + *len],
         }
     }
 
@@ -778,6 +1026,7 @@ impl Buffer {
             Self::Shared(buf) => buf.as_ptr(),
             Self::HeapView { data, start } => unsafe { data.as_ptr().add(*start) },
             Self::Pooled(buf) => buf.as_ptr(),
+            Self::Aligned { data, start, .. } => unsafe { data.as_ptr().add(*start) },
         }
     }
     #[inline]
@@ -787,6 +1036,7 @@ impl Buffer {
             Self::Shared(_) => panic!("Buffer::Shared is immutable"),
             Self::HeapView { data, start } => unsafe { (data.as_ptr() as *mut u8).add(*start) },
             Self::Pooled(buf) => buf.as_ptr() as *mut u8,
+            Self::Aligned { data, start, .. } => unsafe { (data.as_ptr() as *mut u8).add(*start) },
         }
     }
 
@@ -797,7 +1047,10 @@ impl Buffer {
 
     #[inline]
     pub fn is_heap(&self) -> bool {
-        matches!(self, Self::Heap(..) | Self::HeapView { .. })
+        matches!(
+            self,
+            Self::Heap(..) | Self::HeapView { .. } | Self::Aligned { .. }
+        )
     }
 }
 
@@ -846,6 +1099,17 @@ mod buffer_tests {
         assert_eq!(buffer.as_slice(), &[2, 3, 4]);
         assert_eq!(buffer.as_ptr(), shared.as_ptr());
     }
+
+    #[test]
+    fn aligned_buffer_starts_on_alignment() {
+        for len in [512, 4096, 65536] {
+            let buffer = Buffer::new_aligned(len, DIRECT_IO_ALIGNMENT);
+            assert_eq!(buffer.len(), len);
+            assert_eq!(buffer.as_ptr() as usize % DIRECT_IO_ALIGNMENT, 0);
+            buffer.as_mut_slice().fill(7);
+            assert!(buffer.as_slice().iter().all(|&b| b == 7));
+        }
+    }
 }
 
 /// A cache for temporary or any additional `Buffer` allocations beyond
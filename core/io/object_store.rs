@@ -0,0 +1,896 @@
+//! IO backend keeping database files in an object store.
+//!
+//! Object stores don't update objects in place, and every request is slow and
+//! billed, so a database file is stored as a log of immutable segment objects
+//! plus an index object. Writes only touch memory. A sync uploads all the
+//! blocks dirtied since the previous one as a single segment (a multipart
+//! upload once it is larger than the part size), then replaces the index,
+//! which maps every block of the file to the segment holding its latest
+//! version. The index upload is the commit point: a file reopened after a
+//! failed sync sees the previous index, and segments it doesn't reference are
+//! simply unused. Superseded segments are left in the store.
+//!
+//! Reads fetch single blocks with ranged `GET`s through the embedder's
+//! [ObjectStore] and keep a bounded number of them in memory. As with
+//! [HttpRangeIO](super::HttpRangeIO), requests never block and are polled from
+//! [IO::step].
+//!
+//! Only database files go to the store. The WAL and other auxiliary files are
+//! written in small unaligned pieces and synced on every commit, so they stay
+//! on a local [IO]; pages reach the store when they are checkpointed. Temp
+//! files hold spilled rows and are never shared, so they stay local too. The
+//! database file must be opened by a single process at a time.
+
+use super::compressed::AUXILIARY_FILE_SUFFIXES;
+use super::{is_temp_file, Buffer, Clock, Completion, File, FileId, FileSyncType, OpenFlags, IO};
+use crate::error::CompletionError;
+use crate::io::clock::{MonotonicInstant, WallClockInstant};
+use crate::sync::Mutex;
+use crate::{LimboError, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Default block size, the default page size. Databases with another page
+/// size need a matching [ObjectStoreIO::with_block_size].
+pub const OBJECT_STORE_DEFAULT_BLOCK_SIZE: usize = 4096;
+
+/// Default size of the parts of a multipart upload.
+pub const OBJECT_STORE_DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Fetched blocks kept in memory per file.
+const CLEAN_BLOCKS_CAPACITY: usize = 1024;
+
+const INDEX_MAGIC: u32 = 0x746f_626a;
+const INDEX_VERSION: u32 = 1;
+/// magic, version, block size, reserved (u32 each), file size, next segment
+/// and entry count (u64 each).
+const INDEX_HEADER_SIZE: usize = 40;
+/// block, segment and offset in the segment (u64 each).
+const INDEX_ENTRY_SIZE: usize = 24;
+
+/// Transport used by [ObjectStoreIO].
+pub trait ObjectStore: Send + Sync {
+    /// Start fetching object `key`, or only `len` bytes of it at `offset` if
+    /// `range` is given.
+    fn get(&self, key: &str, range: Option<(u64, usize)>) -> Result<Box<dyn ObjectRequest>>;
+
+    /// Start uploading `data` as object `key`, replacing it atomically.
+    fn put(&self, key: &str, data: Vec<u8>) -> Result<Box<dyn ObjectRequest>>;
+
+    /// Start uploading the concatenation of `parts` as object `key`. Stores
+    /// with multipart uploads should send each part as one; by default the
+    /// parts are joined into a single [ObjectStore::put].
+    fn put_multipart(&self, key: &str, parts: Vec<Vec<u8>>) -> Result<Box<dyn ObjectRequest>> {
+        self.put(key, parts.concat())
+    }
+
+    fn delete(&self, key: &str) -> Result<Box<dyn ObjectRequest>>;
+}
+
+/// A request started by an [ObjectStore].
+pub trait ObjectRequest: Send {
+    /// Return the response body once it has arrived (empty for uploads and
+    /// deletes), without blocking. A missing object must be reported as
+    /// `CompletionError::IOError(ErrorKind::NotFound, ..)`.
+    fn poll(&mut self) -> std::result::Result<Option<Vec<u8>>, CompletionError>;
+}
+
+/// Wait for a request during `open_file` and `remove_file`, which are
+/// synchronous, yielding through `io` while it is in flight.
+fn wait(
+    io: &dyn IO,
+    mut request: Box<dyn ObjectRequest>,
+) -> std::result::Result<Vec<u8>, CompletionError> {
+    loop {
+        if let Some(data) = request.poll()? {
+            return Ok(data);
+        }
+        io.yield_now();
+    }
+}
+
+/// Whether `path` stays on the local [IO] rather than going to the store.
+fn is_local_file(path: &str) -> bool {
+    is_temp_file(path)
+        || AUXILIARY_FILE_SUFFIXES
+            .iter()
+            .any(|suffix| path.ends_with(suffix))
+}
+
+fn index_key(path: &str) -> String {
+    format!("{path}.index")
+}
+
+fn segment_key(path: &str, segment: u64) -> String {
+    format!("{path}.{segment:016x}.seg")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    segment: u64,
+    offset: u64,
+}
+
+/// Contents of the index object, little endian: a header followed by one
+/// entry per stored block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Index {
+    block_size: usize,
+    file_size: u64,
+    next_segment: u64,
+    blocks: BTreeMap<u64, Location>,
+}
+
+impl Index {
+    fn new(block_size: usize) -> Self {
+        Self {
+            block_size,
+            file_size: 0,
+            next_segment: 0,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(INDEX_HEADER_SIZE + self.blocks.len() * INDEX_ENTRY_SIZE);
+        out.extend_from_slice(&INDEX_MAGIC.to_le_bytes());
+        out.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.block_size as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&self.file_size.to_le_bytes());
+        out.extend_from_slice(&self.next_segment.to_le_bytes());
+        out.extend_from_slice(&(self.blocks.len() as u64).to_le_bytes());
+        for (block, location) in &self.blocks {
+            out.extend_from_slice(&block.to_le_bytes());
+            out.extend_from_slice(&location.segment.to_le_bytes());
+            out.extend_from_slice(&location.offset.to_le_bytes());
+        }
+        out
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        if data.len() < INDEX_HEADER_SIZE || u32_at(0) != INDEX_MAGIC {
+            return Err(LimboError::Corrupt("not an object store index".to_string()));
+        }
+        if u32_at(4) != INDEX_VERSION {
+            return Err(LimboError::Corrupt(format!(
+                "unsupported object store index version {}",
+                u32_at(4)
+            )));
+        }
+        let count = u64_at(32) as usize;
+        if data.len() != INDEX_HEADER_SIZE + count * INDEX_ENTRY_SIZE {
+            return Err(LimboError::Corrupt(
+                "object store index has the wrong length".to_string(),
+            ));
+        }
+        let blocks = (0..count)
+            .map(|i| {
+                let at = INDEX_HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+                let location = Location {
+                    segment: u64_at(at + 8),
+                    offset: u64_at(at + 16),
+                };
+                (u64_at(at), location)
+            })
+            .collect();
+        Ok(Self {
+            block_size: u32_at(8) as usize,
+            file_size: u64_at(16),
+            next_segment: u64_at(24),
+            blocks,
+        })
+    }
+}
+
+enum FlushPhase {
+    Segment(Box<dyn ObjectRequest>),
+    Index(Box<dyn ObjectRequest>),
+}
+
+/// A sync being carried out.
+struct Flush {
+    phase: FlushPhase,
+    /// Index to upload once the segment is stored.
+    index: Index,
+    syncs: Vec<Completion>,
+}
+
+type Finished = Vec<(Completion, std::result::Result<i32, CompletionError>)>;
+
+struct FileState {
+    /// What the store holds.
+    index: Index,
+    /// Size including unsynced writes and truncates.
+    size: u64,
+    /// Blocks written since the last sync.
+    dirty: BTreeMap<u64, Arc<[u8]>>,
+    /// Blocks of the flush in progress.
+    uploading: BTreeMap<u64, Arc<[u8]>>,
+    /// Blocks fetched from the store.
+    clean: HashMap<u64, Arc<[u8]>>,
+    fetching: HashMap<u64, Box<dyn ObjectRequest>>,
+    failed: HashMap<u64, CompletionError>,
+    reads: Vec<(u64, Completion)>,
+    flush: Option<Flush>,
+    /// Syncs issued while a flush was in progress, served by the next one.
+    queued_syncs: Vec<Completion>,
+}
+
+/// A database file stored in an [ObjectStore].
+pub struct ObjectStoreFile {
+    path: String,
+    store: Arc<dyn ObjectStore>,
+    part_size: usize,
+    state: Mutex<FileState>,
+}
+
+impl ObjectStoreFile {
+    fn block_size(&self) -> usize {
+        self.state.lock().index.block_size
+    }
+
+    /// Copy what is available of `buf.len()` bytes at `pos` into `buf`.
+    /// Returns `None` and starts fetching if some block isn't in memory.
+    fn try_read(
+        &self,
+        state: &mut FileState,
+        pos: u64,
+        buf: &mut [u8],
+    ) -> std::result::Result<Option<usize>, CompletionError> {
+        if pos >= state.size || buf.is_empty() {
+            return Ok(Some(0));
+        }
+        let block_size = state.index.block_size as u64;
+        let end = (pos + buf.len() as u64).min(state.size);
+        let mut missing = false;
+        for block in pos / block_size..=(end - 1) / block_size {
+            let start = block * block_size;
+            let from = pos.max(start);
+            let to = end.min(start + block_size);
+            let out = &mut buf[(from - pos) as usize..(to - pos) as usize];
+            let data = state
+                .dirty
+                .get(&block)
+                .or_else(|| state.uploading.get(&block))
+                .or_else(|| state.clean.get(&block));
+            if let Some(data) = data {
+                out.copy_from_slice(&data[(from - start) as usize..(to - start) as usize]);
+                continue;
+            }
+            let Some(location) = state.index.blocks.get(&block).copied() else {
+                out.fill(0);
+                continue;
+            };
+            if let Some(err) = state.failed.remove(&block) {
+                return Err(err);
+            }
+            missing = true;
+            if !state.fetching.contains_key(&block) {
+                let request = self
+                    .store
+                    .get(
+                        &segment_key(&self.path, location.segment),
+                        Some((location.offset, block_size as usize)),
+                    )
+                    .map_err(|e| {
+                        tracing::error!("Failed to fetch block {block} of {}: {e}", self.path);
+                        CompletionError::IOError(std::io::ErrorKind::Other, "object store get")
+                    })?;
+                state.fetching.insert(block, request);
+            }
+        }
+        Ok((!missing).then_some((end - pos) as usize))
+    }
+
+    /// Start uploading the dirty blocks, or complete `syncs` right away if
+    /// there is nothing to upload.
+    fn start_flush(&self, state: &mut FileState, syncs: Vec<Completion>, finished: &mut Finished) {
+        if state.dirty.is_empty() && state.size == state.index.file_size {
+            finished.extend(syncs.into_iter().map(|c| (c, Ok(0))));
+            return;
+        }
+        let block_size = state.index.block_size as u64;
+        let mut index = state.index.clone();
+        index.file_size = state.size;
+        index
+            .blocks
+            .retain(|block, _| block * block_size < index.file_size);
+        let uploading = std::mem::take(&mut state.dirty);
+        let request = if uploading.is_empty() {
+            self.store.put(&index_key(&self.path), index.encode())
+        } else {
+            let segment = index.next_segment;
+            index.next_segment += 1;
+            let mut parts: Vec<Vec<u8>> = Vec::new();
+            let mut offset = 0;
+            for (block, data) in &uploading {
+                match parts.last_mut() {
+                    Some(part) if part.len() + data.len() <= self.part_size => {
+                        part.extend_from_slice(data)
+                    }
+                    _ => parts.push(data.to_vec()),
+                }
+                index.blocks.insert(*block, Location { segment, offset });
+                offset += data.len() as u64;
+            }
+            debug!(
+                "uploading {} blocks of {} as segment {segment} in {} parts",
+                uploading.len(),
+                self.path,
+                parts.len()
+            );
+            let key = segment_key(&self.path, segment);
+            if parts.len() == 1 {
+                self.store.put(&key, parts.pop().unwrap())
+            } else {
+                self.store.put_multipart(&key, parts)
+            }
+        };
+        state.uploading = uploading;
+        match request {
+            Ok(request) => {
+                let phase = if state.uploading.is_empty() {
+                    FlushPhase::Index(request)
+                } else {
+                    FlushPhase::Segment(request)
+                };
+                state.flush = Some(Flush {
+                    phase,
+                    index,
+                    syncs,
+                });
+            }
+            Err(e) => {
+                tracing::error!("Failed to start upload of {}: {e}", self.path);
+                self.abort_flush(
+                    state,
+                    syncs,
+                    CompletionError::IOError(std::io::ErrorKind::Other, "object store put"),
+                    finished,
+                );
+            }
+        }
+    }
+
+    /// Put the blocks of a failed flush back among the dirty ones.
+    fn abort_flush(
+        &self,
+        state: &mut FileState,
+        syncs: Vec<Completion>,
+        err: CompletionError,
+        finished: &mut Finished,
+    ) {
+        for (block, data) in std::mem::take(&mut state.uploading) {
+            state.dirty.entry(block).or_insert(data);
+        }
+        finished.extend(syncs.into_iter().map(|c| (c, Err(err))));
+    }
+
+    fn step(&self) -> Finished {
+        let mut finished = Vec::new();
+        let mut state = self.state.lock();
+        let state = &mut *state;
+
+        let mut fetched = Vec::new();
+        let failed = &mut state.failed;
+        state
+            .fetching
+            .retain(|block, request| match request.poll() {
+                Ok(None) => true,
+                Ok(Some(data)) => {
+                    fetched.push((*block, data));
+                    false
+                }
+                Err(err) => {
+                    failed.insert(*block, err);
+                    false
+                }
+            });
+        for (block, data) in fetched {
+            if state.clean.len() >= CLEAN_BLOCKS_CAPACITY {
+                let evict = *state.clean.keys().next().unwrap();
+                state.clean.remove(&evict);
+            }
+            state.clean.insert(block, data.into());
+        }
+
+        for (pos, c) in std::mem::take(&mut state.reads) {
+            if c.finished() {
+                continue;
+            }
+            let buf = c.as_read().buf().as_mut_slice();
+            match self.try_read(state, pos, buf) {
+                Ok(Some(n)) => finished.push((c, Ok(n as i32))),
+                Ok(None) => state.reads.push((pos, c)),
+                Err(err) => finished.push((c, Err(err))),
+            }
+        }
+
+        if let Some(mut flush) = state.flush.take() {
+            let poll = match &mut flush.phase {
+                FlushPhase::Segment(request) | FlushPhase::Index(request) => request.poll(),
+            };
+            match (poll, flush.phase) {
+                (Ok(None), phase) => {
+                    flush.phase = phase;
+                    state.flush = Some(flush);
+                }
+                (Ok(Some(_)), FlushPhase::Segment(_)) => {
+                    match self.store.put(&index_key(&self.path), flush.index.encode()) {
+                        Ok(request) => {
+                            flush.phase = FlushPhase::Index(request);
+                            state.flush = Some(flush);
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to upload index of {}: {e}", self.path);
+                            let err = CompletionError::IOError(
+                                std::io::ErrorKind::Other,
+                                "object store put",
+                            );
+                            self.abort_flush(state, flush.syncs, err, &mut finished);
+                        }
+                    }
+                }
+                (Ok(Some(_)), FlushPhase::Index(_)) => {
+                    state.index = flush.index;
+                    for (block, data) in std::mem::take(&mut state.uploading) {
+                        // A fetch started before the upload would bring back
+                        // the old version.
+                        state.fetching.remove(&block);
+                        state.failed.remove(&block);
+                        if state.clean.len() < CLEAN_BLOCKS_CAPACITY {
+                            state.clean.insert(block, data);
+                        } else {
+                            state.clean.remove(&block);
+                        }
+                    }
+                    finished.extend(flush.syncs.into_iter().map(|c| (c, Ok(0))));
+                    let queued = std::mem::take(&mut state.queued_syncs);
+                    if !queued.is_empty() {
+                        self.start_flush(state, queued, &mut finished);
+                    }
+                }
+                (Err(err), _) => {
+                    tracing::error!("Failed to flush {}: {err}", self.path);
+                    self.abort_flush(state, flush.syncs, err, &mut finished);
+                }
+            }
+        }
+        finished
+    }
+}
+
+fn run_finished(finished: Finished) {
+    for (c, result) in finished {
+        match result {
+            Ok(n) => c.complete(n),
+            Err(err) => c.error(err),
+        }
+    }
+}
+
+impl File for ObjectStoreFile {
+    /// The file must only be opened by one process; there is nothing to lock.
+    fn lock_file(&self, _exclusive: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        let result = {
+            let mut state = self.state.lock();
+            let buf = c.as_read().buf().as_mut_slice();
+            let result = self.try_read(&mut state, pos, buf)?;
+            if result.is_none() {
+                state.reads.push((pos, c.clone()));
+            }
+            result
+        };
+        if let Some(n) = result {
+            c.complete(n as i32);
+        }
+        Ok(c)
+    }
+
+    /// Writes must cover whole blocks.
+    fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion> {
+        let block_size = self.block_size();
+        let data = buffer.as_slice();
+        if pos % block_size as u64 != 0 || data.len() % block_size != 0 {
+            return Err(LimboError::InternalError(format!(
+                "object store files only take whole {block_size}-byte blocks, got {} bytes at {pos}",
+                data.len()
+            )));
+        }
+        {
+            let mut state = self.state.lock();
+            for (i, block) in data.chunks(block_size).enumerate() {
+                let block_no = pos / block_size as u64 + i as u64;
+                state.dirty.insert(block_no, block.into());
+                state.failed.remove(&block_no);
+            }
+            state.size = state.size.max(pos + data.len() as u64);
+        }
+        c.complete(data.len() as i32);
+        Ok(c)
+    }
+
+    fn sync(&self, c: Completion, _sync_type: FileSyncType) -> Result<Completion> {
+        let mut finished = Vec::new();
+        {
+            let mut state = self.state.lock();
+            if state.flush.is_some() {
+                state.queued_syncs.push(c.clone());
+            } else {
+                self.start_flush(&mut state, vec![c.clone()], &mut finished);
+            }
+        }
+        run_finished(finished);
+        Ok(c)
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.state.lock().size)
+    }
+
+    /// Takes effect in the store with the next sync.
+    fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
+        {
+            let mut state = self.state.lock();
+            let block_size = state.index.block_size as u64;
+            state.size = len;
+            state.dirty.retain(|block, _| block * block_size < len);
+            state.clean.retain(|block, _| block * block_size < len);
+        }
+        c.complete(0);
+        Ok(c)
+    }
+}
+
+/// [IO] backend storing database files in an [ObjectStore] and their WAL on
+/// a local [IO].
+pub struct ObjectStoreIO {
+    store: Arc<dyn ObjectStore>,
+    local: Arc<dyn IO>,
+    block_size: usize,
+    part_size: usize,
+    files: Mutex<HashMap<String, Arc<ObjectStoreFile>>>,
+}
+
+impl ObjectStoreIO {
+    pub fn new(store: Arc<dyn ObjectStore>, local: Arc<dyn IO>) -> Self {
+        debug!("Using IO backend 'object_store'");
+        Self {
+            store,
+            local,
+            block_size: OBJECT_STORE_DEFAULT_BLOCK_SIZE,
+            part_size: OBJECT_STORE_DEFAULT_PART_SIZE,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Block size of the files created from now on; it must be the page size
+    /// of the database. Existing files keep the one they were created with.
+    pub fn with_block_size(mut self, block_size: usize) -> Result<Self> {
+        if !block_size.is_power_of_two() || !(512..=65536).contains(&block_size) {
+            return Err(LimboError::InvalidArgument(format!(
+                "object store block size must be a power of two between 512 and 65536, got {block_size}"
+            )));
+        }
+        self.block_size = block_size;
+        Ok(self)
+    }
+
+    /// Segments larger than `part_size` are sent as multipart uploads of
+    /// parts of at most that size (rounded up to whole blocks).
+    pub fn with_part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size;
+        self
+    }
+}
+
+impl Clock for ObjectStoreIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        self.local.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        self.local.current_time_wall_clock()
+    }
+}
+
+impl IO for ObjectStoreIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        if is_local_file(path) {
+            return self.local.open_file(path, flags, direct);
+        }
+        if let Some(file) = self.files.lock().get(path) {
+            return Ok(file.clone());
+        }
+        let index = match wait(self.local.as_ref(), self.store.get(&index_key(path), None)?) {
+            Ok(data) => Index::decode(&data)?,
+            Err(CompletionError::IOError(std::io::ErrorKind::NotFound, _))
+                if flags.contains(OpenFlags::Create) =>
+            {
+                Index::new(self.block_size)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let file = Arc::new(ObjectStoreFile {
+            path: path.to_string(),
+            store: self.store.clone(),
+            part_size: self.part_size,
+            state: Mutex::new(FileState {
+                size: index.file_size,
+                index,
+                dirty: BTreeMap::new(),
+                uploading: BTreeMap::new(),
+                clean: HashMap::new(),
+                fetching: HashMap::new(),
+                failed: HashMap::new(),
+                reads: Vec::new(),
+                flush: None,
+                queued_syncs: Vec::new(),
+            }),
+        });
+        self.files.lock().insert(path.to_string(), file.clone());
+        Ok(file)
+    }
+
+    /// Deletes the index of a database file, which empties it. Its segments
+    /// are left in the store.
+    fn remove_file(&self, path: &str) -> Result<()> {
+        if is_local_file(path) {
+            return self.local.remove_file(path);
+        }
+        self.files.lock().remove(path);
+        match wait(self.local.as_ref(), self.store.delete(&index_key(path))?) {
+            Ok(_) | Err(CompletionError::IOError(std::io::ErrorKind::NotFound, _)) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn step(&self) -> Result<()> {
+        let files: Vec<_> = self.files.lock().values().cloned().collect();
+        for file in files {
+            // Completion callbacks may issue new requests, so run them unlocked.
+            run_finished(file.step());
+        }
+        self.local.step()
+    }
+
+    fn generate_random_number(&self) -> i64 {
+        self.local.generate_random_number()
+    }
+
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.local.fill_bytes(dest)
+    }
+
+    fn get_memory_io(&self) -> Arc<super::MemoryIO> {
+        self.local.get_memory_io()
+    }
+
+    fn yield_now(&self) {
+        self.local.yield_now()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) {
+        self.local.sleep(duration)
+    }
+
+    fn file_id(&self, path: &str) -> Result<FileId> {
+        Ok(FileId::from_path_hash(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CheckpointMode, Database, MemoryIO, SqliteDialect, Value};
+
+    /// Keeps objects in memory and answers each request on its second poll.
+    #[derive(Default)]
+    struct FakeStore {
+        objects: Mutex<HashMap<String, Vec<u8>>>,
+        puts: Mutex<Vec<(String, usize)>>,
+    }
+
+    struct FakeRequest {
+        result: Option<std::result::Result<Vec<u8>, CompletionError>>,
+        polled: bool,
+    }
+
+    impl FakeRequest {
+        fn new(result: std::result::Result<Vec<u8>, CompletionError>) -> Box<dyn ObjectRequest> {
+            Box::new(Self {
+                result: Some(result),
+                polled: false,
+            })
+        }
+    }
+
+    impl ObjectRequest for FakeRequest {
+        fn poll(&mut self) -> std::result::Result<Option<Vec<u8>>, CompletionError> {
+            if !std::mem::replace(&mut self.polled, true) {
+                return Ok(None);
+            }
+            self.result.take().unwrap().map(Some)
+        }
+    }
+
+    const NOT_FOUND: CompletionError =
+        CompletionError::IOError(std::io::ErrorKind::NotFound, "fake store");
+
+    impl ObjectStore for FakeStore {
+        fn get(&self, key: &str, range: Option<(u64, usize)>) -> Result<Box<dyn ObjectRequest>> {
+            let objects = self.objects.lock();
+            let result = match (objects.get(key), range) {
+                (None, _) => Err(NOT_FOUND),
+                (Some(data), None) => Ok(data.clone()),
+                (Some(data), Some((offset, len))) => {
+                    let start = (offset as usize).min(data.len());
+                    let end = (start + len).min(data.len());
+                    Ok(data[start..end].to_vec())
+                }
+            };
+            Ok(FakeRequest::new(result))
+        }
+
+        fn put(&self, key: &str, data: Vec<u8>) -> Result<Box<dyn ObjectRequest>> {
+            self.put_multipart(key, vec![data])
+        }
+
+        fn put_multipart(&self, key: &str, parts: Vec<Vec<u8>>) -> Result<Box<dyn ObjectRequest>> {
+            self.puts.lock().push((key.to_string(), parts.len()));
+            self.objects.lock().insert(key.to_string(), parts.concat());
+            Ok(FakeRequest::new(Ok(Vec::new())))
+        }
+
+        fn delete(&self, key: &str) -> Result<Box<dyn ObjectRequest>> {
+            let result = match self.objects.lock().remove(key) {
+                Some(_) => Ok(Vec::new()),
+                None => Err(NOT_FOUND),
+            };
+            Ok(FakeRequest::new(result))
+        }
+    }
+
+    fn write(io: &ObjectStoreIO, file: &Arc<dyn File>, pos: u64, data: Vec<u8>) {
+        let c = file
+            .pwrite(
+                pos,
+                Arc::new(Buffer::new(data)),
+                Completion::new_write(|_| {}),
+            )
+            .unwrap();
+        io.wait_for_completion(c).unwrap();
+    }
+
+    fn read(io: &ObjectStoreIO, file: &Arc<dyn File>, pos: u64, len: usize) -> Vec<u8> {
+        let buf = Arc::new(Buffer::new_temporary(len));
+        let c = file
+            .pread(pos, Completion::new_read(buf.clone(), |_| None))
+            .unwrap();
+        io.wait_for_completion(c).unwrap();
+        buf.as_slice().to_vec()
+    }
+
+    fn sync(io: &ObjectStoreIO, file: &Arc<dyn File>) {
+        let c = file
+            .sync(Completion::new_sync(|_| {}), FileSyncType::Fsync)
+            .unwrap();
+        io.wait_for_completion(c).unwrap();
+    }
+
+    #[test]
+    fn test_sync_uploads_one_segment() {
+        let store = Arc::new(FakeStore::default());
+        let io = ObjectStoreIO::new(store.clone(), Arc::new(MemoryIO::new()));
+        let file = io.open_file("a.db", OpenFlags::Create, false).unwrap();
+        for i in 0..10u8 {
+            write(&io, &file, i as u64 * 4096, vec![i; 4096]);
+        }
+        assert!(store.puts.lock().is_empty());
+        sync(&io, &file);
+        assert_eq!(
+            *store.puts.lock(),
+            vec![(segment_key("a.db", 0), 1), (index_key("a.db"), 1)]
+        );
+        // Nothing new to upload.
+        sync(&io, &file);
+        assert_eq!(store.puts.lock().len(), 2);
+
+        write(&io, &file, 4096, vec![42; 4096]);
+        sync(&io, &file);
+
+        let io = ObjectStoreIO::new(store.clone(), Arc::new(MemoryIO::new()));
+        let file = io.open_file("a.db", OpenFlags::None, false).unwrap();
+        assert_eq!(file.size().unwrap(), 10 * 4096);
+        assert_eq!(read(&io, &file, 0, 4096), vec![0; 4096]);
+        assert_eq!(read(&io, &file, 4096, 4096), vec![42; 4096]);
+        assert_eq!(read(&io, &file, 9 * 4096 + 100, 10), vec![9; 10]);
+
+        assert!(file
+            .pwrite(
+                100,
+                Arc::new(Buffer::new(vec![0; 4096])),
+                Completion::new_write(|_| {})
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_large_flush_is_multipart() {
+        let store = Arc::new(FakeStore::default());
+        let io =
+            ObjectStoreIO::new(store.clone(), Arc::new(MemoryIO::new())).with_part_size(4 * 4096);
+        let file = io.open_file("a.db", OpenFlags::Create, false).unwrap();
+        write(&io, &file, 0, vec![7; 10 * 4096]);
+        sync(&io, &file);
+        assert_eq!(store.puts.lock()[0], (segment_key("a.db", 0), 3));
+
+        file.truncate(4096, Completion::new_trunc(|_| {})).unwrap();
+        sync(&io, &file);
+        let index = Index::decode(&store.objects.lock()[&index_key("a.db")]).unwrap();
+        assert_eq!(index.file_size, 4096);
+        assert_eq!(index.blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_database_on_object_store() {
+        let store = Arc::new(FakeStore::default());
+        let io = Arc::new(ObjectStoreIO::new(store.clone(), Arc::new(MemoryIO::new())));
+        let db = Database::open_file(io.clone(), "remote.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT randomblob(500) FROM generate_series(1, 100)")
+            .unwrap();
+        conn.checkpoint(CheckpointMode::Truncate {
+            upper_bound_inclusive: None,
+        })
+        .unwrap();
+        conn.close().unwrap();
+        drop(db);
+
+        // A fresh local IO: everything needed is in the store.
+        let io = Arc::new(ObjectStoreIO::new(store, Arc::new(MemoryIO::new())));
+        let db = Database::open_file(io, "remote.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT count(*), sum(length(x)) FROM t")
+            .unwrap();
+        assert_eq!(
+            stmt.run_collect_rows().unwrap(),
+            vec![vec![Value::from_i64(100), Value::from_i64(50_000)]]
+        );
+    }
+
+    #[test]
+    fn test_temp_files_stay_local() {
+        let store = Arc::new(FakeStore::default());
+        let io = Arc::new(ObjectStoreIO::new(store.clone(), Arc::new(MemoryIO::new())));
+        let db = Database::open_file(io, "remote.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        // The sorter buffer is sized like the page cache; shrinking it to the
+        // minimum makes the ORDER BY below spill to a temp file.
+        conn.execute("PRAGMA cache_size = -1").unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 2000)")
+            .unwrap();
+        let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x").unwrap();
+        assert_eq!(stmt.run_collect_rows().unwrap().len(), 2000);
+        assert!(store
+            .objects
+            .lock()
+            .keys()
+            .all(|key| key.starts_with("remote.db.")));
+    }
+}
@@ -0,0 +1,384 @@
+//! Size quota over any IO backend.
+//!
+//! [QuotaIO] wraps another [IO] and keeps track of the size of every file
+//! opened through it: the database, its WAL and any journal. A write or
+//! truncate that would take their total past the limit fails with
+//! [LimboError::DatabaseFull], the counterpart of `SQLITE_FULL`, without
+//! reaching the backend, so an embedder can cap the storage of each tenant by
+//! giving every database its own `QuotaIO`.
+//!
+//! Sizes are accounted for when an operation is submitted rather than when it
+//! completes, so a write that later fails still counts until the file is
+//! truncated or reopened. Temp files (sorter and hash join spills, ephemeral
+//! tables) count while they are open: they are unlinked as soon as they are
+//! created, so their space is given back when they are closed. Shared WAL
+//! coordination files are scratch space and are not counted.
+
+use super::{
+    is_temp_file, Buffer, Clock, Completion, File, FileId, FileSyncType, LockLevel, OpenFlags,
+    SharedWalLockKind, SharedWalMappedRegion, IO,
+};
+use crate::io::clock::{MonotonicInstant, WallClockInstant};
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::Mutex;
+use crate::{LimboError, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Default)]
+struct Usage {
+    /// Size of every file, by path.
+    sizes: HashMap<String, u64>,
+    total: u64,
+}
+
+struct Quota {
+    limit: AtomicU64,
+    usage: Mutex<Usage>,
+}
+
+impl Quota {
+    /// Record `path` as being `size` bytes long, failing if that takes the
+    /// total past the limit.
+    fn resize(&self, path: &str, size: u64) -> Result<()> {
+        let mut usage = self.usage.lock();
+        let old = usage.sizes.get(path).copied().unwrap_or(0);
+        if size > old {
+            drop(usage);
+            return self.grow(path, size);
+        }
+        usage.total -= old - size;
+        usage.sizes.insert(path.to_string(), size);
+        Ok(())
+    }
+
+    /// Record that `path` is at least `end` bytes long.
+    fn grow(&self, path: &str, end: u64) -> Result<()> {
+        let mut usage = self.usage.lock();
+        let old = usage.sizes.get(path).copied().unwrap_or(0);
+        if end <= old {
+            return Ok(());
+        }
+        let limit = self.limit.load(Ordering::Acquire);
+        let total = usage.total + (end - old);
+        if total > limit {
+            return Err(LimboError::DatabaseFull(format!(
+                "writing {path} would use {total} bytes, over the quota of {limit}"
+            )));
+        }
+        usage.total = total;
+        usage.sizes.insert(path.to_string(), end);
+        Ok(())
+    }
+
+    /// Stop counting `path`, which is gone.
+    fn release(&self, path: &str) {
+        let mut usage = self.usage.lock();
+        if let Some(size) = usage.sizes.remove(path) {
+            usage.total -= size;
+        }
+    }
+}
+
+/// [IO] backend that caps the total size of the files of another one.
+pub struct QuotaIO {
+    inner: Arc<dyn IO>,
+    quota: Arc<Quota>,
+}
+
+impl QuotaIO {
+    /// Allow the files opened through `inner` to grow to `limit` bytes in
+    /// total.
+    pub fn new(inner: Arc<dyn IO>, limit: u64) -> Self {
+        tracing::debug!("Using IO backend 'quota' with a limit of {limit} bytes");
+        Self {
+            inner,
+            quota: Arc::new(Quota {
+                limit: AtomicU64::new(limit),
+                usage: Mutex::new(Usage::default()),
+            }),
+        }
+    }
+
+    /// Bytes used by the files opened so far.
+    pub fn usage(&self) -> u64 {
+        self.quota.usage.lock().total
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.quota.limit.load(Ordering::Acquire)
+    }
+
+    /// Change the limit. Lowering it below the current usage only stops the
+    /// files from growing further.
+    pub fn set_limit(&self, limit: u64) {
+        self.quota.limit.store(limit, Ordering::Release);
+    }
+}
+
+impl Clock for QuotaIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        self.inner.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        self.inner.current_time_wall_clock()
+    }
+}
+
+impl IO for QuotaIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        let file = self.inner.open_file(path, flags, direct)?;
+        if path.ends_with("-tshm") {
+            return Ok(file);
+        }
+        // A file that was already over the quota can still be opened and
+        // read, it just can't grow.
+        let size = file.size()?;
+        {
+            let mut usage = self.quota.usage.lock();
+            let old = usage.sizes.insert(path.to_string(), size).unwrap_or(0);
+            usage.total = usage.total - old + size;
+        }
+        Ok(Arc::new(QuotaFile {
+            file,
+            path: path.to_string(),
+            quota: self.quota.clone(),
+            temporary: is_temp_file(path),
+        }))
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.inner.remove_file(path)?;
+        self.quota.release(path);
+        Ok(())
+    }
+
+    fn step(&self) -> Result<()> {
+        self.inner.step()
+    }
+
+    fn generate_random_number(&self) -> i64 {
+        self.inner.generate_random_number()
+    }
+
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest)
+    }
+
+    fn get_memory_io(&self) -> Arc<super::MemoryIO> {
+        self.inner.get_memory_io()
+    }
+
+    fn yield_now(&self) {
+        self.inner.yield_now()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) {
+        self.inner.sleep(duration)
+    }
+
+    fn file_id(&self, path: &str) -> Result<FileId> {
+        self.inner.file_id(path)
+    }
+
+    fn supports_shared_wal_coordination(&self) -> bool {
+        self.inner.supports_shared_wal_coordination()
+    }
+}
+
+/// File opened through a [QuotaIO].
+struct QuotaFile {
+    file: Arc<dyn File>,
+    path: String,
+    quota: Arc<Quota>,
+    /// A temp file, whose space is freed when it is closed.
+    temporary: bool,
+}
+
+impl Drop for QuotaFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            self.quota.release(&self.path);
+        }
+    }
+}
+
+impl File for QuotaFile {
+    fn lock_file(&self, exclusive: bool) -> Result<()> {
+        self.file.lock_file(exclusive)
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        self.file.unlock_file()
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        self.file.lock_level(level)
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        self.file.unlock_level(level)
+    }
+
+    fn check_reserved_lock(&self) -> Result<bool> {
+        self.file.check_reserved_lock()
+    }
+
+    fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        self.file.pread(pos, c)
+    }
+
+    fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion> {
+        self.quota.grow(&self.path, pos + buffer.len() as u64)?;
+        self.file.pwrite(pos, buffer, c)
+    }
+
+    fn pwritev(&self, pos: u64, buffers: Vec<Arc<Buffer>>, c: Completion) -> Result<Completion> {
+        let len: usize = buffers.iter().map(|b| b.len()).sum();
+        self.quota.grow(&self.path, pos + len as u64)?;
+        self.file.pwritev(pos, buffers, c)
+    }
+
+    fn sync(&self, c: Completion, sync_type: FileSyncType) -> Result<Completion> {
+        self.file.sync(c, sync_type)
+    }
+
+    fn size(&self) -> Result<u64> {
+        self.file.size()
+    }
+
+    fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
+        self.quota.resize(&self.path, len)?;
+        self.file.truncate(len, c)
+    }
+
+    fn set_mmap_limit(&self, limit: u64) -> Result<u64> {
+        self.file.set_mmap_limit(limit)
+    }
+
+    fn mmap_limit(&self) -> u64 {
+        self.file.mmap_limit()
+    }
+
+    fn shared_wal_lock_byte(
+        &self,
+        offset: u64,
+        exclusive: bool,
+        kind: SharedWalLockKind,
+    ) -> Result<()> {
+        self.file.shared_wal_lock_byte(offset, exclusive, kind)
+    }
+
+    fn shared_wal_try_lock_byte(
+        &self,
+        offset: u64,
+        exclusive: bool,
+        kind: SharedWalLockKind,
+    ) -> Result<bool> {
+        self.file.shared_wal_try_lock_byte(offset, exclusive, kind)
+    }
+
+    fn shared_wal_unlock_byte(&self, offset: u64, kind: SharedWalLockKind) -> Result<()> {
+        self.file.shared_wal_unlock_byte(offset, kind)
+    }
+
+    fn shared_wal_set_len(&self, len: u64) -> Result<()> {
+        self.file.shared_wal_set_len(len)
+    }
+
+    fn shared_wal_map(&self, offset: u64, len: usize) -> Result<Box<dyn SharedWalMappedRegion>> {
+        self.file.shared_wal_map(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemoryIO;
+    use crate::{Database, SqliteDialect, StepResult, Value};
+
+    #[test]
+    fn test_writes_past_quota_fail() {
+        let io = QuotaIO::new(Arc::new(MemoryIO::new()), 10_000);
+        let file = io.open_file("a", OpenFlags::Create, false).unwrap();
+        let write = |pos: u64, len: usize| {
+            let buf = Arc::new(Buffer::new(vec![1; len]));
+            file.pwrite(pos, buf, Completion::new_write(|_| {}))
+        };
+
+        write(0, 4096).unwrap();
+        write(4096, 4096).unwrap();
+        assert_eq!(io.usage(), 8192);
+        // Overwrites don't count twice.
+        write(0, 4096).unwrap();
+        assert!(matches!(
+            write(8192, 4096),
+            Err(LimboError::DatabaseFull(_))
+        ));
+        assert_eq!(io.usage(), 8192);
+
+        file.truncate(4096, Completion::new_trunc(|_| {})).unwrap();
+        assert_eq!(io.usage(), 4096);
+        write(4096, 4096).unwrap();
+
+        // Reopening takes the size from the backend.
+        drop(file);
+        let _file = io.open_file("a", OpenFlags::None, false).unwrap();
+        assert_eq!(io.usage(), 8192);
+        io.remove_file("a").unwrap();
+        assert_eq!(io.usage(), 0);
+    }
+
+    #[test]
+    fn test_temp_files_are_released_when_closed() {
+        let io = Arc::new(QuotaIO::new(Arc::new(MemoryIO::new()), 64 * 1024 * 1024));
+        let db = Database::open_file(io.clone(), "tenant.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 2000)")
+            .unwrap();
+        let used = io.usage();
+
+        // The sorter buffer is sized like the page cache; shrinking it to the
+        // minimum makes the ORDER BY below spill to a temp file.
+        conn.execute("PRAGMA cache_size = -1").unwrap();
+        let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x").unwrap();
+        loop {
+            match stmt.step().unwrap() {
+                StepResult::Row => break,
+                StepResult::IO => io.step().unwrap(),
+                result => panic!("expected a row, got {result:?}"),
+            }
+        }
+        assert!(io.usage() > used + 1_000_000);
+        drop(stmt);
+        assert_eq!(io.usage(), used);
+    }
+
+    #[test]
+    fn test_database_full() {
+        let io = Arc::new(QuotaIO::new(Arc::new(MemoryIO::new()), 64 * 1024));
+        let db = Database::open_file(io.clone(), "tenant.db", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        conn.execute("INSERT INTO t VALUES (zeroblob(1000))")
+            .unwrap();
+        let used = io.usage();
+        assert!(used > 0 && used <= io.limit());
+
+        let res = conn.execute("INSERT INTO t SELECT zeroblob(1000) FROM generate_series(1, 100)");
+        assert!(
+            matches!(res, Err(LimboError::DatabaseFull(_))),
+            "expected DatabaseFull, got {res:?}"
+        );
+        assert!(io.usage() <= io.limit());
+
+        // The failed statement left the table as it was.
+        let mut stmt = conn.prepare("SELECT count(*) FROM t").unwrap();
+        assert_eq!(
+            stmt.run_collect_rows().unwrap(),
+            vec![vec![Value::from_i64(1)]]
+        );
+    }
+}
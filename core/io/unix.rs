@@ -1,8 +1,10 @@
-use super::{Completion, File, OpenFlags, SharedWalLockKind, SharedWalMappedRegion, IO};
+use super::lock_level::{LockLevels, RangeLocks};
+use super::{Completion, File, LockLevel, OpenFlags, SharedWalLockKind, SharedWalMappedRegion, IO};
 use crate::error::{io_error, CompletionError, LimboError};
 use crate::io::clock::{Clock, DefaultClock, MonotonicInstant, WallClockInstant};
 use crate::io::common;
 use crate::io::FileSyncType;
+use crate::sync::RwLock;
 use crate::Result;
 use rustix::{
     fd::{AsFd, AsRawFd},
@@ -12,9 +14,7 @@ use std::os::fd::RawFd;
 use std::ptr::NonNull;
 
 use std::{io::ErrorKind, sync::Arc};
-#[cfg(feature = "fs")]
-use tracing::debug;
-use tracing::{instrument, trace, Level};
+use tracing::{debug, instrument, trace, Level};
 
 // Darwin fails pwrite() and pwritev() calls with buffer size larger than INT_MAX so let's treat
 // that as maximum buffer size.
@@ -47,7 +47,7 @@ impl IO for UnixIO {
         true
     }
 
-    fn open_file(&self, path: &str, flags: OpenFlags, _direct: bool) -> Result<Arc<dyn File>> {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
         trace!("open_file(path = {})", path);
         let mut file = std::fs::File::options();
         file.read(true);
@@ -58,11 +58,20 @@ impl IO for UnixIO {
         }
 
         let file = file.open(path).map_err(|e| io_error(e, "open"))?;
+        if direct {
+            // Not all filesystems support bypassing the page cache, so ignore
+            // any errors and keep the file buffered.
+            if let Err(error) = set_direct_io(&file) {
+                debug!("Error {error:?} returned when enabling direct I/O on '{path}'. Reads and writes will go through the page cache");
+            }
+        }
 
         #[allow(clippy::arc_with_non_send_sync)]
         let unix_file = Arc::new(UnixFile {
             file,
             path: path.to_string(),
+            read_map: RwLock::new(ReadMap::default()),
+            lock_levels: LockLevels::new(),
         });
         if std::env::var(common::ENV_DISABLE_FILE_LOCK).is_err()
             && !flags.intersects(OpenFlags::ReadOnly | OpenFlags::NoLock)
@@ -83,9 +92,127 @@ impl IO for UnixIO {
     }
 }
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_direct_io(file: &std::fs::File) -> std::io::Result<()> {
+    let fd = file.as_fd();
+    let flags = fs::fcntl_getfl(fd)?;
+    fs::fcntl_setfl(fd, flags | fs::OFlags::DIRECT)?;
+    Ok(())
+}
+
+#[cfg(target_vendor = "apple")]
+fn set_direct_io(file: &std::fs::File) -> std::io::Result<()> {
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+fn set_direct_io(_file: &std::fs::File) -> std::io::Result<()> {
+    Err(ErrorKind::Unsupported.into())
+}
+
 pub struct UnixFile {
     file: std::fs::File,
     path: String,
+    read_map: RwLock<ReadMap>,
+    lock_levels: LockLevels,
+}
+
+/// Reads served from memory, see [File::set_mmap_limit].
+#[derive(Default)]
+struct ReadMap {
+    /// Most bytes to map, 0 when reads are not mapped.
+    limit: u64,
+    /// The first `min(limit, file size)` bytes of the file, as of the last
+    /// remap.
+    mapping: Option<UnixReadMapping>,
+}
+
+struct UnixReadMapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+unsafe impl Send for UnixReadMapping {}
+unsafe impl Sync for UnixReadMapping {}
+
+impl Drop for UnixReadMapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+impl UnixFile {
+    /// Replace the read mapping with one of the first `min(limit, file size)`
+    /// bytes of the file. If the file can't be mapped reads go through
+    /// `pread` as usual.
+    fn remap(&self, read_map: &mut ReadMap) -> Result<()> {
+        read_map.mapping = None;
+        if read_map.limit == 0 {
+            return Ok(());
+        }
+        let len = read_map.limit.min(self.size()?) as usize;
+        if len == 0 {
+            return Ok(());
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                self.file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            debug!(
+                "mmap of {} failed, reading it with pread: {}",
+                self.path,
+                std::io::Error::last_os_error()
+            );
+            return Ok(());
+        }
+        read_map.mapping = Some(UnixReadMapping {
+            ptr: NonNull::new(ptr.cast()).expect("mmap returned null"),
+            len,
+        });
+        Ok(())
+    }
+
+    fn grow_read_map(&self) -> Result<()> {
+        if self.read_map.read().limit == 0 {
+            return Ok(());
+        }
+        let mut read_map = self.read_map.write();
+        let mapped = read_map.mapping.as_ref().map_or(0, |m| m.len as u64);
+        if read_map.limit > mapped {
+            self.remap(&mut read_map)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the range `c` reads from the read mapping if it covers all of it,
+    /// returning the number of bytes read.
+    fn read_mapped(&self, pos: u64, c: &Completion) -> Option<usize> {
+        let read_map = self.read_map.read();
+        let mapping = read_map.mapping.as_ref()?;
+        let r = c.as_read();
+        let buf = r.buf();
+        let slice = buf.as_mut_slice();
+        if pos.checked_add(slice.len() as u64)? > mapping.len as u64 {
+            return None;
+        }
+        let mapped = unsafe {
+            std::slice::from_raw_parts(mapping.ptr.as_ptr().add(pos as usize), slice.len())
+        };
+        slice.copy_from_slice(mapped);
+        Some(slice.len())
+    }
 }
 
 pub(crate) struct UnixSharedWalMapping {
@@ -274,6 +401,63 @@ pub(crate) fn unix_shared_wal_map(
     }))
 }
 
+/// POSIX record locks, converted in place. Being owned by the process, they
+/// only exclude other processes.
+impl RangeLocks for UnixFile {
+    const CONVERTS_IN_PLACE: bool = true;
+
+    fn lock_range(&self, start: u64, len: u64, exclusive: bool) -> Result<bool> {
+        unix_lock_range(self.file.as_raw_fd(), start, len, exclusive)
+    }
+
+    fn unlock_range(&self, start: u64, len: u64) -> Result<()> {
+        unix_unlock_range(self.file.as_raw_fd(), start, len)
+    }
+}
+
+/// Take a POSIX record lock on `len` bytes of `fd` at `start` without waiting,
+/// returning `false` if another process holds a conflicting lock.
+pub(crate) fn unix_lock_range(fd: RawFd, start: u64, len: u64, exclusive: bool) -> Result<bool> {
+    let kind = if exclusive {
+        libc::F_WRLCK
+    } else {
+        libc::F_RDLCK
+    };
+    match set_record_lock(fd, kind as libc::c_short, start, len) {
+        Ok(()) => Ok(true),
+        Err(error)
+            if error.kind() == ErrorKind::WouldBlock
+                || error.raw_os_error() == Some(libc::EACCES) =>
+        {
+            Ok(false)
+        }
+        Err(error) => Err(LimboError::LockingError(format!(
+            "Failed locking file, {error}"
+        ))),
+    }
+}
+
+pub(crate) fn unix_unlock_range(fd: RawFd, start: u64, len: u64) -> Result<()> {
+    set_record_lock(fd, libc::F_UNLCK as libc::c_short, start, len)
+        .map_err(|error| LimboError::LockingError(format!("Failed to release file lock: {error}")))
+}
+
+fn set_record_lock(fd: RawFd, kind: libc::c_short, start: u64, len: u64) -> std::io::Result<()> {
+    let mut flock = libc::flock {
+        l_type: kind,
+        l_whence: libc::SEEK_SET as libc::c_short,
+        l_start: start as libc::off_t,
+        l_len: len as libc::off_t,
+        l_pid: 0,
+        #[cfg(target_os = "freebsd")]
+        l_sysid: 0,
+    };
+    if unsafe { libc::fcntl(fd, libc::F_SETLK, &mut flock) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 impl File for UnixFile {
     fn lock_file(&self, exclusive: bool) -> Result<()> {
         let fd = self.file.as_fd();
@@ -298,6 +482,7 @@ impl File for UnixFile {
             };
             LimboError::LockingError(message)
         })?;
+        self.lock_levels.whole_file_locked();
 
         Ok(())
     }
@@ -310,11 +495,28 @@ impl File for UnixFile {
                 std::io::Error::from(e)
             ))
         })?;
-        Ok(())
+        self.lock_levels.whole_file_unlocked(self)
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        self.lock_levels.lock(self, level)
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        self.lock_levels.unlock(self, level)
+    }
+
+    fn check_reserved_lock(&self) -> Result<bool> {
+        self.lock_levels.check_reserved(self)
     }
 
     #[instrument(err, skip_all, level = Level::TRACE)]
     fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
+        if let Some(n) = self.read_mapped(pos, &c) {
+            trace!("pread n: {} (mmap)", n);
+            c.complete(n as i32);
+            return Ok(c);
+        }
         let result = unsafe {
             let r = c.as_read();
             let buf = r.buf();
@@ -483,6 +685,9 @@ impl File for UnixFile {
             #[cfg(not(target_vendor = "apple"))]
             trace!("fsync");
 
+            // Checkpoints sync the database file after writing pages back,
+            // which may have grown it past the mapped range.
+            self.grow_read_map()?;
             c.complete(0);
             Ok(c)
         }
@@ -499,7 +704,14 @@ impl File for UnixFile {
 
     #[instrument(err, skip_all, level = Level::DEBUG)]
     fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
-        let result = self.file.set_len(len);
+        let result = {
+            // Reading mapped pages past the new end of the file would fault.
+            let mut read_map = self.read_map.write();
+            read_map.mapping = None;
+            let result = self.file.set_len(len);
+            self.remap(&mut read_map)?;
+            result
+        };
         match result {
             Ok(()) => {
                 trace!("file truncated to len=({})", len);
@@ -510,6 +722,40 @@ impl File for UnixFile {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    fn allocate(&self, len: u64) -> Result<()> {
+        let size = self
+            .file
+            .metadata()
+            .map_err(|e| io_error(e, "metadata"))?
+            .len();
+        if len <= size {
+            return Ok(());
+        }
+        match fs::fallocate(
+            self.file.as_fd(),
+            fs::FallocateFlags::KEEP_SIZE,
+            size,
+            len - size,
+        ) {
+            Ok(()) => Ok(()),
+            // Only a hint: file systems without preallocation are fine.
+            Err(rustix::io::Errno::OPNOTSUPP) => Ok(()),
+            Err(e) => Err(CompletionError::RustixIOError(e).into()),
+        }
+    }
+
+    fn set_mmap_limit(&self, limit: u64) -> Result<u64> {
+        let mut read_map = self.read_map.write();
+        read_map.limit = limit.min(isize::MAX as u64);
+        self.remap(&mut read_map)?;
+        Ok(read_map.limit)
+    }
+
+    fn mmap_limit(&self) -> u64 {
+        self.read_map.read().limit
+    }
+
     fn shared_wal_lock_byte(
         &self,
         offset: u64,
@@ -595,6 +841,39 @@ mod tests {
         common::tests::test_multiple_processes_cannot_open_file(UnixIO::new);
     }
 
+    #[test]
+    fn test_lock_levels_exclude_other_processes() {
+        const PATH_VAR: &str = "TURSO_TEST_LOCK_LEVEL_PATH";
+        if let Ok(path) = std::env::var(PATH_VAR) {
+            // In the child process: the parent holds RESERVED.
+            let io = UnixIO::new().unwrap();
+            let file = io.open_file(&path, OpenFlags::NoLock, false).unwrap();
+            let reserved = file.check_reserved_lock().unwrap();
+            file.lock_level(LockLevel::Shared).unwrap();
+            let busy = matches!(file.lock_level(LockLevel::Reserved), Err(LimboError::Busy));
+            std::process::exit(if reserved && busy { 0 } else { 1 });
+        }
+
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let path = temp.path().to_str().unwrap();
+        let io = UnixIO::new().unwrap();
+        let file = io.open_file(path, OpenFlags::NoLock, false).unwrap();
+        file.lock_level(LockLevel::Reserved).unwrap();
+        assert!(file.check_reserved_lock().unwrap());
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "io::unix::tests::test_lock_levels_exclude_other_processes",
+            ])
+            .env(PATH_VAR, path)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        file.unlock_level(LockLevel::None).unwrap();
+        assert!(!file.check_reserved_lock().unwrap());
+    }
+
     #[test]
     fn test_shared_wal_map_supports_unaligned_logical_offset() {
         let file = tempfile::NamedTempFile::new().unwrap();
@@ -607,6 +886,46 @@ mod tests {
         assert_eq!(mapped.len(), 81920);
         let slice = unsafe { std::slice::from_raw_parts(mapped.ptr().as_ptr(), mapped.len()) };
         assert_eq!(&slice[..128], &bytes[4096..4096 + 128]);
-        assert_eq!(&slice[mapped.len() - 128..], &bytes[4096 + 81920 - 128..4096 + 81920]);
+        assert_eq!(
+            &slice[mapped.len() - 128..],
+            &bytes[4096 + 81920 - 128..4096 + 81920]
+        );
+    }
+
+    #[test]
+    fn test_mmap_reads_follow_writes_and_truncation() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let bytes: Vec<u8> = (0..3 * 4096).map(|i| (i % 251) as u8).collect();
+        file.as_file().write_all(&bytes).unwrap();
+        let io = UnixIO::new().unwrap();
+        let db = io
+            .open_file(file.path().to_str().unwrap(), OpenFlags::None, false)
+            .unwrap();
+        let read = |pos: u64| {
+            let buf = Arc::new(crate::Buffer::new_temporary(4096));
+            db.pread(pos, Completion::new_read(buf.clone(), |_| None))
+                .unwrap();
+            buf.as_slice().to_vec()
+        };
+
+        assert_eq!(db.set_mmap_limit(8192).unwrap(), 8192);
+        assert_eq!(read(0), bytes[..4096]);
+        // Past the mapped range reads fall back to pread.
+        assert_eq!(read(8192), bytes[8192..]);
+
+        let page = vec![7u8; 4096];
+        db.pwrite(
+            4096,
+            Arc::new(crate::Buffer::new(page.clone())),
+            Completion::new_write(|_| {}),
+        )
+        .unwrap();
+        assert_eq!(read(4096), page);
+
+        db.truncate(4096, Completion::new_trunc(|_| {})).unwrap();
+        assert_eq!(db.mmap_limit(), 8192);
+        assert_eq!(read(0), bytes[..4096]);
+        db.set_mmap_limit(0).unwrap();
+        assert_eq!(read(0), bytes[..4096]);
     }
 }
@@ -1,12 +1,17 @@
-use super::{Buffer, Completion, File, FileSyncType, OpenFlags, IO};
+use super::{
+    Buffer, Completion, File, FileSyncType, OpenFlags, SharedWalLockKind, SharedWalMappedRegion, IO,
+};
 use crate::ext::VfsMod;
 use crate::io::clock::{Clock, DefaultClock, MonotonicInstant, WallClockInstant};
-use crate::io::CompletionInner;
+use crate::sync::atomic::{AtomicU64, Ordering};
 use crate::sync::Arc;
-use crate::{LimboError, Result};
+use crate::{CompletionError, LimboError, Result};
 use std::ffi::{c_void, CString};
 use std::ptr::NonNull;
-use turso_ext::{BufferRef, IOCallback, SendPtr, VfsFileImpl, VfsImpl};
+use std::time::Duration;
+use turso_ext::{
+    BufferRef, IOCallback, LockLevel, ResultCode, SendPtr, ShmLockScope, VfsFileImpl, VfsImpl,
+};
 
 impl Clock for VfsMod {
     fn current_time_monotonic(&self) -> MonotonicInstant {
@@ -29,7 +34,14 @@ impl IO for VfsMod {
         if file.is_null() {
             return Err(LimboError::ExtensionError("File not found".to_string()));
         }
-        Ok(Arc::new(turso_ext::VfsFileImpl::new(file, self.ctx)?))
+        let file: Arc<dyn File> = Arc::new(VfsModFile {
+            file: VfsFileImpl::new(file, self.ctx)?,
+            path: std::sync::Arc::from(path),
+            counters: self.counters.clone(),
+        });
+        self.counters.opens.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(path, flags = flags.0, "opened file through VFS extension");
+        Ok(file)
     }
 
     fn remove_file(&self, path: &str) -> Result<()> {
@@ -64,9 +76,22 @@ impl IO for VfsMod {
         let vfs = unsafe { &*self.ctx };
         unsafe { (vfs.gen_random_number)() }
     }
+
+    fn supports_shared_wal_coordination(&self) -> bool {
+        if self.ctx.is_null() {
+            return false;
+        }
+        let vfs = unsafe { &*self.ctx };
+        unsafe { (vfs.supports_shm)(vfs.vfs) }
+    }
 }
 
 impl VfsMod {
+    /// Operations performed on the files opened through this VFS so far.
+    pub fn stats(&self) -> VfsStats {
+        self.counters.snapshot()
+    }
+
     #[allow(dead_code)] // used in FFI call
     fn get_current_time(&self) -> String {
         if self.ctx.is_null() {
@@ -81,85 +106,329 @@ impl VfsMod {
     }
 }
 
+/// Operations performed through an extension VFS, summed over all of its
+/// files and so over every connection to a database opened through it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VfsStats {
+    pub opens: u64,
+    pub closes: u64,
+    pub reads: u64,
+    pub bytes_read: u64,
+    pub writes: u64,
+    pub bytes_written: u64,
+    pub syncs: u64,
+    pub truncates: u64,
+    /// Operations that were rejected when submitted or completed with an
+    /// error.
+    pub errors: u64,
+    /// Time between submitting reads, writes, syncs and truncates and their
+    /// callbacks running, summed over all of them.
+    pub io_time: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct VfsCounters {
+    opens: AtomicU64,
+    closes: AtomicU64,
+    reads: AtomicU64,
+    bytes_read: AtomicU64,
+    writes: AtomicU64,
+    bytes_written: AtomicU64,
+    syncs: AtomicU64,
+    truncates: AtomicU64,
+    errors: AtomicU64,
+    io_time_us: AtomicU64,
+}
+
+impl VfsCounters {
+    fn record(&self, op: VfsOp, result: i32, elapsed: Duration) {
+        self.io_time_us
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if result < 0 {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let (count, bytes) = match op {
+            VfsOp::Read => (&self.reads, Some(&self.bytes_read)),
+            VfsOp::Write => (&self.writes, Some(&self.bytes_written)),
+            VfsOp::Sync => (&self.syncs, None),
+            VfsOp::Truncate => (&self.truncates, None),
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        if let Some(bytes) = bytes {
+            bytes.fetch_add(result as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> VfsStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        VfsStats {
+            opens: load(&self.opens),
+            closes: load(&self.closes),
+            reads: load(&self.reads),
+            bytes_read: load(&self.bytes_read),
+            writes: load(&self.writes),
+            bytes_written: load(&self.bytes_written),
+            syncs: load(&self.syncs),
+            truncates: load(&self.truncates),
+            errors: load(&self.errors),
+            io_time: Duration::from_micros(load(&self.io_time_us)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum VfsOp {
+    Read,
+    Write,
+    Sync,
+    Truncate,
+}
+
+impl VfsOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            VfsOp::Read => "read",
+            VfsOp::Write => "write",
+            VfsOp::Sync => "sync",
+            VfsOp::Truncate => "truncate",
+        }
+    }
+}
+
+/// Operation handed to an extension, passed back to [callback_fn] as the
+/// callback's context.
+struct PendingOp {
+    completion: Completion,
+    op: VfsOp,
+    file: std::sync::Arc<str>,
+    pos: u64,
+    len: usize,
+    start: MonotonicInstant,
+    counters: Arc<VfsCounters>,
+}
+
 /// # Safety
 /// the callback wrapper in the extension library is FnOnce, so we know
 /// that the into_raw/from_raw contract will hold
+///
+/// Extensions report a failed operation with a negated errno as the result.
 unsafe extern "C" fn callback_fn(result: i32, ctx: SendPtr) {
-    let completion = Completion {
-        inner: (Some(Arc::from_raw(ctx.inner().as_ptr() as *mut CompletionInner))),
-    };
-    completion.complete(result);
+    let pending = Box::from_raw(ctx.inner().as_ptr() as *mut PendingOp);
+    let elapsed = MonotonicInstant::now().duration_since(pending.start);
+    pending.counters.record(pending.op, result, elapsed);
+    tracing::trace!(
+        op = pending.op.as_str(),
+        path = &*pending.file,
+        pos = pending.pos,
+        len = pending.len,
+        result,
+        elapsed_us = elapsed.as_micros() as u64,
+        "VFS operation completed"
+    );
+    if result < 0 {
+        let kind = std::io::Error::from_raw_os_error(-result).kind();
+        pending
+            .completion
+            .error(CompletionError::IOError(kind, "vfs"));
+    } else {
+        pending.completion.complete(result);
+    }
 }
 
-fn to_callback(c: Completion) -> IOCallback {
-    IOCallback::new(callback_fn, unsafe {
-        NonNull::new_unchecked(Arc::into_raw(c.get_inner().clone()) as *mut c_void)
-    })
+fn shm_lock_scope(kind: SharedWalLockKind) -> ShmLockScope {
+    match kind {
+        SharedWalLockKind::LinuxOfd => ShmLockScope::OpenFile,
+        SharedWalLockKind::ProcessScopedFcntl => ShmLockScope::Process,
+    }
 }
 
-impl File for VfsFileImpl {
-    fn lock_file(&self, exclusive: bool) -> Result<()> {
+/// Region of a shared-memory file mapped by an extension VFS, handed back to
+/// the extension when dropped.
+struct VfsShmMapping {
+    vfs: *const VfsImpl,
+    ptr: NonNull<u8>,
+    offset: u64,
+    len: usize,
+}
+
+// SAFETY: the mapping is shared memory that callers synchronize through the
+// WAL coordination locks, and the `VfsImpl` lives as long as the VFS is
+// registered.
+unsafe impl Send for VfsShmMapping {}
+unsafe impl Sync for VfsShmMapping {}
+
+impl SharedWalMappedRegion for VfsShmMapping {
+    fn ptr(&self) -> NonNull<u8> {
+        self.ptr
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Drop for VfsShmMapping {
+    fn drop(&mut self) {
         let vfs = unsafe { &*self.vfs };
-        let result = unsafe { (vfs.lock)(self.file, exclusive) };
-        if result.is_ok() {
-            return Err(LimboError::ExtensionError(result.to_string()));
+        let result = unsafe { (vfs.shm_unmap)(vfs.vfs, self.ptr.as_ptr(), self.offset, self.len) };
+        if !result.is_ok() {
+            tracing::warn!("failed to unmap VFS shared memory: {result}");
         }
-        Ok(())
+    }
+}
+
+/// File opened through an extension VFS.
+struct VfsModFile {
+    file: VfsFileImpl,
+    path: std::sync::Arc<str>,
+    counters: Arc<VfsCounters>,
+}
+
+impl VfsModFile {
+    /// Callback completing `c` once the extension is done with `op`.
+    fn callback(&self, c: Completion, op: VfsOp, pos: u64, len: usize) -> IOCallback {
+        tracing::trace!(
+            op = op.as_str(),
+            path = &*self.path,
+            pos,
+            len,
+            "submitting VFS operation"
+        );
+        let pending = Box::new(PendingOp {
+            completion: c,
+            op,
+            file: self.path.clone(),
+            pos,
+            len,
+            start: MonotonicInstant::now(),
+            counters: self.counters.clone(),
+        });
+        IOCallback::new(callback_fn, unsafe {
+            NonNull::new_unchecked(Box::into_raw(pending) as *mut c_void)
+        })
+    }
+
+    fn rejected(&self, op: VfsOp, res: ResultCode) {
+        self.counters.errors.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(
+            op = op.as_str(),
+            path = &*self.path,
+            "VFS rejected operation: {res}"
+        );
+    }
+}
+
+impl Drop for VfsModFile {
+    fn drop(&mut self) {
+        self.counters.closes.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(
+            path = &*self.path,
+            "closing file opened through VFS extension"
+        );
+    }
+}
+
+impl File for VfsModFile {
+    fn lock_file(&self, exclusive: bool) -> Result<()> {
+        let level = if exclusive {
+            LockLevel::Exclusive
+        } else {
+            LockLevel::Shared
+        };
+        self.lock_level(level).map_err(|e| match e {
+            LimboError::Busy => LimboError::LockingError(
+                "Failed locking file. File is locked by another process".to_string(),
+            ),
+            e => e,
+        })
     }
 
     fn unlock_file(&self) -> Result<()> {
-        if self.vfs.is_null() {
+        self.unlock_level(LockLevel::None)
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        if self.file.vfs.is_null() {
             return Err(LimboError::ExtensionError("VFS is null".to_string()));
         }
-        let vfs = unsafe { &*self.vfs };
-        let result = unsafe { (vfs.unlock)(self.file) };
-        if result.is_ok() {
+        let vfs = unsafe { &*self.file.vfs };
+        let result = unsafe { (vfs.lock_level)(self.file.file, level) };
+        match result {
+            ResultCode::OK => Ok(()),
+            ResultCode::Busy => Err(LimboError::Busy),
+            _ => Err(LimboError::ExtensionError(result.to_string())),
+        }
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        if self.file.vfs.is_null() {
+            return Err(LimboError::ExtensionError("VFS is null".to_string()));
+        }
+        let vfs = unsafe { &*self.file.vfs };
+        let result = unsafe { (vfs.unlock_level)(self.file.file, level) };
+        if !result.is_ok() {
             return Err(LimboError::ExtensionError(result.to_string()));
         }
         Ok(())
     }
 
+    fn check_reserved_lock(&self) -> Result<bool> {
+        if self.file.vfs.is_null() {
+            return Err(LimboError::ExtensionError("VFS is null".to_string()));
+        }
+        let vfs = unsafe { &*self.file.vfs };
+        let mut reserved = false;
+        let result = unsafe { (vfs.check_reserved_lock)(self.file.file, &mut reserved) };
+        if !result.is_ok() {
+            return Err(LimboError::ExtensionError(result.to_string()));
+        }
+        Ok(reserved)
+    }
+
     fn pread(&self, pos: u64, c: Completion) -> Result<Completion> {
-        if self.vfs.is_null() {
+        if self.file.vfs.is_null() {
             c.complete(-1);
             return Err(LimboError::ExtensionError("VFS is null".to_string()));
         }
         let r = c.as_read();
         let buf = r.buf();
         let len = buf.len();
-        let cb = to_callback(c.clone());
-        let vfs = unsafe { &*self.vfs };
+        let cb = self.callback(c.clone(), VfsOp::Read, pos, len);
+        let vfs = unsafe { &*self.file.vfs };
         let res = unsafe {
             (vfs.read)(
-                self.file,
+                self.file.file,
                 BufferRef::new(buf.as_mut_ptr(), len),
                 pos as i64,
                 cb,
             )
         };
         if res.is_error() {
+            self.rejected(VfsOp::Read, res);
             return Err(LimboError::ExtensionError("pread failed".to_string()));
         }
         Ok(c)
     }
 
     fn pwrite(&self, pos: u64, buffer: Arc<Buffer>, c: Completion) -> Result<Completion> {
-        if self.vfs.is_null() {
+        if self.file.vfs.is_null() {
             c.complete(-1);
             return Err(LimboError::ExtensionError("VFS is null".to_string()));
         }
-        let vfs = unsafe { &*self.vfs };
+        let vfs = unsafe { &*self.file.vfs };
         let res = unsafe {
             let len = buffer.len();
-            let cb = to_callback(c.clone());
+            let cb = self.callback(c.clone(), VfsOp::Write, pos, len);
             (vfs.write)(
-                self.file,
+                self.file.file,
                 BufferRef::new(buffer.as_ptr() as *mut u8, len),
                 pos as i64,
                 cb,
             )
         };
         if res.is_error() {
+            self.rejected(VfsOp::Write, res);
             return Err(LimboError::ExtensionError("pwrite failed".to_string()));
         }
         // Keep the buffer alive until the VFS completion fires — the extension
@@ -169,22 +438,23 @@ impl File for VfsFileImpl {
     }
 
     fn sync(&self, c: Completion, _sync_type: FileSyncType) -> Result<Completion> {
-        if self.vfs.is_null() {
+        if self.file.vfs.is_null() {
             c.complete(-1);
             return Err(LimboError::ExtensionError("VFS is null".to_string()));
         }
-        let vfs = unsafe { &*self.vfs };
-        let cb = to_callback(c.clone());
-        let res = unsafe { (vfs.sync)(self.file, cb) };
+        let vfs = unsafe { &*self.file.vfs };
+        let cb = self.callback(c.clone(), VfsOp::Sync, 0, 0);
+        let res = unsafe { (vfs.sync)(self.file.file, cb) };
         if res.is_error() {
+            self.rejected(VfsOp::Sync, res);
             return Err(LimboError::ExtensionError("sync failed".to_string()));
         }
         Ok(c)
     }
 
     fn size(&self) -> Result<u64> {
-        let vfs = unsafe { &*self.vfs };
-        let result = unsafe { (vfs.size)(self.file) };
+        let vfs = unsafe { &*self.file.vfs };
+        let result = unsafe { (vfs.size)(self.file.file) };
         if result < 0 {
             Err(LimboError::ExtensionError("size failed".to_string()))
         } else {
@@ -193,18 +463,122 @@ impl File for VfsFileImpl {
     }
 
     fn truncate(&self, len: u64, c: Completion) -> Result<Completion> {
-        if self.vfs.is_null() {
+        if self.file.vfs.is_null() {
             c.complete(-1);
             return Err(LimboError::ExtensionError("VFS is null".to_string()));
         }
-        let vfs = unsafe { &*self.vfs };
-        let cb = to_callback(c.clone());
-        let res = unsafe { (vfs.truncate)(self.file, len as i64, cb) };
+        let vfs = unsafe { &*self.file.vfs };
+        let cb = self.callback(c.clone(), VfsOp::Truncate, len, 0);
+        let res = unsafe { (vfs.truncate)(self.file.file, len as i64, cb) };
         if res.is_error() {
+            self.rejected(VfsOp::Truncate, res);
             return Err(LimboError::ExtensionError("truncate failed".to_string()));
         }
         Ok(c)
     }
+
+    fn allocate(&self, len: u64) -> Result<()> {
+        if self.file.vfs.is_null() {
+            return Err(LimboError::ExtensionError("VFS is null".to_string()));
+        }
+        let vfs = unsafe { &*self.file.vfs };
+        let result = unsafe { (vfs.allocate)(self.file.file, len as i64) };
+        if !result.is_ok() {
+            return Err(LimboError::ExtensionError(result.to_string()));
+        }
+        Ok(())
+    }
+
+    fn shared_wal_lock_byte(
+        &self,
+        offset: u64,
+        exclusive: bool,
+        kind: SharedWalLockKind,
+    ) -> Result<()> {
+        shm_lock(&self.file, offset, exclusive, true, kind).map(|_| ())
+    }
+
+    fn shared_wal_try_lock_byte(
+        &self,
+        offset: u64,
+        exclusive: bool,
+        kind: SharedWalLockKind,
+    ) -> Result<bool> {
+        shm_lock(&self.file, offset, exclusive, false, kind)
+    }
+
+    fn shared_wal_unlock_byte(&self, offset: u64, kind: SharedWalLockKind) -> Result<()> {
+        if self.file.vfs.is_null() {
+            return Err(LimboError::ExtensionError("VFS is null".to_string()));
+        }
+        let vfs = unsafe { &*self.file.vfs };
+        let result = unsafe { (vfs.shm_unlock)(self.file.file, offset, shm_lock_scope(kind)) };
+        if !result.is_ok() {
+            return Err(LimboError::ExtensionError(result.to_string()));
+        }
+        Ok(())
+    }
+
+    fn shared_wal_set_len(&self, len: u64) -> Result<()> {
+        if self.file.vfs.is_null() {
+            return Err(LimboError::ExtensionError("VFS is null".to_string()));
+        }
+        let vfs = unsafe { &*self.file.vfs };
+        let result = unsafe { (vfs.shm_set_len)(self.file.file, len) };
+        if !result.is_ok() {
+            return Err(LimboError::ExtensionError(result.to_string()));
+        }
+        Ok(())
+    }
+
+    fn shared_wal_map(&self, offset: u64, len: usize) -> Result<Box<dyn SharedWalMappedRegion>> {
+        if self.file.vfs.is_null() {
+            return Err(LimboError::ExtensionError("VFS is null".to_string()));
+        }
+        let vfs = unsafe { &*self.file.vfs };
+        let mut ptr = std::ptr::null_mut();
+        let result = unsafe { (vfs.shm_map)(self.file.file, offset, len, &mut ptr) };
+        if !result.is_ok() {
+            return Err(LimboError::ExtensionError(result.to_string()));
+        }
+        let ptr = NonNull::new(ptr).ok_or_else(|| {
+            LimboError::ExtensionError("VFS returned a null shared memory mapping".to_string())
+        })?;
+        Ok(Box::new(VfsShmMapping {
+            vfs: self.file.vfs,
+            ptr,
+            offset,
+            len,
+        }))
+    }
+}
+
+fn shm_lock(
+    file: &VfsFileImpl,
+    offset: u64,
+    exclusive: bool,
+    blocking: bool,
+    kind: SharedWalLockKind,
+) -> Result<bool> {
+    if file.vfs.is_null() {
+        return Err(LimboError::ExtensionError("VFS is null".to_string()));
+    }
+    let vfs = unsafe { &*file.vfs };
+    let mut acquired = false;
+    let result = unsafe {
+        (vfs.shm_lock)(
+            file.file,
+            offset,
+            exclusive,
+            blocking,
+            shm_lock_scope(kind),
+            &mut acquired,
+        )
+    };
+    if !result.is_ok() {
+        return Err(LimboError::ExtensionError(result.to_string()));
+    }
+    Ok(acquired)
 }
 
 impl Drop for VfsMod {
@@ -2,7 +2,9 @@ use crate::error::io_error;
 use crate::io::clock::{DefaultClock, MonotonicInstant, WallClockInstant};
 use crate::io::common;
 use crate::io::FileSyncType;
+use super::lock_level::{LockLevels, RangeLocks};
 use super::windows_lock::{acquire_process_file_lock, ProcessFileLockGuard};
+use super::LockLevel;
 use crate::sync::Arc;
 use crate::{Clock, Completion, CompletionError, File, LimboError, OpenFlags, Result, IO};
 use std::cell::Cell;
@@ -12,8 +14,8 @@ use std::ptr;
 use tracing::debug;
 use tracing::{instrument, trace, Level};
 use windows_sys::Win32::Foundation::{
-    CloseHandle, GetLastError, ERROR_HANDLE_EOF, ERROR_IO_PENDING, FALSE, GENERIC_READ,
-    GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE, TRUE, WAIT_OBJECT_0,
+    CloseHandle, GetLastError, ERROR_HANDLE_EOF, ERROR_IO_PENDING, ERROR_LOCK_VIOLATION, FALSE,
+    GENERIC_READ, GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE, TRUE, WAIT_OBJECT_0,
 };
 use windows_sys::Win32::Storage::FileSystem::{
     CreateFileW, FileEndOfFileInfo, FlushFileBuffers, GetFileSizeEx, LockFileEx, ReadFile,
@@ -191,6 +193,7 @@ impl IO for WindowsIO {
         Ok(Arc::new(WindowsFile {
             handle: file_handle,
             _process_lock: process_lock,
+            lock_levels: LockLevels::new(),
         }))
     }
 
@@ -223,11 +226,68 @@ pub struct WindowsFile {
     /// in a dedicated handle managed by `ProcessFileLockEntry`; this field
     /// just keeps the registry refcount up. Dropped automatically with `self`.
     _process_lock: Option<ProcessFileLockGuard>,
+    lock_levels: LockLevels,
 }
 
 unsafe impl Send for WindowsFile {}
 unsafe impl Sync for WindowsFile {}
 
+/// `LockFileEx` locks, which can't be converted in place.
+impl RangeLocks for WindowsFile {
+    const CONVERTS_IN_PLACE: bool = false;
+
+    fn lock_range(&self, start: u64, len: u64, exclusive: bool) -> Result<bool> {
+        let flags = if exclusive {
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY
+        } else {
+            LOCKFILE_FAIL_IMMEDIATELY
+        };
+        let mut overlapped = overlapped_at(start);
+        let result = unsafe {
+            LockFileEx(
+                self.handle,
+                flags,
+                0,
+                len as u32,
+                (len >> 32) as u32,
+                &mut overlapped,
+            )
+        };
+        if result != FALSE {
+            return Ok(true);
+        }
+        match unsafe { GetLastError() } {
+            // The handle is overlapped: a lock that can't be had right away
+            // may be reported as pending.
+            ERROR_LOCK_VIOLATION | ERROR_IO_PENDING => Ok(false),
+            _ => Err(LimboError::LockingError(format!(
+                "Failed locking file, {}",
+                std::io::Error::last_os_error()
+            ))),
+        }
+    }
+
+    fn unlock_range(&self, start: u64, len: u64) -> Result<()> {
+        let mut overlapped = overlapped_at(start);
+        let result = unsafe {
+            UnlockFileEx(
+                self.handle,
+                0,
+                len as u32,
+                (len >> 32) as u32,
+                &mut overlapped,
+            )
+        };
+        if result == FALSE {
+            let err = std::io::Error::last_os_error();
+            return Err(LimboError::LockingError(format!(
+                "Failed to release file lock: {err}"
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl File for WindowsFile {
     #[instrument(err, skip_all, level = Level::TRACE)]
     fn lock_file(&self, exclusive: bool) -> Result<()> {
@@ -253,6 +313,7 @@ impl File for WindowsFile {
             };
             return Err(LimboError::LockingError(message));
         }
+        self.lock_levels.whole_file_locked();
         Ok(())
     }
 
@@ -269,7 +330,19 @@ impl File for WindowsFile {
                 "Failed to release file lock: {err}"
             )));
         }
-        Ok(())
+        self.lock_levels.whole_file_unlocked(self)
+    }
+
+    fn lock_level(&self, level: LockLevel) -> Result<()> {
+        self.lock_levels.lock(self, level)
+    }
+
+    fn unlock_level(&self, level: LockLevel) -> Result<()> {
+        self.lock_levels.unlock(self, level)
+    }
+
+    fn check_reserved_lock(&self) -> Result<bool> {
+        self.lock_levels.check_reserved(self)
     }
 
     #[instrument(skip(self, c), level = Level::TRACE)]
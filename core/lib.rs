@@ -70,6 +70,7 @@ mod pragma;
 mod progress;
 mod pseudo;
 mod regexp;
+mod schema_cache;
 #[cfg(feature = "series")]
 mod series;
 mod stack;
@@ -85,8 +86,11 @@ mod uuid;
 #[cfg(not(any(feature = "fuzz", feature = "bench")))]
 mod vdbe;
 mod vtab;
+mod workload;
 
 pub use function::Func;
+#[cfg(feature = "coverage")]
+pub use vdbe::coverage;
 #[cfg(any(feature = "fuzz", feature = "bench"))]
 pub use function::MathFunc;
 
@@ -98,7 +102,7 @@ use crate::{
     schema::Trigger,
     stats::refresh_analyze_stats,
     storage::{
-        checksum::CHECKSUM_REQUIRED_RESERVED_BYTES,
+        checksum::{CHECKSUM_PAGE_SIZE, CHECKSUM_REQUIRED_RESERVED_BYTES},
         encryption::{AtomicCipherMode, SQLITE_HEADER, TURSO_HEADER_PREFIX},
         journal_mode,
         pager::{self, AutoVacuumMode, HeaderRef, HeaderRefMut},
@@ -140,13 +144,17 @@ pub use connection::{resolve_ext_path, Connection, Row, StepResult, SymbolTable}
 pub(crate) use connection::{AtomicTransactionState, TransactionState};
 pub use dialect::{Dialect, SqliteDialect};
 pub use error::{io_error, CompletionError, LimboError};
+pub use ext::{
+    cancel_auto_extension, register_auto_extension, reset_auto_extension, AutoExtension,
+    ExtensionCapabilities,
+};
 pub use function::ContextCollationFunction;
 #[cfg(feature = "io_memory_yield")]
 pub use io::MemoryYieldIO;
 #[cfg(all(feature = "fs", target_family = "unix", not(miri)))]
 pub use io::UnixIO;
-#[cfg(all(feature = "fs", target_os = "linux", feature = "io_uring", not(miri)))]
-pub use io::UringIO;
+#[cfg(feature = "fs")]
+pub use io::VfsStats;
 #[cfg(all(
     feature = "fs",
     target_os = "windows",
@@ -157,20 +165,29 @@ pub use io::WindowsIOCP;
 pub use io::{
     clock::{Clock, MonotonicInstant, WallClockInstant},
     get_registered_io, list_registered_io, register_io, unregister_io, Buffer, Completion,
-    CompletionType, File, GroupCompletion, MemoryIO, OpenFlags, PlatformIO, SharedBufferData,
-    SyscallIO, WriteCompletion, IO,
+    CompletionType, CompressedIO, ContentAddressedIO, ContentAddressedStats, File, GroupCompletion,
+    HttpRangeIO, HttpRangeStats, MemoryIO, ObjectRequest, ObjectStore, ObjectStoreIO, OpenFlags,
+    PlatformIO, QuotaIO, RangeClient, RangeRequest, RangeResponse, RetryPolicy,
+    RetryingRangeClient, SharedBufferData, SyscallIO, WriteCompletion, IO,
 };
+#[cfg(feature = "io_fault")]
+pub use io::{Fault, FaultIO, FaultOp, FaultSchedule};
+#[cfg(all(feature = "fs", target_os = "linux", feature = "io_uring", not(miri)))]
+pub use io::{UringIO, UringOptions};
 pub use numeric::{nonnan::NonNan, Numeric};
 pub use statement::{ColumnTypeInfo, ColumnTypeKind, Statement, StatementStatusCounter};
 pub use storage::{
     buffer_pool::BufferPool,
+    cache_policy::PageCachePolicy,
     database::{DatabaseStorage, IOContext},
     encryption::{CipherMode, EncryptionContext, EncryptionKey},
+    page_cache::PageCacheStats,
     pager::{Page, PageRef, Pager},
     wal::{CheckpointMode, CheckpointResult, Wal, WalAutoActions, WalFile, WalFileShared},
 };
+pub use translate::advisor::IndexSuggestion;
 pub use translate::expr::{walk_expr_mut, WalkControl};
-pub use turso_ext::ContextDestructor;
+pub use turso_ext::{ContextDestructor, ErrorClass};
 pub use turso_macros::{
     turso_assert, turso_assert_all, turso_assert_eq, turso_assert_greater_than,
     turso_assert_greater_than_or_equal, turso_assert_less_than, turso_assert_less_than_or_equal,
@@ -187,6 +204,7 @@ pub use vdbe::{
     FromValueRow, PrepareContext, PreparedProgram, Program, Register,
 };
 pub use vtab::{InternalVirtualTable, InternalVirtualTableCursor, VirtualTable};
+pub use workload::{RedactionRule, WorkloadCapture};
 
 /// Database index for the main database (always 0 in SQLite).
 pub const MAIN_DB_ID: usize = 0;
@@ -237,6 +255,11 @@ pub struct DatabaseOpts {
     pub enable_without_rowid: bool,
     pub enable_experimental_mvcc_passive_checkpoint: bool,
     pub unsafe_testing: bool,
+    pub page_cache_policy: PageCachePolicy,
+    /// Open the database file buffered. Direct I/O is the default, and the
+    /// io_uring backend relies on it.
+    pub disable_direct_io: bool,
+    pub enable_page_checksums: bool,
     enable_load_extension: bool,
 }
 
@@ -310,6 +333,32 @@ impl DatabaseOpts {
         self.unsafe_testing = enable;
         self
     }
+
+    /// Evict pages from the page caches of this database according to
+    /// `policy`. Databases attached to it, its temp database and the target
+    /// of a VACUUM use the same policy.
+    pub fn with_page_cache_policy(mut self, policy: PageCachePolicy) -> Self {
+        self.page_cache_policy = policy;
+        self
+    }
+
+    /// Open the database file with direct I/O, bypassing the OS page cache.
+    /// On by default. See [IO::open_file] for what that requires from the
+    /// filesystem.
+    pub fn with_direct_io(mut self, enable: bool) -> Self {
+        self.disable_direct_io = !enable;
+        self
+    }
+
+    /// Store a checksum in the reserved bytes of every page and verify it on
+    /// read, so a corrupted page fails with an error instead of being used.
+    /// Only databases created with this option have room for the checksums,
+    /// and only 4096 byte pages carry one. It must be passed on every open:
+    /// pages written without it keep stale checksums.
+    pub fn with_page_checksums(mut self, enable: bool) -> Self {
+        self.enable_page_checksums = enable;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -460,6 +509,25 @@ pub enum TempStore {
     Memory = 2,
 }
 
+/// How thoroughly [Database::verify] checks the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyLevel {
+    /// `PRAGMA quick_check`: the btree structure, the freelist and the
+    /// constraints on every row, without comparing indexes with their tables.
+    Quick,
+    /// `PRAGMA integrity_check`: everything above, plus the content of every index.
+    Full,
+}
+
+impl VerifyLevel {
+    const fn pragma(self) -> &'static str {
+        match self {
+            VerifyLevel::Quick => "quick_check",
+            VerifyLevel::Full => "integrity_check",
+        }
+    }
+}
+
 pub(crate) type MvStore = mvcc::MvStore<mvcc::MvccClock, alloc::DynAllocator>;
 
 pub(crate) type MvCursor = mvcc::cursor::MvccLazyCursor<mvcc::MvccClock, alloc::DynAllocator>;
@@ -697,6 +765,8 @@ pub struct Database<A: alloc::ConcurrentAllocator = alloc::DynAllocator> {
     wal_path: String,
     pub io: Arc<dyn IO>,
     buffer_pool: Arc<BufferPool>,
+    /// Shared by the pagers of every connection, see [storage::io_scheduler].
+    io_scheduler: Arc<storage::io_scheduler::IoScheduler>,
     // Shared structures of a Database are the parts that are common to multiple threads that might
     // create DB connections.
     _shared_page_cache: Arc<RwLock<PageCache>>,
@@ -811,7 +881,8 @@ impl Database {
 
         let db_size = db_file.size()?;
 
-        let shared_page_cache = Arc::new(RwLock::new(PageCache::default()));
+        let shared_page_cache =
+            Arc::new(RwLock::new(PageCache::with_policy(opts.page_cache_policy)));
         let syms = SymbolTable::new();
         let arena_size = if std::env::var("TESTING").is_ok_and(|v| v.eq_ignore_ascii_case("true")) {
             BufferPool::TEST_ARENA_SIZE
@@ -857,6 +928,7 @@ impl Database {
             init_lock: Arc::new(Mutex::new(())),
             opts,
             buffer_pool: BufferPool::begin_init(io, arena_size),
+            io_scheduler: Arc::new(storage::io_scheduler::IoScheduler::new()),
             n_connections: AtomicUsize::new(0),
 
             init_page_1: Arc::new(ArcSwapOption::new(init_page_1)),
@@ -869,7 +941,7 @@ impl Database {
         };
 
         db.register_global_builtin_extensions()
-            .expect("unable to register global extensions");
+            .map_err(LimboError::ExtensionError)?;
         Ok(db)
     }
 
@@ -917,6 +989,37 @@ impl Database {
         Ok(db)
     }
 
+    /// Open an in-memory database from an image produced by
+    /// [`Connection::serialize`], like `sqlite3_deserialize`.
+    ///
+    /// The image is copied, so writes never reach `image`; pass
+    /// `OpenFlags::ReadOnly` in `options` to reject them instead. The
+    /// database is private to the returned instance and bypasses the
+    /// registry. `options.storage` must not be set.
+    pub fn deserialize(image: &[u8], options: OpenOptions) -> Result<Arc<Database>> {
+        if options.storage.is_some() {
+            return Err(LimboError::InvalidArgument(
+                "OpenOptions::storage cannot be combined with Database::deserialize".to_string(),
+            ));
+        }
+        // Every valid page size is a multiple of the smallest one.
+        if image.len() % PageSize::MIN as usize != 0 {
+            return Err(LimboError::InvalidArgument(format!(
+                "database image of {} bytes is not a whole number of pages",
+                image.len()
+            )));
+        }
+        let io: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let path = ":memory:";
+        let file = io.open_file(path, OpenFlags::Create, false)?;
+        if !image.is_empty() {
+            let c = Completion::new_write(|_| {});
+            let c = file.pwrite(0, Arc::new(Buffer::new(image.to_vec())), c)?;
+            io.wait_for_completion(c)?;
+        }
+        Self::open(io, path, options.storage(Arc::new(DatabaseFile::new(file))))
+    }
+
     #[cfg(feature = "fs")]
     #[cfg(host_shared_wal)]
     fn effective_open_flags_for_path(
@@ -1181,7 +1284,10 @@ impl Database {
             options.flags,
             options.db_opts,
         )?;
-        let file = io.open_file(path, effective_flags, true)?;
+        let file = io.open_file(path, effective_flags, !options.db_opts.disable_direct_io)?;
+        // A crashed SQLite write transaction must be rolled back before
+        // anything reads the file.
+        storage::rollback_journal::recover_hot_journal(io, path, &file, effective_flags)?;
 
         // 3. legacy open re-checks after `open_file()` in case a multiprocess
         //    authority appeared between the initial probe and the actual open
@@ -1578,6 +1684,17 @@ impl Database {
                     let schema = Schema::try_make_mut(guard)?;
                     schema.schema_version = header_schema_cookie;
 
+                    let db = state
+                        .db
+                        .as_ref()
+                        .expect("db must be initialized in Init phase");
+                    if let Some(cached) = schema_cache::lookup(db, header_schema_cookie) {
+                        **guard = cached;
+                        state.schema_guard = None;
+                        state.phase = OpenDbAsyncPhase::BootstrapMvStore;
+                        continue;
+                    }
+
                     state.phase = OpenDbAsyncPhase::LoadingSchema;
                 }
 
@@ -1612,20 +1729,22 @@ impl Database {
                         dialect.as_ref(),
                     );
 
-                    match result {
+                    let complete = match result {
                         Ok(IOResult::IO(io)) => return Ok(IOResult::IO(io)),
                         Ok(IOResult::Done(())) => {
                             // Release the schema lock
                             state.schema_guard = None;
+                            true
                         }
                         Err(LimboError::ExtensionError(e)) => {
                             // this means that a vtab exists and we no longer have the module loaded.
                             // we print a warning to the user to load the module
                             state.schema_guard = None;
                             tracing::warn!("open warning, failed to load extension: {e}");
+                            false
                         }
                         Err(e) => return Err(e),
-                    }
+                    };
 
                     // Load custom types from __turso_internal_types if the table
                     // exists and custom types are enabled. The schema loaded by
@@ -1659,6 +1778,15 @@ impl Database {
                         }
                     }
 
+                    if complete {
+                        let db = state
+                            .db
+                            .as_ref()
+                            .expect("db must be initialized in Init phase");
+                        let schema = db.schema.lock().clone();
+                        schema_cache::publish(db, &schema);
+                    }
+
                     state.phase = OpenDbAsyncPhase::BootstrapMvStore;
                 }
 
@@ -2246,6 +2374,33 @@ impl Database {
         self._connect(false, None, encryption_key)
     }
 
+    /// Check the consistency of the database on a new connection, as cheaply
+    /// as `level` allows, and return the problems found. An empty list means
+    /// the check passed. Meant for embedders that verify a long-lived database
+    /// periodically; encrypted databases must run the pragma themselves.
+    pub fn verify(self: &Arc<Database>, level: VerifyLevel) -> Result<Vec<String>> {
+        let conn = self.connect()?;
+        let rows = conn.pragma_query(level.pragma());
+        conn.close()?;
+        let problems = rows?
+            .into_iter()
+            .filter_map(|row| match row.into_iter().next() {
+                Some(Value::Text(text)) => Some(text.as_str().to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if problems.is_empty() {
+            return Err(LimboError::InternalError(format!(
+                "PRAGMA {} did not return a value",
+                level.pragma()
+            )));
+        }
+        if problems == ["ok"] {
+            return Ok(Vec::new());
+        }
+        Ok(problems)
+    }
+
     #[instrument(skip_all, level = Level::DEBUG)]
     fn _connect(
         self: &Arc<Database>,
@@ -2309,7 +2464,9 @@ impl Database {
             temp: crate::connection::TempDbContext::new(),
             attached_databases: RwLock::new(DatabaseCatalog::new()),
             query_only: AtomicBool::new(false),
+            read_only_tx: AtomicBool::new(false),
             vdbe_trace: AtomicBool::new(false),
+            poison_temp_registers: AtomicBool::new(false),
             dml_require_where: AtomicBool::new(false),
             dqs_dml: AtomicBool::new(true),
             sequence_inner_retries: AtomicU64::new(0),
@@ -2330,10 +2487,13 @@ impl Database {
             encryption_cipher_mode: AtomicCipherMode::new(encryption_cipher),
             sync_mode: AtomicSyncMode::new(SyncMode::Full),
             temp_store: AtomicTempStore::new(TempStore::Default),
+            temp_store_directory: RwLock::new(None),
+            workload_capture: RwLock::new(None),
             data_sync_retry: AtomicBool::new(false),
             busy_handler: RwLock::new(BusyHandler::None),
             progress_handler: ProgressHandler::new(),
             query_timeout_ms: AtomicU64::new(0),
+            statement_memory_limit: AtomicU64::new(0),
             interrupt_requested: AtomicBool::new(false),
             is_mvcc_bootstrap_connection: AtomicBool::new(is_mvcc_bootstrap_connection),
             full_column_names: AtomicBool::new(false),
@@ -2884,19 +3044,26 @@ impl Database {
             None
         };
 
-        let pager = Pager::new(
+        let mut pager = Pager::new(
             self.db_file.clone(),
             pager_wal,
             self.io.clone(),
-            PageCache::default(),
+            PageCache::with_policy(self.opts.page_cache_policy),
             buffer_pool,
             self.init_lock.clone(),
             self.init_page_1.clone(),
         )?;
+        pager.set_io_scheduler(self.io_scheduler.clone());
         pager.set_page_size(page_size);
         if let Some(reserved_bytes) = reserved_bytes {
             pager.set_reserved_space_bytes(reserved_bytes);
         }
+        if self.opts.enable_page_checksums
+            && matches!(cipher, CipherMode::None)
+            && page_size.get() as usize == CHECKSUM_PAGE_SIZE
+        {
+            pager.set_checksum_context();
+        }
         if disable_checksums {
             pager.reset_checksum_context();
         }
@@ -2927,7 +3094,7 @@ impl Database {
         {
             Some(vfs) => vfs,
             None => match vfs.as_ref() {
-                "memory" => Arc::new(MemoryIO::new()),
+                "memory" | "memdb" => Arc::new(MemoryIO::new()),
                 #[cfg(feature = "io_memory_yield")]
                 "memory_yield" => Arc::new(MemoryYieldIO::new()),
                 "syscall" => Arc::new(SyscallIO::new()?),
@@ -3068,6 +3235,11 @@ impl Database {
         self.opts.enable_vacuum
     }
 
+    /// The eviction policy of the page caches of this database.
+    pub fn page_cache_policy(&self) -> PageCachePolicy {
+        self.opts.page_cache_policy
+    }
+
     pub fn experimental_mvcc_passive_checkpoint_enabled(&self) -> bool {
         self.opts.enable_experimental_mvcc_passive_checkpoint
     }
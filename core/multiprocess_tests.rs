@@ -33,6 +33,9 @@ const MULTIPROCESS_ASYNC_OPEN_CHILD_TEST: &str =
     "multiprocess_tests::multiprocess_async_open_child_process";
 const MULTIPROCESS_HOLD_OPEN_CHILD_TEST: &str =
     "multiprocess_tests::multiprocess_hold_open_child_process";
+#[cfg(unix)]
+const MULTIPROCESS_DB_FILE_LOCK_PROBE_CHILD_TEST: &str =
+    "multiprocess_tests::multiprocess_db_file_lock_probe_child_process";
 
 fn multiprocess_test_io() -> Arc<dyn IO> {
     #[cfg(all(target_os = "windows", feature = "experimental_win_iocp"))]
@@ -2798,3 +2801,83 @@ fn test_multiprocess_autoinc_burst_no_duplicates() {
 
     observer_conn.close().unwrap();
 }
+
+/// Report the lock another process holds on the database file the way a
+/// SQLite process sees it: "reserved", "shared" or "none".
+#[cfg(unix)]
+#[test]
+fn multiprocess_db_file_lock_probe_child_process() {
+    let Some(db_path) = std::env::var_os("TURSO_MULTIPROCESS_DB_PATH") else {
+        return;
+    };
+    let expected = std::env::var("TURSO_MULTIPROCESS_EXPECTED_LOCK").unwrap();
+
+    let io: Arc<dyn IO> = multiprocess_test_io();
+    let file = io
+        .open_file(db_path.to_str().unwrap(), OpenFlags::NoLock, false)
+        .unwrap();
+    let reserved = file.check_reserved_lock().unwrap();
+    let held = match (reserved, file.lock_level(crate::io::LockLevel::Exclusive)) {
+        (true, Err(LimboError::Busy)) => "reserved",
+        (false, Err(LimboError::Busy)) => "shared",
+        (false, Ok(())) => "none",
+        other => panic!("unexpected lock state: {other:?}"),
+    };
+    file.unlock_level(crate::io::LockLevel::None).unwrap();
+    assert_eq!(
+        held, expected,
+        "lock held by the parent on the database file"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn multiprocess_transactions_lock_db_file_levels() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("lock-levels.db");
+    let db_path_str = db_path.to_str().unwrap();
+    let io: Arc<dyn IO> = multiprocess_test_io();
+    let db = open_multiprocess_db(io, db_path_str).unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute("create table test(id integer primary key, value text)")
+        .unwrap();
+
+    let current_exe = std::env::current_exe().unwrap();
+    let probe = |expected: &str| {
+        let output = Command::new(&current_exe)
+            .arg(MULTIPROCESS_DB_FILE_LOCK_PROBE_CHILD_TEST)
+            .arg("--exact")
+            .arg("--nocapture")
+            .env("TURSO_MULTIPROCESS_DB_PATH", db_path_str)
+            .env("TURSO_MULTIPROCESS_EXPECTED_LOCK", expected)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "lock probe expecting {expected} failed: stdout={}; stderr={}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+
+    probe("none");
+    conn.execute("begin").unwrap();
+    assert_eq!(count_test_rows(&conn), 0);
+    probe("shared");
+    conn.execute("insert into test(value) values ('parent')")
+        .unwrap();
+    probe("reserved");
+    conn.execute("commit").unwrap();
+    probe("none");
+
+    // A second connection of this process keeps the lock while the first
+    // one ends its transaction.
+    let reader = db.connect().unwrap();
+    reader.execute("begin").unwrap();
+    assert_eq!(count_test_rows(&reader), 1);
+    conn.execute("insert into test(value) values ('parent')")
+        .unwrap();
+    probe("shared");
+    reader.execute("commit").unwrap();
+    probe("none");
+}
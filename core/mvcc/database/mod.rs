@@ -7957,7 +7957,7 @@ impl<Clock: LogicalClock, A: ConcurrentAllocator> MvStore<Clock, A> {
                     // TRUNCATE checkpoint below isn't blocked by our own read lock.
                     // Idempotent across re-entry (holds_read_lock is checked first).
                     if wal.holds_read_lock() {
-                        wal.end_read_tx();
+                        pager.end_read_tx();
                     }
                     let file = self.get_logical_log_file();
                     // Header is never encrypted; no EncryptionContext needed.
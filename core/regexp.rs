@@ -1,13 +1,13 @@
-use crate::ext::register_scalar_function;
+use crate::ext::register_deterministic_scalar_function;
 use turso_ext::{scalar, ExtensionApi, Value};
 
 pub fn register_extension(ext_api: &mut ExtensionApi) {
     unsafe {
-        register_scalar_function(ext_api.ctx, c"regexp".as_ptr(), regexp);
+        register_deterministic_scalar_function(ext_api.ctx, c"regexp".as_ptr(), regexp);
     }
 }
 
-#[scalar(name = "regexp")]
+#[scalar(name = "regexp", deterministic)]
 fn regexp(args: &[Value]) -> Value {
     if !(1..=2).contains(&args.len()) {
         return Value::error_with_message("wrong number of arguments to function regexp()".into());
@@ -2123,6 +2123,7 @@ impl Schema {
                             mod_name,
                             module_args_from_sql(sql)?,
                             syms,
+                            true,
                         )?
                     };
                     self.add_virtual_table(vtab)?;
@@ -5885,7 +5886,7 @@ impl Index {
 
     /// Walk the where_clause Expr of a partial index and validate that it doesn't reference any other
     /// tables or use any disallowed constructs.
-    pub fn validate_where_expr(&self, table: &Table, _resolver: &Resolver) -> bool {
+    pub fn validate_where_expr(&self, table: &Table, resolver: &Resolver) -> bool {
         let Some(where_clause) = &self.where_clause else {
             return true;
         };
@@ -5901,7 +5902,15 @@ impl Index {
         let is_tbl = |ns: &str| normalize_ident(ns) == tbl_norm;
         let is_deterministic_fn = |name: &str, argc: usize| {
             let n = normalize_ident(name);
-            Func::resolve_function(&n, argc).is_ok_and(|f| f.is_some_and(|f| f.is_deterministic()))
+            match Func::resolve_function(&n, argc) {
+                Ok(Some(f)) => f.is_deterministic(),
+                // Extension functions qualify only if registered as deterministic.
+                Ok(None) => resolver
+                    .symbol_table
+                    .resolve_function(&n, argc)
+                    .is_some_and(|f| f.is_deterministic()),
+                Err(_) => false,
+            }
         };
 
         let mut ok = true;
@@ -0,0 +1,244 @@
+//! Process-wide cache of parsed schemas, keyed by file identity and schema cookie.
+//!
+//! Loading a schema means reading and parsing every row of `sqlite_schema`.
+//! Within one [Database] the parsed schema is already shared by all of its
+//! connections, but a fresh [Database] for a file that was opened before in
+//! this process (after every earlier handle was dropped), or a connection that
+//! sees another process bump the cookie to a value this process has already
+//! parsed, would parse it again. Both consult this cache first.
+//!
+//! An entry is only valid for the exact cookie it was parsed at. Any schema
+//! change bumps the cookie, so a stale entry simply misses, and the schema
+//! parsed at the new cookie replaces it.
+//!
+//! Only files with a real identity on the host filesystem are cached: backends
+//! without inodes identify files by path, so unrelated databases that happen to
+//! share a path (e.g. `test.db` on two MemoryIOs) would share entries. Since an
+//! inode can be reused by a file created after the cached one was deleted, the
+//! key also holds the file's creation time.
+
+use crate::io::FileId;
+use crate::schema::{Schema, Table};
+use crate::sync::{Arc, LazyLock};
+use crate::{is_memory_like, Database};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap as HashMap;
+
+/// Number of files whose schema is kept. Beyond that the least recently used
+/// entry is dropped.
+const SCHEMA_CACHE_CAPACITY: usize = 64;
+
+/// Everything besides the file contents that parsing a schema depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SchemaCacheKey {
+    file_id: FileId,
+    created: std::time::SystemTime,
+    dialect: &'static str,
+    custom_types: bool,
+    generated_columns: bool,
+}
+
+struct SchemaCacheEntry {
+    cookie: u32,
+    schema: Arc<Schema>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct SchemaCache {
+    entries: HashMap<SchemaCacheKey, SchemaCacheEntry>,
+    clock: u64,
+}
+
+static SCHEMA_CACHE: LazyLock<Mutex<SchemaCache>> =
+    LazyLock::new(|| Mutex::new(SchemaCache::default()));
+
+fn cache_key(db: &Database) -> Option<SchemaCacheKey> {
+    // In-memory databases have no identity beyond their `Database`, and with
+    // MVCC the schema also reflects rows not yet checkpointed to the file.
+    if is_memory_like(&db.path) || db.get_mv_store().is_some() {
+        return None;
+    }
+    let file_id = db.io.file_id(&db.path).ok()?;
+    if file_id.is_synthetic() {
+        return None;
+    }
+    // Filesystems that don't record creation times can't tell a recreated
+    // file from the original, so their files aren't cached.
+    let created = std::fs::metadata(&db.path)
+        .and_then(|metadata| metadata.created())
+        .ok()?;
+    Some(SchemaCacheKey {
+        file_id,
+        created,
+        dialect: db.dialect.name(),
+        custom_types: db.experimental_custom_types_enabled(),
+        generated_columns: db.experimental_generated_columns_enabled(),
+    })
+}
+
+/// Whether `schema` only holds immutable objects that don't depend on the
+/// extensions loaded by whoever parsed it.
+fn is_shareable(schema: &Schema) -> bool {
+    schema.incremental_views.is_empty()
+        && schema.tables.values().all(|table| match table.as_ref() {
+            Table::Virtual(vtab) => {
+                matches!(vtab.kind, turso_ext::VTabKind::TableValuedFunction)
+            }
+            _ => true,
+        })
+        && schema
+            .indexes
+            .values()
+            .flatten()
+            .all(|index| index.index_method.is_none())
+}
+
+/// The schema of `db`'s file as parsed at `cookie`, if this process has one.
+pub(crate) fn lookup(db: &Database, cookie: u32) -> Option<Arc<Schema>> {
+    let key = cache_key(db)?;
+    let mut cache = SCHEMA_CACHE.lock();
+    cache.clock += 1;
+    let now = cache.clock;
+    let entry = cache.entries.get_mut(&key)?;
+    if entry.cookie != cookie {
+        return None;
+    }
+    entry.last_used = now;
+    tracing::debug!("schema cache hit: path={}, cookie={}", db.path, cookie);
+    Some(entry.schema.clone())
+}
+
+/// Remember `schema`, freshly parsed from `db`'s file, for later [lookup]s.
+pub(crate) fn publish(db: &Database, schema: &Arc<Schema>) {
+    // A fresh file has nothing worth caching.
+    if schema.schema_version == 0 || !is_shareable(schema) {
+        return;
+    }
+    let Some(key) = cache_key(db) else {
+        return;
+    };
+    let mut cache = SCHEMA_CACHE.lock();
+    cache.clock += 1;
+    let last_used = cache.clock;
+    if !cache.entries.contains_key(&key) && cache.entries.len() >= SCHEMA_CACHE_CAPACITY {
+        let oldest = cache
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            cache.entries.remove(&oldest);
+        }
+    }
+    cache.entries.insert(
+        key,
+        SchemaCacheEntry {
+            cookie: schema.schema_version,
+            schema: schema.clone(),
+            last_used,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DatabaseOpts, OpenFlags, PlatformIO, SqliteDialect, IO};
+
+    fn open(path: &str) -> Arc<Database> {
+        let io: Arc<dyn IO> = Arc::new(PlatformIO::new().unwrap());
+        Database::open_file_with_flags(
+            io,
+            path,
+            OpenFlags::default(),
+            DatabaseOpts::new(),
+            None,
+            Arc::new(SqliteDialect),
+        )
+        .unwrap()
+    }
+
+    fn records_creation_time(dir: &std::path::Path) -> bool {
+        std::fs::metadata(dir)
+            .and_then(|metadata| metadata.created())
+            .is_ok()
+    }
+
+    #[test]
+    fn test_reopened_database_reuses_schema_until_cookie_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        if !records_creation_time(dir.path()) {
+            return;
+        }
+        let path = dir.path().join("cached.db");
+        let path = path.to_str().unwrap();
+        {
+            let db = open(path);
+            let conn = db.connect().unwrap();
+            conn.execute("CREATE TABLE t(x)").unwrap();
+            conn.close().unwrap();
+        }
+
+        let parsed = {
+            let db = open(path);
+            let schema = db.schema.lock().clone();
+            assert!(Arc::ptr_eq(
+                &lookup(&db, schema.schema_version).unwrap(),
+                &schema
+            ));
+            schema
+        };
+
+        let db = open(path);
+        assert!(Arc::ptr_eq(&db.schema.lock(), &parsed));
+        let conn = db.connect().unwrap();
+        conn.execute("INSERT INTO t VALUES (1)").unwrap();
+        conn.execute("CREATE TABLE u(y)").unwrap();
+        assert!(lookup(&db, db.schema.lock().schema_version).is_none());
+        conn.close().unwrap();
+    }
+
+    #[test]
+    fn test_recreated_file_does_not_reuse_schema() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("recreated.db");
+        let path = path.to_str().unwrap();
+        for table in ["t", "u"] {
+            // Both files are at the same schema cookie, and the second one
+            // may well get the inode of the first.
+            for suffix in ["", "-wal"] {
+                let _ = std::fs::remove_file(format!("{path}{suffix}"));
+            }
+            let db = open(path);
+            let conn = db.connect().unwrap();
+            conn.execute(format!("CREATE TABLE {table}(x)")).unwrap();
+            conn.close().unwrap();
+            drop(db);
+
+            let db = open(path);
+            let schema = db.schema.lock().clone();
+            let other = if table == "t" { "u" } else { "t" };
+            assert!(schema.get_table(table).is_some());
+            assert!(schema.get_table(other).is_none());
+        }
+    }
+
+    #[test]
+    fn test_databases_without_inodes_are_not_cached() {
+        let io: Arc<dyn IO> = Arc::new(crate::MemoryIO::new());
+        let db = Database::open_file_with_flags(
+            io,
+            "test.db",
+            OpenFlags::default(),
+            DatabaseOpts::new(),
+            None,
+            Arc::new(SqliteDialect),
+        )
+        .unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(x)").unwrap();
+        assert!(cache_key(&db).is_none());
+        assert!(lookup(&db, db.schema.lock().schema_version).is_none());
+    }
+}
@@ -64,6 +64,8 @@ pub enum StatementStatusCounter {
     Reprepare,
     RowsRead,
     RowsWritten,
+    /// Most bytes held at once by the statement's sorters.
+    MemoryHighWater,
 }
 
 impl StatementOrigin {
@@ -313,6 +315,9 @@ pub struct Statement {
     /// True if this statement called `Connection::start_nested()` during
     /// construction and therefore must call `end_nested()` on drop.
     nested_guard_active: bool,
+    /// True once the current execution has been written to the connection's
+    /// workload capture, so that steps resumed after IO don't record it again.
+    workload_recorded: bool,
 }
 
 crate::assert::assert_send_sync!(Statement);
@@ -368,6 +373,7 @@ impl Statement {
             origin,
             counted_as_active_root: false,
             nested_guard_active,
+            workload_recorded: false,
         }
     }
 
@@ -449,6 +455,7 @@ impl Statement {
             StatementStatusCounter::Reprepare => metrics.reprepares,
             StatementStatusCounter::RowsRead => metrics.rows_read,
             StatementStatusCounter::RowsWritten => metrics.rows_written,
+            StatementStatusCounter::MemoryHighWater => metrics.memory_high_water,
         }
     }
 
@@ -532,6 +539,21 @@ impl Statement {
             }
         }
 
+        if !self.workload_recorded
+            && self.origin == StatementOrigin::Root
+            && self.query_mode == QueryMode::Normal
+        {
+            self.workload_recorded = true;
+            if let Some(capture) = self.program.connection.workload_capture() {
+                capture.record(
+                    self.pager.io.current_time_monotonic(),
+                    &self.program.sql,
+                    &self.program.parameters,
+                    |index| self.state.get_parameter(index),
+                )?;
+            }
+        }
+
         self.arm_query_timeout_if_needed();
 
         // If we're waiting for a busy handler timeout, check if we can proceed
@@ -1268,6 +1290,7 @@ impl Statement {
     }
 
     pub fn reset(&mut self) -> Result<()> {
+        self.workload_recorded = false;
         self.reset_internal(None, None, false)
     }
 
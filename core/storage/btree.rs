@@ -36,10 +36,10 @@ use crate::{
     turso_assert,
     types::{
         find_compare, get_tie_breaker_from_seek_op, IOCompletions, IndexInfo, RecordCompare,
-        SeekResult,
+        RecordHeaderCache, SeekResult,
     },
     util::IOExt,
-    vdbe::Register,
+    vdbe::{Register, ValueIteratorExt},
     Completion, MvStore,
 };
 use crate::{
@@ -710,6 +710,13 @@ pub trait CursorTrait: Any + Send + Sync {
     fn note_external_row_write(&mut self, _rowid: Option<i64>) {}
     /// Get the record of the entry the cursor is poiting to if any
     fn record(&mut self) -> Result<IOResult<Option<&ImmutableRecord>>>;
+    /// Decode column `column` of the current row into `dest` without
+    /// materializing the row through [CursorTrait::record]. Returns `false`,
+    /// leaving `dest` untouched, when that isn't possible and the caller has
+    /// to fall back to `record()`.
+    fn column_into_register(&mut self, _column: usize, _dest: &mut Register) -> Result<bool> {
+        Ok(false)
+    }
     /// Move the cursor based on the key and the type of operation (op).
     fn seek(&mut self, key: SeekKey<'_>, op: SeekOp) -> Result<IOResult<SeekResult>>;
     /// Seek using registers directly without serializing them into an ImmutableRecord first.
@@ -801,6 +808,9 @@ pub struct BTreeCursor {
     stack: PageStack,
     /// Reusable immutable record, used to allow better allocation strategy.
     reusable_immutable_record: Option<ImmutableRecord>,
+    /// Column offsets of the current row, shared by reads from the page and
+    /// from `reusable_immutable_record`. Invalidated together with the latter.
+    record_header_cache: RecordHeaderCache,
     /// Information about the index key structure (sort order, collation, etc)
     pub index_info: Option<Arc<IndexInfo>>,
     /// Maintain count of the number of records in the btree. Used for the `Count` opcode
@@ -1099,6 +1109,7 @@ impl BTreeCursor {
                 stack: [const { None }; BTCURSOR_MAX_DEPTH + 1],
             },
             reusable_immutable_record: None,
+            record_header_cache: RecordHeaderCache::new(),
             index_info: None,
             count: 0,
             context: None,
@@ -6506,9 +6517,61 @@ impl CursorTrait for BTreeCursor {
         Ok(IOResult::Done(self.reusable_immutable_record.as_ref()))
     }
 
+    /// Reads the column straight from the b-tree page, so a row is never
+    /// copied out of the page just to read some of its columns. When the row
+    /// spills into overflow pages only its local prefix is on the page:
+    /// columns that fit in it are still served from there, so reading the
+    /// small columns of a row doesn't pull in its large TEXT/BLOB values. Once
+    /// the row has been materialized by [CursorTrait::record], its copy is
+    /// read instead. Either way `record_header_cache` keeps the column
+    /// offsets, so the header is parsed once per row rather than per column.
+    fn column_into_register(&mut self, column: usize, dest: &mut Register) -> Result<bool> {
+        if self.needs_restore() || !self.has_record() || self.read_overflow_state.is_some() {
+            return Ok(false);
+        }
+        let (payload, complete) = match self
+            .reusable_immutable_record
+            .as_ref()
+            .filter(|record| !record.is_invalidated())
+        {
+            Some(record) => (record.get_payload(), true),
+            None => {
+                let contents = self.stack.top_ref().get_contents();
+                let cell_idx = self.stack.current_cell_index() as usize;
+                match contents.cell_get(cell_idx, self.usable_space_cached)? {
+                    BTreeCell::TableLeafCell(TableLeafCell {
+                        payload,
+                        first_overflow_page,
+                        ..
+                    })
+                    | BTreeCell::IndexInteriorCell(IndexInteriorCell {
+                        payload,
+                        first_overflow_page,
+                        ..
+                    })
+                    | BTreeCell::IndexLeafCell(IndexLeafCell {
+                        payload,
+                        first_overflow_page,
+                        ..
+                    }) => (payload, first_overflow_page.is_none()),
+                    _ => return Ok(false),
+                }
+            }
+        };
+        let Some(mut values) = self.record_header_cache.column(payload, column, complete)? else {
+            return Ok(false);
+        };
+        match values.nth_into_register(0, dest) {
+            Some(result) => result.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
     #[cfg_attr(debug_assertions, instrument(skip_all, level = Level::DEBUG))]
     fn insert(&mut self, key: &BTreeKey) -> Result<IOResult<()>> {
         tracing::debug!(valid_state = ?self.valid_state, cursor_state = ?self.state, is_write_in_progress = self.is_write_in_progress());
+        // The cell under the cursor is rewritten or shifted on the page.
+        self.record_header_cache.invalidate();
         // saveAllCursors at the head of sqlite3BtreeInsert (btree.c:9348).
         return_if_io!(self.drive_pending_peer_save(key.maybe_rowid()));
         return_if_io!(self.insert_into_page(key));
@@ -6540,6 +6603,7 @@ impl CursorTrait for BTreeCursor {
             let deleted_rowid = self.current_table_leaf_rowid();
             return_if_io!(self.drive_pending_peer_save(deleted_rowid));
             self.invalidate_count_cache();
+            self.record_header_cache.invalidate();
             self.state = CursorState::Delete(DeleteState::Start);
         }
 
@@ -7164,6 +7228,7 @@ impl CursorTrait for BTreeCursor {
         if let Some(record) = self.reusable_immutable_record.as_mut() {
             record.invalidate();
         }
+        self.record_header_cache.invalidate();
     }
 
     #[inline]
@@ -7459,6 +7524,10 @@ pub enum IntegrityCheckError {
     FreelistPointerOutOfRange { page_id: i64, pointer: i64 },
     #[error("overflow list length is {got} but should be {expected}")]
     OverflowListLengthMismatch { got: usize, expected: usize },
+    #[error("Page {page_id}: checksum mismatch")]
+    PageChecksumMismatch { page_id: i64 },
+    #[error("Page {page_id}: checksum mismatch, repaired by the newer copy in the WAL at the next checkpoint")]
+    PageChecksumMismatchRepairable { page_id: i64 },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -11399,6 +11468,124 @@ mod tests {
         }
     }
 
+    /// Random inserts, overwrites and deletes of table rows checked against a
+    /// `BTreeMap` model. Rows are big enough for the tree to grow several
+    /// interior levels, some spill into overflow pages, and the second half of
+    /// the run mostly deletes, so interior splits, sibling redistribution and
+    /// merging, and defragmentation of reused pages are all exercised. Every
+    /// few operations the tree must be well formed and a full scan must return
+    /// exactly the model's rows.
+    fn btree_table_model_fuzz_run(attempts: usize, operations: usize) {
+        const VALIDATE_INTERVAL: usize = 250;
+        let (mut rng, seed) = rng_from_time_or_env();
+        tracing::info!("super seed: {}", seed);
+
+        let row = |size: usize, byte: u8| {
+            let regs = &[Register::Value(Value::Blob(crate::alloc::vec![byte; size]))];
+            ImmutableRecord::from_registers(regs, regs.len()).unwrap()
+        };
+        for _ in 0..attempts {
+            let (pager, root_page, _db, conn) = empty_btree();
+            let mut cursor = BTreeCursor::new_table(pager.clone(), root_page, 1);
+            let mut model = std::collections::BTreeMap::<i64, (usize, u8)>::new();
+            let mut max_depth = 0;
+            for op in 0..operations {
+                pager.begin_read_tx().unwrap();
+                pager
+                    .io
+                    .block(|| pager.begin_write_tx(WalAutoActions::all_enabled()))
+                    .unwrap();
+                let delete_chance = if op < operations / 2 { 10 } else { 60 };
+                if !model.is_empty() && rng.next_u64() % 100 < delete_chance {
+                    let key = *model
+                        .keys()
+                        .nth(rng.next_u64() as usize % model.len())
+                        .unwrap();
+                    tracing::info!("DELETE FROM t WHERE rowid = {key}; -- {op}");
+                    let found = run_until_done(
+                        || cursor.seek(SeekKey::TableRowId(key), SeekOp::GE { eq_only: true }),
+                        pager.deref(),
+                    )
+                    .unwrap();
+                    assert!(
+                        matches!(found, SeekResult::Found),
+                        "key {key} not found, seed: {seed}"
+                    );
+                    run_until_done(|| cursor.delete(), pager.deref()).unwrap();
+                    model.remove(&key);
+                } else {
+                    let key = (rng.next_u64() % (1 << 14)) as i64;
+                    let size = if rng.next_u64() % 10 == 0 {
+                        4096 + rng.next_u64() % 8192
+                    } else {
+                        1000 + rng.next_u64() % 2000
+                    } as usize;
+                    let byte = rng.next_u64() as u8;
+                    tracing::info!(
+                        "INSERT OR REPLACE INTO t VALUES ({key}, {size}, {byte}); -- {op}"
+                    );
+                    run_until_done(
+                        || cursor.seek(SeekKey::TableRowId(key), SeekOp::GE { eq_only: true }),
+                        pager.deref(),
+                    )
+                    .unwrap();
+                    let value = row(size, byte);
+                    run_until_done(
+                        || cursor.insert(&BTreeKey::new_table_rowid(key, Some(&value))),
+                        pager.deref(),
+                    )
+                    .unwrap();
+                    model.insert(key, (size, byte));
+                }
+                let _c = cursor.move_to_root().unwrap();
+                pager.io.block(|| pager.commit_tx(&conn, true)).unwrap();
+                pager.end_read_tx();
+
+                if (op + 1) % VALIDATE_INTERVAL != 0 && op + 1 != operations {
+                    continue;
+                }
+                pager.begin_read_tx().unwrap();
+                if !model.is_empty() {
+                    let (depth, valid) = validate_btree(pager.clone(), root_page);
+                    assert!(
+                        valid,
+                        "invalid btree after {} operations, seed: {seed}",
+                        op + 1
+                    );
+                    max_depth = max_depth.max(depth);
+                }
+                let _c = cursor.move_to_root().unwrap();
+                run_until_done(|| cursor.rewind(), pager.deref()).unwrap();
+                for (&key, &(size, byte)) in model.iter() {
+                    assert!(cursor.has_record(), "key {key} missing, seed: {seed}");
+                    let rowid = run_until_done(|| cursor.rowid(), pager.deref())
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(rowid, key, "seed: {seed}");
+                    let record = loop {
+                        match cursor.record().unwrap() {
+                            IOResult::Done(r) => break r,
+                            IOResult::IO(io) => io.wait(&*pager.io).unwrap(),
+                        }
+                    };
+                    assert_eq!(
+                        record.unwrap().get_payload(),
+                        row(size, byte).get_payload(),
+                        "row {key} differs, seed: {seed}"
+                    );
+                    run_until_done(|| cursor.next(), pager.deref()).unwrap();
+                }
+                assert!(!cursor.has_record(), "extra rows in btree, seed: {seed}");
+                pager.end_read_tx();
+            }
+            // A leaf root has depth 2, so 4 means interior pages were split.
+            assert!(
+                max_depth >= 4,
+                "btree only reached depth {max_depth}, seed: {seed}"
+            );
+        }
+    }
+
     fn validate_expected_keys(
         pager: &Arc<Pager>,
         cursor: &mut BTreeCursor,
@@ -11560,6 +11747,11 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn btree_table_insert_delete_model_fuzz_run() {
+        btree_table_model_fuzz_run(2, 3000);
+    }
+
     #[test]
     pub fn btree_insert_fuzz_run_random() {
         btree_insert_fuzz_run(128, 16, |rng| (rng.next_u32() % 4096) as usize);
@@ -12400,6 +12592,60 @@ mod tests {
         }
     }
 
+    /// Deleting every other row of a leaf scatters its free space over
+    /// freeblocks too small for a big row. Inserting that row must compact the
+    /// page instead of splitting it.
+    #[test]
+    pub fn test_insert_into_fragmented_leaf_defragments_instead_of_splitting() {
+        let (pager, root_page, _db, conn) = empty_btree();
+        let mut cursor = BTreeCursor::new_table(pager.clone(), root_page, 1);
+        pager.begin_read_tx().unwrap();
+        pager
+            .io
+            .block(|| pager.begin_write_tx(WalAutoActions::all_enabled()))
+            .unwrap();
+        let insert = |cursor: &mut BTreeCursor, key: i64, size: usize| {
+            run_until_done(
+                || cursor.seek(SeekKey::TableRowId(key), SeekOp::GE { eq_only: true }),
+                pager.deref(),
+            )
+            .unwrap();
+            let regs = &[Register::Value(Value::Blob(crate::alloc::vec![0; size]))];
+            let value = ImmutableRecord::from_registers(regs, regs.len()).unwrap();
+            run_until_done(
+                || cursor.insert(&BTreeKey::new_table_rowid(key, Some(&value))),
+                pager.deref(),
+            )
+            .unwrap();
+        };
+        for key in 0..18 {
+            insert(&mut cursor, key, 100 + 10 * key as usize);
+        }
+        for key in (0..18).step_by(2) {
+            run_until_done(
+                || cursor.seek(SeekKey::TableRowId(key), SeekOp::GE { eq_only: true }),
+                pager.deref(),
+            )
+            .unwrap();
+            run_until_done(|| cursor.delete(), pager.deref()).unwrap();
+        }
+        let (page, _c) = cursor.read_page_blocking(root_page).unwrap();
+        let contents = page.get_contents();
+        assert_ne!(contents.first_freeblock(), 0);
+        let unallocated =
+            contents.cell_content_area() as usize - contents.unallocated_region_start();
+        assert!(unallocated < 1000);
+
+        insert(&mut cursor, 100, 1000);
+        let (page, _c) = cursor.read_page_blocking(root_page).unwrap();
+        let contents = page.get_contents();
+        assert_eq!(contents.page_type().unwrap(), PageType::TableLeaf);
+        assert_eq!(contents.cell_count(), 10);
+        assert_eq!(contents.first_freeblock(), 0);
+        assert_eq!(contents.num_frag_free_bytes(), 0);
+        pager.io.block(|| pager.commit_tx(&conn, true)).unwrap();
+    }
+
     #[test]
     pub fn test_fuzz_drop_defragment_insert() {
         let db = get_database();
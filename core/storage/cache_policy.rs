@@ -0,0 +1,761 @@
+//! Eviction policies for the [PageCache](super::page_cache::PageCache).
+//!
+//! The cache owns the pages and decides whether a page *can* be evicted
+//! (clean, unpinned, unlocked, ...). The policy only orders the resident pages
+//! and picks which evictable page goes next. It is told about every insert,
+//! access and removal, and hands out a [PolicyHandle] per page so that those
+//! notifications don't need a second lookup by key.
+
+use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListLink};
+use rustc_hash::FxHashMap as HashMap;
+
+use super::page_cache::PageCacheKey;
+use crate::turso_assert;
+
+/// Opaque per-page token returned by [EvictionPolicy::on_insert] and passed
+/// back on every later notification about that page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PolicyHandle(*mut ());
+
+pub(crate) trait EvictionPolicy {
+    /// A page that was not resident entered the cache.
+    fn on_insert(&mut self, key: PageCacheKey) -> PolicyHandle;
+    /// A resident page was read or replaced.
+    fn on_access(&mut self, handle: PolicyHandle);
+    /// A page was deleted from the cache.
+    fn on_remove(&mut self, handle: PolicyHandle);
+    /// A page was evicted, after being picked by [EvictionPolicy::victim].
+    /// Policies that remember recently evicted pages override this.
+    fn on_evict(&mut self, handle: PolicyHandle) {
+        self.on_remove(handle);
+    }
+    /// The next page to evict among those for which `evictable` is true, or
+    /// `None` if none of them can be.
+    fn victim(&mut self, evictable: &dyn Fn(PageCacheKey) -> bool) -> Option<PageCacheKey>;
+    /// Forget every page.
+    fn clear(&mut self);
+    /// Number of pages tracked.
+    fn len(&self) -> usize;
+    /// The cache now holds up to `capacity` pages.
+    fn set_capacity(&mut self, _capacity: usize) {}
+
+    #[cfg(test)]
+    fn ref_bit(&self, _handle: PolicyHandle) -> Option<u8> {
+        None
+    }
+
+    /// The page the next eviction scan starts at, for policies that have one.
+    #[cfg(test)]
+    fn hand(&self) -> Option<PageCacheKey> {
+        None
+    }
+}
+
+/// The eviction policies a database can be configured with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageCachePolicy {
+    /// SIEVE with small reference counters (GClock): accesses only bump a
+    /// counter, and a clock hand sweeping from the oldest page gives every
+    /// referenced page another chance before evicting it. Scan resistant and
+    /// cheap on hits.
+    #[default]
+    Sieve,
+    /// Least recently used: every access moves the page to the front, and the
+    /// evictable page that was used the longest time ago goes first.
+    Lru,
+    /// 2Q: new pages go through a FIFO and are only promoted to an LRU when
+    /// they come back shortly after being evicted, so one-off scans don't
+    /// push out the pages that are used over and over.
+    TwoQueue,
+    /// Adaptive replacement cache: an LRU of pages seen once and one of pages
+    /// seen more than once, with the share of each tuned by the pages that
+    /// come back after being evicted from either.
+    Arc,
+}
+
+impl PageCachePolicy {
+    /// The policy for a cache holding up to `capacity` pages.
+    pub(crate) fn build(self, capacity: usize) -> Box<dyn EvictionPolicy> {
+        match self {
+            PageCachePolicy::Sieve => Box::new(Sieve::new()),
+            PageCachePolicy::Lru => Box::new(Lru::new()),
+            PageCachePolicy::TwoQueue => Box::new(TwoQueue::new(capacity)),
+            PageCachePolicy::Arc => Box::new(AdaptiveReplacement::new(capacity)),
+        }
+    }
+}
+
+const CLEAR: u8 = 0;
+pub(crate) const REF_MAX: u8 = 3;
+
+struct SieveEntry {
+    key: PageCacheKey,
+    /// Starts at zero, bumped on access, decremented when the clock hand
+    /// passes. Only pages at zero are evicted.
+    ref_bit: u8,
+    link: LinkedListLink,
+}
+
+intrusive_adapter!(SieveAdapter = Box<SieveEntry>: SieveEntry { link: LinkedListLink });
+
+impl SieveEntry {
+    #[inline]
+    fn bump_ref(&mut self) {
+        self.ref_bit = std::cmp::min(self.ref_bit + 1, REF_MAX);
+    }
+
+    #[inline]
+    fn decrement_ref(&mut self) {
+        self.ref_bit = self.ref_bit.saturating_sub(1);
+    }
+}
+
+/// A variation of SIEVE that keeps a circular queue of pages with a small
+/// reference counter each.
+///
+/// The ring is circular. `clock_hand` points at the tail (LRU).
+/// Sweep order follows next: tail (LRU) -> head (MRU) -> .. -> tail
+/// New pages are inserted after the clock hand in the `next` direction,
+/// which places them at head (MRU) (i.e. `tail.next` is the head).
+pub(crate) struct Sieve {
+    queue: LinkedList<SieveAdapter>,
+    /// Entry the next sweep starts at, or null when the queue is empty.
+    clock_hand: *mut SieveEntry,
+    len: usize,
+}
+
+impl Sieve {
+    pub(crate) fn new() -> Self {
+        Self {
+            queue: LinkedList::new(SieveAdapter::new()),
+            clock_hand: std::ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    /// Advances the clock hand to the next entry in the circular queue.
+    /// Follows the "next" direction: from tail/LRU through the list back to tail.
+    /// With our insertion-after-hand strategy, this moves through entries in age order.
+    fn advance_clock_hand(&mut self) {
+        if self.clock_hand.is_null() {
+            return;
+        }
+
+        unsafe {
+            let mut cursor = self.queue.cursor_mut_from_ptr(self.clock_hand);
+            cursor.move_next();
+
+            if cursor.get().is_some() {
+                self.clock_hand = cursor.as_cursor().get().unwrap() as *const _ as *mut SieveEntry;
+            } else {
+                // Reached end, wrap to front
+                let front_cursor = self.queue.front_mut();
+                if front_cursor.get().is_some() {
+                    self.clock_hand =
+                        front_cursor.as_cursor().get().unwrap() as *const _ as *mut SieveEntry;
+                } else {
+                    self.clock_hand = std::ptr::null_mut();
+                }
+            }
+        }
+    }
+
+    fn entry_mut(&mut self, handle: PolicyHandle) -> &mut SieveEntry {
+        unsafe { &mut *(handle.0 as *mut SieveEntry) }
+    }
+}
+
+impl EvictionPolicy for Sieve {
+    fn on_insert(&mut self, key: PageCacheKey) -> PolicyHandle {
+        let entry = Box::new(SieveEntry {
+            key,
+            ref_bit: CLEAR,
+            link: LinkedListLink::new(),
+        });
+        let entry_ptr = if self.clock_hand.is_null() {
+            // First entry - just push it
+            self.queue.push_back(entry);
+            let entry_ptr = self.queue.back().get().unwrap() as *const _ as *mut SieveEntry;
+            self.clock_hand = entry_ptr;
+            entry_ptr
+        } else {
+            // Insert after clock hand (in circular list semantics, this makes it the new head/MRU)
+            unsafe {
+                let mut cursor = self.queue.cursor_mut_from_ptr(self.clock_hand);
+                cursor.insert_after(entry);
+                cursor.move_next();
+                cursor.get().unwrap() as *const SieveEntry as *mut SieveEntry
+            }
+        };
+        self.len += 1;
+        PolicyHandle(entry_ptr as *mut ())
+    }
+
+    #[inline]
+    fn on_access(&mut self, handle: PolicyHandle) {
+        self.entry_mut(handle).bump_ref();
+    }
+
+    fn on_remove(&mut self, handle: PolicyHandle) {
+        let entry_ptr = handle.0 as *mut SieveEntry;
+        // If clock hand points to this entry, advance it before removing
+        if self.clock_hand == entry_ptr {
+            self.advance_clock_hand();
+            // If hand is still pointing to the same entry after advance, we're removing the last entry
+            if self.clock_hand == entry_ptr {
+                self.clock_hand = std::ptr::null_mut();
+            }
+        }
+        unsafe {
+            let mut cursor = self.queue.cursor_mut_from_ptr(entry_ptr);
+            cursor.remove();
+        }
+        self.len -= 1;
+    }
+
+    /// Start at the clock hand. A page at zero that is evictable is the
+    /// victim; an evictable page above zero gets a second chance by having its
+    /// counter decremented; unevictable pages are skipped.
+    fn victim(&mut self, evictable: &dyn Fn(PageCacheKey) -> bool) -> Option<PageCacheKey> {
+        let mut examined = 0usize;
+        let max_examinations = self.len.saturating_mul(REF_MAX as usize + 1);
+        while examined < max_examinations {
+            turso_assert!(
+                !self.clock_hand.is_null(),
+                "page_cache: clock hand is null during eviction",
+                { "entries": self.len }
+            );
+            let entry = unsafe { &mut *self.clock_hand };
+            if evictable(entry.key) {
+                if entry.ref_bit == CLEAR {
+                    return Some(entry.key);
+                }
+                entry.decrement_ref();
+            }
+            self.advance_clock_hand();
+            examined += 1;
+        }
+        None
+    }
+
+    fn clear(&mut self) {
+        self.queue.clear();
+        self.clock_hand = std::ptr::null_mut();
+        self.len = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[cfg(test)]
+    fn ref_bit(&self, handle: PolicyHandle) -> Option<u8> {
+        Some(unsafe { (*(handle.0 as *const SieveEntry)).ref_bit })
+    }
+
+    #[cfg(test)]
+    fn hand(&self) -> Option<PageCacheKey> {
+        (!self.clock_hand.is_null()).then(|| unsafe { (*self.clock_hand).key })
+    }
+}
+
+struct LruEntry {
+    key: PageCacheKey,
+    link: LinkedListLink,
+}
+
+intrusive_adapter!(LruAdapter = Box<LruEntry>: LruEntry { link: LinkedListLink });
+
+/// Pages ordered from most (front) to least (back) recently used.
+pub(crate) struct Lru {
+    list: LinkedList<LruAdapter>,
+    len: usize,
+}
+
+impl Lru {
+    pub(crate) fn new() -> Self {
+        Self {
+            list: LinkedList::new(LruAdapter::new()),
+            len: 0,
+        }
+    }
+}
+
+impl EvictionPolicy for Lru {
+    fn on_insert(&mut self, key: PageCacheKey) -> PolicyHandle {
+        self.list.push_front(Box::new(LruEntry {
+            key,
+            link: LinkedListLink::new(),
+        }));
+        self.len += 1;
+        PolicyHandle(self.list.front().get().unwrap() as *const LruEntry as *mut ())
+    }
+
+    fn on_access(&mut self, handle: PolicyHandle) {
+        // Moving the box keeps the entry at the same address, so the handle
+        // stays valid.
+        let entry = unsafe {
+            self.list
+                .cursor_mut_from_ptr(handle.0 as *const LruEntry)
+                .remove()
+        };
+        if let Some(entry) = entry {
+            self.list.push_front(entry);
+        }
+    }
+
+    fn on_remove(&mut self, handle: PolicyHandle) {
+        unsafe {
+            self.list
+                .cursor_mut_from_ptr(handle.0 as *const LruEntry)
+                .remove();
+        }
+        self.len -= 1;
+    }
+
+    fn victim(&mut self, evictable: &dyn Fn(PageCacheKey) -> bool) -> Option<PageCacheKey> {
+        let mut cursor = self.list.back();
+        while let Some(entry) = cursor.get() {
+            if evictable(entry.key) {
+                return Some(entry.key);
+            }
+            cursor.move_prev();
+        }
+        None
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.len = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// The lists of [TwoQueue] and [AdaptiveReplacement]. Each keeps resident
+/// pages on two lists and remembers the keys of pages evicted from each on a
+/// ghost list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Queue {
+    /// Pages seen once since they entered the cache.
+    Recent,
+    /// Pages seen again.
+    Frequent,
+    /// Keys of pages evicted from `Recent`.
+    RecentGhost,
+    /// Keys of pages evicted from `Frequent`.
+    FrequentGhost,
+}
+
+impl Queue {
+    fn is_ghost(self) -> bool {
+        matches!(self, Queue::RecentGhost | Queue::FrequentGhost)
+    }
+}
+
+struct QueueEntry {
+    key: PageCacheKey,
+    queue: Queue,
+    link: LinkedListLink,
+}
+
+intrusive_adapter!(QueueAdapter = Box<QueueEntry>: QueueEntry { link: LinkedListLink });
+
+/// Four lists ordered from most (front) to least (back) recently used, and an
+/// index of the ghost entries by key. Entries keep their address when they
+/// move between lists, so handles to resident pages stay valid.
+struct Queues {
+    lists: [LinkedList<QueueAdapter>; 4],
+    lens: [usize; 4],
+    ghosts: HashMap<PageCacheKey, *const QueueEntry>,
+}
+
+impl Queues {
+    fn new() -> Self {
+        Self {
+            lists: std::array::from_fn(|_| LinkedList::new(QueueAdapter::new())),
+            lens: [0; 4],
+            ghosts: HashMap::default(),
+        }
+    }
+
+    fn len(&self, queue: Queue) -> usize {
+        self.lens[queue as usize]
+    }
+
+    fn resident(&self) -> usize {
+        self.len(Queue::Recent) + self.len(Queue::Frequent)
+    }
+
+    fn push_front(&mut self, queue: Queue, key: PageCacheKey) -> PolicyHandle {
+        self.link(Box::new(QueueEntry {
+            key,
+            queue,
+            link: LinkedListLink::new(),
+        }))
+    }
+
+    fn link(&mut self, entry: Box<QueueEntry>) -> PolicyHandle {
+        let queue = entry.queue;
+        let list = &mut self.lists[queue as usize];
+        list.push_front(entry);
+        let ptr = list.front().get().unwrap() as *const QueueEntry;
+        self.lens[queue as usize] += 1;
+        if queue.is_ghost() {
+            self.ghosts.insert(unsafe { (*ptr).key }, ptr);
+        }
+        PolicyHandle(ptr as *mut ())
+    }
+
+    fn unlink(&mut self, ptr: *const QueueEntry) -> Box<QueueEntry> {
+        let queue = unsafe { (*ptr).queue };
+        let entry = unsafe { self.lists[queue as usize].cursor_mut_from_ptr(ptr).remove() }
+            .expect("queue entry is not on its list");
+        self.lens[queue as usize] -= 1;
+        if queue.is_ghost() {
+            self.ghosts.remove(&entry.key);
+        }
+        entry
+    }
+
+    /// Moves the entry behind `handle` to the front of `queue`.
+    fn move_to_front(&mut self, handle: PolicyHandle, queue: Queue) {
+        let mut entry = self.unlink(handle.0 as *const QueueEntry);
+        entry.queue = queue;
+        self.link(entry);
+    }
+
+    fn queue_of(&self, handle: PolicyHandle) -> Queue {
+        unsafe { (*(handle.0 as *const QueueEntry)).queue }
+    }
+
+    /// If `key` is on a ghost list, drops it from there and returns the list.
+    fn take_ghost(&mut self, key: PageCacheKey) -> Option<Queue> {
+        let ptr = *self.ghosts.get(&key)?;
+        Some(self.unlink(ptr).queue)
+    }
+
+    /// Forgets the least recently used entry of `queue`.
+    fn pop_back(&mut self, queue: Queue) {
+        if let Some(ptr) = self.lists[queue as usize]
+            .back()
+            .get()
+            .map(|entry| entry as *const QueueEntry)
+        {
+            self.unlink(ptr);
+        }
+    }
+
+    /// The least recently used page of `queue` for which `evictable` is true.
+    fn scan(&self, queue: Queue, evictable: &dyn Fn(PageCacheKey) -> bool) -> Option<PageCacheKey> {
+        let mut cursor = self.lists[queue as usize].back();
+        while let Some(entry) = cursor.get() {
+            if evictable(entry.key) {
+                return Some(entry.key);
+            }
+            cursor.move_prev();
+        }
+        None
+    }
+
+    fn clear(&mut self) {
+        for list in &mut self.lists {
+            list.clear();
+        }
+        self.lens = [0; 4];
+        self.ghosts.clear();
+    }
+}
+
+/// The full version of 2Q (Johnson and Shasha). New pages enter `A1in`
+/// (`Recent`), a FIFO that accesses don't reorder. Pages evicted from it are
+/// remembered on `A1out` (`RecentGhost`), and a page that is inserted again
+/// while remembered there goes to `Am` (`Frequent`), an LRU.
+pub(crate) struct TwoQueue {
+    queues: Queues,
+    /// Size `A1in` is kept to while `Am` has evictable pages.
+    max_recent: usize,
+    /// Number of keys `A1out` remembers.
+    max_ghosts: usize,
+}
+
+impl TwoQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let mut policy = Self {
+            queues: Queues::new(),
+            max_recent: 0,
+            max_ghosts: 0,
+        };
+        policy.set_capacity(capacity);
+        policy
+    }
+}
+
+impl EvictionPolicy for TwoQueue {
+    fn on_insert(&mut self, key: PageCacheKey) -> PolicyHandle {
+        let queue = match self.queues.take_ghost(key) {
+            Some(_) => Queue::Frequent,
+            None => Queue::Recent,
+        };
+        self.queues.push_front(queue, key)
+    }
+
+    fn on_access(&mut self, handle: PolicyHandle) {
+        if self.queues.queue_of(handle) == Queue::Frequent {
+            self.queues.move_to_front(handle, Queue::Frequent);
+        }
+    }
+
+    fn on_remove(&mut self, handle: PolicyHandle) {
+        self.queues.unlink(handle.0 as *const QueueEntry);
+    }
+
+    fn on_evict(&mut self, handle: PolicyHandle) {
+        let entry = self.queues.unlink(handle.0 as *const QueueEntry);
+        if entry.queue == Queue::Recent {
+            self.queues.push_front(Queue::RecentGhost, entry.key);
+            while self.queues.len(Queue::RecentGhost) > self.max_ghosts {
+                self.queues.pop_back(Queue::RecentGhost);
+            }
+        }
+    }
+
+    fn victim(&mut self, evictable: &dyn Fn(PageCacheKey) -> bool) -> Option<PageCacheKey> {
+        let order = if self.queues.len(Queue::Recent) > self.max_recent {
+            [Queue::Recent, Queue::Frequent]
+        } else {
+            [Queue::Frequent, Queue::Recent]
+        };
+        order
+            .into_iter()
+            .find_map(|queue| self.queues.scan(queue, evictable))
+    }
+
+    fn clear(&mut self) {
+        self.queues.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.queues.resident()
+    }
+
+    /// `A1in` gets a quarter of the cache and `A1out` remembers half as many
+    /// pages as the cache holds, as the paper recommends.
+    fn set_capacity(&mut self, capacity: usize) {
+        self.max_recent = (capacity / 4).max(1);
+        self.max_ghosts = (capacity / 2).max(1);
+        while self.queues.len(Queue::RecentGhost) > self.max_ghosts {
+            self.queues.pop_back(Queue::RecentGhost);
+        }
+    }
+}
+
+/// ARC (Megiddo and Modha). `T1` (`Recent`) holds pages seen once and `T2`
+/// (`Frequent`) pages seen more than once; `B1` and `B2` remember the pages
+/// evicted from each. A page inserted again while on `B1` means `T1` is too
+/// small and grows its target size, one on `B2` shrinks it.
+pub(crate) struct AdaptiveReplacement {
+    queues: Queues,
+    capacity: usize,
+    /// Target size of `T1`.
+    target_recent: usize,
+}
+
+impl AdaptiveReplacement {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            queues: Queues::new(),
+            capacity,
+            target_recent: 0,
+        }
+    }
+
+    /// Keeps `T1` and `B1` within the capacity, and all four lists within
+    /// twice the capacity.
+    fn trim_ghosts(&mut self) {
+        let queues = &mut self.queues;
+        while queues.len(Queue::RecentGhost) > 0
+            && queues.len(Queue::Recent) + queues.len(Queue::RecentGhost) > self.capacity
+        {
+            queues.pop_back(Queue::RecentGhost);
+        }
+        while queues.len(Queue::RecentGhost) + queues.len(Queue::FrequentGhost) > 0
+            && queues.resident() + queues.len(Queue::RecentGhost) + queues.len(Queue::FrequentGhost)
+                > 2 * self.capacity
+        {
+            match queues.len(Queue::FrequentGhost) {
+                0 => queues.pop_back(Queue::RecentGhost),
+                _ => queues.pop_back(Queue::FrequentGhost),
+            }
+        }
+    }
+}
+
+impl EvictionPolicy for AdaptiveReplacement {
+    fn on_insert(&mut self, key: PageCacheKey) -> PolicyHandle {
+        let recent_ghosts = self.queues.len(Queue::RecentGhost);
+        let frequent_ghosts = self.queues.len(Queue::FrequentGhost);
+        let queue = match self.queues.take_ghost(key) {
+            Some(Queue::RecentGhost) => {
+                let delta = (frequent_ghosts / recent_ghosts).max(1);
+                self.target_recent = (self.target_recent + delta).min(self.capacity);
+                Queue::Frequent
+            }
+            Some(_) => {
+                let delta = (recent_ghosts / frequent_ghosts).max(1);
+                self.target_recent = self.target_recent.saturating_sub(delta);
+                Queue::Frequent
+            }
+            None => Queue::Recent,
+        };
+        let handle = self.queues.push_front(queue, key);
+        self.trim_ghosts();
+        handle
+    }
+
+    fn on_access(&mut self, handle: PolicyHandle) {
+        self.queues.move_to_front(handle, Queue::Frequent);
+    }
+
+    fn on_remove(&mut self, handle: PolicyHandle) {
+        self.queues.unlink(handle.0 as *const QueueEntry);
+    }
+
+    fn on_evict(&mut self, handle: PolicyHandle) {
+        let entry = self.queues.unlink(handle.0 as *const QueueEntry);
+        let ghost = match entry.queue {
+            Queue::Recent => Queue::RecentGhost,
+            _ => Queue::FrequentGhost,
+        };
+        self.queues.push_front(ghost, entry.key);
+        self.trim_ghosts();
+    }
+
+    fn victim(&mut self, evictable: &dyn Fn(PageCacheKey) -> bool) -> Option<PageCacheKey> {
+        let order = if self.queues.len(Queue::Recent) > self.target_recent {
+            [Queue::Recent, Queue::Frequent]
+        } else {
+            [Queue::Frequent, Queue::Recent]
+        };
+        order
+            .into_iter()
+            .find_map(|queue| self.queues.scan(queue, evictable))
+    }
+
+    fn clear(&mut self) {
+        self.queues.clear();
+        self.target_recent = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.queues.resident()
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.target_recent = self.target_recent.min(capacity);
+        self.trim_ghosts();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_evicts_least_recently_used_evictable_page() {
+        let mut lru = Lru::new();
+        let handles: Vec<_> = (1..=4)
+            .map(|pgno| lru.on_insert(PageCacheKey::new(pgno)))
+            .collect();
+        lru.on_access(handles[0]);
+        // 2 is the least recently used, but say it is pinned.
+        let victim = lru.victim(&|key| key != PageCacheKey::new(2));
+        assert_eq!(victim, Some(PageCacheKey::new(3)));
+        lru.on_remove(handles[2]);
+        lru.on_remove(handles[1]);
+        assert_eq!(lru.victim(&|_| true), Some(PageCacheKey::new(4)));
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.victim(&|_| false), None);
+    }
+
+    #[test]
+    fn test_two_queue_keeps_reused_pages_through_a_scan() {
+        let mut policy = TwoQueue::new(8);
+        let key = PageCacheKey::new;
+        let mut handles = HashMap::default();
+        // 1 and 2 are evicted from A1in and come back, so they land in Am.
+        for pgno in 1..=2 {
+            handles.insert(pgno, policy.on_insert(key(pgno)));
+        }
+        for pgno in 1..=2 {
+            policy.on_evict(handles.remove(&pgno).unwrap());
+            handles.insert(pgno, policy.on_insert(key(pgno)));
+        }
+        // A scan over pages seen once fills A1in past its share, so they go
+        // first even though 1 and 2 are older.
+        for pgno in 3..=8 {
+            handles.insert(pgno, policy.on_insert(key(pgno)));
+        }
+        for pgno in 3..=6 {
+            assert_eq!(policy.victim(&|_| true), Some(key(pgno)));
+            policy.on_evict(handles.remove(&pgno).unwrap());
+        }
+        // A1in is down to its share, so Am gives up its least recently used
+        // page.
+        assert_eq!(policy.len(), 4);
+        assert_eq!(policy.victim(&|_| true), Some(key(1)));
+    }
+
+    #[test]
+    fn test_two_queue_forgets_deleted_pages() {
+        let mut policy = TwoQueue::new(4);
+        let handle = policy.on_insert(PageCacheKey::new(1));
+        policy.on_remove(handle);
+        // A deleted page is not remembered, so it comes back through A1in.
+        let handle = policy.on_insert(PageCacheKey::new(1));
+        assert_eq!(policy.queues.queue_of(handle), Queue::Recent);
+        assert_eq!(policy.queues.len(Queue::RecentGhost), 0);
+    }
+
+    #[test]
+    fn test_arc_adapts_to_pages_coming_back() {
+        let mut policy = AdaptiveReplacement::new(4);
+        let key = PageCacheKey::new;
+        let mut handles: Vec<_> = (1..=4).map(|pgno| policy.on_insert(key(pgno))).collect();
+        // 4 is accessed again and moves to T2; the oldest of T1 goes first.
+        policy.on_access(handles[3]);
+        assert_eq!(policy.victim(&|_| true), Some(key(1)));
+        policy.on_evict(handles.remove(0));
+        assert_eq!(policy.queues.len(Queue::RecentGhost), 1);
+        // 1 comes back: T1 was too small, so its target grows and 1 goes to T2.
+        let handle = policy.on_insert(key(1));
+        assert_eq!(policy.target_recent, 1);
+        assert_eq!(policy.queues.queue_of(handle), Queue::Frequent);
+        // T1 holds 2 and 3, above its target, so it is still evicted from.
+        assert_eq!(policy.victim(&|_| true), Some(key(2)));
+        // With T1 pinned, T2 gives up its least recently used page.
+        let victim =
+            policy.victim(&|key| key != PageCacheKey::new(2) && key != PageCacheKey::new(3));
+        assert_eq!(victim, Some(key(4)));
+        assert_eq!(policy.len(), 4);
+    }
+
+    #[test]
+    fn test_arc_bounds_ghosts_by_capacity() {
+        let mut policy = AdaptiveReplacement::new(2);
+        for pgno in 1..=10 {
+            let handle = policy.on_insert(PageCacheKey::new(pgno));
+            policy.on_evict(handle);
+        }
+        let ghosts =
+            policy.queues.len(Queue::RecentGhost) + policy.queues.len(Queue::FrequentGhost);
+        assert!(ghosts <= 2, "{ghosts} ghosts for a capacity of 2");
+        assert_eq!(policy.queues.ghosts.len(), ghosts);
+        policy.clear();
+        assert_eq!(policy.len(), 0);
+        assert!(policy.queues.ghosts.is_empty());
+    }
+}
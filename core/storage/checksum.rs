@@ -1,7 +1,13 @@
-#![allow(unused_variables, dead_code)]
+//! Per-page checksums stored in the reserved bytes at the end of each page.
+//!
+//! Enabled for every new database with the `checksum` feature, or per database
+//! with [DatabaseOpts::with_page_checksums](crate::DatabaseOpts::with_page_checksums).
+//! A database keeps them only if it was created with
+//! [CHECKSUM_REQUIRED_RESERVED_BYTES] reserved bytes, and only pages of
+//! [CHECKSUM_PAGE_SIZE] bytes carry one.
 use crate::{CompletionError, Result};
 
-const CHECKSUM_PAGE_SIZE: usize = 4096;
+pub(crate) const CHECKSUM_PAGE_SIZE: usize = 4096;
 const CHECKSUM_SIZE: usize = 8;
 pub(crate) const CHECKSUM_REQUIRED_RESERVED_BYTES: u8 = CHECKSUM_SIZE as u8;
 
@@ -13,25 +19,6 @@ impl ChecksumContext {
         ChecksumContext {}
     }
 
-    #[cfg(not(feature = "checksum"))]
-    pub fn add_checksum_to_page(&self, _page: &mut [u8], _page_id: usize) -> Result<()> {
-        use crate::LimboError;
-        Err(LimboError::InternalError(
-            "tursodb must be recompiled with checksum feature in order to use checksums"
-                .to_string(),
-        ))
-    }
-
-    #[cfg(not(feature = "checksum"))]
-    pub fn verify_checksum(
-        &self,
-        _page: &mut [u8],
-        _page_id: usize,
-    ) -> std::result::Result<(), CompletionError> {
-        Err(CompletionError::ChecksumNotEnabled)
-    }
-
-    #[cfg(feature = "checksum")]
     pub fn add_checksum_to_page(&self, page: &mut [u8], _page_id: usize) -> Result<()> {
         if page.len() != CHECKSUM_PAGE_SIZE {
             return Ok(());
@@ -47,7 +34,6 @@ impl ChecksumContext {
         Ok(())
     }
 
-    #[cfg(feature = "checksum")]
     pub fn verify_checksum(
         &self,
         page: &mut [u8],
@@ -94,7 +80,6 @@ impl Default for ChecksumContext {
 }
 
 #[cfg(test)]
-#[cfg(feature = "checksum")]
 mod tests {
     use super::*;
 
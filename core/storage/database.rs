@@ -1,7 +1,7 @@
-use crate::io::FileSyncType;
+use crate::io::{FileSyncType, LockLevel};
 use crate::storage::checksum::ChecksumContext;
 use crate::storage::encryption::EncryptionContext;
-use crate::sync::Arc;
+use crate::sync::{Arc, Mutex};
 use crate::{io::Completion, Buffer, CompletionError, LimboError, Result};
 use crate::{
     turso_assert, turso_assert_eq, turso_assert_greater_than, turso_assert_greater_than_or_equal,
@@ -29,6 +29,13 @@ impl IOContext {
         }
     }
 
+    pub fn checksum_context(&self) -> Option<&ChecksumContext> {
+        match &self.encryption_or_checksum {
+            EncryptionOrChecksum::Checksum(ctx) => Some(ctx),
+            _ => None,
+        }
+    }
+
     pub fn get_reserved_space_bytes(&self) -> u8 {
         match &self.encryption_or_checksum {
             EncryptionOrChecksum::Encryption(ctx) => ctx.required_reserved_bytes(),
@@ -45,6 +52,10 @@ impl IOContext {
         &self.encryption_or_checksum
     }
 
+    pub fn set_checksum(&mut self, checksum_ctx: ChecksumContext) {
+        self.encryption_or_checksum = EncryptionOrChecksum::Checksum(checksum_ctx);
+    }
+
     pub fn reset_checksum(&mut self) {
         self.encryption_or_checksum = EncryptionOrChecksum::None;
     }
@@ -89,11 +100,65 @@ pub trait DatabaseStorage: Send + Sync {
     fn sync(&self, c: Completion, sync_type: FileSyncType) -> Result<Completion>;
     fn size(&self) -> Result<u64>;
     fn truncate(&self, len: usize, c: Completion) -> Result<Completion>;
+
+    /// See [crate::io::File::set_mmap_limit].
+    fn set_mmap_limit(&self, _limit: u64) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// See [crate::io::File::mmap_limit].
+    fn mmap_limit(&self) -> u64 {
+        0
+    }
+
+    /// See [crate::io::File::allocate].
+    fn allocate(&self, _len: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Move the lock of one connection on the database file from `held` to
+    /// `level`, see [crate::io::File::lock_level]. A lock is only ever lowered
+    /// to `LockLevel::Shared` or `LockLevel::None`.
+    fn set_lock_level(&self, _held: LockLevel, _level: LockLevel) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
 pub struct DatabaseFile {
     file: Arc<dyn crate::io::File>,
+    locks: Arc<Mutex<ConnectionLocks>>,
+}
+
+/// Locks of the connections of this process on the database file, which
+/// holds the strongest of them, like SQLite's `unixInodeInfo`.
+#[derive(Debug, Default, Clone, Copy)]
+struct ConnectionLocks {
+    /// Number of connections holding SHARED or stronger.
+    shared: usize,
+    /// Level of the connection holding more than SHARED. Writers of a process
+    /// are serialized by the WAL, so there is at most one.
+    writer: Option<LockLevel>,
+}
+
+impl ConnectionLocks {
+    fn level(&self) -> LockLevel {
+        match self.writer {
+            Some(level) => level,
+            None if self.shared > 0 => LockLevel::Shared,
+            None => LockLevel::None,
+        }
+    }
+
+    fn set(&mut self, held: LockLevel, level: LockLevel) {
+        match (held, level) {
+            (LockLevel::None, LockLevel::None) => {}
+            (LockLevel::None, _) => self.shared += 1,
+            (_, LockLevel::None) => self.shared -= 1,
+            _ => {}
+        }
+        self.writer = (level > LockLevel::Shared).then_some(level);
+    }
 }
 
 impl DatabaseStorage for DatabaseFile {
@@ -277,12 +342,51 @@ impl DatabaseStorage for DatabaseFile {
         let c = self.file.truncate(len as u64, c)?;
         Ok(c)
     }
+
+    fn set_mmap_limit(&self, limit: u64) -> Result<u64> {
+        self.file.set_mmap_limit(limit)
+    }
+
+    fn mmap_limit(&self) -> u64 {
+        self.file.mmap_limit()
+    }
+
+    fn allocate(&self, len: u64) -> Result<()> {
+        self.file.allocate(len)
+    }
+
+    fn set_lock_level(&self, held: LockLevel, level: LockLevel) -> Result<()> {
+        if held == level {
+            return Ok(());
+        }
+        let mut locks = self.locks.lock();
+        let before = *locks;
+        locks.set(held, level);
+        let (from, to) = (before.level(), locks.level());
+        if to < from {
+            return self.file.unlock_level(to);
+        }
+        if to > from {
+            if let Err(e) = self.file.lock_level(to) {
+                // Give back whatever part of the way up the file got.
+                *locks = before;
+                if from <= LockLevel::Shared {
+                    self.file.unlock_level(from)?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "fs")]
 impl DatabaseFile {
     pub fn new(file: Arc<dyn crate::io::File>) -> Self {
-        Self { file }
+        Self {
+            file,
+            locks: Arc::new(Mutex::new(ConnectionLocks::default())),
+        }
     }
 }
 
@@ -348,6 +452,7 @@ mod tests {
     fn checksum_read_wrapper_propagates_callback_errors() {
         let db_file = DatabaseFile {
             file: Arc::new(MockFile { read_result: Ok(0) }),
+            locks: Arc::new(Mutex::new(ConnectionLocks::default())),
         };
         let io_ctx = IOContext::default();
         let page_idx = 1usize;
@@ -389,6 +494,7 @@ mod tests {
             file: Arc::new(MockFile {
                 read_result: Err(CompletionError::Aborted),
             }),
+            locks: Arc::new(Mutex::new(ConnectionLocks::default())),
         };
         let io_ctx = IOContext::default();
         let page_idx = 1usize;
@@ -0,0 +1,162 @@
+//! Prioritization of foreground page reads over background checkpoint I/O.
+//!
+//! With an asynchronous IO backend (e.g. io_uring) a checkpoint round can
+//! submit hundreds of WAL reads plus a large vectored write to the database
+//! file, and a page read issued by a statement in the meantime queues behind
+//! all of it. An `IoScheduler` is shared by every pager of a database:
+//! foreground reads register themselves while they are in flight, and the
+//! checkpoint asks [IoScheduler::checkpoint_round_pages] how much I/O its next
+//! round may issue. While foreground reads are pending the round is shrunk to
+//! [THROTTLED_ROUND_PAGES], so statement reads only wait behind a small amount
+//! of checkpoint I/O. To keep a steady read load from starving the checkpoint,
+//! every [MAX_THROTTLED_ROUNDS]th consecutive throttled round runs at full size.
+//!
+//! Synchronous backends complete a read before `pread` returns, so no read is
+//! ever pending when the checkpoint asks and the checkpoint is never throttled.
+
+use crate::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Pages a checkpoint round may read and write while foreground reads are pending.
+pub(crate) const THROTTLED_ROUND_PAGES: usize = 32;
+
+/// Consecutive throttled checkpoint rounds after which one round runs at full size.
+pub(crate) const MAX_THROTTLED_ROUNDS: usize = 8;
+
+pub struct IoScheduler {
+    /// Foreground page reads that were submitted and have not completed yet.
+    foreground_reads: AtomicUsize,
+    /// Checkpoint rounds throttled in a row since the last full-size round.
+    throttled_rounds: AtomicUsize,
+}
+
+impl IoScheduler {
+    pub fn new() -> Self {
+        Self {
+            foreground_reads: AtomicUsize::new(0),
+            throttled_rounds: AtomicUsize::new(0),
+        }
+    }
+
+    /// Register a foreground read. The read counts as pending until the
+    /// returned guard is finished or dropped.
+    pub(crate) fn begin_foreground_read(self: &Arc<Self>) -> ForegroundRead {
+        self.foreground_reads.fetch_add(1, Ordering::AcqRel);
+        ForegroundRead {
+            scheduler: self.clone(),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    /// Number of foreground reads currently in flight.
+    pub fn pending_foreground_reads(&self) -> usize {
+        self.foreground_reads.load(Ordering::Acquire)
+    }
+
+    /// How many pages the next checkpoint round may read and write, given that
+    /// an unthrottled round uses `full`.
+    pub(crate) fn checkpoint_round_pages(&self, full: usize) -> usize {
+        if self.pending_foreground_reads() == 0 {
+            self.throttled_rounds.store(0, Ordering::Release);
+            return full;
+        }
+        let throttled = self.throttled_rounds.fetch_add(1, Ordering::AcqRel) + 1;
+        if throttled >= MAX_THROTTLED_ROUNDS {
+            self.throttled_rounds.store(0, Ordering::Release);
+            full
+        } else {
+            full.min(THROTTLED_ROUND_PAGES)
+        }
+    }
+}
+
+impl Default for IoScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pending foreground read, see [IoScheduler::begin_foreground_read].
+pub(crate) struct ForegroundRead {
+    scheduler: Arc<IoScheduler>,
+    finished: AtomicBool,
+}
+
+impl ForegroundRead {
+    /// Mark the read as completed. Only the first call has an effect.
+    pub(crate) fn finish(&self) {
+        if !self.finished.swap(true, Ordering::AcqRel) {
+            self.scheduler
+                .foreground_reads
+                .fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl Drop for ForegroundRead {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_foreground_read_finishes_once() {
+        let scheduler = Arc::new(IoScheduler::new());
+        let read = scheduler.begin_foreground_read();
+        let other = scheduler.begin_foreground_read();
+        assert_eq!(scheduler.pending_foreground_reads(), 2);
+        read.finish();
+        read.finish();
+        assert_eq!(scheduler.pending_foreground_reads(), 1);
+        drop(read);
+        assert_eq!(scheduler.pending_foreground_reads(), 1);
+        drop(other);
+        assert_eq!(scheduler.pending_foreground_reads(), 0);
+    }
+
+    #[test]
+    fn test_checkpoint_round_throttled_while_reads_pending() {
+        let scheduler = Arc::new(IoScheduler::new());
+        assert_eq!(scheduler.checkpoint_round_pages(512), 512);
+
+        let read = scheduler.begin_foreground_read();
+        assert_eq!(scheduler.checkpoint_round_pages(512), THROTTLED_ROUND_PAGES);
+        // Never grows a round beyond what the caller asked for.
+        assert_eq!(scheduler.checkpoint_round_pages(8), 8);
+
+        drop(read);
+        assert_eq!(scheduler.checkpoint_round_pages(512), 512);
+    }
+
+    #[test]
+    fn test_checkpoint_not_starved_by_steady_reads() {
+        let scheduler = Arc::new(IoScheduler::new());
+        let _read = scheduler.begin_foreground_read();
+        for _ in 0..3 {
+            for _ in 1..MAX_THROTTLED_ROUNDS {
+                assert_eq!(scheduler.checkpoint_round_pages(512), THROTTLED_ROUND_PAGES);
+            }
+            assert_eq!(scheduler.checkpoint_round_pages(512), 512);
+        }
+    }
+
+    #[test]
+    fn test_unthrottled_round_resets_starvation_counter() {
+        let scheduler = Arc::new(IoScheduler::new());
+        let read = scheduler.begin_foreground_read();
+        for _ in 1..MAX_THROTTLED_ROUNDS {
+            scheduler.checkpoint_round_pages(512);
+        }
+        drop(read);
+        assert_eq!(scheduler.checkpoint_round_pages(512), 512);
+
+        let _read = scheduler.begin_foreground_read();
+        assert_eq!(scheduler.checkpoint_round_pages(512), THROTTLED_ROUND_PAGES);
+    }
+}
@@ -12,13 +12,17 @@
 //! for the database, also either local or remote.
 pub(crate) mod btree;
 pub(crate) mod buffer_pool;
+pub(crate) mod cache_policy;
 pub(crate) mod checksum;
 pub mod database;
 pub(crate) mod encryption;
+pub(crate) mod io_scheduler;
 pub(crate) mod journal_mode;
 pub(crate) mod page_cache;
 #[allow(clippy::arc_with_non_send_sync)]
 pub(crate) mod pager;
+#[cfg(feature = "fs")]
+pub(crate) mod rollback_journal;
 #[cfg(host_shared_wal)]
 #[allow(dead_code)]
 pub(crate) mod shared_wal_coordination;
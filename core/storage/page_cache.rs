@@ -1,5 +1,4 @@
 use crate::sync::Arc;
-use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListLink};
 use rustc_hash::FxHashMap as HashMap;
 use tracing::trace;
 
@@ -8,6 +7,7 @@ use crate::{
     turso_assert,
 };
 
+use super::cache_policy::{EvictionPolicy, PageCachePolicy, PolicyHandle};
 use super::pager::PageRef;
 
 #[cfg(not(target_family = "wasm"))]
@@ -30,48 +30,23 @@ const DEFAULT_SPILL_THRESHOLD_PERCENT: usize = 90;
 #[repr(transparent)]
 pub struct PageCacheKey(usize);
 
-const CLEAR: u8 = 0;
-const REF_MAX: u8 = 3;
-
 /// An entry in the page cache.
-///
-/// The entry is stored in the intrusive linked list in PageCache::list`.
 struct PageCacheEntry {
-    /// Key identifying this page
-    key: PageCacheKey,
     /// The cached page
     page: PageRef,
-    /// Reference counter (SIEVE/GClock): starts at zero, bumped on access,
-    /// decremented during eviction, only pages at 0 are evicted.
-    ref_bit: u8,
-    /// Intrusive link for SIEVE queue
-    link: LinkedListLink,
+    /// The page's position in the eviction policy
+    handle: PolicyHandle,
 }
 
-intrusive_adapter!(EntryAdapter = Box<PageCacheEntry>: PageCacheEntry { link: LinkedListLink });
-
-impl PageCacheEntry {
-    fn new(key: PageCacheKey, page: PageRef) -> Box<Self> {
-        Box::new(Self {
-            key,
-            page,
-            ref_bit: CLEAR,
-            link: LinkedListLink::new(),
-        })
-    }
-
-    #[inline]
-    fn bump_ref(&mut self) {
-        self.ref_bit = std::cmp::min(self.ref_bit + 1, REF_MAX);
-    }
-
-    #[inline]
-    /// Returns the old value
-    fn decrement_ref(&mut self) -> u8 {
-        let old = self.ref_bit;
-        self.ref_bit = old.saturating_sub(1);
-        old
-    }
+/// Counters of page cache lookups and evictions since the cache was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageCacheStats {
+    /// Lookups that found a loaded page.
+    pub hits: u64,
+    /// Lookups that had to go to the WAL or the database file.
+    pub misses: u64,
+    /// Pages dropped to make room for others.
+    pub evictions: u64,
 }
 
 /// Result returned when attempting to spill dirty pages from the cache.
@@ -87,29 +62,26 @@ pub enum SpillResult {
     CacheFull,
 }
 
-/// PageCache implements a variation of the SIEVE algorithm that maintains an intrusive linked list queue of
-/// pages which keep a 'reference_bit' to determine how recently/frequently the page has been accessed.
-/// The bit is set to `Clear` on initial insertion and then bumped on each access and decremented
-/// during eviction scans.
-///
-/// The ring is circular. `clock_hand` points at the tail (LRU).
-/// Sweep order follows next: tail (LRU) -> head (MRU) -> .. -> tail
-/// New pages are inserted after the clock hand in the `next` direction,
-/// which places them at head (MRU) (i.e. `tail.next` is the head).
+/// PageCache keeps up to `capacity` pages resident and evicts clean pages to
+/// make room for new ones. Which evictable page goes first is decided by an
+/// [EvictionPolicy], SIEVE by default (see [PageCachePolicy]).
 pub struct PageCache {
     /// Capacity in pages
     capacity: usize,
-    /// Map of Key -> pointer to entry in the queue
-    map: HashMap<PageCacheKey, *mut PageCacheEntry>,
-    /// The eviction queue (intrusive doubly-linked list)
-    queue: LinkedList<EntryAdapter>,
-    /// Clock hand cursor for SIEVE eviction (pointer to an entry in the queue, or null)
-    clock_hand: *mut PageCacheEntry,
+    /// Map of Key -> resident page
+    map: HashMap<PageCacheKey, PageCacheEntry>,
+    /// Orders the resident pages for eviction
+    policy: Box<dyn EvictionPolicy>,
+    /// The policy `policy` was built from
+    policy_kind: PageCachePolicy,
     /// Threshold number of pages at which we start spilling dirty pages.
     spill_threshold: usize,
+    /// Minimum spill threshold set with `PRAGMA cache_spill=N`, 0 if unset.
+    spill_size: usize,
     spill_enabled: bool,
     /// Conservative estimation of pages that are evictable based on dirty/spilled state.
     evictable_count: usize,
+    stats: PageCacheStats,
 }
 
 unsafe impl Send for PageCache {}
@@ -154,46 +126,43 @@ impl PageCache {
         Self::new_with_spill(capacity, false)
     }
 
+    /// Create a new PageCache with the default capacity that evicts pages
+    /// according to `policy`.
+    pub fn with_policy(policy: PageCachePolicy) -> Self {
+        Self::new_with_spill_and_policy(
+            DEFAULT_PAGE_CACHE_SIZE_IN_PAGES,
+            cfg!(not(target_family = "wasm")),
+            policy,
+        )
+    }
+
     /// Create a new PageCache with explicit spill control.
     fn new_with_spill(capacity: usize, spill_enabled: bool) -> Self {
+        Self::new_with_spill_and_policy(capacity, spill_enabled, PageCachePolicy::default())
+    }
+
+    fn new_with_spill_and_policy(
+        capacity: usize,
+        spill_enabled: bool,
+        policy: PageCachePolicy,
+    ) -> Self {
         let spill_threshold = (capacity * DEFAULT_SPILL_THRESHOLD_PERCENT) / 100;
         Self {
             capacity,
             map: HashMap::default(),
-            queue: LinkedList::new(EntryAdapter::new()),
-            clock_hand: std::ptr::null_mut(),
+            policy: policy.build(capacity),
+            policy_kind: policy,
             spill_threshold: spill_threshold.max(1),
+            spill_size: 0,
             spill_enabled,
             evictable_count: 0,
+            stats: PageCacheStats::default(),
         }
     }
 
-    /// Advances the clock hand to the next entry in the circular queue.
-    /// Follows the "next" direction: from tail/LRU through the list back to tail.
-    /// With our insertion-after-hand strategy, this moves through entries in age order.
-    fn advance_clock_hand(&mut self) {
-        if self.clock_hand.is_null() {
-            return;
-        }
-
-        unsafe {
-            let mut cursor = self.queue.cursor_mut_from_ptr(self.clock_hand);
-            cursor.move_next();
-
-            if cursor.get().is_some() {
-                self.clock_hand =
-                    cursor.as_cursor().get().unwrap() as *const _ as *mut PageCacheEntry;
-            } else {
-                // Reached end, wrap to front
-                let front_cursor = self.queue.front_mut();
-                if front_cursor.get().is_some() {
-                    self.clock_hand =
-                        front_cursor.as_cursor().get().unwrap() as *const _ as *mut PageCacheEntry;
-                } else {
-                    self.clock_hand = std::ptr::null_mut();
-                }
-            }
-        }
+    /// The eviction policy of this cache.
+    pub fn policy(&self) -> PageCachePolicy {
+        self.policy_kind
     }
 
     pub fn contains_key(&self, key: &PageCacheKey) -> bool {
@@ -259,8 +228,7 @@ impl PageCache {
     ) -> Result<(), CacheError> {
         trace!("insert(key={:?})", key);
 
-        if let Some(&entry_ptr) = self.map.get(&key) {
-            let entry = unsafe { &mut *entry_ptr };
+        if let Some(entry) = self.map.get_mut(&key) {
             let p = &entry.page;
 
             if !p.is_loaded() && !p.is_locked() {
@@ -268,7 +236,7 @@ impl PageCache {
                 self._delete(key, true)?;
                 // Proceed to insert new entry
             } else {
-                entry.bump_ref();
+                self.policy.on_access(entry.handle);
                 if update_in_place {
                     // Track evictable count change if page state differs
                     let old_evictable = Self::counted_as_evictable(&entry.page);
@@ -296,27 +264,14 @@ impl PageCache {
         // Track evictable count for the new page
         let is_evictable = Self::counted_as_evictable(&value);
 
-        let entry = PageCacheEntry::new(key, value);
-
-        if self.clock_hand.is_null() {
-            // First entry - just push it
-            self.queue.push_back(entry);
-            let entry_ptr = self.queue.back().get().unwrap() as *const _ as *mut PageCacheEntry;
-            self.map.insert(key, entry_ptr);
-            self.clock_hand = entry_ptr;
-        } else {
-            // Insert after clock hand (in circular list semantics, this makes it the new head/MRU)
-            unsafe {
-                let mut cursor = self.queue.cursor_mut_from_ptr(self.clock_hand);
-                cursor.insert_after(entry);
-                // The inserted entry is now at the next position after clock hand
-                cursor.move_next();
-                let entry_ptr = cursor.get().ok_or_else(|| {
-                    CacheError::InternalError("Failed to get inserted entry pointer".into())
-                })? as *const PageCacheEntry as *mut PageCacheEntry;
-                self.map.insert(key, entry_ptr);
-            }
-        }
+        let handle = self.policy.on_insert(key);
+        self.map.insert(
+            key,
+            PageCacheEntry {
+                page: value,
+                handle,
+            },
+        );
 
         // Update evictable count after successful insertion
         if is_evictable {
@@ -327,11 +282,10 @@ impl PageCache {
     }
 
     fn _delete(&mut self, key: PageCacheKey, clean_page: bool) -> Result<(), CacheError> {
-        let Some(&entry_ptr) = self.map.get(&key) else {
+        let Some(entry) = self.map.get(&key) else {
             return Ok(());
         };
 
-        let entry = unsafe { &mut *entry_ptr };
         let page = &entry.page;
 
         if page.is_locked() {
@@ -358,23 +312,9 @@ impl PageCache {
             let _ = page.get().buffer.take();
         }
 
-        // Remove from map first
+        let handle = entry.handle;
         self.map.remove(&key);
-
-        // If clock hand points to this entry, advance it before removing
-        if self.clock_hand == entry_ptr {
-            self.advance_clock_hand();
-            // If hand is still pointing to the same entry after advance, we're removing the last entry
-            if self.clock_hand == entry_ptr {
-                self.clock_hand = std::ptr::null_mut();
-            }
-        }
-
-        // Remove the entry from the queue
-        unsafe {
-            let mut cursor = self.queue.cursor_mut_from_ptr(entry_ptr);
-            cursor.remove();
-        }
+        self.policy.on_remove(handle);
 
         // Update evictable count after successful removal
         if was_evictable {
@@ -404,12 +344,13 @@ impl PageCache {
 
     #[inline]
     pub fn get(&mut self, key: &PageCacheKey) -> crate::Result<Option<PageRef>> {
-        let Some(&entry_ptr) = self.map.get(key) else {
+        let Some(entry) = self.map.get(key) else {
+            self.stats.misses += 1;
             return Ok(None);
         };
 
-        let entry = unsafe { &mut *entry_ptr };
         let page = entry.page.clone();
+        let handle = entry.handle;
 
         // Because we can abort a read_page completion, this means a page can be in the cache but be unloaded and unlocked.
         // However, if we do not evict that page from the page cache, we will return an unloaded page later which will trigger
@@ -417,22 +358,27 @@ impl PageCache {
         // in one Statement, and trigger some error in the next one if we don't evict the page here.
         if !page.is_loaded() && !page.is_locked() {
             self.delete(*key)?;
+            self.stats.misses += 1;
             return Ok(None);
         }
 
-        entry.bump_ref();
+        self.policy.on_access(handle);
+        self.stats.hits += 1;
         Ok(Some(page))
     }
 
     #[inline]
     pub fn peek(&mut self, key: &PageCacheKey, touch: bool) -> Option<PageRef> {
-        let &entry_ptr = self.map.get(key)?;
-        let entry = unsafe { &mut *entry_ptr };
-        let page = entry.page.clone();
+        let entry = self.map.get(key)?;
         if touch {
-            entry.bump_ref();
+            self.policy.on_access(entry.handle);
         }
-        Some(page)
+        Some(entry.page.clone())
+    }
+
+    /// Lookup and eviction counters since the cache was created.
+    pub fn stats(&self) -> PageCacheStats {
+        self.stats
     }
 
     /// Resizes the cache to a new capacity.
@@ -448,10 +394,17 @@ impl PageCache {
             }
         }
         self.capacity = new_cap;
-        self.spill_threshold = ((new_cap * DEFAULT_SPILL_THRESHOLD_PERCENT) / 100).max(1);
+        self.policy.set_capacity(new_cap);
+        self.update_spill_threshold();
         CacheResizeResult::Done
     }
 
+    fn update_spill_threshold(&mut self) {
+        self.spill_threshold = ((self.capacity * DEFAULT_SPILL_THRESHOLD_PERCENT) / 100)
+            .max(self.spill_size)
+            .max(1);
+    }
+
     /// Returns true if the cache is at or above the spill threshold and spilling is enabled.
     /// This indicates that dirty pages should be flushed to make room for new pages.
     #[inline]
@@ -484,10 +437,7 @@ impl PageCache {
     fn count_evictable_pages(&self) -> usize {
         self.map
             .values()
-            .filter(|&&entry_ptr| {
-                let entry = unsafe { &*entry_ptr };
-                Self::evictable(&entry.page)
-            })
+            .filter(|entry| Self::evictable(&entry.page))
             .count()
     }
 
@@ -502,6 +452,20 @@ impl PageCache {
         self.spill_enabled = enabled;
     }
 
+    /// Don't spill dirty pages before the cache holds `pages` pages, even if
+    /// that is more than its capacity. 0 restores the default threshold.
+    pub fn set_spill_size(&mut self, pages: usize) {
+        self.spill_size = pages;
+        self.update_spill_threshold();
+    }
+
+    /// Number of pages a transaction can keep in the cache before its dirty
+    /// pages are spilled, as reported by `PRAGMA cache_spill`: the larger of
+    /// the spill size and the capacity.
+    pub fn spill_size(&self) -> usize {
+        self.spill_size.max(self.capacity)
+    }
+
     #[inline]
     fn spillable(page: &PageRef) -> bool {
         page.is_dirty()
@@ -530,8 +494,7 @@ impl PageCache {
     /// This should be called when a page transitions from clean/spilled to dirty.
     /// The page must already be in the cache.
     pub fn notify_page_dirty(&mut self, key: PageCacheKey) {
-        if let Some(&entry_ptr) = self.map.get(&key) {
-            let entry = unsafe { &*entry_ptr };
+        if let Some(entry) = self.map.get(&key) {
             let page = &entry.page;
             // Page was evictable (clean or spilled) before becoming dirty,
             // now it's dirty && !spilled, so not evictable
@@ -547,8 +510,7 @@ impl PageCache {
     /// This should be called when a page transitions from dirty to spilled.
     /// The page must already be in the cache.
     pub fn notify_page_spilled(&mut self, key: PageCacheKey) {
-        if let Some(&entry_ptr) = self.map.get(&key) {
-            let entry = unsafe { &*entry_ptr };
+        if let Some(entry) = self.map.get(&key) {
             let page = &entry.page;
             // Page was dirty && !spilled (not evictable), now it's spilled (evictable)
             if page.get().id != DatabaseHeader::PAGE_ID {
@@ -572,8 +534,7 @@ impl PageCache {
         const EST_SPILL: usize = 128;
         let mut spillable: Vec<PinGuard> = Vec::with_capacity(EST_SPILL);
 
-        for (_, &entry_ptr) in self.map.iter() {
-            let entry = unsafe { &*entry_ptr };
+        for entry in self.map.values() {
             let page = &entry.page;
             if Self::spillable(page) {
                 spillable.push(PinGuard::new(page.clone()));
@@ -603,13 +564,8 @@ impl PageCache {
 
     /// Ensures at least `n` free slots are available
     ///
-    /// Uses the SIEVE algorithm to evict pages if necessary:
-    /// Start at clock hand position
-    /// If page ref_bit > 0, decrement and continue
-    /// If page ref_bit == 0 and evictable, evict it
-    /// If page is unevictable (dirty/locked/pinned), continue sweep
-    /// On sweep, pages with ref_bit > 0 are given a second chance by decrementing
-    /// their ref_bit and leaving them in place; only pages with ref_bit == 0 are evicted.
+    /// Evicts the pages chosen by the eviction policy among the evictable ones
+    /// (clean or spilled, unlocked and unpinned) until there is room.
     ///
     /// Returns `CacheError::Full` if not enough pages can be evicted
     fn make_room_for(&mut self, n: usize, bypass_capacity: bool) -> Result<(), CacheError> {
@@ -636,7 +592,7 @@ impl PageCache {
             && Arc::strong_count(page) == 1
     }
 
-    /// Evicts a single page using the SIEVE algorithm
+    /// Evicts the single page the eviction policy picks
     fn evict_one(&mut self) -> Result<(), CacheError> {
         if self.len() == 0 {
             return Err(CacheError::InternalError(
@@ -644,71 +600,38 @@ impl PageCache {
             ));
         }
 
-        let mut examined = 0usize;
-        let max_examinations = self.len().saturating_mul(REF_MAX as usize + 1);
-
-        while examined < max_examinations {
-            // Clock hand should never be null here since we checked len() > 0
-            turso_assert!(
-                !self.clock_hand.is_null(),
-                "page_cache: clock hand is null during eviction",
-                { "entries": self.len() }
-            );
-
-            let entry_ptr = self.clock_hand;
-            let entry = unsafe { &mut *entry_ptr };
-            let key = entry.key;
-            let page = &entry.page;
-
-            let evictable = Self::evictable(page);
-
-            if evictable && entry.ref_bit == CLEAR {
-                turso_assert!(
-                    Self::counted_as_evictable(page),
-                    "mismatched evictable count state"
-                );
-
-                // Evict this entry
-                self.advance_clock_hand();
-                // Check if clock hand wrapped back to the same entry (meaning this is the only/last entry)
-                if self.clock_hand == entry_ptr {
-                    self.clock_hand = std::ptr::null_mut();
-                }
-
-                self.map.remove(&key);
-                // Clean the page
-                page.clear_loaded();
-                let _ = page.get().buffer.take();
-
-                // Remove from queue
-                unsafe {
-                    let mut cursor = self.queue.cursor_mut_from_ptr(entry_ptr);
-                    cursor.remove();
-                }
+        let map = &self.map;
+        let Some(key) = self.policy.victim(&|key| {
+            map.get(&key)
+                .is_some_and(|entry| Self::evictable(&entry.page))
+        }) else {
+            return Err(CacheError::Full);
+        };
 
-                // Update evictable count after successful eviction
-                self.evictable_count = self.evictable_count.saturating_sub(1);
+        let entry = self
+            .map
+            .remove(&key)
+            .expect("eviction policy picked a page that is not in the cache");
+        let page = &entry.page;
+        turso_assert!(
+            Self::counted_as_evictable(page),
+            "mismatched evictable count state"
+        );
+        // Clean the page
+        page.clear_loaded();
+        let _ = page.get().buffer.take();
+        self.policy.on_evict(entry.handle);
 
-                return Ok(());
-            } else if evictable {
-                // Decrement ref bit and continue
-                entry.decrement_ref();
-                self.advance_clock_hand();
-                examined += 1;
-            } else {
-                // Skip unevictable page
-                self.advance_clock_hand();
-                examined += 1;
-            }
-        }
+        // Update evictable count after successful eviction
+        self.evictable_count = self.evictable_count.saturating_sub(1);
+        self.stats.evictions += 1;
 
-        Err(CacheError::Full)
+        Ok(())
     }
 
     pub fn clear(&mut self, clear_dirty: bool) -> Result<(), CacheError> {
         // Check all pages are clean
-        for &entry_ptr in self.map.values() {
-            let entry = unsafe { &*entry_ptr };
+        for entry in self.map.values() {
             if entry.page.is_dirty() && !clear_dirty {
                 return Err(CacheError::Dirty {
                     pgno: entry.page.get().id,
@@ -717,15 +640,13 @@ impl PageCache {
         }
 
         // Clean all pages
-        for &entry_ptr in self.map.values() {
-            let entry = unsafe { &*entry_ptr };
+        for entry in self.map.values() {
             entry.page.clear_loaded();
             let _ = entry.page.get().buffer.take();
         }
 
         self.map.clear();
-        self.queue.clear();
-        self.clock_hand = std::ptr::null_mut();
+        self.policy.clear();
         self.evictable_count = 0;
         Ok(())
     }
@@ -752,8 +673,7 @@ impl PageCache {
         for key in self
             .map
             .iter()
-            .filter_map(|(key, &entry_ptr)| {
-                let entry = unsafe { &*entry_ptr };
+            .filter_map(|(key, entry)| {
                 let page = &entry.page;
                 if !page.is_dirty() && page.has_wal_tag() {
                     let (frame, _) = page.wal_tag_pair();
@@ -776,20 +696,16 @@ impl PageCache {
 
         tracing::debug!("page_cache_len={}", self.map.len());
 
-        let mut cursor = self.queue.front();
-        let mut i = 0;
-        while let Some(entry) = cursor.get() {
+        for (i, (key, entry)) in self.map.iter().enumerate() {
             let page = &entry.page;
             tracing::debug!(
                 "slot={}, page={:?}, flags={}, pin_count={}, ref_bit={:?}",
                 i,
-                entry.key,
+                key,
                 page.get().flags.load(Ordering::SeqCst),
                 page.get().pin_count.load(Ordering::SeqCst),
-                entry.ref_bit,
+                self.policy.ref_bit(entry.handle),
             );
-            cursor.move_next();
-            i += 1;
         }
     }
 
@@ -808,45 +724,23 @@ impl PageCache {
 
     #[cfg(test)]
     fn verify_cache_integrity(&self) {
-        use rustc_hash::FxHashSet as HashSet;
-
         let map_len = self.map.len();
-
-        // Count entries in queue
-        let mut queue_len = 0;
-        let mut cursor = self.queue.front();
-        let mut seen_keys = HashSet::default();
-
-        while let Some(entry) = cursor.get() {
-            queue_len += 1;
-            seen_keys.insert(entry.key);
-            cursor.move_next();
-        }
-
-        assert_eq!(map_len, queue_len, "map and queue length mismatch");
-        assert_eq!(map_len, seen_keys.len(), "duplicate keys in queue");
-
-        // Verify all map entries are in queue
-        for &key in self.map.keys() {
-            assert!(seen_keys.contains(&key), "map key not in queue");
-        }
+        assert_eq!(map_len, self.policy.len(), "map and policy length mismatch");
 
         // Verify clock hand
-        if !self.clock_hand.is_null() {
-            assert!(map_len > 0, "clock hand set but map is empty");
-            let hand_key = unsafe { (*self.clock_hand).key };
+        if let Some(hand_key) = self.policy.hand() {
             assert!(
                 self.map.contains_key(&hand_key),
                 "clock hand points to non-existent entry"
             );
-        } else {
-            assert_eq!(map_len, 0, "clock hand null but map not empty");
         }
     }
 
     #[cfg(test)]
     fn ref_of(&self, key: &PageCacheKey) -> Option<u8> {
-        self.map.get(key).map(|&ptr| unsafe { (*ptr).ref_bit })
+        self.map
+            .get(key)
+            .and_then(|entry| self.policy.ref_bit(entry.handle))
     }
 }
 
@@ -859,6 +753,7 @@ impl Default for PageCache {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::cache_policy::REF_MAX;
     use crate::storage::page_cache::CacheError;
     use crate::storage::pager::{Page, PageRef};
     use crate::sync::Arc;
@@ -890,6 +785,80 @@ mod tests {
         key
     }
 
+    #[test]
+    fn test_spill_size_raises_spill_threshold() {
+        let mut cache = PageCache::new_with_spill(10, true);
+        assert_eq!((cache.spill_threshold, cache.spill_size()), (9, 10));
+        cache.set_spill_size(50);
+        assert_eq!((cache.spill_threshold, cache.spill_size()), (50, 50));
+        assert_eq!(cache.resize(100), CacheResizeResult::Done);
+        assert_eq!((cache.spill_threshold, cache.spill_size()), (90, 100));
+        cache.set_spill_size(0);
+        assert_eq!(cache.resize(10), CacheResizeResult::Done);
+        assert_eq!((cache.spill_threshold, cache.spill_size()), (9, 10));
+    }
+
+    #[test]
+    fn test_stats_count_hits_misses_and_evictions() {
+        let mut cache = PageCache::new_with_spill(2, true);
+        let key2 = insert_page(&mut cache, 2);
+        let _key3 = insert_page(&mut cache, 3);
+        assert!(cache.get(&key2).unwrap().is_some());
+        assert!(cache.get(&create_key(9)).unwrap().is_none());
+        let _key4 = insert_page(&mut cache, 4);
+        assert_eq!(
+            cache.stats(),
+            PageCacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_lru_policy_evicts_least_recently_used() {
+        let mut cache = PageCache::with_policy(PageCachePolicy::Lru);
+        assert_eq!(cache.resize(3), CacheResizeResult::Done);
+        let key2 = insert_page(&mut cache, 2);
+        let key3 = insert_page(&mut cache, 3);
+        let key4 = insert_page(&mut cache, 4);
+        assert!(cache.get(&key2).unwrap().is_some());
+        let _key5 = insert_page(&mut cache, 5);
+        assert!(cache.contains_key(&key2));
+        assert!(!cache.contains_key(&key3));
+        assert!(cache.contains_key(&key4));
+        assert_eq!(cache.policy.len(), 3);
+    }
+
+    #[test]
+    fn test_every_policy_evicts_within_capacity() {
+        for policy in [
+            PageCachePolicy::Sieve,
+            PageCachePolicy::Lru,
+            PageCachePolicy::TwoQueue,
+            PageCachePolicy::Arc,
+        ] {
+            let mut cache = PageCache::with_policy(policy);
+            assert_eq!(cache.policy(), policy);
+            assert_eq!(cache.capacity(), DEFAULT_PAGE_CACHE_SIZE_IN_PAGES);
+            assert_eq!(cache.resize(4), CacheResizeResult::Done);
+            for pgno in 1..=20 {
+                insert_page(&mut cache, pgno);
+                // Pages evicted earlier come back, so the ghost lists of 2Q
+                // and ARC are exercised.
+                if pgno % 3 == 0 && cache.get(&create_key(pgno / 3)).unwrap().is_none() {
+                    insert_page(&mut cache, pgno / 3);
+                }
+                assert!(cache.len() <= 4, "{policy:?} holds {} pages", cache.len());
+                cache.verify_cache_integrity();
+            }
+            assert!(cache.delete(create_key(20)).is_ok());
+            assert_eq!(cache.policy.len(), cache.len());
+            assert!(cache.stats().evictions > 0, "{policy:?}");
+        }
+    }
+
     #[test]
     fn test_delete_only_element() {
         let mut cache = PageCache::default();
@@ -1716,7 +1685,7 @@ mod tests {
 
         // Delete single element
         assert!(cache.delete(key1).is_ok());
-        assert!(cache.clock_hand.is_null());
+        assert!(cache.policy.hand().is_none());
 
         // Insert after empty should work
         let key2 = insert_page(&mut cache, 2);
@@ -1733,16 +1702,16 @@ mod tests {
         let _key3 = insert_page(&mut cache, 3);
 
         // Note initial hand position
-        let initial_hand = cache.clock_hand;
+        let initial_hand = cache.policy.hand();
 
         // Force eviction
         let _key4 = insert_page(&mut cache, 4);
 
         // Hand should exist (not null)
-        let new_hand = cache.clock_hand;
-        assert!(!new_hand.is_null());
+        let new_hand = cache.policy.hand();
+        assert!(new_hand.is_some());
         // Hand moved during sweep (exact position depends on eviction)
-        assert!(initial_hand.is_null() || new_hand != initial_hand || cache.len() < 2);
+        assert!(initial_hand.is_none() || new_hand != initial_hand || cache.len() < 2);
         cache.verify_cache_integrity();
     }
 
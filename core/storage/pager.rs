@@ -2,8 +2,10 @@ use crate::assert::assert_send_sync;
 #[cfg(target_vendor = "apple")]
 use crate::io::AtomicFileSyncType;
 use crate::io::FileSyncType;
+use crate::io::LockLevel;
 use crate::io::WriteBatch;
 use crate::storage::btree::PinGuard;
+use crate::storage::io_scheduler::IoScheduler;
 use crate::storage::subjournal::Subjournal;
 use crate::storage::wal::{CheckpointLockSource, PreparedFrames};
 use crate::storage::{
@@ -46,7 +48,10 @@ use super::btree::offset::{
 use super::btree::{
     btree_init_page, payload_overflow_threshold_max, payload_overflow_threshold_min,
 };
-use super::page_cache::{CacheError, CacheResizeResult, PageCache, PageCacheKey, SpillResult};
+use super::cache_policy::PageCachePolicy;
+use super::page_cache::{
+    CacheError, CacheResizeResult, PageCache, PageCacheKey, PageCacheStats, SpillResult,
+};
 use super::sqlite3_ondisk::read_varint;
 use super::sqlite3_ondisk::{
     begin_write_btree_page, read_btree_cell, read_u32, BTreeCell, FREELIST_LEAF_PTR_SIZE,
@@ -54,6 +59,7 @@ use super::sqlite3_ondisk::{
     FREELIST_TRUNK_OFFSET_NEXT_TRUNK_PTR,
 };
 use super::wal::{CheckpointMode, WalAutoActions};
+use crate::storage::checksum::ChecksumContext;
 use crate::storage::encryption::{CipherMode, EncryptionContext, EncryptionKey};
 
 /// SQLite's default maximum page count
@@ -70,6 +76,8 @@ const PENDING_BYTE: u32 = 0x40000000;
 
 #[cfg(not(feature = "omit_autovacuum"))]
 use ptrmap::*;
+#[cfg(not(feature = "omit_autovacuum"))]
+use std::collections::BTreeSet;
 
 #[derive(Debug, Clone)]
 pub struct HeaderRef(PageRef);
@@ -1207,6 +1215,71 @@ enum BtreeCreateVacuumFullState {
     PtrMapPut { allocated_page_id: u32 },
 }
 
+/// What a page changed by the current write transaction holds, as far as its
+/// pointer map entry is concerned. Recorded when the page is allocated or
+/// freed, so that the pointer map can be brought up to date before commit.
+#[cfg(not(feature = "omit_autovacuum"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageRole {
+    Root,
+    Btree,
+    Overflow,
+    Free,
+}
+
+#[cfg(not(feature = "omit_autovacuum"))]
+impl PageRole {
+    fn from_ptrmap_type(entry_type: PtrmapType) -> Self {
+        match entry_type {
+            PtrmapType::RootPage => Self::Root,
+            PtrmapType::FreePage => Self::Free,
+            PtrmapType::Overflow1 | PtrmapType::Overflow2 => Self::Overflow,
+            PtrmapType::BTreeNode => Self::Btree,
+        }
+    }
+}
+
+/// Roles of the pages allocated or freed by the current write transaction,
+/// with an undo log so that rolling back to a savepoint forgets the roles
+/// recorded after it.
+#[cfg(not(feature = "omit_autovacuum"))]
+#[derive(Debug, Default)]
+struct PageRoles {
+    roles: HashMap<u32, PageRole>,
+    undo: Vec<(u32, Option<PageRole>)>,
+}
+
+#[cfg(not(feature = "omit_autovacuum"))]
+impl PageRoles {
+    fn set(&mut self, page_id: u32, role: PageRole) {
+        let previous = self.roles.insert(page_id, role);
+        self.undo.push((page_id, previous));
+    }
+
+    fn get(&self, page_id: u32) -> Option<PageRole> {
+        self.roles.get(&page_id).copied()
+    }
+
+    fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    fn rollback_to(&mut self, len: usize) {
+        while self.undo.len() > len {
+            let (page_id, previous) = self.undo.pop().unwrap();
+            match previous {
+                Some(role) => self.roles.insert(page_id, role),
+                None => self.roles.remove(&page_id),
+            };
+        }
+    }
+
+    fn clear(&mut self) {
+        self.roles.clear();
+        self.undo.clear();
+    }
+}
+
 #[derive(Debug, Clone)]
 enum SavepointKind {
     Statement,
@@ -1243,6 +1316,7 @@ struct SavepointSnapshot {
     db_size: u32,
     wal_pos: Option<SavepointWalPos>,
     deferred_fk_violations: isize,
+    page_roles_len: usize,
 }
 
 struct Savepoint {
@@ -1265,6 +1339,9 @@ struct Savepoint {
     wal_pos: RwLock<Option<SavepointWalPos>>,
     /// Deferred FK counter value at the start of this savepoint.
     deferred_fk_violations: AtomicIsize,
+    /// Length of the undo log of the transaction's page roles at the start
+    /// of this savepoint.
+    page_roles_len: usize,
 }
 
 impl Savepoint {
@@ -1274,6 +1351,7 @@ impl Savepoint {
         db_size: u32,
         wal_pos: Option<SavepointWalPos>,
         deferred_fk_violations: isize,
+        page_roles_len: usize,
     ) -> Self {
         Self {
             kind,
@@ -1283,6 +1361,7 @@ impl Savepoint {
             db_size: AtomicU32::new(db_size),
             wal_pos: RwLock::new(wal_pos),
             deferred_fk_violations: AtomicIsize::new(deferred_fk_violations),
+            page_roles_len,
         }
     }
 
@@ -1313,6 +1392,7 @@ impl Savepoint {
             db_size: self.db_size.load(Ordering::Acquire),
             wal_pos: *self.wal_pos.read(),
             deferred_fk_violations: self.deferred_fk_violations.load(Ordering::Acquire),
+            page_roles_len: self.page_roles_len,
         }
     }
 
@@ -1325,6 +1405,7 @@ impl Savepoint {
             db_size: AtomicU32::new(snapshot.db_size),
             wal_pos: RwLock::new(snapshot.wal_pos),
             deferred_fk_violations: AtomicIsize::new(snapshot.deferred_fk_violations),
+            page_roles_len: snapshot.page_roles_len,
         }
     }
 }
@@ -1344,6 +1425,9 @@ pub struct Pager {
     pub buffer_pool: Arc<BufferPool>,
     /// I/O interface for input/output operations.
     pub io: Arc<dyn crate::io::IO>,
+    /// Shared with the other pagers of the database; ranks foreground reads
+    /// ahead of checkpoint I/O.
+    io_scheduler: Arc<IoScheduler>,
     /// Reads that have begun (disk IO issued, page allocated) but whose
     /// `cache_insert` has not yet succeeded because the cache was full and we
     /// yielded waiting for a spill to complete. The next call to
@@ -1399,6 +1483,9 @@ pub struct Pager {
     /// Counterpart of SQLite's BtShared.pCursor list; bucketing per root
     /// supplies the BTCF_Multiple fast path (btree.c:9348).
     pub(crate) cursor_registry: Mutex<rustc_hash::FxHashMap<i64, Vec<RegisteredCursor>>>,
+    /// Lock this connection holds on the database file: SHARED during a read
+    /// transaction, RESERVED during a write transaction.
+    db_file_lock: Mutex<LockLevel>,
 }
 
 /// Raw fat pointer to a registered cursor.
@@ -1505,6 +1592,7 @@ pub struct VacuumState {
     /// State machine for [Pager::ptrmap_put]
     ptrmap_put_state: PtrMapPutState,
     btree_create_vacuum_full_state: BtreeCreateVacuumFullState,
+    page_roles: PageRoles,
 }
 
 #[derive(Debug, Clone)]
@@ -1637,6 +1725,7 @@ impl Pager {
             wal,
             page_cache: Arc::new(RwLock::new(page_cache)),
             io,
+            io_scheduler: Arc::new(IoScheduler::new()),
             pending_reads: RwLock::new(HashMap::new()),
             #[cfg(test)]
             spill_yield: SpillYieldHook::new(),
@@ -1672,6 +1761,7 @@ impl Pager {
                 ptrmap_get_state: PtrMapGetState::Start,
                 ptrmap_put_state: PtrMapPutState::Start,
                 btree_create_vacuum_full_state: BtreeCreateVacuumFullState::Start,
+                page_roles: PageRoles::default(),
             }),
             io_ctx: RwLock::new(IOContext::default()),
             enable_encryption: AtomicBool::new(false),
@@ -1679,9 +1769,20 @@ impl Pager {
             #[cfg(target_vendor = "apple")]
             sync_type: AtomicFileSyncType::new(FileSyncType::Fsync),
             cursor_registry: Mutex::new(rustc_hash::FxHashMap::default()),
+            db_file_lock: Mutex::new(LockLevel::None),
         })
     }
 
+    /// Share `scheduler` with this pager, so that foreground reads and
+    /// checkpoints of every connection to a database see each other.
+    pub(crate) fn set_io_scheduler(&mut self, scheduler: Arc<IoScheduler>) {
+        self.io_scheduler = scheduler;
+    }
+
+    pub(crate) fn io_scheduler(&self) -> &Arc<IoScheduler> {
+        &self.io_scheduler
+    }
+
     /// Add a cursor to the registry. Called from Cursor::new_btree once the
     /// cursor lives in its final heap location; BTreeCursor::drop unregisters.
     pub(crate) fn register_cursor(&self, cursor: &dyn crate::storage::btree::CursorTrait) {
@@ -1852,6 +1953,23 @@ impl Pager {
     pub fn get_spill_enabled(&self) -> bool {
         self.page_cache.read().is_spill_enabled()
     }
+    /// Set the minimum number of cached pages before dirty pages are spilled.
+    pub fn set_spill_size(&self, pages: usize) {
+        self.page_cache.write().set_spill_size(pages);
+    }
+    /// Get the number of cached pages at which dirty pages are spilled.
+    pub fn get_spill_size(&self) -> usize {
+        self.page_cache.read().spill_size()
+    }
+    /// Read the first `bytes` bytes of the database file through a memory
+    /// mapping where the storage supports it. Returns the limit in effect.
+    pub fn set_mmap_size(&self, bytes: u64) -> Result<u64> {
+        self.db_file.set_mmap_limit(bytes)
+    }
+    /// Get the number of bytes of the database file read through a mapping.
+    pub fn get_mmap_size(&self) -> u64 {
+        self.db_file.mmap_limit()
+    }
 
     /// Open the subjournal if not yet open.
     /// The subjournal is a file that is used to store the "before images" of pages for the
@@ -2179,6 +2297,7 @@ impl Pager {
             db_size,
             wal_pos,
             deferred_fk_violations,
+            self.page_roles_len(),
         );
         self.savepoints.write().push(savepoint);
         Ok(())
@@ -2204,6 +2323,11 @@ impl Pager {
         journal_end_offset: u64,
     ) -> Result<()> {
         self.reset_internal_states();
+        #[cfg(not(feature = "omit_autovacuum"))]
+        self.vacuum_state
+            .write()
+            .page_roles
+            .rollback_to(savepoint.page_roles_len);
 
         let subjournal = self.subjournal.read();
         let Some(subjournal) = subjournal.as_ref() else {
@@ -2264,17 +2388,7 @@ impl Pager {
         // are never subjournaled (see subjournal_page_if_required), so the loop
         // above won't encounter them. We must clean them from dirty_pages before
         // truncating the cache, or phantom dirty entries survive into commit.
-        {
-            let mut cache = self.page_cache.write();
-            for page_id in dirty_pages.iter().filter(|&id| id > db_size) {
-                if let Some(page) = cache.get(&PageCacheKey::new(page_id as usize))? {
-                    page.clear_dirty();
-                    page.try_unpin();
-                }
-            }
-            dirty_pages.remove_range((db_size + 1)..);
-            cache.truncate(db_size as usize)?;
-        }
+        self.forget_pages_after(&mut dirty_pages, db_size)?;
 
         // No WAL position: the transaction never upgraded to a write
         // transaction, so there are no frames to rewind.
@@ -2300,6 +2414,20 @@ impl Pager {
         Ok(())
     }
 
+    /// Drops every page past `db_size` from the dirty set and the cache.
+    fn forget_pages_after(&self, dirty_pages: &mut RoaringBitmap, db_size: u32) -> Result<()> {
+        let mut cache = self.page_cache.write();
+        for page_id in dirty_pages.iter().filter(|&id| id > db_size) {
+            if let Some(page) = cache.get(&PageCacheKey::new(page_id as usize))? {
+                page.clear_dirty();
+                page.try_unpin();
+            }
+        }
+        dirty_pages.remove_range((db_size + 1)..);
+        cache.truncate(db_size as usize)?;
+        Ok(())
+    }
+
     #[cfg(feature = "test_helper")]
     pub fn get_pending_byte() -> u32 {
         PENDING_BYTE.load(Ordering::Relaxed)
@@ -2574,6 +2702,420 @@ impl Pager {
         }
     }
 
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn page_roles_len(&self) -> usize {
+        self.vacuum_state.read().page_roles.undo_len()
+    }
+
+    #[cfg(feature = "omit_autovacuum")]
+    fn page_roles_len(&self) -> usize {
+        0
+    }
+
+    /// Records what `page_id` holds now, for [Pager::sync_ptrmap]. Does
+    /// nothing if the database has no pointer map.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn note_page_role(&self, page_id: u32, role: PageRole) {
+        if self.get_auto_vacuum_mode() != AutoVacuumMode::None {
+            self.vacuum_state.write().page_roles.set(page_id, role);
+        }
+    }
+
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn read_page_blocking(&self, page_id: u32) -> Result<PageRef> {
+        let (page, c) = self.io.block(|| self.read_page(page_id as i64))?;
+        if let Some(c) = c {
+            self.io.wait_for_completion(c)?;
+        }
+        Ok(page)
+    }
+
+    /// Pointers from the btree page `page` to other pages: where each one is
+    /// stored in the page, the page it points to and the pointer map type of
+    /// that page.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn btree_page_pointers(
+        page: &PageContent,
+        usable_space: usize,
+    ) -> Result<Vec<(usize, u32, PtrmapType)>> {
+        let mut pointers = Vec::new();
+        for idx in 0..page.cell_count() {
+            let (left_child, first_overflow_page) = match page.cell_get(idx, usable_space)? {
+                BTreeCell::TableInteriorCell(cell) => (Some(cell.left_child_page), None),
+                BTreeCell::TableLeafCell(cell) => (None, cell.first_overflow_page),
+                BTreeCell::IndexInteriorCell(cell) => {
+                    (Some(cell.left_child_page), cell.first_overflow_page)
+                }
+                BTreeCell::IndexLeafCell(cell) => (None, cell.first_overflow_page),
+            };
+            let (start, len) = page.cell_get_raw_region(idx, usable_space)?;
+            if let Some(child) = left_child {
+                pointers.push((start, child, PtrmapType::BTreeNode));
+            }
+            if let Some(overflow) = first_overflow_page {
+                // The first overflow page number ends the cell.
+                pointers.push((start + len - 4, overflow, PtrmapType::Overflow1));
+            }
+        }
+        if let Some(child) = page.rightmost_pointer()? {
+            pointers.push((
+                page.offset() + BTREE_RIGHTMOST_PTR,
+                child,
+                PtrmapType::BTreeNode,
+            ));
+        }
+        Ok(pointers)
+    }
+
+    /// Brings the pointer map up to date with the current write transaction:
+    /// writes the entries of the pages it allocated or freed and of every page
+    /// the pages it changed point to.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn sync_ptrmap(&self) -> Result<()> {
+        let (db_size, page_size) = self.io.block(|| {
+            self.with_header(|header| (header.database_size.get(), header.page_size.get() as usize))
+        })?;
+        let usable_space = self.usable_space();
+        let pending_byte_page = self.pending_byte_page_id();
+        let mut pages = self.dirty_pages.read().clone();
+        pages.extend(self.vacuum_state.read().page_roles.roles.keys().copied());
+
+        let mut entries = Vec::new();
+        for page_id in pages.iter().take_while(|&page_id| page_id <= db_size) {
+            if is_ptrmap_page(page_id, page_size) || Some(page_id) == pending_byte_page {
+                continue;
+            }
+            let role = self.vacuum_state.read().page_roles.get(page_id);
+            let role = match (page_id, role) {
+                (1, _) => PageRole::Btree,
+                (_, Some(role)) => role,
+                (_, None) => match self.io.block(|| self.ptrmap_get(page_id))? {
+                    Some(entry) => PageRole::from_ptrmap_type(entry.entry_type),
+                    None => continue,
+                },
+            };
+            match role {
+                PageRole::Free => {
+                    entries.push((page_id, PtrmapType::FreePage, 0));
+                    continue;
+                }
+                PageRole::Root => entries.push((page_id, PtrmapType::RootPage, 0)),
+                PageRole::Btree | PageRole::Overflow => {}
+            }
+            let page = self.read_page_blocking(page_id)?;
+            let contents = page.get_contents();
+            if role == PageRole::Overflow {
+                let next = contents.read_u32_no_offset(0);
+                if next != 0 {
+                    entries.push((next, PtrmapType::Overflow2, page_id));
+                }
+                continue;
+            }
+            for (_, child, entry_type) in Self::btree_page_pointers(contents, usable_space)? {
+                entries.push((child, entry_type, page_id));
+            }
+        }
+        for (page_id, entry_type, parent_page_no) in entries {
+            self.io
+                .block(|| self.ptrmap_put(page_id, entry_type, parent_page_no))?;
+        }
+        Ok(())
+    }
+
+    /// Pages on the freelist.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn load_freelist(&self) -> Result<BTreeSet<u32>> {
+        let (mut trunk_page_id, freelist_pages, db_size) = self.io.block(|| {
+            self.with_header(|header| {
+                (
+                    header.freelist_trunk_page.get(),
+                    header.freelist_pages.get(),
+                    header.database_size.get(),
+                )
+            })
+        })?;
+        let max_leaves = self.usable_space() / FREELIST_LEAF_PTR_SIZE - 2;
+        let mut free = BTreeSet::new();
+        while trunk_page_id != 0 {
+            if trunk_page_id > db_size || !free.insert(trunk_page_id) {
+                return Err(LimboError::Corrupt(format!(
+                    "Invalid freelist trunk page {trunk_page_id}"
+                )));
+            }
+            let trunk_page = self.read_page_blocking(trunk_page_id)?;
+            let contents = trunk_page.get_contents();
+            let leaf_count = contents.read_u32_no_offset(FREELIST_TRUNK_OFFSET_LEAF_COUNT) as usize;
+            if leaf_count > max_leaves {
+                return Err(LimboError::Corrupt(format!(
+                    "Freelist trunk page {trunk_page_id} has {leaf_count} leaves"
+                )));
+            }
+            for idx in 0..leaf_count {
+                let leaf = contents.read_u32_no_offset(
+                    FREELIST_TRUNK_OFFSET_FIRST_LEAF_PTR + idx * FREELIST_LEAF_PTR_SIZE,
+                );
+                if leaf < 2 || leaf > db_size || !free.insert(leaf) {
+                    return Err(LimboError::Corrupt(format!(
+                        "Invalid freelist leaf page {leaf} on trunk page {trunk_page_id}"
+                    )));
+                }
+            }
+            trunk_page_id = contents.read_u32_no_offset(FREELIST_TRUNK_OFFSET_NEXT_TRUNK_PTR);
+        }
+        if free.len() != freelist_pages as usize {
+            return Err(LimboError::Corrupt(format!(
+                "Freelist has {} pages but the header says {freelist_pages}",
+                free.len()
+            )));
+        }
+        Ok(free)
+    }
+
+    /// Rebuilds the freelist so that it holds exactly the pages of `free`.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn write_freelist(&self, free: &BTreeSet<u32>) -> Result<()> {
+        let max_leaves = self.usable_space() / FREELIST_LEAF_PTR_SIZE - 2;
+        let pages: Vec<u32> = free.iter().copied().collect();
+        let mut next_trunk_page_id = 0;
+        for chunk in pages.chunks(max_leaves + 1).rev() {
+            let trunk_page = self.read_page_blocking(chunk[0])?;
+            self.add_dirty(&trunk_page)?;
+            let contents = trunk_page.get_contents();
+            contents.write_u32_no_offset(FREELIST_TRUNK_OFFSET_NEXT_TRUNK_PTR, next_trunk_page_id);
+            contents.write_u32_no_offset(FREELIST_TRUNK_OFFSET_LEAF_COUNT, chunk.len() as u32 - 1);
+            for (idx, leaf) in chunk[1..].iter().enumerate() {
+                contents.write_u32_no_offset(
+                    FREELIST_TRUNK_OFFSET_FIRST_LEAF_PTR + idx * FREELIST_LEAF_PTR_SIZE,
+                    *leaf,
+                );
+            }
+            next_trunk_page_id = chunk[0];
+        }
+        let freelist_pages = pages.len() as u32;
+        self.io.block(|| {
+            self.with_header_mut(|header| {
+                header.freelist_trunk_page = next_trunk_page_id.into();
+                header.freelist_pages = freelist_pages.into();
+            })
+        })?;
+        Ok(())
+    }
+
+    /// Moves the content of page `from`, whose pointer map entry is `entry`,
+    /// to page `to`, and points its parent and its children at `to`.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn relocate_page(&self, from: u32, to: u32, entry: PtrmapEntry) -> Result<()> {
+        tracing::debug!("relocate_page(from={from}, to={to}, entry={entry:?})");
+        let usable_space = self.usable_space();
+        let content = self
+            .read_page_blocking(from)?
+            .get_contents()
+            .as_ptr()
+            .to_vec();
+        let page = self.read_page_blocking(to)?;
+        self.add_dirty(&page)?;
+        let contents = page.get_contents();
+        contents.as_ptr().copy_from_slice(&content);
+        let children: Vec<(u32, PtrmapType)> = match entry.entry_type {
+            PtrmapType::Overflow1 | PtrmapType::Overflow2 => match contents.read_u32_no_offset(0) {
+                0 => vec![],
+                next => vec![(next, PtrmapType::Overflow2)],
+            },
+            _ => Self::btree_page_pointers(contents, usable_space)?
+                .into_iter()
+                .map(|(_, child, entry_type)| (child, entry_type))
+                .collect(),
+        };
+        self.note_page_role(to, PageRole::from_ptrmap_type(entry.entry_type));
+
+        let parent_page_no = entry.parent_page_no;
+        let parent = self.read_page_blocking(parent_page_no)?;
+        let parent_contents = parent.get_contents();
+        let slot = match entry.entry_type {
+            PtrmapType::Overflow2 => (parent_contents.read_u32_no_offset(0) == from).then_some(0),
+            entry_type => Self::btree_page_pointers(parent_contents, usable_space)?
+                .into_iter()
+                .find(|&(_, child, child_type)| child == from && child_type == entry_type)
+                .map(|(offset, ..)| offset),
+        };
+        let Some(slot) = slot else {
+            return Err(LimboError::Corrupt(format!(
+                "Page {parent_page_no} does not point to page {from} as its pointer map entry says"
+            )));
+        };
+        self.add_dirty(&parent)?;
+        parent_contents.write_u32_no_offset(slot, to);
+
+        self.io
+            .block(|| self.ptrmap_put(to, entry.entry_type, parent_page_no))?;
+        for (child, entry_type) in children {
+            self.io.block(|| self.ptrmap_put(child, entry_type, to))?;
+        }
+        Ok(())
+    }
+
+    /// Gives free pages back by moving the last pages of the database into
+    /// free pages further down and truncating the database, taking at most
+    /// `max_pages` pages off the freelist if given. Root pages never move, so
+    /// this stops at the last one. Expects the pointer map to be up to date.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn vacuum_free_pages(&self, max_pages: Option<u32>) -> Result<()> {
+        let (db_size, freelist_pages, page_size) = self.io.block(|| {
+            self.with_header(|header| {
+                (
+                    header.database_size.get(),
+                    header.freelist_pages.get(),
+                    header.page_size.get() as usize,
+                )
+            })
+        })?;
+        if freelist_pages == 0 {
+            return Ok(());
+        }
+        let pending_byte_page = self.pending_byte_page_id();
+        let is_reserved =
+            |page_id| is_ptrmap_page(page_id, page_size) || Some(page_id) == pending_byte_page;
+
+        let mut free = self.load_freelist()?;
+        let mut last = db_size;
+        let mut taken = 0;
+        while last > 1 && !free.is_empty() && max_pages.is_none_or(|max| taken < max) {
+            if !is_reserved(last) {
+                if !free.remove(&last) {
+                    let entry = self.io.block(|| self.ptrmap_get(last))?.ok_or_else(|| {
+                        LimboError::Corrupt(format!("Page {last} has no pointer map entry"))
+                    })?;
+                    match entry.entry_type {
+                        PtrmapType::RootPage => break,
+                        PtrmapType::FreePage => {
+                            return Err(LimboError::Corrupt(format!(
+                                "Page {last} is marked free in the pointer map but is not on the freelist"
+                            )));
+                        }
+                        _ => {
+                            let to = free.pop_first().expect("freelist is not empty");
+                            self.relocate_page(last, to, entry)?;
+                        }
+                    }
+                }
+                taken += 1;
+            }
+            last -= 1;
+        }
+        while last > 1 && is_reserved(last) {
+            last -= 1;
+        }
+        if last == db_size {
+            return Ok(());
+        }
+        tracing::debug!("vacuum_free_pages(db_size={db_size}, new_db_size={last})");
+
+        // Rolling back to a savepoint brings the truncated pages back, so
+        // journal the ones changed before it started.
+        let truncated_dirty_pages: Vec<u32> = self
+            .dirty_pages
+            .read()
+            .iter()
+            .filter(|&page_id| page_id > last)
+            .collect();
+        for page_id in truncated_dirty_pages {
+            let page = self.read_page_blocking(page_id)?;
+            self.subjournal_page_if_required(&page)?;
+        }
+
+        self.write_freelist(&free)?;
+        self.io
+            .block(|| self.with_header_mut(|header| header.database_size = last.into()))?;
+        self.invalidate_all_cursors();
+        self.forget_pages_after(&mut self.dirty_pages.write(), last)?;
+        Ok(())
+    }
+
+    /// Writes the pointer map entries of the current write transaction and,
+    /// in full auto-vacuum mode, gives the free pages back, before commit.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn prepare_auto_vacuum_commit(&self) -> Result<()> {
+        let auto_vacuum_mode = self.get_auto_vacuum_mode();
+        if auto_vacuum_mode == AutoVacuumMode::None {
+            return Ok(());
+        }
+        if !self.dirty_pages.read().is_empty() {
+            self.sync_ptrmap()?;
+            if auto_vacuum_mode == AutoVacuumMode::Full {
+                self.vacuum_free_pages(None)?;
+            }
+        }
+        self.vacuum_state.write().page_roles.clear();
+        Ok(())
+    }
+
+    /// Takes up to `max_pages` pages off the freelist of an incremental
+    /// auto-vacuum database, or all of them if `max_pages` is 0, shrinking
+    /// the database by as many pages. Does nothing in other modes.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    pub fn incremental_vacuum(&self, max_pages: u32) -> Result<()> {
+        if self.get_auto_vacuum_mode() != AutoVacuumMode::Incremental {
+            return Ok(());
+        }
+        self.sync_ptrmap()?;
+        self.vacuum_free_pages((max_pages > 0).then_some(max_pages))
+    }
+
+    #[cfg(feature = "omit_autovacuum")]
+    pub fn incremental_vacuum(&self, _max_pages: u32) -> Result<()> {
+        Ok(())
+    }
+
+    /// Makes page `root_page_num` available for a new root page of type
+    /// `page_type`, `page` having been allocated in its place: a free page
+    /// `root_page_num` swaps places with `page` on the freelist, anything else
+    /// moves to `page`.
+    #[cfg(not(feature = "omit_autovacuum"))]
+    fn claim_root_page(
+        &self,
+        page: &PageRef,
+        root_page_num: u32,
+        page_type: PageType,
+    ) -> Result<()> {
+        let page_id = page.get().id as u32;
+        self.sync_ptrmap()?;
+        let entry = self
+            .io
+            .block(|| self.ptrmap_get(root_page_num))?
+            .ok_or_else(|| {
+                LimboError::Corrupt(format!("Page {root_page_num} has no pointer map entry"))
+            })?;
+        match entry.entry_type {
+            PtrmapType::RootPage => {
+                return Err(LimboError::Corrupt(format!(
+                    "Page {root_page_num} is past the largest root page but is a root page"
+                )));
+            }
+            PtrmapType::FreePage => {
+                let mut free = self.load_freelist()?;
+                if !free.remove(&root_page_num) {
+                    return Err(LimboError::Corrupt(format!(
+                        "Page {root_page_num} is marked free in the pointer map but is not on the freelist"
+                    )));
+                }
+                free.insert(page_id);
+                self.write_freelist(&free)?;
+                self.io
+                    .block(|| self.ptrmap_put(page_id, PtrmapType::FreePage, 0))?;
+                self.note_page_role(page_id, PageRole::Free);
+            }
+            _ => {
+                self.invalidate_all_cursors();
+                self.relocate_page(root_page_num, page_id, entry)?;
+            }
+        }
+        let root_page = self.read_page_blocking(root_page_num)?;
+        self.add_dirty(&root_page)?;
+        root_page.get_contents().as_ptr().fill(0);
+        btree_init_page(&root_page, page_type, 0, self.usable_space());
+        Ok(())
+    }
+
     /// This method is used to allocate a new root page for a btree, both for tables and indexes
     /// FIXME: handle no room in page cache
     #[instrument(skip_all, level = Level::DEBUG)]
@@ -2600,7 +3142,7 @@ impl Pager {
                         return_if_io!(self.do_allocate_page(page_type, 0, BtreePageAllocMode::Any));
                     Ok(IOResult::Done(page.get().id as u32))
                 }
-                AutoVacuumMode::Full => {
+                AutoVacuumMode::Full | AutoVacuumMode::Incremental => {
                     loop {
                         let btree_create_vacuum_full_state = {
                             let vacuum_state = self.vacuum_state.read();
@@ -2624,7 +3166,9 @@ impl Pager {
                                     "can never be less than 2 because we have already incremented"
                                 );
 
-                                while is_ptrmap_page(root_page_num, page_size as usize) {
+                                while is_ptrmap_page(root_page_num, page_size as usize)
+                                    || Some(root_page_num) == self.pending_byte_page_id()
+                                {
                                     root_page_num += 1;
                                 }
                                 turso_assert_greater_than_or_equal!(
@@ -2642,8 +3186,17 @@ impl Pager {
                                     0,
                                     BtreePageAllocMode::Exact(root_page_num),
                                 ));
-                                let allocated_page_id = page.get().id as u32;
-
+                                let mut allocated_page_id = page.get().id as u32;
+                                //  Root pages must come before every other page so that
+                                //  vacuuming never has to move one.
+                                if allocated_page_id > root_page_num {
+                                    self.claim_root_page(&page, root_page_num, page_type)?;
+                                    allocated_page_id = root_page_num;
+                                }
+                                self.vacuum_state.write().btree_create_vacuum_full_state =
+                                    BtreeCreateVacuumFullState::PtrMapPut { allocated_page_id };
+                            }
+                            BtreeCreateVacuumFullState::PtrMapPut { allocated_page_id } => {
                                 return_if_io!(self.with_header_mut(|header| {
                                     if allocated_page_id
                                         > header.vacuum_mode_largest_root_page.get()
@@ -2657,22 +3210,12 @@ impl Pager {
                                             allocated_page_id.into();
                                     }
                                 }));
-
-                                if allocated_page_id != root_page_num {
-                                    //  TODO(Zaid): Handle swapping the allocated page with the desired root page
-                                }
-
-                                //  TODO(Zaid): Update the header metadata to reflect the new root page number
-                                self.vacuum_state.write().btree_create_vacuum_full_state =
-                                    BtreeCreateVacuumFullState::PtrMapPut { allocated_page_id };
-                            }
-                            BtreeCreateVacuumFullState::PtrMapPut { allocated_page_id } => {
-                                //  For now map allocated_page_id since we are not swapping it with root_page_num
                                 return_if_io!(self.ptrmap_put(
                                     allocated_page_id,
                                     PtrmapType::RootPage,
                                     0,
                                 ));
+                                self.note_page_role(allocated_page_id, PageRole::Root);
                                 self.vacuum_state.write().btree_create_vacuum_full_state =
                                     BtreeCreateVacuumFullState::Start;
                                 return Ok(IOResult::Done(allocated_page_id));
@@ -2680,11 +3223,6 @@ impl Pager {
                         }
                     }
                 }
-                AutoVacuumMode::Incremental => {
-                    return Err(LimboError::InternalError(
-                        "Incremental auto-vacuum is not supported".to_string(),
-                    ));
-                }
             }
         }
     }
@@ -2700,6 +3238,8 @@ impl Pager {
         let contents = page.get_contents();
         let buf = contents.as_ptr();
         buf.fill(0);
+        #[cfg(not(feature = "omit_autovacuum"))]
+        self.note_page_role(page.get().id as u32, PageRole::Overflow);
 
         Ok(IOResult::Done(page))
     }
@@ -2721,6 +3261,8 @@ impl Pager {
             "offset doesn't match computed offset for page"
         );
         btree_init_page(&page, page_type, offset, self.usable_space());
+        #[cfg(not(feature = "omit_autovacuum"))]
+        self.note_page_role(page.get().id as u32, PageRole::Btree);
         tracing::debug!(
             "do_allocate_page(id={}, page_type={:?})",
             page.get().id,
@@ -2924,7 +3466,11 @@ impl Pager {
         let Some(wal) = self.wal.as_ref() else {
             return Ok(());
         };
-        let changed = wal.begin_read_tx()?;
+        let held = *self.db_file_lock.lock();
+        self.acquire_db_file_lock(LockLevel::Shared)?;
+        let changed = wal
+            .begin_read_tx()
+            .inspect_err(|_| self.release_db_file_lock(held))?;
         if changed {
             // Someone else changed the database -> assume our page cache is invalid (this is default SQLite behavior, we can probably do better with more granular invalidation)
             self.clear_page_cache(false);
@@ -2973,13 +3519,42 @@ impl Pager {
         let Some(wal) = self.wal.as_ref() else {
             return Ok(IOResult::Done(()));
         };
-        wal.begin_write_tx(allowed_auto_actions)?;
+        let held = *self.db_file_lock.lock();
+        self.acquire_db_file_lock(LockLevel::Reserved)?;
+        wal.begin_write_tx(allowed_auto_actions)
+            .inspect_err(|_| self.release_db_file_lock(held))?;
         // Must run after the upgrade (and any log restart it performed) so
         // the positions belong to the current WAL generation.
         self.materialize_savepoint_wal_positions();
         Ok(IOResult::Done(()))
     }
 
+    /// Raise this connection's lock on the database file to `level`, if it is
+    /// not already that strong.
+    fn acquire_db_file_lock(&self, level: LockLevel) -> Result<()> {
+        let mut held = self.db_file_lock.lock();
+        if *held >= level {
+            return Ok(());
+        }
+        self.db_file.set_lock_level(*held, level)?;
+        *held = level;
+        Ok(())
+    }
+
+    /// Lower this connection's lock on the database file to `level`,
+    /// `LockLevel::Shared` or `LockLevel::None`. The transaction is over
+    /// either way, so a failure is only logged.
+    fn release_db_file_lock(&self, level: LockLevel) {
+        let mut held = self.db_file_lock.lock();
+        if *held <= level {
+            return;
+        }
+        if let Err(e) = self.db_file.set_lock_level(*held, level) {
+            tracing::error!("failed to release the lock on the database file: {e}");
+        }
+        *held = level;
+    }
+
     /// Fill in the WAL position of savepoints opened before this write
     /// transaction, mirroring SQLite's `sqlite3PagerOpenSavepoint` at
     /// write-transaction begin. Idempotent: only fills unmaterialized
@@ -3016,7 +3591,9 @@ impl Pager {
         let wal = self.wal.as_ref().ok_or_else(|| {
             LimboError::InternalError("begin_vacuum_blocking_tx requires WAL mode".into())
         })?;
-        wal.begin_vacuum_blocking_tx()?;
+        self.acquire_db_file_lock(LockLevel::Reserved)?;
+        wal.begin_vacuum_blocking_tx()
+            .inspect_err(|_| self.release_db_file_lock(LockLevel::None))?;
         // let's be conservative and clear all cache for vacuum
         // todo: clear cache only if we detect that new writes have occurred like `begin_read_tx`
         self.clear_page_cache(false);
@@ -3037,11 +3614,11 @@ impl Pager {
             // Parent statement will handle the transaction commit.
             return Ok(IOResult::Done(()));
         }
-        let Some(wal) = self.wal.as_ref() else {
+        if self.wal.is_none() {
             // TODO: Unsure what the semantics of "end_tx" is for in-memory databases, ephemeral tables and ephemeral indexes.
             self.clear_savepoints()?;
             return Ok(IOResult::Done(()));
-        };
+        }
 
         let complete_commit = || {
             if update_transaction_state {
@@ -3089,8 +3666,8 @@ impl Pager {
                         _ => false,
                     };
 
-                    wal.end_write_tx();
-                    wal.end_read_tx();
+                    self.end_write_tx();
+                    self.end_read_tx();
                     // we do not set TransactionState::None here - because caller can decide that nothing should be done for this connection
                     // and skip next calls of the commit_tx methods after IO
 
@@ -3116,10 +3693,10 @@ impl Pager {
             // Parent statement will handle the transaction rollback.
             return;
         }
-        let Some(wal) = self.wal.as_ref() else {
+        if self.wal.is_none() {
             // TODO: Unsure what the semantics of "end_tx" is for in-memory databases, ephemeral tables and ephemeral indexes.
             return;
-        };
+        }
         let (is_write, schema_did_change) = match connection.get_tx_state() {
             TransactionState::Write { schema_did_change } => (true, schema_did_change),
             _ => (false, false),
@@ -3132,11 +3709,11 @@ impl Pager {
             // Otherwise, another thread could commit new frames to frame_cache between
             // end_write_tx() and rollback(), and rollback() would incorrectly remove them.
             self.rollback(schema_did_change, connection, is_write);
-            wal.end_write_tx();
+            self.end_write_tx();
         } else {
             self.rollback(schema_did_change, connection, is_write);
         }
-        wal.end_read_tx();
+        self.end_read_tx();
     }
 
     pub(crate) fn cleanup_read_tx(&self) {
@@ -3145,7 +3722,7 @@ impl Pager {
         };
         self.reset_internal_states();
         if wal.holds_read_lock() {
-            wal.end_read_tx();
+            self.end_read_tx();
         }
     }
 
@@ -3155,6 +3732,7 @@ impl Pager {
             return;
         };
         wal.end_read_tx();
+        self.release_db_file_lock(LockLevel::None);
     }
 
     /// End just the write transaction on the WAL, without affecting the read lock.
@@ -3163,6 +3741,7 @@ impl Pager {
             return;
         };
         wal.end_write_tx();
+        self.release_db_file_lock(LockLevel::Shared);
     }
 
     /// Returns true if this pager's WAL currently holds a read lock.
@@ -3193,15 +3772,17 @@ impl Pager {
             // Clear dirty pages and page cache before releasing the write lock
             self.clear_page_cache(true);
             self.dirty_pages.write().clear();
+            #[cfg(not(feature = "omit_autovacuum"))]
+            self.vacuum_state.write().page_roles.clear();
             self.reset_internal_states();
             self.set_schema_cookie(None);
             wal.rollback(None);
-            wal.end_write_tx();
+            self.end_write_tx();
         } else {
             self.cleanup_read_tx();
         }
         if wal.holds_read_lock() {
-            wal.end_read_tx();
+            self.end_read_tx();
         }
     }
 
@@ -3345,6 +3926,7 @@ impl Pager {
             page_idx,
             allow_empty_read,
             io_ctx,
+            Some(self.io_scheduler.begin_foreground_read()),
         )
     }
 
@@ -3428,6 +4010,16 @@ impl Pager {
         Ok(page)
     }
 
+    /// Lookup and eviction counters of the page cache.
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        self.page_cache.read().stats()
+    }
+
+    /// The eviction policy of the page cache.
+    pub fn page_cache_policy(&self) -> PageCachePolicy {
+        self.page_cache.read().policy()
+    }
+
     /// Changes the size of the page cache.
     pub fn change_page_cache_size(&self, capacity: usize) -> Result<CacheResizeResult> {
         let mut page_cache = self.page_cache.write();
@@ -4078,6 +4670,8 @@ impl Pager {
 
             match state {
                 CommitState::PrepareWal => {
+                    #[cfg(not(feature = "omit_autovacuum"))]
+                    self.prepare_auto_vacuum_commit()?;
                     let page_sz = self.get_page_size_unchecked();
                     let c = wal.prepare_wal_start(page_sz)?;
                     let Some(c) = c else {
@@ -5096,6 +5690,8 @@ impl Pager {
                     };
                     page.get().overflow_cells.clear();
                     header.freelist_pages = (header.freelist_pages.get() + 1).into();
+                    #[cfg(not(feature = "omit_autovacuum"))]
+                    self.note_page_role(page_id as u32, PageRole::Free);
 
                     let trunk_page_id = header.freelist_trunk_page.get();
 
@@ -5304,10 +5900,8 @@ impl Pager {
                         //  If the following conditions are met, allocate a pointer map page, add to cache and increment the database size
                         //  - autovacuum is enabled
                         //  - the last page is a pointer map page
-                        if matches!(
-                            AutoVacuumMode::from(self.auto_vacuum_mode.load(Ordering::SeqCst)),
-                            AutoVacuumMode::Full
-                        ) && is_ptrmap_page(new_db_size + 1, header.page_size.get() as usize)
+                        if self.get_auto_vacuum_mode() != AutoVacuumMode::None
+                            && is_ptrmap_page(new_db_size + 1, header.page_size.get() as usize)
                         {
                             // we will allocate a ptrmap page, so increment size
                             new_db_size += 1;
@@ -5586,6 +6180,8 @@ impl Pager {
             // since we only need to clear the dirty pages that were modified by the write transaction.
             self.clear_page_cache(clear_dirty);
             self.dirty_pages.write().clear();
+            #[cfg(not(feature = "omit_autovacuum"))]
+            self.vacuum_state.write().page_roles.clear();
         } else {
             turso_assert!(
                 self.dirty_pages.read().is_empty(),
@@ -5682,6 +6278,93 @@ impl Pager {
         Ok(())
     }
 
+    /// Re-encrypt every page of the database with `key`, then use it for all
+    /// further reads and writes.
+    ///
+    /// Every page of the database file is appended to the WAL under the new
+    /// key in a single write transaction. A crash before its commit frame is
+    /// synced leaves the database under the old key; after it, the WAL holds
+    /// every page under the new one. The WAL must have been fully checkpointed
+    /// and nothing else may use the database meanwhile. The pages reach the
+    /// database file at the next checkpoint.
+    pub fn rekey(&self, cipher_mode: CipherMode, key: &EncryptionKey) -> Result<()> {
+        let Some(wal) = self.wal.as_ref() else {
+            return Err(LimboError::InvalidArgument(
+                "cannot rekey a database without a WAL".to_string(),
+            ));
+        };
+        let page_size = self.get_page_size_unchecked().get() as usize;
+        let old_io_ctx = self.io_ctx.read().clone();
+        turso_assert!(
+            old_io_ctx.encryption_context().is_some(),
+            "rekey requires an encryption context"
+        );
+        let mut new_io_ctx = old_io_ctx.clone();
+        new_io_ctx.set_encryption(EncryptionContext::new(cipher_mode, key, page_size)?);
+
+        self.begin_read_tx()?;
+        if let Err(err) = self
+            .io
+            .block(|| self.begin_write_tx(WalAutoActions::empty()))
+        {
+            self.end_read_tx();
+            return Err(err);
+        }
+        let result = self.append_rekeyed_pages(wal.as_ref(), page_size, &old_io_ctx, &new_io_ctx);
+        if result.is_err() {
+            wal.rollback(None);
+        }
+        self.end_write_tx();
+        self.end_read_tx();
+        result?;
+
+        *self.io_ctx.write() = new_io_ctx;
+        wal.set_io_context(self.io_ctx.read().clone());
+        self.clear_page_cache(false);
+        Ok(())
+    }
+
+    /// Append every page of the database file to the empty WAL, decrypted with
+    /// `old_io_ctx` and encrypted with `new_io_ctx`, and commit them.
+    fn append_rekeyed_pages(
+        &self,
+        wal: &dyn Wal,
+        page_size: usize,
+        old_io_ctx: &IOContext,
+        new_io_ctx: &IOContext,
+    ) -> Result<()> {
+        if wal.get_max_frame_in_wal() != 0 {
+            return Err(LimboError::Busy);
+        }
+        let new_ctx = new_io_ctx
+            .encryption_context()
+            .expect("new io context is encrypted");
+        let page_count = self.db_file.size()? as usize / page_size;
+        for page_idx in 1..=page_count {
+            let buf = Arc::new(Buffer::new_temporary(page_size));
+            let c = Completion::new_read(buf.clone(), |_| None);
+            let c = self.db_file.read_page(page_idx, old_io_ctx, c)?;
+            self.io.wait_for_completion(c)?;
+            let page = new_ctx.encrypt_page(buf.as_slice(), page_idx)?;
+            // The last frame commits the transaction.
+            let db_size = if page_idx == page_count {
+                page_count as u64
+            } else {
+                0
+            };
+            wal.write_frame_raw(
+                self.buffer_pool.clone(),
+                page_idx as u64,
+                page_idx as u64,
+                db_size,
+                &page,
+                self.get_sync_type(),
+            )?;
+        }
+        let c = wal.sync(self.get_sync_type())?;
+        self.io.wait_for_completion(c)
+    }
+
     pub fn reset_checksum_context(&self) {
         {
             let mut io_ctx = self.io_ctx.write();
@@ -5691,6 +6374,45 @@ impl Pager {
         wal.set_io_context(self.io_ctx.read().clone())
     }
 
+    /// Checksum every page written from now on and verify every page read.
+    /// See [crate::DatabaseOpts::with_page_checksums].
+    pub fn set_checksum_context(&self) {
+        {
+            let mut io_ctx = self.io_ctx.write();
+            io_ctx.set_checksum(ChecksumContext::new());
+        }
+        let Some(wal) = self.wal.as_ref() else { return };
+        wal.set_io_context(self.io_ctx.read().clone())
+    }
+
+    pub fn is_checksum_ctx_set(&self) -> bool {
+        self.io_ctx.read().checksum_context().is_some()
+    }
+
+    /// Read page `page_idx` as it is stored in the database file, ignoring the
+    /// page cache and the WAL, and without verifying its checksum.
+    pub fn read_db_file_page_unverified(
+        &self,
+        page_idx: usize,
+    ) -> Result<(Arc<Buffer>, Completion)> {
+        let page_size = self.get_page_size_unchecked().get() as usize;
+        let buf = Arc::new(Buffer::new_temporary(page_size));
+        let mut io_ctx = self.io_ctx.read().clone();
+        io_ctx.reset_checksum();
+        let c = Completion::new_read(buf.clone(), |_| None);
+        let c = self.db_file.read_page(page_idx, &io_ctx, c)?;
+        Ok((buf, c))
+    }
+
+    /// Whether the WAL holds a copy of `page_idx` visible to this connection,
+    /// which the next checkpoint writes over the one in the database file.
+    pub fn is_page_in_wal(&self, page_idx: usize) -> Result<bool> {
+        let Some(wal) = self.wal.as_ref() else {
+            return Ok(false);
+        };
+        Ok(wal.find_frame(page_idx as u64, None)?.is_some())
+    }
+
     pub fn set_reserved_space_bytes(&self, value: u8) {
         self.set_reserved_space(value);
     }
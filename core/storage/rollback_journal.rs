@@ -0,0 +1,266 @@
+//! Hot rollback-journal recovery.
+//!
+//! Turso only writes through the WAL, but it opens databases that SQLite left
+//! in one of the rollback-journal modes (DELETE, TRUNCATE, PERSIST) and
+//! converts them to WAL. If SQLite crashed in the middle of a write
+//! transaction, the database file holds a mix of old and new pages, and the
+//! `<db>-journal` file next to it holds the original content of every page the
+//! transaction touched. Such a *hot* journal must be played back before
+//! anything reads the database, exactly like SQLite does on its next open.
+//!
+//! The journal is a sequence of segments, each made of a header padded to the
+//! sector size and followed by `nRec` page records:
+//!
+//! ```text
+//! header: magic(8) nRec(4) cksumInit(4) dbPages(4) sectorSize(4) pageSize(4)
+//! record: pgno(4) page(pageSize) checksum(4)
+//! ```
+//!
+//! A transaction spanning several databases ends the journal with the name of
+//! its super-journal. If that file is gone the transaction committed and the
+//! journal is stale rather than hot.
+
+use crate::io::{File, FileSyncType, DIRECT_IO_ALIGNMENT};
+use crate::storage::pager::Pager;
+use crate::sync::Arc;
+use crate::{Buffer, Completion, CompletionError, LimboError, OpenFlags, Result, IO};
+
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+/// Bytes of the header that are meaningful; the rest of its sector is padding.
+const JOURNAL_HEADER_SIZE: u64 = 28;
+/// Length, checksum and magic that follow a super-journal name.
+const SUPER_JOURNAL_TRAILER_SIZE: u64 = 16;
+
+struct JournalHeader {
+    records: u32,
+    checksum_init: u32,
+    db_pages: u32,
+    sector_size: u32,
+    page_size: u32,
+}
+
+/// Roll back the hot journal of the database at `db_path`, if there is one.
+/// Returns whether a journal was played back.
+///
+/// `db_file` must already be open, and locked unless `flags` contains
+/// [OpenFlags::NoLock], in which case the journal may belong to a live
+/// transaction of another process and is left alone.
+pub(crate) fn recover_hot_journal(
+    io: &Arc<dyn IO>,
+    db_path: &str,
+    db_file: &Arc<dyn File>,
+    flags: OpenFlags,
+) -> Result<bool> {
+    let journal_path = format!("{db_path}-journal");
+    if flags.contains(OpenFlags::NoLock) || !std::path::Path::new(&journal_path).exists() {
+        return Ok(false);
+    }
+    let journal = io.open_file(
+        &journal_path,
+        OpenFlags::ReadOnly | OpenFlags::NoLock,
+        false,
+    )?;
+    let journal_size = journal.size()?;
+    // An empty journal (TRUNCATE mode) or a zeroed header (PERSIST mode) is
+    // what a committed transaction leaves behind.
+    if journal_size < JOURNAL_HEADER_SIZE || db_file.size()? == 0 {
+        return Ok(false);
+    }
+    if read_exact(io, &journal, 0, JOURNAL_MAGIC.len())?.as_slice() != JOURNAL_MAGIC {
+        return Ok(false);
+    }
+    let read_only = flags.contains(OpenFlags::ReadOnly);
+    let playback_end = match super_journal(io, &journal, journal_size)? {
+        Some((name, _)) if !std::path::Path::new(&name).exists() => {
+            tracing::info!("{journal_path} belongs to a committed multi-database transaction");
+            drop(journal);
+            if !read_only {
+                io.remove_file(&journal_path)?;
+            }
+            return Ok(false);
+        }
+        Some((_, start)) => start,
+        None => journal_size,
+    };
+    if read_only {
+        tracing::error!("{db_path} has a hot journal but was opened read-only");
+        return Err(LimboError::ReadOnly);
+    }
+
+    tracing::info!("rolling back hot journal {journal_path}");
+    let pages = playback(io, &journal, playback_end, db_file)?;
+    io.wait_for_completion(db_file.sync(Completion::new_sync(|_| {}), FileSyncType::Fsync)?)?;
+    drop(journal);
+    io.remove_file(&journal_path)?;
+    tracing::info!("restored {pages} pages of {db_path} from its hot journal");
+    Ok(true)
+}
+
+/// Write every valid record of the journal back to the database file and
+/// truncate it to its size before the transaction. Returns the number of pages
+/// restored. Like SQLite, stops at the first torn or invalid record.
+fn playback(
+    io: &Arc<dyn IO>,
+    journal: &Arc<dyn File>,
+    journal_size: u64,
+    db_file: &Arc<dyn File>,
+) -> Result<u64> {
+    let mut offset = 0;
+    let mut first: Option<JournalHeader> = None;
+    let mut restored = 0;
+    'segments: while offset + JOURNAL_HEADER_SIZE <= journal_size {
+        let Some(mut header) = read_header(io, journal, offset)? else {
+            break;
+        };
+        // The page and sector sizes of later headers are not meaningful.
+        let (page_size, sector_size) = match &first {
+            Some(first) => (first.page_size as u64, first.sector_size as u64),
+            None => {
+                if !valid_size(header.page_size, 512) || !valid_size(header.sector_size, 32) {
+                    break;
+                }
+                let page_size = header.page_size as u64;
+                let c = Completion::new_trunc(|_| {});
+                io.wait_for_completion(db_file.truncate(header.db_pages as u64 * page_size, c)?)?;
+                (page_size, header.sector_size as u64)
+            }
+        };
+        let db_pages = first.as_ref().unwrap_or(&header).db_pages;
+        let record_size = page_size + 8;
+        offset += sector_size;
+        if header.records == u32::MAX {
+            header.records = (journal_size.saturating_sub(offset) / record_size) as u32;
+        }
+        let pending_byte_page = Pager::get_pending_byte() as u64 / page_size + 1;
+        for _ in 0..header.records {
+            if offset + record_size > journal_size {
+                break 'segments;
+            }
+            let record = read_exact(io, journal, offset, record_size as usize)?;
+            let record = record.as_slice();
+            let page_no = u32::from_be_bytes(record[..4].try_into().unwrap());
+            let page = &record[4..4 + page_size as usize];
+            let checksum = u32::from_be_bytes(record[4 + page_size as usize..].try_into().unwrap());
+            if page_no == 0
+                || page_no as u64 == pending_byte_page
+                || checksum != page_checksum(header.checksum_init, page)
+            {
+                break 'segments;
+            }
+            if page_no <= db_pages {
+                let c = Completion::new_write(|_| {});
+                // The database file may be open with direct I/O.
+                let buffer = Buffer::new_aligned(page.len(), DIRECT_IO_ALIGNMENT);
+                buffer.as_mut_slice().copy_from_slice(page);
+                let buffer = Arc::new(buffer);
+                io.wait_for_completion(db_file.pwrite(
+                    (page_no as u64 - 1) * page_size,
+                    buffer,
+                    c,
+                )?)?;
+                restored += 1;
+            }
+            offset += record_size;
+        }
+        offset = offset.next_multiple_of(sector_size);
+        first.get_or_insert(header);
+    }
+    Ok(restored)
+}
+
+fn read_header(
+    io: &Arc<dyn IO>,
+    journal: &Arc<dyn File>,
+    offset: u64,
+) -> Result<Option<JournalHeader>> {
+    let buffer = read_exact(io, journal, offset, JOURNAL_HEADER_SIZE as usize)?;
+    let bytes = buffer.as_slice();
+    if bytes[..8] != JOURNAL_MAGIC {
+        return Ok(None);
+    }
+    let field = |i: usize| u32::from_be_bytes(bytes[8 + 4 * i..12 + 4 * i].try_into().unwrap());
+    Ok(Some(JournalHeader {
+        records: field(0),
+        checksum_init: field(1),
+        db_pages: field(2),
+        sector_size: field(3),
+        page_size: field(4),
+    }))
+}
+
+/// The super-journal name recorded at the end of the journal and the offset
+/// where its record starts, if there is one.
+fn super_journal(
+    io: &Arc<dyn IO>,
+    journal: &Arc<dyn File>,
+    journal_size: u64,
+) -> Result<Option<(String, u64)>> {
+    let Some(trailer_start) = journal_size.checked_sub(SUPER_JOURNAL_TRAILER_SIZE) else {
+        return Ok(None);
+    };
+    let trailer = read_exact(
+        io,
+        journal,
+        trailer_start,
+        SUPER_JOURNAL_TRAILER_SIZE as usize,
+    )?;
+    let trailer = trailer.as_slice();
+    if trailer[8..] != JOURNAL_MAGIC {
+        return Ok(None);
+    }
+    let len = u32::from_be_bytes(trailer[..4].try_into().unwrap()) as u64;
+    let checksum = u32::from_be_bytes(trailer[4..8].try_into().unwrap());
+    // The name is preceded by a 4 byte pseudo page number.
+    if len == 0 || len + 4 > trailer_start {
+        return Ok(None);
+    }
+    let name = read_exact(io, journal, trailer_start - len, len as usize)?;
+    let name = name.as_slice();
+    // SQLite sums the name as `char`s, which are signed on the platforms it
+    // is usually built for.
+    let sum = name
+        .iter()
+        .fold(0u32, |sum, &byte| sum.wrapping_add(byte as i8 as u32));
+    if sum != checksum {
+        return Ok(None);
+    }
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+    if name.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((
+        String::from_utf8_lossy(name).into_owned(),
+        trailer_start - len - 4,
+    )))
+}
+
+/// SQLite's journal checksum: the nonce plus every 200th byte of the page,
+/// walking back from its end.
+fn page_checksum(checksum_init: u32, page: &[u8]) -> u32 {
+    (1..=page.len().saturating_sub(1) / 200)
+        .map(|i| page[page.len() - 200 * i] as u32)
+        .fold(checksum_init, u32::wrapping_add)
+}
+
+fn valid_size(size: u32, min: u32) -> bool {
+    size.is_power_of_two() && (min..=65536).contains(&size)
+}
+
+fn read_exact(io: &Arc<dyn IO>, file: &Arc<dyn File>, pos: u64, len: usize) -> Result<Arc<Buffer>> {
+    let buffer = Arc::new(Buffer::new_temporary(len));
+    let c = Completion::new_read(
+        buffer.clone(),
+        move |res: Result<(Arc<Buffer>, i32), CompletionError>| {
+            let Ok((_, bytes_read)) = res else {
+                return None;
+            };
+            (bytes_read as usize != len).then_some(CompletionError::ShortRead {
+                page_idx: 0,
+                expected: len,
+                actual: bytes_read as usize,
+            })
+        },
+    );
+    io.wait_for_completion(file.pread(pos, c)?)?;
+    Ok(buffer)
+}
@@ -2552,11 +2552,12 @@ impl OngoingCheckpoint {
     }
 
     #[inline]
-    /// Whether or not new reads should be issued during checkpoint processing.
-    fn should_issue_reads(&self) -> bool {
+    /// Whether or not new reads should be issued during checkpoint processing,
+    /// given the round budget from [crate::storage::io_scheduler::IoScheduler::checkpoint_round_pages].
+    fn should_issue_reads(&self, round_pages: usize) -> bool {
         (self.current_page as usize) < self.pages_to_checkpoint.len()
             && !self.pending_writes.is_full()
-            && self.inflight_reads.len() < MAX_INFLIGHT_READS
+            && self.inflight_reads.len() < round_pages
     }
 
     #[inline]
@@ -2570,8 +2571,9 @@ impl OngoingCheckpoint {
 
     #[inline]
     /// Whether we should flush an exisitng batch of writes and begin concurrently aggregating a new one.
-    fn should_flush_batch(&self) -> bool {
+    fn should_flush_batch(&self, round_pages: usize) -> bool {
         self.pending_writes.is_full()
+            || self.pending_writes.len() >= round_pages
             || (self.pending_writes.len() >= MIN_BATCH_LEN_FOR_FLUSH
                 && self.pending_writes.avg_run_len() >= MIN_AVG_RUN_FOR_FLUSH)
             || ((self.current_page as usize) >= self.pages_to_checkpoint.len()
@@ -4777,6 +4779,14 @@ impl WalFile {
                         .iter_latest_frames(oc_min_frame, oc_max_frame);
                     // sort by frame_id for read locality
                     to_checkpoint.sort_unstable_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0)));
+                    // Let the file system reserve the space the backfill
+                    // needs, like SQLITE_FCNTL_SIZE_HINT. Only a hint.
+                    if let Some(max_page) = to_checkpoint.iter().map(|(page, _)| *page).max() {
+                        let page_size = pager.get_page_size_unchecked().get() as u64;
+                        if let Err(e) = pager.db_file.allocate(max_page * page_size) {
+                            tracing::debug!("checkpoint: failed to preallocate database file: {e}");
+                        }
+                    }
                     {
                         let mut oc = self.ongoing_checkpoint.write();
                         oc.pages_to_checkpoint = to_checkpoint;
@@ -4824,8 +4834,13 @@ impl WalFile {
                         return Err(LimboError::CompletionError(e));
                     }
                     let epoch = self.coordination.checkpoint_epoch();
+                    // Shrinks while other connections have page reads in flight, so
+                    // that they don't queue behind a full round of checkpoint I/O.
+                    let round_pages = pager
+                        .io_scheduler()
+                        .checkpoint_round_pages(MAX_INFLIGHT_READS);
                     // Issue reads until we hit limits
-                    'inner: while ongoing_chkpt.should_issue_reads() {
+                    'inner: while ongoing_chkpt.should_issue_reads(round_pages) {
                         let (page_id, target_frame) = {
                             ongoing_chkpt.pages_to_checkpoint[ongoing_chkpt.current_page as usize]
                         };
@@ -4862,7 +4877,7 @@ impl WalFile {
 
                     // Start a write if batch is ready and we're not at write limit
                     let should_flush = ongoing_chkpt.inflight_writes.len() < MAX_INFLIGHT_WRITES
-                        && ongoing_chkpt.should_flush_batch();
+                        && ongoing_chkpt.should_flush_batch(round_pages);
                     if should_flush {
                         let batch_map = ongoing_chkpt.pending_writes.take();
                         if !batch_map.is_empty() {
@@ -5649,9 +5664,18 @@ impl WalFileShared {
             }
             Err(e) => return Err(e),
         };
-        Ok(OpenSharedWal::Build(sqlite3_ondisk::BuildSharedWal::begin(
-            &file,
-        )?))
+        match sqlite3_ondisk::BuildSharedWal::begin(&file) {
+            Ok(build) => Ok(OpenSharedWal::Build(build)),
+            // Backends that open files lazily, like `HttpRangeIO`, only find out
+            // that the WAL is missing once its size is asked for.
+            Err(LimboError::CompletionError(CompletionError::IOError(
+                std::io::ErrorKind::NotFound,
+                _,
+            ))) if flags.contains(crate::OpenFlags::ReadOnly) => {
+                Ok(OpenSharedWal::Noop(WalFileShared::new_noop()))
+            }
+            Err(e) => Err(e),
+        }
     }
 
     pub fn is_initialized(&self) -> Result<bool> {
@@ -0,0 +1,483 @@
+//! Index advisor: what-if analysis of a workload against hypothetical indexes.
+//!
+//! Similar in spirit to SQLite's `expert` extension. Every SELECT, UPDATE and
+//! DELETE of the workload is planned against the current schema, candidate
+//! indexes are derived from the columns its WHERE, join and ORDER BY clauses
+//! constrain, and the workload is planned again once per candidate with that
+//! candidate added to a private copy of the schema. The candidate indexes are
+//! never created and hold no data, so the optimizer costs them with its default
+//! estimates (or ANALYZE statistics of the table, when present).
+//!
+//! UPDATE and DELETE are costed as the `SELECT` that finds the rows they touch.
+//! Costs are the optimizer's estimates for the top-level join of each
+//! statement; subqueries are planned but their costs are not added up.
+
+use crate::alloc::TryClone;
+use crate::schema::{BTreeTable, Index, Schema};
+use crate::sync::Arc;
+use crate::translate::emitter::Resolver;
+use crate::translate::optimizer::{expr_references_table, optimize_plan};
+use crate::translate::plan::{Plan, QueryDestination, SelectPlan};
+use crate::translate::select::prepare_select_plan;
+use crate::util::quote_identifier;
+use crate::vdbe::builder::{ProgramBuilder, ProgramBuilderOpts, QueryMode};
+use crate::{Connection, Result, SymbolTable};
+use turso_parser::ast::{self, Cmd, SortOrder};
+use turso_parser::parser::Parser;
+
+/// An index suggested by [Connection::suggest_indexes].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSuggestion {
+    /// `CREATE INDEX` statement that creates the suggested index.
+    pub create_sql: String,
+    pub table_name: String,
+    pub columns: Vec<String>,
+    /// Estimated cost of the whole workload with the current schema.
+    pub cost_before: f64,
+    /// Estimated cost of the whole workload if this index existed.
+    pub cost_after: f64,
+    /// Positions in the workload of the statements that get cheaper with this index.
+    pub improved_statements: Vec<usize>,
+}
+
+impl IndexSuggestion {
+    /// How much the estimated workload cost drops with this index.
+    pub fn cost_delta(&self) -> f64 {
+        self.cost_before - self.cost_after
+    }
+}
+
+/// A candidate index: the table it is on and its key columns, as positions in the table.
+struct Candidate {
+    table: Arc<BTreeTable>,
+    columns: Vec<(usize, SortOrder)>,
+}
+
+impl Candidate {
+    fn same_key(&self, other: &Candidate) -> bool {
+        self.table.name == other.table.name && self.columns == other.columns
+    }
+}
+
+pub(crate) fn suggest_indexes(
+    connection: &Arc<Connection>,
+    workload: &[impl AsRef<str>],
+) -> Result<Vec<IndexSuggestion>> {
+    connection.maybe_update_schema();
+    let schema = connection.schema.read().clone();
+    let syms = connection.syms.read();
+
+    let mut statements = Vec::with_capacity(workload.len());
+    for (position, sql) in workload.iter().enumerate() {
+        let (cmd, _) = connection.parse_sql(sql.as_ref())?;
+        let Some(Cmd::Stmt(stmt) | Cmd::Explain(stmt) | Cmd::ExplainQueryPlan(stmt)) = cmd else {
+            continue;
+        };
+        if let Some(select) = plannable_select(stmt)? {
+            statements.push((position, select));
+        }
+    }
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut costs_before = Vec::with_capacity(statements.len());
+    for (_, select) in statements.iter() {
+        let mut plan = prepare(&schema, select.clone(), connection, &syms)?;
+        collect_candidates(&plan, &schema, &mut candidates);
+        costs_before.push(optimized_cost(&mut plan, &schema, connection, &syms)?);
+    }
+    let cost_before: f64 = costs_before.iter().sum();
+
+    let mut suggestions = Vec::new();
+    for candidate in candidates {
+        let index_name = hypothetical_index_name(&schema, &candidate);
+        let column_names = candidate
+            .columns
+            .iter()
+            .map(|(pos, _)| {
+                candidate.table.columns()[*pos]
+                    .name
+                    .clone()
+                    .expect("BTree table columns are named")
+            })
+            .collect::<Vec<_>>();
+        let create_sql = format!(
+            "CREATE INDEX {} ON {}({})",
+            quote_identifier(&index_name),
+            quote_identifier(&candidate.table.name),
+            column_names
+                .iter()
+                .zip(candidate.columns.iter())
+                .map(|(name, (_, order))| match order {
+                    SortOrder::Asc => quote_identifier(name),
+                    SortOrder::Desc => format!("{} DESC", quote_identifier(name)),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let index = Index::from_sql(&syms, &create_sql, 0, &candidate.table)?;
+        let mut what_if = schema.as_ref().try_clone()?;
+        what_if.add_index(Arc::new(index))?;
+
+        let mut cost_after = 0.0;
+        let mut improved_statements = Vec::new();
+        for ((position, select), before) in statements.iter().zip(costs_before.iter()) {
+            let mut plan = prepare(&what_if, select.clone(), connection, &syms)?;
+            let after = optimized_cost(&mut plan, &what_if, connection, &syms)?;
+            if after < *before {
+                improved_statements.push(*position);
+            }
+            cost_after += after;
+        }
+        if cost_after < cost_before {
+            suggestions.push(IndexSuggestion {
+                create_sql,
+                table_name: candidate.table.name.clone(),
+                columns: column_names,
+                cost_before,
+                cost_after,
+                improved_statements,
+            });
+        }
+    }
+    suggestions.sort_by(|a, b| b.cost_delta().total_cmp(&a.cost_delta()));
+    Ok(suggestions)
+}
+
+/// Turn a workload statement into the SELECT whose plan decides its cost.
+fn plannable_select(stmt: ast::Stmt) -> Result<Option<ast::Select>> {
+    let (with, tbl_name, where_clause) = match stmt {
+        ast::Stmt::Select(select) => return Ok(Some(select)),
+        ast::Stmt::Delete {
+            with,
+            tbl_name,
+            where_clause,
+            ..
+        } => (with, tbl_name, where_clause),
+        ast::Stmt::Update(update) => (update.with, update.tbl_name, update.where_clause),
+        _ => return Ok(None),
+    };
+    let mut sql = String::new();
+    if let Some(with) = with {
+        sql.push_str(&format!("{with} "));
+    }
+    sql.push_str(&format!("SELECT 1 FROM {tbl_name}"));
+    if let Some(where_clause) = where_clause {
+        sql.push_str(&format!(" WHERE {where_clause}"));
+    }
+    let mut parser = Parser::new(sql.as_bytes());
+    match parser.next_cmd()? {
+        Some(Cmd::Stmt(ast::Stmt::Select(select))) => Ok(Some(select)),
+        _ => Ok(None),
+    }
+}
+
+fn resolver<'a>(
+    schema: &'a Schema,
+    connection: &'a Arc<Connection>,
+    syms: &'a SymbolTable,
+) -> Resolver<'a> {
+    Resolver::new(
+        schema,
+        connection.database_schemas(),
+        &connection.temp.database,
+        connection.attached_databases(),
+        syms,
+        connection.experimental_custom_types_enabled(),
+        connection.get_dqs_dml().into(),
+        connection.dialect(),
+    )
+}
+
+fn program_builder() -> ProgramBuilder {
+    ProgramBuilder::new(
+        QueryMode::ExplainQueryPlan,
+        None,
+        ProgramBuilderOpts::new(1, 32, 2),
+    )
+}
+
+fn prepare(
+    schema: &Schema,
+    select: ast::Select,
+    connection: &Arc<Connection>,
+    syms: &SymbolTable,
+) -> Result<Plan> {
+    let resolver = resolver(schema, connection, syms);
+    prepare_select_plan(
+        select,
+        &resolver,
+        &mut program_builder(),
+        &[],
+        QueryDestination::ResultRows,
+        connection,
+    )
+}
+
+fn optimized_cost(
+    plan: &mut Plan,
+    schema: &Schema,
+    connection: &Arc<Connection>,
+    syms: &SymbolTable,
+) -> Result<f64> {
+    let resolver = resolver(schema, connection, syms);
+    optimize_plan(&mut program_builder(), plan, &resolver)?;
+    let cost = |select: &SelectPlan| select.estimated_cost.unwrap_or(0.0);
+    Ok(match plan {
+        Plan::Select(select) => cost(select),
+        Plan::CompoundSelect {
+            left, right_most, ..
+        } => left.iter().map(|(select, _)| cost(select)).sum::<f64>() + cost(right_most),
+        Plan::Delete(_) | Plan::Update(_) => 0.0,
+    })
+}
+
+fn collect_candidates(plan: &Plan, schema: &Schema, candidates: &mut Vec<Candidate>) {
+    match plan {
+        Plan::Select(select) => collect_select_candidates(select, schema, candidates),
+        Plan::CompoundSelect {
+            left, right_most, ..
+        } => {
+            for (select, _) in left {
+                collect_select_candidates(select, schema, candidates);
+            }
+            collect_select_candidates(right_most, schema, candidates);
+        }
+        Plan::Delete(_) | Plan::Update(_) => {}
+    }
+}
+
+/// Derive candidates for every table of a SELECT, in the same shape SQLite's expert uses:
+/// the columns constrained by equality (including join conditions), followed either by
+/// one range-constrained column or by the ORDER BY columns.
+fn collect_select_candidates(
+    select: &SelectPlan,
+    schema: &Schema,
+    candidates: &mut Vec<Candidate>,
+) {
+    for joined_table in select.joined_tables() {
+        let Some(table) = joined_table.table.btree() else {
+            continue;
+        };
+        // Only tables of the main database can get a hypothetical index.
+        if !schema
+            .get_btree_table(&table.name)
+            .is_some_and(|main_table| Arc::ptr_eq(&main_table, &table))
+        {
+            continue;
+        }
+        let table_id = joined_table.internal_id;
+        let column_of = |expr: &ast::Expr| match expr {
+            ast::Expr::Column {
+                table,
+                column,
+                is_rowid_alias: false,
+                ..
+            } if *table == table_id => Some(*column),
+            _ => None,
+        };
+        let mut eq_columns = Vec::new();
+        let mut range_columns = Vec::new();
+        for term in select.where_clause.iter() {
+            let (constrained, other, is_eq) = match &term.expr {
+                ast::Expr::Binary(lhs, op, rhs) => {
+                    let is_eq = match op {
+                        ast::Operator::Equals | ast::Operator::Is => true,
+                        ast::Operator::Less
+                        | ast::Operator::LessEquals
+                        | ast::Operator::Greater
+                        | ast::Operator::GreaterEquals => false,
+                        _ => continue,
+                    };
+                    match (column_of(lhs), column_of(rhs)) {
+                        (Some(column), _) => (column, vec![rhs.as_ref()], is_eq),
+                        (None, Some(column)) => (column, vec![lhs.as_ref()], is_eq),
+                        (None, None) => continue,
+                    }
+                }
+                ast::Expr::InList {
+                    lhs,
+                    not: false,
+                    rhs,
+                } => match column_of(lhs) {
+                    Some(column) => (column, rhs.iter().map(|e| e.as_ref()).collect(), true),
+                    None => continue,
+                },
+                ast::Expr::Between {
+                    lhs,
+                    not: false,
+                    start,
+                    end,
+                } => match column_of(lhs) {
+                    Some(column) => (column, vec![start.as_ref(), end.as_ref()], false),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if other
+                .iter()
+                .any(|expr| expr_references_table(expr, table_id))
+            {
+                continue;
+            }
+            let columns = if is_eq {
+                &mut eq_columns
+            } else {
+                &mut range_columns
+            };
+            if !columns.contains(&constrained) {
+                columns.push(constrained);
+            }
+        }
+        range_columns.retain(|column| !eq_columns.contains(column));
+
+        let eq_prefix = eq_columns
+            .iter()
+            .map(|column| (*column, SortOrder::Asc))
+            .collect::<Vec<_>>();
+        let mut shapes = Vec::new();
+        if let Some(range_column) = range_columns.first() {
+            let mut columns = eq_prefix.clone();
+            columns.push((*range_column, SortOrder::Asc));
+            shapes.push(columns);
+        } else if !eq_prefix.is_empty() {
+            shapes.push(eq_prefix.clone());
+        }
+        if select.joined_tables().len() == 1 && !select.order_by.is_empty() {
+            let order_columns = select
+                .order_by
+                .iter()
+                .map(|(expr, order, _)| column_of(expr).map(|column| (column, *order)))
+                .collect::<Option<Vec<_>>>();
+            if let Some(order_columns) = order_columns {
+                let mut columns = eq_prefix;
+                for (column, order) in order_columns {
+                    if !columns.iter().any(|(existing, _)| *existing == column) {
+                        columns.push((column, order));
+                    }
+                }
+                shapes.push(columns);
+            }
+        }
+
+        for columns in shapes {
+            let candidate = Candidate {
+                table: table.clone(),
+                columns,
+            };
+            if !candidates
+                .iter()
+                .any(|existing| existing.same_key(&candidate))
+                && !already_indexed(schema, &candidate)
+            {
+                candidates.push(candidate);
+            }
+        }
+    }
+}
+
+/// Whether an existing index starts with the candidate's columns in the same order.
+fn already_indexed(schema: &Schema, candidate: &Candidate) -> bool {
+    schema.get_indices(&candidate.table.name).any(|index| {
+        index.where_clause.is_none()
+            && index.index_method.is_none()
+            && index.columns.len() >= candidate.columns.len()
+            && index.columns.iter().zip(candidate.columns.iter()).all(
+                |(index_column, (pos, order))| {
+                    index_column.expr.is_none()
+                        && index_column.pos_in_table == *pos
+                        && index_column.order == *order
+                },
+            )
+    })
+}
+
+fn hypothetical_index_name(schema: &Schema, candidate: &Candidate) -> String {
+    let base = format!(
+        "{}_idx_{}",
+        candidate.table.name,
+        candidate
+            .columns
+            .iter()
+            .map(|(pos, _)| candidate.table.columns()[*pos]
+                .name
+                .as_deref()
+                .unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("_")
+    );
+    let mut name = base.clone();
+    let mut suffix = 1;
+    while schema.get_object_type(&name).is_some() {
+        suffix += 1;
+        name = format!("{base}_{suffix}");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::MemoryIO;
+    use crate::sync::Arc;
+    use crate::{Database, SqliteDialect};
+
+    fn connection(schema: &[&str]) -> Arc<crate::Connection> {
+        let io = Arc::new(MemoryIO::new());
+        let db = Database::open_file(io, ":memory:", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        for sql in schema {
+            conn.execute(sql).unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn test_suggests_index_for_equality_and_range() {
+        let conn = connection(&["CREATE TABLE t(a, b, c)"]);
+        let suggestions = conn
+            .suggest_indexes(&[
+                "SELECT c FROM t WHERE a = ? AND b > 10",
+                "INSERT INTO t VALUES (1, 2, 3)",
+                "DELETE FROM t WHERE a = 1 AND b < 3",
+            ])
+            .unwrap();
+        let best = &suggestions[0];
+        assert_eq!(best.table_name, "t");
+        assert_eq!(best.columns, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(best.create_sql, "CREATE INDEX t_idx_a_b ON t(a, b)");
+        assert_eq!(best.improved_statements, vec![0, 2]);
+        assert!(best.cost_after < best.cost_before);
+        // Nothing was created.
+        assert!(conn.schema.read().get_indices("t").next().is_none());
+    }
+
+    #[test]
+    fn test_existing_index_is_not_suggested_again() {
+        let conn = connection(&["CREATE TABLE t(a, b)", "CREATE INDEX t_a ON t(a, b)"]);
+        let suggestions = conn
+            .suggest_indexes(&["SELECT * FROM t WHERE a = 1"])
+            .unwrap();
+        assert!(suggestions.is_empty(), "{suggestions:?}");
+    }
+
+    #[test]
+    fn test_suggests_index_for_join_and_order_by() {
+        let conn = connection(&["CREATE TABLE p(pid, name)", "CREATE TABLE c(p_id, x)"]);
+        let suggestions = conn
+            .suggest_indexes(&[
+                "SELECT p.name, c.x FROM p JOIN c ON c.p_id = p.pid",
+                "SELECT x FROM c ORDER BY x DESC",
+            ])
+            .unwrap();
+        assert!(
+            suggestions
+                .iter()
+                .any(|s| s.columns == ["p_id"] || s.columns == ["pid"]),
+            "{suggestions:?}"
+        );
+        let order_by = suggestions
+            .iter()
+            .find(|s| s.create_sql == "CREATE INDEX c_idx_x ON c(x DESC)")
+            .expect("ORDER BY column should be suggested");
+        assert_eq!(order_by.improved_statements, vec![1]);
+    }
+}
@@ -605,7 +605,8 @@ fn emit_add_virtual_column_validation(
         .columns_mut()
         .retain(|c| !c.is_virtual_generated());
     let original_table = Arc::new(original_table);
-    let cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(original_table.clone()));
+    let cursor = program.alloc_table_cursor(None, original_table.clone());
+    let cursor_id = cursor.id();
     program.emit_insn(Insn::OpenRead {
         cursor_id,
         root_page: original_table.root_page,
@@ -621,10 +622,7 @@ fn emit_add_virtual_column_validation(
     program.preassign_label_to_next_insn(loop_start);
 
     let rowid_reg = program.alloc_register();
-    program.emit_insn(Insn::RowId {
-        cursor_id,
-        dest: rowid_reg,
-    });
+    program.emit_rowid(cursor, rowid_reg);
 
     let layout = resolved_table.column_layout()?;
     let base_dest_reg = program.alloc_registers(layout.column_count());
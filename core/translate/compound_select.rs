@@ -1,5 +1,5 @@
 use crate::alloc::TursoIteratorExt;
-use crate::schema::{Index, IndexColumn, PseudoCursorType};
+use crate::schema::{Index, IndexColumn};
 use crate::sync::Arc;
 use crate::translate::collate::get_collseq_from_expr;
 use crate::translate::emitter::{
@@ -10,7 +10,7 @@ use crate::translate::expr::translate_expr;
 use crate::translate::order_by::{custom_type_comparator, sorter_insert};
 use crate::translate::plan::{CompoundOrderByKey, Plan, QueryDestination, SelectPlan};
 use crate::translate::result_row::emit_columns_to_destination;
-use crate::vdbe::builder::{CursorType, ProgramBuilder};
+use crate::vdbe::builder::{CursorType, ProgramBuilder, SorterLoopLabels};
 use crate::vdbe::insn::Insn;
 use crate::{emit_explain, LimboError};
 use tracing::instrument;
@@ -884,7 +884,7 @@ fn emit_compound_order_by(
         .try_collect()?;
     let sorter_column_count = order_by.len() + 1 + non_dedup_count;
 
-    let sort_cursor = program.alloc_cursor_id(CursorType::Sorter);
+    let sort_cursor = program.alloc_sorter_cursor();
     // Resolve custom type comparators for ORDER BY columns (e.g. numeric(10,2) needs
     // NumericLt to sort correctly instead of default blob/text comparison).
     let comparators: crate::alloc::Vec<Option<crate::vdbe::insn::SortComparatorType>> = order_by
@@ -902,7 +902,7 @@ fn emit_compound_order_by(
         .chain(std::iter::once(None))
         .try_collect()?;
     program.emit_insn(Insn::SorterOpen {
-        cursor_id: sort_cursor,
+        cursor_id: sort_cursor.id(),
         columns: order_collations_nulls.len(),
         order_collations_nulls,
         comparators,
@@ -974,7 +974,7 @@ fn emit_compound_order_by(
         program,
         sorter_regs,
         sorter_column_count,
-        sort_cursor,
+        sort_cursor.id(),
         reg_sorter_data,
     );
 
@@ -1060,75 +1060,58 @@ fn emit_compound_order_by(
     // Sort and emit results
     emit_explain!(program, false, "USE SORTER FOR ORDER BY".to_owned());
 
-    let pseudo_cursor = program.alloc_cursor_id(CursorType::Pseudo(PseudoCursorType {
-        column_count: sorter_column_count,
-    }));
+    let pseudo_cursor = program.alloc_pseudo_cursor(sorter_column_count);
     program.emit_insn(Insn::OpenPseudo {
-        cursor_id: pseudo_cursor,
+        cursor_id: pseudo_cursor.id(),
         content_reg: reg_sorter_data,
         num_fields: sorter_column_count,
     });
 
-    let sort_loop_start = program.allocate_label();
-    let sort_loop_next = program.allocate_label();
-    let sort_loop_end = program.allocate_label();
+    let sort_loop = SorterLoopLabels::new(program);
 
     // Skip output entirely if LIMIT is 0
     if let Some(limit_reg) = limit_ctx {
         program.emit_insn(Insn::IfNot {
             reg: limit_reg,
-            target_pc: sort_loop_end,
+            target_pc: sort_loop.end,
             jump_if_null: false,
         });
     }
 
-    program.emit_insn(Insn::SorterSort {
-        cursor_id: sort_cursor,
-        pc_if_empty: sort_loop_end,
-    });
-
-    program.preassign_label_to_next_insn(sort_loop_start);
-
-    // Apply OFFSET
-    if let Some(offset_r) = offset_reg {
-        program.emit_insn(Insn::IfPos {
-            reg: offset_r,
-            target_pc: sort_loop_next,
-            decrement_by: 1,
-        });
-    }
-
-    program.emit_insn(Insn::SorterData {
-        cursor_id: sort_cursor,
-        dest_reg: reg_sorter_data,
-        pseudo_cursor,
-    });
-
-    // Read result columns from the pseudo cursor, remapping from sorter order to SELECT order
-    let result_start_reg = program.alloc_registers(num_result_cols);
-    for (col_idx, &(sorter_idx, _deduplicated)) in
-        remappings.iter().enumerate().take(num_result_cols)
-    {
-        program.emit_column_or_rowid(pseudo_cursor, sorter_idx, result_start_reg + col_idx);
-    }
-
-    // Emit to real destination
-    emit_columns_to_destination(program, real_destination, result_start_reg, num_result_cols)?;
+    program.emit_sorter_loop(sort_cursor, sort_loop, |program| {
+        // Apply OFFSET
+        if let Some(offset_r) = offset_reg {
+            program.emit_insn(Insn::IfPos {
+                reg: offset_r,
+                target_pc: sort_loop.next,
+                decrement_by: 1,
+            });
+        }
 
-    // Apply LIMIT
-    if let Some(limit_reg) = limit_ctx {
-        program.emit_insn(Insn::DecrJumpZero {
-            reg: limit_reg,
-            target_pc: sort_loop_end,
-        });
-    }
+        program.emit_sorter_data(sort_cursor, pseudo_cursor, reg_sorter_data);
+
+        // Read result columns from the pseudo cursor, remapping from sorter order to SELECT order
+        let result_start_reg = program.alloc_registers(num_result_cols);
+        for (col_idx, &(sorter_idx, _deduplicated)) in
+            remappings.iter().enumerate().take(num_result_cols)
+        {
+            program.emit_column_or_rowid(
+                pseudo_cursor.id(),
+                sorter_idx,
+                result_start_reg + col_idx,
+            );
+        }
 
-    program.preassign_label_to_next_insn(sort_loop_next);
-    program.emit_insn(Insn::SorterNext {
-        cursor_id: sort_cursor,
-        pc_if_next: sort_loop_start,
-    });
-    program.preassign_label_to_next_insn(sort_loop_end);
+        // Emit to real destination
+        emit_columns_to_destination(program, real_destination, result_start_reg, num_result_cols)?;
 
-    Ok(Some(result_start_reg))
+        // Apply LIMIT
+        if let Some(limit_reg) = limit_ctx {
+            program.emit_insn(Insn::DecrJumpZero {
+                reg: limit_reg,
+                target_pc: sort_loop.end,
+            });
+        }
+        Ok(Some(result_start_reg))
+    })
 }
@@ -321,7 +321,7 @@ pub fn emit_fk_child_decrement_on_delete(
             let parent_tbl = resolver
                 .with_schema(database_id, |s| s.get_btree_table(&fk_ref.fk.parent_table))
                 .expect("parent btree");
-            let pcur = open_read_table(program, &parent_tbl, database_id);
+            let pcur = open_read_table(program, &parent_tbl, database_id).id();
 
             let (pos, col) = child_tbl.get_column(&fk_ref.fk.child_columns[0]).unwrap();
             let val = if col.is_rowid_alias() {
@@ -372,7 +372,7 @@ pub fn emit_fk_child_decrement_on_delete(
                 .with_schema(database_id, |s| s.get_btree_table(&fk_ref.fk.parent_table))
                 .expect("parent btree");
             let idx = fk_ref.parent_unique_index.as_ref().expect("unique index");
-            let icur = open_read_index(program, idx, database_id);
+            let icur = open_read_index(program, idx, database_id).id();
 
             // Build probe from current child row
             let n = fk_ref.fk.child_columns.len();
@@ -71,6 +71,58 @@ pub(super) fn resolve_qualified_on_ref(
     Ok(None)
 }
 
+/// The virtual table in the FROM clause that `name` refers to, unless `name` is
+/// also a column of one of the tables.
+fn virtual_table_by_name<'t>(
+    referenced_tables: &'t TableReferences,
+    name: &str,
+) -> Option<&'t crate::translate::plan::JoinedTable> {
+    let name = normalize_ident(name);
+    let joined_tables = referenced_tables.joined_tables();
+    let is_column = joined_tables.iter().any(|t| {
+        t.table.columns().iter().any(|c| {
+            c.name
+                .as_ref()
+                .is_some_and(|n| n.eq_ignore_ascii_case(&name))
+        })
+    });
+    if is_column {
+        return None;
+    }
+    joined_tables
+        .iter()
+        .find(|t| matches!(t.table, Table::Virtual(_)) && t.identifier.eq_ignore_ascii_case(&name))
+}
+
+/// The column that `name MATCH ...` refers to when `name` is not a column but a virtual
+/// table in the FROM clause: the table's first hidden column.
+fn virtual_table_match_column(
+    referenced_tables: &TableReferences,
+    name: &str,
+) -> Option<(TableInternalId, usize)> {
+    let table = virtual_table_by_name(referenced_tables, name)?;
+    let col_idx = table.table.columns().iter().position(|c| c.hidden())?;
+    Some((table.internal_id, col_idx))
+}
+
+/// The column that `func(name)` refers to when `name` is not a column but a virtual
+/// table in the FROM clause that has a hidden column called `func`.
+fn virtual_table_function_column(
+    referenced_tables: &TableReferences,
+    func: &str,
+    name: &str,
+) -> Option<(TableInternalId, usize)> {
+    let table = virtual_table_by_name(referenced_tables, name)?;
+    let func = normalize_ident(func);
+    let col_idx = table.table.columns().iter().position(|c| {
+        c.hidden()
+            && c.name
+                .as_ref()
+                .is_some_and(|n| n.eq_ignore_ascii_case(&func))
+    })?;
+    Some((table.internal_id, col_idx))
+}
+
 /// Rewrite ast::Expr in place, binding Column references/rewriting Expr::Id -> Expr::Column
 /// using the provided TableReferences, and replacing anonymous parameters with internal named
 /// ones
@@ -683,8 +735,62 @@ pub fn bind_and_rewrite_expr<'a>(
                 // cycles on invalid queries, and keeps the translate_expr match
                 // arms focused on code generation.
                 Expr::FunctionCall { name, args, .. } => {
+                    // Auxiliary functions of virtual tables, like `bm25(docs)`, read the
+                    // hidden column of the same name. With more arguments, as in
+                    // `highlight(docs, 0, '<b>', '</b>')`, the column takes the table's
+                    // place and the function is called with it.
+                    let target = match (args.first(), referenced_tables.as_deref()) {
+                        (Some(arg), Some(tables)) => match arg.as_ref() {
+                            Expr::Id(id) => {
+                                virtual_table_function_column(tables, name.as_str(), id.as_str())
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if let (Some((table_id, col_idx)), Some(tables)) =
+                        (target, referenced_tables.as_deref_mut())
+                    {
+                        tables.mark_column_used(table_id, col_idx);
+                        let column = Expr::Column {
+                            database: None,
+                            table: table_id,
+                            column: col_idx,
+                            is_rowid_alias: false,
+                        };
+                        if args.len() == 1 {
+                            *expr = column;
+                            return Ok(WalkControl::Continue);
+                        }
+                        *args[0] = column;
+                    }
                     validate_custom_type_function_call(name.as_str(), args, resolver)?;
                 }
+                // As with SQLite's full-text modules, `docs MATCH 'query'` searches the
+                // virtual table `docs`: the table name stands for its first hidden column.
+                Expr::Like {
+                    lhs,
+                    op: ast::LikeOperator::Match,
+                    ..
+                } => {
+                    let target = match (lhs.as_ref(), referenced_tables.as_deref()) {
+                        (Expr::Id(id), Some(tables)) => {
+                            virtual_table_match_column(tables, id.as_str())
+                        }
+                        _ => None,
+                    };
+                    if let (Some((table_id, col_idx)), Some(tables)) =
+                        (target, referenced_tables.as_deref_mut())
+                    {
+                        **lhs = Expr::Column {
+                            database: None,
+                            table: table_id,
+                            column: col_idx,
+                            is_rowid_alias: false,
+                        };
+                        tables.mark_column_used(table_id, col_idx);
+                    }
+                }
                 _ => {}
             }
             Ok(WalkControl::Continue)
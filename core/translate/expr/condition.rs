@@ -274,20 +274,24 @@ pub fn translate_condition_expr(
             crate::bail_parse_error!("RAISE in WHERE clause is not supported");
         }
         ast::Expr::Between { .. } => {
-            let between_result_reg = program.alloc_register();
-            translate_between_expr(
-                program,
-                Some(referenced_tables),
-                expr.clone(),
-                between_result_reg,
-                resolver,
-            )?;
-            emit_cond_jump(program, condition_metadata, between_result_reg);
+            program.with_temp_registers(1, |program, between_result_reg| -> Result<()> {
+                translate_between_expr(
+                    program,
+                    Some(referenced_tables),
+                    expr.clone(),
+                    between_result_reg,
+                    resolver,
+                )?;
+                emit_cond_jump(program, condition_metadata, between_result_reg);
+                Ok(())
+            })?;
         }
         ast::Expr::Variable(_) => {
-            let reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), expr, reg, resolver)?;
-            emit_cond_jump(program, condition_metadata, reg);
+            program.with_temp_registers(1, |program, reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), expr, reg, resolver)?;
+                emit_cond_jump(program, condition_metadata, reg);
+                Ok(())
+            })?;
         }
         ast::Expr::Name(_) => {
             crate::bail_parse_error!("Name as a direct predicate in WHERE clause is not supported");
@@ -354,9 +358,11 @@ pub fn translate_condition_expr(
                 ast::Expr::Literal(ast::Literal::True) | ast::Expr::Literal(ast::Literal::False)
             ) =>
         {
-            let reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), expr, reg, resolver)?;
-            emit_cond_jump(program, condition_metadata, reg);
+            program.with_temp_registers(1, |program, reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), expr, reg, resolver)?;
+                emit_cond_jump(program, condition_metadata, reg);
+                Ok(())
+            })?;
         }
         // Handle IS NULL/IS NOT NULL in conditions using IsNull/NotNull opcodes.
         // "a IS NULL" is parsed as Binary(a, Is, Null), but we need to use the IsNull opcode
@@ -364,36 +370,40 @@ pub fn translate_condition_expr(
         ast::Expr::Binary(e1, ast::Operator::Is, e2)
             if matches!(e2.as_ref(), ast::Expr::Literal(ast::Literal::Null)) =>
         {
-            let cur_reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), e1, cur_reg, resolver)?;
-            if condition_metadata.jump_if_condition_is_true {
-                program.emit_insn(Insn::IsNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_true,
-                });
-            } else {
-                program.emit_insn(Insn::NotNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_false,
-                });
-            }
+            program.with_temp_registers(1, |program, cur_reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), e1, cur_reg, resolver)?;
+                if condition_metadata.jump_if_condition_is_true {
+                    program.emit_insn(Insn::IsNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_true,
+                    });
+                } else {
+                    program.emit_insn(Insn::NotNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_false,
+                    });
+                }
+                Ok(())
+            })?;
         }
         ast::Expr::Binary(e1, ast::Operator::IsNot, e2)
             if matches!(e2.as_ref(), ast::Expr::Literal(ast::Literal::Null)) =>
         {
-            let cur_reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), e1, cur_reg, resolver)?;
-            if condition_metadata.jump_if_condition_is_true {
-                program.emit_insn(Insn::NotNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_true,
-                });
-            } else {
-                program.emit_insn(Insn::IsNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_false,
-                });
-            }
+            program.with_temp_registers(1, |program, cur_reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), e1, cur_reg, resolver)?;
+                if condition_metadata.jump_if_condition_is_true {
+                    program.emit_insn(Insn::NotNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_true,
+                    });
+                } else {
+                    program.emit_insn(Insn::IsNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_false,
+                    });
+                }
+                Ok(())
+            })?;
         }
         ast::Expr::Binary(e1, op, e2) => {
             // Check if either operand has a custom type with a matching operator
@@ -429,9 +439,11 @@ pub fn translate_condition_expr(
         | ast::Expr::Column { .. }
         | ast::Expr::RowId { .. }
         | ast::Expr::Case { .. } => {
-            let reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), expr, reg, resolver)?;
-            emit_cond_jump(program, condition_metadata, reg);
+            program.with_temp_registers(1, |program, reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), expr, reg, resolver)?;
+                emit_cond_jump(program, condition_metadata, reg);
+                Ok(())
+            })?;
         }
 
         ast::Expr::InList { lhs, not, rhs } => {
@@ -484,23 +496,25 @@ pub fn translate_condition_expr(
             }
         }
         ast::Expr::Like { not, .. } => {
-            let cur_reg = program.alloc_register();
-            translate_like_base(program, Some(referenced_tables), expr, cur_reg, resolver)?;
-            if !*not {
-                emit_cond_jump(program, condition_metadata, cur_reg);
-            } else if condition_metadata.jump_if_condition_is_true {
-                program.emit_insn(Insn::IfNot {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_true,
-                    jump_if_null: false,
-                });
-            } else {
-                program.emit_insn(Insn::If {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_false,
-                    jump_if_null: true,
-                });
-            }
+            program.with_temp_registers(1, |program, cur_reg| -> Result<()> {
+                translate_like_base(program, Some(referenced_tables), expr, cur_reg, resolver)?;
+                if !*not {
+                    emit_cond_jump(program, condition_metadata, cur_reg);
+                } else if condition_metadata.jump_if_condition_is_true {
+                    program.emit_insn(Insn::IfNot {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_true,
+                        jump_if_null: false,
+                    });
+                } else {
+                    program.emit_insn(Insn::If {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_false,
+                        jump_if_null: true,
+                    });
+                }
+                Ok(())
+            })?;
         }
         ast::Expr::Parenthesized(exprs) => {
             if exprs.len() == 1 {
@@ -518,34 +532,38 @@ pub fn translate_condition_expr(
             }
         }
         ast::Expr::NotNull(expr) => {
-            let cur_reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), expr, cur_reg, resolver)?;
-            if condition_metadata.jump_if_condition_is_true {
-                program.emit_insn(Insn::NotNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_true,
-                });
-            } else {
-                program.emit_insn(Insn::IsNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_false,
-                });
-            }
+            program.with_temp_registers(1, |program, cur_reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), expr, cur_reg, resolver)?;
+                if condition_metadata.jump_if_condition_is_true {
+                    program.emit_insn(Insn::NotNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_true,
+                    });
+                } else {
+                    program.emit_insn(Insn::IsNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_false,
+                    });
+                }
+                Ok(())
+            })?;
         }
         ast::Expr::IsNull(expr) => {
-            let cur_reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), expr, cur_reg, resolver)?;
-            if condition_metadata.jump_if_condition_is_true {
-                program.emit_insn(Insn::IsNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_true,
-                });
-            } else {
-                program.emit_insn(Insn::NotNull {
-                    reg: cur_reg,
-                    target_pc: condition_metadata.jump_target_when_false,
-                });
-            }
+            program.with_temp_registers(1, |program, cur_reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), expr, cur_reg, resolver)?;
+                if condition_metadata.jump_if_condition_is_true {
+                    program.emit_insn(Insn::IsNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_true,
+                    });
+                } else {
+                    program.emit_insn(Insn::NotNull {
+                        reg: cur_reg,
+                        target_pc: condition_metadata.jump_target_when_false,
+                    });
+                }
+                Ok(())
+            })?;
         }
         ast::Expr::Unary(_, _) => {
             // This is an inefficient implementation for op::NOT, because translate_expr() will emit an Insn::Not,
@@ -553,9 +571,11 @@ pub fn translate_condition_expr(
             // like to emit the negation instruction Insn::Not at all, since we could just emit the "opposite" jump instruction
             // directly. However, using translate_expr() directly simplifies our conditional jump code for unary expressions,
             // and we'd rather be correct than maximally efficient, for now.
-            let expr_reg = program.alloc_register();
-            translate_expr(program, Some(referenced_tables), expr, expr_reg, resolver)?;
-            emit_cond_jump(program, condition_metadata, expr_reg);
+            program.with_temp_registers(1, |program, expr_reg| -> Result<()> {
+                translate_expr(program, Some(referenced_tables), expr, expr_reg, resolver)?;
+                emit_cond_jump(program, condition_metadata, expr_reg);
+                Ok(())
+            })?;
         }
         ast::Expr::Default => {
             crate::bail_parse_error!("DEFAULT is only valid in INSERT VALUES");
@@ -1,6 +1,24 @@
 use super::*;
 use crate::alloc::TursoIteratorExt;
 
+/// Emit `func` with no arguments, which evaluates it for the current time.
+fn emit_current_time(
+    program: &mut ProgramBuilder,
+    func: ScalarFunc,
+    target_register: usize,
+) -> Result<usize> {
+    program.emit_insn(Insn::Function {
+        constant_mask: 0,
+        start_reg: target_register,
+        dest: target_register,
+        func: FuncCtx {
+            func: Func::Scalar(func),
+            arg_count: 0,
+        },
+    });
+    Ok(target_register)
+}
+
 /// Emit literal values - shared between regular and RETURNING expression evaluation
 pub fn emit_literal(
     program: &mut ProgramBuilder,
@@ -74,35 +92,11 @@ pub fn emit_literal(
             });
             Ok(target_register)
         }
-        ast::Literal::CurrentDate => {
-            program.emit_insn(Insn::String8 {
-                value: datetime::exec_date::<&[_; 0], std::slice::Iter<'_, Value>, &Value>(&[])
-                    .to_string(),
-                dest: target_register,
-            });
-            Ok(target_register)
-        }
-        ast::Literal::CurrentTime => {
-            program.emit_insn(Insn::String8 {
-                value: datetime::exec_time::<&[_; 0], std::slice::Iter<'_, Value>, &Value>(&[])
-                    .to_string(),
-                dest: target_register,
-            });
-            Ok(target_register)
-        }
+        // Evaluated when the statement runs, with the clock of the connection's IO
+        ast::Literal::CurrentDate => emit_current_time(program, ScalarFunc::Date, target_register),
+        ast::Literal::CurrentTime => emit_current_time(program, ScalarFunc::Time, target_register),
         ast::Literal::CurrentTimestamp => {
-            program.emit_insn(
-                Insn::String8 {
-                    value: datetime::exec_datetime_full::<
-                        &[_; 0],
-                        std::slice::Iter<'_, Value>,
-                        &Value,
-                    >(&[])
-                    .to_string(),
-                    dest: target_register,
-                },
-            );
-            Ok(target_register)
+            emit_current_time(program, ScalarFunc::DateTime, target_register)
         }
     }
 }
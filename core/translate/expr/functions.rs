@@ -1,4 +1,5 @@
 use super::*;
+use crate::translate::emitter::emit_columns_and_dependencies;
 use crate::translate::sequence::emit_sequence_descriptor_literals;
 use crate::vdbe::builder::CursorType;
 use crate::vdbe::insn::{to_u16, InsertFlags, RegisterOrLiteral};
@@ -185,6 +186,26 @@ pub(super) fn wrap_eval_jump_expr_zero_or_null(
     program.preassign_label_to_next_insn(if_true_label);
 }
 
+/// Split an optionally schema-qualified object name given as a string, such as
+/// `'aux.my_seq'`, into the id of its database and its normalized name.
+fn resolve_qualified_name(resolver: &Resolver, raw: &str) -> Result<(usize, String)> {
+    let Some((schema, name)) = raw.split_once('.') else {
+        return Ok((crate::MAIN_DB_ID, normalize_ident(raw)));
+    };
+    let schema_norm = normalize_ident(schema);
+    let db_id = match schema_norm.as_str() {
+        "main" => crate::MAIN_DB_ID,
+        "temp" => crate::TEMP_DB_ID,
+        _ => resolver
+            .get_attached_database(&schema_norm)
+            .map(|(idx, _)| idx)
+            .ok_or_else(|| {
+                LimboError::InvalidArgument(format!("no such database: {schema_norm}"))
+            })?,
+    };
+    Ok((db_id, normalize_ident(name)))
+}
+
 /// Translate nextval/setval with disk-only backing-table state.
 ///
 /// ## Persistence design
@@ -221,23 +242,7 @@ pub(super) fn translate_sequence_function(
     let is_nextval = matches!(&func_ctx.func, Func::Scalar(ScalarFunc::NextVal));
 
     let seq_name_raw = extract_string_literal(&args[0])?;
-    let (database_id, normalized_name) = if let Some((schema, name)) = seq_name_raw.split_once('.')
-    {
-        let schema_norm = normalize_ident(schema);
-        let db_id = match schema_norm.as_str() {
-            "main" => crate::MAIN_DB_ID,
-            "temp" => crate::TEMP_DB_ID,
-            _ => resolver
-                .get_attached_database(&schema_norm)
-                .map(|(idx, _)| idx)
-                .ok_or_else(|| {
-                    LimboError::InvalidArgument(format!("no such database: {schema_norm}"))
-                })?,
-        };
-        (db_id, normalize_ident(name))
-    } else {
-        (crate::MAIN_DB_ID, normalize_ident(&seq_name_raw))
-    };
+    let (database_id, normalized_name) = resolve_qualified_name(resolver, &seq_name_raw)?;
 
     let backing_table_name =
         crate::translate::sequence::sequence_backing_table_name(&normalized_name);
@@ -378,3 +383,106 @@ pub(super) fn translate_sequence_function(
 
     Ok(target_register)
 }
+
+/// Translate `hash_table('t')`: scan the table and fold the [Value::row_hash] of
+/// every row into the sum that `turso-dbhash` computes for it, so two tables
+/// with the same rows get the same digest whatever their rowids and order.
+///
+/// The rows are the ones `SELECT *` returns, generated columns included. The
+/// accumulator is the first argument of the `Function` instruction and the
+/// columns of the row follow it.
+pub(super) fn translate_hash_table(
+    program: &mut ProgramBuilder,
+    args: &[Box<ast::Expr>],
+    resolver: &Resolver,
+    target_register: usize,
+) -> Result<usize> {
+    let table_name_raw = extract_string_literal(&args[0])?;
+    let (database_id, table_name) = resolve_qualified_name(resolver, &table_name_raw)?;
+    let Some(table) = resolver.with_schema(database_id, |s| s.get_btree_table(&table_name)) else {
+        crate::bail_parse_error!("no such table: {}", table_name_raw);
+    };
+    let columns: Vec<usize> = table
+        .columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, col)| !col.hidden())
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let schema_cookie = resolver.with_schema(database_id, |s| s.schema_version);
+    program.begin_read_on_database(database_id, schema_cookie)?;
+
+    let acc_reg = program.alloc_registers(1 + columns.len());
+    program.emit_insn(Insn::Integer {
+        value: 0,
+        dest: acc_reg,
+    });
+    let root_page = table.root_page;
+    let cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(table.clone()));
+    program.emit_insn(Insn::OpenRead {
+        cursor_id,
+        root_page,
+        db: database_id,
+    });
+    let end_label = program.allocate_label();
+    let loop_label = program.allocate_label();
+    program.emit_insn(Insn::Rewind {
+        cursor_id,
+        pc_if_empty: end_label,
+    });
+    program.preassign_label_to_next_insn(loop_label);
+    if table.has_virtual_columns() {
+        let rowid_reg = program.alloc_register();
+        program.emit_insn(Insn::RowId {
+            cursor_id,
+            dest: rowid_reg,
+        });
+        let dml_ctx = emit_columns_and_dependencies(
+            program,
+            &table,
+            cursor_id,
+            rowid_reg,
+            columns.iter().copied(),
+            resolver,
+        )?;
+        for (i, &idx) in columns.iter().enumerate() {
+            emit_table_column_for_dml(
+                program,
+                cursor_id,
+                dml_ctx.clone(),
+                &table.columns()[idx],
+                idx,
+                acc_reg + 1 + i,
+                resolver,
+                &table,
+            )?;
+        }
+    } else {
+        for (i, &idx) in columns.iter().enumerate() {
+            program.emit_column_or_rowid(cursor_id, idx, acc_reg + 1 + i);
+        }
+    }
+    program.emit_insn(Insn::Function {
+        constant_mask: 0,
+        start_reg: acc_reg,
+        dest: acc_reg,
+        func: FuncCtx {
+            func: Func::Scalar(ScalarFunc::HashTable),
+            arg_count: 1 + columns.len(),
+        },
+    });
+    program.emit_insn(Insn::Next {
+        cursor_id,
+        pc_if_next: loop_label,
+    });
+    program.preassign_label_to_next_insn(end_label);
+    program.emit_insn(Insn::Close { cursor_id });
+    program.emit_insn(Insn::Copy {
+        src_reg: acc_reg,
+        dst_reg: target_register,
+        extra_amount: 0,
+    });
+
+    Ok(target_register)
+}
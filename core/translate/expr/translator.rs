@@ -1670,6 +1670,21 @@ pub fn translate_expr(
                             target_register,
                             func_ctx,
                         ),
+                        ScalarFunc::RowHash => {
+                            let args = expect_arguments_min!(args, 1, srf);
+                            translate_function(
+                                program,
+                                args,
+                                referenced_tables,
+                                resolver,
+                                target_register,
+                                func_ctx,
+                            )
+                        }
+                        ScalarFunc::HashTable => {
+                            let args = expect_arguments_exact!(args, 1, srf);
+                            translate_hash_table(program, args, resolver, target_register)
+                        }
                         ScalarFunc::GetByte | ScalarFunc::SetByte => translate_function(
                             program,
                             args,
@@ -47,9 +47,11 @@ pub fn as_binary_components(
             }
             Ok(Some((lhs.as_ref(), (*operator).into(), rhs.as_ref())))
         }
-        ast::Expr::Like { lhs, not, rhs, .. } => Ok(Some((
+        ast::Expr::Like {
+            lhs, not, op, rhs, ..
+        } => Ok(Some((
             lhs.as_ref(),
-            ConstraintOperator::Like { not: *not },
+            ConstraintOperator::Like { op: *op, not: *not },
             rhs.as_ref(),
         ))),
         _ => Ok(None),
@@ -10,7 +10,7 @@ use crate::{
     sync::{Arc, OnceLock, Weak},
     translate::{collate::CollationSeq, emitter::Resolver, planner::ROWID_STRS},
     vdbe::{
-        builder::{CursorType, DmlColumnContext, QueryMode},
+        builder::{CursorType, DmlColumnContext, IndexCursor, QueryMode, TableCursor},
         insn::{CmpInsFlags, Insn, Subprogram},
         BranchOffset, PreparedProgram,
     },
@@ -247,24 +247,28 @@ fn emit_parent_key_change_probes(
     Ok(deferred_new_key_probe)
 }
 
-/// Open a read cursor on an index and return its cursor id.
+/// Open a read cursor on an index and return it.
 #[inline]
-pub fn open_read_index(program: &mut ProgramBuilder, idx: &Arc<Index>, db: usize) -> usize {
-    let icur = program.alloc_cursor_id(CursorType::BTreeIndex(idx.clone()));
+pub fn open_read_index(program: &mut ProgramBuilder, idx: &Arc<Index>, db: usize) -> IndexCursor {
+    let icur = program.alloc_index_cursor(None, idx.clone());
     program.emit_insn(Insn::OpenRead {
-        cursor_id: icur,
+        cursor_id: icur.id(),
         root_page: idx.root_page,
         db,
     });
     icur
 }
 
-/// Open a read cursor on a table and return its cursor id.
+/// Open a read cursor on a table and return it.
 #[inline]
-pub fn open_read_table(program: &mut ProgramBuilder, tbl: &Arc<BTreeTable>, db: usize) -> usize {
-    let tcur = program.alloc_cursor_id(CursorType::BTreeTable(tbl.clone()));
+pub fn open_read_table(
+    program: &mut ProgramBuilder,
+    tbl: &Arc<BTreeTable>,
+    db: usize,
+) -> TableCursor {
+    let tcur = program.alloc_table_cursor(None, tbl.clone());
     program.emit_insn(Insn::OpenRead {
-        cursor_id: tcur,
+        cursor_id: tcur.id(),
         root_page: tbl.root_page,
         db,
     });
@@ -330,7 +334,7 @@ fn copy_context_columns_with_affinity(
 /// Issue an index probe using `Found`/`NotFound` and route to `on_found`/`on_not_found`.
 pub fn index_probe<F, G>(
     program: &mut ProgramBuilder,
-    icur: usize,
+    icur: IndexCursor,
     record_reg: usize,
     num_regs: usize,
     mut on_found: F,
@@ -344,7 +348,7 @@ where
     let lbl_join = program.allocate_label();
 
     program.emit_insn(Insn::Found {
-        cursor_id: icur,
+        cursor_id: icur.id(),
         target_pc: lbl_found,
         record_reg,
         num_regs,
@@ -362,7 +366,9 @@ where
 
     // Join & close once
     program.preassign_label_to_next_insn(lbl_join);
-    program.emit_insn(Insn::Close { cursor_id: icur });
+    program.emit_insn(Insn::Close {
+        cursor_id: icur.id(),
+    });
     Ok(())
 }
 
@@ -372,7 +378,7 @@ where
 /// example to ignore the row currently being updated in a self-referential FK.
 fn index_scan_match_any<F>(
     program: &mut ProgramBuilder,
-    icur: usize,
+    icur: IndexCursor,
     probe_start: usize,
     num_regs: usize,
     self_exclude_rowid: Option<usize>,
@@ -384,7 +390,7 @@ where
     let done = program.allocate_label();
     program.emit_insn(Insn::SeekGE {
         is_index: true,
-        cursor_id: icur,
+        cursor_id: icur.id(),
         start_reg: probe_start,
         num_regs,
         target_pc: done,
@@ -394,7 +400,7 @@ where
     let loop_top = program.allocate_label();
     program.preassign_label_to_next_insn(loop_top);
     program.emit_insn(Insn::IdxGT {
-        cursor_id: icur,
+        cursor_id: icur.id(),
         start_reg: probe_start,
         num_regs,
         target_pc: done,
@@ -403,10 +409,7 @@ where
     let next_row = program.allocate_label();
     if let Some(parent_rowid) = self_exclude_rowid {
         let child_rowid = program.alloc_register();
-        program.emit_insn(Insn::IdxRowId {
-            cursor_id: icur,
-            dest: child_rowid,
-        });
+        program.emit_idx_rowid(icur, child_rowid);
         program.emit_insn(Insn::Eq {
             lhs: child_rowid,
             rhs: parent_rowid,
@@ -420,12 +423,14 @@ where
 
     program.preassign_label_to_next_insn(next_row);
     program.emit_insn(Insn::Next {
-        cursor_id: icur,
+        cursor_id: icur.id(),
         pc_if_next: loop_top,
     });
 
     program.preassign_label_to_next_insn(done);
-    program.emit_insn(Insn::Close { cursor_id: icur });
+    program.emit_insn(Insn::Close {
+        cursor_id: icur.id(),
+    });
     Ok(())
 }
 
@@ -464,7 +469,7 @@ where
     let ccur = open_read_table(program, child_tbl, database_id);
     let done = program.allocate_label();
     program.emit_insn(Insn::Rewind {
-        cursor_id: ccur,
+        cursor_id: ccur.id(),
         pc_if_empty: done,
     });
 
@@ -479,7 +484,7 @@ where
             .ok_or_else(|| LimboError::InternalError(format!("child col {cname} missing")))?;
         let tmp = program.alloc_register();
         program.emit_insn(Insn::Column {
-            cursor_id: ccur,
+            cursor_id: ccur.id(),
             column: pos,
             dest: tmp,
             default: None,
@@ -509,10 +514,7 @@ where
     if let Some(parent_rowid) = self_exclude_rowid {
         let child_rowid = program.alloc_register();
         let skip = program.allocate_label();
-        program.emit_insn(Insn::RowId {
-            cursor_id: ccur,
-            dest: child_rowid,
-        });
+        program.emit_rowid(ccur, child_rowid);
         program.emit_insn(Insn::Eq {
             lhs: child_rowid,
             rhs: parent_rowid,
@@ -528,12 +530,14 @@ where
 
     program.preassign_label_to_next_insn(next_row);
     program.emit_insn(Insn::Next {
-        cursor_id: ccur,
+        cursor_id: ccur.id(),
         pc_if_next: loop_top,
     });
 
     program.preassign_label_to_next_insn(done);
-    program.emit_insn(Insn::Close { cursor_id: ccur });
+    program.emit_insn(Insn::Close {
+        cursor_id: ccur.id(),
+    });
     Ok(())
 }
 
@@ -1100,7 +1104,7 @@ pub fn emit_fk_child_update_counters(
                     let parent_tbl = resolver
                         .with_schema(database_id, |s| s.get_btree_table(&fk_ref.fk.parent_table))
                         .expect("parent btree");
-                    let pcur = open_read_table(program, &parent_tbl, database_id);
+                    let pcur = open_read_table(program, &parent_tbl, database_id).id();
 
                     // first FK col is the rowid value
                     let rid = program.alloc_register();
@@ -1236,7 +1240,7 @@ pub fn emit_fk_child_update_counters(
             let parent_tbl = resolver
                 .with_schema(database_id, |s| s.get_btree_table(&fk_ref.fk.parent_table))
                 .expect("parent btree");
-            let pcur = open_read_table(program, &parent_tbl, database_id);
+            let pcur = open_read_table(program, &parent_tbl, database_id).id();
 
             // Take the first child column value from NEW image
             let (i_child, col_child) = child_tbl.get_column(&fk_ref.fk.child_columns[0]).unwrap();
@@ -2428,7 +2432,7 @@ pub fn emit_fk_drop_table_check(
     let collect_done = program.allocate_label();
 
     program.emit_insn(Insn::Rewind {
-        cursor_id: parent_cur,
+        cursor_id: parent_cur.id(),
         pc_if_empty: collect_done,
     });
 
@@ -2437,23 +2441,20 @@ pub fn emit_fk_drop_table_check(
 
     // Get parent rowid and add to RowSet
     let parent_rowid_reg = program.alloc_register();
-    program.emit_insn(Insn::RowId {
-        cursor_id: parent_cur,
-        dest: parent_rowid_reg,
-    });
+    program.emit_rowid(parent_cur, parent_rowid_reg);
     program.emit_insn(Insn::RowSetAdd {
         rowset_reg,
         value_reg: parent_rowid_reg,
     });
 
     program.emit_insn(Insn::Next {
-        cursor_id: parent_cur,
+        cursor_id: parent_cur.id(),
         pc_if_next: collect_loop,
     });
 
     program.preassign_label_to_next_insn(collect_done);
     program.emit_insn(Insn::Close {
-        cursor_id: parent_cur,
+        cursor_id: parent_cur.id(),
     });
 
     // For each parent rowid, check/execute FK actions
@@ -2543,7 +2544,7 @@ pub fn emit_fk_drop_table_check(
         )?;
 
         // Scan child table for matching rows
-        let child_cur = open_read_table(program, child_tbl, database_id);
+        let child_cur = open_read_table(program, child_tbl, database_id).id();
         let child_done = program.allocate_label();
 
         program.emit_insn(Insn::Rewind {
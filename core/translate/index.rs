@@ -929,7 +929,7 @@ fn resolve_sorted_columns_with_resolver(
                 .expect("resolved index columns vector was preallocated to cols.len()");
             continue;
         }
-        if !validate_index_expression(unwrapped_expr, table) {
+        if !validate_index_expression(unwrapped_expr, table, resolver) {
             crate::bail_parse_error!("Error: invalid expression in CREATE INDEX: {}", sc.expr);
         }
         resolved
@@ -1017,7 +1017,11 @@ fn resolve_index_column<'a>(
 /// Expressions in CREATE INDEX statements may not use subqueries.
 /// Additionally, a standalone string literal is interpreted as a column name (for backwards
 /// compatibility with SQLite), not as a string literal. It is rejected if no such column exists.
-fn validate_index_expression(expr: &Expr, table: &BTreeTable) -> bool {
+///
+/// Extension functions are accepted when they were registered as deterministic. Without a
+/// resolver (re-reading an index that was already validated from the schema table) calls
+/// that are not built-in functions are trusted.
+fn validate_index_expression(expr: &Expr, table: &BTreeTable, resolver: Option<&Resolver>) -> bool {
     // A top-level string literal would have been handled by resolve_index_column().
     // If we get here with a string literal, it means the column doesn't exist.
     // (SQLite interprets standalone string literals as column names for backwards compat.)
@@ -1037,8 +1041,16 @@ fn validate_index_expression(expr: &Expr, table: &BTreeTable) -> bool {
     let is_tbl = |ns: &str| normalize_ident(ns).eq_ignore_ascii_case(&tbl_norm);
     let is_deterministic_fn = |name: &str, args: &[Box<Expr>]| {
         let n = normalize_ident(name);
-        Func::resolve_function(&n, args.len())
-            .is_ok_and(|f| f.is_some_and(|f| is_deterministic_schema_function_call(&f, args)))
+        match Func::resolve_function(&n, args.len()) {
+            Ok(Some(f)) => is_deterministic_schema_function_call(&f, args),
+            Ok(None) => resolver.is_none_or(|resolver| {
+                resolver
+                    .symbol_table
+                    .resolve_function(&n, args.len())
+                    .is_some_and(|f| f.is_deterministic())
+            }),
+            Err(_) => false,
+        }
     };
 
     let mut ok = true;
@@ -1257,8 +1269,8 @@ pub fn translate_drop_index(
 
     // We're going to use this cursor to search through sqlite_schema
     let sqlite_table = resolver.schema().get_btree_table(SQLITE_TABLEID).unwrap();
-    let sqlite_schema_cursor_id =
-        program.alloc_cursor_id(CursorType::BTreeTable(sqlite_table.clone()));
+    let sqlite_schema_cursor = program.alloc_table_cursor(None, sqlite_table.clone());
+    let sqlite_schema_cursor_id = sqlite_schema_cursor.id();
 
     // Open sqlite_schema for writing
     program.emit_insn(Insn::OpenWrite {
@@ -1302,10 +1314,7 @@ pub fn translate_drop_index(
         collation: program.curr_collation(),
     });
 
-    program.emit_insn(Insn::RowId {
-        cursor_id: sqlite_schema_cursor_id,
-        dest: row_id_reg,
-    });
+    program.emit_rowid(sqlite_schema_cursor, row_id_reg);
 
     let label_once_end = program.allocate_label();
     program.emit_insn(Insn::Once {
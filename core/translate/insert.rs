@@ -3959,7 +3959,7 @@ pub fn emit_fk_child_insert_checks(
             .with_schema(database_id, |s| s.get_btree_table(&fk_ref.fk.parent_table))
             .expect("parent btree");
         if fk_ref.parent_uses_rowid {
-            let pcur = open_read_table(program, &parent_tbl, database_id);
+            let pcur = open_read_table(program, &parent_tbl, database_id).id();
 
             // first child col carries rowid
             let (i_child, col_child) = child_tbl.get_column(&fk_ref.fk.child_columns[0]).unwrap();
@@ -4237,7 +4237,7 @@ pub fn emit_parent_side_fk_decrement_on_insert(
         });
 
         if let Some(ix) = idx {
-            let icur = open_read_index(program, ix, database_id);
+            let icur = open_read_index(program, ix, database_id).id();
             // Copy key into probe regs and apply child-index affinities
             let probe_start = program.alloc_registers(n_cols);
             for i in 0..n_cols {
@@ -4275,7 +4275,7 @@ pub fn emit_parent_side_fk_decrement_on_insert(
             program.preassign_label_to_next_insn(skip);
         } else {
             // fallback scan :(
-            let ccur = open_read_table(program, child_tbl, database_id);
+            let ccur = open_read_table(program, child_tbl, database_id).id();
             let done = program.allocate_label();
             program.emit_insn(Insn::Rewind {
                 cursor_id: ccur,
@@ -7,6 +7,7 @@
 //! a SELECT statement will be translated into a sequence of instructions that
 //! will read rows from the database and filter them according to a WHERE clause.
 
+pub(crate) mod advisor;
 pub(crate) mod aggregation;
 pub(crate) mod alter;
 pub(crate) mod analyze;
@@ -103,6 +104,7 @@ pub fn translate(
         ProgramBuilderOpts::new(1, 32, 2),
     ));
     program.set_mvcc_enabled(connection.mvcc_enabled());
+    program.set_poison_temp_registers(connection.get_poison_temp_registers());
 
     program.prologue();
     let mut resolver = Resolver::new(
@@ -188,6 +190,10 @@ pub fn translate_inner(
         bail_parse_error!("Cannot execute write statement in query_only mode")
     }
 
+    if is_write && connection.is_read_only_tx() {
+        bail_parse_error!("Cannot execute write statement in a read-only transaction")
+    }
+
     let is_select = matches!(stmt, ast::Stmt::Select { .. });
 
     match stmt {
@@ -477,6 +483,16 @@ pub fn translate_inner(
     Ok(())
 }
 
+/// Whether `stmt` can be translated and run without modifying a database,
+/// judged from its syntax so that it can be checked before translation.
+pub(crate) fn stmt_is_read_only(stmt: &ast::Stmt) -> bool {
+    match stmt {
+        ast::Stmt::Select(_) => true,
+        ast::Stmt::Pragma { name, body } => pragma::pragma_is_query(name, body.as_ref()),
+        _ => false,
+    }
+}
+
 fn stmt_kind(stmt: &ast::Stmt) -> &'static str {
     match stmt {
         ast::Stmt::AlterTable(_) => "alter_table",
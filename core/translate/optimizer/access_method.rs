@@ -2,7 +2,7 @@ use crate::sync::Arc;
 use rustc_hash::FxHashMap as HashMap;
 use smallvec::SmallVec;
 
-use turso_ext::{ConstraintInfo, ConstraintUsage, ResultCode};
+use turso_ext::{ConstraintInfo, ConstraintOp, ConstraintUsage, OrderByInfo, ResultCode};
 use turso_parser::ast::{self, SortOrder, TableInternalId};
 
 use crate::alloc::{TursoIteratorExt, TursoTryWithCapacityExt, TursoVecExt};
@@ -38,7 +38,7 @@ use super::{
         estimate_cost_for_scan_or_seek, estimate_index_cost, estimate_rows_per_seek, AnalyzeCtx,
         Cost, IndexInfo,
     },
-    join::JoinPlanningContext,
+    join::{JoinPlanningContext, VtabLimit},
     multi_index::{
         consider_multi_index_intersection, consider_multi_index_union, MultiIndexBranchParams,
     },
@@ -104,6 +104,9 @@ pub enum AccessMethodParams {
         /// Information returned by the virtual table's `best_index` method
         /// describing how each constraint will be used.
         constraint_usages: Vec<ConstraintUsage>,
+        /// Whether the virtual table returns its rows in the order of the entire
+        /// [OrderTarget] it was offered.
+        order_by_consumed: bool,
     },
     /// FROM-subquery scan. Coroutine-backed scans run forwards; materialized
     /// subqueries may also be scanned backwards when their intrinsic order
@@ -739,9 +742,13 @@ pub fn find_best_access_method_for_join_order(
             params,
         ),
         Table::Virtual(vtab) => find_best_access_method_for_vtab(
+            rhs_table,
             vtab,
             &rhs_constraints.constraints,
             join_order,
+            planning_context.maybe_order_target,
+            planning_context.vtab_limit,
+            where_clause,
             input_cardinality,
             base_row_count,
             params,
@@ -929,19 +936,77 @@ fn find_best_access_method_for_btree(
     Ok(Some(best_access_method))
 }
 
+/// The ORDER BY terms offered to a virtual table's `best_index`: all of the
+/// [OrderTarget]'s terms if they are plain columns of that table, otherwise none.
+fn vtab_order_by(
+    rhs_table: &JoinedTable,
+    maybe_order_target: Option<&OrderTarget>,
+) -> Vec<OrderByInfo> {
+    let Some(order_target) = maybe_order_target.filter(|target| !target.is_extremum()) else {
+        return Vec::new();
+    };
+    order_target
+        .columns
+        .iter()
+        .map(|column| match column.target {
+            ColumnTarget::Column(idx) if column.table_id == rhs_table.internal_id => {
+                Some(OrderByInfo {
+                    column_index: idx as u32,
+                    desc: column.order == SortOrder::Desc,
+                })
+            }
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+#[allow(clippy::too_many_arguments)]
 fn find_best_access_method_for_vtab(
+    rhs_table: &JoinedTable,
     vtab: &VirtualTable,
     constraints: &[Constraint],
     join_order: &[JoinOrderMember],
+    maybe_order_target: Option<&OrderTarget>,
+    vtab_limit: Option<VtabLimit>,
+    where_clause: &[WhereTerm],
     input_cardinality: f64,
     base_row_count: RowCountEstimate,
     params: &CostModelParams,
 ) -> Result<Option<AccessMethod>> {
-    let vtab_constraints = convert_to_vtab_constraint(constraints, join_order)?;
-
-    // TODO: get proper order_by information to pass to the vtab.
-    // maybe encode more info on t_ctx? we need: [col_idx , is_descending]
-    let best_index_result = vtab.best_index(&vtab_constraints, &[]);
+    let mut vtab_constraints = convert_to_vtab_constraint(constraints, join_order)?;
+    let order_by = vtab_order_by(rhs_table, maybe_order_target);
+    // As in SQLite, the LIMIT and OFFSET are only offered along with every WHERE and
+    // ORDER BY term, so that the table can tell whether it may apply them itself.
+    let offer_limit = vtab_limit.filter(|vtab_limit| {
+        (!vtab_limit.ordered || !order_by.is_empty())
+            && where_clause
+                .iter()
+                .enumerate()
+                .filter(|(_, term)| !term.consumed)
+                .all(|(pos, _)| {
+                    vtab_constraints
+                        .iter()
+                        .any(|c| constraints[c.index].where_clause_pos.0 == pos)
+                })
+    });
+    if let Some(vtab_limit) = offer_limit {
+        let limit_ops: &[ConstraintOp] = if vtab_limit.has_offset {
+            &[ConstraintOp::Limit, ConstraintOp::Offset]
+        } else {
+            &[ConstraintOp::Limit]
+        };
+        for &op in limit_ops {
+            vtab_constraints.push(ConstraintInfo {
+                column_index: 0,
+                op,
+                usable: true,
+                // not a WHERE term: build_vtab_scan_op takes the LIMIT or OFFSET instead
+                index: usize::MAX,
+            });
+        }
+    }
+    let best_index_result = vtab.best_index(&vtab_constraints, &order_by);
 
     match best_index_result {
         Ok(index_info) => {
@@ -965,6 +1030,7 @@ fn find_best_access_method_for_vtab(
                     idx_str: index_info.idx_str,
                     constraints: vtab_constraints,
                     constraint_usages: index_info.constraint_usages,
+                    order_by_consumed: !order_by.is_empty() && index_info.order_by_consumed,
                 },
             }))
         }
@@ -94,7 +94,7 @@ pub struct Constraint {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConstraintOperator {
     AstNativeOperator(ast::Operator),
-    Like { not: bool },
+    Like { op: ast::LikeOperator, not: bool },
     In { not: bool, estimated_values: f64 },
 }
 
@@ -354,8 +354,8 @@ fn estimate_selectivity(
         | ConstraintOperator::AstNativeOperator(ast::Operator::LessEquals) => params.sel_range,
         ConstraintOperator::AstNativeOperator(ast::Operator::Is) => params.sel_is_null,
         ConstraintOperator::AstNativeOperator(ast::Operator::IsNot) => params.sel_is_not_null,
-        ConstraintOperator::Like { not: false } => params.sel_like,
-        ConstraintOperator::Like { not: true } => params.sel_not_like,
+        ConstraintOperator::Like { not: false, .. } => params.sel_like,
+        ConstraintOperator::Like { not: true, .. } => params.sel_not_like,
         ConstraintOperator::In {
             not,
             estimated_values,
@@ -1572,28 +1572,36 @@ pub fn convert_to_vtab_constraint(
             }
             let all_required_tables_are_on_left_side =
                 lhs_mask.contains_all_set_bits_of(&constraint.lhs_mask);
-            to_ext_constraint_op(&constraint.operator).map(|op| ConstraintInfo {
-                column_index: table_col_pos as u32,
-                op,
-                usable: all_required_tables_are_on_left_side,
-                index: i,
+            to_ext_constraint_op(&constraint.operator, constraint.where_clause_pos.1).map(|op| {
+                ConstraintInfo {
+                    column_index: table_col_pos as u32,
+                    op,
+                    usable: all_required_tables_are_on_left_side,
+                    index: i,
+                }
             })
         })
         .collect();
     Ok(constraints)
 }
 
-fn to_ext_constraint_op(op: &ConstraintOperator) -> Option<ConstraintOp> {
-    let ConstraintOperator::AstNativeOperator(op) = op else {
-        return None;
-    };
+/// `side` is the side of the where term holding the constraining expression. MATCH is
+/// only passed on as `column MATCH expr`, since `expr MATCH column` means something else.
+fn to_ext_constraint_op(op: &ConstraintOperator, side: BinaryExprSide) -> Option<ConstraintOp> {
     match op {
-        ast::Operator::Equals => Some(ConstraintOp::Eq),
-        ast::Operator::Less => Some(ConstraintOp::Lt),
-        ast::Operator::LessEquals => Some(ConstraintOp::Le),
-        ast::Operator::Greater => Some(ConstraintOp::Gt),
-        ast::Operator::GreaterEquals => Some(ConstraintOp::Ge),
-        ast::Operator::NotEquals => Some(ConstraintOp::Ne),
+        ConstraintOperator::AstNativeOperator(op) => match op {
+            ast::Operator::Equals => Some(ConstraintOp::Eq),
+            ast::Operator::Less => Some(ConstraintOp::Lt),
+            ast::Operator::LessEquals => Some(ConstraintOp::Le),
+            ast::Operator::Greater => Some(ConstraintOp::Gt),
+            ast::Operator::GreaterEquals => Some(ConstraintOp::Ge),
+            ast::Operator::NotEquals => Some(ConstraintOp::Ne),
+            _ => None,
+        },
+        ConstraintOperator::Like {
+            op: ast::LikeOperator::Match,
+            not: false,
+        } if side == BinaryExprSide::Rhs => Some(ConstraintOp::Match),
         _ => None,
     }
 }
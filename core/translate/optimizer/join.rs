@@ -45,13 +45,26 @@ use crate::{
 /// join planner as we add order-aware access path choices.
 pub(crate) struct JoinPlanningContext<'a> {
     pub maybe_order_target: Option<&'a OrderTarget>,
+    /// The LIMIT of a single-table query that a virtual table may apply itself.
+    pub vtab_limit: Option<VtabLimit>,
+}
+
+/// A LIMIT, and maybe an OFFSET, that can be offered to a virtual table.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct VtabLimit {
+    pub has_offset: bool,
+    /// Whether the query has an ORDER BY, which the table must then be offered too.
+    pub ordered: bool,
 }
 
 impl<'a> JoinPlanningContext<'a> {
     /// Convenience constructor used by the default planner entrypoints and tests.
     #[cfg_attr(not(test), allow(dead_code))]
     fn default_with_order_target(maybe_order_target: Option<&'a OrderTarget>) -> Self {
-        Self { maybe_order_target }
+        Self {
+            maybe_order_target,
+            vtab_limit: None,
+        }
     }
 }
 
@@ -57,7 +57,9 @@ use constraints::{
     ConstraintOperator, ConstraintRef,
 };
 use cost::Cost;
-use join::{compute_best_join_order_with_context, BestJoinOrderResult, JoinPlanningContext};
+use join::{
+    compute_best_join_order_with_context, BestJoinOrderResult, JoinPlanningContext, VtabLimit,
+};
 use lift_common_subexpressions::lift_common_subexpressions_from_binary_or_terms;
 use order::{
     compute_order_target, plan_satisfies_order_target, simple_aggregate_order_target,
@@ -65,7 +67,7 @@ use order::{
 };
 use rustc_hash::FxHashMap as HashMap;
 use std::{cmp::Ordering, collections::VecDeque, sync::Arc};
-use turso_ext::{ConstraintInfo, ConstraintUsage};
+use turso_ext::{ConstraintInfo, ConstraintOp, ConstraintUsage};
 use turso_parser::ast::RefAct;
 use turso_parser::ast::{self, Expr, SortOrder, SubqueryType, TableInternalId, TriggerEvent};
 
@@ -594,6 +596,13 @@ fn transform_match_to_fts_match(
             .unwrap_or(false)
     };
 
+    let table_is_virtual = |table_id: TableInternalId| -> bool {
+        table_references
+            .joined_tables()
+            .iter()
+            .any(|t| t.internal_id == table_id && matches!(t.table, Table::Virtual(_)))
+    };
+
     let mut match_without_fts = false;
     for term in where_clause.iter_mut() {
         let _ = walk_expr_mut(&mut term.expr, &mut |e: &mut Expr| -> Result<WalkControl> {
@@ -605,6 +614,10 @@ fn transform_match_to_fts_match(
                     rhs,
                     escape: _,
                 } => {
+                    // MATCH on a virtual table is a constraint for its module to handle
+                    if get_table_id_from_expr(lhs).is_some_and(table_is_virtual) {
+                        return Ok(WalkControl::SkipChildren);
+                    }
                     // Check if the specific table referenced by this MATCH has an FTS index
                     let has_fts = get_table_id_from_expr(lhs).is_some_and(table_has_fts_index);
 
@@ -725,6 +738,7 @@ fn detect_simple_aggregate(plan: &SelectPlan) -> Option<SimpleAggregate> {
 struct OptimizeTableAccessResult {
     join_order: Vec<JoinOrderMember>,
     output_rows: f64,
+    cost: Cost,
     min_max_fast_path: bool,
 }
 
@@ -772,6 +786,10 @@ pub fn optimize_select_plan(plan: &mut SelectPlan, resolver: &Resolver) -> Resul
     }
 
     plan.simple_aggregate = detect_simple_aggregate(plan);
+    let limit_counts_scanned_rows = plan.group_by.is_none()
+        && plan.aggregates.is_empty()
+        && !plan.distinctness.is_distinct()
+        && plan.window.is_none();
     let best_join_order = optimize_table_access(
         schema,
         resolver.dialect.as_ref(),
@@ -785,6 +803,7 @@ pub fn optimize_select_plan(plan: &mut SelectPlan, resolver: &Resolver) -> Resul
         &plan.non_from_clause_subqueries,
         &mut plan.limit,
         &mut plan.offset,
+        limit_counts_scanned_rows,
         plan.input_cardinality_hint.unwrap_or(1.0),
     )?;
 
@@ -799,10 +818,12 @@ pub fn optimize_select_plan(plan: &mut SelectPlan, resolver: &Resolver) -> Resul
     if let Some(OptimizeTableAccessResult {
         join_order,
         output_rows,
+        cost,
         ..
     }) = best_join_order
     {
         plan.join_order = join_order;
+        plan.estimated_cost = Some(cost.0);
         let mut est = output_rows;
         // Clamp to LIMIT when it's a literal non-negative number.
         // Negative LIMIT means "no limit" in SQLite, so we skip those.
@@ -865,6 +886,7 @@ fn optimize_delete_plan(plan: &mut DeletePlan, resolver: &Resolver) -> Result<()
         &plan.non_from_clause_subqueries,
         &mut plan.limit,
         &mut plan.offset,
+        false,
         1.0,
     )?;
 
@@ -927,6 +949,7 @@ fn optimize_update_plan(
         &plan.non_from_clause_subqueries,
         &mut plan.limit,
         &mut plan.offset,
+        false,
         1.0,
     )?;
     plan.target_table = target_tables
@@ -1230,6 +1253,7 @@ fn build_update_write_set_plan(
         window: None,
         input_cardinality_hint: None,
         estimated_output_rows: None,
+        estimated_cost: None,
         // For regular UPDATEs, only WHERE-clause subqueries move into the ephemeral plan.
         // For UPDATE ... FROM, SET expressions become part of the ephemeral SELECT payload,
         // so their subqueries move too.
@@ -1663,7 +1687,7 @@ fn where_term_is_null_rejecting_for_table(
 }
 
 /// Returns true if an expression references a column from `table_id`.
-fn expr_references_table(expr: &ast::Expr, table_id: ast::TableInternalId) -> bool {
+pub(crate) fn expr_references_table(expr: &ast::Expr, table_id: ast::TableInternalId) -> bool {
     use crate::translate::expr::{walk_expr, WalkControl};
     let mut found = false;
     let _ = walk_expr(expr, &mut |inner: &ast::Expr| -> Result<WalkControl> {
@@ -1835,6 +1859,10 @@ fn enforce_indexed_by_hints(
 /// - Removes predicates from the `where_clause` that are now redundant due to the selected access methods.
 /// - Removes sorting operations if the selected join order and access methods satisfy the [crate::translate::optimizer::order::OrderTarget].
 ///
+/// `limit_counts_scanned_rows` tells whether each row of the join is a row of the output,
+/// with no grouping, aggregation, DISTINCT or window in between, so that a virtual table
+/// scanned on its own may apply the LIMIT itself.
+///
 /// Returns the join order if it was optimized, or None if the default join order was considered best.
 #[allow(clippy::too_many_arguments)]
 fn optimize_table_access(
@@ -1854,6 +1882,7 @@ fn optimize_table_access(
     subqueries: &[NonFromClauseSubquery],
     limit: &mut Option<Box<Expr>>,
     offset: &mut Option<Box<Expr>>,
+    limit_counts_scanned_rows: bool,
     initial_input_cardinality: f64,
 ) -> Result<Option<OptimizeTableAccessResult>> {
     // When optimizer_params feature is enabled, use lazily-loaded params (cached process-wide).
@@ -2034,8 +2063,17 @@ fn optimize_table_access(
         &mut constraints_per_table,
     )?;
 
+    let vtab_limit = (limit_counts_scanned_rows
+        && is_single_table
+        && limit.is_some()
+        && matches!(table_references.joined_tables()[0].table, Table::Virtual(_)))
+    .then_some(VtabLimit {
+        has_offset: offset.is_some(),
+        ordered: !order_by.is_empty(),
+    });
     let planning_context = JoinPlanningContext {
         maybe_order_target: maybe_order_target.as_ref(),
+        vtab_limit,
     };
 
     let Some(best_join_order_result) = compute_best_join_order_with_context(
@@ -2065,10 +2103,10 @@ fn optimize_table_access(
 
     // See if best_ordered_plan is better than the overall best_plan if we add a sorting penalty
     // to the unordered plan's cost.
+    const SORT_COST_PER_ROW_MULTIPLIER: f64 = 0.001;
     let best_plan = if let Some(best_ordered_plan) = best_ordered_plan {
         let best_unordered_plan_cost = best_plan.cost;
         let best_ordered_plan_cost = best_ordered_plan.cost;
-        const SORT_COST_PER_ROW_MULTIPLIER: f64 = 0.001;
         let sorting_penalty = Cost(best_plan.output_cardinality * SORT_COST_PER_ROW_MULTIPLIER);
         if best_unordered_plan_cost + sorting_penalty > best_ordered_plan_cost {
             best_ordered_plan
@@ -2114,6 +2152,14 @@ fn optimize_table_access(
         sort_eliminated = satisfies_order_target;
     }
 
+    // Charge the same sorting penalty used above when the chosen plan still needs a sort,
+    // so that the reported cost reflects what the plan choice was based on.
+    let estimated_cost = if maybe_order_target.is_some() && !sort_eliminated {
+        best_plan.cost + Cost(final_output_cardinality * SORT_COST_PER_ROW_MULTIPLIER)
+    } else {
+        best_plan.cost
+    };
+
     let (best_access_methods, best_table_numbers) = (
         best_plan.best_access_methods().collect::<Vec<_>>(),
         best_plan.table_numbers().collect::<Vec<_>>(),
@@ -2437,6 +2483,7 @@ fn optimize_table_access(
                 idx_str,
                 constraints,
                 constraint_usages,
+                ..
             } => {
                 table_references.joined_tables_mut()[table_idx].op = build_vtab_scan_op(
                     where_clause,
@@ -2445,6 +2492,8 @@ fn optimize_table_access(
                     idx_str,
                     constraints,
                     constraint_usages,
+                    limit,
+                    offset,
                     Some(table_references),
                 )?;
             }
@@ -2687,11 +2736,15 @@ fn optimize_table_access(
     Ok(Some(OptimizeTableAccessResult {
         join_order: best_join_order,
         output_rows: final_output_cardinality,
+        cost: estimated_cost,
         min_max_fast_path: matches!(simple_aggregate, Some(SimpleAggregate::MinMax(_)))
             && sort_eliminated,
     }))
 }
 
+/// Build the scan of a virtual table, passing it the constraints it asked for. An OFFSET
+/// the table used and omitted is removed from `offset`, as the table skips the rows.
+#[allow(clippy::too_many_arguments)]
 fn build_vtab_scan_op(
     where_clause: &mut [WhereTerm],
     table_constraints: &TableConstraints,
@@ -2699,6 +2752,8 @@ fn build_vtab_scan_op(
     idx_str: &Option<String>,
     vtab_constraints: &[ConstraintInfo],
     constraint_usages: &[ConstraintUsage],
+    limit: &Option<Box<Expr>>,
+    offset: &mut Option<Box<Expr>>,
     referenced_tables: Option<&TableReferences>,
 ) -> Result<Operation> {
     if constraint_usages.len() != vtab_constraints.len() {
@@ -2711,6 +2766,7 @@ fn build_vtab_scan_op(
 
     let mut constraints = vec![None; constraint_usages.len()];
     let mut arg_count = 0;
+    let mut offset_omitted = false;
 
     for (i, vtab_constraint) in vtab_constraints.iter().enumerate() {
         let usage = constraint_usages[i];
@@ -2733,11 +2789,34 @@ fn build_vtab_scan_op(
             )));
         }
 
-        let constraint = &table_constraints.constraints[vtab_constraint.index];
-        if usage.omit {
-            where_clause[constraint.where_clause_pos.0].consumed = true;
-        }
-        let (_, expr, _) = constraint.get_constraining_expr(where_clause, referenced_tables);
+        let expr = match vtab_constraint.op {
+            ConstraintOp::Limit | ConstraintOp::Offset => {
+                let is_offset = vtab_constraint.op == ConstraintOp::Offset;
+                let clause = if is_offset { &*offset } else { limit };
+                let Some(expr) = clause else {
+                    return Err(LimboError::InternalError(format!(
+                        "{:?} offered to a virtual table without one in the query",
+                        vtab_constraint.op
+                    )));
+                };
+                offset_omitted |= is_offset && usage.omit;
+                expr.as_ref().clone()
+            }
+            _ => {
+                let constraint = &table_constraints.constraints[vtab_constraint.index];
+                if usage.omit {
+                    where_clause[constraint.where_clause_pos.0].consumed = true;
+                }
+                if constraint.operator.as_ast_operator().is_some() {
+                    constraint
+                        .get_constraining_expr(where_clause, referenced_tables)
+                        .1
+                } else {
+                    // e.g. MATCH, which has no binary operator to go with the expression
+                    constraint.get_constraining_expr_ref(where_clause).clone()
+                }
+            }
+        };
         constraints[zero_based_argv_index] = Some(expr);
         arg_count += 1;
     }
@@ -2756,6 +2835,9 @@ fn build_vtab_scan_op(
             })
         })
         .collect::<Result<Vec<_>>>()?;
+    if offset_omitted {
+        *offset = None;
+    }
 
     Ok(Operation::Scan(Scan::VirtualTable {
         idx_num: *idx_num,
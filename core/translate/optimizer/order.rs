@@ -314,6 +314,12 @@ pub fn plan_satisfies_order_target(
                     schema,
                 )
             }
+            // The virtual table agreed to the order of the whole target, which only
+            // holds for the output if no outer loop repeats its rows.
+            AccessMethodParams::VirtualTable {
+                order_by_consumed: true,
+                ..
+            } if loop_pos == 0 => num_cols_in_order_target,
             _ => return false,
         };
 
@@ -309,6 +309,12 @@ pub fn resolve_window_and_aggregate_functions(
                             let func = AggFunc::External(f.func.clone().into());
                             if let ExtFunc::Aggregate { .. } = f.as_ref().func {
                                 if let Some(over_clause) = filter_over.over_clause.as_ref() {
+                                    if !f.func.is_window_aggregate() {
+                                        crate::bail_parse_error!(
+                                            "{} may not be used as a window function",
+                                            name.as_str()
+                                        );
+                                    }
                                     link_with_window(
                                         windows.as_deref_mut(),
                                         named_windows,
@@ -411,6 +417,12 @@ pub fn resolve_window_and_aggregate_functions(
                             let func = AggFunc::External(f.func.clone().into());
                             if let ExtFunc::Aggregate { .. } = f.as_ref().func {
                                 if let Some(over_clause) = filter_over.over_clause.as_ref() {
+                                    if !f.func.is_window_aggregate() {
+                                        crate::bail_parse_error!(
+                                            "{} may not be used as a window function",
+                                            name.as_str()
+                                        );
+                                    }
                                     link_with_window(
                                         windows.as_deref_mut(),
                                         named_windows,
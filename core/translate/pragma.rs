@@ -13,7 +13,7 @@ use super::integrity_check::{
     translate_integrity_check, translate_quick_check, MAX_INTEGRITY_CHECK_ERRORS,
 };
 use crate::function::Func;
-use crate::pragma::pragma_for;
+use crate::pragma::{pragma_for, PragmaFlags};
 use crate::schema::Schema;
 use crate::storage::encryption::{CipherMode, EncryptionKey};
 use crate::storage::pager::AutoVacuumMode;
@@ -209,6 +209,45 @@ fn emit_table_list_rows_for_schema(
     }
 }
 
+/// Pragmas that take a parameter but are queries, not setters.
+fn takes_query_argument(pragma: &PragmaName) -> bool {
+    matches!(
+        pragma,
+        PragmaName::IndexInfo
+            | PragmaName::IndexXinfo
+            | PragmaName::IndexList
+            | PragmaName::ForeignKeyList
+            | PragmaName::TableList
+            | PragmaName::TableInfo
+            | PragmaName::TableXinfo
+            | PragmaName::IntegrityCheck
+            | PragmaName::DatabaseList
+            | PragmaName::QuickCheck
+    )
+}
+
+/// Whether the pragma only reads: translating a setter applies it right away,
+/// so this can't be judged from the translated program. Unknown pragmas are
+/// ignored and read nothing.
+pub(crate) fn pragma_is_query(name: &ast::QualifiedName, body: Option<&ast::PragmaBody>) -> bool {
+    if name.name.as_str().eq_ignore_ascii_case("pragma_list") {
+        return true;
+    }
+    let Ok(pragma) = PragmaName::from_str(name.name.as_str()) else {
+        return true;
+    };
+    match body {
+        None => match pragma {
+            PragmaName::LegacyFileFormat
+            | PragmaName::FullColumnNames
+            | PragmaName::ShortColumnNames
+            | PragmaName::EmptyResultCallbacks => true,
+            pragma => pragma_for(&pragma).flags.contains(PragmaFlags::Result0),
+        },
+        Some(_) => takes_query_argument(&pragma),
+    }
+}
+
 pub fn translate_pragma(
     resolver: &Resolver,
     name: &ast::QualifiedName,
@@ -244,18 +283,10 @@ pub fn translate_pragma(
             schema_was_explicit,
             program,
         )?,
-        Some(ast::PragmaBody::Equals(value) | ast::PragmaBody::Call(value)) => match pragma {
-            // These pragmas take a parameter but are queries, not setters
-            PragmaName::IndexInfo
-            | PragmaName::IndexXinfo
-            | PragmaName::IndexList
-            | PragmaName::ForeignKeyList
-            | PragmaName::TableList
-            | PragmaName::TableInfo
-            | PragmaName::TableXinfo
-            | PragmaName::IntegrityCheck
-            | PragmaName::DatabaseList
-            | PragmaName::QuickCheck => query_pragma(
+        Some(ast::PragmaBody::Equals(value) | ast::PragmaBody::Call(value))
+            if takes_query_argument(&pragma) =>
+        {
+            query_pragma(
                 pragma,
                 resolver,
                 Some(*value),
@@ -264,18 +295,18 @@ pub fn translate_pragma(
                 database_id,
                 schema_was_explicit,
                 program,
-            )?,
-            _ => update_pragma(
-                pragma,
-                resolver,
-                *value,
-                pager,
-                connection,
-                database_id,
-                schema_was_explicit,
-                program,
-            )?,
-        },
+            )?
+        }
+        Some(ast::PragmaBody::Equals(value) | ast::PragmaBody::Call(value)) => update_pragma(
+            pragma,
+            resolver,
+            *value,
+            pager,
+            connection,
+            database_id,
+            schema_was_explicit,
+            program,
+        )?,
     };
     match mode {
         TransactionMode::None => {}
@@ -360,11 +391,34 @@ fn update_pragma(
             Ok(TransactionMode::None)
         }
         PragmaName::CacheSpill => {
-            let enabled = parse_pragma_enabled(&value);
+            // `PRAGMA cache_spill=N` sets the spill size and enables spilling
+            // unless N is 0, like SQLite. Anything else is a boolean.
+            let enabled = match parse_signed_number(&value) {
+                Ok(Value::Numeric(Numeric::Integer(size))) => {
+                    update_cache_spill(size, &pager);
+                    size != 0
+                }
+                _ => parse_pragma_enabled(&value),
+            };
             connection.get_pager().set_spill_enabled(enabled);
             connection.bump_prepare_context_generation();
             Ok(TransactionMode::None)
         }
+        PragmaName::MmapSize => {
+            let mmap_size = match parse_signed_number(&value)? {
+                Value::Numeric(Numeric::Integer(size)) => size,
+                Value::Numeric(Numeric::Float(size)) => f64::from(size) as i64,
+                _ => bail_parse_error!("Invalid value for mmap_size pragma"),
+            };
+            // With multiprocess WAL another process can shrink the database
+            // file under the mapping, so keep reading it with pread.
+            if !connection.experimental_multiprocess_wal_enabled() {
+                let pager = connection.get_pager_from_database_index(&database_id)?;
+                // Negative values restore the default, which is no mapping.
+                pager.set_mmap_size(mmap_size.max(0) as u64)?;
+            }
+            Ok(TransactionMode::None)
+        }
         PragmaName::Encoding => {
             let year = chrono::Local::now().year();
             bail_parse_error!("It's {year}. UTF-8 won.");
@@ -515,20 +569,6 @@ fn update_pragma(
                 ));
             }
 
-            let is_empty = is_database_empty(resolver.schema(), &pager)?;
-            tracing::debug!(
-                "Checking if database is empty for auto_vacuum pragma: {}",
-                is_empty
-            );
-
-            if !is_empty {
-                // SQLite's behavior is to silently ignore this pragma if the database is not empty.
-                tracing::debug!(
-                    "Attempted to set auto_vacuum, database is not empty so we are ignoring pragma."
-                );
-                return Ok(TransactionMode::None);
-            }
-
             let auto_vacuum_mode = match value {
                 Expr::Name(name) => {
                     let name = name.as_str().as_bytes();
@@ -543,21 +583,49 @@ fn update_pragma(
                         }
                     })
                 }
+                Expr::Literal(Literal::Numeric(n)) => match n.as_str() {
+                    "0" => 0,
+                    "1" => 1,
+                    "2" => 2,
+                    _ => {
+                        return Err(LimboError::InvalidArgument(
+                            "invalid auto vacuum mode".to_string(),
+                        ));
+                    }
+                },
                 _ => {
                     return Err(LimboError::InvalidArgument(
                         "invalid auto vacuum mode".to_string(),
                     ));
                 }
             };
-            match auto_vacuum_mode {
-                0 => pager.persist_auto_vacuum_mode(AutoVacuumMode::None)?,
-                1 => pager.persist_auto_vacuum_mode(AutoVacuumMode::Full)?,
-                2 => pager.persist_auto_vacuum_mode(AutoVacuumMode::Incremental)?,
-                _ => {
-                    return Err(LimboError::InvalidArgument(
-                        "invalid auto vacuum mode".to_string(),
-                    ));
-                }
+            let new_mode = match auto_vacuum_mode {
+                0 => AutoVacuumMode::None,
+                1 => AutoVacuumMode::Full,
+                _ => AutoVacuumMode::Incremental,
+            };
+
+            let is_empty = is_database_empty(resolver.schema(), &pager)?;
+            tracing::debug!(
+                "Checking if database is empty for auto_vacuum pragma: {}",
+                is_empty
+            );
+
+            if is_empty {
+                pager.persist_auto_vacuum_mode(new_mode)?;
+            } else if pager.get_auto_vacuum_mode() != AutoVacuumMode::None
+                && new_mode != AutoVacuumMode::None
+            {
+                // The pointer map stays, only the incremental vacuum flag
+                // written below changes.
+                pager.set_auto_vacuum_mode(new_mode);
+            } else {
+                // SQLite's behavior is to silently ignore this pragma if the database is not empty
+                // and it would have to add or drop the pointer map.
+                tracing::debug!(
+                    "Attempted to set auto_vacuum, database is not empty so we are ignoring pragma."
+                );
+                return Ok(TransactionMode::None);
             }
             let largest_root_page_number_reg = program.alloc_register();
             program.emit_insn(Insn::ReadCookie {
@@ -622,6 +690,16 @@ fn update_pragma(
             schema_was_explicit,
             program,
         ),
+        PragmaName::IncrementalVacuum => query_pragma(
+            PragmaName::IncrementalVacuum,
+            resolver,
+            Some(value),
+            pager,
+            connection,
+            database_id,
+            schema_was_explicit,
+            program,
+        ),
         PragmaName::FreelistCount => query_pragma(
             PragmaName::FreelistCount,
             resolver,
@@ -644,6 +722,20 @@ fn update_pragma(
             connection.set_encryption_cipher(cipher)?;
             Ok(TransactionMode::None)
         }
+        PragmaName::EncryptionRekey => {
+            let value = parse_string(&value)?;
+            // Only validated here: the database is re-encrypted when the
+            // statement runs.
+            EncryptionKey::from_hex_string(&value)?;
+            if database_id != crate::MAIN_DB_ID {
+                bail_parse_error!("hexrekey only applies to the main database");
+            }
+            program.emit_insn(Insn::Rekey {
+                db: database_id,
+                hex_key: value,
+            });
+            Ok(TransactionMode::None)
+        }
         PragmaName::Synchronous => {
             use crate::SyncMode;
             let mode = if let Expr::Literal(Literal::Numeric(n)) = &value {
@@ -766,6 +858,12 @@ fn update_pragma(
             connection.set_temp_store(temp_store);
             Ok(TransactionMode::None)
         }
+        PragmaName::TempStoreDirectory => {
+            // As in SQLite, an empty string resets to the default location.
+            let dir = parse_string(&value)?;
+            connection.set_temp_store_directory((!dir.is_empty()).then_some(dir))?;
+            Ok(TransactionMode::None)
+        }
         PragmaName::VdbeTrace => {
             let enabled = parse_pragma_enabled(&value);
             connection.set_vdbe_trace(enabled);
@@ -822,8 +920,13 @@ fn query_pragma(
             Ok(TransactionMode::None)
         }
         PragmaName::CacheSpill => {
-            let spill_enabled = connection.get_pager().get_spill_enabled();
-            program.emit_int(spill_enabled as i64, register);
+            let pager = connection.get_pager();
+            let spill_size = if pager.get_spill_enabled() {
+                pager.get_spill_size() as i64
+            } else {
+                0
+            };
+            program.emit_int(spill_size, register);
             program.emit_result_row(register, 1);
             program.add_pragma_result_column(pragma.to_string());
             Ok(TransactionMode::None)
@@ -1005,6 +1108,13 @@ fn query_pragma(
             program.add_pragma_result_column(pragma.to_string());
             Ok(TransactionMode::Read)
         }
+        PragmaName::MmapSize => {
+            let pager = connection.get_pager_from_database_index(&database_id)?;
+            program.emit_int(pager.get_mmap_size() as i64, register);
+            program.emit_result_row(register, 1);
+            program.add_pragma_result_column(pragma.to_string());
+            Ok(TransactionMode::None)
+        }
         PragmaName::MaxPageCount => {
             program.emit_insn(Insn::MaxPgcnt {
                 db: database_id,
@@ -1514,6 +1624,18 @@ fn query_pragma(
             Ok(TransactionMode::None)
         }
         PragmaName::VdbeTrace => Ok(TransactionMode::None),
+        PragmaName::IncrementalVacuum => {
+            // Like SQLite, a missing or non-positive count frees every page.
+            let max_pages = match value {
+                Some(Expr::Literal(Literal::Numeric(n))) => n.parse::<u32>().unwrap_or(0),
+                _ => 0,
+            };
+            program.emit_insn(Insn::IncrVacuum {
+                db: database_id,
+                max_pages,
+            });
+            Ok(TransactionMode::Write)
+        }
         PragmaName::FreelistCount => {
             let value = pager.freepage_list();
             let register = program.alloc_register();
@@ -1536,6 +1658,8 @@ fn query_pragma(
             program.add_pragma_result_column(pragma.to_string());
             Ok(TransactionMode::None)
         }
+        // Like SQLCipher's `PRAGMA rekey`, there is nothing to report.
+        PragmaName::EncryptionRekey => Ok(TransactionMode::None),
         PragmaName::EncryptionCipher => {
             if let Some(cipher) = connection.get_encryption_cipher_mode() {
                 let register = program.alloc_register();
@@ -1618,6 +1742,15 @@ fn query_pragma(
             program.add_pragma_result_column(pragma.to_string());
             Ok(TransactionMode::None)
         }
+        PragmaName::TempStoreDirectory => {
+            if let Some(dir) = connection.get_temp_store_directory() {
+                let register = program.alloc_register();
+                program.emit_string8(dir, register);
+                program.emit_result_row(register, 1);
+            }
+            program.add_pragma_result_column(pragma.to_string());
+            Ok(TransactionMode::None)
+        }
         PragmaName::ListTypes => {
             let base_reg = register;
             program.alloc_registers(5); // 6 total (1 already allocated)
@@ -1826,6 +1959,22 @@ fn update_cache_size(
     Ok(())
 }
 
+/// Set the spill size of the connection's page cache. Like `cache_size`, a
+/// negative value is a size in KiB.
+fn update_cache_spill(value: i64, pager: &Pager) {
+    let pages = if value < 0 {
+        let page_size = pager
+            .io
+            .block(|| pager.with_header(|header| header.page_size))
+            .unwrap_or_default()
+            .get() as i64;
+        value.checked_abs().unwrap_or(i64::MAX).saturating_mul(1024) / page_size.max(1)
+    } else {
+        value
+    };
+    pager.set_spill_size(pages.min(CacheSize::MAX_SAFE) as usize);
+}
+
 pub const TURSO_CDC_DEFAULT_TABLE_NAME: &str = "turso_cdc";
 pub const TURSO_CDC_VERSION_TABLE_NAME: &str = "turso_cdc_version";
 
@@ -40,6 +40,7 @@ pub fn translate_rollback(
         program.emit_insn(Insn::AutoCommit {
             auto_commit: true,
             rollback: true,
+            read_only: false,
         });
         program.rollback();
     }
@@ -432,6 +432,8 @@ fn resolve_scalar_func_return_type(
         | ScalarFunc::NumericEq
         | ScalarFunc::ValidateIpAddr
         | ScalarFunc::GetByte
+        | ScalarFunc::RowHash
+        | ScalarFunc::HashTable
         | ScalarFunc::UnixEpoch => Ok(CheckExprType::Integer),
 
         // Functions that always return TEXT
@@ -2482,11 +2484,9 @@ fn validate_type_expr(expr: &ast::Expr, kind: &str, resolver: &Resolver) -> Resu
                             name.as_str()
                         );
                     }
-                    // Reject known non-deterministic built-in functions.
-                    // External functions are excluded from this check since
-                    // they default to non-deterministic but may actually be
-                    // deterministic (e.g. uuid_blob).
-                    if !matches!(func, Func::External(_)) && !func.is_deterministic() {
+                    // External functions qualify only if they were registered
+                    // as deterministic.
+                    if !func.is_deterministic() {
                         bail_parse_error!(
                             "non-deterministic functions prohibited in {kind} expressions: {}",
                             name.as_str()
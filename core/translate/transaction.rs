@@ -19,12 +19,22 @@ pub fn translate_tx_begin(
     let schema = resolver.schema();
     let tx_type = tx_type.unwrap_or(TransactionType::Deferred);
     match tx_type {
+        TransactionType::ReadOnly => {
+            // Like DEFERRED, but the connection rejects writes until the
+            // transaction ends.
+            program.emit_insn(Insn::AutoCommit {
+                auto_commit: false,
+                rollback: false,
+                read_only: true,
+            });
+        }
         TransactionType::Deferred => {
             // SQLite emits only AutoCommit for deferred — no
             // Transaction opcodes at all (for any database).
             program.emit_insn(Insn::AutoCommit {
                 auto_commit: false,
                 rollback: false,
+                read_only: false,
             });
         }
         TransactionType::Immediate | TransactionType::Exclusive => {
@@ -54,6 +64,7 @@ pub fn translate_tx_begin(
             program.emit_insn(Insn::AutoCommit {
                 auto_commit: false,
                 rollback: false,
+                read_only: false,
             });
         }
         TransactionType::Concurrent => {
@@ -82,6 +93,7 @@ pub fn translate_tx_begin(
             program.emit_insn(Insn::AutoCommit {
                 auto_commit: false,
                 rollback: false,
+                read_only: false,
             });
         }
     }
@@ -104,6 +116,7 @@ pub fn translate_tx_commit(
     program.emit_insn(Insn::AutoCommit {
         auto_commit: true,
         rollback: false,
+        read_only: false,
     });
     Ok(())
 }
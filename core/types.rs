@@ -1,7 +1,9 @@
 use crate::turso_debug_assert;
 use branches::{mark_unlikely, unlikely};
 use either::Either;
-use turso_ext::{AggCtx, ContextDestructor, FinalizeFunction, StepFunction, ValueDestructor};
+use turso_ext::{
+    AggCtx, ContextDestructor, FinalizeFunction, StepFunction, ValueDestructor, ValueFunction,
+};
 use turso_parser::ast::SortOrder;
 
 use crate::alloc::*;
@@ -633,6 +635,8 @@ pub struct ExternalAggState {
     pub argc: usize,
     pub step_fn: StepFunction,
     pub finalize_fn: FinalizeFunction,
+    /// Set if the aggregate can be used as a window function.
+    pub value_fn: Option<ValueFunction>,
     pub aggregate_destructor: Option<ContextDestructor>,
     pub value_destructor: Option<ValueDestructor>,
 }
@@ -696,10 +700,10 @@ impl Value {
                 Ok(Value::build_text(text.to_string()))
             }
             ExtValueType::Blob => {
-                let Some(blob) = v.to_blob() else {
+                let Some(blob) = v.blob_ref() else {
                     return Ok(Value::Null);
                 };
-                Ok(Value::from_slice(&blob)?)
+                Ok(Value::from_slice(blob)?)
             }
             ExtValueType::Error => {
                 let Some(err) = v.to_error_details() else {
@@ -911,6 +915,28 @@ impl AggContext {
         }
     }
 
+    /// Current result of an external aggregate used as a window function.
+    /// Unlike [Self::compute_external], the state stays usable for further
+    /// steps.
+    pub fn external_value(&self) -> Result<Value> {
+        let Self::External(ext_state) = self else {
+            panic!("AggContext::external_value() expected External, found {self:?}");
+        };
+        let Some(value_fn) = ext_state.value_fn else {
+            return Err(LimboError::InternalError(
+                "external aggregate cannot be used as a window function".to_string(),
+            ));
+        };
+        let mut value = unsafe { value_fn(ext_state.context, ext_state.state) };
+        let result = Value::from_ffi_ref(&value);
+        if let Some(value_destructor) = ext_state.value_destructor {
+            unsafe { value_destructor(&mut value) };
+        } else {
+            unsafe { value.__free_internal_type() };
+        }
+        result
+    }
+
     /// Get a mutable reference to the builtin payload as a slice
     pub fn payload_mut(&mut self) -> &mut [Value] {
         match self {
@@ -2121,6 +2147,112 @@ impl<'a> Clone for ValueIterator<'a> {
     }
 }
 
+/// Column offsets of the record a cursor is positioned on, parsed from the
+/// record header on demand (SQLite's `aType`/`aOffset` in `VdbeCursor`).
+///
+/// Reading column `n` parses serial types only up to `n`, and any later read
+/// of a column at or before `n` starts decoding right at its value instead of
+/// walking the header again. The cache only stores offsets, so the payload can
+/// be borrowed from wherever the record currently lives: the b-tree page, or
+/// the cursor's copy of a record that spills into overflow pages.
+pub struct RecordHeaderCache {
+    /// Per parsed column: offset of its serial type and of its value in the
+    /// payload.
+    columns: crate::alloc::Vec<(usize, usize)>,
+    header_size: usize,
+    /// Offset of the first serial type that hasn't been parsed yet.
+    next_header_offset: usize,
+    /// Offset of the value of that column, i.e. the end of the last parsed one.
+    next_data_offset: usize,
+    valid: bool,
+}
+
+impl Default for RecordHeaderCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordHeaderCache {
+    pub fn new() -> Self {
+        Self {
+            columns: crate::alloc::vec![],
+            header_size: 0,
+            next_header_offset: 0,
+            next_data_offset: 0,
+            valid: false,
+        }
+    }
+
+    /// Forget the current record. Must be called whenever the cursor moves or
+    /// the row under it changes.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// A [ValueIterator] over `payload` whose next item is column `column`.
+    ///
+    /// `payload` may be just the prefix of the record stored on a b-tree page,
+    /// in which case `complete` is false and `None` is returned when the header
+    /// or the column's value continue past it. `None` is also returned when the
+    /// record has fewer columns.
+    pub fn column<'a>(
+        &mut self,
+        payload: &'a [u8],
+        column: usize,
+        complete: bool,
+    ) -> Result<Option<ValueIterator<'a>>> {
+        if !self.valid {
+            let (header_size, header_varint_len) = read_varint(payload)?;
+            let header_size = header_size as usize;
+            if header_varint_len > header_size || (complete && header_size > payload.len()) {
+                return Err(LimboError::Corrupt(
+                    "Payload too small for indicated header size".into(),
+                ));
+            }
+            self.columns.clear();
+            self.header_size = header_size;
+            self.next_header_offset = header_varint_len;
+            self.next_data_offset = header_size;
+            self.valid = true;
+        }
+        if self.header_size > payload.len() {
+            return Ok(None);
+        }
+        while self.columns.len() <= column {
+            if self.next_header_offset >= self.header_size {
+                return Ok(None);
+            }
+            let (serial_type, bytes_read) =
+                read_varint(&payload[self.next_header_offset..self.header_size])?;
+            self.columns
+                .try_push((self.next_header_offset, self.next_data_offset))?;
+            self.next_header_offset += bytes_read;
+            self.next_data_offset += get_serial_type_size(serial_type)?;
+        }
+        let (header_offset, data_offset) = self.columns[column];
+        let data_end = self
+            .columns
+            .get(column + 1)
+            .map_or(self.next_data_offset, |&(_, next_data_offset)| {
+                next_data_offset
+            });
+        if data_end > payload.len() {
+            if complete {
+                return Err(LimboError::Corrupt(
+                    "Data section too small for indicated serial type size".into(),
+                ));
+            }
+            return Ok(None);
+        }
+        Ok(Some(ValueIterator {
+            header_section: Cell::new(&payload[header_offset..self.header_size]),
+            data_section: Cell::new(&payload[data_offset..]),
+        }))
+    }
+}
+
 impl<'a> ValueRef<'a> {
     pub fn from_f64(f: f64) -> Self {
         match NonNan::new(f) {
@@ -4819,4 +4951,58 @@ mod tests {
             assert_eq!(value.try_clone().unwrap(), value);
         }
     }
+
+    #[test]
+    fn test_record_header_cache() {
+        fn read(
+            cache: &mut RecordHeaderCache,
+            payload: &[u8],
+            column: usize,
+            complete: bool,
+        ) -> Option<Value> {
+            let mut values = cache.column(payload, column, complete).unwrap()?;
+            Some(values.next().unwrap().unwrap().to_owned().unwrap())
+        }
+
+        let values = [
+            Value::from_i64(7),
+            Value::build_text("small"),
+            Value::Blob(vec![1; 5000]),
+            Value::from_i64(9),
+        ];
+        let record = ImmutableRecord::from_values(&values, values.len()).unwrap();
+        let payload = record.get_payload();
+        // What a leaf page keeps locally of a row whose blob overflows.
+        let prefix = &payload[..100];
+
+        let mut cache = RecordHeaderCache::new();
+        assert_eq!(read(&mut cache, prefix, 1, false), Some(values[1].clone()));
+        // The blob and the column after it aren't in the prefix.
+        assert_eq!(read(&mut cache, prefix, 2, false), None);
+        assert_eq!(read(&mut cache, prefix, 3, false), None);
+        // Once the whole record is available the offsets parsed so far still
+        // apply, in any order.
+        for column in [3, 0, 2, 1] {
+            assert_eq!(
+                read(&mut cache, payload, column, true),
+                Some(values[column].clone())
+            );
+        }
+        assert_eq!(read(&mut cache, payload, 4, true), None);
+
+        let other = [Value::Null, Value::build_text("other")];
+        let other = ImmutableRecord::from_values(&other, other.len()).unwrap();
+        cache.invalidate();
+        assert_eq!(
+            read(&mut cache, other.get_payload(), 1, true),
+            Some(Value::build_text("other"))
+        );
+
+        // A complete payload that is too short for its header is corrupt.
+        cache.invalidate();
+        assert!(matches!(
+            cache.column(prefix, 3, true),
+            Err(LimboError::Corrupt(_))
+        ));
+    }
 }
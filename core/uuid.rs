@@ -1,4 +1,4 @@
-use crate::ext::register_scalar_function;
+use crate::ext::{register_deterministic_scalar_function, register_scalar_function};
 use turso_ext::{scalar, ExtensionApi, ResultCode, Value, ValueType};
 
 pub fn register_extension(ext_api: &mut ExtensionApi) {
@@ -9,9 +9,13 @@ pub fn register_extension(ext_api: &mut ExtensionApi) {
         register_scalar_function(ext_api.ctx, c"uuid4".as_ptr(), uuid4_blob);
         register_scalar_function(ext_api.ctx, c"uuid7_str".as_ptr(), uuid7_str);
         register_scalar_function(ext_api.ctx, c"uuid7".as_ptr(), uuid7);
-        register_scalar_function(ext_api.ctx, c"uuid7_timestamp_ms".as_ptr(), uuid7_ts);
-        register_scalar_function(ext_api.ctx, c"uuid_str".as_ptr(), uuid_str);
-        register_scalar_function(ext_api.ctx, c"uuid_blob".as_ptr(), uuid_blob);
+        register_deterministic_scalar_function(
+            ext_api.ctx,
+            c"uuid7_timestamp_ms".as_ptr(),
+            uuid7_ts,
+        );
+        register_deterministic_scalar_function(ext_api.ctx, c"uuid_str".as_ptr(), uuid_str);
+        register_deterministic_scalar_function(ext_api.ctx, c"uuid_blob".as_ptr(), uuid_blob);
     }
 }
 
@@ -85,7 +89,7 @@ fn uuid7(&self, args: &[Value]) -> Value {
     Value::from_blob(bytes.to_vec())
 }
 
-#[scalar(name = "uuid7_timestamp_ms")]
+#[scalar(name = "uuid7_timestamp_ms", deterministic)]
 fn uuid7_ts(args: &[Value]) -> Value {
     match args.first().map(|a| a.value_type()) {
         Some(ValueType::Blob) => {
@@ -115,7 +119,7 @@ fn uuid7_ts(args: &[Value]) -> Value {
     }
 }
 
-#[scalar(name = "uuid_str")]
+#[scalar(name = "uuid_str", deterministic)]
 fn uuid_str(args: &[Value]) -> Value {
     match args.first() {
         Some(arg) => match arg.to_blob() {
@@ -142,7 +146,7 @@ fn uuid_str(args: &[Value]) -> Value {
     }
 }
 
-#[scalar(name = "uuid_blob")]
+#[scalar(name = "uuid_blob", deterministic)]
 fn uuid_blob(&self, args: &[Value]) -> Value {
     match args.first() {
         Some(arg) => match arg.to_text() {
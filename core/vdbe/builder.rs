@@ -40,8 +40,8 @@ impl TableRefIdCounter {
 }
 
 use super::{
-    affinity::Affinity, BranchOffset, CursorID, Insn, InsnReference, PrepareContext,
-    PreparedProgram, Program,
+    affinity::Affinity, insn::InsnVariants, peephole, BranchOffset, CursorID, Insn, InsnReference,
+    PrepareContext, PreparedProgram, Program,
 };
 use crate::translate::plan::BitSet;
 use std::num::NonZeroUsize;
@@ -206,6 +206,10 @@ pub struct ProgramBuilder {
     /// `anchor_offset + 1` so it tracks whichever instruction ends up at that
     /// position, even after `emit_constant_insns` reorders the program.
     label_to_resolved_offset: Vec<Option<InsnReference>>,
+    /// Where each label was allocated, indexed by label number. Only kept in
+    /// debug builds, to point at the culprit when a label is never resolved.
+    #[cfg(debug_assertions)]
+    label_allocation_sites: Vec<&'static core::panic::Location<'static>>,
     // map of instruction index to manual comment (used in EXPLAIN only)
     comments: Vec<(InsnReference, &'static str)>,
     pub parameters: Parameters,
@@ -227,6 +231,9 @@ pub struct ProgramBuilder {
     capture_data_changes_info: Option<CaptureDataChangesInfo>,
     /// Whether the main database uses MVCC journal mode, set once at translation time from the connection.
     mvcc_enabled: bool,
+    /// Emit [Insn::ReleaseReg] for released temporary registers, so that reading one before
+    /// it is written again fails. Set at translation time from the connection.
+    poison_temp_registers: bool,
     // TODO: when we support multiple dbs, this should be a write mask to track which DBs need to be written
     txn_mode: TransactionMode,
     /// Set of database IDs that need write transactions (for attached databases).
@@ -257,6 +264,12 @@ pub struct ProgramBuilder {
     /// True once any `Insn::Function` has been emitted. See [`Self::may_abort`].
     emitted_function_call: bool,
     next_free_register: usize,
+    /// Released temporary register ranges as (start, count), reused by
+    /// [ProgramBuilder::alloc_temp_registers] before growing the register file.
+    free_temp_registers: Vec<(usize, usize)>,
+    /// Temporary register ranges currently handed out, as (start, count, offset of the
+    /// next instruction at allocation time).
+    temp_registers_in_use: Vec<(usize, usize, usize)>,
     next_free_cursor_id: usize,
     next_hash_table_id: usize,
     pub table_references: TableReferences,
@@ -461,6 +474,55 @@ pub struct MaterializedCteInfo {
     pub num_columns: usize,
 }
 
+macro_rules! typed_cursor_id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name(CursorID);
+
+        impl $name {
+            pub const fn id(self) -> CursorID {
+                self.0
+            }
+        }
+    };
+}
+
+typed_cursor_id!(
+    /// A cursor over a B-tree table, allocated with [ProgramBuilder::alloc_table_cursor].
+    TableCursor
+);
+typed_cursor_id!(
+    /// A cursor over a B-tree index, allocated with [ProgramBuilder::alloc_index_cursor].
+    IndexCursor
+);
+typed_cursor_id!(
+    /// A sorter cursor, allocated with [ProgramBuilder::alloc_sorter_cursor].
+    SorterCursor
+);
+typed_cursor_id!(
+    /// A pseudo cursor over a single record, allocated with [ProgramBuilder::alloc_pseudo_cursor].
+    PseudoCursor
+);
+
+/// Labels of a loop emitted by [ProgramBuilder::emit_sorter_loop]. Jump to `next` to
+/// skip to the next sorted row and to `end` to leave the loop.
+#[derive(Debug, Clone, Copy)]
+pub struct SorterLoopLabels {
+    pub next: BranchOffset,
+    pub end: BranchOffset,
+}
+
+impl SorterLoopLabels {
+    #[track_caller]
+    pub fn new(program: &mut ProgramBuilder) -> Self {
+        Self {
+            next: program.allocate_label(),
+            end: program.allocate_label(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum CursorType {
     BTreeTable(Arc<BTreeTable>),
@@ -656,12 +718,16 @@ impl ProgramBuilder {
         Self {
             table_reference_counter: TableRefIdCounter::new(),
             next_free_register: 1,
+            free_temp_registers: Vec::new(),
+            temp_registers_in_use: Vec::new(),
             next_free_cursor_id: 0,
             next_hash_table_id: HASH_TABLE_ID_BASE,
             insns: Vec::with_capacity(opts.approx_num_insns),
             cursor_ref: Vec::with_capacity(opts.num_cursors),
             constant_spans: Vec::new(),
             label_to_resolved_offset: Vec::with_capacity(opts.approx_num_labels),
+            #[cfg(debug_assertions)]
+            label_allocation_sites: Vec::with_capacity(opts.approx_num_labels),
             comments: Vec::new(),
             parameters: Parameters::new(),
             result_columns: Vec::new(),
@@ -673,6 +739,7 @@ impl ProgramBuilder {
             start_offset: BranchOffset::Placeholder,
             capture_data_changes_info,
             mvcc_enabled: false,
+            poison_temp_registers: false,
             txn_mode: TransactionMode::None,
             write_databases: BitSet::default(),
             read_databases: BitSet::default(),
@@ -862,6 +929,10 @@ impl ProgramBuilder {
         self.mvcc_enabled = enabled;
     }
 
+    pub fn set_poison_temp_registers(&mut self, enabled: bool) {
+        self.poison_temp_registers = enabled;
+    }
+
     pub fn extend(&mut self, opts: &ProgramBuilderOpts) {
         self.insns.reserve(opts.approx_num_insns);
         self.cursor_ref.reserve(opts.num_cursors);
@@ -931,6 +1002,77 @@ impl ProgramBuilder {
         reg
     }
 
+    /// Allocate `amount` contiguous temporary registers, reusing released ones when possible.
+    ///
+    /// Temporaries are scoped: the caller must write them before reading them and release
+    /// them once the last instruction reading them has been emitted, after which they may be
+    /// handed out again for unrelated values.
+    pub fn alloc_temp_registers(&mut self, amount: usize) -> usize {
+        let start = match self
+            .free_temp_registers
+            .iter()
+            .position(|(_, count)| *count >= amount)
+        {
+            Some(idx) => {
+                let (start, count) = self.free_temp_registers[idx];
+                if count == amount {
+                    self.free_temp_registers.swap_remove(idx);
+                } else {
+                    self.free_temp_registers[idx] = (start + amount, count - amount);
+                }
+                start
+            }
+            None => self.alloc_registers(amount),
+        };
+        self.temp_registers_in_use
+            .push((start, amount, self.insns.len()));
+        start
+    }
+
+    /// Release temporary registers allocated by [Self::alloc_temp_registers] starting at `start`.
+    ///
+    /// The registers are not reused if any of the instructions emitted while they were in use
+    /// belong to a constant span: those get hoisted to the start of the program, so a value
+    /// they store must survive for the whole program. Otherwise, when poisoning is enabled,
+    /// an [Insn::ReleaseReg] marks them as released for the instructions that follow.
+    pub fn release_temp_registers(&mut self, start: usize) {
+        let idx = self
+            .temp_registers_in_use
+            .iter()
+            .rposition(|(reg, _, _)| *reg == start)
+            .expect("released register must be an allocated temporary");
+        let (_, count, allocated_at) = self.temp_registers_in_use.swap_remove(idx);
+        let now = self.insns.len();
+        if self
+            .constant_spans
+            .iter()
+            .any(|(span_start, span_end)| *span_start < now && *span_end >= allocated_at)
+        {
+            return;
+        }
+        if self.poison_temp_registers {
+            self.emit_insn(Insn::ReleaseReg {
+                start_reg: start,
+                count,
+            });
+        }
+        self.free_temp_registers.push((start, count));
+    }
+
+    /// Run `f` with `amount` temporary registers, passing it the first one, and release them
+    /// when `f` returns, including when it returns an error. The registers must not be used
+    /// by instructions emitted after `f` returns.
+    pub fn with_temp_registers<T>(
+        &mut self,
+        amount: usize,
+        f: impl FnOnce(&mut Self, usize) -> T,
+    ) -> T {
+        let start = self.alloc_temp_registers(amount);
+        let result = f(self, start);
+        self.release_temp_registers(start);
+        result
+    }
+
     /// Returns the next register that will be allocated by alloc_register/alloc_registers.
     pub const fn peek_next_register(&self) -> usize {
         self.next_free_register
@@ -1004,6 +1146,30 @@ impl ProgramBuilder {
         self._alloc_cursor_id(None, cursor_type)
     }
 
+    pub fn alloc_table_cursor(
+        &mut self,
+        key: Option<CursorKey>,
+        table: Arc<BTreeTable>,
+    ) -> TableCursor {
+        TableCursor(self._alloc_cursor_id(key, CursorType::BTreeTable(table)))
+    }
+
+    /// Allocate a cursor over the B-tree of `index`. Indexes implemented by an index
+    /// method without a backing B-tree need [Self::alloc_cursor_index] instead.
+    pub fn alloc_index_cursor(&mut self, key: Option<CursorKey>, index: Arc<Index>) -> IndexCursor {
+        IndexCursor(self._alloc_cursor_id(key, CursorType::BTreeIndex(index)))
+    }
+
+    pub fn alloc_sorter_cursor(&mut self) -> SorterCursor {
+        SorterCursor(self._alloc_cursor_id(None, CursorType::Sorter))
+    }
+
+    pub fn alloc_pseudo_cursor(&mut self, column_count: usize) -> PseudoCursor {
+        PseudoCursor(
+            self._alloc_cursor_id(None, CursorType::Pseudo(PseudoCursorType { column_count })),
+        )
+    }
+
     fn _alloc_cursor_id(&mut self, key: Option<CursorKey>, cursor_type: CursorType) -> usize {
         let cursor = self.next_free_cursor_id;
         self.next_free_cursor_id += 1;
@@ -1232,9 +1398,13 @@ impl ProgramBuilder {
         BranchOffset::Offset(self.insns.len() as InsnReference)
     }
 
+    #[track_caller]
     pub fn allocate_label(&mut self) -> BranchOffset {
         let label_n = self.label_to_resolved_offset.len();
         self.label_to_resolved_offset.push(None);
+        #[cfg(debug_assertions)]
+        self.label_allocation_sites
+            .push(core::panic::Location::caller());
         BranchOffset::Label(label_n as u32)
     }
 
@@ -1286,234 +1456,61 @@ impl ProgramBuilder {
     ///
     /// This function scans all instructions and resolves any labels to their corresponding offsets.
     /// It ensures that all labels are resolved correctly and updates the target program counter (PC)
-    /// of each instruction that references a label.
+    /// of each instruction that references a label. If any referenced label was never resolved,
+    /// nothing is rewritten and the error lists every such label, together with where it was
+    /// allocated in debug builds.
     pub fn resolve_labels(&mut self) -> crate::Result<()> {
-        let resolve = |pc: &mut BranchOffset, insn_name: &str| -> crate::Result<()> {
-            if let BranchOffset::Label(label) = pc {
-                let Some(Some(anchor)) = self.label_to_resolved_offset.get(*label as usize) else {
-                    crate::bail_corrupt_error!(
-                        "Reference to undefined or unresolved label in {insn_name}: {label}"
-                    );
-                };
-                *pc = BranchOffset::Offset(anchor + 1);
+        let mut unresolved = Vec::new();
+        for (offset, (insn, _)) in self.insns.iter_mut().enumerate() {
+            for pc in insn.branch_targets_mut() {
+                if let BranchOffset::Label(label) = *pc {
+                    if !matches!(
+                        self.label_to_resolved_offset.get(label as usize),
+                        Some(Some(_))
+                    ) {
+                        unresolved.push((label, offset, InsnVariants::from(&*insn)));
+                    }
+                }
             }
-            Ok(())
-        };
+        }
+        if !unresolved.is_empty() {
+            let labels = unresolved
+                .iter()
+                .map(|(label, offset, variant)| {
+                    self.describe_unresolved_label(*label, *offset, *variant)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            crate::bail_corrupt_error!("Reference to undefined or unresolved label: {labels}");
+        }
         for (insn, _) in self.insns.iter_mut() {
-            match insn {
-                Insn::Init { target_pc } => {
-                    resolve(target_pc, "Init")?;
-                }
-                Insn::Eq {
-                    lhs: _lhs,
-                    rhs: _rhs,
-                    target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "Eq")?;
-                }
-                Insn::Ne {
-                    lhs: _lhs,
-                    rhs: _rhs,
-                    target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "Ne")?;
-                }
-                Insn::Lt {
-                    lhs: _lhs,
-                    rhs: _rhs,
-                    target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "Lt")?;
-                }
-                Insn::Le {
-                    lhs: _lhs,
-                    rhs: _rhs,
-                    target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "Le")?;
-                }
-                Insn::Gt {
-                    lhs: _lhs,
-                    rhs: _rhs,
-                    target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "Gt")?;
-                }
-                Insn::Ge {
-                    lhs: _lhs,
-                    rhs: _rhs,
-                    target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "Ge")?;
-                }
-                Insn::If {
-                    reg: _reg,
-                    target_pc,
-                    jump_if_null: _,
-                } => {
-                    resolve(target_pc, "If")?;
-                }
-                Insn::IfNot {
-                    reg: _reg,
-                    target_pc,
-                    jump_if_null: _,
-                } => {
-                    resolve(target_pc, "IfNot")?;
-                }
-                Insn::Rewind { pc_if_empty, .. } => {
-                    resolve(pc_if_empty, "Rewind")?;
-                }
-                Insn::Last { pc_if_empty, .. } => {
-                    resolve(pc_if_empty, "Last")?;
-                }
-                Insn::Goto { target_pc } => {
-                    resolve(target_pc, "Goto")?;
-                }
-                Insn::DecrJumpZero {
-                    reg: _reg,
-                    target_pc,
-                } => {
-                    resolve(target_pc, "DecrJumpZero")?;
+            for pc in insn.branch_targets_mut() {
+                if let BranchOffset::Label(label) = pc {
+                    let anchor = self.label_to_resolved_offset[*label as usize]
+                        .expect("label resolution was checked above");
+                    *pc = BranchOffset::Offset(anchor + 1);
                 }
-                Insn::SorterNext {
-                    cursor_id: _cursor_id,
-                    pc_if_next,
-                } => {
-                    resolve(pc_if_next, "SorterNext")?;
-                }
-                Insn::SorterSort { pc_if_empty, .. } => {
-                    resolve(pc_if_empty, "SorterSort")?;
-                }
-                Insn::SorterCompare {
-                    pc_when_nonequal: target_pc,
-                    ..
-                } => {
-                    resolve(target_pc, "SorterCompare")?;
-                }
-                Insn::NotNull {
-                    reg: _reg,
-                    target_pc,
-                } => {
-                    resolve(target_pc, "NotNull")?;
-                }
-                Insn::ColumnHasField { target_pc, .. } => {
-                    resolve(target_pc, "ColumnHasField")?;
-                }
-                Insn::IfPos { target_pc, .. } => {
-                    resolve(target_pc, "IfPos")?;
-                }
-                Insn::Next { pc_if_next, .. } => {
-                    resolve(pc_if_next, "Next")?;
-                }
-                Insn::Once {
-                    target_pc_when_reentered,
-                    ..
-                } => {
-                    resolve(target_pc_when_reentered, "Once")?;
-                }
-                Insn::Prev { pc_if_prev, .. } => {
-                    resolve(pc_if_prev, "Prev")?;
-                }
-                Insn::InitCoroutine {
-                    yield_reg: _,
-                    jump_on_definition,
-                    start_offset,
-                } => {
-                    resolve(jump_on_definition, "InitCoroutine")?;
-                    resolve(start_offset, "InitCoroutine")?;
-                }
-                Insn::NotExists {
-                    cursor: _,
-                    rowid_reg: _,
-                    target_pc,
-                } => {
-                    resolve(target_pc, "NotExists")?;
-                }
-                Insn::MustBeInt {
-                    target_pc: Some(target_pc),
-                    ..
-                } => {
-                    resolve(target_pc, "MustBeInt")?;
-                }
-                Insn::Yield {
-                    yield_reg: _,
-                    end_offset,
-                    subtype_clear_start_reg: _,
-                    subtype_clear_count: _,
-                } => {
-                    resolve(end_offset, "Yield")?;
-                }
-                Insn::SeekRowid { target_pc, .. } => {
-                    resolve(target_pc, "SeekRowid")?;
-                }
-                Insn::Gosub { target_pc, .. } => {
-                    resolve(target_pc, "Gosub")?;
-                }
-                Insn::Jump {
-                    target_pc_eq,
-                    target_pc_lt,
-                    target_pc_gt,
-                } => {
-                    resolve(target_pc_eq, "Jump")?;
-                    resolve(target_pc_lt, "Jump")?;
-                    resolve(target_pc_gt, "Jump")?;
-                }
-                Insn::SeekGE { target_pc, .. } => resolve(target_pc, "SeekGE")?,
-                Insn::SeekGT { target_pc, .. } => resolve(target_pc, "SeekGT")?,
-                Insn::SeekLE { target_pc, .. } => resolve(target_pc, "SeekLE")?,
-                Insn::SeekLT { target_pc, .. } => resolve(target_pc, "SeekLT")?,
-                Insn::IdxGE { target_pc, .. } => resolve(target_pc, "IdxGE")?,
-                Insn::IdxLE { target_pc, .. } => resolve(target_pc, "IdxLE")?,
-                Insn::IdxGT { target_pc, .. } => resolve(target_pc, "IdxGT")?,
-                Insn::IdxLT { target_pc, .. } => resolve(target_pc, "IdxLT")?,
-                Insn::IndexMethodQuery { pc_if_empty, .. } => {
-                    resolve(pc_if_empty, "IndexMethodQuery")?;
-                }
-                Insn::IsNull { reg: _, target_pc } => resolve(target_pc, "IsNull")?,
-                Insn::VNext { pc_if_next, .. } => resolve(pc_if_next, "VNext")?,
-                Insn::VFilter { pc_if_empty, .. } => resolve(pc_if_empty, "VFilter")?,
-                Insn::RowSetRead { pc_if_empty, .. } => resolve(pc_if_empty, "RowSetRead")?,
-                Insn::RowSetTest { pc_if_found, .. } => resolve(pc_if_found, "RowSetTest")?,
-                Insn::NoConflict { target_pc, .. } => resolve(target_pc, "NoConflict")?,
-                Insn::Found { target_pc, .. } => resolve(target_pc, "Found")?,
-                Insn::NotFound { target_pc, .. } => resolve(target_pc, "NotFound")?,
-                Insn::FkIfZero { target_pc, .. } => resolve(target_pc, "FkIfZero")?,
-                Insn::Filter { target_pc, .. } => resolve(target_pc, "Filter")?,
-                Insn::HashProbe { target_pc, .. } => resolve(target_pc, "HashProbe")?,
-                Insn::HashNext { target_pc, .. } => resolve(target_pc, "HashNext")?,
-                Insn::HashDistinct { data } => resolve(&mut data.target_pc, "HashDistinct")?,
-                Insn::HashScanUnmatched { target_pc, .. } => {
-                    resolve(target_pc, "HashScanUnmatched")?
-                }
-                Insn::HashNextUnmatched { target_pc, .. } => {
-                    resolve(target_pc, "HashNextUnmatched")?
-                }
-                Insn::HashGraceInit { target_pc, .. } => resolve(target_pc, "HashGraceInit")?,
-                Insn::HashGraceLoadPartition { target_pc, .. } => {
-                    resolve(target_pc, "HashGraceLoadPartition")?
-                }
-                Insn::HashGraceNextProbe { target_pc, .. } => {
-                    resolve(target_pc, "HashGraceNextProbe")?
-                }
-                Insn::HashGraceAdvancePartition { target_pc, .. } => {
-                    resolve(target_pc, "HashGraceAdvancePartition")?
-                }
-                Insn::Program {
-                    ignore_jump_target, ..
-                } => resolve(ignore_jump_target, "Program")?,
-                _ => {}
             }
         }
         self.label_to_resolved_offset.clear();
+        #[cfg(debug_assertions)]
+        self.label_allocation_sites.clear();
         Ok(())
     }
 
+    fn describe_unresolved_label(
+        &self,
+        label: u32,
+        offset: usize,
+        variant: InsnVariants,
+    ) -> String {
+        #[cfg(debug_assertions)]
+        if let Some(site) = self.label_allocation_sites.get(label as usize) {
+            return format!("label {label} in {variant:?} at {offset} (allocated at {site})");
+        }
+        format!("label {label} in {variant:?} at {offset}")
+    }
+
     /// Set a cursor override for a table. When resolving a table cursor for this table,
     /// the override cursor will be used instead of the normal resolution.
     pub fn set_cursor_override(&mut self, table_ref_id: TableInternalId, cursor_id: CursorID) {
@@ -1858,6 +1855,59 @@ impl ProgramBuilder {
         }
     }
 
+    pub fn emit_rowid(&mut self, cursor: TableCursor, dest: usize) {
+        self.emit_insn(Insn::RowId {
+            cursor_id: cursor.id(),
+            dest,
+        });
+    }
+
+    pub fn emit_idx_rowid(&mut self, cursor: IndexCursor, dest: usize) {
+        self.emit_insn(Insn::IdxRowId {
+            cursor_id: cursor.id(),
+            dest,
+        });
+    }
+
+    /// Copy the current sorted row of `sorter` into `dest_reg` and point `pseudo` at it.
+    pub fn emit_sorter_data(
+        &mut self,
+        sorter: SorterCursor,
+        pseudo: PseudoCursor,
+        dest_reg: usize,
+    ) {
+        self.emit_insn(Insn::SorterData {
+            cursor_id: sorter.id(),
+            dest_reg,
+            pseudo_cursor: pseudo.id(),
+        });
+    }
+
+    /// Sort `sorter` and emit `body` once per sorted row, skipping the loop entirely when
+    /// the sorter is empty. `labels` are resolved here, but may be jumped to from code
+    /// emitted before the loop.
+    pub fn emit_sorter_loop<T>(
+        &mut self,
+        sorter: SorterCursor,
+        labels: SorterLoopLabels,
+        body: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let loop_start = self.allocate_label();
+        self.emit_insn(Insn::SorterSort {
+            cursor_id: sorter.id(),
+            pc_if_empty: labels.end,
+        });
+        self.preassign_label_to_next_insn(loop_start);
+        let result = body(self)?;
+        self.preassign_label_to_next_insn(labels.next);
+        self.emit_insn(Insn::SorterNext {
+            cursor_id: sorter.id(),
+            pc_if_next: loop_start,
+        });
+        self.preassign_label_to_next_insn(labels.end);
+        Ok(result)
+    }
+
     /// Emit a ColumnHasField instruction that jumps to `target_pc` if the
     /// cursor's record has a field at the given logical column index.
     /// Falls through if the record is short (ALTER TABLE ADD COLUMN).
@@ -1976,6 +2026,7 @@ impl ProgramBuilder {
         sql: &str,
     ) -> crate::Result<PreparedProgram> {
         self.resolve_labels()?;
+        peephole::optimize(&mut self.insns, &mut self.comments);
 
         self.parameters.list.dedup();
 
@@ -2024,3 +2075,177 @@ impl ProgramBuilder {
         Ok(Program::from_prepared(Arc::new(prepared), connection))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> ProgramBuilder {
+        ProgramBuilder::new(QueryMode::Normal, None, ProgramBuilderOpts::new(1, 32, 2))
+    }
+
+    #[test]
+    fn test_released_temp_registers_are_reused() {
+        let mut program = builder();
+        let a = program.alloc_temp_registers(3);
+        program.emit_insn(Insn::Null {
+            dest: a,
+            dest_end: Some(a + 2),
+        });
+        program.release_temp_registers(a);
+        let b = program.alloc_temp_registers(1);
+        let c = program.alloc_temp_registers(2);
+        assert_eq!((b, c), (a, a + 1));
+        // The released range is used up, so the next temporary is a fresh register.
+        let d = program.alloc_temp_registers(1);
+        assert_eq!(d, a + 3);
+        assert_eq!(program.peek_next_register(), a + 4);
+    }
+
+    #[test]
+    fn test_temp_registers_written_by_constant_code_are_not_reused() {
+        let mut program = builder();
+        let a = program.alloc_temp_registers(1);
+        let span = program.constant_span_start();
+        program.emit_insn(Insn::Integer { value: 1, dest: a });
+        program.constant_span_end(span);
+        program.release_temp_registers(a);
+        assert_ne!(program.alloc_temp_registers(1), a);
+    }
+
+    #[test]
+    fn test_released_temp_registers_are_poisoned() {
+        let mut program = builder();
+        program.set_poison_temp_registers(true);
+        let a = program.alloc_temp_registers(2);
+        program.emit_insn(Insn::Null {
+            dest: a,
+            dest_end: Some(a + 1),
+        });
+        program.release_temp_registers(a);
+        assert!(matches!(
+            program.insns.last(),
+            Some((Insn::ReleaseReg { start_reg, count: 2 }, _)) if *start_reg == a
+        ));
+
+        // Constants keep their registers for the whole program, so there is
+        // nothing to poison.
+        let b = program.alloc_temp_registers(1);
+        let span = program.constant_span_start();
+        program.emit_insn(Insn::Integer { value: 1, dest: b });
+        program.constant_span_end(span);
+        program.release_temp_registers(b);
+        assert!(matches!(
+            program.insns.last(),
+            Some((Insn::Integer { .. }, _))
+        ));
+    }
+
+    #[test]
+    fn test_with_temp_registers_releases_on_error() {
+        let mut program = builder();
+        let mut allocated = 0;
+        let result: Result<()> = program.with_temp_registers(2, |program, start| {
+            allocated = start;
+            program.emit_insn(Insn::Null {
+                dest: start,
+                dest_end: Some(start + 1),
+            });
+            Err(crate::LimboError::InternalError(
+                "codegen failed".to_string(),
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(program.alloc_temp_registers(2), allocated);
+    }
+
+    #[test]
+    fn test_typed_cursors_read_rowids() {
+        let mut program = builder();
+        let table = Arc::new(BTreeTable::from_sql("CREATE TABLE t (a, b)", 2).unwrap());
+        let index = Index::from_sql(
+            &crate::SymbolTable::new(),
+            "CREATE INDEX t_a ON t (a)",
+            3,
+            &table,
+        )
+        .unwrap();
+        let table_cursor = program.alloc_table_cursor(None, table);
+        let index_cursor = program.alloc_index_cursor(None, Arc::new(index));
+        assert_ne!(table_cursor.id(), index_cursor.id());
+        program.emit_rowid(table_cursor, 1);
+        program.emit_idx_rowid(index_cursor, 2);
+
+        let insns: Vec<_> = program.insns.iter().map(|(insn, _)| insn).collect();
+        assert!(matches!(
+            insns[..],
+            [
+                Insn::RowId { cursor_id: t, dest: 1 },
+                Insn::IdxRowId { cursor_id: i, dest: 2 },
+            ] if *t == table_cursor.id() && *i == index_cursor.id()
+        ));
+    }
+
+    #[test]
+    fn test_unresolved_labels_are_reported_together() {
+        let mut program = builder();
+        let resolved = program.allocate_label();
+        let dangling = program.allocate_label();
+        let also_dangling = program.allocate_label();
+        program.emit_insn(Insn::Goto {
+            target_pc: dangling,
+        });
+        program.preassign_label_to_next_insn(resolved);
+        program.emit_insn(Insn::Goto {
+            target_pc: resolved,
+        });
+        program.emit_insn(Insn::Goto {
+            target_pc: also_dangling,
+        });
+        let err = program.resolve_labels().unwrap_err().to_string();
+        assert!(err.contains("label 1 in Goto at 0"), "{err}");
+        assert!(err.contains("label 2 in Goto at 2"), "{err}");
+        assert!(!err.contains("label 0"), "{err}");
+        #[cfg(debug_assertions)]
+        assert!(err.contains(file!()), "{err}");
+    }
+
+    #[test]
+    fn test_sorter_loop_jumps_back_to_its_body() {
+        let mut program = builder();
+        let sorter = program.alloc_sorter_cursor();
+        let pseudo = program.alloc_pseudo_cursor(1);
+        let data_reg = program.alloc_register();
+        let labels = SorterLoopLabels::new(&mut program);
+        program
+            .emit_sorter_loop(sorter, labels, |program| {
+                program.emit_sorter_data(sorter, pseudo, data_reg);
+                Ok(())
+            })
+            .unwrap();
+        program.emit_insn(Insn::Halt {
+            err_code: 0,
+            description: String::new(),
+            on_error: None,
+            description_reg: None,
+        });
+        program.resolve_labels().unwrap();
+
+        let insns: Vec<_> = program.insns.iter().map(|(insn, _)| insn).collect();
+        assert!(matches!(
+            insns[..],
+            [
+                Insn::SorterSort {
+                    pc_if_empty: BranchOffset::Offset(3),
+                    ..
+                },
+                Insn::SorterData { pseudo_cursor, .. },
+                Insn::SorterNext {
+                    pc_if_next: BranchOffset::Offset(1),
+                    ..
+                },
+                Insn::Halt { .. },
+            ] if *pseudo_cursor == pseudo.id()
+        ));
+    }
+}
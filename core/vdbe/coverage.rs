@@ -0,0 +1,117 @@
+//! Opcode and branch coverage of the programs run on the current thread, for
+//! coverage-guided testing.
+//!
+//! Recording is off until [enable] turns it on. From then on every instruction that
+//! completes counts towards the edge it took: falling through to the next instruction,
+//! or jumping anywhere else.
+
+use std::cell::{Cell, RefCell};
+
+use strum::EnumCount;
+
+use super::insn::{Insn, InsnVariants};
+
+/// How an instruction left the program counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Edge {
+    FallThrough,
+    Jump,
+}
+
+/// An opcode, and the edge it took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CoveragePoint {
+    pub opcode: &'static str,
+    pub edge: Edge,
+}
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static HITS: RefCell<[[u64; 2]; InsnVariants::COUNT]> =
+        const { RefCell::new([[0; 2]; InsnVariants::COUNT]) };
+}
+
+/// Start or stop recording coverage on the current thread.
+pub fn enable(enabled: bool) {
+    ENABLED.with(|cell| cell.set(enabled));
+}
+
+#[inline]
+pub(crate) fn record(insn: &Insn, pc_before: u32, pc_after: u32) {
+    if !ENABLED.with(Cell::get) {
+        return;
+    }
+    let edge = if pc_after == pc_before.wrapping_add(1) {
+        Edge::FallThrough
+    } else {
+        Edge::Jump
+    };
+    HITS.with_borrow_mut(|hits| {
+        let count = &mut hits[insn.discriminant() as usize][edge as usize];
+        *count = count.saturating_add(1);
+    });
+}
+
+/// The points hit on the current thread since the last call, with the number of times
+/// each of them was hit.
+pub fn take() -> Vec<(CoveragePoint, u64)> {
+    HITS.with_borrow_mut(|hits| {
+        let mut points = Vec::new();
+        for (idx, counts) in hits.iter_mut().enumerate() {
+            let Some(variant) = InsnVariants::from_repr(idx as u8) else {
+                continue;
+            };
+            let opcode: &'static str = variant.into();
+            for (edge, count) in [Edge::FallThrough, Edge::Jump].into_iter().zip(counts) {
+                if *count > 0 {
+                    points.push((CoveragePoint { opcode, edge }, std::mem::take(count)));
+                }
+            }
+        }
+        points
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdbe::BranchOffset;
+
+    #[test]
+    fn records_edges_only_while_enabled() {
+        let goto = Insn::Goto {
+            target_pc: BranchOffset::Offset(7),
+        };
+        take();
+        record(&goto, 3, 7);
+        assert!(take().is_empty());
+
+        enable(true);
+        record(&goto, 3, 7);
+        record(&goto, 3, 7);
+        record(&goto, 6, 7);
+        enable(false);
+        let mut points = take();
+        points.sort();
+        assert_eq!(
+            points,
+            vec![
+                (
+                    CoveragePoint {
+                        opcode: "Goto",
+                        edge: Edge::FallThrough
+                    },
+                    1
+                ),
+                (
+                    CoveragePoint {
+                        opcode: "Goto",
+                        edge: Edge::Jump
+                    },
+                    2
+                ),
+            ]
+        );
+        assert!(take().is_empty());
+    }
+}
@@ -17,6 +17,7 @@ use crate::state_machine::StateMachine;
 use crate::storage::btree::{
     integrity_check, CursorTrait, IntegrityCheckError, IntegrityCheckState, PageCategory,
 };
+use crate::storage::checksum::ChecksumContext;
 use crate::storage::database::DatabaseFile;
 use crate::storage::journal_mode;
 use crate::storage::page_cache::PageCache;
@@ -67,7 +68,7 @@ use crate::{
         builder::CursorType,
         insn::{IdxInsertFlags, Insn, SavepointOp},
     },
-    CaptureDataChangesInfo, CdcVersion, CheckpointMode, Completion, Connection, Database,
+    Buffer, CaptureDataChangesInfo, CdcVersion, CheckpointMode, Completion, Connection, Database,
     DatabaseStorage, IOExt, MvCursor, NonNan, OpenFlags, QueryMode, Statement, TransactionState,
     ValueRef, WalAutoActions, MAIN_DB_ID, TEMP_DB_ID,
 };
@@ -80,7 +81,8 @@ use crate::{
     function::{AggFunc, ExtFunc, MathFunc, MathFuncArity, ScalarFunc, VectorFunc},
     functions::{
         datetime::{
-            exec_date, exec_datetime_full, exec_julianday, exec_strftime, exec_time, exec_unixepoch,
+            exec_date, exec_datetime_full, exec_julianday, exec_strftime, exec_time,
+            exec_unixepoch, with_clock,
         },
         printf::exec_printf,
     },
@@ -138,9 +140,8 @@ use turso_parser::parser::Parser;
 
 use super::sorter::Sorter;
 use crate::vdbe::vacuum::{
-    capture_custom_types, mirror_symbols, reject_unsupported_vacuum_auto_vacuum_mode,
-    vacuum_target_build_step, vacuum_target_opts_from_source, VacuumDbHeaderMeta,
-    VacuumTargetBuildConfig, VacuumTargetBuildContext,
+    capture_custom_types, mirror_symbols, vacuum_target_build_step, vacuum_target_opts_from_source,
+    VacuumDbHeaderMeta, VacuumTargetBuildConfig, VacuumTargetBuildContext,
 };
 
 #[cfg(feature = "json")]
@@ -1364,8 +1365,14 @@ pub fn op_vcreate(
         vec![]
     };
     let conn = program.connection.clone();
-    let table =
-        crate::VirtualTable::table(Some(&table_name), &module_name, args, &conn.syms.read())?;
+    let table = crate::VirtualTable::table(
+        Some(&table_name),
+        &module_name,
+        args,
+        &conn.syms.read(),
+        false,
+    )?;
+    table.created(&conn)?;
     {
         conn.syms.write().vtabs.insert(table_name, table);
     }
@@ -1508,7 +1515,7 @@ pub fn op_vupdate(
             unreachable!("sqlite_dbpage writes require cli_only feature");
         }
     } else {
-        virtual_table.update(&argv)
+        virtual_table.update(&argv, &program.connection)
     };
     match result {
         Ok(Some(new_rowid)) => {
@@ -1578,7 +1585,7 @@ pub fn op_vdestroy(
                 "Could not find Virtual Table to Destroy".to_string(),
             ));
         };
-        vtab.destroy()?;
+        vtab.destroy(&conn)?;
     }
 
     state.pc += 1;
@@ -1897,6 +1904,10 @@ fn op_column_fetch(
                     return Ok(InsnFunctionStepResult::Step);
                 }
 
+                if cursor.column_into_register(column, &mut state.registers[dest])? {
+                    return Ok(InsnFunctionStepResult::Step);
+                }
+
                 let record_result = return_if_io!(cursor.record());
                 let Some(record) = record_result else {
                     // Cursor is not positioned on a valid row (e.g., empty table).
@@ -3273,6 +3284,10 @@ pub fn halt(
                 .fk_deferred_violations
                 .swap(0, Ordering::AcqRel);
             if deferred_violations > 0 {
+                let was_writing = matches!(
+                    program.connection.get_tx_state(),
+                    TransactionState::Write { .. }
+                );
                 vtab_rollback_all(&program.connection)?;
                 if let Some(mv_store) = mv_store.as_ref() {
                     if let Some(tx_id) = program.connection.get_mv_tx_id() {
@@ -3283,6 +3298,9 @@ pub fn halt(
                     pager.rollback_tx(&program.connection);
                 }
                 program.connection.set_tx_state(TransactionState::None);
+                if was_writing {
+                    program.connection.run_rollback_hooks();
+                }
                 return Err(LimboError::ForeignKeyConstraint(
                     "deferred foreign key constraint failed".to_string(),
                 ));
@@ -3369,18 +3387,26 @@ pub fn halt(
 }
 
 /// Call xCommit on all virtual tables that participated in the current transaction.
-pub(crate) fn vtab_commit_all(conn: &Connection) -> crate::Result<()> {
-    let mut set = conn.vtab_txn_states.write();
-    if set.is_empty() {
+pub(crate) fn vtab_commit_all(conn: &Arc<Connection>) -> crate::Result<()> {
+    let ids: Vec<_> = conn.vtab_txn_states.write().drain().collect();
+    if ids.is_empty() {
         return Ok(());
     }
-    let reg = &conn.syms.read().vtabs;
-    for id in set.drain() {
-        let vtab = reg
-            .iter()
-            .find(|(_, vtab)| vtab.id() == id)
-            .expect("vtab must exist");
-        vtab.1.commit()?;
+    // modules may run statements on the connection while committing, so no locks
+    // are held during the calls
+    let vtabs: Vec<_> = {
+        let reg = &conn.syms.read().vtabs;
+        ids.into_iter()
+            .map(|id| {
+                reg.values()
+                    .find(|vtab| vtab.id() == id)
+                    .expect("vtab must exist")
+                    .clone()
+            })
+            .collect()
+    };
+    for vtab in vtabs {
+        vtab.commit(conn)?;
     }
     Ok(())
 }
@@ -3410,7 +3436,7 @@ pub(crate) fn index_method_pre_commit_all(
 }
 
 /// Rollback all virtual tables that are part of the current transaction.
-fn vtab_rollback_all(conn: &Connection) -> crate::Result<()> {
+pub(crate) fn vtab_rollback_all(conn: &Connection) -> crate::Result<()> {
     let mut set = conn.vtab_txn_states.write();
     if set.is_empty() {
         return Ok(());
@@ -3721,7 +3747,7 @@ pub fn op_transaction_inner(
             OpTransactionState::Start => {
                 let conn = program.connection.clone();
                 let mut started_secondary_tx = false;
-                if write && conn.is_readonly(*db) {
+                if write && (conn.is_readonly(*db) || conn.is_write_protected()) {
                     return Err(LimboError::ReadOnly);
                 }
                 let active_writers = conn.n_active_writes.load(Ordering::SeqCst);
@@ -4279,7 +4305,8 @@ pub fn op_auto_commit(
     load_insn!(
         AutoCommit {
             auto_commit,
-            rollback
+            rollback,
+            read_only
         },
         insn
     );
@@ -4363,6 +4390,7 @@ pub fn op_auto_commit(
 
         match tx_op {
             TxOp::Rollback => {
+                let was_writing = matches!(conn.get_tx_state(), TransactionState::Write { .. });
                 if let Some(mv_store) = mv_store.as_ref() {
                     if let Some(tx_id) = conn.get_mv_tx_id() {
                         mv_store.rollback_tx(tx_id, pager.clone(), &conn, MAIN_DB_ID);
@@ -4375,8 +4403,12 @@ pub fn op_auto_commit(
                 conn.rollback_attached_wal_txns();
                 conn.rollback_temp_schema();
                 conn.set_tx_state(TransactionState::None);
+                conn.read_only_tx.store(false, Ordering::SeqCst);
                 conn.auto_commit.store(true, Ordering::SeqCst);
                 conn.set_cdc_transaction_id(-1);
+                if was_writing {
+                    conn.run_rollback_hooks();
+                }
             }
             TxOp::Commit => {
                 if conn.tx_is_poisoned() {
@@ -4392,6 +4424,7 @@ pub fn op_auto_commit(
                 }
                 // Pre-check deferred FKs; leave tx open and do NOT clear violations
                 check_deferred_fk_on_commit(&conn)?;
+                conn.read_only_tx.store(false, Ordering::SeqCst);
                 conn.auto_commit.store(true, Ordering::SeqCst);
             }
             TxOp::Begin => {
@@ -4399,6 +4432,7 @@ pub fn op_auto_commit(
                     !conn.tx_is_poisoned(),
                     "rollback-only marker leaked outside an explicit transaction"
                 );
+                conn.read_only_tx.store(*read_only, Ordering::SeqCst);
                 conn.auto_commit.store(false, Ordering::SeqCst);
                 return Ok(InsnFunctionStepResult::Done);
             }
@@ -4588,6 +4622,7 @@ pub fn op_savepoint(
                             !conn.tx_is_poisoned(),
                             "rollback-only marker leaked outside an explicit transaction"
                         );
+                        conn.read_only_tx.store(false, Ordering::SeqCst);
                         conn.auto_commit.store(false, Ordering::SeqCst);
                     }
 
@@ -4627,6 +4662,7 @@ pub fn op_savepoint(
                     let auto_commit = Insn::AutoCommit {
                         auto_commit: true,
                         rollback: false,
+                        read_only: false,
                     };
                     return op_auto_commit(program, state, &auto_commit, pager);
                 }
@@ -7067,6 +7103,7 @@ pub fn op_agg_step(
                     init,
                     step,
                     finalize,
+                    window,
                     argc,
                     aggregate_destructor,
                     value_destructor,
@@ -7077,6 +7114,7 @@ pub fn op_agg_step(
                     argc: (*argc).max(0) as usize,
                     step_fn: *step,
                     finalize_fn: *finalize,
+                    value_fn: window.map(|(value, _)| value),
                     aggregate_destructor: *aggregate_destructor,
                     value_destructor: *value_destructor,
                 })),
@@ -7227,6 +7265,10 @@ pub fn op_agg_final(
     match &state.registers[acc_reg] {
         Register::Aggregate(agg) => {
             let value = match agg {
+                AggContext::External(_) if matches!(insn, Insn::AggValue { .. }) => {
+                    // Window aggregates keep stepping after producing a value
+                    agg.external_value()?
+                }
                 AggContext::External(_) => {
                     // External aggregates use FFI finalization
                     agg.compute_external()?
@@ -7365,6 +7407,10 @@ pub fn op_sorter_open(
         sort_comparators.push(comparator);
     }
     let temp_store = program.connection.get_temp_store();
+    let memory_limit = program.connection.get_statement_memory_limit();
+    state
+        .memory
+        .set_limit(usize::try_from(memory_limit).unwrap_or(usize::MAX));
     let cursor = Sorter::new(
         &order,
         collations,
@@ -7374,6 +7420,8 @@ pub fn op_sorter_open(
         page_size,
         pager.io.clone(),
         temp_store,
+        program.connection.temp_directory(),
+        state.memory.charge(),
     )?;
     let cursors = &mut state.cursors;
     cursors
@@ -8075,6 +8123,22 @@ pub fn op_function(
                 let result = Value::exec_concat_ws(reg_values.iter().map(|reg| reg.get_value()));
                 state.registers[*dest].set_value(result);
             }
+            ScalarFunc::RowHash => {
+                let reg_values = &state.registers[*start_reg..*start_reg + arg_count];
+                let result = Value::exec_row_hash(reg_values.iter().map(|reg| reg.get_value()));
+                state.registers[*dest].set_value(result);
+            }
+            ScalarFunc::HashTable => {
+                // one row of hash_table(): the digest so far, then the columns of the row
+                let digest = match state.registers[*start_reg].get_value() {
+                    Value::Numeric(Numeric::Integer(digest)) => *digest as u64,
+                    _ => 0,
+                };
+                let row = &state.registers[*start_reg + 1..*start_reg + arg_count];
+                let row_hash = Value::row_hash(row.iter().map(|reg| reg.get_value()));
+                state.registers[*dest]
+                    .set_value(Value::from_i64(digest.wrapping_add(row_hash) as i64));
+            }
             ScalarFunc::Glob => {
                 if arg_count != 2 {
                     mark_unlikely();
@@ -8444,13 +8508,13 @@ pub fn op_function(
             ScalarFunc::Date => {
                 let values =
                     registers_to_ref_values(&state.registers[*start_reg..*start_reg + arg_count]);
-                let result = exec_date(values);
+                let result = with_clock(pager.io.clone(), || exec_date(values));
                 state.registers[*dest].set_value(result);
             }
             ScalarFunc::Time => {
                 let values =
                     registers_to_ref_values(&state.registers[*start_reg..*start_reg + arg_count]);
-                let result = exec_time(values);
+                let result = with_clock(pager.io.clone(), || exec_time(values));
                 state.registers[*dest].set_value(result);
             }
             ScalarFunc::TimeDiff => {
@@ -8460,7 +8524,9 @@ pub fn op_function(
                     let start = state.registers[*start_reg].get_value();
                     let end = state.registers[*start_reg + 1].get_value();
 
-                    let result = crate::functions::datetime::exec_timediff([start, end]);
+                    let result = with_clock(pager.io.clone(), || {
+                        crate::functions::datetime::exec_timediff([start, end])
+                    });
 
                     state.registers[*dest].set_value(result);
                 }
@@ -8473,19 +8539,19 @@ pub fn op_function(
             ScalarFunc::DateTime => {
                 let values =
                     registers_to_ref_values(&state.registers[*start_reg..*start_reg + arg_count]);
-                let result = exec_datetime_full(values);
+                let result = with_clock(pager.io.clone(), || exec_datetime_full(values));
                 state.registers[*dest].set_value(result);
             }
             ScalarFunc::JulianDay => {
                 let values =
                     registers_to_ref_values(&state.registers[*start_reg..*start_reg + arg_count]);
-                let result = exec_julianday(values);
+                let result = with_clock(pager.io.clone(), || exec_julianday(values));
                 state.registers[*dest].set_value(result);
             }
             ScalarFunc::UnixEpoch => {
                 let values =
                     registers_to_ref_values(&state.registers[*start_reg..*start_reg + arg_count]);
-                let result = exec_unixepoch(values);
+                let result = with_clock(pager.io.clone(), || exec_unixepoch(values));
                 state.registers[*dest].set_value(result);
             }
             ScalarFunc::TursoVersion => {
@@ -8535,7 +8601,7 @@ pub fn op_function(
             ScalarFunc::StrfTime => {
                 let values =
                     registers_to_ref_values(&state.registers[*start_reg..*start_reg + arg_count]);
-                let result = exec_strftime(values);
+                let result = with_clock(pager.io.clone(), || exec_strftime(values));
                 state.registers[*dest].set_value(result);
             }
             ScalarFunc::Printf => {
@@ -11439,6 +11505,23 @@ pub fn op_soft_null(
     Ok(InsnFunctionStepResult::Step)
 }
 
+/// Poison released temporary registers with an invalidated record, which
+/// [Register::get_value] refuses to read.
+pub fn op_release_reg(
+    _program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    _pager: &Arc<Pager>,
+) -> Result<InsnFunctionStepResult> {
+    load_insn!(ReleaseReg { start_reg, count }, insn);
+    for reg in &mut state.registers[*start_reg..*start_reg + *count] {
+        let buf = reg.take_buf();
+        *reg = Register::Record(ImmutableRecord::from_buf(buf));
+    }
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
 #[derive(Clone, Copy)]
 pub enum OpNoConflictState {
     Start,
@@ -13950,7 +14033,7 @@ pub fn op_open_ephemeral(
             let conn = program.connection.clone();
             let io = conn.pager.load().io.clone();
             let temp_store = conn.get_temp_store();
-            let temp_file = TempFile::with_temp_store(&io, temp_store)?;
+            let temp_file = TempFile::with_temp_store(&io, temp_store, &conn.temp_directory())?;
             let db_file: Arc<dyn DatabaseStorage> =
                 Arc::new(DatabaseFile::new(temp_file.file.clone()));
             let db_file_io: Arc<dyn crate::IO> = io;
@@ -13965,7 +14048,7 @@ pub fn op_open_ephemeral(
                 db_file,
                 None,
                 db_file_io,
-                PageCache::default(),
+                PageCache::with_policy(conn.db.page_cache_policy()),
                 buffer_pool,
                 Arc::new(Mutex::new(())),
                 ephemeral_init_page_1,
@@ -14387,6 +14470,17 @@ fn has_freelist_error(errors: &[IntegrityCheckError]) -> bool {
 
 pub enum OpIntegrityCheckState {
     Start,
+    /// Verify the checksum of every page in the database file, before the
+    /// b-tree walk trips over a page that fails it.
+    CheckingPageChecksums {
+        errors: Vec<IntegrityCheckError>,
+        next_page: usize,
+        last_page: usize,
+        read: Option<(Arc<Buffer>, Completion)>,
+    },
+    StartingBTreeStructure {
+        errors: Vec<IntegrityCheckError>,
+    },
     CheckingBTreeStructure {
         errors: Vec<IntegrityCheckError>,
         current_root_idx: usize,
@@ -14427,25 +14521,92 @@ pub fn op_integrity_check(
     let physical_header_store = if passive { None } else { mv_store.as_ref() };
     match state.active_op_state.integrity_check() {
         OpIntegrityCheckState::Start => {
-            let (freelist_trunk_page, db_size) = return_if_io!(with_header(
-                &target_pager,
-                physical_header_store,
-                program,
-                *db,
-                |header| (header.freelist_trunk_page.get(), header.database_size.get())
-            ));
-            let mut errors = Vec::new();
-            let mut integrity_check_state = IntegrityCheckState::new(db_size as usize);
-            let mut current_root_idx = 0;
-
-            if freelist_trunk_page > 0 {
-                let expected_freelist_count = return_if_io!(with_header(
+            let errors = Vec::new();
+            *state.active_op_state.integrity_check() = if target_pager.is_checksum_ctx_set() {
+                let page_size = target_pager.get_page_size_unchecked().get() as u64;
+                OpIntegrityCheckState::CheckingPageChecksums {
+                    errors,
+                    next_page: 1,
+                    last_page: (target_pager.db_file.size()? / page_size) as usize,
+                    read: None,
+                }
+            } else {
+                OpIntegrityCheckState::StartingBTreeStructure { errors }
+            };
+        }
+        OpIntegrityCheckState::CheckingPageChecksums {
+            errors,
+            next_page,
+            last_page,
+            read,
+        } => {
+            while *next_page <= *last_page && errors.len() < *max_errors {
+                let Some((buf, c)) = read.take() else {
+                    let (buf, c) = target_pager.read_db_file_page_unverified(*next_page)?;
+                    *read = Some((buf, c.clone()));
+                    return Ok(InsnFunctionStepResult::IO(IOCompletions(c)));
+                };
+                if let Some(err) = c.get_error() {
+                    return Err(LimboError::CompletionError(err));
+                }
+                let page_idx = *next_page;
+                *next_page += 1;
+                let checksum_ctx = ChecksumContext::new();
+                if checksum_ctx
+                    .verify_checksum(buf.as_mut_slice(), page_idx)
+                    .is_ok()
+                {
+                    continue;
+                }
+                errors.push(if target_pager.is_page_in_wal(page_idx)? {
+                    IntegrityCheckError::PageChecksumMismatchRepairable {
+                        page_id: page_idx as i64,
+                    }
+                } else {
+                    IntegrityCheckError::PageChecksumMismatch {
+                        page_id: page_idx as i64,
+                    }
+                });
+            }
+            // The b-tree walk would fail on the first page it can't verify, so
+            // report the bad pages alone.
+            let unreadable = errors
+                .iter()
+                .any(|e| matches!(e, IntegrityCheckError::PageChecksumMismatch { .. }));
+            if unreadable || errors.len() >= *max_errors {
+                errors.truncate(*max_errors);
+                match format_integrity_check_result(errors) {
+                    Some(msg) => state.registers[*message_register].set_text(Text::new(msg))?,
+                    None => state.registers[*message_register].set_null(),
+                }
+                state.active_op_state.clear();
+                state.pc += 1;
+                return Ok(InsnFunctionStepResult::Step);
+            }
+            let errors = std::mem::take(errors);
+            *state.active_op_state.integrity_check() =
+                OpIntegrityCheckState::StartingBTreeStructure { errors };
+        }
+        OpIntegrityCheckState::StartingBTreeStructure { errors } => {
+            let (freelist_trunk_page, db_size, expected_freelist_count) =
+                return_if_io!(with_header(
                     &target_pager,
                     physical_header_store,
                     program,
                     *db,
-                    |header| { header.freelist_pages.get() }
+                    |header| (
+                        header.freelist_trunk_page.get(),
+                        header.database_size.get(),
+                        header.freelist_pages.get()
+                    )
                 ));
+            // Taken only once no more IO is needed, so a yield can't lose
+            // errors from the checksum pass.
+            let mut errors = std::mem::take(errors);
+            let mut integrity_check_state = IntegrityCheckState::new(db_size as usize);
+            let mut current_root_idx = 0;
+
+            if freelist_trunk_page > 0 {
                 integrity_check_state.set_expected_freelist_count(expected_freelist_count as usize);
                 integrity_check_state.start(
                     freelist_trunk_page as i64,
@@ -15557,6 +15718,7 @@ pub fn op_hash_build(
             num_keys: data.num_keys,
             collations: data.collations.try_to_vec()?,
             temp_store,
+            temp_dir: program.connection.temp_directory(),
             track_matched: data.track_matched,
             partition_count: None,
         };
@@ -15663,6 +15825,7 @@ pub fn op_hash_distinct(
             num_keys: data.num_keys,
             collations: data.collations.try_to_vec()?,
             temp_store,
+            temp_dir: program.connection.temp_directory(),
             track_matched: false,
             partition_count: None,
         };
@@ -17191,7 +17354,6 @@ fn op_vacuum_into_inner(
                     .connection
                     .get_pager_from_database_index(&source_db_id)?;
                 let source_auto_vacuum_mode = source_pager.get_auto_vacuum_mode();
-                reject_unsupported_vacuum_auto_vacuum_mode(source_auto_vacuum_mode)?;
                 let header_meta = if let Some(mv_store) =
                     program.connection.mv_store_for_db(source_db_id)
                 {
@@ -17312,6 +17474,40 @@ fn op_vacuum_into_inner(
     }
 }
 
+pub fn op_rekey(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    _pager: &Arc<Pager>,
+) -> Result<InsnFunctionStepResult> {
+    load_insn!(Rekey { db, hex_key }, insn);
+    turso_assert!(
+        *db == crate::MAIN_DB_ID,
+        "only the main database can be rekeyed"
+    );
+    let key = crate::storage::encryption::EncryptionKey::from_hex_string(hex_key)?;
+    program.connection.rekey(key)?;
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
+pub fn op_incr_vacuum(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    _pager: &Arc<Pager>,
+) -> Result<InsnFunctionStepResult> {
+    load_insn!(IncrVacuum { db, max_pages }, insn);
+    // MVCC only does pager operations in checkpoint
+    if program.connection.mv_store_for_db(*db).is_none() {
+        program
+            .get_pager_from_database_index(db)?
+            .incremental_vacuum(*max_pages)?;
+    }
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
 /// In-place VACUUM - compact the database via target build + direct-WAL
 /// copy-back. The opcode owns the source transaction lifecycle.
 pub fn op_vacuum(
@@ -1575,6 +1575,15 @@ pub fn insn_to_row(
                 0,
                 "".to_string(),
             ),
+            Insn::ReleaseReg { start_reg, count } => (
+                "ReleaseReg",
+                *start_reg as i64,
+                *count as i64,
+                0,
+                Value::build_text(""),
+                0,
+                format!("release r[{}..{}]", start_reg, start_reg + count - 1),
+            ),
             Insn::NoConflict {
                 cursor_id,
                 target_pc,
@@ -1652,7 +1661,15 @@ pub fn insn_to_row(
                 *extra_amount as i64,
                 Value::build_text(""),
                 0,
-                format!("r[{dst_reg}]=r[{src_reg}]"),
+                if *extra_amount == 0 {
+                    format!("r[{dst_reg}]=r[{src_reg}]")
+                } else {
+                    format!(
+                        "r[{dst_reg}..{}]=r[{src_reg}..{}]",
+                        dst_reg + extra_amount,
+                        src_reg + extra_amount
+                    )
+                },
             ),
             Insn::CreateBtree { db, root, flags } => (
                 "CreateBtree",
@@ -2107,14 +2124,19 @@ pub fn insn_to_row(
             Insn::AutoCommit {
                 auto_commit,
                 rollback,
+                read_only,
             } => (
                 "AutoCommit",
                 *auto_commit as i64,
                 *rollback as i64,
-                0,
+                *read_only as i64,
                 Value::build_text(""),
                 0,
-                format!("auto_commit={auto_commit}, rollback={rollback}"),
+                if *read_only {
+                    format!("auto_commit={auto_commit}, rollback={rollback}, read_only")
+                } else {
+                    format!("auto_commit={auto_commit}, rollback={rollback}")
+                },
             ),
             Insn::Savepoint { op, name } => (
                 "Savepoint",
@@ -2350,6 +2372,25 @@ pub fn insn_to_row(
                 format!("r[{dest}]=journal_mode(db[{db}]{})",
                     new_mode.as_ref().map_or(String::new(), |m| format!(",'{m}'"))),
             ),
+            // The key is left out of the output.
+            Insn::Rekey { db, .. } => (
+                "Rekey",
+                *db as i64,
+                0,
+                0,
+                Value::Null,
+                0,
+                format!("rekey(db[{db}])"),
+            ),
+            Insn::IncrVacuum { db, max_pages } => (
+                "IncrVacuum",
+                *db as i64,
+                *max_pages as i64,
+                0,
+                Value::Null,
+                0,
+                format!("incremental_vacuum(db[{db}], {max_pages})"),
+            ),
             Insn::IfNeg { reg, target_pc } => (
                 "IfNeg",
                 *reg as i64,
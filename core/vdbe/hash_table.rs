@@ -3,7 +3,7 @@ use crate::alloc::*;
 use crate::turso_assert;
 use crate::{
     error::LimboError,
-    io::{Buffer, Completion, TempFile, IO},
+    io::{Buffer, Completion, TempDirectory, TempFile, IO},
     io_yield_one, return_if_io,
     storage::sqlite3_ondisk::{read_varint, read_varint_partial, varint_len, write_varint},
     sync::{
@@ -781,6 +781,8 @@ pub struct HashTableConfig {
     pub collations: Vec<CollationSeq>,
     /// Only spill to a file when != TempStore::Memory
     pub temp_store: crate::TempStore,
+    /// Directory spill files are created in.
+    pub temp_dir: TempDirectory,
     /// Whether to track which entries have been matched during probing (for FULL OUTER JOIN).
     pub track_matched: bool,
     /// Optional override for the number of partitions (must be power of two).
@@ -795,6 +797,7 @@ impl Default for HashTableConfig {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         }
@@ -819,6 +822,7 @@ impl SpillState {
     fn new(
         io: &Arc<dyn IO>,
         temp_store: crate::TempStore,
+        temp_dir: &TempDirectory,
         partitioning: Partitioning,
     ) -> Result<Self> {
         Ok(SpillState {
@@ -827,7 +831,7 @@ impl SpillState {
                 .try_collect()?,
             partitions: vec![],
             next_spill_offset: 0,
-            temp_file: TempFile::with_temp_store(io, temp_store)?,
+            temp_file: TempFile::with_temp_store(io, temp_store, temp_dir)?,
             partitioning,
         })
     }
@@ -868,6 +872,7 @@ impl ProbeSpillState {
     fn new(
         io: &Arc<dyn IO>,
         temp_store: crate::TempStore,
+        temp_dir: &TempDirectory,
         partitioning: Partitioning,
         mem_budget: usize,
     ) -> Result<Self> {
@@ -877,7 +882,7 @@ impl ProbeSpillState {
                 .try_collect()?,
             partitions: vec![],
             next_spill_offset: 0,
-            temp_file: TempFile::with_temp_store(io, temp_store)?,
+            temp_file: TempFile::with_temp_store(io, temp_store, temp_dir)?,
             partitioning,
             mem_used: 0,
             mem_budget,
@@ -984,6 +989,8 @@ pub struct HashTable {
     loaded_partitions_mem: usize,
     /// Temp storage mode (memory vs file) for spilled data
     temp_store: crate::TempStore,
+    /// Directory spill files are created in
+    temp_dir: TempDirectory,
     /// Whether to track matched entries (for FULL OUTER JOIN).
     track_matched: bool,
     /// Parallel to `buckets`: one Vec<bool> per bucket tracking which entries were matched.
@@ -1084,6 +1091,7 @@ impl HashTable {
             loaded_partitions_mem: 0,
             non_empty_buckets: vec![],
             temp_store: config.temp_store,
+            temp_dir: config.temp_dir,
             track_matched: config.track_matched,
             matched_bits,
             unmatched_scan_bucket: 0,
@@ -1201,7 +1209,12 @@ impl HashTable {
                 // Move all existing bucket entries into partition buffers
                 let partition_count = self.choose_partition_count(entry_size);
                 let partitioning = Partitioning::new(partition_count);
-                self.spill_state = Some(SpillState::new(&self.io, self.temp_store, partitioning)?);
+                self.spill_state = Some(SpillState::new(
+                    &self.io,
+                    self.temp_store,
+                    &self.temp_dir,
+                    partitioning,
+                )?);
                 self.redistribute_to_partitions()?;
                 self.state = HashTableState::Spilled;
             };
@@ -1352,7 +1365,12 @@ impl HashTable {
             if self.spill_state.is_none() {
                 let partition_count = self.choose_partition_count(entry_size);
                 let partitioning = Partitioning::new(partition_count);
-                self.spill_state = Some(SpillState::new(&self.io, self.temp_store, partitioning)?);
+                self.spill_state = Some(SpillState::new(
+                    &self.io,
+                    self.temp_store,
+                    &self.temp_dir,
+                    partitioning,
+                )?);
                 self.redistribute_to_partitions()?;
                 self.state = HashTableState::Spilled;
             }
@@ -2706,6 +2724,7 @@ impl HashTable {
             self.probe_spill_state = Some(ProbeSpillState::new(
                 &self.io,
                 self.temp_store,
+                &self.temp_dir,
                 partitioning,
                 self.mem_budget / 2,
             )?);
@@ -3392,6 +3411,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -3434,6 +3454,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -3466,6 +3487,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -3643,6 +3665,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: Some(64),
         };
@@ -3664,6 +3687,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -3688,6 +3712,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: Some(16),
         };
@@ -3741,12 +3766,15 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: Some(16),
         };
         let mut ht = HashTable::new(config, io.clone()).unwrap();
         let partitioning = Partitioning::new(16);
-        let temp_file = TempFile::with_temp_store(&io, crate::TempStore::Default).unwrap();
+        let temp_file =
+            TempFile::with_temp_store(&io, crate::TempStore::Default, &TempDirectory::System)
+                .unwrap();
 
         let mut partition = SpilledPartition::new(0);
         partition.add_chunk(0, 0, 0).unwrap();
@@ -3777,6 +3805,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: Some(16),
         };
@@ -3787,7 +3816,9 @@ mod hashtests {
         entry.serialize(&mut buf).unwrap();
         let truncated = &buf[..buf.len() - 1];
 
-        let temp_file = TempFile::with_temp_store(&io, crate::TempStore::Default).unwrap();
+        let temp_file =
+            TempFile::with_temp_store(&io, crate::TempStore::Default, &TempDirectory::System)
+                .unwrap();
         let write_buf = Buffer::new_temporary(truncated.len());
         write_buf.as_mut_slice().copy_from_slice(truncated);
         let write_buf = Arc::new(write_buf);
@@ -3984,6 +4015,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             ..Default::default()
         };
@@ -4026,6 +4058,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4070,6 +4103,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4120,6 +4154,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4174,6 +4209,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4206,6 +4242,7 @@ mod hashtests {
             num_keys: 2,
             collations: vec![CollationSeq::Binary, CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4251,6 +4288,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4287,6 +4325,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             partition_count: None,
         };
@@ -4441,6 +4480,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             ..Default::default()
         };
@@ -4567,6 +4607,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: false,
             ..Default::default()
         };
@@ -4773,6 +4814,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: true,
             partition_count: Some(4),
         };
@@ -4919,6 +4961,7 @@ mod hashtests {
             num_keys: 1,
             collations: vec![CollationSeq::Binary],
             temp_store: crate::TempStore::Default,
+            temp_dir: TempDirectory::System,
             track_matched: true,
             partition_count: Some(16),
         };
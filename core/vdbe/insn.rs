@@ -19,7 +19,7 @@ use crate::{
     PreparedProgram, Value,
 };
 use strum::EnumCount;
-use strum_macros::{EnumDiscriminants, FromRepr, VariantArray};
+use strum_macros::{EnumDiscriminants, FromRepr, IntoStaticStr, VariantArray};
 use turso_macros::Description;
 use turso_parser::ast::{ResolveType, SortOrder};
 
@@ -276,7 +276,7 @@ pub struct HashDistinctData {
 #[repr(u8)]
 #[derive(Description, Debug, Clone, EnumDiscriminants)]
 #[strum_discriminants(vis(pub(crate)))]
-#[strum_discriminants(derive(VariantArray, EnumCount, FromRepr))]
+#[strum_discriminants(derive(VariantArray, EnumCount, FromRepr, IntoStaticStr))]
 #[strum_discriminants(name(InsnVariants))]
 pub enum Insn {
     /// Initialize the program state and jump to the given PC.
@@ -850,6 +850,8 @@ pub enum Insn {
     AutoCommit {
         auto_commit: bool,
         rollback: bool,
+        /// When starting a transaction, reject writes until it ends (`BEGIN READONLY`).
+        read_only: bool,
     },
 
     /// Execute a named savepoint operation.
@@ -1253,6 +1255,14 @@ pub enum Insn {
         reg: usize,
     },
 
+    /// Mark the `count` registers starting at `start_reg` as released, so that reading one
+    /// of them before it is written again fails. Only emitted when temporary register
+    /// poisoning is enabled for debugging, see [crate::Connection::set_poison_temp_registers].
+    ReleaseReg {
+        start_reg: usize,
+        count: usize,
+    },
+
     /// If P4==0 then register P3 holds a blob constructed by [MakeRecord](https://sqlite.org/opcode.html#MakeRecord).
     /// If P4>0 then register P3 is the first of P4 registers that form an unpacked record.
     ///
@@ -1764,6 +1774,19 @@ pub enum Insn {
         dest: usize,              // P2: output register for result
         new_mode: Option<String>, // P3: new journal mode (if setting)
     },
+    /// Re-encrypt database P1 with the hexadecimal key in P4, see
+    /// [crate::Connection::rekey].
+    Rekey {
+        db: usize,
+        hex_key: String,
+    },
+    /// Take up to P2 pages off the freelist of incremental auto-vacuum
+    /// database P1, or all of them if P2 is 0, see
+    /// [crate::storage::pager::Pager::incremental_vacuum].
+    IncrVacuum {
+        db: usize,
+        max_pages: u32,
+    },
     IfNeg {
         reg: usize,
         target_pc: BranchOffset,
@@ -2121,6 +2144,7 @@ impl InsnVariants {
             InsnVariants::NewRowid => execute::op_new_rowid,
             InsnVariants::MustBeInt => execute::op_must_be_int,
             InsnVariants::SoftNull => execute::op_soft_null,
+            InsnVariants::ReleaseReg => execute::op_release_reg,
             InsnVariants::NoConflict => execute::op_no_conflict,
             InsnVariants::NotExists => execute::op_not_exists,
             InsnVariants::OffsetLimit => execute::op_offset_limit,
@@ -2178,6 +2202,8 @@ impl InsnVariants {
             InsnVariants::AlterColumn => execute::op_alter_column,
             InsnVariants::MaxPgcnt => execute::op_max_pgcnt,
             InsnVariants::JournalMode => execute::op_journal_mode,
+            InsnVariants::Rekey => execute::op_rekey,
+            InsnVariants::IncrVacuum => execute::op_incr_vacuum,
             InsnVariants::IfNeg => execute::op_if_neg,
             InsnVariants::Explain => execute::op_noop,
             InsnVariants::OpenDup => execute::op_open_dup,
@@ -2274,6 +2300,8 @@ impl Insn {
             | Self::AddColumn { .. }
             | Self::AlterColumn { .. }
             | Self::JournalMode { .. }
+            | Self::Rekey { .. }
+            | Self::IncrVacuum { .. }
             | Self::Vacuum { .. } => false,
             Self::MaxPgcnt { new_max, .. } => *new_max == 0,
             // A recursive foreign-key action is treated as writable while it's still being
@@ -2285,6 +2313,91 @@ impl Insn {
             _ => true,
         }
     }
+
+    /// Mutable references to every jump target of this instruction.
+    pub(crate) fn branch_targets_mut(&mut self) -> impl Iterator<Item = &mut BranchOffset> {
+        let targets: [Option<&mut BranchOffset>; 3] = match self {
+            Self::Jump {
+                target_pc_lt,
+                target_pc_eq,
+                target_pc_gt,
+            } => [Some(target_pc_lt), Some(target_pc_eq), Some(target_pc_gt)],
+            Self::InitCoroutine {
+                jump_on_definition,
+                start_offset,
+                ..
+            } => [Some(jump_on_definition), Some(start_offset), None],
+            Self::Init { target_pc }
+            | Self::IfPos { target_pc, .. }
+            | Self::NotNull { target_pc, .. }
+            | Self::Eq { target_pc, .. }
+            | Self::Filter { target_pc, .. }
+            | Self::Ne { target_pc, .. }
+            | Self::Lt { target_pc, .. }
+            | Self::Le { target_pc, .. }
+            | Self::Gt { target_pc, .. }
+            | Self::Ge { target_pc, .. }
+            | Self::If { target_pc, .. }
+            | Self::IfNot { target_pc, .. }
+            | Self::ColumnHasField { target_pc, .. }
+            | Self::Goto { target_pc }
+            | Self::Gosub { target_pc, .. }
+            | Self::SeekRowid { target_pc, .. }
+            | Self::SeekGE { target_pc, .. }
+            | Self::SeekGT { target_pc, .. }
+            | Self::SeekLE { target_pc, .. }
+            | Self::SeekLT { target_pc, .. }
+            | Self::IdxGE { target_pc, .. }
+            | Self::IdxGT { target_pc, .. }
+            | Self::IdxLE { target_pc, .. }
+            | Self::IdxLT { target_pc, .. }
+            | Self::DecrJumpZero { target_pc, .. }
+            | Self::MustBeInt {
+                target_pc: Some(target_pc),
+                ..
+            }
+            | Self::NoConflict { target_pc, .. }
+            | Self::NotExists { target_pc, .. }
+            | Self::IsNull { target_pc, .. }
+            | Self::Found { target_pc, .. }
+            | Self::NotFound { target_pc, .. }
+            | Self::IfNeg { target_pc, .. }
+            | Self::SequenceTest { target_pc, .. }
+            | Self::FkIfZero { target_pc, .. }
+            | Self::HashProbe { target_pc, .. }
+            | Self::HashNext { target_pc, .. }
+            | Self::HashScanUnmatched { target_pc, .. }
+            | Self::HashNextUnmatched { target_pc, .. }
+            | Self::HashGraceInit { target_pc, .. }
+            | Self::HashGraceLoadPartition { target_pc, .. }
+            | Self::HashGraceNextProbe { target_pc, .. }
+            | Self::HashGraceAdvancePartition { target_pc, .. } => [Some(target_pc), None, None],
+            Self::HashDistinct { data } => [Some(&mut data.target_pc), None, None],
+            Self::VFilter { pc_if_empty, .. }
+            | Self::Rewind { pc_if_empty, .. }
+            | Self::Last { pc_if_empty, .. }
+            | Self::SorterSort { pc_if_empty, .. }
+            | Self::RowSetRead { pc_if_empty, .. }
+            | Self::IndexMethodQuery { pc_if_empty, .. } => [Some(pc_if_empty), None, None],
+            Self::VNext { pc_if_next, .. }
+            | Self::Next { pc_if_next, .. }
+            | Self::SorterNext { pc_if_next, .. } => [Some(pc_if_next), None, None],
+            Self::Prev { pc_if_prev, .. } => [Some(pc_if_prev), None, None],
+            Self::Program {
+                ignore_jump_target, ..
+            } => [Some(ignore_jump_target), None, None],
+            Self::SorterCompare {
+                pc_when_nonequal, ..
+            } => [Some(pc_when_nonequal), None, None],
+            Self::RowSetTest { pc_if_found, .. } => [Some(pc_if_found), None, None],
+            Self::Yield { end_offset, .. } => [Some(end_offset), None, None],
+            Self::Once {
+                target_pc_when_reentered,
+            } => [Some(target_pc_when_reentered), None, None],
+            _ => [None, None, None],
+        };
+        targets.into_iter().flatten()
+    }
 }
 
 // TODO: Add remaining cookies.
@@ -0,0 +1,116 @@
+//! Per-statement memory accounting.
+//!
+//! Transient structures a statement builds while it runs (currently the
+//! in-memory buffer of each sorter) charge the bytes they hold to the
+//! statement's [StatementMemory]. The account tracks the current total and its
+//! high-water mark, which are exposed through the statement's metrics, and can
+//! refuse a charge that would exceed the connection's statement memory limit.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::{LimboError, Result};
+
+#[derive(Debug, Default)]
+pub struct StatementMemory {
+    in_use: Cell<usize>,
+    high_water: Cell<usize>,
+    /// 0 means unlimited.
+    limit: Cell<usize>,
+}
+
+impl StatementMemory {
+    pub fn new() -> Rc<Self> {
+        Rc::new(Self::default())
+    }
+
+    /// Bytes currently charged to the statement.
+    pub fn in_use(&self) -> usize {
+        self.in_use.get()
+    }
+
+    /// The most bytes charged at once since the account was created or the
+    /// high-water mark was last reset.
+    pub fn high_water(&self) -> usize {
+        self.high_water.get()
+    }
+
+    pub fn reset_high_water(&self) {
+        self.high_water.set(self.in_use.get());
+    }
+
+    /// Set the most bytes that can be charged at once. 0 removes the limit.
+    /// Existing charges are kept even if they exceed the new limit.
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.set(limit);
+    }
+
+    /// Open a new, empty charge against this account.
+    pub fn charge(self: &Rc<Self>) -> MemoryCharge {
+        MemoryCharge {
+            account: self.clone(),
+            bytes: 0,
+        }
+    }
+}
+
+/// Bytes held by one structure, released from the account when dropped.
+#[derive(Debug)]
+pub struct MemoryCharge {
+    account: Rc<StatementMemory>,
+    bytes: usize,
+}
+
+impl MemoryCharge {
+    /// Update the charge to `bytes`. Growing past the account's limit fails
+    /// with [LimboError::OutOfMemory] and leaves the charge as it was.
+    pub fn set(&mut self, bytes: usize) -> Result<()> {
+        let account = &self.account;
+        let in_use = account.in_use.get() - self.bytes + bytes;
+        let limit = account.limit.get();
+        if bytes > self.bytes && limit != 0 && in_use > limit {
+            return Err(LimboError::OutOfMemory);
+        }
+        account.in_use.set(in_use);
+        account.high_water.set(account.high_water.get().max(in_use));
+        self.bytes = bytes;
+        Ok(())
+    }
+
+    pub const fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        self.account
+            .in_use
+            .set(self.account.in_use.get() - self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charges_track_high_water_and_respect_limit() {
+        let memory = StatementMemory::new();
+        memory.set_limit(100);
+        let mut a = memory.charge();
+        let mut b = memory.charge();
+        a.set(60).unwrap();
+        b.set(30).unwrap();
+        assert!(matches!(b.set(50), Err(LimboError::OutOfMemory)));
+        assert_eq!((b.bytes(), memory.in_use()), (30, 90));
+        a.set(10).unwrap();
+        b.set(50).unwrap();
+        drop(a);
+        assert_eq!((memory.in_use(), memory.high_water()), (50, 90));
+        memory.reset_high_water();
+        assert_eq!(memory.high_water(), 50);
+        drop(b);
+        assert_eq!(memory.in_use(), 0);
+    }
+}
@@ -94,6 +94,9 @@ pub struct StatementMetrics {
     /// rewind to avoid double-counting. Exposed as `sqlite3_search_count`.
     pub search_count: i64,
 
+    /// Most bytes held at once by the statement's sorters.
+    pub memory_high_water: u64,
+
     // Hash join spill/probe metrics
     pub hash_join: HashJoinMetrics,
 }
@@ -125,6 +128,7 @@ impl StatementMetrics {
         self.btree_next = self.btree_next.saturating_add(other.btree_next);
         self.btree_prev = self.btree_prev.saturating_add(other.btree_prev);
         self.search_count = self.search_count.saturating_add(other.search_count);
+        self.memory_high_water = self.memory_high_water.max(other.memory_high_water);
         self.hash_join.merge(&other.hash_join);
     }
 
@@ -154,6 +158,8 @@ impl fmt::Display for StatementMetrics {
         writeln!(f, "    Seeks:            {}", self.btree_seeks)?;
         writeln!(f, "    Next:             {}", self.btree_next)?;
         writeln!(f, "    Prev:             {}", self.btree_prev)?;
+        writeln!(f, "  Memory:")?;
+        writeln!(f, "    High water:       {}", self.memory_high_water)?;
         writeln!(f, "  Hash Join:")?;
         writeln!(
             f,
@@ -27,12 +27,16 @@ pub mod array;
 mod blob_io_tests;
 pub mod bloom_filter;
 pub mod builder;
+#[cfg(feature = "coverage")]
+pub mod coverage;
 pub mod execute;
 pub mod explain;
 #[allow(dead_code)]
 pub mod hash_table;
 pub mod insn;
+pub mod memory;
 pub mod metrics;
+mod peephole;
 pub mod rowset;
 pub mod sorter;
 #[cfg(test)]
@@ -819,6 +823,8 @@ pub struct ProgramState {
     seek_state: OpSeekState,
     /// Metrics collected for the lifetime of this prepared statement.
     pub metrics: StatementMetrics,
+    /// Bytes held by this statement's sorters, see [memory::StatementMemory].
+    pub(crate) memory: std::rc::Rc<memory::StatementMemory>,
     op_vacuum_state: VacuumOpState,
     /// State machine for committing view deltas with I/O handling
     view_delta_state: ViewDeltaCommitState,
@@ -913,6 +919,7 @@ impl ProgramState {
             active_op_state: ActiveOpStateSlot::default(),
             seek_state: OpSeekState::Start,
             metrics: StatementMetrics::new(),
+            memory: memory::StatementMemory::new(),
             distinct_key_values: Vec::new(),
             op_vacuum_state: VacuumOpState::None,
             view_delta_state: ViewDeltaCommitState::NotStarted,
@@ -1122,6 +1129,7 @@ impl ProgramState {
 
     pub(crate) fn metrics(&self) -> StatementMetrics {
         let mut metrics = self.metrics.clone();
+        metrics.memory_high_water = self.memory.high_water() as u64;
         if let Some(OpProgramState::Step { statement, .. }) = self.active_op_state.program_ref() {
             metrics.merge(&statement.metrics());
         }
@@ -1133,6 +1141,7 @@ impl ProgramState {
 
     pub(crate) fn reset_metrics(&mut self) {
         self.metrics.reset();
+        self.memory.reset_high_water();
         if let Some(OpProgramState::Step { statement, .. }) = self.active_op_state.program_mut() {
             statement.reset_metrics();
         }
@@ -1151,6 +1160,9 @@ impl ProgramState {
             crate::statement::StatementStatusCounter::Reprepare => self.metrics.reprepares = 0,
             crate::statement::StatementStatusCounter::RowsRead => self.metrics.rows_read = 0,
             crate::statement::StatementStatusCounter::RowsWritten => self.metrics.rows_written = 0,
+            crate::statement::StatementStatusCounter::MemoryHighWater => {
+                self.memory.reset_high_water()
+            }
         }
         if let Some(OpProgramState::Step { statement, .. }) = self.active_op_state.program_mut() {
             statement.reset_stmt_status(counter);
@@ -1903,15 +1915,21 @@ impl Program {
                 }
                 // Always increment VM steps for every loop iteration
                 state.metrics.vm_steps = state.metrics.vm_steps.saturating_add(1);
+                #[cfg(feature = "coverage")]
+                let pc_before = state.pc;
 
                 match insn_function(self, state, insn, pager) {
                     Ok(InsnFunctionStepResult::Step) => {
                         // Instruction completed, moving to next
                         state.metrics.insn_executed = state.metrics.insn_executed.saturating_add(1);
+                        #[cfg(feature = "coverage")]
+                        coverage::record(insn, pc_before, state.pc);
                     }
                     Ok(InsnFunctionStepResult::Done) => {
                         // Instruction completed execution
                         state.metrics.insn_executed = state.metrics.insn_executed.saturating_add(1);
+                        #[cfg(feature = "coverage")]
+                        coverage::record(insn, pc_before, state.pc);
                         state.auto_txn_cleanup = TxnCleanup::None;
                         return Ok(StepResult::Done);
                     }
@@ -1939,6 +1957,8 @@ impl Program {
                     Ok(InsnFunctionStepResult::Row) => {
                         // Instruction completed (ResultRow already incremented PC)
                         state.metrics.insn_executed = state.metrics.insn_executed.saturating_add(1);
+                        #[cfg(feature = "coverage")]
+                        coverage::record(insn, pc_before, state.pc);
                         return Ok(StepResult::Row);
                     }
                     Err(LimboError::Busy) => {
@@ -2111,6 +2131,14 @@ impl Program {
             // We don't want to commit on nested statements. Let parent handle it.
             return Ok(IOResult::Done(()));
         }
+        if !rollback
+            && matches!(program_state.commit_state, CommitState::Ready)
+            && matches!(tx_state, TransactionState::Write { .. })
+            && self.connection.auto_commit.load(Ordering::SeqCst)
+        {
+            // A veto surfaces as an error, and abort() rolls the transaction back.
+            self.connection.run_commit_hooks()?;
+        }
         let res = if let Some(mv_store) = mv_store {
             self.commit_txn_mvcc(pager, program_state, mv_store, rollback)
         } else {
@@ -2707,6 +2735,11 @@ impl Program {
                 // state, and the in-progress statement it collided with must
                 // keep running unharmed.
                 Some(LimboError::StatementsInProgress(_)) => {}
+                // A write rejected up front (PRAGMA query_only, BEGIN READONLY, or a
+                // read-only database) before the statement opened any transaction
+                // state leaves the explicit transaction usable, like in SQLite.
+                Some(LimboError::ReadOnly)
+                    if state.auto_txn_cleanup == TxnCleanup::None && !can_autocommit_now => {}
                 // BusySnapshot errors do not cause a rollback either - user must rollback explicitly.
                 // BusySnapshot is distinct from Busy in that a busy_timeout or handler should not be
                 // used because it will not help - the snapshot is permanently stale and rollback is
@@ -0,0 +1,488 @@
+//! Peephole optimization over the final instruction stream.
+//!
+//! Runs once per program after labels are resolved, so every jump target is a
+//! plain [BranchOffset::Offset]. The pass is purely local and only rewrites
+//! patterns whose behavior is obviously unchanged:
+//!
+//! - a jump to a `Goto` is redirected to wherever that `Goto` ends up, so that
+//!   chains of `Goto`s are collapsed into a single jump,
+//! - a `Goto` to the instruction right after it is dropped,
+//! - instructions after a `Halt` or `Goto` that no instruction jumps to are
+//!   dropped, since they can never run,
+//! - adjacent `Null`s over contiguous registers are merged into one `Null`,
+//!   and adjacent `Copy`s of contiguous register ranges into one `Copy`.
+//!
+//! Dropping instructions shifts the ones after them, so jump targets, EXPLAIN
+//! comments and `Explain` parent pointers are remapped after each round. The
+//! rounds repeat until nothing changes, as one rewrite can expose another (a
+//! dropped dead instruction can turn a `Goto` into a jump to the next one).
+//!
+//! Return addresses are never stored in the program: `Gosub`, `Yield` and
+//! `InitCoroutine` compute them from the program counter at runtime, and they
+//! always point at the instruction after a non-terminal one, which is never
+//! dropped as dead code.
+
+use super::{insn::Insn, BranchOffset, InsnReference};
+
+/// Run the peephole pass over a program whose labels are all resolved.
+pub(crate) fn optimize(
+    insns: &mut Vec<(Insn, usize)>,
+    comments: &mut Vec<(InsnReference, &'static str)>,
+) {
+    loop {
+        collapse_goto_chains(insns);
+        let removed = merge_and_mark_removed(insns);
+        if !removed.contains(&true) {
+            return;
+        }
+        compact(insns, comments, &removed);
+    }
+}
+
+/// The target of the instruction at `idx` if it is an unconditional jump.
+fn goto_target(insns: &[(Insn, usize)], idx: usize) -> Option<usize> {
+    match insns.get(idx) {
+        Some((
+            Insn::Goto {
+                target_pc: BranchOffset::Offset(target),
+            },
+            _,
+        )) => Some(*target as usize),
+        _ => None,
+    }
+}
+
+fn collapse_goto_chains(insns: &mut [(Insn, usize)]) {
+    let final_targets: Vec<Option<usize>> = (0..insns.len())
+        .map(|idx| {
+            let mut target = goto_target(insns, idx)?;
+            // Bounded so that a cycle of Gotos leaves us somewhere in the cycle.
+            for _ in 0..insns.len() {
+                match goto_target(insns, target) {
+                    Some(next) if next != target => target = next,
+                    _ => break,
+                }
+            }
+            Some(target)
+        })
+        .collect();
+    for (insn, _) in insns.iter_mut() {
+        for pc in insn.branch_targets_mut() {
+            if let BranchOffset::Offset(target) = pc {
+                if let Some(Some(final_target)) = final_targets.get(*target as usize) {
+                    *target = *final_target as InsnReference;
+                }
+            }
+        }
+    }
+}
+
+/// Merge mergeable neighbours into the first of them and return which
+/// instructions are to be dropped.
+fn merge_and_mark_removed(insns: &mut [(Insn, usize)]) -> Vec<bool> {
+    let mut is_target = vec![false; insns.len() + 1];
+    for (insn, _) in insns.iter_mut() {
+        for pc in insn.branch_targets_mut() {
+            if let BranchOffset::Offset(target) = pc {
+                if let Some(is_target) = is_target.get_mut(*target as usize) {
+                    *is_target = true;
+                }
+            }
+        }
+    }
+
+    let mut removed = vec![false; insns.len()];
+    let mut falls_through = true;
+    let mut prev_kept: Option<usize> = None;
+    for idx in 0..insns.len() {
+        if !falls_through && !is_target[idx] {
+            removed[idx] = true;
+            continue;
+        }
+        if goto_target(insns, idx) == Some(idx + 1) {
+            // Jumps to the Goto now land on the next instruction, which must
+            // then not be merged into the one before the Goto.
+            is_target[idx + 1] |= is_target[idx];
+            removed[idx] = true;
+            falls_through = true;
+            continue;
+        }
+        if let Some(prev) = prev_kept.filter(|_| !is_target[idx]) {
+            let (before, after) = insns.split_at_mut(idx);
+            if try_merge(&mut before[prev].0, &after[0].0) {
+                removed[idx] = true;
+                continue;
+            }
+        }
+        falls_through = !matches!(insns[idx].0, Insn::Halt { .. } | Insn::Goto { .. });
+        prev_kept = Some(idx);
+    }
+    removed
+}
+
+/// Fold `next` into `insn` if running the result is the same as running both.
+fn try_merge(insn: &mut Insn, next: &Insn) -> bool {
+    match (insn, next) {
+        (
+            Insn::Null { dest, dest_end },
+            Insn::Null {
+                dest: next_dest,
+                dest_end: next_dest_end,
+            },
+        ) => {
+            let end = dest_end.unwrap_or(*dest);
+            let next_end = next_dest_end.unwrap_or(*next_dest);
+            if *next_dest > end + 1 || *dest > next_end + 1 {
+                return false;
+            }
+            let start = (*dest).min(*next_dest);
+            let end = end.max(next_end);
+            *dest = start;
+            *dest_end = (end > start).then_some(end);
+            true
+        }
+        (
+            Insn::Copy {
+                src_reg,
+                dst_reg,
+                extra_amount,
+            },
+            Insn::Copy {
+                src_reg: next_src_reg,
+                dst_reg: next_dst_reg,
+                extra_amount: next_extra_amount,
+            },
+        ) => {
+            // Copy moves registers one by one in ascending order, so the merged
+            // Copy performs exactly the same moves in the same order.
+            let len = *extra_amount + 1;
+            if *next_src_reg != *src_reg + len || *next_dst_reg != *dst_reg + len {
+                return false;
+            }
+            *extra_amount += next_extra_amount + 1;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn compact(
+    insns: &mut Vec<(Insn, usize)>,
+    comments: &mut Vec<(InsnReference, &'static str)>,
+    removed: &[bool],
+) {
+    // A removed instruction maps to the first kept one after it, which is where
+    // a jump to it now continues.
+    let mut remap = vec![0 as InsnReference; insns.len() + 1];
+    let mut next_kept = removed.iter().filter(|removed| !**removed).count();
+    remap[insns.len()] = next_kept as InsnReference;
+    for idx in (0..insns.len()).rev() {
+        if !removed[idx] {
+            next_kept -= 1;
+        }
+        remap[idx] = next_kept as InsnReference;
+    }
+
+    let mut idx = 0;
+    insns.retain(|_| {
+        idx += 1;
+        !removed[idx - 1]
+    });
+    for (new_idx, (insn, orig_idx)) in insns.iter_mut().enumerate() {
+        *orig_idx = new_idx;
+        for pc in insn.branch_targets_mut() {
+            if let BranchOffset::Offset(target) = pc {
+                if let Some(new_target) = remap.get(*target as usize) {
+                    *target = *new_target;
+                }
+            }
+        }
+        if let Insn::Explain { p1, p2, .. } = insn {
+            *p1 = new_idx;
+            *p2 = p2.map(|parent| remap[parent] as usize);
+        }
+    }
+
+    comments.retain_mut(|(offset, _)| {
+        let Some(&was_removed) = removed.get(*offset as usize) else {
+            return true;
+        };
+        *offset = remap[*offset as usize];
+        !was_removed
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemoryIO;
+    use crate::sync::Arc;
+    use crate::{Database, SqliteDialect};
+
+    fn goto(target: InsnReference) -> Insn {
+        Insn::Goto {
+            target_pc: BranchOffset::Offset(target),
+        }
+    }
+
+    fn halt() -> Insn {
+        Insn::Halt {
+            err_code: 0,
+            description: String::new(),
+            on_error: None,
+            description_reg: None,
+        }
+    }
+
+    fn integer(value: i64, dest: usize) -> Insn {
+        Insn::Integer { value, dest }
+    }
+
+    fn run(program: Vec<Insn>) -> (Vec<Insn>, Vec<(InsnReference, &'static str)>) {
+        let mut insns = program
+            .into_iter()
+            .enumerate()
+            .map(|(idx, insn)| (insn, idx))
+            .collect::<Vec<_>>();
+        let mut comments = (0..insns.len() as InsnReference)
+            .map(|idx| (idx, "c"))
+            .collect::<Vec<_>>();
+        optimize(&mut insns, &mut comments);
+        for (idx, (_, orig_idx)) in insns.iter().enumerate() {
+            assert_eq!(idx, *orig_idx);
+        }
+        (insns.into_iter().map(|(insn, _)| insn).collect(), comments)
+    }
+
+    fn target(insn: &Insn) -> InsnReference {
+        match insn {
+            Insn::Goto { target_pc } | Insn::If { target_pc, .. } => target_pc.as_offset_int(),
+            _ => panic!("not a jump: {insn:?}"),
+        }
+    }
+
+    #[test]
+    fn test_goto_to_next_is_removed() {
+        let (insns, comments) = run(vec![
+            Insn::If {
+                reg: 1,
+                target_pc: BranchOffset::Offset(3),
+                jump_if_null: false,
+            },
+            goto(2),
+            integer(1, 2),
+            halt(),
+        ]);
+        assert_eq!(insns.len(), 3);
+        assert!(matches!(insns[1], Insn::Integer { .. }));
+        // The If targeted the Halt, which moved up by one.
+        assert_eq!(target(&insns[0]), 2);
+        assert_eq!(comments, vec![(0, "c"), (1, "c"), (2, "c")]);
+    }
+
+    #[test]
+    fn test_goto_chain_is_collapsed() {
+        let (insns, _) = run(vec![
+            Insn::If {
+                reg: 1,
+                target_pc: BranchOffset::Offset(3),
+                jump_if_null: false,
+            },
+            integer(1, 2),
+            halt(),
+            goto(5),
+            halt(),
+            goto(7),
+            halt(),
+            integer(2, 2),
+            halt(),
+        ]);
+        // Both intermediate Gotos become unreachable once the If jumps straight
+        // to their final target, and so do the Halts between them.
+        assert_eq!(insns.len(), 5);
+        assert_eq!(target(&insns[0]), 3);
+        assert!(matches!(insns[3], Insn::Integer { value: 2, .. }));
+    }
+
+    #[test]
+    fn test_goto_cycle_stays_a_loop() {
+        // The pass must terminate, and the entry point must still spin.
+        let (insns, _) = run(vec![goto(1), goto(0)]);
+        assert_eq!(target(&insns[0]), 0);
+    }
+
+    #[test]
+    fn test_removed_goto_target_is_not_merged_away() {
+        let (insns, _) = run(vec![
+            Insn::If {
+                reg: 1,
+                target_pc: BranchOffset::Offset(2),
+                jump_if_null: false,
+            },
+            Insn::Null {
+                dest: 1,
+                dest_end: None,
+            },
+            goto(3),
+            Insn::Null {
+                dest: 2,
+                dest_end: None,
+            },
+            halt(),
+        ]);
+        assert_eq!(insns.len(), 4);
+        assert_eq!(target(&insns[0]), 2);
+        assert!(matches!(insns[2], Insn::Null { dest: 2, .. }));
+    }
+
+    #[test]
+    fn test_dead_code_after_halt_is_removed_unless_targeted() {
+        let (insns, comments) = run(vec![
+            Insn::If {
+                reg: 1,
+                target_pc: BranchOffset::Offset(4),
+                jump_if_null: false,
+            },
+            halt(),
+            integer(1, 2),
+            integer(2, 2),
+            integer(3, 2),
+            halt(),
+        ]);
+        assert_eq!(insns.len(), 4);
+        assert!(matches!(insns[2], Insn::Integer { value: 3, .. }));
+        assert_eq!(target(&insns[0]), 2);
+        assert_eq!(comments, vec![(0, "c"), (1, "c"), (2, "c"), (3, "c")]);
+    }
+
+    #[test]
+    fn test_null_runs_are_merged() {
+        let (insns, _) = run(vec![
+            Insn::Null {
+                dest: 1,
+                dest_end: None,
+            },
+            Insn::Null {
+                dest: 2,
+                dest_end: Some(4),
+            },
+            Insn::Null {
+                dest: 5,
+                dest_end: None,
+            },
+            Insn::Null {
+                dest: 7,
+                dest_end: None,
+            },
+            halt(),
+        ]);
+        assert_eq!(insns.len(), 3);
+        assert!(matches!(
+            insns[0],
+            Insn::Null {
+                dest: 1,
+                dest_end: Some(5)
+            }
+        ));
+        assert!(matches!(
+            insns[1],
+            Insn::Null {
+                dest: 7,
+                dest_end: None
+            }
+        ));
+    }
+
+    #[test]
+    fn test_jump_target_is_not_merged_away() {
+        let (insns, _) = run(vec![
+            Insn::If {
+                reg: 1,
+                target_pc: BranchOffset::Offset(2),
+                jump_if_null: false,
+            },
+            Insn::Null {
+                dest: 1,
+                dest_end: None,
+            },
+            Insn::Null {
+                dest: 2,
+                dest_end: None,
+            },
+            halt(),
+        ]);
+        assert_eq!(insns.len(), 4);
+    }
+
+    #[test]
+    fn test_copy_runs_are_merged() {
+        let (insns, _) = run(vec![
+            Insn::Copy {
+                src_reg: 1,
+                dst_reg: 10,
+                extra_amount: 1,
+            },
+            Insn::Copy {
+                src_reg: 3,
+                dst_reg: 12,
+                extra_amount: 0,
+            },
+            Insn::Copy {
+                src_reg: 5,
+                dst_reg: 13,
+                extra_amount: 0,
+            },
+            halt(),
+        ]);
+        assert_eq!(insns.len(), 3);
+        assert!(matches!(
+            insns[0],
+            Insn::Copy {
+                src_reg: 1,
+                dst_reg: 10,
+                extra_amount: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_compiled_programs_have_no_trivial_jumps_or_dead_code() {
+        let io = Arc::new(MemoryIO::new());
+        let db = Database::open_file(io, ":memory:", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE t(a, b, c)").unwrap();
+        conn.execute("CREATE INDEX t_b ON t(b)").unwrap();
+        for sql in [
+            "SELECT a, b FROM t WHERE b > 1 ORDER BY a LIMIT 3",
+            "SELECT b, count(*) FROM t GROUP BY b",
+            "SELECT * FROM t WHERE a IN (SELECT b FROM t)",
+            "INSERT INTO t VALUES (1, 2, 3), (4, 5, 6)",
+            "UPDATE t SET c = c + 1 WHERE b = 2",
+            "DELETE FROM t WHERE a IS NULL",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let insns = &stmt.get_program().insns;
+            let mut is_target = vec![false; insns.len() + 1];
+            for (insn, _) in insns.iter() {
+                let mut insn = insn.clone();
+                for pc in insn.branch_targets_mut() {
+                    is_target[pc.as_offset_int() as usize] = true;
+                }
+            }
+            for (idx, (insn, _)) in insns.iter().enumerate() {
+                assert_ne!(
+                    goto_target(insns, idx),
+                    Some(idx + 1),
+                    "{sql}: Goto to the next instruction at {idx}"
+                );
+                if idx + 1 < insns.len() && matches!(insn, Insn::Halt { .. } | Insn::Goto { .. }) {
+                    assert!(
+                        is_target[idx + 1],
+                        "{sql}: unreachable instruction at {}",
+                        idx + 1
+                    );
+                }
+            }
+        }
+    }
+}
@@ -11,8 +11,9 @@ use std::rc::Rc;
 
 use crate::alloc::vec;
 use crate::alloc::*;
-use crate::io::TempFile;
+use crate::io::{TempDirectory, TempFile};
 use crate::types::{cmp_in_column, cmp_with_sort, IOCompletions, ValueIterator};
+use crate::vdbe::memory::MemoryCharge;
 use crate::{
     error::LimboError,
     io::{Buffer, Completion, CompletionGroup, File, IO},
@@ -197,6 +198,10 @@ pub struct Sorter {
     pending_completion: Option<(Completion, usize)>,
     /// Temp storage mode (memory vs file) for spilled data
     temp_store: crate::TempStore,
+    /// Directory the chunk file is created in when spilling to a file
+    temp_dir: TempDirectory,
+    /// The in-memory buffer's footprint, charged to the owning statement.
+    memory: MemoryCharge,
 }
 
 impl Sorter {
@@ -210,6 +215,8 @@ impl Sorter {
         min_chunk_read_buffer_size_bytes: usize,
         io: Arc<dyn IO>,
         temp_store: crate::TempStore,
+        temp_dir: TempDirectory,
+        memory: MemoryCharge,
     ) -> Result<Self> {
         turso_assert_eq!(order.len(), collations.len());
         let index_key_info = order
@@ -243,6 +250,8 @@ impl Sorter {
             init_chunk_heap_state: InitChunkHeapState::Start,
             pending_completion: None,
             temp_store,
+            temp_dir,
+            memory,
         };
         Ok(this)
     }
@@ -329,6 +338,7 @@ impl Sorter {
 
                     if self.records.is_empty() {
                         self.arena.reset();
+                        self.charge_buffer_memory()?;
                     }
                 }
                 None => self.current = None,
@@ -372,6 +382,13 @@ impl Sorter {
         current
     }
 
+    /// Charge the arena and the record pointers to the owning statement.
+    fn charge_buffer_memory(&mut self) -> Result<()> {
+        let bytes = self.arena.allocated_bytes()
+            + self.records.capacity() * std::mem::size_of::<NonNull<ArenaSortableRecord>>();
+        self.memory.set(bytes)
+    }
+
     pub fn insert(&mut self, record: &ImmutableRecord) -> Result<IOResult<()>> {
         let payload_size = record.get_payload().len();
         loop {
@@ -411,6 +428,7 @@ impl Sorter {
                     self.max_payload_size_in_buffer =
                         self.max_payload_size_in_buffer.max(payload_size);
                     self.insert_state = InsertState::Start;
+                    self.charge_buffer_memory()?;
                     return Ok(IOResult::Done(()));
                 }
             }
@@ -532,7 +550,8 @@ impl Sorter {
         let chunk_file = match &self.temp_file {
             Some(temp_file) => temp_file.file.clone(),
             None => {
-                let temp_file = TempFile::with_temp_store(&self.io, self.temp_store)?;
+                let temp_file =
+                    TempFile::with_temp_store(&self.io, self.temp_store, &self.temp_dir)?;
                 let chunk_file = temp_file.file.clone();
                 self.temp_file = Some(temp_file);
                 chunk_file
@@ -563,6 +582,7 @@ impl Sorter {
 
         self.records.clear();
         self.arena.reset();
+        self.charge_buffer_memory()?;
 
         self.current_buffer_size = 0;
         self.max_payload_size_in_buffer = 0;
@@ -1278,6 +1298,8 @@ mod tests {
                 64,
                 io.clone(),
                 crate::TempStore::Default,
+                TempDirectory::System,
+                crate::vdbe::memory::StatementMemory::new().charge(),
             )
             .unwrap();
 
@@ -1376,6 +1398,8 @@ mod tests {
             64,
             io.clone(),
             crate::TempStore::Default,
+            TempDirectory::System,
+            crate::vdbe::memory::StatementMemory::new().charge(),
         )
         .unwrap();
 
@@ -191,15 +191,7 @@ pub(crate) fn vacuum_target_opts_from_source(source_db: &Database) -> DatabaseOp
         .with_attach(source_db.experimental_attach_enabled())
         .with_generated_columns(source_db.experimental_generated_columns_enabled())
         .with_without_rowid(source_db.experimental_without_rowid_enabled())
-}
-
-pub(crate) fn reject_unsupported_vacuum_auto_vacuum_mode(mode: AutoVacuumMode) -> Result<()> {
-    if matches!(mode, AutoVacuumMode::Incremental) {
-        return Err(LimboError::InternalError(
-            "Incremental auto-vacuum is not supported".to_string(),
-        ));
-    }
-    Ok(())
+        .with_page_cache_policy(source_db.page_cache_policy())
 }
 
 /// Database header metadata that the target build must finalize before commit.
@@ -240,15 +232,15 @@ impl VacuumDbHeaderMeta {
 
 /// File-backed internal temp database used by in-place `VACUUM`.
 ///
-/// The temp directory is dropped after the connection and database handles so
-/// host files can be closed before the directory cleanup runs.
+/// The temp path is dropped after the connection and database handles so
+/// host files can be closed before they are removed.
 pub(crate) struct VacuumTempDb {
     pub conn: Arc<Connection>,
     _db: Arc<Database>,
     #[cfg(test)]
     path: String,
     #[cfg(not(target_family = "wasm"))]
-    _temp_dir: tempfile::TempDir,
+    _temp_path: crate::io::TempPath,
 }
 
 #[cfg(not(target_family = "wasm"))]
@@ -277,24 +269,21 @@ pub(crate) fn open_vacuum_temp_db(
     page_size: u32,
     reserved_space: u8,
 ) -> Result<VacuumTempDb> {
-    let temp_dir = source_conn.create_tempdir()?;
     let source_db_name = std::path::Path::new(&source_db.path)
         .file_name()
         .and_then(|name| name.to_str())
         .unwrap_or("tursodb_vacuum_temp.db");
     // all temp files we will prefix with `etilqs_` as an homage to SQLite's lore
-    let path = temp_dir.path().join(format!("etilqs_{source_db_name}"));
-    let path = path
-        .to_str()
-        .ok_or_else(|| LimboError::InternalError("vacuum temp path is not valid UTF-8".into()))?
-        .to_string();
+    let (_, temp_path) = source_conn
+        .temp_directory()
+        .create_file(&source_db.io, &format!("etilqs_{source_db_name}"))?;
     #[cfg(test)]
-    let test_path = path.clone();
+    let test_path = temp_path.as_str().to_string();
 
     let (encryption_opts, encryption_key) = vacuum_temp_db_encryption(source_conn)?;
     let db = Database::open_file_with_flags(
         source_db.io.clone(),
-        &path,
+        temp_path.as_str(),
         OpenFlags::Create,
         vacuum_target_opts_from_source(source_db),
         encryption_opts,
@@ -310,7 +299,7 @@ pub(crate) fn open_vacuum_temp_db(
         _db: db,
         #[cfg(test)]
         path: test_path,
-        _temp_dir: temp_dir,
+        _temp_path: temp_path,
     })
 }
 
@@ -1707,7 +1696,6 @@ fn vacuum_in_place_step(
                         "cannot VACUUM an in-memory database".to_string(),
                     ));
                 }
-                reject_unsupported_vacuum_auto_vacuum_mode(source_pager.get_auto_vacuum_mode())?;
                 // 6. Reject non-WAL pagers.
                 let wal = source_pager.wal.as_ref().ok_or_else(|| {
                     LimboError::InternalError("VACUUM requires a WAL-mode database".to_string())
@@ -3168,6 +3156,14 @@ mod tests {
         assert_eq!(temp.conn.get_page_size().get(), 4096);
         assert_eq!(temp.conn.get_reserved_bytes(), Some(0));
 
+        // The temp database is created next to the source and removed,
+        // through the same IO, once VACUUM is done with it.
+        let temp_path = temp.path.clone();
+        assert!(temp_path.starts_with(source_dir.path().to_str().unwrap()));
+        drop(temp);
+        assert!(!std::path::Path::new(&temp_path).exists());
+        assert!(!std::path::Path::new(&format!("{temp_path}-wal")).exists());
+
         Ok(())
     }
 
@@ -1,3 +1,4 @@
+use crate::ext::ExtensionCapabilities;
 use crate::pragma::{PragmaVirtualTable, PragmaVirtualTableCursor};
 use crate::schema::Column;
 use crate::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
@@ -87,8 +88,14 @@ impl VirtualTable {
     pub(crate) fn function(name: &str, syms: &SymbolTable) -> crate::Result<Arc<VirtualTable>> {
         let module = syms.vtab_modules.get(name);
         let (vtab_type, schema) = if module.is_some() {
-            ExtVirtualTable::create(name, module, Vec::new(), VTabKind::TableValuedFunction)
-                .map(|(vtab, columns)| (VirtualTableType::External(vtab), columns))?
+            ExtVirtualTable::create(
+                name,
+                module,
+                Vec::new(),
+                VTabKind::TableValuedFunction,
+                false,
+            )
+            .map(|(vtab, columns)| (VirtualTableType::External(vtab), columns))?
         } else {
             return Err(LimboError::ParseError(format!(
                 "No such table-valued function: {name}"
@@ -107,15 +114,22 @@ impl VirtualTable {
         Ok(Arc::new(vtab))
     }
 
+    /// Instantiate a virtual table of an extension module. `connect` is set
+    /// for tables that already exist in the schema, which the module opens
+    /// with `connect` instead of `create`.
     pub fn table(
         tbl_name: Option<&str>,
         module_name: &str,
         args: Vec<turso_ext::Value>,
         syms: &SymbolTable,
+        connect: bool,
     ) -> crate::Result<Arc<VirtualTable>> {
         let module = syms.vtab_modules.get(module_name);
         let (table, schema) =
-            ExtVirtualTable::create(module_name, module, args, VTabKind::VirtualTable)?;
+            ExtVirtualTable::create(module_name, module, args, VTabKind::VirtualTable, connect)?;
+        if let Some(name) = tbl_name {
+            table.set_name(name);
+        }
         let vtab = VirtualTable {
             name: tbl_name.unwrap_or(module_name).to_owned(),
             columns: Self::resolve_columns(schema)?,
@@ -159,18 +173,31 @@ impl VirtualTable {
         }
     }
 
-    pub(crate) fn update(&self, args: &[Value]) -> crate::Result<Option<i64>> {
+    pub(crate) fn update(
+        &self,
+        args: &[Value],
+        conn: &Arc<Connection>,
+    ) -> crate::Result<Option<i64>> {
         match &self.vtab_type {
             VirtualTableType::Pragma(_) => Err(LimboError::ReadOnly),
-            VirtualTableType::External(table) => table.update(args),
+            VirtualTableType::External(table) => table.update(args, conn),
             VirtualTableType::Internal(_) => Err(LimboError::ReadOnly),
         }
     }
 
-    pub(crate) fn destroy(&self) -> crate::Result<()> {
+    pub(crate) fn destroy(&self, conn: &Arc<Connection>) -> crate::Result<()> {
         match &self.vtab_type {
             VirtualTableType::Pragma(_) => Ok(()),
-            VirtualTableType::External(table) => table.destroy(),
+            VirtualTableType::External(table) => table.destroy(conn),
+            VirtualTableType::Internal(_) => Ok(()),
+        }
+    }
+
+    /// Let the module set up a table that `CREATE VIRTUAL TABLE` just created.
+    pub(crate) fn created(&self, conn: &Arc<Connection>) -> crate::Result<()> {
+        match &self.vtab_type {
+            VirtualTableType::Pragma(_) => Ok(()),
+            VirtualTableType::External(table) => table.created(conn),
             VirtualTableType::Internal(_) => Ok(()),
         }
     }
@@ -199,12 +226,12 @@ impl VirtualTable {
         }
     }
 
-    pub(crate) fn commit(&self) -> crate::Result<()> {
+    pub(crate) fn commit(&self, conn: &Arc<Connection>) -> crate::Result<()> {
         match &self.vtab_type {
             VirtualTableType::Pragma(_) => Err(LimboError::ExtensionError(
                 "Pragma virtual tables do not support transactions".to_string(),
             )),
-            VirtualTableType::External(table) => table.commit(),
+            VirtualTableType::External(table) => table.commit(conn),
             VirtualTableType::Internal(_) => Err(LimboError::ExtensionError(
                 "Internal virtual tables currently do not support transactions".to_string(),
             )),
@@ -335,6 +362,7 @@ impl VirtualTableCursor {
 pub(crate) struct ExtVirtualTable {
     implementation: Arc<VTabModuleImpl>,
     table_ptr: AtomicPtr<c_void>,
+    capabilities: ExtensionCapabilities,
 }
 static VTAB_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
 
@@ -343,6 +371,7 @@ impl Clone for ExtVirtualTable {
         Self {
             implementation: self.implementation.clone(),
             table_ptr: AtomicPtr::new(self.table_ptr.load(Ordering::SeqCst)),
+            capabilities: self.capabilities,
         }
     }
 }
@@ -372,6 +401,7 @@ impl ExtVirtualTable {
         module: Option<&Arc<crate::ext::VTabImpl>>,
         args: Vec<turso_ext::Value>,
         kind: VTabKind,
+        connect: bool,
     ) -> crate::Result<(Self, String)> {
         let module = module.ok_or_else(|| {
             LimboError::ExtensionError(format!("Virtual table module not found: {module_name}"))
@@ -385,31 +415,87 @@ impl ExtVirtualTable {
                 "{module_name} is not a {expected} module"
             )));
         }
-        let (schema, table_ptr) = module.implementation.create(args)?;
+        let (schema, table_ptr) = if connect {
+            module.implementation.connect(args)?
+        } else {
+            module.implementation.create(args)?
+        };
         let vtab = ExtVirtualTable {
             implementation: module.implementation.clone(),
             table_ptr: AtomicPtr::new(table_ptr as *mut c_void),
+            capabilities: module.capabilities,
         };
         Ok((vtab, schema))
     }
 
+    /// Leaks a connection handle for the module to query the other tables with.
+    /// Modules from extensions without the `DATABASE_READ` capability get no
+    /// connection, and those without `DATABASE_WRITE` get one that only runs
+    /// read-only statements. `nested` connections run their statements inside
+    /// the statement currently running on `conn`. Must be released with [free_ext_conn].
+    fn ext_conn(&self, conn: &Arc<Connection>, nested: bool) -> Option<NonNull<turso_ext::Conn>> {
+        if !self
+            .capabilities
+            .contains(ExtensionCapabilities::DATABASE_READ)
+        {
+            return None;
+        }
+        // we need a Weak<Connection> to upgrade and call from the extension.
+        let weak = Arc::downgrade(conn);
+        let weak_box = Box::into_raw(Box::new(weak)) as *mut c_void;
+        let allow_writes = self
+            .capabilities
+            .contains(ExtensionCapabilities::DATABASE_WRITE);
+        let conn = match (nested, allow_writes) {
+            (false, true) => {
+                turso_ext::Conn::new(weak_box, crate::ext::prepare_stmt, crate::ext::execute)
+            }
+            (false, false) => turso_ext::Conn::new(
+                weak_box,
+                crate::ext::prepare_stmt_read_only,
+                crate::ext::execute_read_only,
+            ),
+            (true, true) => turso_ext::Conn::new(
+                weak_box,
+                crate::ext::prepare_stmt_nested,
+                crate::ext::execute_nested,
+            ),
+            (true, false) => turso_ext::Conn::new(
+                weak_box,
+                crate::ext::prepare_stmt_nested_read_only,
+                crate::ext::execute_nested_read_only,
+            ),
+        };
+        Some(NonNull::new(Box::into_raw(Box::new(conn))).expect("null pointer"))
+    }
+
+    fn set_connection(&self, ext_conn: Option<NonNull<turso_ext::Conn>>) {
+        unsafe {
+            (self.implementation.set_connection)(
+                self.table_ptr.load(Ordering::SeqCst),
+                ext_conn.map_or(std::ptr::null(), |ptr| ptr.as_ptr() as *const _),
+            )
+        };
+    }
+
+    fn set_name(&self, name: &str) {
+        let Ok(c_name) = std::ffi::CString::new(name) else {
+            return;
+        };
+        unsafe {
+            (self.implementation.set_name)(self.table_ptr.load(Ordering::SeqCst), c_name.as_ptr())
+        };
+    }
+
     /// Accepts a pointer connection that owns the VTable, that the module
     /// can optionally use to query the other tables.
     fn open(&self, conn: Arc<Connection>, id: u64) -> crate::Result<ExtVirtualTableCursor> {
-        // we need a Weak<Connection> to upgrade and call from the extension.
-        let weak = Arc::downgrade(&conn);
-        let weak_box = Box::into_raw(Box::new(weak));
-        let conn = turso_ext::Conn::new(
-            weak_box as *mut c_void,
-            crate::ext::prepare_stmt,
-            crate::ext::execute,
-        );
-        let ext_conn_ptr = NonNull::new(Box::into_raw(Box::new(conn))).expect("null pointer");
+        let ext_conn_ptr = self.ext_conn(&conn, false);
         // store the leaked connection pointer on the table so it can be freed on drop
         let Some(cursor) = NonNull::new(unsafe {
             (self.implementation.open)(
                 self.table_ptr.load(Ordering::SeqCst) as *const c_void,
-                ext_conn_ptr.as_ptr(),
+                ext_conn_ptr.map_or(std::ptr::null_mut(), NonNull::as_ptr),
             ) as *mut c_void
         }) else {
             return Err(LimboError::ExtensionError("Open returned null".to_string()));
@@ -417,10 +503,14 @@ impl ExtVirtualTable {
         ExtVirtualTableCursor::new(cursor, ext_conn_ptr, self.implementation.clone(), id)
     }
 
-    fn update(&self, args: &[Value]) -> crate::Result<Option<i64>> {
+    /// The module gets the connection running the statement for the duration
+    /// of the call, to keep its shadow tables up to date.
+    fn update(&self, args: &[Value], conn: &Arc<Connection>) -> crate::Result<Option<i64>> {
         let arg_count = args.len();
         let ext_args = args.iter().map(|arg| arg.to_ffi()).collect::<Vec<_>>();
         let newrowid = 0i64;
+        let ext_conn = self.ext_conn(conn, true);
+        self.set_connection(ext_conn);
         let rc = unsafe {
             (self.implementation.update)(
                 self.table_ptr.load(Ordering::SeqCst) as *const c_void,
@@ -429,6 +519,10 @@ impl ExtVirtualTable {
                 &newrowid as *const _ as *mut i64,
             )
         };
+        self.set_connection(None);
+        if let Some(ext_conn) = ext_conn {
+            unsafe { free_ext_conn(ext_conn) };
+        }
         for arg in ext_args {
             unsafe {
                 arg.__free_internal_type();
@@ -437,22 +531,52 @@ impl ExtVirtualTable {
         match rc {
             ResultCode::OK => Ok(None),
             ResultCode::RowID => Ok(Some(newrowid)),
-            _ => Err(LimboError::ExtensionError(rc.to_string())),
+            _ => Err(rc.into()),
         }
     }
 
-    fn destroy(&self) -> crate::Result<()> {
+    fn destroy(&self, conn: &Arc<Connection>) -> crate::Result<()> {
+        let ext_conn = self.ext_conn(conn, true);
+        self.set_connection(ext_conn);
+        // destroy frees the table, so the connection is not unset afterwards
         let rc = unsafe {
             (self.implementation.destroy)(self.table_ptr.load(Ordering::SeqCst) as *const c_void)
         };
+        if let Some(ext_conn) = ext_conn {
+            unsafe { free_ext_conn(ext_conn) };
+        }
         match rc {
             ResultCode::OK => Ok(()),
-            _ => Err(LimboError::ExtensionError(rc.to_string())),
+            _ => Err(rc.into()),
         }
     }
 
-    fn commit(&self) -> crate::Result<()> {
+    /// As with [Self::update], the module gets the connection for the duration of the
+    /// call, to create its shadow tables in the transaction of `CREATE VIRTUAL TABLE`.
+    fn created(&self, conn: &Arc<Connection>) -> crate::Result<()> {
+        let ext_conn = self.ext_conn(conn, true);
+        self.set_connection(ext_conn);
+        let rc = unsafe { (self.implementation.created)(self.table_ptr.load(Ordering::SeqCst)) };
+        self.set_connection(None);
+        if let Some(ext_conn) = ext_conn {
+            unsafe { free_ext_conn(ext_conn) };
+        }
+        match rc {
+            ResultCode::OK => Ok(()),
+            _ => Err(rc.into()),
+        }
+    }
+
+    /// As with [Self::update], the module gets the connection for the duration of the
+    /// call, so that it can write to its shadow tables before the transaction commits.
+    fn commit(&self, conn: &Arc<Connection>) -> crate::Result<()> {
+        let ext_conn = self.ext_conn(conn, true);
+        self.set_connection(ext_conn);
         let rc = unsafe { (self.implementation.commit)(self.table_ptr.load(Ordering::SeqCst)) };
+        self.set_connection(None);
+        if let Some(ext_conn) = ext_conn {
+            unsafe { free_ext_conn(ext_conn) };
+        }
         match rc {
             ResultCode::OK => Ok(()),
             _ => Err(LimboError::ExtensionError("Commit failed".to_string())),
@@ -487,6 +611,19 @@ impl ExtVirtualTable {
     }
 }
 
+/// Frees a connection handle leaked by [ExtVirtualTable::ext_conn].
+///
+/// # Safety
+/// `ptr` must come from [ExtVirtualTable::ext_conn] and not be used afterwards.
+unsafe fn free_ext_conn(ptr: NonNull<turso_ext::Conn>) {
+    // first free the boxed turso_ext::Conn pointer itself
+    let conn = unsafe { Box::from_raw(ptr.as_ptr()) };
+    if !conn._ctx.is_null() {
+        // we also leaked the Weak 'ctx' pointer, so free this as well
+        let _ = unsafe { Box::from_raw(conn._ctx as *mut Weak<Connection>) };
+    }
+}
+
 pub struct ExtVirtualTableCursor {
     cursor: NonNull<c_void>,
     // the core `[Connection]` pointer the vtab module needs to
@@ -505,13 +642,13 @@ crate::assert::assert_send_sync!(ExtVirtualTableCursor);
 impl ExtVirtualTableCursor {
     fn new(
         cursor: NonNull<c_void>,
-        conn_ptr: NonNull<turso_ext::Conn>,
+        conn_ptr: Option<NonNull<turso_ext::Conn>>,
         implementation: Arc<VTabModuleImpl>,
         id: u64,
     ) -> crate::Result<Self> {
         Ok(Self {
             cursor,
-            conn_ptr: Some(conn_ptr),
+            conn_ptr,
             implementation,
             vtab_id: id,
         })
@@ -558,7 +695,7 @@ impl ExtVirtualTableCursor {
         match rc {
             ResultCode::OK => Ok(true),
             ResultCode::EOF => Ok(false),
-            _ => Err(LimboError::ExtensionError(rc.to_string())),
+            _ => Err(rc.into()),
         }
     }
 
@@ -580,12 +717,7 @@ impl ExtVirtualTableCursor {
 impl Drop for ExtVirtualTableCursor {
     fn drop(&mut self) {
         if let Some(ptr) = self.conn_ptr.take() {
-            // first free the boxed turso_ext::Conn pointer itself
-            let conn = unsafe { Box::from_raw(ptr.as_ptr()) };
-            if !conn._ctx.is_null() {
-                // we also leaked the Weak 'ctx' pointer, so free this as well
-                let _ = unsafe { Box::from_raw(conn._ctx as *mut Weak<Connection>) };
-            }
+            unsafe { free_ext_conn(ptr) };
         }
         let result = unsafe { (self.implementation.close)(self.cursor.as_ptr()) };
         if !result.is_ok() {
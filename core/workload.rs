@@ -0,0 +1,290 @@
+//! Opt-in capture of the statements a connection executes, for replaying the
+//! workload later (see the `turso-replay` tool).
+//!
+//! A capture is attached with [crate::Connection::set_workload_capture]. Every
+//! user statement is recorded once per execution, when it is first stepped,
+//! as one JSON object per line:
+//!
+//! ```text
+//! {"elapsed_us":1520,"sql":"SELECT * FROM t WHERE id = ?","params":[{"index":1,"type":"integer","value":7}]}
+//! ```
+//!
+//! `elapsed_us` counts from the first recorded statement. Parameters carry
+//! their name when they have one (`"name":":id"`). A parameter hidden by a
+//! [RedactionRule] keeps its type but has `"redacted":true` instead of a value.
+//! Literals inside the SQL text itself are never redacted.
+
+use crate::io::clock::MonotonicInstant;
+use crate::numeric::Numeric;
+use crate::parameters::Parameters;
+use crate::sync::Mutex;
+use crate::types::ValueRef;
+use crate::{LimboError, Result, Value};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::num::NonZero;
+
+/// What to leave out of a workload capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedactionRule {
+    /// Redact every bound parameter.
+    AllParameters,
+    /// Redact parameters with one of these names, including the prefix (e.g. `:password`).
+    NamedParameters(Vec<String>),
+    /// Redact TEXT and BLOB parameters, keeping numbers and NULLs.
+    TextAndBlobParameters,
+    /// Do not record statements whose SQL contains this text, compared case-insensitively.
+    SkipStatementsContaining(String),
+}
+
+/// Destination and rules of a workload capture. See the [module docs](self) for the format.
+pub struct WorkloadCapture {
+    writer: Mutex<Box<dyn Write + Send>>,
+    rules: Vec<RedactionRule>,
+    started_at: Mutex<Option<MonotonicInstant>>,
+}
+
+impl WorkloadCapture {
+    pub fn new(writer: impl Write + Send + 'static, rules: Vec<RedactionRule>) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            rules,
+            started_at: Mutex::new(None),
+        }
+    }
+
+    /// Capture into a newly created (or truncated) file.
+    #[cfg(feature = "fs")]
+    pub fn create_file(
+        path: impl AsRef<std::path::Path>,
+        rules: Vec<RedactionRule>,
+    ) -> Result<Self> {
+        let file =
+            std::fs::File::create(path).map_err(|e| LimboError::InternalError(e.to_string()))?;
+        Ok(Self::new(std::io::BufWriter::new(file), rules))
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.writer
+            .lock()
+            .flush()
+            .map_err(|e| LimboError::InternalError(e.to_string()))
+    }
+
+    pub(crate) fn record(
+        &self,
+        now: MonotonicInstant,
+        sql: &str,
+        parameters: &Parameters,
+        value_at: impl Fn(NonZero<usize>) -> Value,
+    ) -> Result<()> {
+        let skipped = self.rules.iter().any(|rule| match rule {
+            RedactionRule::SkipStatementsContaining(text) => {
+                sql.to_lowercase().contains(&text.to_lowercase())
+            }
+            _ => false,
+        });
+        if skipped {
+            return Ok(());
+        }
+        let started_at = *self.started_at.lock().get_or_insert(now);
+
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "{{\"elapsed_us\":{},\"sql\":",
+            now.duration_since(started_at).as_micros()
+        );
+        push_json_string(&mut line, sql);
+        line.push_str(",\"params\":[");
+        for i in 1..=parameters.count() {
+            let index = NonZero::new(i).expect("parameter indexes start at 1");
+            let name = parameters.name(index).filter(|name| !name.starts_with('?'));
+            let value = value_at(index);
+            if i > 1 {
+                line.push(',');
+            }
+            let _ = write!(line, "{{\"index\":{i},");
+            if let Some(name) = &name {
+                line.push_str("\"name\":");
+                push_json_string(&mut line, name);
+                line.push(',');
+            }
+            let value = value.as_ref();
+            let type_name = match value {
+                ValueRef::Null => "null",
+                ValueRef::Numeric(Numeric::Integer(_)) => "integer",
+                ValueRef::Numeric(Numeric::Float(_)) => "real",
+                ValueRef::Text(_) => "text",
+                ValueRef::Blob(_) => "blob",
+            };
+            let _ = write!(line, "\"type\":\"{type_name}\",");
+            if self.redacts(name.as_deref(), &value) {
+                line.push_str("\"redacted\":true}");
+                continue;
+            }
+            line.push_str("\"value\":");
+            match value {
+                ValueRef::Null => line.push_str("null"),
+                ValueRef::Numeric(Numeric::Integer(i)) => {
+                    let _ = write!(line, "{i}");
+                }
+                ValueRef::Numeric(Numeric::Float(f)) => {
+                    let f: f64 = f.into();
+                    if f.is_finite() {
+                        let _ = write!(line, "{f:?}");
+                    } else {
+                        // JSON has no infinities; the replay tool parses them back from text.
+                        push_json_string(&mut line, &f.to_string());
+                    }
+                }
+                ValueRef::Text(text) => push_json_string(&mut line, text.as_str()),
+                ValueRef::Blob(blob) => {
+                    line.push('"');
+                    for byte in blob {
+                        let _ = write!(line, "{byte:02x}");
+                    }
+                    line.push('"');
+                }
+            }
+            line.push('}');
+        }
+        line.push_str("]}\n");
+
+        self.writer
+            .lock()
+            .write_all(line.as_bytes())
+            .map_err(|e| LimboError::InternalError(e.to_string()))
+    }
+
+    fn redacts(&self, name: Option<&str>, value: &ValueRef) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            RedactionRule::AllParameters => true,
+            RedactionRule::NamedParameters(names) => {
+                name.is_some_and(|name| names.iter().any(|n| n == name))
+            }
+            RedactionRule::TextAndBlobParameters => {
+                matches!(value, ValueRef::Text(_) | ValueRef::Blob(_))
+            }
+            RedactionRule::SkipStatementsContaining(_) => false,
+        })
+    }
+}
+
+impl Drop for WorkloadCapture {
+    fn drop(&mut self) {
+        let _ = self.writer.lock().flush();
+    }
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemoryIO;
+    use crate::sync::Arc;
+    use crate::{Database, SqliteDialect};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn captured(
+        rules: Vec<RedactionRule>,
+        run: impl FnOnce(&Arc<crate::Connection>),
+    ) -> Vec<String> {
+        let io = Arc::new(MemoryIO::new());
+        let db = Database::open_file(io, ":memory:", Arc::new(SqliteDialect)).unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE users(id INTEGER PRIMARY KEY, name TEXT, secret BLOB)")
+            .unwrap();
+        let buffer = SharedBuffer::default();
+        conn.set_workload_capture(Some(Arc::new(WorkloadCapture::new(buffer.clone(), rules))));
+        run(&conn);
+        conn.set_workload_capture(None);
+        let output = String::from_utf8(buffer.0.lock().clone()).unwrap();
+        output.lines().map(str::to_string).collect()
+    }
+
+    fn insert_user(conn: &Arc<crate::Connection>) {
+        let mut stmt = conn
+            .prepare("INSERT INTO users VALUES (?1, :name, ?3)")
+            .unwrap();
+        stmt.bind_at(NonZero::new(1).unwrap(), Value::from_i64(7))
+            .unwrap();
+        stmt.bind_at(
+            NonZero::new(2).unwrap(),
+            Value::build_text("a \"quoted\"\nname"),
+        )
+        .unwrap();
+        stmt.bind_at(
+            NonZero::new(3).unwrap(),
+            Value::from_blob(crate::alloc::vec![0xde, 0xad]),
+        )
+        .unwrap();
+        stmt.run_ignore_rows().unwrap();
+    }
+
+    #[test]
+    fn test_capture_records_statements_and_parameters() {
+        let lines = captured(vec![], |conn| {
+            insert_user(conn);
+            conn.execute("SELECT count(*) FROM users").unwrap();
+        });
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].starts_with("{\"elapsed_us\":0,"), "{}", lines[0]);
+        assert!(lines[0].ends_with(
+            "\"sql\":\"INSERT INTO users VALUES (?1, :name, ?3)\",\"params\":[\
+             {\"index\":1,\"type\":\"integer\",\"value\":7},\
+             {\"index\":2,\"name\":\":name\",\"type\":\"text\",\"value\":\"a \\\"quoted\\\"\\nname\"},\
+             {\"index\":3,\"type\":\"blob\",\"value\":\"dead\"}]}"
+        ), "{}", lines[0]);
+        assert!(lines[1].contains("\"sql\":\"SELECT count(*) FROM users\",\"params\":[]"));
+    }
+
+    #[test]
+    fn test_capture_applies_redaction_rules() {
+        let lines = captured(
+            vec![
+                RedactionRule::NamedParameters(vec![":name".to_string()]),
+                RedactionRule::SkipStatementsContaining("SECRET_TABLE".to_string()),
+            ],
+            |conn| {
+                insert_user(conn);
+                conn.execute("CREATE TABLE secret_table(x)").unwrap();
+            },
+        );
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("{\"index\":1,\"type\":\"integer\",\"value\":7}"));
+        assert!(lines[0]
+            .contains("{\"index\":2,\"name\":\":name\",\"type\":\"text\",\"redacted\":true}"));
+        assert!(lines[0].contains("{\"index\":3,\"type\":\"blob\",\"value\":\"dead\"}"));
+    }
+}
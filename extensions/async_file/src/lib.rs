@@ -0,0 +1,479 @@
+//! A VFS that runs file I/O on a shared tokio runtime.
+//!
+//! Every `read`, `write`, `sync` and `truncate` is handed to the runtime's
+//! blocking pool and returns immediately. When an operation finishes its
+//! result is pushed onto a completion queue shared by the VFS and all of its
+//! files, and the callbacks only ever run from `run_once`, on the thread that
+//! drives the database. This is the same contract the io_uring backend
+//! follows, so it doubles as a reference for writing asynchronous VFSes.
+
+mod lock;
+#[cfg(unix)]
+mod shm;
+
+use lock::FileLock;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use tokio::runtime::{Builder, Runtime};
+#[cfg(unix)]
+use turso_ext::ShmLockScope;
+use turso_ext::{
+    register_extension, BufferRef, Callback, ExtResult, LockLevel, ResultCode, VfsDerive,
+    VfsExtension, VfsFile,
+};
+
+register_extension! {
+    vfs: { AsyncFS },
+}
+
+/// Runtime shared by every instance of the VFS. The extension API builds a
+/// fresh `AsyncFS` for some calls (e.g. `generate_random_number`), so the
+/// runtime can't be owned by the struct and is only started on first use.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        // All the work goes to the blocking pool, the scheduler itself
+        // only needs a single worker.
+        Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("turso-async-file")
+            .build()
+            .expect("failed to start async file runtime")
+    })
+}
+
+#[derive(Default)]
+struct QueueState {
+    ready: VecDeque<(Callback, i32)>,
+    in_flight: usize,
+}
+
+/// Completion queue filled by the runtime and drained by `run_once`.
+#[derive(Clone, Default)]
+struct CompletionQueue {
+    inner: Arc<(Mutex<QueueState>, Condvar)>,
+}
+
+impl CompletionQueue {
+    /// Run `op` on the blocking pool and queue `cb` with its result.
+    fn submit<F>(&self, cb: Callback, op: F)
+    where
+        F: FnOnce() -> io::Result<i32> + Send + 'static,
+    {
+        self.inner.0.lock().unwrap().in_flight += 1;
+        let queue = self.clone();
+        runtime().spawn_blocking(move || {
+            let result = match op() {
+                Ok(n) => n,
+                Err(e) => {
+                    log::error!("async file operation failed: {e}");
+                    -e.raw_os_error().unwrap_or(EIO)
+                }
+            };
+            let (state, ready) = &*queue.inner;
+            let mut state = state.lock().unwrap();
+            state.in_flight -= 1;
+            state.ready.push_back((cb, result));
+            ready.notify_one();
+        });
+    }
+
+    /// Wait until at least one submitted operation has finished, then run
+    /// the callbacks of everything that has. Returns immediately if nothing
+    /// is in flight.
+    fn run_once(&self) {
+        let (state, ready) = &*self.inner;
+        let completed = {
+            let mut state = state.lock().unwrap();
+            while state.ready.is_empty() && state.in_flight > 0 {
+                state = ready.wait(state).unwrap();
+            }
+            std::mem::take(&mut state.ready)
+        };
+        // Callbacks run without the lock held: they may submit new I/O.
+        for (cb, result) in completed {
+            cb(result);
+        }
+    }
+}
+
+/// Reported for errors that don't carry an OS error code.
+const EIO: i32 = 5;
+
+#[derive(VfsDerive, Default)]
+pub struct AsyncFS {
+    completions: CompletionQueue,
+}
+
+pub struct AsyncFile {
+    file: Arc<File>,
+    completions: CompletionQueue,
+    lock: FileLock,
+}
+
+impl VfsExtension for AsyncFS {
+    const NAME: &'static str = "async_file";
+    type File = AsyncFile;
+
+    fn run_once(&self) -> ExtResult<()> {
+        self.completions.run_once();
+        Ok(())
+    }
+
+    fn open_file(&self, path: &str, flags: i32, _direct: bool) -> ExtResult<Self::File> {
+        log::debug!("opening file with async VFS: {path} flags: {flags}");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(flags & 1 != 0)
+            .truncate(false)
+            .open(path)
+            .map_err(|_| ResultCode::Error)?;
+        Ok(AsyncFile {
+            file: Arc::new(file),
+            completions: self.completions.clone(),
+            lock: FileLock::new(),
+        })
+    }
+
+    fn remove_file(&self, path: &str) -> ExtResult<()> {
+        std::fs::remove_file(path).map_err(|_| ResultCode::Error)
+    }
+
+    fn supports_shm(&self) -> bool {
+        cfg!(unix)
+    }
+
+    #[cfg(unix)]
+    fn shm_unmap(&self, ptr: *mut u8, offset: u64, len: usize) -> ExtResult<()> {
+        shm::unmap(ptr, offset, len)
+    }
+}
+
+// The buffers behind a `BufferRef` belong to core, which keeps them alive
+// until the operation's callback has run. Callbacks only run from `run_once`
+// after the task is done with the buffer, so moving the `BufferRef` into the
+// task is sound.
+impl VfsFile for AsyncFile {
+    fn lock_level(&mut self, level: LockLevel) -> ExtResult<()> {
+        self.lock.lock(&self.file, level)
+    }
+
+    fn unlock_level(&mut self, level: LockLevel) -> ExtResult<()> {
+        self.lock.unlock(&self.file, level)
+    }
+
+    fn check_reserved_lock(&self) -> ExtResult<bool> {
+        self.lock.check_reserved(&self.file)
+    }
+
+    #[cfg(unix)]
+    fn shm_lock(
+        &self,
+        offset: u64,
+        exclusive: bool,
+        blocking: bool,
+        scope: ShmLockScope,
+    ) -> ExtResult<bool> {
+        shm::lock(&self.file, offset, exclusive, blocking, scope)
+    }
+
+    #[cfg(unix)]
+    fn shm_unlock(&self, offset: u64, scope: ShmLockScope) -> ExtResult<()> {
+        shm::unlock(&self.file, offset, scope)
+    }
+
+    fn shm_set_len(&self, len: u64) -> ExtResult<()> {
+        self.file.set_len(len).map_err(|_| ResultCode::Error)
+    }
+
+    #[cfg(unix)]
+    fn shm_map(&self, offset: u64, len: usize) -> ExtResult<*mut u8> {
+        shm::map(&self.file, offset, len)
+    }
+
+    fn read(&mut self, mut buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+        let offset = u64::try_from(offset).map_err(|_| ResultCode::InvalidArgs)?;
+        let file = self.file.clone();
+        self.completions.submit(cb, move || {
+            let n = read_at(&file, buf.as_mut_slice(), offset)?;
+            Ok(n as i32)
+        });
+        Ok(())
+    }
+
+    fn write(&mut self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+        let offset = u64::try_from(offset).map_err(|_| ResultCode::InvalidArgs)?;
+        let file = self.file.clone();
+        self.completions.submit(cb, move || {
+            write_all_at(&file, buf.as_slice(), offset)?;
+            Ok(buf.len() as i32)
+        });
+        Ok(())
+    }
+
+    fn sync(&self, cb: Callback) -> ExtResult<()> {
+        let file = self.file.clone();
+        self.completions
+            .submit(cb, move || file.sync_all().map(|_| 0));
+        Ok(())
+    }
+
+    fn truncate(&self, len: i64, cb: Callback) -> ExtResult<()> {
+        let len = u64::try_from(len).map_err(|_| ResultCode::InvalidArgs)?;
+        let file = self.file.clone();
+        self.completions
+            .submit(cb, move || file.set_len(len).map(|_| 0));
+        Ok(())
+    }
+
+    fn size(&self) -> i64 {
+        self.file.metadata().map(|m| m.len() as i64).unwrap_or(-1)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn allocate(&self, len: i64) -> ExtResult<()> {
+        use std::os::fd::AsRawFd;
+        let size = self.size();
+        if size < 0 || len <= size {
+            return Ok(());
+        }
+        // SAFETY: the descriptor is open for as long as `self.file` lives.
+        let ret = unsafe {
+            libc::fallocate(
+                self.file.as_raw_fd(),
+                libc::FALLOC_FL_KEEP_SIZE,
+                size as libc::off_t,
+                (len - size) as libc::off_t,
+            )
+        };
+        if ret != 0 {
+            let err = io::Error::last_os_error();
+            // Only a hint: file systems without preallocation are fine.
+            if err.raw_os_error() != Some(libc::EOPNOTSUPP) {
+                log::error!("fallocate failed: {err}");
+                return Err(ResultCode::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Positional read that keeps going until `buf` is full or EOF is reached,
+/// returning the number of bytes read.
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match pread(file, &mut buf[total..], offset + total as u64) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    let mut total = 0;
+    while total < buf.len() {
+        match pwrite(file, &buf[total..], offset + total as u64) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => total += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn pread(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn pwrite(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::write_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn pread(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn pwrite(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_write(file, buf, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    fn callback() -> (Callback, Arc<AtomicI32>) {
+        let result = Arc::new(AtomicI32::new(i32::MIN));
+        let r = result.clone();
+        (Box::new(move |res| r.store(res, Ordering::SeqCst)), result)
+    }
+
+    fn buffer(data: &mut [u8]) -> BufferRef {
+        unsafe { BufferRef::new(data.as_mut_ptr(), data.len()) }
+    }
+
+    #[test]
+    fn test_write_sync_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let vfs = AsyncFS::default();
+        let mut file = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+
+        let mut data = vec![7u8; 4096];
+        let (cb, written) = callback();
+        file.write(buffer(&mut data), 4096, cb).unwrap();
+        vfs.run_once().unwrap();
+        assert_eq!(written.load(Ordering::SeqCst), 4096);
+        assert_eq!(file.size(), 8192);
+
+        let (cb, synced) = callback();
+        file.sync(cb).unwrap();
+        vfs.run_once().unwrap();
+        assert_eq!(synced.load(Ordering::SeqCst), 0);
+
+        let mut out = vec![0u8; 4096];
+        let (cb, read) = callback();
+        file.read(buffer(&mut out), 4096, cb).unwrap();
+        vfs.run_once().unwrap();
+        assert_eq!(read.load(Ordering::SeqCst), 4096);
+        assert_eq!(out, data);
+
+        // nothing in flight: must not block
+        vfs.run_once().unwrap();
+    }
+
+    #[test]
+    fn test_short_read_and_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let vfs = AsyncFS::default();
+        let mut file = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+
+        let mut data = vec![1u8; 100];
+        let (cb, _) = callback();
+        file.write(buffer(&mut data), 0, cb).unwrap();
+        vfs.run_once().unwrap();
+
+        let mut out = vec![0u8; 4096];
+        let (cb, read) = callback();
+        file.read(buffer(&mut out), 0, cb).unwrap();
+        vfs.run_once().unwrap();
+        assert_eq!(read.load(Ordering::SeqCst), 100);
+
+        let (cb, truncated) = callback();
+        file.truncate(10, cb).unwrap();
+        vfs.run_once().unwrap();
+        assert_eq!(truncated.load(Ordering::SeqCst), 0);
+        assert_eq!(file.size(), 10);
+
+        let (cb, read) = callback();
+        file.read(buffer(&mut out), 0, cb).unwrap();
+        vfs.run_once().unwrap();
+        assert_eq!(read.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn test_lock_levels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let vfs = AsyncFS::default();
+        let mut file = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+
+        file.lock_level(LockLevel::Shared).unwrap();
+        assert!(!file.check_reserved_lock().unwrap());
+        file.lock_level(LockLevel::Reserved).unwrap();
+        assert!(file.check_reserved_lock().unwrap());
+        file.lock_level(LockLevel::Exclusive).unwrap();
+        // Already held.
+        file.lock_level(LockLevel::Shared).unwrap();
+
+        file.unlock_level(LockLevel::Shared).unwrap();
+        assert!(!file.check_reserved_lock().unwrap());
+        assert_eq!(
+            file.unlock_level(LockLevel::Reserved),
+            Err(ResultCode::InvalidArgs)
+        );
+        file.unlock_level(LockLevel::None).unwrap();
+        // Skipping levels takes the ones in between.
+        file.lock_level(LockLevel::Exclusive).unwrap();
+        assert!(file.check_reserved_lock().unwrap());
+        file.unlock_level(LockLevel::None).unwrap();
+    }
+
+    #[test]
+    fn test_allocate_keeps_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let vfs = AsyncFS::default();
+        let mut file = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+
+        let mut data = vec![1u8; 100];
+        let (cb, _) = callback();
+        file.write(buffer(&mut data), 0, cb).unwrap();
+        vfs.run_once().unwrap();
+        file.allocate(1 << 20).unwrap();
+        assert_eq!(file.size(), 100);
+        // Smaller than the file: nothing to do.
+        file.allocate(10).unwrap();
+        assert_eq!(file.size(), 100);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-tshm");
+        let vfs = AsyncFS::default();
+        assert!(vfs.supports_shm());
+        let a = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+        let b = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+        a.shm_set_len(8192).unwrap();
+
+        // Mappings of either open see each other's writes, also off a page
+        // boundary.
+        let pa = a.shm_map(100, 4000).unwrap();
+        let pb = b.shm_map(100, 4000).unwrap();
+        drop(a);
+        unsafe {
+            *pa.add(10) = 42;
+            assert_eq!(*pb.add(10), 42);
+        }
+        vfs.shm_unmap(pa, 100, 4000).unwrap();
+        vfs.shm_unmap(pb, 100, 4000).unwrap();
+
+        let scope = ShmLockScope::Process;
+        assert!(b.shm_lock(3, true, false, scope).unwrap());
+        b.shm_unlock(3, scope).unwrap();
+        assert!(b.shm_lock(3, false, true, scope).unwrap());
+        b.shm_unlock(3, scope).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_shm_open_file_locks_exclude_each_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-tshm");
+        let vfs = AsyncFS::default();
+        let a = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+        let b = vfs.open_file(path.to_str().unwrap(), 1, false).unwrap();
+
+        let scope = ShmLockScope::OpenFile;
+        assert!(a.shm_lock(0, true, false, scope).unwrap());
+        assert!(!b.shm_lock(0, false, false, scope).unwrap());
+        a.shm_unlock(0, scope).unwrap();
+        assert!(b.shm_lock(0, false, false, scope).unwrap());
+        assert!(a.shm_lock(0, false, false, scope).unwrap());
+    }
+}
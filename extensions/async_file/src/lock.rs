@@ -0,0 +1,262 @@
+//! SQLite's lock levels on top of byte-range locks.
+//!
+//! The levels are mapped onto the same bytes SQLite locks, so this VFS and
+//! SQLite exclude each other: one byte at `PENDING_BYTE` for PENDING, the
+//! next one for RESERVED, and the `SHARED_SIZE` bytes after them for SHARED
+//! (read-locked) and EXCLUSIVE (write-locked). Like SQLite's own locks on
+//! Unix, POSIX locks only exclude other processes.
+
+use std::fs::File;
+use turso_ext::{ExtResult, LockLevel, ResultCode};
+
+const PENDING_BYTE: u64 = 0x4000_0000;
+const RESERVED_BYTE: u64 = PENDING_BYTE + 1;
+const SHARED_FIRST: u64 = PENDING_BYTE + 2;
+const SHARED_SIZE: u64 = 510;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Read,
+    Write,
+}
+
+/// Lock held by one open file.
+pub(crate) struct FileLock {
+    level: LockLevel,
+}
+
+impl FileLock {
+    pub(crate) fn new() -> Self {
+        Self {
+            level: LockLevel::None,
+        }
+    }
+
+    /// Raise the lock to `level`. On failure the lock stays at the last level
+    /// reached, as SQLite's does.
+    pub(crate) fn lock(&mut self, file: &File, level: LockLevel) -> ExtResult<()> {
+        if self.level >= level {
+            return Ok(());
+        }
+        if self.level == LockLevel::None {
+            // Nobody gets a new SHARED lock while a writer holds PENDING.
+            lock_range(file, PENDING_BYTE, 1, Mode::Read)?;
+            let shared = lock_range(file, SHARED_FIRST, SHARED_SIZE, Mode::Read);
+            unlock_range(file, PENDING_BYTE, 1)?;
+            shared?;
+            self.level = LockLevel::Shared;
+        }
+        if level >= LockLevel::Reserved && self.level < LockLevel::Reserved {
+            lock_range(file, RESERVED_BYTE, 1, Mode::Write)?;
+            self.level = LockLevel::Reserved;
+        }
+        if level >= LockLevel::Pending && self.level < LockLevel::Pending {
+            lock_range(file, PENDING_BYTE, 1, Mode::Write)?;
+            self.level = LockLevel::Pending;
+        }
+        if level == LockLevel::Exclusive {
+            upgrade_shared(file)?;
+            self.level = LockLevel::Exclusive;
+        }
+        Ok(())
+    }
+
+    /// Lower the lock to `level`, `LockLevel::Shared` or `LockLevel::None`.
+    pub(crate) fn unlock(&mut self, file: &File, level: LockLevel) -> ExtResult<()> {
+        if level > LockLevel::Shared {
+            return Err(ResultCode::InvalidArgs);
+        }
+        if self.level <= level {
+            return Ok(());
+        }
+        if self.level == LockLevel::Exclusive && level == LockLevel::Shared {
+            downgrade_exclusive(file)?;
+        }
+        if self.level >= LockLevel::Pending {
+            unlock_range(file, PENDING_BYTE, 1)?;
+        }
+        if self.level >= LockLevel::Reserved {
+            unlock_range(file, RESERVED_BYTE, 1)?;
+        }
+        if level == LockLevel::None {
+            unlock_range(file, SHARED_FIRST, SHARED_SIZE)?;
+        }
+        self.level = level;
+        Ok(())
+    }
+
+    pub(crate) fn check_reserved(&self, file: &File) -> ExtResult<bool> {
+        if self.level >= LockLevel::Reserved {
+            return Ok(true);
+        }
+        reserved_elsewhere(file)
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::*;
+    use std::os::fd::AsRawFd;
+
+    fn flock(kind: libc::c_int, start: u64, len: u64) -> libc::flock {
+        // SAFETY: `flock` is a plain C struct, all zeroes is a valid value.
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = kind as _;
+        lock.l_whence = libc::SEEK_SET as _;
+        lock.l_start = start as libc::off_t;
+        lock.l_len = len as libc::off_t;
+        lock
+    }
+
+    fn set_lock(file: &File, lock: &libc::flock) -> ExtResult<()> {
+        // SAFETY: the descriptor is open for as long as `file` lives.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, lock as *const libc::flock) } == 0
+        {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EAGAIN) | Some(libc::EACCES) => Err(ResultCode::Busy),
+            _ => {
+                log::error!("fcntl lock failed: {err}");
+                Err(ResultCode::Error)
+            }
+        }
+    }
+
+    pub(super) fn lock_range(file: &File, start: u64, len: u64, mode: Mode) -> ExtResult<()> {
+        let kind = match mode {
+            Mode::Read => libc::F_RDLCK,
+            Mode::Write => libc::F_WRLCK,
+        } as libc::c_int;
+        set_lock(file, &flock(kind, start, len))
+    }
+
+    pub(super) fn unlock_range(file: &File, start: u64, len: u64) -> ExtResult<()> {
+        set_lock(file, &flock(libc::F_UNLCK as libc::c_int, start, len))
+    }
+
+    /// POSIX locks are converted in place.
+    pub(super) fn upgrade_shared(file: &File) -> ExtResult<()> {
+        lock_range(file, SHARED_FIRST, SHARED_SIZE, Mode::Write)
+    }
+
+    pub(super) fn downgrade_exclusive(file: &File) -> ExtResult<()> {
+        lock_range(file, SHARED_FIRST, SHARED_SIZE, Mode::Read)
+    }
+
+    pub(super) fn reserved_elsewhere(file: &File) -> ExtResult<bool> {
+        let mut lock = flock(libc::F_WRLCK as libc::c_int, RESERVED_BYTE, 1);
+        // SAFETY: as in `set_lock`.
+        if unsafe {
+            libc::fcntl(
+                file.as_raw_fd(),
+                libc::F_GETLK,
+                &mut lock as *mut libc::flock,
+            )
+        } != 0
+        {
+            log::error!("fcntl F_GETLK failed: {}", std::io::Error::last_os_error());
+            return Err(ResultCode::Error);
+        }
+        Ok(lock.l_type != libc::F_UNLCK as _)
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::*;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::{ERROR_IO_PENDING, ERROR_LOCK_VIOLATION, HANDLE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        LockFileEx, UnlockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY,
+    };
+    use windows_sys::Win32::System::IO::OVERLAPPED;
+
+    fn overlapped(start: u64) -> OVERLAPPED {
+        // SAFETY: `OVERLAPPED` is a plain C struct, all zeroes is a valid value.
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        overlapped.Anonymous.Anonymous.Offset = start as u32;
+        overlapped.Anonymous.Anonymous.OffsetHigh = (start >> 32) as u32;
+        overlapped
+    }
+
+    pub(super) fn lock_range(file: &File, start: u64, len: u64, mode: Mode) -> ExtResult<()> {
+        let mut flags = LOCKFILE_FAIL_IMMEDIATELY;
+        if mode == Mode::Write {
+            flags |= LOCKFILE_EXCLUSIVE_LOCK;
+        }
+        let mut overlapped = overlapped(start);
+        // SAFETY: the handle is open for as long as `file` lives.
+        let ok = unsafe {
+            LockFileEx(
+                file.as_raw_handle() as HANDLE,
+                flags,
+                0,
+                len as u32,
+                (len >> 32) as u32,
+                &mut overlapped,
+            )
+        };
+        if ok != 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error().map(|code| code as u32) {
+            Some(ERROR_LOCK_VIOLATION) | Some(ERROR_IO_PENDING) => Err(ResultCode::Busy),
+            _ => {
+                log::error!("LockFileEx failed: {err}");
+                Err(ResultCode::Error)
+            }
+        }
+    }
+
+    pub(super) fn unlock_range(file: &File, start: u64, len: u64) -> ExtResult<()> {
+        let mut overlapped = overlapped(start);
+        // SAFETY: as in `lock_range`.
+        let ok = unsafe {
+            UnlockFileEx(
+                file.as_raw_handle() as HANDLE,
+                0,
+                len as u32,
+                (len >> 32) as u32,
+                &mut overlapped,
+            )
+        };
+        if ok == 0 {
+            log::error!("UnlockFileEx failed: {}", std::io::Error::last_os_error());
+            return Err(ResultCode::Error);
+        }
+        Ok(())
+    }
+
+    /// Windows can't convert a lock in place: the read lock is dropped first
+    /// and taken back if the write lock can't be had.
+    pub(super) fn upgrade_shared(file: &File) -> ExtResult<()> {
+        unlock_range(file, SHARED_FIRST, SHARED_SIZE)?;
+        if let Err(e) = lock_range(file, SHARED_FIRST, SHARED_SIZE, Mode::Write) {
+            lock_range(file, SHARED_FIRST, SHARED_SIZE, Mode::Read)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub(super) fn downgrade_exclusive(file: &File) -> ExtResult<()> {
+        unlock_range(file, SHARED_FIRST, SHARED_SIZE)?;
+        lock_range(file, SHARED_FIRST, SHARED_SIZE, Mode::Read)
+    }
+
+    /// There is no way to query a lock: try to take it.
+    pub(super) fn reserved_elsewhere(file: &File) -> ExtResult<bool> {
+        match lock_range(file, RESERVED_BYTE, 1, Mode::Write) {
+            Ok(()) => {
+                unlock_range(file, RESERVED_BYTE, 1)?;
+                Ok(false)
+            }
+            Err(ResultCode::Busy) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+use sys::{downgrade_exclusive, lock_range, reserved_elsewhere, unlock_range, upgrade_shared};
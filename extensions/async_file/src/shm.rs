@@ -0,0 +1,111 @@
+//! Shared memory for WAL coordination: byte locks and `MAP_SHARED` mappings
+//! of the coordination file, Unix only.
+
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use turso_ext::{ExtResult, ResultCode, ShmLockScope};
+
+fn lock_cmd(scope: ShmLockScope, blocking: bool) -> ExtResult<libc::c_int> {
+    match (scope, blocking) {
+        (ShmLockScope::Process, false) => Ok(libc::F_SETLK),
+        (ShmLockScope::Process, true) => Ok(libc::F_SETLKW),
+        #[cfg(target_os = "linux")]
+        (ShmLockScope::OpenFile, false) => Ok(libc::F_OFD_SETLK),
+        #[cfg(target_os = "linux")]
+        (ShmLockScope::OpenFile, true) => Ok(libc::F_OFD_SETLKW),
+        #[cfg(not(target_os = "linux"))]
+        (ShmLockScope::OpenFile, _) => Err(ResultCode::Unimplemented),
+    }
+}
+
+fn set_lock(file: &File, kind: libc::c_int, offset: u64, cmd: libc::c_int) -> ExtResult<bool> {
+    // SAFETY: `flock` is a plain C struct, all zeroes is a valid value. OFD
+    // locks also require `l_pid` to be zero.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = kind as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    lock.l_start = offset as libc::off_t;
+    lock.l_len = 1;
+    loop {
+        // SAFETY: the descriptor is open for as long as `file` lives.
+        if unsafe { libc::fcntl(file.as_raw_fd(), cmd, &lock as *const libc::flock) } == 0 {
+            return Ok(true);
+        }
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EAGAIN) | Some(libc::EACCES) => return Ok(false),
+            _ => {
+                log::error!("fcntl shm lock failed: {err}");
+                return Err(ResultCode::Error);
+            }
+        }
+    }
+}
+
+pub(crate) fn lock(
+    file: &File,
+    offset: u64,
+    exclusive: bool,
+    blocking: bool,
+    scope: ShmLockScope,
+) -> ExtResult<bool> {
+    let kind = if exclusive {
+        libc::F_WRLCK
+    } else {
+        libc::F_RDLCK
+    } as libc::c_int;
+    set_lock(file, kind, offset, lock_cmd(scope, blocking)?)
+}
+
+pub(crate) fn unlock(file: &File, offset: u64, scope: ShmLockScope) -> ExtResult<()> {
+    set_lock(
+        file,
+        libc::F_UNLCK as libc::c_int,
+        offset,
+        lock_cmd(scope, false)?,
+    )
+    .map(|_| ())
+}
+
+/// Distance from `offset` back to the page boundary `mmap` needs.
+fn page_delta(offset: u64) -> usize {
+    // SAFETY: sysconf has no preconditions.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    (offset % page) as usize
+}
+
+pub(crate) fn map(file: &File, offset: u64, len: usize) -> ExtResult<*mut u8> {
+    if len == 0 {
+        return Err(ResultCode::InvalidArgs);
+    }
+    let delta = page_delta(offset);
+    // SAFETY: a fresh shared mapping of the descriptor; the kernel keeps it
+    // alive after the descriptor is closed, until `unmap`.
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len + delta,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            (offset - delta as u64) as libc::off_t,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        log::error!("mmap failed: {}", std::io::Error::last_os_error());
+        return Err(ResultCode::Error);
+    }
+    // SAFETY: `delta` is within the mapping.
+    Ok(unsafe { (ptr as *mut u8).add(delta) })
+}
+
+pub(crate) fn unmap(ptr: *mut u8, offset: u64, len: usize) -> ExtResult<()> {
+    let delta = page_delta(offset);
+    // SAFETY: `ptr` came from `map` with the same `offset` and `len`.
+    if unsafe { libc::munmap(ptr.sub(delta) as *mut libc::c_void, len + delta) } != 0 {
+        log::error!("munmap failed: {}", std::io::Error::last_os_error());
+        return Err(ResultCode::Error);
+    }
+    Ok(())
+}
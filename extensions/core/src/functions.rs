@@ -41,10 +41,33 @@ pub type RegisterAggFn = unsafe extern "C" fn(
     value_destructor: Option<ValueDestructor>,
 ) -> ResultCode;
 
+/// Like [RegisterAggFn], for an aggregate that can also be used as a window
+/// function.
+pub type RegisterWindowFn = unsafe extern "C" fn(
+    ctx: *mut c_void,
+    name: *const c_char,
+    args: i32,
+    context: usize,
+    init: InitAggFunction,
+    step: StepFunction,
+    finalize: FinalizeFunction,
+    value: ValueFunction,
+    inverse: InverseFunction,
+    context_destructor: Option<ContextDestructor>,
+    aggregate_destructor: Option<ContextDestructor>,
+    value_destructor: Option<ValueDestructor>,
+) -> ResultCode;
+
 pub type InitAggFunction = unsafe extern "C" fn(context: usize) -> *mut AggCtx;
 pub type StepFunction =
     unsafe extern "C" fn(context: usize, ctx: *mut AggCtx, argc: i32, argv: *const Value) -> Value;
 pub type FinalizeFunction = unsafe extern "C" fn(context: usize, ctx: *mut AggCtx) -> Value;
+/// Result of a window aggregate for the rows stepped so far. Unlike
+/// [FinalizeFunction] it leaves the state in place for further steps.
+pub type ValueFunction = unsafe extern "C" fn(context: usize, ctx: *mut AggCtx) -> Value;
+/// Remove a row that has left the window frame from the state.
+pub type InverseFunction =
+    unsafe extern "C" fn(context: usize, ctx: *mut AggCtx, argc: i32, argv: *const Value) -> Value;
 
 #[repr(C)]
 pub struct AggCtx {
@@ -61,6 +84,19 @@ pub trait AggFunc {
     fn finalize(state: Self::State) -> Result<Value, Self::Error>;
 }
 
+/// An aggregate that can also be used as a window function, e.g.
+/// `sum_plus_one(x) OVER (ORDER BY y)`. Registered as one by deriving
+/// `AggregateDerive` with the `#[window]` attribute.
+pub trait WindowFunc: AggFunc {
+    /// Result for the rows stepped so far, leaving the state as it is for the
+    /// rows still to come. Called for every row of the partition.
+    fn value(state: &Self::State) -> Result<Value, Self::Error>;
+    /// Remove a row that has left the window frame, undoing the `step` that
+    /// added it. Frames currently always start at the first row of the
+    /// partition, so core does not call this yet.
+    fn inverse(state: &mut Self::State, args: &[Value]);
+}
+
 /// A scalar function that carries opaque, per-registration state.
 ///
 /// `State` is constructed once per registration via [`ScalarFunc::init`], shared
@@ -74,6 +110,10 @@ pub trait ScalarFunc {
     type State: Send + Sync;
     const NAME: &'static str;
     const ALIAS: Option<&'static str> = None;
+    /// Set to `true` if the result depends only on the arguments. Deterministic
+    /// functions can be constant-folded and used in index expressions, partial
+    /// index WHERE clauses, and type or domain expressions.
+    const DETERMINISTIC: bool = false;
 
     fn init() -> Self::State;
     fn call(state: &Self::State, args: &[Value]) -> Value;
@@ -0,0 +1,24 @@
+use crate::{ContextDestructor, ResultCode};
+use std::ffi::c_void;
+
+/// Called before a write transaction commits. Returning anything other than
+/// `ResultCode::OK` vetoes the commit, and the transaction is rolled back instead.
+pub type CommitHook = unsafe extern "C" fn(context: usize) -> ResultCode;
+
+/// Called after a write transaction has been rolled back, including when a commit
+/// hook vetoed the commit.
+pub type RollbackHook = unsafe extern "C" fn(context: usize);
+
+pub type RegisterCommitHookFn = unsafe extern "C" fn(
+    ctx: *mut c_void,
+    context: usize,
+    hook: CommitHook,
+    context_destructor: Option<ContextDestructor>,
+) -> ResultCode;
+
+pub type RegisterRollbackHookFn = unsafe extern "C" fn(
+    ctx: *mut c_void,
+    context: usize,
+    hook: RollbackHook,
+    context_destructor: Option<ContextDestructor>,
+) -> ResultCode;
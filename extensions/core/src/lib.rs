@@ -1,24 +1,27 @@
 mod functions;
+mod hooks;
 mod types;
 #[cfg(feature = "vfs")]
 mod vfs_modules;
 mod vtabs;
 pub use functions::{
-    AggCtx, AggFunc, ContextDestructor, FinalizeFunction, InitAggFunction, ScalarFunc,
-    ScalarFunction, StepFunction, ValueDestructor,
+    AggCtx, AggFunc, ContextDestructor, FinalizeFunction, InitAggFunction, InverseFunction,
+    ScalarFunc, ScalarFunction, StepFunction, ValueDestructor, ValueFunction, WindowFunc,
 };
-use functions::{RegisterAggFn, RegisterScalarFn, UnregisterFunctionFn};
+use functions::{RegisterAggFn, RegisterScalarFn, RegisterWindowFn, UnregisterFunctionFn};
+pub use hooks::{CommitHook, RollbackHook};
+use hooks::{RegisterCommitHookFn, RegisterRollbackHookFn};
 use std::os::raw::c_void;
 #[cfg(feature = "vfs")]
 pub use turso_macros::VfsDerive;
 pub use turso_macros::{
     register_extension, scalar, AggregateDerive, ScalarDerive, VTabModuleDerive,
 };
-pub use types::{ResultCode, StepResult, Value, ValueType};
+pub use types::{ErrorClass, LockLevel, ResultCode, StepResult, Value, ValueType};
 #[cfg(feature = "vfs")]
 pub use vfs_modules::{
-    BufferRef, Callback, IOCallback, RegisterVfsFn, SendPtr, VfsExtension, VfsFile, VfsFileImpl,
-    VfsImpl, VfsInterface,
+    find_vfs, set_find_vfs, BufferRef, Callback, FindVfsFn, IOCallback, RegisterVfsFn, SendPtr,
+    ShmLockScope, VfsExtension, VfsFile, VfsFileImpl, VfsImpl, VfsInterface, VfsRef,
 };
 use vtabs::RegisterModuleFn;
 pub use vtabs::{
@@ -36,8 +39,11 @@ pub struct ExtensionApi {
     pub ctx: *mut c_void,
     pub register_scalar_function: RegisterScalarFn,
     pub register_aggregate_function: RegisterAggFn,
+    pub register_window_function: RegisterWindowFn,
     pub unregister_function: UnregisterFunctionFn,
     pub register_vtab_module: RegisterModuleFn,
+    pub register_commit_hook: RegisterCommitHookFn,
+    pub register_rollback_hook: RegisterRollbackHookFn,
     #[cfg(feature = "vfs")]
     pub vfs_interface: VfsInterface,
 }
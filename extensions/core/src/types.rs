@@ -71,6 +71,143 @@ impl Display for ResultCode {
         }
     }
 }
+
+impl ResultCode {
+    /// The class of error this code reports, or `None` if it isn't an error.
+    pub fn error_class(&self) -> Option<ErrorClass> {
+        let class = match self {
+            ResultCode::OK | ResultCode::EOF | ResultCode::RowID | ResultCode::Row => return None,
+            ResultCode::Error
+            | ResultCode::InvalidArgs
+            | ResultCode::Unknown
+            | ResultCode::AlreadyExists
+            | ResultCode::Unimplemented
+            | ResultCode::Unavailable
+            | ResultCode::CustomError => ErrorClass::Error,
+            ResultCode::OoM => ErrorClass::NoMem,
+            ResultCode::Corrupt => ErrorClass::Corrupt,
+            ResultCode::NotFound => ErrorClass::NotFound,
+            ResultCode::PermissionDenied => ErrorClass::Perm,
+            ResultCode::Aborted => ErrorClass::Abort,
+            ResultCode::OutOfRange => ErrorClass::Range,
+            ResultCode::Internal => ErrorClass::Internal,
+            ResultCode::ReadOnly => ErrorClass::ReadOnly,
+            ResultCode::Interrupt => ErrorClass::Interrupt,
+            ResultCode::Busy => ErrorClass::Busy,
+            ResultCode::ConstraintViolation => ErrorClass::Constraint,
+        };
+        Some(class)
+    }
+}
+
+/// Error classes shared by the core, extensions and tools built on top of them.
+/// They follow SQLite's primary result codes, so every error, whatever its
+/// origin, can be reported and matched on the same way.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ErrorClass {
+    /// Generic error, e.g. bad SQL or a missing table.
+    Error,
+    /// An internal logic error.
+    Internal,
+    Perm,
+    Abort,
+    /// The database file is locked by another connection.
+    Busy,
+    /// A table is locked by the same connection.
+    Locked,
+    NoMem,
+    ReadOnly,
+    Interrupt,
+    IoErr,
+    Corrupt,
+    NotFound,
+    Full,
+    CantOpen,
+    /// The schema changed while a statement was running.
+    Schema,
+    TooBig,
+    Constraint,
+    Mismatch,
+    Misuse,
+    Range,
+    NotADb,
+}
+
+impl ErrorClass {
+    /// The matching SQLite primary result code.
+    pub const fn sqlite_code(self) -> i32 {
+        match self {
+            ErrorClass::Error => 1,
+            ErrorClass::Internal => 2,
+            ErrorClass::Perm => 3,
+            ErrorClass::Abort => 4,
+            ErrorClass::Busy => 5,
+            ErrorClass::Locked => 6,
+            ErrorClass::NoMem => 7,
+            ErrorClass::ReadOnly => 8,
+            ErrorClass::Interrupt => 9,
+            ErrorClass::IoErr => 10,
+            ErrorClass::Corrupt => 11,
+            ErrorClass::NotFound => 12,
+            ErrorClass::Full => 13,
+            ErrorClass::CantOpen => 14,
+            ErrorClass::Schema => 17,
+            ErrorClass::TooBig => 18,
+            ErrorClass::Constraint => 19,
+            ErrorClass::Mismatch => 20,
+            ErrorClass::Misuse => 21,
+            ErrorClass::Range => 25,
+            ErrorClass::NotADb => 26,
+        }
+    }
+
+    /// The class of a SQLite result code. Extended codes are reduced to their
+    /// primary code first.
+    pub const fn from_sqlite_code(code: i32) -> Option<Self> {
+        let class = match code & 0xff {
+            1 => ErrorClass::Error,
+            2 => ErrorClass::Internal,
+            3 => ErrorClass::Perm,
+            4 => ErrorClass::Abort,
+            5 => ErrorClass::Busy,
+            6 => ErrorClass::Locked,
+            7 => ErrorClass::NoMem,
+            8 => ErrorClass::ReadOnly,
+            9 => ErrorClass::Interrupt,
+            10 => ErrorClass::IoErr,
+            11 => ErrorClass::Corrupt,
+            12 => ErrorClass::NotFound,
+            13 => ErrorClass::Full,
+            14 => ErrorClass::CantOpen,
+            17 => ErrorClass::Schema,
+            18 => ErrorClass::TooBig,
+            19 => ErrorClass::Constraint,
+            20 => ErrorClass::Mismatch,
+            21 => ErrorClass::Misuse,
+            25 => ErrorClass::Range,
+            26 => ErrorClass::NotADb,
+            _ => return None,
+        };
+        Some(class)
+    }
+}
+
+/// Lock levels of a database file, from weakest to strongest, with the same
+/// meaning as SQLite's: any number of connections may hold SHARED, one may
+/// hold RESERVED (it is going to write) next to them, PENDING keeps new
+/// SHARED locks out while it waits for the readers to leave, and EXCLUSIVE
+/// excludes everybody else. Used by VFS files, and by the files of core's
+/// own IO backends.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockLevel {
+    None = 0,
+    Shared = 1,
+    Reserved = 2,
+    Pending = 3,
+    Exclusive = 4,
+}
+
 #[repr(C)]
 #[derive(PartialEq, Debug, Eq, Clone, Copy)]
 /// StepResult is used to represent the state of a query as it is exposed
@@ -294,6 +431,11 @@ impl Value {
         self.value_type
     }
 
+    /// Returns true if the Value is NULL
+    pub const fn is_null(&self) -> bool {
+        matches!(self.value_type, ValueType::Null)
+    }
+
     /// Returns the float value or casts the relevant value to a float
     pub fn to_float(&self) -> Option<f64> {
         match self.value_type {
@@ -307,7 +449,8 @@ impl Value {
         }
     }
 
-    /// Returns the text value if the Value is the proper type
+    /// Returns the text value if the Value is the proper type.
+    /// Text values are always UTF-8, whatever the encoding of the database.
     pub fn to_text(&self) -> Option<&str> {
         unsafe {
             if self.value_type == ValueType::Text && !self.value.text.is_null() {
@@ -331,6 +474,12 @@ impl Value {
         }
     }
 
+    /// Returns the UTF-8 bytes of a text value without copying
+    pub fn text_bytes(&self) -> Option<&[u8]> {
+        self.to_text().map(str::as_bytes)
+    }
+
+    /// Returns true if the Value is text carrying the JSON subtype
     pub fn is_json(&self) -> bool {
         unsafe {
             if self.value_type == ValueType::Text && !self.value.text.is_null() {
@@ -379,6 +528,41 @@ impl Value {
         }
     }
 
+    /// Returns the value as an integer only if it can be represented exactly:
+    /// integers as is, floats with no fractional part inside the i64 range, and
+    /// text that parses as an i64. Unlike `to_integer()`, never truncates or saturates.
+    pub fn to_integer_exact(&self) -> Option<i64> {
+        match self.value_type {
+            ValueType::Integer => Some(unsafe { self.value.int }),
+            ValueType::Float => {
+                let f = unsafe { self.value.float };
+                // 2^63 is exactly representable as f64, while i64::MAX is not.
+                let bound = -(i64::MIN as f64);
+                if f.fract() == 0.0 && f >= -bound && f < bound {
+                    Some(f as i64)
+                } else {
+                    None
+                }
+            }
+            ValueType::Text => self.to_text().and_then(|txt| txt.trim().parse().ok()),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float only if it can be represented exactly:
+    /// floats as is, and integers whose magnitude fits in the f64 mantissa.
+    pub fn to_float_exact(&self) -> Option<f64> {
+        match self.value_type {
+            ValueType::Float => Some(unsafe { self.value.float }),
+            ValueType::Integer => {
+                let i = unsafe { self.value.int };
+                let f = i as f64;
+                (f as i128 == i as i128).then_some(f)
+            }
+            _ => None,
+        }
+    }
+
     /// Returns the error code if the value is an error
     pub fn to_error(&self) -> Option<ResultCode> {
         if self.value_type != ValueType::Error {
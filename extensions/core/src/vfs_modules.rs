@@ -1,8 +1,9 @@
-use crate::{ExtResult, ExtensionApi, ResultCode};
+use crate::{ExtResult, ExtensionApi, LockLevel, ResultCode};
 use std::{
     ffi::{c_char, c_void},
     ops::{Deref, DerefMut},
     ptr::NonNull,
+    sync::OnceLock,
 };
 
 /// Field for ExtensionApi to interface with VFS extensions,
@@ -10,6 +11,7 @@ use std::{
 #[repr(C)]
 pub struct VfsInterface {
     pub register_vfs: RegisterVfsFn,
+    pub find_vfs: FindVfsFn,
     pub builtin_vfs: *mut *const VfsImpl,
     pub builtin_vfs_count: i32,
 }
@@ -34,6 +36,29 @@ pub trait VfsExtension: Default + Send + Sync {
     fn get_current_time(&self) -> String {
         chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
     }
+    /// Whether files of this VFS implement the `shm_*` methods of [VfsFile],
+    /// so that core can coordinate the WAL of a database across processes
+    /// through a shared-memory file.
+    fn supports_shm(&self) -> bool {
+        false
+    }
+    /// Release a mapping returned by [VfsFile::shm_map] for the same `offset`
+    /// and `len`.
+    fn shm_unmap(&self, _ptr: *mut u8, _offset: u64, _len: usize) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+}
+
+/// Owner of a lock taken with [VfsFile::shm_lock].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShmLockScope {
+    /// The open file: two opens of the same file in one process exclude each
+    /// other, like Linux OFD locks.
+    OpenFile = 0,
+    /// The process, like POSIX `fcntl` locks. Core keeps connections of the
+    /// same process apart on its own.
+    Process = 1,
 }
 
 pub trait VfsFile: Send + Sync {
@@ -43,6 +68,64 @@ pub trait VfsFile: Send + Sync {
     fn unlock(&self) -> ExtResult<()> {
         Ok(())
     }
+    /// Raise the lock on the file to `level`, passing through the levels in
+    /// between, or fail with `ResultCode::Busy` if another connection is in
+    /// the way. Does nothing if the lock is already at least that strong. The
+    /// default maps SHARED to `lock(false)` and anything stronger to
+    /// `lock(true)`.
+    fn lock_level(&mut self, level: LockLevel) -> ExtResult<()> {
+        match level {
+            LockLevel::None => Ok(()),
+            LockLevel::Shared => self.lock(false),
+            _ => self.lock(true),
+        }
+    }
+    /// Lower the lock on the file to `level`, which is either
+    /// `LockLevel::Shared` or `LockLevel::None`.
+    fn unlock_level(&mut self, level: LockLevel) -> ExtResult<()> {
+        self.unlock()?;
+        match level {
+            LockLevel::None => Ok(()),
+            _ => self.lock(false),
+        }
+    }
+    /// Whether any connection, this one included, holds a RESERVED lock or
+    /// stronger on the file.
+    fn check_reserved_lock(&self) -> ExtResult<bool> {
+        Ok(false)
+    }
+    /// Lock the byte at `offset` of a shared-memory file, shared or
+    /// exclusive, waiting for it if `blocking`. Returns whether the lock was
+    /// taken. Only called if [VfsExtension::supports_shm] is true.
+    fn shm_lock(
+        &self,
+        _offset: u64,
+        _exclusive: bool,
+        _blocking: bool,
+        _scope: ShmLockScope,
+    ) -> ExtResult<bool> {
+        Err(ResultCode::Unimplemented)
+    }
+    fn shm_unlock(&self, _offset: u64, _scope: ShmLockScope) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+    /// Resize a shared-memory file.
+    fn shm_set_len(&self, _len: u64) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+    /// Map `len` bytes of a shared-memory file at `offset`, read-write and
+    /// shared with every other process mapping the file. The mapping must stay
+    /// valid until it is passed to [VfsExtension::shm_unmap], even if the file
+    /// is closed first.
+    fn shm_map(&self, _offset: u64, _len: usize) -> ExtResult<*mut u8> {
+        Err(ResultCode::Unimplemented)
+    }
+    /// Hint that the file is about to grow to `len` bytes, e.g. before a
+    /// checkpoint copies pages into the database file, so the space can be
+    /// reserved up front. Must not change the size of the file.
+    fn allocate(&self, _len: i64) -> ExtResult<()> {
+        Ok(())
+    }
     fn read(&mut self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()>;
     fn write(&mut self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()>;
     fn sync(&self, cb: Callback) -> ExtResult<()>;
@@ -62,11 +145,21 @@ pub struct VfsImpl {
     pub sync: VfsSync,
     pub lock: VfsLock,
     pub unlock: VfsUnlock,
+    pub lock_level: VfsLockLevel,
+    pub unlock_level: VfsUnlockLevel,
+    pub check_reserved_lock: VfsCheckReservedLock,
+    pub supports_shm: VfsSupportsShm,
+    pub shm_lock: VfsShmLock,
+    pub shm_unlock: VfsShmUnlock,
+    pub shm_set_len: VfsShmSetLen,
+    pub shm_map: VfsShmMap,
+    pub shm_unmap: VfsShmUnmap,
     pub size: VfsSize,
     pub run_once: VfsRunOnce,
     pub current_time: VfsGetCurrentTime,
     pub gen_random_number: VfsGenerateRandomNumber,
     pub truncate: VfsTruncate,
+    pub allocate: VfsAllocate,
 }
 
 /// a wrapper around the raw `*mut u8` buffer for extensions.
@@ -152,6 +245,16 @@ impl IOCallback {
             ctx: SendPtr(ctx),
         }
     }
+
+    /// Wrap `cb` to hand it to another VFS.
+    pub fn from_callback(cb: Callback) -> Self {
+        unsafe extern "C" fn call(res: i32, ctx: SendPtr) {
+            let cb = Box::from_raw(ctx.inner().as_ptr() as *mut Callback);
+            cb(res);
+        }
+        let ctx = Box::into_raw(Box::new(cb)) as *mut c_void;
+        Self::new(call, unsafe { NonNull::new_unchecked(ctx) })
+    }
 }
 
 pub type CallbackFn = unsafe extern "C" fn(res: i32, user_data: SendPtr);
@@ -159,6 +262,9 @@ pub type CallbackFn = unsafe extern "C" fn(res: i32, user_data: SendPtr);
 pub type RegisterVfsFn =
     unsafe extern "C" fn(name: *const c_char, vfs: *const VfsImpl) -> ResultCode;
 
+/// Look up a VFS known to core by name. Returns null if there is none.
+pub type FindVfsFn = unsafe extern "C" fn(name: *const c_char) -> *const VfsImpl;
+
 pub type VfsOpen = unsafe extern "C" fn(
     ctx: *const c_void,
     path: *const c_char,
@@ -187,10 +293,45 @@ pub type VfsSync = unsafe extern "C" fn(file: *const c_void, cb: IOCallback) ->
 pub type VfsTruncate =
     unsafe extern "C" fn(file: *const c_void, len: i64, cb: IOCallback) -> ResultCode;
 
+pub type VfsAllocate = unsafe extern "C" fn(file: *const c_void, len: i64) -> ResultCode;
+
 pub type VfsLock = unsafe extern "C" fn(file: *const c_void, exclusive: bool) -> ResultCode;
 
 pub type VfsUnlock = unsafe extern "C" fn(file: *const c_void) -> ResultCode;
 
+pub type VfsLockLevel = unsafe extern "C" fn(file: *const c_void, level: LockLevel) -> ResultCode;
+
+pub type VfsUnlockLevel = unsafe extern "C" fn(file: *const c_void, level: LockLevel) -> ResultCode;
+
+pub type VfsCheckReservedLock =
+    unsafe extern "C" fn(file: *const c_void, reserved: *mut bool) -> ResultCode;
+
+pub type VfsSupportsShm = unsafe extern "C" fn(ctx: *const c_void) -> bool;
+
+pub type VfsShmLock = unsafe extern "C" fn(
+    file: *const c_void,
+    offset: u64,
+    exclusive: bool,
+    blocking: bool,
+    scope: ShmLockScope,
+    acquired: *mut bool,
+) -> ResultCode;
+
+pub type VfsShmUnlock =
+    unsafe extern "C" fn(file: *const c_void, offset: u64, scope: ShmLockScope) -> ResultCode;
+
+pub type VfsShmSetLen = unsafe extern "C" fn(file: *const c_void, len: u64) -> ResultCode;
+
+pub type VfsShmMap = unsafe extern "C" fn(
+    file: *const c_void,
+    offset: u64,
+    len: usize,
+    ptr: *mut *mut u8,
+) -> ResultCode;
+
+pub type VfsShmUnmap =
+    unsafe extern "C" fn(ctx: *const c_void, ptr: *mut u8, offset: u64, len: usize) -> ResultCode;
+
 pub type VfsSize = unsafe extern "C" fn(file: *const c_void) -> i64;
 
 pub type VfsRunOnce = unsafe extern "C" fn(file: *const c_void) -> ResultCode;
@@ -218,6 +359,125 @@ impl VfsFileImpl {
     }
 }
 
+fn result(rc: ResultCode) -> ExtResult<()> {
+    if rc.is_ok() {
+        Ok(())
+    } else {
+        Err(rc)
+    }
+}
+
+impl VfsFileImpl {
+    fn vfs(&self) -> &VfsImpl {
+        unsafe { &*self.vfs }
+    }
+
+    pub fn read(&self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+        let cb = IOCallback::from_callback(cb);
+        result(unsafe { (self.vfs().read)(self.file, buf, offset, cb) })
+    }
+
+    pub fn write(&self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+        let cb = IOCallback::from_callback(cb);
+        result(unsafe { (self.vfs().write)(self.file, buf, offset, cb) })
+    }
+
+    pub fn sync(&self, cb: Callback) -> ExtResult<()> {
+        let cb = IOCallback::from_callback(cb);
+        result(unsafe { (self.vfs().sync)(self.file, cb) })
+    }
+
+    pub fn truncate(&self, len: i64, cb: Callback) -> ExtResult<()> {
+        let cb = IOCallback::from_callback(cb);
+        result(unsafe { (self.vfs().truncate)(self.file, len, cb) })
+    }
+
+    pub fn allocate(&self, len: i64) -> ExtResult<()> {
+        result(unsafe { (self.vfs().allocate)(self.file, len) })
+    }
+
+    pub fn size(&self) -> i64 {
+        unsafe { (self.vfs().size)(self.file) }
+    }
+
+    pub fn lock_level(&self, level: LockLevel) -> ExtResult<()> {
+        result(unsafe { (self.vfs().lock_level)(self.file, level) })
+    }
+
+    pub fn unlock_level(&self, level: LockLevel) -> ExtResult<()> {
+        result(unsafe { (self.vfs().unlock_level)(self.file, level) })
+    }
+
+    pub fn check_reserved_lock(&self) -> ExtResult<bool> {
+        let mut reserved = false;
+        result(unsafe { (self.vfs().check_reserved_lock)(self.file, &mut reserved) })?;
+        Ok(reserved)
+    }
+}
+
+static FIND_VFS: OnceLock<FindVfsFn> = OnceLock::new();
+
+/// Remember how to look up the VFSes of the core this extension is loaded
+/// into. Called by the registration functions `VfsDerive` generates.
+#[doc(hidden)]
+pub fn set_find_vfs(find: FindVfsFn) {
+    let _ = FIND_VFS.set(find);
+}
+
+/// Look up a VFS by name, for a VFS extension to layer itself on top of it.
+/// Finds the VFSes of other extensions as well as the IO backends built into
+/// core (`syscall`, `memory`, ...) or registered with it. Returns `None` if
+/// there is no such VFS or no VFS of this extension has been registered yet.
+pub fn find_vfs(name: &str) -> Option<VfsRef> {
+    let find = FIND_VFS.get()?;
+    let name = std::ffi::CString::new(name).ok()?;
+    unsafe { VfsRef::from_raw(find(name.as_ptr())) }
+}
+
+/// A VFS found with [find_vfs].
+#[derive(Clone, Copy)]
+pub struct VfsRef {
+    vfs: *const VfsImpl,
+}
+
+unsafe impl Send for VfsRef {}
+unsafe impl Sync for VfsRef {}
+
+impl VfsRef {
+    /// # Safety
+    /// `vfs` must be null or point to a `VfsImpl` that is never freed.
+    pub unsafe fn from_raw(vfs: *const VfsImpl) -> Option<Self> {
+        if vfs.is_null() {
+            return None;
+        }
+        Some(Self { vfs })
+    }
+
+    fn vfs(&self) -> &VfsImpl {
+        unsafe { &*self.vfs }
+    }
+
+    pub fn open_file(&self, path: &str, flags: i32, direct: bool) -> ExtResult<VfsFileImpl> {
+        let path = std::ffi::CString::new(path).map_err(|_| ResultCode::InvalidArgs)?;
+        let file =
+            unsafe { (self.vfs().open)(self.vfs as *const c_void, path.as_ptr(), flags, direct) };
+        VfsFileImpl::new(file, self.vfs)
+    }
+
+    pub fn remove_file(&self, path: &str) -> ExtResult<()> {
+        let path = std::ffi::CString::new(path).map_err(|_| ResultCode::InvalidArgs)?;
+        result(unsafe { (self.vfs().remove)(self.vfs as *const c_void, path.as_ptr()) })
+    }
+
+    pub fn run_once(&self) -> ExtResult<()> {
+        result(unsafe { (self.vfs().run_once)(self.vfs().vfs) })
+    }
+
+    pub fn generate_random_number(&self) -> i64 {
+        unsafe { (self.vfs().gen_random_number)() }
+    }
+}
+
 impl Drop for VfsFileImpl {
     fn drop(&mut self) {
         if self.vfs.is_null() || self.file.is_null() {
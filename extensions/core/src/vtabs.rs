@@ -18,6 +18,7 @@ pub struct VTabModuleImpl {
     pub name: *const c_char,
     pub readonly: bool,
     pub create: VtabFnCreate,
+    pub connect: VtabFnCreate,
     pub open: VtabFnOpen,
     pub close: VtabFnClose,
     pub filter: VtabFnFilter,
@@ -27,11 +28,15 @@ pub struct VTabModuleImpl {
     pub update: VtabFnUpdate,
     pub rowid: VtabRowIDFn,
     pub destroy: VtabFnDestroy,
+    pub disconnect: VtabFnDestroy,
     pub best_idx: BestIdxFn,
     pub begin: VtabBegin,
     pub commit: VtabCommit,
     pub rollback: VtabRollback,
     pub rename: VtabRename,
+    pub set_connection: VtabSetConnection,
+    pub set_name: VtabSetName,
+    pub created: VtabCreated,
 }
 
 // SAFETY: VTabModuleImpl contains function pointers and a name pointer that are
@@ -49,7 +54,20 @@ pub struct VTabCreateResult {
 #[cfg(feature = "core_only")]
 impl VTabModuleImpl {
     pub fn create(&self, args: Vec<Value>) -> crate::ExtResult<(String, *const c_void)> {
-        let result = unsafe { (self.create)(args.as_ptr(), args.len() as i32) };
+        self.instantiate(self.create, args)
+    }
+
+    /// Like [Self::create], for a table that already exists in the schema.
+    pub fn connect(&self, args: Vec<Value>) -> crate::ExtResult<(String, *const c_void)> {
+        self.instantiate(self.connect, args)
+    }
+
+    fn instantiate(
+        &self,
+        func: VtabFnCreate,
+        args: Vec<Value>,
+    ) -> crate::ExtResult<(String, *const c_void)> {
+        let result = unsafe { func(args.as_ptr(), args.len() as i32) };
         for arg in args {
             unsafe { arg.__free_internal_type() };
         }
@@ -69,8 +87,9 @@ impl VTabModuleImpl {
     //       is executed, using the `shell_add_schema` UDF function.
     pub fn create_schema(&self, args: Vec<Value>) -> crate::ExtResult<String> {
         self.create(args).and_then(|(schema, table)| {
-            // Drop the allocated table instance to avoid a memory leak.
-            let result = unsafe { (self.destroy)(table) };
+            // Drop the allocated table instance to avoid a memory leak,
+            // without destroying whatever the module created for it.
+            let result = unsafe { (self.disconnect)(table) };
             if result.is_ok() {
                 Ok(schema)
             } else {
@@ -115,6 +134,9 @@ pub type VtabFnDestroy = unsafe extern "C" fn(table: *const c_void) -> ResultCod
 pub type VtabBegin = unsafe extern "C" fn(table: *mut c_void) -> ResultCode;
 pub type VtabCommit = unsafe extern "C" fn(table: *mut c_void) -> ResultCode;
 pub type VtabRollback = unsafe extern "C" fn(table: *mut c_void) -> ResultCode;
+pub type VtabSetConnection = unsafe extern "C" fn(table: *mut c_void, conn: *const Conn);
+pub type VtabSetName = unsafe extern "C" fn(table: *mut c_void, name: *const c_char);
+pub type VtabCreated = unsafe extern "C" fn(table: *mut c_void) -> ResultCode;
 pub type VtabRename =
     unsafe extern "C" fn(table: *mut c_void, new_name: *const c_char) -> ResultCode;
 
@@ -141,6 +163,13 @@ pub trait VTabModule: 'static {
     /// Creates a new instance of a virtual table.
     /// Returns a tuple where the first element is the table's schema.
     fn create(args: &[Value]) -> Result<(String, Self::Table), ResultCode>;
+
+    /// Opens a virtual table that already exists in the schema, e.g. when the
+    /// database is opened again, as opposed to `create`, which runs for
+    /// `CREATE VIRTUAL TABLE`. Defaults to `create`.
+    fn connect(args: &[Value]) -> Result<(String, Self::Table), ResultCode> {
+        Self::create(args)
+    }
 }
 
 pub trait VTable {
@@ -174,6 +203,23 @@ pub trait VTable {
     fn destroy(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
+    /// Called with the connection running the statement right before `created`, `insert`,
+    /// `update`, `delete`, `commit` or `destroy`, and with `None` once any of them but
+    /// `destroy` returns.
+    /// Tables that keep their data in shadow tables can query them through it, inside the
+    /// transaction of that statement. The connection must not be used after it is unset.
+    fn set_connection(&mut self, _conn: Option<Arc<Connection>>) {}
+
+    /// Called with the name of the table right after `create` or `connect`, e.g. to name
+    /// its shadow tables. `rename` is called when the table is renamed afterwards.
+    fn set_name(&mut self, _name: &str) {}
+
+    /// Called once the table is created by `CREATE VIRTUAL TABLE`, after `set_name`, e.g. to
+    /// create its shadow tables. It is not called for tables opened with `connect`, whose
+    /// shadow tables already exist. An error fails the statement.
+    fn created(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
 
     /// The query planner may call this method multiple times during optimization, exploring
     /// different join orders. Each call asks the virtual table which constraints (WHERE clause
@@ -243,6 +289,12 @@ pub enum ConstraintOp {
     IsNull = 71,
     Is = 72,
     In = 73,
+    /// The LIMIT of the query. Only offered, with column index 0, when the table is
+    /// the only one in the query and was offered every WHERE and ORDER BY term.
+    Limit = 74,
+    /// The OFFSET of the query, offered along with its LIMIT. Core skips the rows
+    /// itself unless the constraint is used and omitted.
+    Offset = 75,
 }
 
 #[repr(C)]
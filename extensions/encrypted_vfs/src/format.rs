@@ -0,0 +1,215 @@
+//! On-disk format of encrypted files. See the crate docs.
+
+use aes_gcm::aead::{AeadCore, AeadInPlace, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce, Tag};
+
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+/// Bytes a record takes in the underlying file on top of its plaintext.
+pub(crate) const RECORD_OVERHEAD: u64 = (NONCE_SIZE + TAG_SIZE) as u64;
+
+/// Size of the plaintext header at the start of every file: magic (8 bytes),
+/// file id (16), size of the first record (4) and of the others (4).
+pub(crate) const HEADER_SIZE: usize = 32;
+const MAGIC: &[u8; 8] = b"TursoEVF";
+
+const WAL_MAGIC: [u32; 2] = [0x377f0682, 0x377f0683];
+const WAL_HEADER_SIZE: u64 = 32;
+const WAL_FRAME_HEADER_SIZE: u64 = 24;
+const DB_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Plaintext sizes of the records of a file: the first one, then all the
+/// others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layout {
+    first: u64,
+    rest: u64,
+}
+
+impl Layout {
+    /// Layout for a file whose first write, at offset 0, is `data`.
+    pub(crate) fn for_first_write(data: &[u8]) -> Option<Self> {
+        let len = data.len() as u64;
+        Self::from_header(data).or((len > 0).then_some(Self {
+            first: len,
+            rest: len,
+        }))
+    }
+
+    /// Layout of a WAL or database file, read from the header `data` starts
+    /// with: the WAL header and then its frames, or the pages of the database.
+    pub(crate) fn from_header(data: &[u8]) -> Option<Self> {
+        let be32 = |at: usize| {
+            data.get(at..at + 4)
+                .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as u64)
+        };
+        let valid =
+            |page_size: u64| page_size.is_power_of_two() && (512..=65536).contains(&page_size);
+        if be32(0).is_some_and(|magic| WAL_MAGIC.contains(&(magic as u32))) {
+            let page_size = be32(8).filter(|size| valid(*size))?;
+            return Some(Self {
+                first: WAL_HEADER_SIZE,
+                rest: WAL_FRAME_HEADER_SIZE + page_size,
+            });
+        }
+        if data.starts_with(DB_MAGIC) && data.len() >= 18 {
+            let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+                1 => 65536,
+                size => size as u64,
+            };
+            return valid(page_size).then_some(Self {
+                first: page_size,
+                rest: page_size,
+            });
+        }
+        None
+    }
+
+    /// Offset of `record` in the file.
+    pub(crate) fn start(&self, record: u64) -> u64 {
+        match record {
+            0 => 0,
+            _ => self.first + (record - 1) * self.rest,
+        }
+    }
+
+    pub(crate) fn len(&self, record: u64) -> u64 {
+        match record {
+            0 => self.first,
+            _ => self.rest,
+        }
+    }
+
+    /// Record holding the byte at `pos`.
+    pub(crate) fn record_at(&self, pos: u64) -> u64 {
+        match pos.checked_sub(self.first) {
+            None => 0,
+            Some(pos) => 1 + pos / self.rest,
+        }
+    }
+
+    pub(crate) fn is_boundary(&self, pos: u64) -> bool {
+        self.start(self.record_at(pos)) == pos
+    }
+
+    /// Offset of `record` in the underlying file.
+    pub(crate) fn physical_offset(&self, record: u64) -> u64 {
+        HEADER_SIZE as u64 + self.start(record) + record * RECORD_OVERHEAD
+    }
+
+    /// Number of records an underlying file of `size` bytes holds in full.
+    pub(crate) fn complete_records(&self, size: u64) -> u64 {
+        match size.checked_sub(self.physical_offset(1)) {
+            None => 0,
+            Some(rest) => 1 + rest / (self.rest + RECORD_OVERHEAD),
+        }
+    }
+}
+
+/// Plaintext header at the start of every file.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FileHeader {
+    bytes: [u8; HEADER_SIZE],
+    layout: Layout,
+}
+
+impl FileHeader {
+    /// Header of a new file, with a random id.
+    pub(crate) fn new(layout: Layout) -> Self {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[..8].copy_from_slice(MAGIC);
+        getrandom::fill(&mut bytes[8..24]).expect("failed to generate a file id");
+        bytes[24..28].copy_from_slice(&(layout.first as u32).to_be_bytes());
+        bytes[28..32].copy_from_slice(&(layout.rest as u32).to_be_bytes());
+        Self { bytes, layout }
+    }
+
+    pub(crate) fn parse(bytes: &[u8; HEADER_SIZE]) -> Option<Self> {
+        if &bytes[..8] != MAGIC {
+            return None;
+        }
+        let field = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as u64;
+        let layout = Layout {
+            first: field(24),
+            rest: field(28),
+        };
+        if layout.first == 0 || layout.rest == 0 {
+            return None;
+        }
+        Some(Self {
+            bytes: *bytes,
+            layout,
+        })
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub(crate) fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Associated data of `record`: the whole header, so that the record is
+    /// bound to this file and its layout, and the index of the record.
+    fn associated_data(&self, record: u64) -> [u8; HEADER_SIZE + 8] {
+        let mut aad = [0u8; HEADER_SIZE + 8];
+        aad[..HEADER_SIZE].copy_from_slice(&self.bytes);
+        aad[HEADER_SIZE..].copy_from_slice(&record.to_be_bytes());
+        aad
+    }
+}
+
+pub(crate) struct Cipher(Aes256Gcm);
+
+impl Cipher {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        Self(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)))
+    }
+
+    /// Append `record` of the file with `header`, holding `plain`, to `out`
+    /// as it is stored in the underlying file.
+    pub(crate) fn seal(
+        &self,
+        header: &FileHeader,
+        record: u64,
+        plain: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), aes_gcm::Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        out.extend_from_slice(&nonce);
+        let start = out.len();
+        out.extend_from_slice(plain);
+        let tag = self.0.encrypt_in_place_detached(
+            &nonce,
+            &header.associated_data(record),
+            &mut out[start..],
+        )?;
+        out.extend_from_slice(&tag);
+        Ok(())
+    }
+
+    /// Decrypt `record`, `sealed` being what the underlying file holds for
+    /// it, into `out`. Fails if it doesn't authenticate.
+    pub(crate) fn open(
+        &self,
+        header: &FileHeader,
+        record: u64,
+        sealed: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), aes_gcm::Error> {
+        if sealed.len() < RECORD_OVERHEAD as usize {
+            return Err(aes_gcm::Error);
+        }
+        let (nonce, rest) = sealed.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+        out.clear();
+        out.extend_from_slice(ciphertext);
+        self.0.decrypt_in_place_detached(
+            Nonce::from_slice(nonce),
+            &header.associated_data(record),
+            out,
+            Tag::from_slice(tag),
+        )
+    }
+}
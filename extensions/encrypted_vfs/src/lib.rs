@@ -0,0 +1,651 @@
+//! A VFS that encrypts the files of another VFS with AES-256-GCM.
+//!
+//! The `encrypted` VFS stores every file in an inner VFS, which only ever
+//! sees ciphertext. The inner VFS is named by the `TURSO_ENCRYPTED_VFS_INNER`
+//! environment variable and defaults to `syscall`, plain files. It can be any
+//! VFS core knows: another extension's, one of core's backends, or a Rust
+//! backend registered with `register_io`, e.g. one keeping files in an object
+//! store. This is independent of the pager's encryption and covers the WAL as
+//! well.
+//!
+//! The 256-bit key is read as 64 hex digits from `TURSO_ENCRYPTED_VFS_KEY`.
+//! When the extension is linked in statically, [set_key_callback] can instead
+//! install a callback asked for the key of every file as it is opened.
+//!
+//! Files are stored as a plaintext header followed by records, each sealed on
+//! its own as a random nonce (12 bytes), the ciphertext and the tag (16
+//! bytes). Records match what core writes, so a write never re-encrypts data
+//! that is already on disk: the WAL is split into its header and then one
+//! record per frame, the database into pages. The sizes come from the first
+//! write to the file, which is the header of either, and are kept in the file
+//! header along with a random file id. The file header and the index of a
+//! record are its associated data, so records can't be moved around, within a
+//! file or between files, undetected. Writes that don't cover whole records
+//! fail, so files written in other patterns, like the MVCC logical log, are
+//! not supported.
+//!
+//! A crash in the middle of an append can leave the last record torn. It reads
+//! as the end of the file, the way core expects a torn WAL frame to, while any
+//! other record that fails to authenticate fails the read.
+//!
+//! Only one process may use a database through this VFS at a time.
+
+mod format;
+
+use format::{Cipher, FileHeader, Layout, HEADER_SIZE, RECORD_OVERHEAD};
+use std::sync::{Arc, Mutex, OnceLock};
+use turso_ext::{
+    register_extension, BufferRef, Callback, ExtResult, LockLevel, ResultCode, VfsDerive,
+    VfsExtension, VfsFile, VfsFileImpl, VfsRef,
+};
+
+register_extension! {
+    vfs: { EncryptedVfs },
+}
+
+/// Environment variable naming the VFS the encrypted files are stored in.
+pub const INNER_VFS_VAR: &str = "TURSO_ENCRYPTED_VFS_INNER";
+/// Environment variable holding the key as 64 hex digits.
+pub const KEY_VAR: &str = "TURSO_ENCRYPTED_VFS_KEY";
+const DEFAULT_INNER_VFS: &str = "syscall";
+
+/// Reported for records that fail to authenticate and short writes.
+const EIO: i32 = 5;
+
+/// Returns the key of the file at the given path, or `None` to refuse to open
+/// it.
+pub type KeyCallback = dyn Fn(&str) -> Option<[u8; 32]> + Send + Sync;
+
+static KEY_CALLBACK: OnceLock<Box<KeyCallback>> = OnceLock::new();
+
+/// Ask `callback` for the key of every file opened from now on instead of
+/// reading [KEY_VAR]. Only works if the extension is linked in statically, as
+/// a loaded library has its own copy of this crate. Returns false if a
+/// callback was already set.
+pub fn set_key_callback(callback: Box<KeyCallback>) -> bool {
+    KEY_CALLBACK.set(callback).is_ok()
+}
+
+fn key_for(path: &str) -> ExtResult<[u8; 32]> {
+    if let Some(callback) = KEY_CALLBACK.get() {
+        return callback(path).ok_or_else(|| {
+            log::error!("no encryption key for {path}");
+            ResultCode::Error
+        });
+    }
+    let Ok(value) = std::env::var(KEY_VAR) else {
+        log::error!("{KEY_VAR} is not set");
+        return Err(ResultCode::Error);
+    };
+    parse_hex_key(value.trim()).ok_or_else(|| {
+        log::error!("{KEY_VAR} must hold a 256-bit key as 64 hex digits");
+        ResultCode::InvalidArgs
+    })
+}
+
+fn parse_hex_key(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+/// Run `op` and wait for its callback, driving `vfs` in the meantime.
+fn wait(vfs: &VfsRef, op: impl FnOnce(Callback) -> ExtResult<()>) -> ExtResult<i32> {
+    let result = Arc::new(Mutex::new(None));
+    let done = result.clone();
+    op(Box::new(move |res| *done.lock().unwrap() = Some(res)))?;
+    loop {
+        if let Some(res) = result.lock().unwrap().take() {
+            return Ok(res);
+        }
+        vfs.run_once()?;
+    }
+}
+
+#[derive(VfsDerive, Default)]
+pub struct EncryptedVfs {
+    inner: OnceLock<VfsRef>,
+}
+
+impl EncryptedVfs {
+    /// The VFS the files are stored in, looked up on first use since it may be
+    /// registered after this one.
+    fn inner(&self) -> ExtResult<VfsRef> {
+        if let Some(inner) = self.inner.get() {
+            return Ok(*inner);
+        }
+        let name = std::env::var(INNER_VFS_VAR).unwrap_or_else(|_| DEFAULT_INNER_VFS.to_string());
+        if name == Self::NAME {
+            log::error!("the encrypted VFS can't store its files in itself");
+            return Err(ResultCode::InvalidArgs);
+        }
+        let inner = turso_ext::find_vfs(&name).ok_or_else(|| {
+            log::error!("no VFS named {name} to store encrypted files in");
+            ResultCode::NotFound
+        })?;
+        Ok(*self.inner.get_or_init(|| inner))
+    }
+}
+
+impl VfsExtension for EncryptedVfs {
+    const NAME: &'static str = "encrypted";
+    type File = EncryptedFile;
+
+    fn open_file(&self, path: &str, flags: i32, _direct: bool) -> ExtResult<Self::File> {
+        log::debug!("opening file with encrypted VFS: {path} flags: {flags}");
+        EncryptedFile::open(self.inner()?, path, flags, &key_for(path)?)
+    }
+
+    fn remove_file(&self, path: &str) -> ExtResult<()> {
+        self.inner()?.remove_file(path)
+    }
+
+    fn run_once(&self) -> ExtResult<()> {
+        self.inner()?.run_once()
+    }
+}
+
+pub struct EncryptedFile {
+    file: VfsFileImpl,
+    path: Arc<str>,
+    cipher: Arc<Cipher>,
+    /// `None` until the first write to an empty file.
+    header: Arc<Mutex<Option<FileHeader>>>,
+}
+
+impl EncryptedFile {
+    /// Open `path` in `vfs`. If the file exists it must have been written with
+    /// `key`.
+    pub fn open(vfs: VfsRef, path: &str, flags: i32, key: &[u8; 32]) -> ExtResult<Self> {
+        // Records are not aligned in the underlying file.
+        let file = vfs.open_file(path, flags, false)?;
+        let mut header = None;
+        if file.size() >= HEADER_SIZE as i64 {
+            let mut bytes = [0u8; HEADER_SIZE];
+            let buf = unsafe { BufferRef::new(bytes.as_mut_ptr(), HEADER_SIZE) };
+            let n = wait(&vfs, |cb| file.read(buf, 0, cb))?;
+            if n != HEADER_SIZE as i32 {
+                log::error!("failed to read the header of {path}: {n}");
+                return Err(ResultCode::Error);
+            }
+            // A file whose creation was torn before its header reached the
+            // disk is empty.
+            if bytes.iter().any(|b| *b != 0) {
+                header = Some(FileHeader::parse(&bytes).ok_or_else(|| {
+                    log::error!("{path} is not a file of the encrypted VFS");
+                    ResultCode::Corrupt
+                })?);
+            }
+        }
+        Ok(Self {
+            file,
+            path: Arc::from(path),
+            cipher: Arc::new(Cipher::new(key)),
+            header: Arc::new(Mutex::new(header)),
+        })
+    }
+}
+
+impl VfsFile for EncryptedFile {
+    fn lock_level(&mut self, level: LockLevel) -> ExtResult<()> {
+        self.file.lock_level(level)
+    }
+
+    fn unlock_level(&mut self, level: LockLevel) -> ExtResult<()> {
+        self.file.unlock_level(level)
+    }
+
+    fn check_reserved_lock(&self) -> ExtResult<bool> {
+        self.file.check_reserved_lock()
+    }
+
+    fn allocate(&self, len: i64) -> ExtResult<()> {
+        let Some(header) = *self.header.lock().unwrap() else {
+            return Ok(());
+        };
+        let layout = header.layout();
+        let len = len as u64;
+        if !layout.is_boundary(len) {
+            return Ok(());
+        }
+        self.file
+            .allocate(layout.physical_offset(layout.record_at(len)) as i64)
+    }
+
+    fn read(&mut self, mut buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+        let offset = offset as u64;
+        let Some(header) = *self.header.lock().unwrap() else {
+            cb(0);
+            return Ok(());
+        };
+        let layout = header.layout();
+        let records = layout.complete_records(self.file.size().max(0) as u64);
+        let end = (offset + buf.len() as u64).min(layout.start(records));
+        if offset >= end {
+            cb(0);
+            return Ok(());
+        }
+        let first = layout.record_at(offset);
+        let last = layout.record_at(end - 1);
+        let start = layout.physical_offset(first);
+        let mut sealed = vec![0u8; (layout.physical_offset(last + 1) - start) as usize];
+        let sealed_buf = unsafe { BufferRef::new(sealed.as_mut_ptr(), sealed.len()) };
+        let cipher = self.cipher.clone();
+        let path = self.path.clone();
+        self.file.read(
+            sealed_buf,
+            start as i64,
+            Box::new(move |res| {
+                if res < 0 {
+                    cb(res);
+                    return;
+                }
+                let sealed = &sealed[..res as usize];
+                let mut plain = Vec::new();
+                let mut copied = 0;
+                for record in first..=last {
+                    let at = (layout.physical_offset(record) - start) as usize;
+                    let len = (layout.len(record) + RECORD_OVERHEAD) as usize;
+                    // The file shrank since the read was submitted.
+                    let Some(bytes) = sealed.get(at..at + len) else {
+                        break;
+                    };
+                    if cipher.open(&header, record, bytes, &mut plain).is_err() {
+                        if record + 1 == records {
+                            log::warn!("last record of {path} is torn, reading it as the end");
+                            break;
+                        }
+                        log::error!("record {record} of {path} failed to authenticate");
+                        cb(-EIO);
+                        return;
+                    }
+                    let record_start = layout.start(record);
+                    let from = offset.saturating_sub(record_start) as usize;
+                    let to = ((end - record_start) as usize).min(plain.len());
+                    buf[copied..copied + to - from].copy_from_slice(&plain[from..to]);
+                    copied += to - from;
+                }
+                cb(copied as i32);
+            }),
+        )
+    }
+
+    fn write(&mut self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+        let offset = offset as u64;
+        let data = buf.as_slice();
+        if data.is_empty() {
+            cb(0);
+            return Ok(());
+        }
+        let mut guard = self.header.lock().unwrap();
+        let (header, new_header) = match *guard {
+            Some(header) => (header, false),
+            None => {
+                if offset != 0 {
+                    log::error!("first write to {} is at {offset}", self.path);
+                    return Err(ResultCode::Error);
+                }
+                let layout = Layout::for_first_write(data).ok_or(ResultCode::Error)?;
+                (FileHeader::new(layout), true)
+            }
+        };
+        let layout = header.layout();
+        let end = offset + data.len() as u64;
+        if !layout.is_boundary(offset) || !layout.is_boundary(end) {
+            log::error!(
+                "write of {} bytes at {offset} to {} doesn't cover whole records of {layout:?}",
+                data.len(),
+                self.path
+            );
+            return Err(ResultCode::Error);
+        }
+        if offset == 0 && Layout::from_header(data).is_some_and(|l| l != layout) {
+            log::error!("page size of {} changed, truncate it first", self.path);
+            return Err(ResultCode::Error);
+        }
+
+        let first = layout.record_at(offset);
+        let mut sealed = Vec::with_capacity(
+            HEADER_SIZE
+                + data.len()
+                + (layout.record_at(end) - first) as usize * RECORD_OVERHEAD as usize,
+        );
+        let pos = if new_header {
+            sealed.extend_from_slice(header.as_bytes());
+            0
+        } else {
+            layout.physical_offset(first)
+        };
+        let mut record = first;
+        let mut at = 0;
+        while at < data.len() {
+            let len = layout.len(record) as usize;
+            self.cipher
+                .seal(&header, record, &data[at..at + len], &mut sealed)
+                .map_err(|_| ResultCode::Error)?;
+            at += len;
+            record += 1;
+        }
+        if new_header {
+            *guard = Some(header);
+        }
+        drop(guard);
+
+        let expected = sealed.len();
+        let written = data.len() as i32;
+        let sealed_buf = unsafe { BufferRef::new(sealed.as_mut_ptr(), expected) };
+        let state = self.header.clone();
+        let forget_header = move || {
+            if new_header {
+                state.lock().unwrap().take();
+            }
+        };
+        let on_failure = forget_header.clone();
+        let result = self.file.write(
+            sealed_buf,
+            pos as i64,
+            Box::new(move |res| {
+                let _sealed = sealed;
+                if res >= 0 && res as usize == expected {
+                    cb(written);
+                    return;
+                }
+                on_failure();
+                cb(if res < 0 { res } else { -EIO });
+            }),
+        );
+        if result.is_err() {
+            forget_header();
+        }
+        result
+    }
+
+    fn sync(&self, cb: Callback) -> ExtResult<()> {
+        self.file.sync(cb)
+    }
+
+    fn truncate(&self, len: i64, cb: Callback) -> ExtResult<()> {
+        let len = len as u64;
+        let mut header = self.header.lock().unwrap();
+        let physical = match *header {
+            None => 0,
+            // The next write picks the record sizes again, and a new file id.
+            Some(_) if len == 0 => {
+                *header = None;
+                0
+            }
+            Some(current) => {
+                let layout = current.layout();
+                if !layout.is_boundary(len) {
+                    log::error!(
+                        "truncating {} to {len} would cut a record of {layout:?}",
+                        self.path
+                    );
+                    return Err(ResultCode::Error);
+                }
+                layout.physical_offset(layout.record_at(len))
+            }
+        };
+        drop(header);
+        self.file.truncate(physical as i64, cb)
+    }
+
+    fn size(&self) -> i64 {
+        let size = self.file.size();
+        if size < 0 {
+            return size;
+        }
+        match *self.header.lock().unwrap() {
+            None => 0,
+            Some(header) => {
+                let layout = header.layout();
+                layout.start(layout.complete_records(size as u64)) as i64
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    type Files = Mutex<HashMap<String, Arc<Mutex<Vec<u8>>>>>;
+
+    /// Contents of the files of [MemVfs], by path.
+    fn files() -> &'static Files {
+        static FILES: OnceLock<Files> = OnceLock::new();
+        FILES.get_or_init(Default::default)
+    }
+
+    fn contents(path: &str) -> Arc<Mutex<Vec<u8>>> {
+        files()
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Inner VFS keeping its files in memory and completing every operation
+    /// right away.
+    #[derive(VfsDerive, Default)]
+    struct MemVfs;
+
+    struct MemFile(Arc<Mutex<Vec<u8>>>);
+
+    impl VfsExtension for MemVfs {
+        const NAME: &'static str = "encrypted_test_memory";
+        type File = MemFile;
+
+        fn open_file(&self, path: &str, _flags: i32, _direct: bool) -> ExtResult<MemFile> {
+            Ok(MemFile(contents(path)))
+        }
+
+        fn remove_file(&self, path: &str) -> ExtResult<()> {
+            files().lock().unwrap().remove(path);
+            Ok(())
+        }
+    }
+
+    impl VfsFile for MemFile {
+        fn read(&mut self, mut buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+            let data = self.0.lock().unwrap();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            cb(n as i32);
+            Ok(())
+        }
+
+        fn write(&mut self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+            let mut data = self.0.lock().unwrap();
+            let end = offset as usize + buf.len();
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[offset as usize..end].copy_from_slice(&buf);
+            cb(buf.len() as i32);
+            Ok(())
+        }
+
+        fn sync(&self, cb: Callback) -> ExtResult<()> {
+            cb(0);
+            Ok(())
+        }
+
+        fn truncate(&self, len: i64, cb: Callback) -> ExtResult<()> {
+            self.0.lock().unwrap().truncate(len as usize);
+            cb(0);
+            Ok(())
+        }
+
+        fn size(&self) -> i64 {
+            self.0.lock().unwrap().len() as i64
+        }
+    }
+
+    const KEY: [u8; 32] = [7; 32];
+    const PAGE_SIZE: usize = 4096;
+
+    fn inner() -> VfsRef {
+        unsafe { VfsRef::from_raw(register_static_MemVfs()) }.unwrap()
+    }
+
+    fn open(path: &str, key: &[u8; 32]) -> EncryptedFile {
+        EncryptedFile::open(inner(), path, 1, key).unwrap()
+    }
+
+    fn callback() -> (Callback, Arc<AtomicI32>) {
+        let result = Arc::new(AtomicI32::new(i32::MIN));
+        let r = result.clone();
+        (Box::new(move |res| r.store(res, Ordering::SeqCst)), result)
+    }
+
+    fn buffer(data: &mut [u8]) -> BufferRef {
+        unsafe { BufferRef::new(data.as_mut_ptr(), data.len()) }
+    }
+
+    fn write(file: &mut EncryptedFile, offset: usize, mut data: Vec<u8>) {
+        let (cb, written) = callback();
+        file.write(buffer(&mut data), offset as i64, cb).unwrap();
+        assert_eq!(written.load(Ordering::SeqCst), data.len() as i32);
+    }
+
+    /// Read `len` bytes at `offset`, returning the bytes read or the error.
+    fn read(file: &mut EncryptedFile, offset: usize, len: usize) -> Result<Vec<u8>, i32> {
+        let mut out = vec![0u8; len];
+        let (cb, read) = callback();
+        file.read(buffer(&mut out), offset as i64, cb).unwrap();
+        let n = read.load(Ordering::SeqCst);
+        if n < 0 {
+            return Err(n);
+        }
+        out.truncate(n as usize);
+        Ok(out)
+    }
+
+    /// A database page, page 1 starting with the database header.
+    fn page(number: u8) -> Vec<u8> {
+        let mut page = vec![number; PAGE_SIZE];
+        if number == 1 {
+            page[..16].copy_from_slice(b"SQLite format 3\0");
+            page[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+        }
+        page
+    }
+
+    fn wal_header() -> Vec<u8> {
+        let mut header = vec![0u8; 32];
+        header[..4].copy_from_slice(&0x377f0682u32.to_be_bytes());
+        header[8..12].copy_from_slice(&(PAGE_SIZE as u32).to_be_bytes());
+        header
+    }
+
+    fn frame(number: u8) -> Vec<u8> {
+        vec![number; 24 + PAGE_SIZE]
+    }
+
+    #[test]
+    fn test_pages_round_trip() {
+        let path = "pages.db";
+        let mut file = open(path, &KEY);
+        write(&mut file, 0, page(1));
+        write(&mut file, PAGE_SIZE, [page(2), page(3)].concat());
+        assert_eq!(file.size(), 3 * PAGE_SIZE as i64);
+        // Only ciphertext reaches the inner VFS.
+        assert!(!contents(path)
+            .lock()
+            .unwrap()
+            .windows(64)
+            .any(|w| w.iter().all(|b| *b == 2)));
+
+        let mut file = open(path, &KEY);
+        assert_eq!(read(&mut file, 0, PAGE_SIZE).unwrap(), page(1));
+        // Reads may span and split records.
+        let both = read(&mut file, PAGE_SIZE + 100, PAGE_SIZE).unwrap();
+        assert_eq!(both, [&page(2)[100..], &page(3)[..100]].concat());
+        // Past the end.
+        assert_eq!(
+            read(&mut file, 2 * PAGE_SIZE, 2 * PAGE_SIZE).unwrap(),
+            page(3)
+        );
+        assert!(read(&mut file, 3 * PAGE_SIZE, 100).unwrap().is_empty());
+
+        // Without the key nothing can be read.
+        let mut other = open(path, &[8; 32]);
+        assert!(read(&mut other, 0, PAGE_SIZE).is_err());
+
+        let (cb, truncated) = callback();
+        file.truncate(PAGE_SIZE as i64, cb).unwrap();
+        assert_eq!(truncated.load(Ordering::SeqCst), 0);
+        assert_eq!(file.size(), PAGE_SIZE as i64);
+    }
+
+    #[test]
+    fn test_writes_cover_whole_records() {
+        let mut file = open("partial.db", &KEY);
+        write(&mut file, 0, page(1));
+        let mut data = vec![0u8; 100];
+        let (cb, _) = callback();
+        assert!(file.write(buffer(&mut data), 10, cb).is_err());
+        let (cb, _) = callback();
+        assert!(file.truncate(100, cb).is_err());
+        assert_eq!(read(&mut file, 0, PAGE_SIZE).unwrap(), page(1));
+    }
+
+    #[test]
+    fn test_wal_torn_tail_reads_short() {
+        let path = "torn.db-wal";
+        let frame_size = 24 + PAGE_SIZE;
+        let mut file = open(path, &KEY);
+        write(&mut file, 0, wal_header());
+        write(&mut file, 32, [frame(1), frame(2)].concat());
+        write(&mut file, 32 + 2 * frame_size, frame(3));
+        assert_eq!(file.size(), (32 + 3 * frame_size) as i64);
+
+        // The last frame only partly reached the disk.
+        let torn = contents(path).lock().unwrap().len() - 10;
+        contents(path).lock().unwrap().truncate(torn);
+        let mut file = open(path, &KEY);
+        assert_eq!(file.size(), (32 + 2 * frame_size) as i64);
+
+        // The last complete frame is all there, but garbled.
+        let last = 32 + 32 + 2 * frame_size + 3 * 28 - 100;
+        contents(path).lock().unwrap()[last] ^= 1;
+        assert_eq!(
+            read(&mut file, 32, 2 * frame_size).unwrap(),
+            frame(1),
+            "a torn tail is a short read"
+        );
+        assert!(read(&mut file, 32 + frame_size, frame_size)
+            .unwrap()
+            .is_empty());
+
+        // Anywhere else it is corruption.
+        contents(path).lock().unwrap()[100] ^= 1;
+        assert!(read(&mut file, 32, frame_size).is_err());
+    }
+
+    #[test]
+    fn test_records_are_bound_to_their_file() {
+        let mut a = open("a.db", &KEY);
+        let mut b = open("b.db", &KEY);
+        for file in [&mut a, &mut b] {
+            write(file, 0, page(1));
+            write(file, PAGE_SIZE, page(2));
+        }
+        // Same key, same index, but another file.
+        let record = 32 + PAGE_SIZE + 28;
+        let from_a = contents("a.db").lock().unwrap()[record..].to_vec();
+        contents("b.db").lock().unwrap()[record..].copy_from_slice(&from_a);
+        assert!(read(&mut b, PAGE_SIZE, PAGE_SIZE).is_err());
+        assert_eq!(read(&mut a, PAGE_SIZE, PAGE_SIZE).unwrap(), page(2));
+    }
+}
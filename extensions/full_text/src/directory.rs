@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tantivy::directory::error::{DeleteError, OpenReadError, OpenWriteError};
+use tantivy::directory::{
+    AntiCallToken, Directory, FileHandle, OwnedBytes, TerminatingWrite, WatchCallback, WatchHandle,
+    WritePtr,
+};
+
+/// The files of a tantivy index, kept in memory and saved to the `_segments` shadow
+/// table of the full-text table, so the index lives in the database file next to the
+/// rows it indexes. The directory tracks which files changed since they were last
+/// taken with [DbDirectory::take_changes], so only those are written to the table.
+///
+/// Core's `HybridBTreeDirectory` also keeps the files of its fts indexes in a B-tree,
+/// and reads them in chunks as they are needed, but it works on core's pager and
+/// B-tree cursors, which an extension has no access to. An extension runs SQL
+/// statements, on a connection it is only lent while a statement runs, and tantivy
+/// reads the files at any time, from its own threads too. So the files are read when
+/// the index is loaded, and kept in memory.
+#[derive(Clone, Default)]
+pub(crate) struct DbDirectory {
+    files: Arc<RwLock<Files>>,
+}
+
+#[derive(Default)]
+struct Files {
+    contents: HashMap<PathBuf, OwnedBytes>,
+    written: HashSet<PathBuf>,
+    deleted: HashSet<PathBuf>,
+    /// Set until the changes are first taken, unless the files were loaded from the
+    /// table, in which case the table holds no other index's files.
+    loaded: bool,
+}
+
+/// The files to write to, and delete from, the table that stores them.
+pub(crate) struct Changes {
+    /// The table's files belong to another index, and must all be deleted first.
+    pub(crate) replace: bool,
+    pub(crate) written: Vec<(String, Vec<u8>)>,
+    pub(crate) deleted: Vec<String>,
+}
+
+/// Lock files only matter to the writer holding them, and are never saved.
+fn is_lock(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "lock")
+}
+
+/// The files of a segment are named after its random id, and never rewritten. The
+/// JSON files are rewritten in place, and the delete files are named after an opstamp
+/// as well, which another index may have used for other deletes.
+fn is_written_once(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext != "json" && ext != "del" && ext != "lock")
+}
+
+impl DbDirectory {
+    /// A directory holding the files saved to the table.
+    pub(crate) fn load(files: impl IntoIterator<Item = (String, OwnedBytes)>) -> Self {
+        let contents = files
+            .into_iter()
+            .map(|(path, data)| (PathBuf::from(path), data))
+            .collect();
+        Self {
+            files: Arc::new(RwLock::new(Files {
+                contents,
+                loaded: true,
+                ..Files::default()
+            })),
+        }
+    }
+
+    /// The contents of `path` if it is a file that is never rewritten, so that an index
+    /// loaded again from the table shares it instead of reading it again.
+    pub(crate) fn written_once_file(&self, path: &str) -> Option<OwnedBytes> {
+        let path = Path::new(path);
+        if !is_written_once(path) {
+            return None;
+        }
+        self.read().contents.get(path).cloned()
+    }
+
+    /// The files written and deleted since the last call, or since the directory was
+    /// loaded.
+    pub(crate) fn take_changes(&self) -> Changes {
+        let mut files = self.write();
+        let written = std::mem::take(&mut files.written)
+            .into_iter()
+            .filter(|path| !is_lock(path))
+            .filter_map(|path| {
+                let data = files.contents.get(&path)?.as_slice().to_vec();
+                Some((path.to_string_lossy().into_owned(), data))
+            })
+            .collect();
+        let deleted = std::mem::take(&mut files.deleted)
+            .into_iter()
+            .filter(|path| !is_lock(path))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        let replace = !std::mem::replace(&mut files.loaded, true);
+        Changes {
+            replace,
+            written,
+            deleted,
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Files> {
+        self.files.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Files> {
+        self.files.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn store(&self, path: &Path, data: Vec<u8>) {
+        let mut files = self.write();
+        files
+            .contents
+            .insert(path.to_path_buf(), OwnedBytes::new(data));
+        files.deleted.remove(path);
+        files.written.insert(path.to_path_buf());
+    }
+
+    fn get(&self, path: &Path) -> Result<OwnedBytes, OpenReadError> {
+        self.read()
+            .contents
+            .get(path)
+            .cloned()
+            .ok_or_else(|| OpenReadError::FileDoesNotExist(path.to_path_buf()))
+    }
+}
+
+impl fmt::Debug for DbDirectory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbDirectory")
+            .field("files", &self.read().contents.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Directory for DbDirectory {
+    fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        Ok(Arc::new(self.get(path)?))
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), DeleteError> {
+        let mut files = self.write();
+        if files.contents.remove(path).is_none() {
+            return Err(DeleteError::FileDoesNotExist(path.to_path_buf()));
+        }
+        files.written.remove(path);
+        files.deleted.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
+        Ok(self.read().contents.contains_key(path))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<WritePtr, OpenWriteError> {
+        if self.read().contents.contains_key(path) {
+            return Err(OpenWriteError::FileAlreadyExists(path.to_path_buf()));
+        }
+        // the file exists as soon as it is opened, like one on disk
+        self.store(path, Vec::new());
+        Ok(BufWriter::new(Box::new(FileWriter {
+            directory: self.clone(),
+            path: path.to_path_buf(),
+            data: Vec::new(),
+        })))
+    }
+
+    fn atomic_read(&self, path: &Path) -> Result<Vec<u8>, OpenReadError> {
+        Ok(self.get(path)?.as_slice().to_vec())
+    }
+
+    fn atomic_write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.store(path, data.to_vec());
+        Ok(())
+    }
+
+    fn watch(&self, _callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        // readers are reloaded by hand once changes are committed
+        Ok(WatchHandle::empty())
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes a file, which is stored in the directory whenever it is flushed.
+struct FileWriter {
+    directory: DbDirectory,
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.directory.store(&self.path, self.data.clone());
+        Ok(())
+    }
+}
+
+impl TerminatingWrite for FileWriter {
+    fn terminate_ref(&mut self, _: AntiCallToken) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::SearchIndex;
+
+    fn text(values: &[&str]) -> Vec<Option<String>> {
+        values.iter().map(|value| Some(value.to_string())).collect()
+    }
+
+    fn rowids(index: &SearchIndex, query: &str) -> Vec<i64> {
+        let query = index.parse(query).unwrap();
+        let searcher = index.searcher();
+        let mut rowids: Vec<i64> = index
+            .search(&searcher, query.as_ref(), 0, 10)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.rowid)
+            .collect();
+        rowids.sort();
+        rowids
+    }
+
+    #[test]
+    fn indexes_reopen_from_their_saved_files() {
+        let columns = vec!["body".to_string()];
+        let rows = vec![
+            (1, text(&["a whale"])),
+            (2, text(&["the sea"])),
+            (3, text(&["a whale at sea"])),
+        ];
+        let index = SearchIndex::build(&columns, rows).unwrap();
+        let changes = index.directory().take_changes();
+        assert!(changes.replace);
+        assert!(changes.deleted.iter().all(|path| !path.ends_with(".lock")));
+        assert!(changes.written.iter().any(|(path, _)| path == "meta.json"));
+
+        let files = changes
+            .written
+            .into_iter()
+            .map(|(path, data)| (path, OwnedBytes::new(data)));
+        let reopened = SearchIndex::open(&columns, DbDirectory::load(files)).unwrap();
+        assert_eq!(rowids(&reopened, "whale"), vec![1, 3]);
+        assert!(!reopened.directory().take_changes().replace);
+    }
+
+    #[test]
+    fn only_files_written_once_are_shared() {
+        let directory = DbDirectory::default();
+        for path in [
+            "0a1b.idx",
+            "0a1b.7.del",
+            "meta.json",
+            ".tantivy-writer.lock",
+        ] {
+            directory.atomic_write(Path::new(path), b"data").unwrap();
+        }
+        assert_eq!(
+            directory.written_once_file("0a1b.idx").unwrap().as_slice(),
+            b"data"
+        );
+        assert!(directory.written_once_file("0a1b.7.del").is_none());
+        assert!(directory.written_once_file("meta.json").is_none());
+        assert!(directory
+            .written_once_file(".tantivy-writer.lock")
+            .is_none());
+        assert!(directory.written_once_file("2c3d.idx").is_none());
+    }
+
+    #[test]
+    fn changes_are_taken_once() {
+        let directory = DbDirectory::default();
+        directory.atomic_write(Path::new("a"), b"first").unwrap();
+        directory.atomic_write(Path::new("b"), b"second").unwrap();
+        directory
+            .atomic_write(Path::new(".tantivy-writer.lock"), b"")
+            .unwrap();
+        let changes = directory.take_changes();
+        assert_eq!(changes.written.len(), 2);
+        assert!(changes.deleted.is_empty());
+
+        directory.delete(Path::new("a")).unwrap();
+        let changes = directory.take_changes();
+        assert!(!changes.replace);
+        assert!(changes.written.is_empty());
+        assert_eq!(changes.deleted, vec!["a".to_string()]);
+        assert_eq!(directory.atomic_read(Path::new("b")).unwrap(), b"second");
+        assert!(directory.atomic_read(Path::new("a")).is_err());
+    }
+}
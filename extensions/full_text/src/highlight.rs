@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+/// The most tokens `snippet()` returns, as in SQLite's FTS5.
+const MAX_SNIPPET_TOKENS: usize = 64;
+
+/// A token of a column's text, and whether the search matched it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub(crate) offsets: Range<usize>,
+    pub(crate) matched: bool,
+}
+
+/// The text of a column of a matched row, split into tokens by the column's tokenizer.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MatchedText {
+    pub(crate) text: String,
+    pub(crate) tokens: Vec<Token>,
+}
+
+impl MatchedText {
+    pub(crate) fn matches(&self) -> usize {
+        self.tokens.iter().filter(|token| token.matched).count()
+    }
+
+    /// The whole text, with every matched token between `open` and `close`.
+    pub(crate) fn highlight(&self, open: &str, close: &str) -> String {
+        self.fragment(0..self.tokens.len(), open, close)
+    }
+
+    /// The `max_tokens` consecutive tokens with the most matches, highlighted, with
+    /// `ellipsis` where text was left out.
+    pub(crate) fn snippet(
+        &self,
+        open: &str,
+        close: &str,
+        ellipsis: &str,
+        max_tokens: usize,
+    ) -> String {
+        let len = max_tokens.clamp(1, MAX_SNIPPET_TOKENS);
+        if self.tokens.len() <= len {
+            return self.highlight(open, close);
+        }
+        let matched = |start: usize| {
+            self.tokens[start..start + len]
+                .iter()
+                .filter(|t| t.matched)
+                .count()
+        };
+        let mut start = 0;
+        for candidate in 1..=self.tokens.len() - len {
+            if matched(candidate) > matched(start) {
+                start = candidate;
+            }
+        }
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push_str(ellipsis);
+        }
+        snippet.push_str(&self.fragment(start..start + len, open, close));
+        if start + len < self.tokens.len() {
+            snippet.push_str(ellipsis);
+        }
+        snippet
+    }
+
+    /// The text of `tokens` with matched ones highlighted. The text before the first
+    /// token and after the last one is included when the range reaches them.
+    fn fragment(&self, tokens: Range<usize>, open: &str, close: &str) -> String {
+        let start = match tokens.start {
+            0 => 0,
+            idx => self.tokens[idx].offsets.start,
+        };
+        let end = if tokens.end == self.tokens.len() {
+            self.text.len()
+        } else {
+            self.tokens[tokens.end - 1].offsets.end
+        };
+        let mut fragment = String::new();
+        let mut pos = start;
+        for token in self.tokens[tokens].iter().filter(|token| token.matched) {
+            fragment.push_str(&self.text[pos..token.offsets.start]);
+            fragment.push_str(open);
+            fragment.push_str(&self.text[token.offsets.clone()]);
+            fragment.push_str(close);
+            pos = token.offsets.end;
+        }
+        fragment.push_str(&self.text[pos..end]);
+        fragment
+    }
+}
+
+/// Encodes the matched text of every column of a row into the blob that the hidden
+/// `highlight` and `snippet` columns hand to the functions of the same name.
+pub(crate) fn encode(columns: &[MatchedText]) -> Vec<u8> {
+    let mut blob = Vec::new();
+    let mut put = |n: usize| blob.extend_from_slice(&(n as u32).to_le_bytes());
+    put(columns.len());
+    for column in columns {
+        put(column.text.len());
+        put(column.tokens.len());
+        for token in &column.tokens {
+            put(token.offsets.start);
+            put(token.offsets.end);
+            put(token.matched as usize);
+        }
+    }
+    for column in columns {
+        blob.extend_from_slice(column.text.as_bytes());
+    }
+    blob
+}
+
+/// Decodes a blob made by [encode], or returns `None` for anything else.
+pub(crate) fn decode(blob: &[u8]) -> Option<Vec<MatchedText>> {
+    let mut pos = 0;
+    let mut get = || {
+        let bytes = blob.get(pos..pos + 4)?;
+        pos += 4;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    };
+    let count = get()?;
+    let mut layout = Vec::new();
+    for _ in 0..count {
+        let text_len = get()?;
+        let token_count = get()?;
+        let mut tokens = Vec::new();
+        for _ in 0..token_count {
+            let offsets = get()?..get()?;
+            let matched = get()? != 0;
+            tokens.push(Token { offsets, matched });
+        }
+        layout.push((text_len, tokens));
+    }
+    let mut texts = &blob[pos..];
+    let mut columns = Vec::with_capacity(count);
+    for (text_len, tokens) in layout {
+        let text = std::str::from_utf8(texts.get(..text_len)?).ok()?;
+        texts = &texts[text_len..];
+        let in_bounds = tokens.iter().all(|token| {
+            token.offsets.start <= token.offsets.end
+                && text.is_char_boundary(token.offsets.start)
+                && text.is_char_boundary(token.offsets.end)
+        });
+        let ordered = tokens
+            .windows(2)
+            .all(|pair| pair[0].offsets.end <= pair[1].offsets.start);
+        if !in_bounds || !ordered {
+            return None;
+        }
+        columns.push(MatchedText {
+            text: text.to_string(),
+            tokens,
+        });
+    }
+    Some(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits on spaces, matching the tokens in `matched`.
+    fn matched_text(text: &str, matched: &[&str]) -> MatchedText {
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        for word in text.split(' ') {
+            if !word.is_empty() {
+                tokens.push(Token {
+                    offsets: start..start + word.len(),
+                    matched: matched.contains(&word),
+                });
+            }
+            start += word.len() + 1;
+        }
+        MatchedText {
+            text: text.to_string(),
+            tokens,
+        }
+    }
+
+    #[test]
+    fn highlights_matched_tokens() {
+        let text = matched_text("the whale and the sea.", &["whale", "sea."]);
+        assert_eq!(text.highlight("[", "]"), "the [whale] and the [sea.]");
+    }
+
+    #[test]
+    fn snippets_pick_the_window_with_the_most_matches() {
+        let text = matched_text("a b c whale d whale e f g", &["whale"]);
+        assert_eq!(text.snippet("[", "]", "...", 3), "...[whale] d [whale]...");
+        assert_eq!(text.snippet("[", "]", "...", 64), text.highlight("[", "]"));
+        assert_eq!(text.snippet("[", "]", "...", 0), "...[whale]...");
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let columns = vec![
+            matched_text("moby dick", &["moby"]),
+            matched_text("", &[]),
+            matched_text("the whale", &["whale"]),
+        ];
+        assert_eq!(decode(&encode(&columns)), Some(columns));
+        assert_eq!(decode(b"not matched text"), None);
+    }
+}
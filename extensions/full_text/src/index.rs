@@ -0,0 +1,227 @@
+use crate::directory::DbDirectory;
+use crate::highlight::{MatchedText, Token};
+use std::collections::HashSet;
+use std::sync::{Mutex, PoisonError};
+use tantivy::{
+    collector::TopDocs,
+    query::{Query, QueryParser},
+    schema::{Field, Schema, Value as _, INDEXED, STORED, TEXT},
+    DocAddress, Index, IndexReader, IndexSettings, IndexWriter, ReloadPolicy, Searcher,
+    TantivyDocument, Term,
+};
+use turso_ext::ResultCode;
+
+const ROWID_FIELD: &str = "rowid";
+const WRITER_MEMORY_BUDGET: usize = 15_000_000;
+
+/// A change to the rows of a full-text table, applied to its index once the
+/// transaction that made it commits.
+#[derive(Debug)]
+pub(crate) enum Change {
+    /// Index the row with this rowid and column text, replacing the indexed row
+    /// with the same rowid if there is one.
+    Upsert(i64, Vec<Option<String>>),
+    Delete(i64),
+}
+
+/// A tantivy index over the rows of a full-text table. The rows themselves live in the
+/// table's content table, which the index is built from, and committed changes are
+/// then applied to the index as they happen. Its files are kept in a [DbDirectory].
+pub(crate) struct SearchIndex {
+    index: Index,
+    directory: DbDirectory,
+    writer: Mutex<IndexWriter>,
+    reader: IndexReader,
+    parser: QueryParser,
+    rowid: Field,
+    fields: Vec<Field>,
+}
+
+/// A document matching a search.
+pub(crate) struct Hit {
+    pub(crate) rowid: i64,
+    pub(crate) score: f32,
+    pub(crate) address: DocAddress,
+}
+
+impl SearchIndex {
+    /// Index `rows`, each a rowid with the text of every column, or `None` for values
+    /// that are not indexed.
+    pub(crate) fn build(
+        columns: &[String],
+        rows: impl IntoIterator<Item = (i64, Vec<Option<String>>)>,
+    ) -> Result<Self, String> {
+        let mut builder = Schema::builder();
+        let rowid = builder.add_i64_field(ROWID_FIELD, INDEXED | STORED);
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|column| builder.add_text_field(column, TEXT | STORED))
+            .collect();
+        let directory = DbDirectory::default();
+        let index = Index::create(directory.clone(), builder.build(), IndexSettings::default())
+            .map_err(|e| e.to_string())?;
+        let search_index = Self::new(index, directory, rowid, fields)?;
+        let rows = rows
+            .into_iter()
+            .map(|(rowid, values)| Change::Upsert(rowid, values));
+        search_index.apply(rows)?;
+        Ok(search_index)
+    }
+
+    /// Open the index whose files are in `directory`, which must have been built over
+    /// the same `columns`.
+    pub(crate) fn open(columns: &[String], directory: DbDirectory) -> Result<Self, String> {
+        let index = Index::open(directory.clone()).map_err(|e| e.to_string())?;
+        let schema = index.schema();
+        let rowid = schema.get_field(ROWID_FIELD).map_err(|e| e.to_string())?;
+        let fields = columns
+            .iter()
+            .map(|column| schema.get_field(column).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(index, directory, rowid, fields)
+    }
+
+    fn new(
+        index: Index,
+        directory: DbDirectory,
+        rowid: Field,
+        fields: Vec<Field>,
+    ) -> Result<Self, String> {
+        let writer: IndexWriter = index
+            .writer_with_num_threads(1, WRITER_MEMORY_BUDGET)
+            .map_err(|e| e.to_string())?;
+        // changes become visible when they are applied, not some time after
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        let parser = QueryParser::for_index(&index, fields.clone());
+        Ok(Self {
+            index,
+            directory,
+            writer: Mutex::new(writer),
+            reader,
+            parser,
+            rowid,
+            fields,
+        })
+    }
+
+    /// The files of the index, to save them to the database.
+    pub(crate) fn directory(&self) -> &DbDirectory {
+        &self.directory
+    }
+
+    /// Apply `changes` and commit them, so that they are seen by searches started
+    /// afterwards.
+    pub(crate) fn apply(&self, changes: impl IntoIterator<Item = Change>) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        for change in changes {
+            match change {
+                Change::Upsert(rowid, values) => {
+                    writer.delete_term(Term::from_field_i64(self.rowid, rowid));
+                    let mut doc = TantivyDocument::default();
+                    doc.add_i64(self.rowid, rowid);
+                    for (field, value) in self.fields.iter().zip(values) {
+                        if let Some(text) = value {
+                            doc.add_text(*field, text);
+                        }
+                    }
+                    writer.add_document(doc).map_err(|e| e.to_string())?;
+                }
+                Change::Delete(rowid) => {
+                    writer.delete_term(Term::from_field_i64(self.rowid, rowid));
+                }
+            }
+        }
+        writer.commit().map_err(|e| e.to_string())?;
+        self.reader.reload().map_err(|e| e.to_string())
+    }
+
+    /// Queries that tantivy's query parser rejects fail with `ResultCode::InvalidArgs`.
+    pub(crate) fn parse(&self, query: &str) -> Result<Box<dyn Query>, ResultCode> {
+        self.parser
+            .parse_query(query)
+            .map_err(|_| ResultCode::InvalidArgs)
+    }
+
+    /// A snapshot of the index to search, which later changes do not affect.
+    pub(crate) fn searcher(&self) -> Searcher {
+        self.reader.searcher()
+    }
+
+    /// The documents matching `query`, best match first, skipping the first `offset`
+    /// hits and collecting at most `limit` of them.
+    pub(crate) fn search(
+        &self,
+        searcher: &Searcher,
+        query: &dyn Query,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Hit>, ResultCode> {
+        // TopDocs does not accept a limit of zero
+        let collector = TopDocs::with_limit(limit.max(1))
+            .and_offset(offset)
+            .order_by_score();
+        let hits = searcher
+            .search(query, &collector)
+            .map_err(|_| ResultCode::Error)?;
+        hits.into_iter()
+            .map(|(score, address)| {
+                let doc: TantivyDocument = searcher.doc(address).map_err(|_| ResultCode::Error)?;
+                let rowid = doc
+                    .get_first(self.rowid)
+                    .and_then(|value| value.as_i64())
+                    .ok_or(ResultCode::Error)?;
+                Ok(Hit {
+                    rowid,
+                    score,
+                    address,
+                })
+            })
+            .collect()
+    }
+
+    /// The text of every column of a matched document, as stored in the index, with
+    /// the tokens that `query` matched.
+    pub(crate) fn matched_text(
+        &self,
+        searcher: &Searcher,
+        query: &dyn Query,
+        address: DocAddress,
+    ) -> Result<Vec<MatchedText>, ResultCode> {
+        let doc: TantivyDocument = searcher.doc(address).map_err(|_| ResultCode::Error)?;
+        let mut terms = Vec::new();
+        query.query_terms(&mut |term, _| terms.push(term.clone()));
+        self.fields
+            .iter()
+            .map(|&field| {
+                let text = doc
+                    .get_first(field)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let matching: HashSet<&str> = terms
+                    .iter()
+                    .filter(|term| term.field() == field)
+                    .filter_map(|term| term.value().as_str())
+                    .collect();
+                let mut analyzer = self
+                    .index
+                    .tokenizer_for_field(field)
+                    .map_err(|_| ResultCode::Error)?;
+                let mut stream = analyzer.token_stream(&text);
+                let mut tokens = Vec::new();
+                while let Some(token) = stream.next() {
+                    tokens.push(Token {
+                        offsets: token.offset_from..token.offset_to,
+                        matched: matching.contains(token.text.as_str()),
+                    });
+                }
+                drop(stream);
+                Ok(MatchedText { text, tokens })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,1047 @@
+//! Full-text search virtual tables backed by tantivy.
+//!
+//! ```sql
+//! CREATE VIRTUAL TABLE docs USING fts(title, body);
+//! INSERT INTO docs VALUES ('Turso', 'An in-process SQL database');
+//! SELECT title, score FROM docs WHERE query = 'database' ORDER BY score DESC;
+//! SELECT title FROM docs WHERE docs MATCH 'title:turso OR database';
+//! SELECT title, bm25(docs) FROM docs WHERE docs MATCH 'database' ORDER BY rank LIMIT 10;
+//! ```
+//!
+//! `docs MATCH '...'` is shorthand for `query = '...'`. Queries use tantivy's query
+//! syntax, and ones it cannot parse fail the statement.
+//!
+//! As in SQLite's FTS5, `rank` and `bm25(docs)` are the negated score, so ascending
+//! order puts the best matches first. Searches return rows in that order, which lets
+//! `ORDER BY rank` skip the sort. The `LIMIT` and `OFFSET` of a search that nothing
+//! else filters or sorts are passed on to tantivy, which then only collects the hits
+//! that are returned. Other searches collect hits in growing batches, so they stop
+//! early too when the statement stops reading rows.
+//!
+//! `highlight(docs, col, open, close)` returns the text of the `col`th indexed column
+//! with the matched terms between `open` and `close`. `snippet(docs, col, open, close,
+//! ellipsis, max_tokens)` returns the at most 64 `max_tokens` of it with the most
+//! matches, with `ellipsis` where text was left out; a `col` of -1 picks the column
+//! with the most matches.
+//!
+//! The rows of a table named `docs` are stored in the shadow table `docs_content`, so
+//! they are written in the transaction of the statement that modifies `docs`. The
+//! shadow tables are created along with `docs`, by `CREATE VIRTUAL TABLE`. Every write
+//! records a new content version in `docs_config`, and the changes of a transaction are
+//! applied to the index when it commits and dropped when it rolls back. The files of
+//! the index are saved to `docs_segments` as part of the commit, along with the version
+//! they reflect, so the index is stored in the database file and is loaded from it
+//! rather than rebuilt from `docs_content`. Loading it again after another connection
+//! changed it reads only the segments it did not hold yet. The index is rebuilt only
+//! when the saved version is not the recorded one, such as for tables written before
+//! their index was saved. Searches inside a transaction that changed the table go
+//! through an index of the uncommitted content instead.
+//!
+//! Turso's `fts` index method (`CREATE INDEX ... USING fts`) is backed by tantivy too,
+//! but this extension keeps an index of its own rather than building on it. The index
+//! method is only compiled into `turso_core` with its `fts` feature, and never for
+//! wasm, while the extension can be loaded into any build. Its directory and cursors
+//! drive core's B-trees and pager directly, whereas an extension reaches the database
+//! only through SQL statements on the `Connection` of `turso_ext`, which it is lent
+//! while a statement runs. And `highlight()` and `snippet()` need the tokens that
+//! matched in each column, which `fts_highlight()` does not expose.
+mod directory;
+mod highlight;
+mod index;
+
+use directory::DbDirectory;
+use index::{Change, Hit, SearchIndex};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
+use tantivy::{directory::OwnedBytes, query::Query, DocAddress, Searcher};
+use turso_ext::{
+    register_extension, scalar, Connection, ConstraintInfo, ConstraintOp, ConstraintUsage,
+    IndexInfo, OrderByInfo, ResultCode, StepResult, VTabCursor, VTabKind, VTabModule,
+    VTabModuleDerive, VTable, Value, ValueType,
+};
+
+register_extension! {
+    scalars: { highlight, snippet },
+    vtabs: { FtsVTabModule }
+}
+
+/// The hidden columns, which precede the indexed ones: `query` takes the search
+/// query, `score` is how well each row matches it, and `rank` and `bm25` are the
+/// negated score. `highlight` and `snippet` hold what the functions of the same name
+/// need to mark up the current match.
+const QUERY_COLUMN: u32 = 0;
+const SCORE_COLUMN: u32 = 1;
+const RANK_COLUMN: u32 = 2;
+const BM25_COLUMN: u32 = 3;
+const HIGHLIGHT_COLUMN: u32 = 4;
+const SNIPPET_COLUMN: u32 = 5;
+const HIDDEN_COLUMNS: usize = 6;
+const RESERVED_NAMES: [&str; 7] = [
+    "rowid",
+    "query",
+    "score",
+    "rank",
+    "bm25",
+    "highlight",
+    "snippet",
+];
+/// Search for `argv[0]`; the other plans add `argv[1]` as the LIMIT, then `argv[2]` as
+/// the OFFSET.
+const QUERY_IDX_NUM: i32 = 1;
+const QUERY_LIMIT_IDX_NUM: i32 = 2;
+const QUERY_LIMIT_OFFSET_IDX_NUM: i32 = 3;
+const QUERY_IDX_STR: &str = "query";
+
+#[derive(Debug, VTabModuleDerive, Default)]
+pub struct FtsVTabModule;
+
+impl VTabModule for FtsVTabModule {
+    type Table = FtsTable;
+    const VTAB_KIND: VTabKind = VTabKind::VirtualTable;
+    const NAME: &'static str = "fts";
+    const READONLY: bool = false;
+
+    fn create(args: &[Value]) -> Result<(String, Self::Table), ResultCode> {
+        let mut columns: Vec<String> = Vec::with_capacity(args.len());
+        for arg in args {
+            let column = column_name(arg).ok_or(ResultCode::InvalidArgs)?;
+            if RESERVED_NAMES.contains(&column.to_lowercase().as_str())
+                || columns.iter().any(|c| c.eq_ignore_ascii_case(&column))
+            {
+                return Err(ResultCode::InvalidArgs);
+            }
+            columns.push(column);
+        }
+        if columns.is_empty() {
+            return Err(ResultCode::InvalidArgs);
+        }
+        let schema = format!(
+            "CREATE TABLE x (query HIDDEN, score HIDDEN, rank HIDDEN, bm25 HIDDEN, \
+             highlight HIDDEN, snippet HIDDEN, {})",
+            quoted_list(&columns)
+        );
+        Ok((
+            schema,
+            FtsTable {
+                name: None,
+                columns,
+                conn: None,
+                state: Arc::default(),
+            },
+        ))
+    }
+}
+
+/// Extracts the column name from a module argument, ignoring any declared type.
+fn column_name(arg: &Value) -> Option<String> {
+    let arg = arg.to_text()?.trim();
+    let name = match arg.chars().next()? {
+        quote @ ('"' | '`' | '\'' | '[') => {
+            let close = if quote == '[' { ']' } else { quote };
+            let end = arg[1..].find(close)? + 1;
+            &arg[1..end]
+        }
+        _ => arg.split_whitespace().next()?,
+    };
+    if name.is_empty() || name.starts_with('-') {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn quoted_list(idents: &[String]) -> String {
+    idents
+        .iter()
+        .map(|ident| quote(ident))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An owned copy of a value read from, or to be written to, the content table.
+#[derive(Debug)]
+enum Cell {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Cell {
+    fn from_value(value: &Value) -> Self {
+        match value.value_type() {
+            ValueType::Integer => value.to_integer().map_or(Cell::Null, Cell::Integer),
+            ValueType::Float => value.to_float().map_or(Cell::Null, Cell::Float),
+            ValueType::Text => value
+                .to_text()
+                .map_or(Cell::Null, |text| Cell::Text(text.to_string())),
+            ValueType::Blob => value.to_blob().map_or(Cell::Null, Cell::Blob),
+            ValueType::Null | ValueType::Error => Cell::Null,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Cell::Null => Value::null(),
+            Cell::Integer(i) => Value::from_integer(*i),
+            Cell::Float(f) => Value::from_float(*f),
+            Cell::Text(text) => Value::from_text(text.clone()),
+            Cell::Blob(blob) => Value::from_blob(blob.clone()),
+        }
+    }
+
+    /// The text indexed for this value. Blobs and NULLs are not indexed.
+    fn indexed_text(&self) -> Option<String> {
+        match self {
+            Cell::Integer(i) => Some(i.to_string()),
+            Cell::Float(f) => Some(f.to_string()),
+            Cell::Text(text) => Some(text.clone()),
+            Cell::Null | Cell::Blob(_) => None,
+        }
+    }
+}
+
+/// The shadow tables of a full-text table, which must be queried through a
+/// connection to its database.
+#[derive(Debug, Clone)]
+struct Shadow {
+    name: String,
+    columns: Vec<String>,
+}
+
+impl Shadow {
+    fn content(&self) -> String {
+        quote(&format!("{}_content", self.name))
+    }
+
+    fn config(&self) -> String {
+        quote(&format!("{}_config", self.name))
+    }
+
+    fn segments(&self) -> String {
+        quote(&format!("{}_segments", self.name))
+    }
+
+    fn create(&self, conn: &Arc<Connection>) -> Result<(), String> {
+        execute(
+            conn,
+            &format!(
+                "CREATE TABLE {} ({})",
+                self.content(),
+                quoted_list(&self.columns)
+            ),
+            Vec::new(),
+        )?;
+        execute(
+            conn,
+            &format!("CREATE TABLE {} (k TEXT PRIMARY KEY, v)", self.config()),
+            Vec::new(),
+        )?;
+        execute(
+            conn,
+            &format!(
+                "CREATE TABLE {} (path TEXT PRIMARY KEY, data BLOB)",
+                self.segments()
+            ),
+            Vec::new(),
+        )?;
+        Ok(())
+    }
+
+    fn drop_tables(&self, conn: &Arc<Connection>) -> Result<(), String> {
+        execute(
+            conn,
+            &format!("DROP TABLE IF EXISTS {}", self.content()),
+            Vec::new(),
+        )?;
+        execute(
+            conn,
+            &format!("DROP TABLE IF EXISTS {}", self.config()),
+            Vec::new(),
+        )?;
+        execute(
+            conn,
+            &format!("DROP TABLE IF EXISTS {}", self.segments()),
+            Vec::new(),
+        )?;
+        Ok(())
+    }
+
+    /// Record that the content changed, returning the new version. The version is
+    /// random rather than incremented, so that a version written by a transaction that
+    /// was rolled back is not reused for different content.
+    fn bump_version(&self, conn: &Arc<Connection>) -> Result<Option<i64>, String> {
+        execute(
+            conn,
+            &format!(
+                "INSERT OR REPLACE INTO {} (k, v) VALUES ('version', random())",
+                self.config()
+            ),
+            Vec::new(),
+        )?;
+        Ok(self.version(conn))
+    }
+
+    /// The current content version, or `None` if the table was never written to.
+    fn version(&self, conn: &Arc<Connection>) -> Option<i64> {
+        self.setting(conn, "version")
+    }
+
+    fn setting(&self, conn: &Arc<Connection>, key: &str) -> Option<i64> {
+        let sql = format!("SELECT v FROM {} WHERE k = ?", self.config());
+        let rows = query(conn, &sql, vec![Cell::Text(key.to_string())]).ok()?;
+        match rows.first()?.first()? {
+            Cell::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// An index of the content at `version`, which is the current one. `previous` is an
+    /// index of another version, which shares its files with the saved index.
+    fn build_index(
+        &self,
+        conn: &Arc<Connection>,
+        version: Option<i64>,
+        previous: Option<&SearchIndex>,
+    ) -> Result<SearchIndex, String> {
+        if let Some(index) = self.saved_index(conn, version, previous) {
+            return Ok(index);
+        }
+        let rows = match version {
+            Some(_) => self.rows(conn)?,
+            None => Vec::new(),
+        };
+        let rows = rows
+            .into_iter()
+            .map(|(rowid, values)| (rowid, values.iter().map(Cell::indexed_text).collect()));
+        SearchIndex::build(&self.columns, rows)
+    }
+
+    /// The index saved to the segments table, if it reflects the content at `version`.
+    /// The files that `previous`, an index loaded or saved before, holds already are
+    /// not read again.
+    fn saved_index(
+        &self,
+        conn: &Arc<Connection>,
+        version: Option<i64>,
+        previous: Option<&SearchIndex>,
+    ) -> Option<SearchIndex> {
+        version.filter(|_| self.setting(conn, "index_version") == version)?;
+        let sql = format!("SELECT path FROM {}", self.segments());
+        let paths = query(conn, &sql, Vec::new()).ok()?;
+        let read = format!("SELECT data FROM {} WHERE path = ?", self.segments());
+        let files = paths
+            .into_iter()
+            .map(|row| {
+                let Some(Cell::Text(path)) = row.into_iter().next() else {
+                    return None;
+                };
+                let shared = previous.and_then(|index| index.directory().written_once_file(&path));
+                if let Some(data) = shared {
+                    return Some((path, data));
+                }
+                let rows = query(conn, &read, vec![Cell::Text(path.clone())]).ok()?;
+                match rows.into_iter().next()?.into_iter().next()? {
+                    Cell::Blob(data) => Some((path, OwnedBytes::new(data))),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        SearchIndex::open(&self.columns, DbDirectory::load(files)).ok()
+    }
+
+    /// Write the files of `index` that changed since it was last saved, recording that
+    /// it reflects the content at `version`.
+    fn save_index(
+        &self,
+        conn: &Arc<Connection>,
+        index: &SearchIndex,
+        version: Option<i64>,
+    ) -> Result<(), String> {
+        let changes = index.directory().take_changes();
+        if changes.replace {
+            execute(
+                conn,
+                &format!("DELETE FROM {}", self.segments()),
+                Vec::new(),
+            )?;
+        }
+        let delete = format!("DELETE FROM {} WHERE path = ?", self.segments());
+        for path in changes.deleted {
+            execute(conn, &delete, vec![Cell::Text(path)])?;
+        }
+        let write = format!(
+            "INSERT OR REPLACE INTO {} (path, data) VALUES (?, ?)",
+            self.segments()
+        );
+        for (path, data) in changes.written {
+            execute(conn, &write, vec![Cell::Text(path), Cell::Blob(data)])?;
+        }
+        let version = version.map_or(Cell::Null, Cell::Integer);
+        execute(
+            conn,
+            &format!(
+                "INSERT OR REPLACE INTO {} (k, v) VALUES ('index_version', ?)",
+                self.config()
+            ),
+            vec![version],
+        )?;
+        Ok(())
+    }
+
+    fn rows(&self, conn: &Arc<Connection>) -> Result<Vec<(i64, Vec<Cell>)>, String> {
+        let sql = format!(
+            "SELECT rowid, {} FROM {} ORDER BY rowid",
+            quoted_list(&self.columns),
+            self.content()
+        );
+        Ok(query(conn, &sql, Vec::new())?
+            .into_iter()
+            .filter_map(|mut row| match row.remove(0) {
+                Cell::Integer(rowid) => Some((rowid, row)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn row(&self, conn: &Arc<Connection>, rowid: i64) -> Result<Option<Vec<Cell>>, String> {
+        let sql = format!(
+            "SELECT {} FROM {} WHERE rowid = ?",
+            quoted_list(&self.columns),
+            self.content()
+        );
+        Ok(query(conn, &sql, vec![Cell::Integer(rowid)])?
+            .into_iter()
+            .next())
+    }
+
+    fn insert(&self, conn: &Arc<Connection>, values: Vec<Cell>) -> Result<i64, String> {
+        let placeholders = vec!["?"; self.columns.len()].join(", ");
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({placeholders})",
+            self.content(),
+            quoted_list(&self.columns)
+        );
+        let rowid = execute(conn, &sql, values)?;
+        Ok(rowid.unwrap_or_default() as i64)
+    }
+
+    fn update(&self, conn: &Arc<Connection>, rowid: i64, values: Vec<Cell>) -> Result<(), String> {
+        let assignments = self
+            .columns
+            .iter()
+            .map(|column| format!("{} = ?", quote(column)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "UPDATE {} SET {assignments} WHERE rowid = ?",
+            self.content()
+        );
+        let mut args = values;
+        args.push(Cell::Integer(rowid));
+        execute(conn, &sql, args)?;
+        Ok(())
+    }
+
+    fn delete(&self, conn: &Arc<Connection>, rowid: i64) -> Result<(), String> {
+        let sql = format!("DELETE FROM {} WHERE rowid = ?", self.content());
+        execute(conn, &sql, vec![Cell::Integer(rowid)])?;
+        Ok(())
+    }
+}
+
+fn execute(conn: &Arc<Connection>, sql: &str, args: Vec<Cell>) -> Result<Option<usize>, String> {
+    // the connection takes ownership of the argument values
+    let args: Vec<Value> = args.iter().map(Cell::to_value).collect();
+    conn.execute(sql, &args)
+        .map_err(|rc| format!("{sql}: {rc}"))
+}
+
+fn query(conn: &Arc<Connection>, sql: &str, args: Vec<Cell>) -> Result<Vec<Vec<Cell>>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|rc| format!("{sql}: {rc}"))?;
+    for (idx, arg) in args.iter().enumerate() {
+        stmt.bind_at(NonZeroUsize::new(idx + 1).unwrap(), arg.to_value());
+    }
+    let mut rows = Vec::new();
+    loop {
+        match stmt.step() {
+            StepResult::Row => rows.push(stmt.get_row().iter().map(Cell::from_value).collect()),
+            StepResult::Done => return Ok(rows),
+            _ => return Err(format!("{sql}: failed to step")),
+        }
+    }
+}
+
+/// The index of a table, shared by the table and its cursors, and the changes of the
+/// transaction in progress.
+#[derive(Default)]
+struct IndexState {
+    /// The index and the content version it reflects.
+    committed: Option<(Option<i64>, Arc<SearchIndex>)>,
+    txn: Option<Txn>,
+}
+
+/// The changes made by the transaction in progress, which are applied to the index
+/// only if it still reflects the content the transaction started from.
+struct Txn {
+    /// The content version before the first change.
+    base: Option<i64>,
+    /// The content version after the last change.
+    version: Option<i64>,
+    changes: Vec<Change>,
+}
+
+pub struct FtsTable {
+    /// Set by core right after the table is created or connected.
+    name: Option<String>,
+    columns: Vec<String>,
+    conn: Option<Arc<Connection>>,
+    state: Arc<Mutex<IndexState>>,
+}
+
+impl FtsTable {
+    fn shadow(&self) -> Result<Shadow, String> {
+        let name = self.name.clone().ok_or("fts table has no name")?;
+        Ok(Shadow {
+            name,
+            columns: self.columns.clone(),
+        })
+    }
+
+    /// The shadow tables and the connection running the write.
+    fn writer(&self) -> Result<(Shadow, &Arc<Connection>), String> {
+        let conn = self.conn.as_ref().ok_or("no connection")?;
+        Ok((self.shadow()?, conn))
+    }
+
+    /// Record a new content version, and `change` for the index once the transaction
+    /// commits. `base` is the content version before the change.
+    fn record(
+        &self,
+        shadow: &Shadow,
+        conn: &Arc<Connection>,
+        base: Option<i64>,
+        change: Change,
+    ) -> Result<(), String> {
+        let version = shadow.bump_version(conn)?;
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let txn = state.txn.get_or_insert_with(|| Txn {
+            base,
+            version,
+            changes: Vec::new(),
+        });
+        txn.version = version;
+        txn.changes.push(change);
+        Ok(())
+    }
+
+    /// The indexed column values of an insert or update, which come after the hidden columns.
+    fn values(&self, args: &[Value]) -> Vec<Cell> {
+        (0..self.columns.len())
+            .map(|idx| {
+                args.get(HIDDEN_COLUMNS + idx)
+                    .map_or(Cell::Null, Cell::from_value)
+            })
+            .collect()
+    }
+}
+
+impl VTable for FtsTable {
+    type Cursor = FtsCursor;
+    type Error = String;
+
+    fn open(&self, conn: Option<Arc<Connection>>) -> Result<Self::Cursor, Self::Error> {
+        Ok(FtsCursor {
+            shadow: self.shadow()?,
+            conn,
+            state: self.state.clone(),
+            query: None,
+            search: None,
+            rows: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    fn set_connection(&mut self, conn: Option<Arc<Connection>>) {
+        self.conn = conn;
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    fn created(&mut self) -> Result<(), Self::Error> {
+        let conn = self.conn.as_ref().ok_or("no connection")?;
+        self.shadow()?.create(conn)
+    }
+
+    fn rename(&mut self, _new_name: &str) -> Result<(), Self::Error> {
+        // the shadow tables are named after the table, and there is no connection to
+        // rename them with
+        Err("fts tables cannot be renamed".to_string())
+    }
+
+    fn insert(&mut self, args: &[Value]) -> Result<i64, Self::Error> {
+        let (shadow, conn) = self.writer()?;
+        let base = shadow.version(conn);
+        let values = self.values(args);
+        let text = values.iter().map(Cell::indexed_text).collect();
+        let rowid = shadow.insert(conn, values)?;
+        self.record(&shadow, conn, base, Change::Upsert(rowid, text))?;
+        Ok(rowid)
+    }
+
+    fn update(&mut self, rowid: i64, args: &[Value]) -> Result<(), Self::Error> {
+        let (shadow, conn) = self.writer()?;
+        let base = shadow.version(conn);
+        let values = self.values(args);
+        let text = values.iter().map(Cell::indexed_text).collect();
+        shadow.update(conn, rowid, values)?;
+        self.record(&shadow, conn, base, Change::Upsert(rowid, text))
+    }
+
+    fn delete(&mut self, rowid: i64) -> Result<(), Self::Error> {
+        let (shadow, conn) = self.writer()?;
+        let base = shadow.version(conn);
+        shadow.delete(conn, rowid)?;
+        self.record(&shadow, conn, base, Change::Delete(rowid))
+    }
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .txn = None;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(txn) = state.txn.take() else {
+            return Ok(());
+        };
+        let conn = self.conn.as_ref().ok_or("no connection")?;
+        let shadow = self.shadow()?;
+        let version = shadow.version(conn);
+        // The changes only add up to the current content if they were made to an index
+        // of the content the transaction started from, and none of them was rolled back
+        // with its statement. Otherwise the index is rebuilt.
+        let committed = state.committed.take().or_else(|| {
+            let saved = shadow.saved_index(conn, txn.base, None)?;
+            Some((txn.base, Arc::new(saved)))
+        });
+        let index = match committed {
+            Some((base, index)) if base == txn.base && txn.version == version => {
+                index.apply(txn.changes)?;
+                index
+            }
+            committed => {
+                let previous = committed.as_ref().map(|(_, index)| index.as_ref());
+                Arc::new(shadow.build_index(conn, version, previous)?)
+            }
+        };
+        // the index is saved in the transaction, and so commits or rolls back with it
+        shadow.save_index(conn, &index, version)?;
+        state.committed = Some((version, index));
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .txn = None;
+        Ok(())
+    }
+
+    fn destroy(&mut self) -> Result<(), Self::Error> {
+        let conn = self.conn.as_ref().ok_or("no connection")?;
+        self.shadow()?.drop_tables(conn)
+    }
+
+    fn best_index(
+        constraints: &[ConstraintInfo],
+        order_by: &[OrderByInfo],
+    ) -> Result<IndexInfo, ResultCode> {
+        let query = constraints.iter().position(|c| {
+            c.usable
+                && matches!(c.op, ConstraintOp::Eq | ConstraintOp::Match)
+                && c.column_index == QUERY_COLUMN
+        });
+        // searches return the best matches first
+        let order_by_consumed = !order_by.is_empty()
+            && order_by.iter().all(|o| match o.column_index {
+                SCORE_COLUMN => o.desc,
+                RANK_COLUMN | BM25_COLUMN => !o.desc,
+                _ => false,
+            });
+        // The LIMIT and OFFSET can only be applied to the hits if every other
+        // constraint is the search, and the rows need no other order.
+        let position = |op: ConstraintOp| constraints.iter().position(|c| c.op == op);
+        let (limit, offset) = match (position(ConstraintOp::Limit), query) {
+            (Some(limit), Some(query))
+                if (order_by.is_empty() || order_by_consumed)
+                    && constraints.iter().enumerate().all(|(idx, c)| {
+                        idx == query || matches!(c.op, ConstraintOp::Limit | ConstraintOp::Offset)
+                    }) =>
+            {
+                (Some(limit), position(ConstraintOp::Offset))
+            }
+            _ => (None, None),
+        };
+        let constraint_usages = (0..constraints.len())
+            .map(|idx| {
+                let argv_index = [query, limit, offset]
+                    .iter()
+                    .position(|used| *used == Some(idx))
+                    .map(|pos| pos as u32 + 1);
+                ConstraintUsage {
+                    argv_index,
+                    omit: argv_index.is_some(),
+                }
+            })
+            .collect();
+        Ok(match query {
+            Some(_) => IndexInfo {
+                idx_num: match (limit, offset) {
+                    (Some(_), Some(_)) => QUERY_LIMIT_OFFSET_IDX_NUM,
+                    (Some(_), None) => QUERY_LIMIT_IDX_NUM,
+                    _ => QUERY_IDX_NUM,
+                },
+                idx_str: Some(QUERY_IDX_STR.to_string()),
+                order_by_consumed,
+                estimated_cost: 10.0,
+                estimated_rows: 100,
+                constraint_usages,
+            },
+            None => IndexInfo {
+                idx_num: 0,
+                idx_str: None,
+                order_by_consumed: false,
+                estimated_cost: 1_000_000.0,
+                estimated_rows: u32::MAX,
+                constraint_usages,
+            },
+        })
+    }
+}
+
+struct FtsRow {
+    rowid: i64,
+    values: Vec<Cell>,
+    score: Option<f32>,
+    address: Option<DocAddress>,
+}
+
+/// Hits are collected in batches that double in size, starting from this one, or
+/// from the LIMIT if the search has one.
+const FIRST_BATCH: usize = 16;
+const MAX_BATCH: usize = 4096;
+
+/// A search in progress, which keeps the index it started on even if the content
+/// changes while its rows are read.
+struct Search {
+    index: Arc<SearchIndex>,
+    searcher: Searcher,
+    query: Box<dyn Query>,
+    collected: usize,
+    /// The number of hits past which none are returned, with the OFFSET skipped.
+    end: Option<usize>,
+    batch: usize,
+    exhausted: bool,
+}
+
+pub struct FtsCursor {
+    shadow: Shadow,
+    conn: Option<Arc<Connection>>,
+    state: Arc<Mutex<IndexState>>,
+    query: Option<String>,
+    search: Option<Search>,
+    rows: Vec<FtsRow>,
+    pos: usize,
+}
+
+impl FtsCursor {
+    fn scan(&mut self, conn: &Arc<Connection>) -> Result<(), String> {
+        if self.shadow.version(conn).is_none() {
+            return Ok(());
+        }
+        self.rows = self
+            .shadow
+            .rows(conn)?
+            .into_iter()
+            .map(|(rowid, values)| FtsRow {
+                rowid,
+                values,
+                score: None,
+                address: None,
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// The index of the current content, loaded or rebuilt first if it reflects
+    /// another version.
+    fn current_index(&self, conn: &Arc<Connection>) -> Result<Arc<SearchIndex>, ResultCode> {
+        let version = self.shadow.version(conn);
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, index)) = state.committed.as_ref().filter(|(v, _)| *v == version) {
+            return Ok(index.clone());
+        }
+        let previous = state.committed.as_ref().map(|(_, index)| index.as_ref());
+        let built = Arc::new(
+            self.shadow
+                .build_index(conn, version, previous)
+                .map_err(|_| ResultCode::Error)?,
+        );
+        // the changes of the transaction in progress may still be rolled back
+        if state.txn.is_none() {
+            state.committed = Some((version, built.clone()));
+        }
+        Ok(built)
+    }
+
+    /// Start searching for `query`, returning at most `limit` hits after the first
+    /// `offset`. Malformed queries fail with `ResultCode::InvalidArgs`.
+    fn search(
+        &mut self,
+        conn: &Arc<Connection>,
+        query: &str,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<(), ResultCode> {
+        let index = self.current_index(conn)?;
+        let query = index.parse(query)?;
+        self.search = Some(Search {
+            searcher: index.searcher(),
+            index,
+            query,
+            collected: offset,
+            end: limit.map(|limit| offset.saturating_add(limit)),
+            batch: limit.map_or(FIRST_BATCH, |limit| limit.min(MAX_BATCH)),
+            exhausted: limit == Some(0),
+        });
+        self.collect(conn)
+    }
+
+    /// Replace the rows with the next batch of hits, until there is a row or no hits
+    /// are left.
+    fn collect(&mut self, conn: &Arc<Connection>) -> Result<(), ResultCode> {
+        let Some(search) = self.search.as_mut() else {
+            return Ok(());
+        };
+        self.rows.clear();
+        self.pos = 0;
+        while self.rows.is_empty() && !search.exhausted {
+            let batch = search
+                .end
+                .map_or(search.batch, |end| search.batch.min(end - search.collected));
+            let hits = search.index.search(
+                &search.searcher,
+                search.query.as_ref(),
+                search.collected,
+                batch,
+            )?;
+            search.collected += hits.len();
+            search.exhausted =
+                hits.len() < batch || search.end.is_some_and(|end| search.collected >= end);
+            search.batch = (search.batch * 2).min(MAX_BATCH);
+            for Hit {
+                rowid,
+                score,
+                address,
+            } in hits
+            {
+                let row = self
+                    .shadow
+                    .row(conn, rowid)
+                    .map_err(|_| ResultCode::Error)?;
+                if let Some(values) = row {
+                    self.rows.push(FtsRow {
+                        rowid,
+                        values,
+                        score: Some(score),
+                        address: Some(address),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VTabCursor for FtsCursor {
+    type Error = String;
+
+    fn filter(&mut self, args: &[Value], idx_info: Option<(&str, i32)>) -> ResultCode {
+        self.rows.clear();
+        self.pos = 0;
+        self.query = None;
+        self.search = None;
+        let Some(conn) = self.conn.clone() else {
+            return ResultCode::Error;
+        };
+        let result = match idx_info {
+            Some((_, QUERY_IDX_NUM | QUERY_LIMIT_IDX_NUM | QUERY_LIMIT_OFFSET_IDX_NUM)) => {
+                match args.first().and_then(Value::to_text) {
+                    Some(query) => {
+                        // a negative LIMIT means no limit, and a negative OFFSET none
+                        let count = |idx: usize| {
+                            args.get(idx)
+                                .and_then(Value::to_integer)
+                                .and_then(|n| usize::try_from(n).ok())
+                        };
+                        let limit = count(1);
+                        let offset = count(2).unwrap_or(0);
+                        self.query = Some(query.to_string());
+                        self.search(&conn, query, limit, offset)
+                    }
+                    None => Ok(()),
+                }
+            }
+            _ => self.scan(&conn).map_err(|_| ResultCode::Error),
+        };
+        match result {
+            Err(rc) => rc,
+            Ok(()) if self.eof() => ResultCode::EOF,
+            Ok(()) => ResultCode::OK,
+        }
+    }
+
+    fn rowid(&self) -> i64 {
+        self.rows.get(self.pos).map_or(-1, |row| row.rowid)
+    }
+
+    fn column(&self, idx: u32) -> Result<Value, Self::Error> {
+        let row = self.rows.get(self.pos).ok_or("cursor is not on a row")?;
+        match idx {
+            QUERY_COLUMN => Ok(self
+                .query
+                .as_ref()
+                .map_or_else(Value::null, |query| Value::from_text(query.clone()))),
+            SCORE_COLUMN => Ok(row
+                .score
+                .map_or_else(Value::null, |score| Value::from_float(score as f64))),
+            RANK_COLUMN | BM25_COLUMN => Ok(row
+                .score
+                .map_or_else(Value::null, |score| Value::from_float(-(score as f64)))),
+            HIGHLIGHT_COLUMN | SNIPPET_COLUMN => match (&self.search, row.address) {
+                (Some(search), Some(address)) => {
+                    let columns = search
+                        .index
+                        .matched_text(&search.searcher, search.query.as_ref(), address)
+                        .map_err(|rc| rc.to_string())?;
+                    Ok(Value::from_blob(highlight::encode(&columns)))
+                }
+                _ => Ok(Value::null()),
+            },
+            _ => row
+                .values
+                .get(idx as usize - HIDDEN_COLUMNS)
+                .map(Cell::to_value)
+                .ok_or_else(|| format!("invalid column index {idx}")),
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.rows.len()
+    }
+
+    fn next(&mut self) -> ResultCode {
+        self.pos += 1;
+        if self.eof() {
+            if let Some(conn) = self.conn.clone() {
+                if let Err(rc) = self.collect(&conn) {
+                    return rc;
+                }
+            }
+        }
+        if self.eof() {
+            ResultCode::EOF
+        } else {
+            ResultCode::OK
+        }
+    }
+}
+
+/// The matched text of the column `args[1]` of the row that `args[0]` comes from, or
+/// of the column with the most matches for a column of -1 when `best` is set.
+fn matched_column(args: &[Value], best: bool) -> Option<highlight::MatchedText> {
+    let columns = highlight::decode(args.first()?.blob_ref()?)?;
+    match args.get(1)?.to_integer()? {
+        -1 if best => columns
+            .into_iter()
+            .enumerate()
+            .max_by_key(|(idx, column)| (column.matches(), std::cmp::Reverse(*idx)))
+            .map(|(_, column)| column),
+        idx => columns.into_iter().nth(usize::try_from(idx).ok()?),
+    }
+}
+
+fn text_arg(args: &[Value], idx: usize) -> Option<&str> {
+    args.get(idx)?.to_text()
+}
+
+/// `highlight(docs, col, open, close)`
+#[scalar(name = "highlight")]
+fn highlight(args: &[Value]) -> Value {
+    let (Some(column), Some(open), Some(close)) = (
+        matched_column(args, false),
+        text_arg(args, 2),
+        text_arg(args, 3),
+    ) else {
+        return Value::null();
+    };
+    Value::from_text(column.highlight(open, close))
+}
+
+/// `snippet(docs, col, open, close, ellipsis, max_tokens)`
+#[scalar(name = "snippet")]
+fn snippet(args: &[Value]) -> Value {
+    let (Some(column), Some(open), Some(close), Some(ellipsis), Some(max_tokens)) = (
+        matched_column(args, true),
+        text_arg(args, 2),
+        text_arg(args, 3),
+        text_arg(args, 4),
+        args.get(5).and_then(Value::to_integer),
+    ) else {
+        return Value::null();
+    };
+    let max_tokens = usize::try_from(max_tokens).unwrap_or(0);
+    Value::from_text(column.snippet(open, close, ellipsis, max_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(args: &[&str]) -> Result<(String, FtsTable), ResultCode> {
+        let args: Vec<Value> = args
+            .iter()
+            .map(|arg| Value::from_text(arg.to_string()))
+            .collect();
+        FtsVTabModule::create(&args)
+    }
+
+    #[test]
+    fn columns_follow_the_hidden_columns() {
+        let (schema, table) = create(&["title", "\"body text\" TEXT"]).unwrap();
+        assert_eq!(
+            schema,
+            "CREATE TABLE x (query HIDDEN, score HIDDEN, rank HIDDEN, bm25 HIDDEN, \
+             highlight HIDDEN, snippet HIDDEN, \"title\", \"body text\")"
+        );
+        assert_eq!(table.columns, vec!["title", "body text"]);
+    }
+
+    #[test]
+    fn rejects_missing_duplicate_and_reserved_columns() {
+        assert!(create(&[]).is_err());
+        assert!(create(&["title", "TITLE"]).is_err());
+        assert!(create(&["score"]).is_err());
+    }
+}
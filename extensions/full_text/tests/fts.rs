@@ -0,0 +1,364 @@
+use std::sync::Arc;
+use turso_core::{Connection, Database, MemoryIO, SqliteDialect, Value, IO};
+
+fn connect() -> turso_core::Result<Arc<Connection>> {
+    turso_core::auto_extension!(limbo_full_text);
+    let io: Arc<dyn IO> = Arc::new(MemoryIO::new());
+    let db = Database::open_file(io, ":memory:", Arc::new(SqliteDialect))?;
+    db.connect()
+}
+
+fn titles(conn: &Arc<Connection>, sql: &str) -> turso_core::Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    Ok(stmt
+        .run_collect_rows()?
+        .into_iter()
+        .map(|row| row[0].to_string())
+        .collect())
+}
+
+#[test]
+fn searches_follow_inserts_updates_and_deletes() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(title, body)")?;
+    conn.execute(
+        "INSERT INTO docs VALUES \
+         ('sqlite', 'a small database engine'), \
+         ('tantivy', 'a full-text search engine library'), \
+         ('turso', 'a database with full-text search')",
+    )?;
+
+    assert_eq!(
+        titles(&conn, "SELECT title FROM docs")?,
+        vec!["sqlite", "tantivy", "turso"]
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE query = 'database' ORDER BY title"
+        )?,
+        vec!["sqlite", "turso"]
+    );
+    assert_eq!(
+        titles(&conn, "SELECT title FROM docs WHERE query = 'title:turso'")?,
+        vec!["turso"]
+    );
+
+    conn.execute("UPDATE docs SET body = 'an embedded database' WHERE title = 'tantivy'")?;
+    conn.execute("DELETE FROM docs WHERE title = 'sqlite'")?;
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE query = 'database' ORDER BY title"
+        )?,
+        vec!["tantivy", "turso"]
+    );
+    assert!(titles(&conn, "SELECT title FROM docs WHERE query = 'small'")?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn match_searches_the_table() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(title, body)")?;
+    conn.execute(
+        "INSERT INTO docs VALUES \
+         ('moby dick', 'a whale and the sea'), \
+         ('the old man', 'a fisherman at sea'), \
+         ('walden', 'a cabin by a pond')",
+    )?;
+
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'sea whale' ORDER BY score DESC"
+        )?,
+        vec!["moby dick", "the old man"]
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH '+sea +whale'"
+        )?,
+        vec!["moby dick"]
+    );
+    assert!(titles(&conn, "SELECT title FROM docs WHERE docs MATCH 'desert'")?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn rank_and_bm25_order_the_best_matches_first() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(title, body)")?;
+    conn.execute(
+        "INSERT INTO docs VALUES \
+         ('one whale', 'a whale'), \
+         ('three whales', 'whale whale whale at sea'), \
+         ('no whales', 'only the sea')",
+    )?;
+
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY rank"
+        )?,
+        vec!["three whales", "one whale"]
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY bm25(docs) LIMIT 1"
+        )?,
+        vec!["three whales"]
+    );
+
+    let mut stmt =
+        conn.prepare("SELECT bm25(docs), rank, score FROM docs WHERE docs MATCH 'sea'")?;
+    for row in stmt.run_collect_rows()? {
+        let (Value::Numeric(bm25), Value::Numeric(rank), Value::Numeric(score)) =
+            (&row[0], &row[1], &row[2])
+        else {
+            panic!("expected numeric scores, got {row:?}");
+        };
+        assert_eq!(bm25.to_f64(), -score.to_f64());
+        assert_eq!(rank.to_f64(), bm25.to_f64());
+    }
+    Ok(())
+}
+
+#[test]
+fn searches_collect_every_hit_across_batches() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(body)")?;
+    for i in 0..100 {
+        conn.execute(format!("INSERT INTO docs VALUES ('whale number {i}')"))?;
+    }
+    assert_eq!(
+        titles(&conn, "SELECT body FROM docs WHERE docs MATCH 'whale'")?.len(),
+        100
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT body FROM docs WHERE docs MATCH 'whale' ORDER BY rank LIMIT 20"
+        )?
+        .len(),
+        20
+    );
+    Ok(())
+}
+
+#[test]
+fn limit_and_offset_select_the_same_hits() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(title, body)")?;
+    for i in 1..=30 {
+        conn.execute(format!(
+            "INSERT INTO docs VALUES ('doc {i}', '{}')",
+            "whale ".repeat(i)
+        ))?;
+    }
+    let ranked = titles(
+        &conn,
+        "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY rank",
+    )?;
+    assert_eq!(ranked.len(), 30);
+
+    // passed on to the search
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY rank LIMIT 5 OFFSET 3"
+        )?,
+        ranked[3..8]
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY rank LIMIT 0"
+        )?,
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY rank LIMIT -1 OFFSET 28"
+        )?,
+        ranked[28..]
+    );
+    // applied after the other filter or sort
+    let filtered = titles(
+        &conn,
+        "SELECT title FROM docs WHERE docs MATCH 'whale' AND title != 'doc 30' \
+         ORDER BY rank LIMIT 2 OFFSET 1",
+    )?;
+    assert_eq!(filtered, ranked[2..4]);
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT title FROM docs WHERE docs MATCH 'whale' ORDER BY title LIMIT 2 OFFSET 1"
+        )?,
+        vec!["doc 10", "doc 11"]
+    );
+    Ok(())
+}
+
+#[test]
+fn highlight_and_snippet_mark_up_the_matches() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(title, body)")?;
+    conn.execute(
+        "INSERT INTO docs VALUES ('Moby Dick', \
+         'Call me Ishmael. Some years ago, never mind how long precisely, I went to sea \
+         and saw the whale.')",
+    )?;
+
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT highlight(docs, 0, '<b>', '</b>') FROM docs WHERE docs MATCH 'moby'"
+        )?,
+        vec!["<b>Moby</b> Dick"]
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT snippet(docs, -1, '[', ']', '...', 5) FROM docs \
+             WHERE docs MATCH 'sea whale'"
+        )?,
+        vec!["...[sea] and saw the [whale]."]
+    );
+    assert_eq!(
+        titles(
+            &conn,
+            "SELECT snippet(docs, 1, '[', ']', '...', 3) FROM docs WHERE docs MATCH 'ishmael'"
+        )?,
+        vec!["Call me [Ishmael]..."]
+    );
+    Ok(())
+}
+
+#[test]
+fn malformed_queries_are_errors() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(title, body)")?;
+    assert!(titles(
+        &conn,
+        "SELECT title FROM docs WHERE docs MATCH 'author:melville'"
+    )
+    .is_err());
+
+    conn.execute("INSERT INTO docs VALUES ('moby dick', 'a whale and the sea')")?;
+    assert!(titles(&conn, "SELECT title FROM docs WHERE query = 'title:(moby'").is_err());
+    assert_eq!(
+        titles(&conn, "SELECT title FROM docs WHERE docs MATCH 'whale'")?,
+        vec!["moby dick"]
+    );
+    Ok(())
+}
+
+#[test]
+fn index_follows_commits_and_rollbacks() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(body)")?;
+    conn.execute("INSERT INTO docs VALUES ('kept whale')")?;
+    let whales = |conn: &Arc<Connection>| {
+        titles(
+            conn,
+            "SELECT body FROM docs WHERE docs MATCH 'whale' ORDER BY body",
+        )
+    };
+    assert_eq!(whales(&conn)?, vec!["kept whale"]);
+
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO docs VALUES ('discarded whale')")?;
+    conn.execute("DELETE FROM docs WHERE body = 'kept whale'")?;
+    // the transaction sees its own changes
+    assert_eq!(whales(&conn)?, vec!["discarded whale"]);
+    conn.execute("ROLLBACK")?;
+    assert_eq!(whales(&conn)?, vec!["kept whale"]);
+
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO docs VALUES ('second whale')")?;
+    conn.execute("COMMIT")?;
+    assert_eq!(whales(&conn)?, vec!["kept whale", "second whale"]);
+
+    conn.execute("UPDATE docs SET body = 'kept dolphin' WHERE body = 'kept whale'")?;
+    assert_eq!(whales(&conn)?, vec!["second whale"]);
+    Ok(())
+}
+
+#[test]
+fn index_is_saved_in_the_database() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE docs USING fts(body)")?;
+    conn.execute("INSERT INTO docs VALUES ('a whale'), ('the sea')")?;
+    let saved_version = |conn: &Arc<Connection>| {
+        titles(
+            conn,
+            "SELECT index_version.v = version.v FROM docs_config index_version, \
+             docs_config version WHERE index_version.k = 'index_version' \
+             AND version.k = 'version'",
+        )
+    };
+    assert_eq!(saved_version(&conn)?, vec!["1"]);
+    assert!(titles(&conn, "SELECT path FROM docs_segments")?
+        .iter()
+        .any(|path| path == "meta.json"));
+
+    conn.execute("BEGIN")?;
+    conn.execute("DELETE FROM docs WHERE body = 'a whale'")?;
+    conn.execute("COMMIT")?;
+    assert_eq!(saved_version(&conn)?, vec!["1"]);
+    assert!(titles(&conn, "SELECT body FROM docs WHERE docs MATCH 'whale'")?.is_empty());
+
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO docs VALUES ('another whale')")?;
+    conn.execute("ROLLBACK")?;
+    assert_eq!(saved_version(&conn)?, vec!["1"]);
+    assert_eq!(
+        titles(&conn, "SELECT body FROM docs WHERE docs MATCH 'sea'")?,
+        vec!["the sea"]
+    );
+    Ok(())
+}
+
+#[test]
+fn rows_are_stored_in_the_shadow_table() -> turso_core::Result<()> {
+    let conn = connect()?;
+    conn.execute("CREATE VIRTUAL TABLE notes USING fts(body)")?;
+    let shadow_tables = |conn: &Arc<Connection>| {
+        titles(
+            conn,
+            "SELECT name FROM sqlite_schema WHERE name LIKE 'notes_%' ORDER BY name",
+        )
+    };
+    assert_eq!(
+        shadow_tables(&conn)?,
+        vec!["notes_config", "notes_content", "notes_segments"]
+    );
+    conn.execute("INSERT INTO notes VALUES ('kept')")?;
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO notes VALUES ('rolled back')")?;
+    conn.execute("ROLLBACK")?;
+
+    assert_eq!(
+        titles(&conn, "SELECT body FROM notes_content")?,
+        vec!["kept"]
+    );
+    assert!(titles(&conn, "SELECT body FROM notes WHERE query = 'rolled'")?.is_empty());
+
+    let mut stmt = conn.prepare("SELECT score FROM notes WHERE query = 'kept'")?;
+    let rows = stmt.run_collect_rows()?;
+    assert!(matches!(rows.as_slice(), [row] if matches!(row[0], Value::Numeric(_))));
+
+    conn.execute("DROP TABLE notes")?;
+    assert!(shadow_tables(&conn)?.is_empty());
+
+    // the shadow tables are not shared with a table that already has their name
+    conn.execute("CREATE TABLE taken_content (body)")?;
+    assert!(conn
+        .execute("CREATE VIRTUAL TABLE taken USING fts(body)")
+        .is_err());
+    Ok(())
+}
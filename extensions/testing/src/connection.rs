@@ -0,0 +1,328 @@
+use crate::value::{free, TestValue};
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr, CString},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+use turso_ext::{Conn, Connection, ResultCode, Stmt, Value};
+
+/// A statement run through a [MockConnection], with the arguments it was bound to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedStatement {
+    pub sql: String,
+    pub args: Vec<TestValue>,
+}
+
+#[derive(Default)]
+struct Results {
+    columns: Vec<String>,
+    rows: Vec<Vec<TestValue>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    results: Mutex<HashMap<String, Results>>,
+    errors: Mutex<HashMap<String, ResultCode>>,
+    executed: Mutex<Vec<ExecutedStatement>>,
+    last_insert_rowid: AtomicI64,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Stands in for the database connection that core hands to virtual tables, so their
+/// cursors and writes can be tested without a database.
+///
+/// Queries return the rows registered for their exact SQL text with
+/// [MockConnection::with_rows], or no rows. Every statement that runs is recorded,
+/// along with its arguments, and `execute` reports increasing rowids.
+///
+/// The handles returned by [MockConnection::connection] must not outlive it.
+pub struct MockConnection {
+    state: Box<MockState>,
+    conn: Box<Conn>,
+}
+
+impl Default for MockConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockConnection {
+    pub fn new() -> Self {
+        let state = Box::<MockState>::default();
+        let conn = Box::new(Conn::new(
+            &*state as *const MockState as *mut c_void,
+            prepare_stmt,
+            execute,
+        ));
+        Self { state, conn }
+    }
+
+    /// Return `rows`, with the given column names, from statements prepared from `sql`.
+    pub fn with_rows(self, sql: &str, columns: &[&str], rows: Vec<Vec<TestValue>>) -> Self {
+        let columns = columns.iter().map(|c| c.to_string()).collect();
+        lock(&self.state.results).insert(sql.to_string(), Results { columns, rows });
+        self
+    }
+
+    /// Fail statements prepared or executed from `sql` with `code`.
+    pub fn with_error(self, sql: &str, code: ResultCode) -> Self {
+        lock(&self.state.errors).insert(sql.to_string(), code);
+        self
+    }
+
+    /// A handle to pass to `VTable::open` or `VTable::set_connection`.
+    pub fn connection(&self) -> Arc<Connection> {
+        Arc::new(Connection::new(&*self.conn))
+    }
+
+    /// The statements run so far. Prepared statements are recorded once closed.
+    pub fn executed(&self) -> Vec<ExecutedStatement> {
+        lock(&self.state.executed).clone()
+    }
+}
+
+unsafe fn state<'a>(ctx: *mut c_void) -> &'a MockState {
+    unsafe { &*(ctx as *const MockState) }
+}
+
+unsafe fn sql_text(sql: *const c_char) -> String {
+    unsafe { CStr::from_ptr(sql) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+unsafe extern "C" fn execute(
+    ctx: *mut Conn,
+    sql: *const c_char,
+    args: *mut Value,
+    arg_count: i32,
+    last_insert_rowid: *mut i64,
+) -> ResultCode {
+    let Ok(conn) = (unsafe { Conn::from_ptr(ctx) }) else {
+        return ResultCode::Error;
+    };
+    let state = unsafe { state(conn._ctx) };
+    let sql = unsafe { sql_text(sql) };
+    // like core, take ownership of the arguments
+    let args = if args.is_null() || arg_count <= 0 {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts_mut(args, arg_count as usize) }
+            .iter_mut()
+            .map(|arg| TestValue::take(std::mem::take(arg)))
+            .collect()
+    };
+    if let Some(code) = lock(&state.errors).get(&sql) {
+        return *code;
+    }
+    lock(&state.executed).push(ExecutedStatement { sql, args });
+    if !last_insert_rowid.is_null() {
+        unsafe { *last_insert_rowid = state.last_insert_rowid.fetch_add(1, Ordering::SeqCst) + 1 };
+    }
+    ResultCode::OK
+}
+
+struct MockStmt {
+    sql: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<TestValue>>,
+    pos: Option<usize>,
+    args: Vec<(usize, TestValue)>,
+}
+
+unsafe extern "C" fn prepare_stmt(ctx: *mut Conn, sql: *const c_char) -> *mut Stmt {
+    let Ok(conn) = (unsafe { Conn::from_ptr(ctx) }) else {
+        return std::ptr::null_mut();
+    };
+    let state = unsafe { state(conn._ctx) };
+    let sql = unsafe { sql_text(sql) };
+    if lock(&state.errors).contains_key(&sql) {
+        return std::ptr::null_mut();
+    }
+    let (columns, rows) = lock(&state.results)
+        .get(&sql)
+        .map(|results| (results.columns.clone(), results.rows.clone()))
+        .unwrap_or_default();
+    let stmt = Box::new(MockStmt {
+        sql,
+        columns,
+        rows,
+        pos: None,
+        args: Vec::new(),
+    });
+    Box::into_raw(Box::new(Stmt::new(
+        conn._ctx,
+        Box::into_raw(stmt) as *mut c_void,
+        stmt_bind_args,
+        stmt_step,
+        stmt_get_row,
+        stmt_get_column_names,
+        stmt_free_current_row,
+        stmt_close,
+    )))
+}
+
+unsafe fn mock_stmt<'a>(stmt: &Stmt) -> &'a mut MockStmt {
+    unsafe { &mut *(stmt._ctx as *mut MockStmt) }
+}
+
+unsafe extern "C" fn stmt_bind_args(ctx: *mut Stmt, idx: i32, arg: Value) -> ResultCode {
+    let Ok(stmt) = (unsafe { Stmt::from_ptr(ctx) }) else {
+        return ResultCode::Error;
+    };
+    let arg = TestValue::take(arg);
+    if idx < 1 {
+        return ResultCode::Error;
+    }
+    let args = &mut unsafe { mock_stmt(stmt) }.args;
+    args.retain(|(i, _)| *i != idx as usize);
+    args.push((idx as usize, arg));
+    ResultCode::OK
+}
+
+unsafe extern "C" fn stmt_step(ctx: *mut Stmt) -> ResultCode {
+    let Ok(stmt) = (unsafe { Stmt::from_ptr(ctx) }) else {
+        return ResultCode::Error;
+    };
+    let mock = unsafe { mock_stmt(stmt) };
+    let pos = mock.pos.map_or(0, |pos| pos + 1);
+    mock.pos = Some(pos);
+    if pos < mock.rows.len() {
+        ResultCode::Row
+    } else {
+        ResultCode::EOF
+    }
+}
+
+unsafe extern "C" fn stmt_get_row(ctx: *mut Stmt) {
+    let Ok(stmt) = (unsafe { Stmt::from_ptr(ctx) }) else {
+        return;
+    };
+    if !stmt.current_row.is_null() {
+        unsafe { stmt.free_current_row() };
+    }
+    let mock = unsafe { mock_stmt(stmt) };
+    match mock.pos.and_then(|pos| mock.rows.get(pos)) {
+        Some(row) => {
+            let values: Vec<Value> = row.iter().map(TestValue::to_value).collect();
+            stmt.current_row_len = values.len() as i32;
+            stmt.current_row = Box::into_raw(values.into_boxed_slice()) as *mut Value;
+        }
+        None => stmt.current_row_len = 0,
+    }
+}
+
+unsafe extern "C" fn stmt_free_current_row(ctx: *mut Stmt) {
+    let Ok(stmt) = (unsafe { Stmt::from_ptr(ctx) }) else {
+        return;
+    };
+    if stmt.current_row.is_null() {
+        return;
+    }
+    let values = unsafe {
+        Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            stmt.current_row,
+            stmt.current_row_len as usize,
+        ))
+    };
+    values.into_vec().into_iter().for_each(free);
+}
+
+unsafe extern "C" fn stmt_get_column_names(ctx: *mut Stmt, count: *mut i32) -> *mut *mut c_char {
+    if !count.is_null() {
+        unsafe { *count = 0 };
+    }
+    let Ok(stmt) = (unsafe { Stmt::from_ptr(ctx) }) else {
+        return std::ptr::null_mut();
+    };
+    let mock = unsafe { mock_stmt(stmt) };
+    if mock.columns.is_empty() {
+        return std::ptr::null_mut();
+    }
+    let names: Vec<*mut c_char> = mock
+        .columns
+        .iter()
+        .map(|name| CString::new(name.as_str()).unwrap_or_default().into_raw())
+        .collect();
+    if !count.is_null() {
+        unsafe { *count = names.len() as i32 };
+    }
+    Box::into_raw(names.into_boxed_slice()) as *mut *mut c_char
+}
+
+unsafe extern "C" fn stmt_close(ctx: *mut Stmt) {
+    // Unlike core, leave the `Stmt` itself allocated: `Stmt::close` clears its
+    // context after this returns, and a closed `Statement` is closed again on drop.
+    let Ok(stmt) = (unsafe { Stmt::from_ptr(ctx) }) else {
+        return;
+    };
+    if stmt._ctx.is_null() {
+        return;
+    }
+    if !stmt.current_row.is_null() {
+        unsafe { stmt.free_current_row() };
+    }
+    let mut mock = unsafe { Box::from_raw(stmt._ctx as *mut MockStmt) };
+    mock.args.sort_by_key(|(idx, _)| *idx);
+    let state = unsafe { state(stmt._conn) };
+    lock(&state.executed).push(ExecutedStatement {
+        sql: std::mem::take(&mut mock.sql),
+        args: mock.args.drain(..).map(|(_, arg)| arg).collect(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroUsize;
+    use turso_ext::StepResult;
+
+    #[test]
+    fn serves_rows_and_records_statements() {
+        let mock = MockConnection::new()
+            .with_rows(
+                "SELECT name FROM t WHERE id > ?",
+                &["name"],
+                vec![vec!["a".into()], vec!["b".into()]],
+            )
+            .with_error("DROP TABLE t", ResultCode::ReadOnly);
+        let conn = mock.connection();
+
+        let mut stmt = conn.prepare("SELECT name FROM t WHERE id > ?").unwrap();
+        stmt.bind_at(NonZeroUsize::new(1).unwrap(), Value::from_integer(3));
+        assert_eq!(stmt.get_column_names(), vec!["name".to_string()]);
+        let mut names = Vec::new();
+        while let StepResult::Row = stmt.step() {
+            names.push(TestValue::from_value(&stmt.get_row()[0]));
+        }
+        stmt.close();
+        assert_eq!(names, vec![TestValue::from("a"), TestValue::from("b")]);
+
+        let rowid = conn
+            .execute("INSERT INTO t VALUES (?)", &[Value::from_text("c".into())])
+            .unwrap();
+        assert_eq!(rowid, Some(1));
+        assert!(conn.execute("DROP TABLE t", &[]).is_err());
+
+        assert_eq!(
+            mock.executed(),
+            vec![
+                ExecutedStatement {
+                    sql: "SELECT name FROM t WHERE id > ?".into(),
+                    args: vec![TestValue::Integer(3)],
+                },
+                ExecutedStatement {
+                    sql: "INSERT INTO t VALUES (?)".into(),
+                    args: vec![TestValue::from("c")],
+                },
+            ]
+        );
+    }
+}
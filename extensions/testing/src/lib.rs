@@ -0,0 +1,40 @@
+//! Helpers for unit testing extensions without opening a database.
+//!
+//! - [TestValue] is an owned, comparable copy of a [turso_ext::Value], and
+//!   [ext_args!] builds argument lists from plain Rust values.
+//! - [call_scalar] and [call_scalar_func] call scalar functions the way core does.
+//! - [MockConnection] stands in for the connection handed to virtual tables.
+//! - [VfsTester] (with the `vfs` feature) drives [turso_ext::VfsFile] I/O to completion.
+//!
+//! Add the crate as a dev-dependency of the extension:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! turso_ext_testing = { workspace = true, features = ["vfs"] }
+//! ```
+mod connection;
+mod scalar;
+mod value;
+#[cfg(feature = "vfs")]
+mod vfs;
+
+pub use connection::{ExecutedStatement, MockConnection};
+pub use scalar::{call_scalar, call_scalar_func};
+pub use value::TestValue;
+#[cfg(feature = "vfs")]
+pub use vfs::VfsTester;
+
+/// Build a `Vec<turso_ext::Value>` from anything that converts into a [TestValue]:
+///
+/// ```
+/// use turso_ext_testing::{ext_args, TestValue};
+///
+/// let args = ext_args![1, 2.5, "text", vec![0u8, 1], TestValue::Null];
+/// assert_eq!(args.len(), 5);
+/// ```
+#[macro_export]
+macro_rules! ext_args {
+    ($($arg:expr),* $(,)?) => {
+        vec![$($crate::TestValue::from($arg).to_value()),*]
+    };
+}
@@ -0,0 +1,60 @@
+use crate::value::{free, TestValue};
+use turso_ext::{ScalarFunc, ScalarFunction, Value};
+
+/// Call a function defined with `#[scalar]` with `args`, the way core does, and
+/// return a copy of its result. The arguments and the result are freed.
+pub fn call_scalar(func: ScalarFunction, args: Vec<Value>) -> TestValue {
+    let result = unsafe { func(0, args.len() as i32, args.as_ptr(), None, None) };
+    args.into_iter().for_each(free);
+    TestValue::take(result)
+}
+
+/// Call a `ScalarDerive` function with `args` and freshly initialized state, and
+/// return a copy of its result. The arguments and the result are freed.
+pub fn call_scalar_func<F: ScalarFunc>(args: Vec<Value>) -> TestValue {
+    let state = F::init();
+    let result = F::call(&state, &args);
+    args.into_iter().for_each(free);
+    TestValue::take(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ext_args;
+    use turso_ext::scalar;
+
+    #[scalar(name = "add_one")]
+    fn add_one(args: &[Value]) -> Value {
+        match args.first().and_then(Value::to_integer) {
+            Some(i) => Value::from_integer(i + 1),
+            None => Value::null(),
+        }
+    }
+
+    struct Repeat;
+
+    impl ScalarFunc for Repeat {
+        type State = usize;
+        const NAME: &'static str = "repeat";
+
+        fn init() -> Self::State {
+            2
+        }
+
+        fn call(times: &Self::State, args: &[Value]) -> Value {
+            let text = args.first().and_then(Value::to_text).unwrap_or_default();
+            Value::from_text(text.repeat(*times))
+        }
+    }
+
+    #[test]
+    fn calls_scalar_functions() {
+        assert_eq!(call_scalar(add_one, ext_args![41]), TestValue::Integer(42));
+        assert_eq!(call_scalar(add_one, ext_args!["x"]), TestValue::Null);
+        assert_eq!(
+            call_scalar_func::<Repeat>(ext_args!["ab"]),
+            TestValue::from("abab")
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use turso_ext::{ResultCode, Value, ValueType};
+
+/// An owned copy of a [Value] that can be compared and printed in assertions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestValue {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Blob(Vec<u8>),
+    Error(ResultCode),
+}
+
+impl TestValue {
+    /// Copy `value`, leaving it untouched.
+    pub fn from_value(value: &Value) -> Self {
+        match value.value_type() {
+            ValueType::Null => Self::Null,
+            ValueType::Integer => Self::Integer(value.to_integer().unwrap_or_default()),
+            ValueType::Float => Self::Float(value.to_float().unwrap_or_default()),
+            ValueType::Text => Self::Text(value.to_text().unwrap_or_default().to_string()),
+            ValueType::Blob => Self::Blob(value.to_blob().unwrap_or_default()),
+            ValueType::Error => Self::Error(value.to_error().unwrap_or(ResultCode::Error)),
+        }
+    }
+
+    /// Copy `value` and free it, as core does with the values extensions return.
+    pub fn take(value: Value) -> Self {
+        let copy = Self::from_value(&value);
+        free(value);
+        copy
+    }
+
+    /// Build a [Value] to pass to an extension. The extension or [TestValue::take]
+    /// must free it.
+    pub fn to_value(&self) -> Value {
+        match self {
+            Self::Null => Value::null(),
+            Self::Integer(i) => Value::from_integer(*i),
+            Self::Float(f) => Value::from_float(*f),
+            Self::Text(s) => Value::from_text(s.clone()),
+            Self::Blob(b) => Value::from_blob(b.clone()),
+            Self::Error(code) => Value::error(*code),
+        }
+    }
+}
+
+pub(crate) fn free(value: Value) {
+    unsafe { value.__free_internal_type() }
+}
+
+impl From<i64> for TestValue {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<i32> for TestValue {
+    fn from(value: i32) -> Self {
+        Self::Integer(value.into())
+    }
+}
+
+impl From<f64> for TestValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for TestValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for TestValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<&[u8]> for TestValue {
+    fn from(value: &[u8]) -> Self {
+        Self::Blob(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for TestValue {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Blob(value)
+    }
+}
+
+impl<T: Into<TestValue>> From<Option<T>> for TestValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip() {
+        let values = [
+            TestValue::Null,
+            TestValue::Integer(-7),
+            TestValue::Float(0.5),
+            TestValue::Text("héllo".into()),
+            TestValue::Blob(vec![0, 255]),
+            TestValue::Error(ResultCode::InvalidArgs),
+        ];
+        for value in values {
+            assert_eq!(TestValue::take(value.to_value()), value);
+        }
+        assert_eq!(TestValue::from(None::<i64>), TestValue::Null);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use turso_ext::{BufferRef, Callback, ExtResult, ResultCode, VfsExtension, VfsFile};
+
+const PENDING: i32 = i32::MIN;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs the asynchronous I/O of a [VfsFile] to completion, the way core does: an
+/// operation is submitted with a callback, then [VfsExtension::run_once] is called
+/// until the callback fires.
+///
+/// Negative results, which core treats as errors, and operations that do not
+/// complete within a few seconds are returned as `ResultCode::Error`.
+pub struct VfsTester<V: VfsExtension> {
+    vfs: V,
+}
+
+impl<V: VfsExtension> VfsTester<V> {
+    pub fn new(vfs: V) -> Self {
+        Self { vfs }
+    }
+
+    pub fn vfs(&self) -> &V {
+        &self.vfs
+    }
+
+    /// Read up to `len` bytes at `offset`, returning the bytes read.
+    pub fn read(&self, file: &mut V::File, offset: i64, len: usize) -> ExtResult<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        let buf_ref = unsafe { BufferRef::new(buf.as_mut_ptr(), buf.len()) };
+        let n = self.wait(|cb| file.read(buf_ref, offset, cb))?;
+        buf.truncate(n as usize);
+        Ok(buf)
+    }
+
+    /// Write `data` at `offset`, returning the number of bytes written.
+    pub fn write(&self, file: &mut V::File, offset: i64, data: &[u8]) -> ExtResult<usize> {
+        let mut buf = data.to_vec();
+        let buf_ref = unsafe { BufferRef::new(buf.as_mut_ptr(), buf.len()) };
+        let n = self.wait(|cb| file.write(buf_ref, offset, cb))?;
+        Ok(n as usize)
+    }
+
+    pub fn sync(&self, file: &V::File) -> ExtResult<()> {
+        self.wait(|cb| file.sync(cb)).map(|_| ())
+    }
+
+    pub fn truncate(&self, file: &V::File, len: i64) -> ExtResult<()> {
+        self.wait(|cb| file.truncate(len, cb)).map(|_| ())
+    }
+
+    fn wait(&self, submit: impl FnOnce(Callback) -> ExtResult<()>) -> ExtResult<i32> {
+        let result = Arc::new(AtomicI32::new(PENDING));
+        let done = result.clone();
+        submit(Box::new(move |res| done.store(res, Ordering::SeqCst)))?;
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            match result.load(Ordering::SeqCst) {
+                PENDING if Instant::now() < deadline => {
+                    self.vfs.run_once()?;
+                    std::thread::yield_now();
+                }
+                PENDING => return Err(ResultCode::Error),
+                res if res < 0 => return Err(ResultCode::Error),
+                res => return Ok(res),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemVfs;
+
+    #[derive(Default)]
+    struct MemFile {
+        data: Mutex<Vec<u8>>,
+    }
+
+    impl VfsExtension for MemVfs {
+        const NAME: &'static str = "mem";
+        type File = MemFile;
+
+        fn open_file(&self, _path: &str, _flags: i32, _direct: bool) -> ExtResult<Self::File> {
+            Ok(MemFile::default())
+        }
+
+        fn remove_file(&self, _path: &str) -> ExtResult<()> {
+            Ok(())
+        }
+    }
+
+    impl VfsFile for MemFile {
+        fn read(&mut self, mut buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+            let data = self.data.lock().unwrap();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            cb(n as i32);
+            Ok(())
+        }
+
+        fn write(&mut self, buf: BufferRef, offset: i64, cb: Callback) -> ExtResult<()> {
+            let mut data = self.data.lock().unwrap();
+            let end = offset as usize + buf.len();
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[offset as usize..end].copy_from_slice(&buf);
+            cb(buf.len() as i32);
+            Ok(())
+        }
+
+        fn sync(&self, cb: Callback) -> ExtResult<()> {
+            cb(0);
+            Ok(())
+        }
+
+        fn truncate(&self, len: i64, cb: Callback) -> ExtResult<()> {
+            self.data.lock().unwrap().truncate(len as usize);
+            cb(0);
+            Ok(())
+        }
+
+        fn size(&self) -> i64 {
+            self.data.lock().unwrap().len() as i64
+        }
+    }
+
+    #[test]
+    fn drives_file_io() {
+        let tester = VfsTester::new(MemVfs);
+        let mut file = tester.vfs().open_file("test.db", 0, false).unwrap();
+        assert_eq!(tester.write(&mut file, 2, b"abc").unwrap(), 3);
+        tester.sync(&file).unwrap();
+        assert_eq!(tester.read(&mut file, 0, 10).unwrap(), b"\0\0abc");
+        tester.truncate(&file, 3).unwrap();
+        assert_eq!(file.size(), 3);
+    }
+}
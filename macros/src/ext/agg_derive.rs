@@ -11,6 +11,95 @@ pub fn derive_agg_func(input: TokenStream) -> TokenStream {
     let finalize_fn_name = format_ident!("{}_finalize", struct_name);
     let init_fn_name = format_ident!("{}_init", struct_name);
     let register_fn_name = format_ident!("register_{}", struct_name);
+    let value_fn_name = format_ident!("{}_value", struct_name);
+    let inverse_fn_name = format_ident!("{}_inverse", struct_name);
+    let is_window = ast.attrs.iter().any(|attr| attr.path().is_ident("window"));
+
+    // With `#[window]` the struct also implements `WindowFunc` and is
+    // registered as a window function.
+    let window_fns = if is_window {
+        quote! {
+            #[no_mangle]
+            pub extern "C" fn #value_fn_name(
+                _context: usize,
+                ctx: *mut ::turso_ext::AggCtx
+            ) -> ::turso_ext::Value {
+                unsafe {
+                    let ctx = &*ctx;
+                    let state = &*(ctx.state as *const <#struct_name as ::turso_ext::AggFunc>::State);
+                    match <#struct_name as ::turso_ext::WindowFunc>::value(state) {
+                        Ok(val) => val,
+                        Err(e) => {
+                            ::turso_ext::Value::error_with_message(e.to_string())
+                        }
+                    }
+                }
+            }
+
+            #[no_mangle]
+            pub extern "C" fn #inverse_fn_name(
+                _context: usize,
+                ctx: *mut ::turso_ext::AggCtx,
+                argc: i32,
+                argv: *const ::turso_ext::Value,
+            ) -> ::turso_ext::Value {
+                unsafe {
+                    let ctx = &mut *ctx;
+                    let state = &mut *(ctx.state as *mut <#struct_name as ::turso_ext::AggFunc>::State);
+                    let args = if argc <= 0 || argv.is_null() {
+                        &[]
+                    } else {
+                        ::std::slice::from_raw_parts(argv, argc as usize)
+                    };
+                    <#struct_name as ::turso_ext::WindowFunc>::inverse(state, args);
+                }
+                ::turso_ext::Value::null()
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let register_call = if is_window {
+        quote! {
+            (api.register_window_function)(
+                api.ctx,
+                c_name.as_ptr(),
+                #struct_name::ARGS,
+                0,
+                #struct_name::#init_fn_name
+                    as ::turso_ext::InitAggFunction,
+                #struct_name::#step_fn_name
+                    as ::turso_ext::StepFunction,
+                #struct_name::#finalize_fn_name
+                    as ::turso_ext::FinalizeFunction,
+                #struct_name::#value_fn_name
+                    as ::turso_ext::ValueFunction,
+                #struct_name::#inverse_fn_name
+                    as ::turso_ext::InverseFunction,
+                None,
+                None,
+                None,
+            )
+        }
+    } else {
+        quote! {
+            (api.register_aggregate_function)(
+                api.ctx,
+                c_name.as_ptr(),
+                #struct_name::ARGS,
+                0,
+                #struct_name::#init_fn_name
+                    as ::turso_ext::InitAggFunction,
+                #struct_name::#step_fn_name
+                    as ::turso_ext::StepFunction,
+                #struct_name::#finalize_fn_name
+                    as ::turso_ext::FinalizeFunction,
+                None,
+                None,
+                None,
+            )
+        }
+    };
 
     let expanded = quote! {
         impl #struct_name {
@@ -75,22 +164,10 @@ pub fn derive_agg_func(input: TokenStream) -> TokenStream {
                     Err(_) => return ::turso_ext::ResultCode::Error,
                 };
 
-                (api.register_aggregate_function)(
-                    api.ctx,
-                    c_name.as_ptr(),
-                    #struct_name::ARGS,
-                    0,
-                    #struct_name::#init_fn_name
-                        as ::turso_ext::InitAggFunction,
-                    #struct_name::#step_fn_name
-                        as ::turso_ext::StepFunction,
-                    #struct_name::#finalize_fn_name
-                        as ::turso_ext::FinalizeFunction,
-                    None,
-                    None,
-                    None,
-                )
+                #register_call
             }
+
+            #window_fns
         }
     };
 
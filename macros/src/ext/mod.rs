@@ -74,6 +74,7 @@ pub fn register_extension(input: TokenStream) -> TokenStream {
             syn::Ident::new(&format!("register_static_{vfs_ident}"), vfs_ident.span());
         quote! {
             {
+                    ::turso_ext::set_find_vfs(api.vfs_interface.find_vfs);
                     let result = api.add_builtin_vfs(unsafe { #static_register()});
                     if !result.is_ok() {
                         return result;
@@ -179,11 +180,16 @@ impl syn::parse::Parse for RegisterExtensionInput {
 pub(crate) struct ScalarInfo {
     pub name: String,
     pub alias: Option<String>,
+    pub deterministic: bool,
 }
 
 impl ScalarInfo {
-    pub fn new(name: String, alias: Option<String>) -> Self {
-        Self { name, alias }
+    pub fn new(name: String, alias: Option<String>, deterministic: bool) -> Self {
+        Self {
+            name,
+            alias,
+            deterministic,
+        }
     }
 }
 
@@ -191,6 +197,7 @@ impl syn::parse::Parse for ScalarInfo {
     fn parse(input: ParseStream) -> syn::parse::Result<Self> {
         let mut name = None;
         let mut alias = None;
+        let mut deterministic = false;
         while !input.is_empty() {
             if let Ok(ident) = input.parse::<Ident>() {
                 if ident.to_string().as_str() == "name" {
@@ -199,6 +206,8 @@ impl syn::parse::Parse for ScalarInfo {
                 } else if ident.to_string().as_str() == "alias" {
                     let _ = input.parse::<Eq>();
                     alias = Some(input.parse::<LitStr>()?);
+                } else if ident.to_string().as_str() == "deterministic" {
+                    deterministic = true;
                 }
             }
             if input.peek(Token![,]) {
@@ -208,6 +217,10 @@ impl syn::parse::Parse for ScalarInfo {
         let Some(name) = name else {
             return Err(input.error("Expected name"));
         };
-        Ok(Self::new(name.value(), alias.map(|i| i.value())))
+        Ok(Self::new(
+            name.value(),
+            alias.map(|i| i.value()),
+            deterministic,
+        ))
     }
 }
@@ -29,6 +29,7 @@ pub fn scalar(attr: TokenStream, input: TokenStream) -> TokenStream {
     let register_fn_name = format_ident!("register_{}", fn_name);
     let args_variable_name = argument_name(&ast, 0, "args");
     let fn_body = &ast.block;
+    let deterministic = scalar_info.deterministic;
     let alias_check = if let Some(alias) = &scalar_info.alias {
         quote! {
             let Ok(alias_c_name) = ::std::ffi::CString::new(#alias) else {
@@ -38,7 +39,7 @@ pub fn scalar(attr: TokenStream, input: TokenStream) -> TokenStream {
                 api.ctx,
                 alias_c_name.as_ptr(),
                 -1,
-                false,
+                #deterministic,
                 0,
                 #fn_name,
                 None,
@@ -65,7 +66,7 @@ pub fn scalar(attr: TokenStream, input: TokenStream) -> TokenStream {
                 api.ctx,
                 c_name.as_ptr(),
                 -1,
-                false,
+                #deterministic,
                 0,
                 #fn_name,
                 None,
@@ -166,7 +167,7 @@ pub fn derive_scalar(input: TokenStream) -> TokenStream {
                 api.ctx,
                 c_name.as_ptr(),
                 -1,
-                false,
+                <#struct_name as ::turso_ext::ScalarFunc>::DETERMINISTIC,
                 context,
                 #call_fn_name,
                 Some(#drop_fn_name),
@@ -190,7 +191,7 @@ pub fn derive_scalar(input: TokenStream) -> TokenStream {
                     api.ctx,
                     alias_c_name.as_ptr(),
                     -1,
-                    false,
+                    <#struct_name as ::turso_ext::ScalarFunc>::DETERMINISTIC,
                     alias_context,
                     #call_fn_name,
                     Some(#drop_fn_name),
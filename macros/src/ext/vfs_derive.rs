@@ -13,8 +13,18 @@ pub fn derive_vfs_module(input: TokenStream) -> TokenStream {
     let read_fn_name = format_ident!("{}_read", struct_name);
     let write_fn_name = format_ident!("{}_write", struct_name);
     let trunc_fn_name = format_ident!("{}_truncate", struct_name);
+    let allocate_fn_name = format_ident!("{}_allocate", struct_name);
     let lock_fn_name = format_ident!("{}_lock", struct_name);
     let unlock_fn_name = format_ident!("{}_unlock", struct_name);
+    let lock_level_fn_name = format_ident!("{}_lock_level", struct_name);
+    let unlock_level_fn_name = format_ident!("{}_unlock_level", struct_name);
+    let check_reserved_lock_fn_name = format_ident!("{}_check_reserved_lock", struct_name);
+    let supports_shm_fn_name = format_ident!("{}_supports_shm", struct_name);
+    let shm_lock_fn_name = format_ident!("{}_shm_lock", struct_name);
+    let shm_unlock_fn_name = format_ident!("{}_shm_unlock", struct_name);
+    let shm_set_len_fn_name = format_ident!("{}_shm_set_len", struct_name);
+    let shm_map_fn_name = format_ident!("{}_shm_map", struct_name);
+    let shm_unmap_fn_name = format_ident!("{}_shm_unmap", struct_name);
     let sync_fn_name = format_ident!("{}_sync", struct_name);
     let size_fn_name = format_ident!("{}_size", struct_name);
     let run_once_fn_name = format_ident!("{}_run_once", struct_name);
@@ -37,9 +47,19 @@ pub fn derive_vfs_module(input: TokenStream) -> TokenStream {
                 write: #write_fn_name,
                 lock: #lock_fn_name,
                 unlock: #unlock_fn_name,
+                lock_level: #lock_level_fn_name,
+                unlock_level: #unlock_level_fn_name,
+                check_reserved_lock: #check_reserved_lock_fn_name,
+                supports_shm: #supports_shm_fn_name,
+                shm_lock: #shm_lock_fn_name,
+                shm_unlock: #shm_unlock_fn_name,
+                shm_set_len: #shm_set_len_fn_name,
+                shm_map: #shm_map_fn_name,
+                shm_unmap: #shm_unmap_fn_name,
                 sync: #sync_fn_name,
                 size: #size_fn_name,
                 truncate: #trunc_fn_name,
+                allocate: #allocate_fn_name,
                 run_once: #run_once_fn_name,
                 gen_random_number: #generate_random_number_fn_name,
                 current_time: #get_current_time_fn_name,
@@ -62,14 +82,25 @@ pub fn derive_vfs_module(input: TokenStream) -> TokenStream {
                 write: #write_fn_name,
                 lock: #lock_fn_name,
                 unlock: #unlock_fn_name,
+                lock_level: #lock_level_fn_name,
+                unlock_level: #unlock_level_fn_name,
+                check_reserved_lock: #check_reserved_lock_fn_name,
+                supports_shm: #supports_shm_fn_name,
+                shm_lock: #shm_lock_fn_name,
+                shm_unlock: #shm_unlock_fn_name,
+                shm_set_len: #shm_set_len_fn_name,
+                shm_map: #shm_map_fn_name,
+                shm_unmap: #shm_unmap_fn_name,
                 sync: #sync_fn_name,
                 size: #size_fn_name,
                 truncate: #trunc_fn_name,
+                allocate: #allocate_fn_name,
                 run_once: #run_once_fn_name,
                 gen_random_number: #generate_random_number_fn_name,
                 current_time: #get_current_time_fn_name,
             };
             let vfsimpl = ::std::boxed::Box::into_raw(::std::boxed::Box::new(vfs_mod)) as *const ::turso_ext::VfsImpl;
+            ::turso_ext::set_find_vfs(api.vfs_interface.find_vfs);
             (api.vfs_interface.register_vfs)(name, vfsimpl)
         }
 
@@ -205,6 +236,134 @@ pub fn derive_vfs_module(input: TokenStream) -> TokenStream {
             ::turso_ext::ResultCode::OK
         }
 
+        #[no_mangle]
+        pub unsafe extern "C" fn #lock_level_fn_name(file_ptr: *const ::std::ffi::c_void, level: ::turso_ext::LockLevel) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &mut <#struct_name as ::turso_ext::VfsExtension>::File =
+                &mut *(vfs_file.file as *mut <#struct_name as ::turso_ext::VfsExtension>::File);
+            if let Err(e) = <#struct_name as ::turso_ext::VfsExtension>::File::lock_level(file, level) {
+                return e;
+            }
+            ::turso_ext::ResultCode::OK
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #unlock_level_fn_name(file_ptr: *const ::std::ffi::c_void, level: ::turso_ext::LockLevel) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &mut <#struct_name as ::turso_ext::VfsExtension>::File =
+                &mut *(vfs_file.file as *mut <#struct_name as ::turso_ext::VfsExtension>::File);
+            if let Err(e) = <#struct_name as ::turso_ext::VfsExtension>::File::unlock_level(file, level) {
+                return e;
+            }
+            ::turso_ext::ResultCode::OK
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #check_reserved_lock_fn_name(file_ptr: *const ::std::ffi::c_void, reserved: *mut bool) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() || reserved.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &<#struct_name as ::turso_ext::VfsExtension>::File =
+                &*(vfs_file.file as *const <#struct_name as ::turso_ext::VfsExtension>::File);
+            match <#struct_name as ::turso_ext::VfsExtension>::File::check_reserved_lock(file) {
+                Ok(held) => {
+                    *reserved = held;
+                    ::turso_ext::ResultCode::OK
+                }
+                Err(e) => e,
+            }
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #supports_shm_fn_name(ctx: *const ::std::ffi::c_void) -> bool {
+            if ctx.is_null() {
+                return false;
+            }
+            let ctx = &*(ctx as *const #struct_name);
+            <#struct_name as ::turso_ext::VfsExtension>::supports_shm(ctx)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #shm_lock_fn_name(file_ptr: *const ::std::ffi::c_void, offset: u64, exclusive: bool, blocking: bool, scope: ::turso_ext::ShmLockScope, acquired: *mut bool) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() || acquired.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &<#struct_name as ::turso_ext::VfsExtension>::File =
+                &*(vfs_file.file as *const <#struct_name as ::turso_ext::VfsExtension>::File);
+            match <#struct_name as ::turso_ext::VfsExtension>::File::shm_lock(file, offset, exclusive, blocking, scope) {
+                Ok(locked) => {
+                    *acquired = locked;
+                    ::turso_ext::ResultCode::OK
+                }
+                Err(e) => e,
+            }
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #shm_unlock_fn_name(file_ptr: *const ::std::ffi::c_void, offset: u64, scope: ::turso_ext::ShmLockScope) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &<#struct_name as ::turso_ext::VfsExtension>::File =
+                &*(vfs_file.file as *const <#struct_name as ::turso_ext::VfsExtension>::File);
+            if let Err(e) = <#struct_name as ::turso_ext::VfsExtension>::File::shm_unlock(file, offset, scope) {
+                return e;
+            }
+            ::turso_ext::ResultCode::OK
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #shm_set_len_fn_name(file_ptr: *const ::std::ffi::c_void, len: u64) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &<#struct_name as ::turso_ext::VfsExtension>::File =
+                &*(vfs_file.file as *const <#struct_name as ::turso_ext::VfsExtension>::File);
+            if let Err(e) = <#struct_name as ::turso_ext::VfsExtension>::File::shm_set_len(file, len) {
+                return e;
+            }
+            ::turso_ext::ResultCode::OK
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #shm_map_fn_name(file_ptr: *const ::std::ffi::c_void, offset: u64, len: usize, ptr: *mut *mut u8) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() || ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &<#struct_name as ::turso_ext::VfsExtension>::File =
+                &*(vfs_file.file as *const <#struct_name as ::turso_ext::VfsExtension>::File);
+            match <#struct_name as ::turso_ext::VfsExtension>::File::shm_map(file, offset, len) {
+                Ok(mapped) => {
+                    *ptr = mapped;
+                    ::turso_ext::ResultCode::OK
+                }
+                Err(e) => e,
+            }
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn #shm_unmap_fn_name(ctx: *const ::std::ffi::c_void, ptr: *mut u8, offset: u64, len: usize) -> ::turso_ext::ResultCode {
+            if ctx.is_null() || ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let ctx = &*(ctx as *const #struct_name);
+            if let Err(e) = <#struct_name as ::turso_ext::VfsExtension>::shm_unmap(ctx, ptr, offset, len) {
+                return e;
+            }
+            ::turso_ext::ResultCode::OK
+        }
+
         #[no_mangle]
         pub unsafe extern "C" fn #sync_fn_name(file_ptr: *const ::std::ffi::c_void, cb: ::turso_ext::IOCallback) -> ::turso_ext::ResultCode {
             if file_ptr.is_null() {
@@ -235,6 +394,20 @@ pub fn derive_vfs_module(input: TokenStream) -> TokenStream {
             ::turso_ext::ResultCode::OK
         }
 
+        #[no_mangle]
+        pub unsafe extern "C" fn #allocate_fn_name(file_ptr: *const ::std::ffi::c_void, len: i64) -> ::turso_ext::ResultCode {
+            if file_ptr.is_null() {
+                return ::turso_ext::ResultCode::Error;
+            }
+            let vfs_file: &mut ::turso_ext::VfsFileImpl = &mut *(file_ptr as *mut ::turso_ext::VfsFileImpl);
+            let file: &<#struct_name as ::turso_ext::VfsExtension>::File =
+                &*(vfs_file.file as *const <#struct_name as ::turso_ext::VfsExtension>::File);
+            if let Err(e) = <#struct_name as ::turso_ext::VfsExtension>::File::allocate(file, len) {
+                return e;
+            }
+            ::turso_ext::ResultCode::OK
+        }
+
         #[no_mangle]
         pub unsafe extern "C" fn #size_fn_name(file_ptr: *const ::std::ffi::c_void) -> i64 {
             if file_ptr.is_null() {
@@ -8,6 +8,7 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
 
     let register_fn_name = format_ident!("register_{}", struct_name);
     let create_fn_name = format_ident!("create_{}", struct_name);
+    let connect_fn_name = format_ident!("connect_{}", struct_name);
     let open_fn_name = format_ident!("open_{}", struct_name);
     let close_fn_name = format_ident!("close_{}", struct_name);
     let filter_fn_name = format_ident!("filter_{}", struct_name);
@@ -17,11 +18,15 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
     let update_fn_name = format_ident!("update_{}", struct_name);
     let rowid_fn_name = format_ident!("rowid_{}", struct_name);
     let destroy_fn_name = format_ident!("destroy_{}", struct_name);
+    let disconnect_fn_name = format_ident!("disconnect_{}", struct_name);
     let best_idx_fn_name = format_ident!("best_idx_{}", struct_name);
     let begin_fn_name = format_ident!("begin_{}", struct_name);
     let rollback_fn_name = format_ident!("rollback_{}", struct_name);
     let commit_fn_name = format_ident!("commit_{}", struct_name);
     let rename_fn_name = format_ident!("rename_{}", struct_name);
+    let set_connection_fn_name = format_ident!("set_connection_{}", struct_name);
+    let set_name_fn_name = format_ident!("set_name_{}", struct_name);
+    let created_fn_name = format_ident!("created_{}", struct_name);
 
     let expanded = quote! {
         impl #struct_name {
@@ -52,6 +57,33 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
                 }
             }
 
+            #[no_mangle]
+            unsafe extern "C" fn #connect_fn_name(
+                argv: *const ::turso_ext::Value, argc: i32
+            ) -> ::turso_ext::VTabCreateResult {
+                let args = if argv.is_null() {
+                    &Vec::new()
+                } else {
+                    ::std::slice::from_raw_parts(argv, argc as usize)
+                };
+                match <#struct_name as ::turso_ext::VTabModule>::connect(&args) {
+                    Ok((schema, table)) => {
+                        ::turso_ext::VTabCreateResult {
+                            code: ::turso_ext::ResultCode::OK,
+                            schema: ::std::ffi::CString::new(schema).unwrap().into_raw(),
+                            table: ::std::boxed::Box::into_raw(::std::boxed::Box::new(table)) as *const ::std::ffi::c_void,
+                        }
+                    },
+                    Err(e) => {
+                        ::turso_ext::VTabCreateResult {
+                            code: e,
+                            schema: ::std::ptr::null(),
+                            table: ::std::ptr::null(),
+                        }
+                    }
+                }
+            }
+
             #[no_mangle]
             unsafe extern "C" fn #open_fn_name(table: *const ::std::ffi::c_void, conn: *const ::turso_ext::Conn) -> *const ::std::ffi::c_void {
                 if table.is_null() {
@@ -216,6 +248,18 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
                 return ::turso_ext::ResultCode::OK;
             }
 
+            #[no_mangle]
+            unsafe extern "C" fn #disconnect_fn_name(
+                table: *const ::std::ffi::c_void,
+            ) -> ::turso_ext::ResultCode {
+                if table.is_null() {
+                    return ::turso_ext::ResultCode::Error;
+                }
+                // Only release the instance: unlike destroy, the table stays.
+                drop(::std::boxed::Box::from_raw(table as *mut <#struct_name as ::turso_ext::VTabModule>::Table));
+                ::turso_ext::ResultCode::OK
+            }
+
             #[no_mangle]
             pub unsafe extern "C" fn #best_idx_fn_name(
                 constraints: *const ::turso_ext::ConstraintInfo,
@@ -300,6 +344,48 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
                 ::turso_ext::ResultCode::OK
             }
 
+            #[no_mangle]
+            pub unsafe extern "C" fn #set_connection_fn_name(
+                table: *mut ::std::ffi::c_void,
+                conn: *const ::turso_ext::Conn,
+            ) {
+                if table.is_null() {
+                    return;
+                }
+                let table = &mut *(table as *mut <#struct_name as ::turso_ext::VTabModule>::Table);
+                let conn = if conn.is_null() { None } else { Some(::std::sync::Arc::new(::turso_ext::Connection::new(conn)))};
+                <#struct_name as ::turso_ext::VTabModule>::Table::set_connection(table, conn);
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn #set_name_fn_name(
+                table: *mut ::std::ffi::c_void,
+                name: *const ::std::ffi::c_char,
+            ) {
+                if table.is_null() || name.is_null() {
+                    return;
+                }
+                let table = &mut *(table as *mut <#struct_name as ::turso_ext::VTabModule>::Table);
+                if let Ok(name) = ::std::ffi::CStr::from_ptr(name).to_str() {
+                    <#struct_name as ::turso_ext::VTabModule>::Table::set_name(table, name);
+                }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn #created_fn_name(
+                table: *mut ::std::ffi::c_void,
+            ) -> ::turso_ext::ResultCode {
+                let table = if table.is_null() {
+                    return ::turso_ext::ResultCode::Error;
+                } else {
+                    &mut *(table as *mut <#struct_name as ::turso_ext::VTabModule>::Table)
+                };
+                if <#struct_name as ::turso_ext::VTabModule>::Table::created(table).is_err() {
+                    return ::turso_ext::ResultCode::Error;
+                }
+                ::turso_ext::ResultCode::OK
+            }
+
             #[no_mangle]
             pub unsafe extern "C" fn #register_fn_name(
                 api: *const ::turso_ext::ExtensionApi
@@ -314,6 +400,7 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
                     name: name_c,
                     readonly: <#struct_name as ::turso_ext::VTabModule>::READONLY,
                     create: Self::#create_fn_name,
+                    connect: Self::#connect_fn_name,
                     open: Self::#open_fn_name,
                     close: Self::#close_fn_name,
                     filter: Self::#filter_fn_name,
@@ -323,11 +410,15 @@ pub fn derive_vtab_module(input: TokenStream) -> TokenStream {
                     update: Self::#update_fn_name,
                     rowid: Self::#rowid_fn_name,
                     destroy: Self::#destroy_fn_name,
+                    disconnect: Self::#disconnect_fn_name,
                     best_idx: Self::#best_idx_fn_name,
                     begin: Self::#begin_fn_name,
                     rollback: Self::#rollback_fn_name,
                     commit: Self::#commit_fn_name,
                     rename: Self::#rename_fn_name,
+                    set_connection: Self::#set_connection_fn_name,
+                    set_name: Self::#set_name_fn_name,
+                    created: Self::#created_fn_name,
                 };
                 (api.register_vtab_module)(api.ctx, name_c, module, <#struct_name as ::turso_ext::VTabModule>::VTAB_KIND)
             }
@@ -309,6 +309,8 @@ pub fn register_extension(input: TokenStream) -> TokenStream {
 /// ```ignore
 /// use turso_ext::{scalar, Value};
 /// #[scalar(name = "double", alias = "twice")] // you can provide an <optional> alias
+/// // add `deterministic` if the result depends only on the arguments:
+/// // #[scalar(name = "double", deterministic)]
 /// fn double(args: &[Value]) -> Value {
 ///       let arg = args.get(0).unwrap();
 ///       match arg.value_type() {
@@ -384,7 +386,24 @@ pub fn derive_scalar(input: TokenStream) -> TokenStream {
 ///     }
 ///}
 /// ```
-#[proc_macro_derive(AggregateDerive)]
+///
+/// Adding `#[window]` registers the aggregate as a window function too, which
+/// requires implementing the `WindowFunc` trait as well:
+/// ```ignore
+///#[derive(AggregateDerive)]
+///#[window]
+///struct SumPlusOne;
+///
+///impl WindowFunc for SumPlusOne {
+///     fn value(state: &Self::State) -> Result<Value, Self::Error> {
+///        Ok(Value::from_integer(*state + 1))
+///     }
+///     fn inverse(state: &mut Self::State, args: &[Value]) {
+///        *state -= args[0].to_integer().unwrap_or(0);
+///     }
+///}
+/// ```
+#[proc_macro_derive(AggregateDerive, attributes(window))]
 pub fn derive_agg_func(input: TokenStream) -> TokenStream {
     ext::derive_agg_func(input)
 }
@@ -455,6 +474,21 @@ pub fn derive_agg_func(input: TokenStream) -> TokenStream {
 ///  fn destroy(&mut self) -> Result<(), Self::Error> {
 ///     Ok(())
 ///  }
+///
+///  /// Receive the connection running the current write or destroy, e.g. to maintain shadow tables
+///  fn set_connection(&mut self, conn: Option<Arc<Connection>>) {
+///     self.conn = conn;
+///  }
+///
+///  /// Receive the name of the table once it is created or connected, e.g. to name shadow tables
+///  fn set_name(&mut self, name: &str) {
+///     self.name = name.to_string();
+///  }
+///
+///  /// Set up a table created by `CREATE VIRTUAL TABLE`, e.g. create its shadow tables
+///  fn created(&mut self) -> Result<(), Self::Error> {
+///     Ok(())
+///  }
 /// }
 ///
 ///  #[derive(Debug)]
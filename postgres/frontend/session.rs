@@ -43,7 +43,7 @@ pub fn open_database_with_io(
     flags: turso_core::OpenFlags,
     opts: turso_core::DatabaseOpts,
 ) -> Result<Arc<turso_core::Database>> {
-    let file = io.open_file(path, flags, true)?;
+    let file = io.open_file(path, flags, !opts.disable_direct_io)?;
     let db_file = Arc::new(turso_core::storage::database::DatabaseFile::new(file));
     turso_core::Database::open(
         io,
@@ -766,7 +766,8 @@ impl TursoDatabase {
                     let db_file = if let Some(db_file) = &self.config.db_file {
                         db_file.clone()
                     } else {
-                        let file = io.open_file(&self.config.path, open_flags, true)?;
+                        let file =
+                            io.open_file(&self.config.path, open_flags, !opts.disable_direct_io)?;
                         Arc::new(DatabaseFile::new(file))
                     };
 
@@ -33,6 +33,20 @@ fn qualified_column_expr(table_name: &str, column_name: &str) -> Expr {
     }
 }
 
+/// `column IS NULL`, or `column IS NOT NULL`. A comparison with NULL is NULL, neither true
+/// nor false, so these are the predicates that are true or false for a NULL column.
+fn null_check_expr(table_name: &str, column_name: &str, is_null: bool) -> Expr {
+    Expr::Binary(
+        Box::new(qualified_column_expr(table_name, column_name)),
+        if is_null {
+            ast::Operator::Is
+        } else {
+            ast::Operator::IsNot
+        },
+        Box::new(Expr::Literal(ast::Literal::Null)),
+    )
+}
+
 impl Predicate {
     /// Produces a true [ast::Expr::Binary] [Predicate] that is true for the provided row in the given table
     pub fn true_binary<R: rand::Rng + ?Sized, C: GenerationContext>(
@@ -60,6 +74,9 @@ impl Predicate {
                 .expect("Column name should have a column suffix for a joined table")
                 .to_string();
         }
+        if value == &SimValue::NULL {
+            return Predicate(null_check_expr(&table_name, &column.name, true));
+        }
 
         let expr = backtrack(
             vec![
@@ -160,6 +177,9 @@ impl Predicate {
                 .expect("Column name should have a column suffix for a joined table")
                 .to_string();
         }
+        if value == &SimValue::NULL {
+            return Predicate(null_check_expr(&table_name, &column.name, false));
+        }
 
         let expr = one_of(
             vec![
@@ -226,6 +246,13 @@ impl SimplePredicate {
         let column = columns[column_index];
         let column_value = &row[column_index];
         let table_name = column.table_name;
+        if column_value == &SimValue::NULL {
+            return SimplePredicate(Predicate(null_check_expr(
+                table_name,
+                &column.column.name,
+                true,
+            )));
+        }
 
         let expr = one_of(
             vec![
@@ -285,6 +312,13 @@ impl SimplePredicate {
         let column = columns[column_index];
         let column_value = &row[column_index];
         let table_name = column.table_name;
+        if column_value == &SimValue::NULL {
+            return SimplePredicate(Predicate(null_check_expr(
+                table_name,
+                &column.column.name,
+                false,
+            )));
+        }
 
         let expr = one_of(
             vec![
@@ -508,6 +542,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fuzz_binary_predicates_on_null_columns() {
+        let seed = get_seed();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let context = &TestContext::default();
+
+        for _ in 0..10000 {
+            let table = Table::arbitrary(&mut rng, context);
+            // Columns added by ALTER TABLE are NULL in the rows that were already there
+            let row: Vec<SimValue> = table
+                .columns
+                .iter()
+                .map(|c| {
+                    if rng.random_bool(0.5) {
+                        SimValue::NULL
+                    } else {
+                        SimValue::arbitrary_from(&mut rng, context, &c.column_type)
+                    }
+                })
+                .collect();
+            let true_predicate = Predicate::true_binary(&mut rng, context, &table, &row);
+            assert_eq!(
+                true_predicate.truth(&row, &table),
+                Some(true),
+                "Predicate: {true_predicate:#?}\nSeed: {seed}"
+            );
+            let false_predicate = Predicate::false_binary(&mut rng, context, &table, &row);
+            assert_eq!(
+                false_predicate.truth(&row, &table),
+                Some(false),
+                "Predicate: {false_predicate:#?}\nSeed: {seed}"
+            );
+            let not_false = Predicate::not(false_predicate);
+            assert!(
+                not_false.test(&row, &table),
+                "Predicate: {not_false:#?}\nSeed: {seed}"
+            );
+        }
+    }
+
     #[test]
     fn fuzz_true_binary_simple_predicate() {
         let seed = get_seed();
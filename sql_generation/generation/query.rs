@@ -72,6 +72,7 @@ impl Arbitrary for FromClause {
         FromClause {
             table: SelectTable::Table(table.name.clone()),
             joins,
+            indexed: None,
         }
     }
 }
@@ -128,6 +129,7 @@ impl Arbitrary for SelectInner {
             from: Some(from),
             where_clause: Predicate::arbitrary_from(rng, env, &join_table),
             order_by,
+            group_by: None,
         }
     }
 }
@@ -241,6 +243,7 @@ impl Arbitrary for Select {
                     .collect(),
             },
             limit: None,
+            offset: None,
         }
     }
 }
@@ -310,13 +313,16 @@ impl Arbitrary for Insert {
                             from: Some(FromClause {
                                 table: SelectTable::Select(select),
                                 joins: Vec::new(),
+                                indexed: None,
                             }),
                             where_clause: Predicate::true_(),
                             order_by: None,
+                            group_by: None,
                         }),
                         compounds: Vec::new(),
                     },
                     limit: None,
+                    offset: None,
                 };
             }
 
@@ -638,9 +644,23 @@ fn gen_insert_upsert_values<R: Rng + ?Sized, C: GenerationContext>(
 impl Arbitrary for Delete {
     fn arbitrary<R: Rng + ?Sized, C: GenerationContext>(rng: &mut R, env: &C) -> Self {
         let table = pick(env.tables(), rng);
+        let limit = if rng.random_bool(0.2) {
+            Some(rng.random_range(0..4))
+        } else {
+            None
+        };
+        // The rows a LIMIT deletes depend on the order of the scan. It is the rowid order
+        // unless the WHERE clause can use an index, so only limit scans that can't.
+        let predicate =
+            if limit.is_some() && (!table.indexes.is_empty() || table.has_any_unique_column()) {
+                Predicate::true_()
+            } else {
+                Predicate::arbitrary_from(rng, env, table)
+            };
         Self {
             table: table.name.clone(),
-            predicate: Predicate::arbitrary_from(rng, env, table),
+            predicate,
+            limit,
         }
     }
 }
@@ -825,14 +845,24 @@ impl Arbitrary for Update {
             let set_values: Vec<(String, SetValue)> =
                 pick_unique(&non_unique_columns, num_cols, rng)
                     .map(|column| {
-                        (
-                            column.name.clone(),
+                        // Sometimes copy another column of the same type instead of a value
+                        let sources: Vec<&Column> = updatable_columns
+                            .iter()
+                            .filter(|c| {
+                                c.name != column.name && c.column_type == column.column_type
+                            })
+                            .copied()
+                            .collect();
+                        let set_value = if !sources.is_empty() && rng.random_bool(0.25) {
+                            SetValue::Column(pick(&sources, rng).name.clone())
+                        } else {
                             SetValue::Simple(SimValue::arbitrary_from(
                                 rng,
                                 env,
                                 &column.column_type,
-                            )),
-                        )
+                            ))
+                        };
+                        (column.name.clone(), set_value)
                     })
                     .collect();
 
@@ -8,10 +8,18 @@ use super::predicate::Predicate;
 pub struct Delete {
     pub table: String,
     pub predicate: Predicate,
+    /// Delete at most this many of the matching rows. Without an ORDER BY, the first ones
+    /// in the order of the scan are deleted.
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
 impl Display for Delete {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "DELETE FROM {} WHERE {}", self.table, self.predicate)
+        write!(f, "DELETE FROM {} WHERE {}", self.table, self.predicate)?;
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+        }
+        Ok(())
     }
 }
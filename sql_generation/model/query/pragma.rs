@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 pub enum Pragma {
     AutoVacuumMode(VacuumMode),
     ForeignKeyList(String),
+    /// Checkpoint the WAL into the database file and truncate it
+    WalCheckpoint,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +34,7 @@ impl Display for Pragma {
                 let table_name = table_name.replace('\'', "''");
                 write!(f, "PRAGMA foreign_key_list('{table_name}')")
             }
+            Pragma::WalCheckpoint => write!(f, "PRAGMA wal_checkpoint(TRUNCATE)"),
         }
     }
 }
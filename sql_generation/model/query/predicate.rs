@@ -92,9 +92,20 @@ impl Predicate {
         expr_to_value(&self.0, row, table)
     }
 
+    /// The truth value of the predicate for `row`, following SQL's three-valued logic:
+    /// `None` when it evaluates to NULL, which is neither true nor false.
+    pub fn truth<T: TableContext>(&self, row: &[SimValue], table: &T) -> Option<bool> {
+        let value = expr_to_value(&self.0, row, table)?;
+        match value.0 {
+            turso_core::Value::Null => None,
+            _ => Some(value.as_bool()),
+        }
+    }
+
+    /// Whether `row` satisfies the predicate the way a WHERE clause does, i.e. whether it is
+    /// true. A predicate that is NULL for the row doesn't select it, and neither does its NOT.
     pub fn test<T: TableContext>(&self, row: &[SimValue], table: &T) -> bool {
-        let value = expr_to_value(&self.0, row, table);
-        value.is_some_and(|value| value.as_bool())
+        self.truth(row, table) == Some(true)
     }
 }
 
@@ -144,6 +155,9 @@ pub fn expr_to_value<T: TableContext>(
         } => {
             let lhs = expr_to_value(lhs, row, table)?;
             let rhs = expr_to_value(rhs, row, table)?;
+            if lhs == SimValue::NULL || rhs == SimValue::NULL {
+                return Some(SimValue::NULL);
+            }
             let res = lhs.like_compare(&rhs, *op).ok()?;
             let value: SimValue = if *not { !res } else { res }.into();
             Some(value)
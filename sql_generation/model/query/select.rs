@@ -1,16 +1,19 @@
+use std::cmp::Ordering;
 use std::fmt::Display;
 
+use anyhow::Context;
 pub use ast::Distinctness;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use turso_core::{numeric::Numeric, types::Value};
 use turso_parser::ast::{
     self,
     fmt::{BlankContext, ToTokens},
     SortOrder,
 };
 
-use crate::model::table::{JoinTable, JoinType, JoinedTable, Table};
+use crate::model::table::{JoinTable, JoinType, JoinedTable, SimValue, Table, TableContext};
 
 use super::predicate::Predicate;
 
@@ -25,6 +28,8 @@ pub enum ResultColumn {
     Star,
     /// column name
     Column(String),
+    /// aggregate function
+    Aggregate(Aggregate),
 }
 
 impl Display for ResultColumn {
@@ -33,13 +38,209 @@ impl Display for ResultColumn {
             ResultColumn::Expr(expr) => write!(f, "({expr})"),
             ResultColumn::Star => write!(f, "*"),
             ResultColumn::Column(name) => write!(f, "{name}"),
+            ResultColumn::Aggregate(aggregate) => write!(f, "{aggregate}"),
         }
     }
 }
+
+impl ResultColumn {
+    fn to_sql_ast(&self) -> ast::ResultColumn {
+        match self {
+            ResultColumn::Expr(expr) => ast::ResultColumn::Expr(expr.0.clone().into_boxed(), None),
+            ResultColumn::Star => ast::ResultColumn::Star,
+            ResultColumn::Column(name) => {
+                ast::ResultColumn::Expr(column_qualified_expr(name).into_boxed(), None)
+            }
+            ResultColumn::Aggregate(aggregate) => {
+                ast::ResultColumn::Expr(aggregate.to_sql_ast().into_boxed(), None)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunc {
+    fn name(&self) -> &'static str {
+        match self {
+            AggregateFunc::Count => "COUNT",
+            AggregateFunc::Sum => "SUM",
+            AggregateFunc::Avg => "AVG",
+            AggregateFunc::Min => "MIN",
+            AggregateFunc::Max => "MAX",
+        }
+    }
+}
+
+/// An aggregate function over a column, or `COUNT(*)` without one
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub func: AggregateFunc,
+    pub column: Option<String>,
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_sql_ast().displayer(&BlankContext).fmt(f)
+    }
+}
+
+impl Aggregate {
+    pub fn count_star() -> Self {
+        Self {
+            func: AggregateFunc::Count,
+            column: None,
+        }
+    }
+
+    fn to_sql_ast(&self) -> ast::Expr {
+        let name = ast::Name::exact(self.func.name().to_string());
+        let filter_over = ast::FunctionTail {
+            filter_clause: None,
+            over_clause: None,
+        };
+        match &self.column {
+            None => ast::Expr::FunctionCallStar { name, filter_over },
+            Some(column) => ast::Expr::FunctionCall {
+                name,
+                distinctness: None,
+                args: vec![column_qualified_expr(column).into_boxed()],
+                order_by: Vec::new(),
+                filter_over,
+            },
+        }
+    }
+
+    /// Evaluate the aggregate over the `rows` of a group, the way SQLite does: NULLs are
+    /// skipped, and `SUM` stays an integer unless it adds up a REAL, failing when the
+    /// integer overflows.
+    pub fn eval<T: TableContext>(
+        &self,
+        rows: &[&Vec<SimValue>],
+        table: &T,
+    ) -> anyhow::Result<SimValue> {
+        let Some(column) = &self.column else {
+            return Ok(SimValue(Value::from_i64(rows.len() as i64)));
+        };
+        let idx = column_position(table, column)
+            .with_context(|| format!("aggregated column {column} not found"))?;
+        let values = rows
+            .iter()
+            .map(|row| &row[idx])
+            .filter(|value| value.0 != Value::Null)
+            .collect::<Vec<_>>();
+        match self.func {
+            AggregateFunc::Count => Ok(SimValue(Value::from_i64(values.len() as i64))),
+            AggregateFunc::Min => Ok(values.into_iter().min().cloned().unwrap_or(SimValue::NULL)),
+            AggregateFunc::Max => Ok(values.into_iter().max().cloned().unwrap_or(SimValue::NULL)),
+            AggregateFunc::Sum | AggregateFunc::Avg => {
+                if values.is_empty() {
+                    return Ok(SimValue::NULL);
+                }
+                let mut int_sum = 0i128;
+                // Compensated, like SQLite's, so that cancellations don't lose precision
+                let (mut float_sum, mut compensation) = (0.0f64, 0.0f64);
+                let mut approx = false;
+                for value in &values {
+                    match Numeric::from_value(&value.0) {
+                        Some(Numeric::Integer(i)) => int_sum += i as i128,
+                        Some(Numeric::Float(f)) => {
+                            approx = true;
+                            let f = f64::from(f);
+                            let total = float_sum + f;
+                            compensation += if float_sum.abs() >= f.abs() {
+                                (float_sum - total) + f
+                            } else {
+                                (f - total) + float_sum
+                            };
+                            float_sum = total;
+                        }
+                        None => unreachable!("NULLs are skipped"),
+                    }
+                }
+                let sum = int_sum as f64 + (float_sum + compensation);
+                if self.func == AggregateFunc::Avg {
+                    Ok(SimValue(Value::from_f64(sum / values.len() as f64)))
+                } else if approx {
+                    Ok(SimValue(Value::from_f64(sum)))
+                } else {
+                    let sum =
+                        i64::try_from(int_sum).map_err(|_| anyhow::anyhow!("integer overflow"))?;
+                    Ok(SimValue(Value::from_i64(sum)))
+                }
+            }
+        }
+    }
+}
+
+/// `GROUP BY` clause
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupBy {
+    pub columns: Vec<String>,
+    /// `HAVING` clause
+    pub having: Option<Having>,
+}
+
+impl GroupBy {
+    fn to_sql_ast(&self) -> ast::GroupBy {
+        ast::GroupBy {
+            exprs: self
+                .columns
+                .iter()
+                .map(|name| column_qualified_expr(name).into_boxed())
+                .collect(),
+            having: self
+                .having
+                .as_ref()
+                .map(|having| having.to_sql_ast().into_boxed()),
+        }
+    }
+}
+
+/// `HAVING <aggregate> <operator> <value>`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Having {
+    pub aggregate: Aggregate,
+    pub operator: ast::Operator,
+    pub value: SimValue,
+}
+
+impl Having {
+    fn to_sql_ast(&self) -> ast::Expr {
+        ast::Expr::Binary(
+            Box::new(self.aggregate.to_sql_ast()),
+            self.operator,
+            Box::new(ast::Expr::Literal((&self.value).into())),
+        )
+    }
+
+    /// Whether the group of `rows` is kept
+    pub fn test<T: TableContext>(
+        &self,
+        rows: &[&Vec<SimValue>],
+        table: &T,
+    ) -> anyhow::Result<bool> {
+        Ok(self
+            .aggregate
+            .eval(rows, table)?
+            .binary_compare(&self.value, self.operator)
+            .as_bool())
+    }
+}
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Select {
     pub body: SelectBody,
     pub limit: Option<usize>,
+    /// `OFFSET`, only written out with a `LIMIT`
+    #[serde(default)]
+    pub offset: Option<usize>,
 }
 
 impl Select {
@@ -62,10 +263,12 @@ impl Select {
                     from: None,
                     where_clause: Predicate::true_(),
                     order_by: None,
+                    group_by: None,
                 }),
                 compounds: Vec::new(),
             },
             limit: None,
+            offset: None,
         }
     }
 
@@ -84,13 +287,16 @@ impl Select {
                     from: Some(FromClause {
                         table: SelectTable::Table(table),
                         joins: Vec::new(),
+                        indexed: None,
                     }),
                     where_clause,
                     order_by: None,
+                    group_by: None,
                 }),
                 compounds: Vec::new(),
             },
             limit,
+            offset: None,
         }
     }
 
@@ -103,6 +309,7 @@ impl Select {
         Select {
             body,
             limit: left.limit.or(right.limit),
+            offset: left.offset.or(right.offset),
         }
     }
 
@@ -144,6 +351,29 @@ pub struct OrderBy {
     pub columns: Vec<(String, SortOrder)>,
 }
 
+impl OrderBy {
+    /// The position of each `ORDER BY` column in the rows of `table`, with its order, or
+    /// `None` if one of the columns is not in the table.
+    pub fn key_columns<T: TableContext>(&self, table: &T) -> Option<Vec<(usize, SortOrder)>> {
+        self.columns
+            .iter()
+            .map(|(name, order)| column_position(table, name).map(|idx| (idx, *order)))
+            .collect()
+    }
+
+    /// Compare two rows on the `keys` from [OrderBy::key_columns]. Values are ordered like
+    /// SQLite does with the BINARY collation: NULLs first, then numbers, text, and blobs.
+    pub fn compare(keys: &[(usize, SortOrder)], a: &[SimValue], b: &[SimValue]) -> Ordering {
+        keys.iter()
+            .map(|&(idx, order)| match order {
+                SortOrder::Asc => a[idx].cmp(&b[idx]),
+                SortOrder::Desc => b[idx].cmp(&a[idx]),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SelectInner {
     /// `DISTINCT`
@@ -156,6 +386,20 @@ pub struct SelectInner {
     pub where_clause: Predicate,
     /// `ORDER BY` clause
     pub order_by: Option<OrderBy>,
+    /// `GROUP BY` clause
+    #[serde(default)]
+    pub group_by: Option<GroupBy>,
+}
+
+impl SelectInner {
+    /// Whether the select returns a row per group instead of a row per matching row
+    pub fn is_aggregate(&self) -> bool {
+        self.group_by.is_some()
+            || self
+                .columns
+                .iter()
+                .any(|column| matches!(column, ResultColumn::Aggregate(_)))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -180,6 +424,9 @@ pub struct FromClause {
     pub table: SelectTable,
     /// `JOIN`ed tables
     pub joins: Vec<JoinedTable>,
+    /// `INDEXED BY` or `NOT INDEXED` on the first table
+    #[serde(default)]
+    pub indexed: Option<ast::Indexed>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -220,12 +467,19 @@ fn column_qualified_expr(name: &str) -> ast::Expr {
     }
 }
 
+/// The position of the column `name`, or `table.name`, in the rows of `table`
+pub fn column_position<T: TableContext>(table: &T, name: &str) -> Option<usize> {
+    table.columns().position(|c| {
+        c.column.name == name || format!("{}.{}", c.table_name, c.column.name) == name
+    })
+}
+
 impl FromClause {
     fn to_sql_ast(&self) -> ast::FromClause {
         ast::FromClause {
             select: Box::new(match &self.table {
                 SelectTable::Table(table) => {
-                    ast::SelectTable::Table(table_qualified_name(table), None, None)
+                    ast::SelectTable::Table(table_qualified_name(table), None, self.indexed.clone())
                 }
                 SelectTable::Select(select) => ast::SelectTable::Select(select.to_sql_ast(), None),
             }),
@@ -329,20 +583,11 @@ impl Select {
                         .select
                         .columns
                         .iter()
-                        .map(|col| match col {
-                            ResultColumn::Expr(expr) => {
-                                ast::ResultColumn::Expr(expr.0.clone().into_boxed(), None)
-                            }
-                            ResultColumn::Star => ast::ResultColumn::Star,
-                            ResultColumn::Column(name) => ast::ResultColumn::Expr(
-                                column_qualified_expr(name).into_boxed(),
-                                None,
-                            ),
-                        })
+                        .map(ResultColumn::to_sql_ast)
                         .collect(),
                     from: self.body.select.from.as_ref().map(|f| f.to_sql_ast()),
                     where_clause: Some(self.body.select.where_clause.0.clone().into_boxed()),
-                    group_by: None,
+                    group_by: self.body.select.group_by.as_ref().map(GroupBy::to_sql_ast),
                     window_clause: Vec::new(),
                 },
                 compounds: self
@@ -360,20 +605,11 @@ impl Select {
                                 .select
                                 .columns
                                 .iter()
-                                .map(|col| match col {
-                                    ResultColumn::Expr(expr) => {
-                                        ast::ResultColumn::Expr(expr.0.clone().into_boxed(), None)
-                                    }
-                                    ResultColumn::Star => ast::ResultColumn::Star,
-                                    ResultColumn::Column(name) => ast::ResultColumn::Expr(
-                                        column_qualified_expr(name).into_boxed(),
-                                        None,
-                                    ),
-                                })
+                                .map(ResultColumn::to_sql_ast)
                                 .collect(),
                             from: compound.select.from.as_ref().map(|f| f.to_sql_ast()),
                             where_clause: Some(compound.select.where_clause.0.clone().into_boxed()),
-                            group_by: None,
+                            group_by: compound.select.group_by.as_ref().map(GroupBy::to_sql_ast),
                             window_clause: Vec::new(),
                         },
                     })
@@ -388,7 +624,7 @@ impl Select {
                     o.columns
                         .iter()
                         .map(|(name, order)| ast::SortedColumn {
-                            expr: ast::Expr::Id(ast::Name::exact(name.clone())).into_boxed(),
+                            expr: column_qualified_expr(name).into_boxed(),
                             order: match order {
                                 SortOrder::Asc => Some(ast::SortOrder::Asc),
                                 SortOrder::Desc => Some(ast::SortOrder::Desc),
@@ -400,7 +636,9 @@ impl Select {
                 .unwrap_or_default(),
             limit: self.limit.map(|l| ast::Limit {
                 expr: ast::Expr::Literal(ast::Literal::Numeric(l.to_string())).into_boxed(),
-                offset: None,
+                offset: self
+                    .offset
+                    .map(|o| ast::Expr::Literal(ast::Literal::Numeric(o.to_string())).into_boxed()),
             }),
         }
     }
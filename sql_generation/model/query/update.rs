@@ -18,6 +18,9 @@ pub enum SetValue {
         then_value: SimValue,
         else_column: String,
     },
+    /// Copy of another column: col = other_col. Like every SET expression, it reads
+    /// the row as it was before the UPDATE, so `SET a = b, b = a` swaps the two.
+    Column(String),
 }
 
 impl Display for SetValue {
@@ -35,6 +38,7 @@ impl Display for SetValue {
                     "CASE WHEN {condition} THEN {then_value} ELSE {else_column} END"
                 )
             }
+            SetValue::Column(column) => write!(f, "{column}"),
         }
     }
 }
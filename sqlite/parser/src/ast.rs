@@ -1803,6 +1803,8 @@ pub enum PragmaName {
     Fullfsync,
     /// Enable or disable CHECK constraint enforcement
     IgnoreCheckConstraints,
+    /// Take pages off the freelist of an incremental auto-vacuum database
+    IncrementalVacuum,
     /// Run integrity check on the database file
     IntegrityCheck,
     /// `journal_mode` pragma
@@ -1815,10 +1817,17 @@ pub enum PragmaName {
     #[strum(serialize = "hexkey")]
     #[cfg_attr(feature = "serde", serde(rename = "hexkey"))]
     EncryptionKey,
+    /// re-encrypt an encrypted database with a new key, specified as hexadecimal string.
+    #[strum(serialize = "hexrekey")]
+    #[cfg_attr(feature = "serde", serde(rename = "hexrekey"))]
+    EncryptionRekey,
     /// Noop as per SQLite docs
     LegacyFileFormat,
     /// Set or get the maximum number of pages in the database file.
     MaxPageCount,
+    /// Set or get the number of bytes of the database file read through a
+    /// memory mapping.
+    MmapSize,
     /// `module_list` pragma
     /// `module_list` lists modules used by virtual tables.
     ModuleList,
@@ -1840,6 +1849,8 @@ pub enum PragmaName {
     Synchronous,
     /// Control where temporary tables and indices are stored (DEFAULT=0, FILE=1, MEMORY=2)
     TempStore,
+    /// Directory where this connection creates temporary files
+    TempStoreDirectory,
     /// returns information about the columns of an index
     IndexInfo,
     /// returns extended information about the columns of an index
@@ -2071,6 +2082,8 @@ pub enum TransactionType {
     Exclusive,
     /// `CONCURRENT`,
     Concurrent,
+    /// `READONLY`: any write inside the transaction is rejected
+    ReadOnly,
 }
 
 /// Upsert clause
@@ -2472,15 +2472,13 @@ impl ToTokens for TransactionType {
         s: &mut S,
         _: &C,
     ) -> Result<(), S::Error> {
-        s.append(
-            match self {
-                Self::Deferred => TK_DEFERRED,
-                Self::Immediate => TK_IMMEDIATE,
-                Self::Exclusive => TK_EXCLUSIVE,
-                Self::Concurrent => TK_CONCURRENT,
-            },
-            None,
-        )
+        match self {
+            Self::Deferred => s.append(TK_DEFERRED, None),
+            Self::Immediate => s.append(TK_IMMEDIATE, None),
+            Self::Exclusive => s.append(TK_EXCLUSIVE, None),
+            Self::Concurrent => s.append(TK_CONCURRENT, None),
+            Self::ReadOnly => s.append(TK_ID, Some("READONLY")),
+        }
     }
 }
 
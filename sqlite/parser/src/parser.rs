@@ -787,6 +787,10 @@ impl<'a> Parser<'a> {
                     eat_assert!(self, TK_CONCURRENT);
                     Some(TransactionType::Concurrent)
                 }
+                TK_ID if tok.to_utf8().eq_ignore_ascii_case("READONLY") => {
+                    eat_assert!(self, TK_ID);
+                    Some(TransactionType::ReadOnly)
+                }
                 _ => None,
             },
         };
@@ -5489,6 +5493,20 @@ mod tests {
                     name: Some(Name::from_string("'my_transaction'")),
                 })],
             ),
+            (
+                b"BEGIN READONLY".as_slice(),
+                vec![Cmd::Stmt(Stmt::Begin {
+                    typ: Some(TransactionType::ReadOnly),
+                    name: None,
+                })],
+            ),
+            (
+                b"BEGIN readonly TRANSACTION my_transaction".as_slice(),
+                vec![Cmd::Stmt(Stmt::Begin {
+                    typ: Some(TransactionType::ReadOnly),
+                    name: Some(Name::from_string("my_transaction")),
+                })],
+            ),
             (
                 ";;;BEGIN;BEGIN;;;;;;BEGIN".as_bytes(),
                 vec![
@@ -827,6 +827,49 @@ def test_vfs():
     turso.quit()
 
 
+def test_async_vfs():
+    turso = TestTursoShell()
+    ext_path = f"{DEBUG_DIR}/liblimbo_async_file"
+    turso.execute_dot(f".load {ext_path}")
+    turso.run_test_fn(".vfslist", lambda res: "async_file" in res, "async_file extension loaded")
+    turso.execute_dot(".open testing/system/async_vfs.db async_file")
+    turso.execute_dot("create table test (id integer primary key, value blob);")
+    for _ in range(50):
+        turso.execute_dot("insert into test (value) values (randomblob(32*1024));")
+    turso.run_test_fn(
+        "SELECT count(*), sum(length(value)) FROM test;",
+        lambda res: res == "50|1638400",
+        "Tested large write to async_file",
+    )
+    turso.run_test_fn("PRAGMA integrity_check;", lambda res: res == "ok", "async_file db is intact")
+    turso.quit()
+
+
+def test_encrypted_vfs():
+    os.environ["TURSO_ENCRYPTED_VFS_KEY"] = "2b" * 32
+    turso = TestTursoShell()
+    ext_path = f"{DEBUG_DIR}/liblimbo_encrypted_vfs"
+    turso.execute_dot(f".load {ext_path}")
+    turso.run_test_fn(".vfslist", lambda res: "encrypted" in res, "encrypted extension loaded")
+    turso.execute_dot(".open testing/system/encrypted_vfs.db encrypted")
+    turso.execute_dot("create table test (id integer primary key, value text);")
+    for i in range(50):
+        turso.execute_dot(f"insert into test (value) values ('secret value {i}');")
+    turso.run_test_fn(
+        "SELECT count(*) FROM test;",
+        lambda res: res == "50",
+        "Tested writes through the encrypted VFS",
+    )
+    turso.run_test_fn("PRAGMA integrity_check;", lambda res: res == "ok", "encrypted db is intact")
+    turso.quit()
+    for path in ("testing/system/encrypted_vfs.db", "testing/system/encrypted_vfs.db-wal"):
+        if os.path.exists(path):
+            data = Path(path).read_bytes()
+            assert b"secret value" not in data, f"{path} holds plaintext"
+            assert b"SQLite format 3" not in data, f"{path} holds a plaintext header"
+    del os.environ["TURSO_ENCRYPTED_VFS_KEY"]
+
+
 def test_sqlite_vfs_compat():
     sqlite = TestTursoShell(
         init_commands="",
@@ -940,6 +983,14 @@ def cleanup():
         os.remove("testing/system/vfs.db")
     if os.path.exists("testing/system/vfs.db-wal"):
         os.remove("testing/system/vfs.db-wal")
+    for path in (
+        "testing/system/async_vfs.db",
+        "testing/system/async_vfs.db-wal",
+        "testing/system/encrypted_vfs.db",
+        "testing/system/encrypted_vfs.db-wal",
+    ):
+        if os.path.exists(path):
+            os.remove(path)
 
 
 def test_tablestats():
@@ -1058,6 +1109,8 @@ def main():
         test_ipaddr()
         test_vfs()
         test_sqlite_vfs_compat()
+        test_async_vfs()
+        test_encrypted_vfs()
         test_kv()
         test_csv()
         test_tablestats()
@@ -0,0 +1,71 @@
+//! Coverage-guided generation. With `--coverage-guided`, the opcodes and branches that
+//! every interaction executes are fed back to generation, which then favors the
+//! properties that recently reached bytecode no earlier interaction had covered, the
+//! way a coverage-guided fuzzer favors the inputs that found new paths.
+//!
+//! This makes the plan of a seed depend on the database's behavior, not only on the
+//! seed, so it stays off by default.
+
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+
+use turso_core::coverage::{self, CoveragePoint};
+
+use crate::model::property::PropertyDiscriminants;
+
+/// Largest factor the weight of a property is multiplied by.
+const MAX_BOOST: u32 = 8;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CoverageGuide {
+    covered: HashSet<CoveragePoint>,
+    /// The new points that the recent runs of each property found, halved on every run
+    /// so that properties that stop finding any fall back to their normal weight.
+    energy: HashMap<PropertyDiscriminants, u32>,
+    /// The id and property of the interactions being run, with the new points they found
+    current: Option<(NonZeroUsize, PropertyDiscriminants, u32)>,
+}
+
+impl CoverageGuide {
+    /// Start recording the coverage of the programs run on this thread.
+    pub(crate) fn start() -> Self {
+        coverage::enable(true);
+        // forget whatever ran before the simulation
+        coverage::take();
+        Self::default()
+    }
+
+    /// Take the coverage of the interaction that just ran, crediting the points that no
+    /// interaction covered before to its property.
+    pub(crate) fn observe(&mut self, id: NonZeroUsize, property: Option<PropertyDiscriminants>) {
+        let found = coverage::take()
+            .into_iter()
+            .filter(|(point, _)| self.covered.insert(*point))
+            .count() as u32;
+        if self.current.is_some_and(|(current, ..)| current != id) {
+            self.settle();
+        }
+        if let Some(property) = property {
+            let (.., total) = self.current.get_or_insert((id, property, 0));
+            *total += found;
+        }
+    }
+
+    fn settle(&mut self) {
+        if let Some((_, property, found)) = self.current.take() {
+            let energy = self.energy.entry(property).or_default();
+            *energy = *energy / 2 + found;
+        }
+    }
+
+    /// The factor to multiply the weight of `property` by.
+    pub(crate) fn boost(&self, property: PropertyDiscriminants) -> u32 {
+        let energy = self.energy.get(&property).copied().unwrap_or_default();
+        1 + energy.min(MAX_BOOST - 1)
+    }
+
+    /// Number of opcode edges covered so far.
+    pub(crate) fn covered(&self) -> usize {
+        self.covered.len()
+    }
+}
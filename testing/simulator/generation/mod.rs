@@ -3,6 +3,7 @@ use sql_generation::generation::GenerationContext;
 
 use crate::runner::env::ShadowTablesMut;
 
+pub mod coverage;
 pub mod plan;
 pub mod property;
 pub mod query;
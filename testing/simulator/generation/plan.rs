@@ -12,11 +12,14 @@ use crate::{
     SimulatorEnv,
     generation::{
         WeightedDistribution,
-        property::PropertyDistribution,
+        property::{
+            PropertyDistribution, property_checkpoint_interleaving, property_interleaving,
+            property_transaction_atomicity,
+        },
         query::{QueryDistribution, possible_queries},
     },
     model::{
-        Query,
+        Query, QueryDiscriminants,
         interactions::{
             Fault, Interaction, InteractionBuilder, InteractionPlan, InteractionPlanIterator,
             InteractionType, Interactions, InteractionsType,
@@ -24,13 +27,11 @@ use crate::{
         metrics::{InteractionStats, Remaining},
         property::Property,
     },
+    runner::env::SimulationType,
 };
 
 impl InteractionPlan {
-    pub fn generator<'a>(
-        &'a mut self,
-        rng: &'a mut impl rand::Rng,
-    ) -> impl InteractionPlanIterator {
+    pub fn generator<'a, R: rand::Rng>(&'a mut self, rng: &'a mut R) -> PlanGenerator<'a, R> {
         let interactions = self.interactions_list().to_vec();
         let iter = interactions.into_iter();
         PlanGenerator {
@@ -152,6 +153,33 @@ pub struct PlanGenerator<'a, R: rand::Rng> {
 }
 
 impl<'a, R: rand::Rng> PlanGenerator<'a, R> {
+    /// See [InteractionPlan::compact].
+    pub fn compact_plan(&mut self, keep: usize) -> Vec<Interaction> {
+        self.plan.compact(keep)
+    }
+
+    pub fn plan(&self) -> &InteractionPlan {
+        self.plan
+    }
+
+    /// Whether every interaction of the last generated property was handed out.
+    pub fn at_property_boundary(&self) -> bool {
+        self.peek.is_none() && self.iter.len() == 0
+    }
+
+    /// Appends `interactions` to the plan as the next property, and returns the
+    /// interactions to run for it. Must be called at a property boundary.
+    pub fn push_interactions(&mut self, interactions: Interactions) -> Vec<Interaction> {
+        assert!(self.at_property_boundary());
+        let id = self.plan.next_property_id();
+        let list = interactions.interactions(id);
+        for interaction in &list {
+            self.plan.push(interaction.clone());
+        }
+        self.plan.push_interactions(id, interactions);
+        list
+    }
+
     fn next_interaction(&mut self, env: &mut SimulatorEnv) -> Option<Interaction> {
         self.iter
             .next()
@@ -169,7 +197,7 @@ impl<'a, R: rand::Rng> PlanGenerator<'a, R> {
 
                 let mut iter = iter.into_iter();
 
-                self.plan.push_interactions(interactions);
+                self.plan.push_interactions(id, interactions);
 
                 let next = iter.next();
                 self.iter = iter;
@@ -275,15 +303,16 @@ impl<'a, R: rand::Rng> InteractionPlanIterator for PlanGenerator<'a, R> {
                         let interactions =
                             Interactions::new(conn_index, InteractionsType::Query(query));
 
+                        let id = self.plan.next_property_id();
                         let interaction = InteractionBuilder::with_interaction(
                             InteractionType::Query(Query::Commit(Commit)),
                         )
                         .connection_index(conn_index)
-                        .id(self.plan.next_property_id())
+                        .id(id)
                         .build()
                         .unwrap();
 
-                        self.plan.push_interactions(interactions);
+                        self.plan.push_interactions(id, interactions);
 
                         interaction
                     });
@@ -392,6 +421,81 @@ impl ArbitraryFrom<(&SimulatorEnv, &InteractionStats, usize)> for Interactions {
             ),
         ];
 
+        // Interleavings, checkpoint interleavings and transaction atomicity run
+        // statements on other connections too, so they are generated here, where the
+        // connections are known, rather than as properties of a single connection
+        let writes = remaining_.insert + remaining_.update + remaining_.delete;
+        let multi_connection = !env.profile.mvcc
+            && !matches!(env.type_, SimulationType::Differential)
+            && env.connections.len() > 1
+            && !(0..env.connections.len()).any(|idx| env.conn_in_transaction(idx))
+            && conn_ctx
+                .tables()
+                .iter()
+                .any(|table| !table.name.contains('.'))
+            && remaining_.select > 0
+            && writes > 0
+            && query_distr.positive_items().any(|query| {
+                matches!(
+                    query,
+                    QueryDiscriminants::Insert
+                        | QueryDiscriminants::Update
+                        | QueryDiscriminants::Delete
+                )
+            });
+        if multi_connection && !env.opts.disable_interleaving {
+            choices.push((
+                remaining_.select.min(writes),
+                Box::new(|rng: &mut R| {
+                    Interactions::new(
+                        conn_index,
+                        InteractionsType::Property(property_interleaving(
+                            rng,
+                            &query_distr,
+                            conn_ctx,
+                            conn_index,
+                            env.connections.len(),
+                        )),
+                    )
+                }),
+            ));
+        }
+        if multi_connection && !env.opts.disable_checkpoint_interleaving {
+            choices.push((
+                remaining_.select.min(writes),
+                Box::new(|rng: &mut R| {
+                    Interactions::new(
+                        conn_index,
+                        InteractionsType::Property(property_checkpoint_interleaving(
+                            rng,
+                            &query_distr,
+                            conn_ctx,
+                            conn_index,
+                            env.connections.len(),
+                            env.opts.power_loss,
+                        )),
+                    )
+                }),
+            ));
+        }
+        if multi_connection && !env.opts.disable_transaction_atomicity {
+            choices.push((
+                remaining_.select.min(writes),
+                Box::new(|rng: &mut R| {
+                    Interactions::new(
+                        conn_index,
+                        InteractionsType::Property(property_transaction_atomicity(
+                            rng,
+                            &query_distr,
+                            conn_ctx,
+                            conn_index,
+                            env.connections.len(),
+                        )),
+                    )
+                }),
+            ));
+        }
+
         if let Ok(property_distr) =
             PropertyDistribution::new(env, &remaining_, &query_distr, conn_ctx)
         {
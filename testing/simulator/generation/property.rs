@@ -9,13 +9,17 @@ use std::num::NonZeroUsize;
 
 use rand::distr::{Distribution, weighted::WeightedIndex};
 use sql_generation::{
-    generation::{Arbitrary, ArbitraryFrom, GenerationContext, pick, pick_index},
+    generation::{Arbitrary, ArbitraryFrom, GenerationContext, pick, pick_index, pick_unique},
     model::{
         query::{
             Create, Delete, Drop, Insert, Select,
             alter_table::{AlterTable, AlterTableType},
+            pragma::Pragma,
             predicate::Predicate,
-            select::{CompoundOperator, CompoundSelect, ResultColumn, SelectBody, SelectInner},
+            select::{
+                Aggregate, AggregateFunc, CompoundOperator, CompoundSelect, GroupBy, Having,
+                OrderBy, ResultColumn, SelectBody, SelectInner,
+            },
             transaction::{Begin, Commit, Rollback},
             update::{SetValue, Update},
         },
@@ -33,12 +37,16 @@ use crate::{
         CreateSequence, DropSequence, Query, QueryCapabilities, QueryDiscriminants,
         ReleaseSavepoint, ResultSet, RollbackToSavepoint, Savepoint, expand_with_generated_columns,
         interactions::{
-            Assertion, Interaction, InteractionBuilder, InteractionType, PropertyMetadata,
+            Assertion, Fault, Interaction, InteractionBuilder, InteractionType, IoFault,
+            PropertyMetadata, reopen_database,
         },
         metrics::Remaining,
-        property::{InteractiveQueryInfo, Property, PropertyDiscriminants},
+        property::{CheckpointStep, InteractiveQueryInfo, Property, PropertyDiscriminants, Reader},
+    },
+    runner::{
+        allocation_fault,
+        env::{SimulationType, SimulatorEnv},
     },
-    runner::env::SimulatorEnv,
 };
 
 type PropertyQueryGenFunc<'a, R, G> =
@@ -95,6 +103,7 @@ impl Property {
                         Query::Delete(Delete {
                             table: t,
                             predicate,
+                            ..
                         }) if t == &table.name && predicate.test(&full_row, table) => {
                             // The inserted row will not be deleted.
                             None
@@ -267,19 +276,28 @@ impl Property {
             Property::Queries { .. } => {
                 unreachable!("No extensional querie generation for `Property::Queries`")
             }
-            Property::FsyncNoWait { .. } | Property::FaultyQuery { .. } => {
+            Property::FsyncNoWait { .. }
+            | Property::FaultyQuery { .. }
+            | Property::ScheduledIoFault { .. }
+            | Property::PowerLoss { .. }
+            | Property::AllocationFailure { .. } => {
                 unreachable!("No extensional queries")
             }
             Property::SequenceMonotonicity { .. } => {
                 unreachable!("No extensional queries for SequenceMonotonicity")
             }
             Property::SelectLimit { .. }
+            | Property::SelectAggregate { .. }
             | Property::SelectSelectOptimizer { .. }
+            | Property::IndexConsistency { .. }
             | Property::WhereTrueFalseNull { .. }
             | Property::UnionAllPreservesCardinality { .. }
             | Property::ReadYourUpdatesBack { .. }
             | Property::TableHasExpectedContent { .. }
-            | Property::AllTableHaveExpectedContent { .. } => {
+            | Property::AllTableHaveExpectedContent { .. }
+            | Property::Interleaving { .. }
+            | Property::CheckpointInterleaving { .. }
+            | Property::TransactionAtomicity { .. } => {
                 unreachable!("No extensional queries")
             }
         }
@@ -456,6 +474,9 @@ impl Property {
                                                         )));
                                                     }
                                                 }
+                                                // The copied value depends on the row, the
+                                                // shadow tables check it
+                                                SetValue::Column(_) => {}
                                             }
                                         }
                                     }
@@ -694,24 +715,98 @@ impl Property {
                 let limit = select
                     .limit
                     .expect("Property::SelectLimit without a LIMIT clause");
+                let offset = select.offset.unwrap_or(0);
 
                 let assertion = InteractionType::Assertion(Assertion::new(
                     "select query should respect the limit clause".to_string(),
-                    move |stack: &Vec<ResultSet>, _| {
-                        let last = stack.last().unwrap();
-                        match last {
-                            Ok(rows) => {
-                                if limit >= rows.len() {
-                                    Ok(Ok(()))
-                                } else {
-                                    Ok(Err(format!(
-                                        "limit {} violated: got {} rows",
-                                        limit,
-                                        rows.len()
-                                    )))
+                    {
+                        let select = select.clone();
+                        move |stack: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            let last = stack.last().unwrap();
+                            let Ok(rows) = last else {
+                                return Ok(Ok(()));
+                            };
+                            if limit < rows.len() {
+                                return Ok(Err(format!(
+                                    "limit {} violated: got {} rows",
+                                    limit,
+                                    rows.len()
+                                )));
+                            }
+
+                            // Every row the select matches, in the order of its ORDER BY
+                            let mut tables = env.get_conn_tables_mut(connection_index);
+                            let unlimited = Select {
+                                limit: None,
+                                offset: None,
+                                ..select.clone()
+                            };
+                            let matching = match unlimited.shadow(&mut tables) {
+                                Ok(matching) => matching,
+                                Err(err) => {
+                                    return Ok(Err(format!(
+                                        "failed to compute the rows of the select: {err}"
+                                    )));
+                                }
+                            };
+                            let table_name = select.dependencies()[0].clone();
+                            let table = tables
+                                .iter()
+                                .find(|t| t.name == table_name)
+                                .expect("table should be in enviroment");
+                            let expected = matching
+                                .iter()
+                                .skip(offset)
+                                .take(limit)
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            if rows.len() != expected.len() {
+                                print_diff(&expected, rows, "simulator", "database");
+                                return Ok(Err(format!(
+                                    "expected {} rows with limit {limit} and offset {offset}, got {}",
+                                    expected.len(),
+                                    rows.len()
+                                )));
+                            }
+                            let matching = matching
+                                .iter()
+                                .map(|r| strip_virtual_cols(table, r))
+                                .collect::<Vec<_>>();
+                            if let Some(row) = rows
+                                .iter()
+                                .find(|r| !matching.contains(&strip_virtual_cols(table, r)))
+                            {
+                                return Ok(Err(format!(
+                                    "row {row:?} is not one of the rows the select matches"
+                                )));
+                            }
+
+                            // Rows that tie on the ORDER BY can come in any order, and a
+                            // LIMIT can cut between them, so only compare the ORDER BY
+                            // columns
+                            if let Some(order_by) = &select.body.select.order_by {
+                                let keys = order_by
+                                    .key_columns(table)
+                                    .expect("ORDER BY column should be in the table");
+                                let sort_key = |row: &[SimValue]| {
+                                    keys.iter()
+                                        .map(|&(idx, _)| row[idx].clone())
+                                        .collect::<Vec<_>>()
+                                };
+                                for (idx, (row, expected_row)) in
+                                    rows.iter().zip(&expected).enumerate()
+                                {
+                                    if sort_key(row) != sort_key(expected_row) {
+                                        print_diff(&expected, rows, "simulator", "database");
+                                        return Ok(Err(format!(
+                                            "row {idx} is out of order: expected ORDER BY values {:?}, got {:?}",
+                                            sort_key(expected_row),
+                                            sort_key(row)
+                                        )));
+                                    }
                                 }
                             }
-                            Err(_) => Ok(Ok(())),
+                            Ok(Ok(()))
                         }
                     },
                     select.dependencies().into_iter().collect(),
@@ -725,6 +820,78 @@ impl Property {
                     InteractionBuilder::with_interaction(assertion),
                 ]
             }
+            Property::SelectAggregate { select } => {
+                let table = select.dependencies()[0].clone();
+                let assumption = InteractionType::Assumption(Assertion::new(
+                    format!("table {table} exists"),
+                    {
+                        let table = table.clone();
+                        move |_: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            let conn_tables = env.get_conn_tables(connection_index);
+                            if conn_tables.iter().any(|t| t.name == table) {
+                                Ok(Ok(()))
+                            } else {
+                                Ok(Err(format!("missing table {table}")))
+                            }
+                        }
+                    },
+                    vec![table.clone()],
+                ));
+
+                let assertion = InteractionType::Assertion(Assertion::new(
+                    "aggregate select should return the groups of the model".to_string(),
+                    {
+                        let select = select.clone();
+                        move |stack: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            let last = stack.last().unwrap();
+                            let Ok(rows) = last else {
+                                return Ok(Ok(()));
+                            };
+                            let mut expected = match select
+                                .shadow(&mut env.get_conn_tables_mut(connection_index))
+                            {
+                                Ok(expected) => expected,
+                                Err(err) => {
+                                    return Ok(Err(format!(
+                                        "failed to compute the groups of the select: {err}"
+                                    )));
+                                }
+                            };
+                            // Groups come in any order. The GROUP BY columns come first and
+                            // are different in every group, so sorting lines them up.
+                            let mut rows = rows.clone();
+                            rows.sort();
+                            expected.sort();
+                            let matches = rows.len() == expected.len()
+                                && rows.iter().zip(&expected).all(|(row, expected_row)| {
+                                    row.len() == expected_row.len()
+                                        && row.iter().zip(expected_row).all(|(value, expected)| {
+                                            aggregate_value_matches(value, expected)
+                                        })
+                                });
+                            if matches {
+                                Ok(Ok(()))
+                            } else {
+                                print_diff(&expected, &rows, "simulator", "database");
+                                Ok(Err(format!(
+                                    "expected {} groups, got {} that differ from the model's",
+                                    expected.len(),
+                                    rows.len()
+                                )))
+                            }
+                        }
+                    },
+                    vec![table.clone()],
+                ));
+
+                vec![
+                    InteractionBuilder::with_interaction(assumption),
+                    InteractionBuilder::with_interaction(InteractionType::Query(Query::Select(
+                        select.clone(),
+                    ))),
+                    InteractionBuilder::with_interaction(assertion),
+                ]
+            }
             Property::DeleteSelect {
                 table,
                 predicate,
@@ -755,6 +922,7 @@ impl Property {
                 let delete = InteractionType::Query(Query::Delete(Delete {
                     table: table.clone(),
                     predicate: predicate.clone(),
+                    limit: None,
                 }));
 
                 let select = InteractionType::Query(Query::Select(Select::simple(
@@ -976,6 +1144,84 @@ impl Property {
                     InteractionBuilder::with_interaction(assertion),
                 ]
             }
+            Property::IndexConsistency {
+                table,
+                index,
+                predicate,
+            } => {
+                let assumption = InteractionType::Assumption(Assertion::new(
+                    format!("index {index} on table {table} exists"),
+                    {
+                        let table = table.clone();
+                        let index = index.clone();
+                        move |_: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            let conn_tables = env.get_conn_tables(connection_index);
+                            match conn_tables.iter().find(|t| t.name == table) {
+                                Some(t) if t.indexes.iter().any(|i| i.index_name == index) => {
+                                    Ok(Ok(()))
+                                }
+                                Some(_) => {
+                                    Ok(Err(format!("index '{index}' not found on table '{table}'")))
+                                }
+                                None => Ok(Err(format!("table '{table}' not found"))),
+                            }
+                        }
+                    },
+                    vec![table.clone()],
+                ));
+
+                let scan = |indexed: ast::Indexed| {
+                    let mut select = Select::simple(table.clone(), predicate.clone());
+                    if let Some(from) = &mut select.body.select.from {
+                        from.indexed = Some(indexed);
+                    }
+                    InteractionType::Query(Query::Select(select))
+                };
+                let index_scan = scan(ast::Indexed::IndexedBy(ast::Name::exact(index.clone())));
+                let full_scan = scan(ast::Indexed::NotIndexed);
+
+                let assertion = InteractionType::Assertion(Assertion::new(
+                    format!("select through index {index} should return the rows of a full scan"),
+                    move |stack: &Vec<ResultSet>, _| {
+                        let full_scan = stack.last().unwrap();
+                        let index_scan = stack.get(stack.len() - 2).unwrap();
+                        match (index_scan, full_scan) {
+                            (Ok(index_rows), Ok(table_rows)) => {
+                                let mut index_rows = index_rows.clone();
+                                let mut table_rows = table_rows.clone();
+                                index_rows.sort();
+                                table_rows.sort();
+                                if index_rows == table_rows {
+                                    Ok(Ok(()))
+                                } else {
+                                    print_diff(&table_rows, &index_rows, "full scan", "index");
+                                    Ok(Err(format!(
+                                        "index scan returned {} rows, full scan returned {}",
+                                        index_rows.len(),
+                                        table_rows.len()
+                                    )))
+                                }
+                            }
+                            (Err(e1), Err(e2)) => {
+                                tracing::debug!("Error in both scans: {}, {}", e1, e2);
+                                Ok(Ok(()))
+                            }
+                            (Err(e), _) | (_, Err(e)) => {
+                                tracing::error!("Error in one of the scans: {}", e);
+                                Err(LimboError::InternalError(e.to_string()))
+                            }
+                        }
+                    },
+                    vec![table.clone()],
+                ));
+
+                vec![
+                    InteractionBuilder::with_interaction(assumption),
+                    InteractionBuilder::with_interaction(index_scan),
+                    InteractionBuilder::with_interaction(full_scan),
+                    InteractionBuilder::with_interaction(assertion),
+                ]
+            }
             Property::FsyncNoWait { query } => {
                 vec![InteractionBuilder::with_interaction(
                     InteractionType::FsyncQuery(query.clone()),
@@ -1038,45 +1284,526 @@ impl Property {
                 .map(InteractionBuilder::with_interaction)
                 .collect()
             }
-            Property::WhereTrueFalseNull { select, predicate } => {
-                let tables_dependencies = select.dependencies().into_iter().collect::<Vec<_>>();
-                let assumption = InteractionType::Assumption(Assertion::new(
+            Property::ScheduledIoFault { fault, query } => {
+                let fault = *fault;
+                let query_clone = query.clone();
+                let tables = query.dependencies().into_iter().collect::<Vec<_>>();
+                let mut run =
+                    InteractionBuilder::with_interaction(InteractionType::Query(query.clone()));
+                run.ignore_error(true);
+                let assert = Assertion::new(
+                    format!("{fault} fault is reported"),
+                    move |stack, env: &mut SimulatorEnv| {
+                        // A fault that did not fire, because the query did not get to the
+                        // operation it applies to, must not fire later in the plan.
+                        let fired = env.io.clear_scheduled_fault();
+                        match stack.last().unwrap() {
+                            Ok(_) if fired => Ok(Err(format!(
+                                "query succeeded although its {fault} fault fired"
+                            ))),
+                            Ok(_) => Ok(Ok(())),
+                            Err(LimboError::CheckpointFailed(msg)) => {
+                                // The transaction committed before the checkpoint failed
+                                tracing::error!(
+                                    "{fault} fault produced CheckpointFailed error: {msg}"
+                                );
+                                query_clone
+                                    .shadow(&mut env.get_conn_tables_mut(connection_index))
+                                    .expect("Failed to shadow tables");
+                                Ok(Ok(()))
+                            }
+                            Err(err) => {
+                                // As for FaultyQuery, the failed statement is rolled back,
+                                // and the enclosing transaction only if the engine left it.
+                                tracing::error!("{fault} fault produced error: {err}");
+                                if !env.conn_db_in_transaction(connection_index) {
+                                    env.rollback_conn(connection_index);
+                                }
+                                Ok(Ok(()))
+                            }
+                        }
+                    },
+                    tables.clone(),
+                );
+                let mut interactions = vec![
+                    InteractionBuilder::with_interaction(InteractionType::Fault(Fault::Io(fault))),
+                    run,
+                    InteractionBuilder::with_interaction(InteractionType::Assertion(assert)),
+                    assert_integrity_check(&tables, connection_index, "an IO fault"),
+                ];
+                interactions.extend(assert_all_table_values(&tables, connection_index));
+                interactions
+            }
+            Property::PowerLoss {
+                crash_point,
+                query,
+                checkpoint,
+            } => {
+                let crash_point = *crash_point;
+                let query_clone = query.clone();
+                let tables = query.dependencies().into_iter().collect::<Vec<_>>();
+                let mut interactions = vec![InteractionBuilder::with_interaction(
+                    InteractionType::Fault(Fault::PowerLoss { crash_point }),
+                )];
+                let mut run =
+                    InteractionBuilder::with_interaction(InteractionType::Query(query.clone()));
+                run.ignore_error(true);
+                interactions.push(run);
+                if *checkpoint {
+                    let mut run = InteractionBuilder::with_interaction(InteractionType::Query(
+                        Query::Pragma(Pragma::WalCheckpoint),
+                    ));
+                    run.ignore_error(true);
+                    interactions.push(run);
+                }
+                let recovered = tables.clone();
+                let assert = Assertion::new(
                     format!(
-                        "tables ({}) exists",
-                        select
-                            .dependencies()
-                            .into_iter()
-                            .collect::<Vec<_>>()
-                            .join(", ")
+                        "database recovers the state before or after the query from a power loss after {crash_point} operations"
                     ),
-                    {
-                        let tables = select.dependencies();
-                        move |_: &Vec<ResultSet>, env: &mut SimulatorEnv| {
-                            let conn_tables = env.get_conn_tables(connection_index);
-                            if tables
-                                .iter()
-                                .all(|table| conn_tables.iter().any(|t| t.name == *table))
-                            {
-                                Ok(Ok(()))
-                            } else {
-                                let missing_tables = tables
+                    move |stack, env: &mut SimulatorEnv| {
+                        // A crash the statements did not reach must not happen later in the plan
+                        let crashed = env.io.clear_scheduled_fault();
+                        if crashed && stack.iter().all(Result::is_ok) {
+                            return Ok(Err(format!(
+                                "no statement failed although the power went out after {crash_point} operations"
+                            )));
+                        }
+                        // Whether the commit of the query may have reached the files
+                        // without the query reporting it
+                        let unknown = match &stack[0] {
+                            Ok(_) => false,
+                            Err(LimboError::CheckpointFailed(msg)) => {
+                                // The transaction committed before the checkpoint failed
+                                tracing::error!(
+                                    "power loss produced CheckpointFailed error: {msg}"
+                                );
+                                query_clone
+                                    .shadow(&mut env.get_conn_tables_mut(connection_index))
+                                    .expect("Failed to shadow tables");
+                                false
+                            }
+                            Err(err) => {
+                                tracing::error!("power loss produced error: {err}");
+                                let autocommit = !env.conn_in_transaction(connection_index);
+                                if !env.conn_db_in_transaction(connection_index) {
+                                    env.rollback_conn(connection_index);
+                                }
+                                autocommit
+                            }
+                        };
+                        // Reopening rolls back the open transactions of the model, as the
+                        // recovery does for the database
+                        reopen_database(env);
+                        if !unknown {
+                            return Ok(Ok(()));
+                        }
+                        let rows = recovered
+                            .iter()
+                            .map(|table| read_table(env, connection_index, table))
+                            .collect::<turso_core::Result<Vec<_>>>()?;
+                        let recovered_model = |env: &SimulatorEnv| {
+                            let model = env.get_conn_tables(connection_index);
+                            recovered.iter().zip(&rows).all(|(name, rows)| {
+                                model
                                     .iter()
-                                    .filter(|t| !conn_tables.iter().any(|t2| t2.name == **t))
-                                    .collect::<Vec<&String>>();
-                                Ok(Err(format!("missing tables: {missing_tables:?}")))
+                                    .find(|table| &table.name == name)
+                                    .is_some_and(|table| same_rows(table, rows))
+                            })
+                        };
+                        if recovered_model(env) {
+                            return Ok(Ok(()));
+                        }
+                        // Compare against the model with the query applied, and keep it if
+                        // the commit made it to the files
+                        Begin::Immediate.shadow(&mut env.get_conn_tables_mut(connection_index));
+                        let committed = query_clone
+                            .shadow(&mut env.get_conn_tables_mut(connection_index))
+                            .is_ok()
+                            && recovered_model(env);
+                        if committed {
+                            Commit.shadow(&mut env.get_conn_tables_mut(connection_index));
+                            Ok(Ok(()))
+                        } else {
+                            Rollback.shadow(&mut env.get_conn_tables_mut(connection_index));
+                            Ok(Err(format!(
+                                "tables {recovered:?} hold neither the state before `{query_clone}` nor the state after it"
+                            )))
+                        }
+                    },
+                    tables.clone(),
+                );
+                interactions.push(InteractionBuilder::with_interaction(
+                    InteractionType::Assertion(assert),
+                ));
+                interactions.push(assert_integrity_check(
+                    &tables,
+                    connection_index,
+                    "a power loss",
+                ));
+                interactions.extend(assert_all_table_values(&tables, connection_index));
+                interactions
+            }
+            Property::AllocationFailure { nth, query } => {
+                let nth = *nth;
+                let query_clone = query.clone();
+                let tables = query.dependencies().into_iter().collect::<Vec<_>>();
+                let mut run =
+                    InteractionBuilder::with_interaction(InteractionType::Query(query.clone()));
+                run.ignore_error(true);
+                let assert = Assertion::new(
+                    format!("allocation failure after {nth} allocations is reported"),
+                    move |stack, env: &mut SimulatorEnv| {
+                        // A failure that did not fire, because the query made fewer
+                        // allocations, must not fire later in the plan.
+                        let fired = allocation_fault::clear();
+                        match stack.last().unwrap() {
+                            // The failed allocation may have been one the query could do
+                            // without, like growing a cache
+                            Ok(_) => {
+                                query_clone
+                                    .shadow(&mut env.get_conn_tables_mut(connection_index))
+                                    .expect("Failed to shadow tables");
+                                Ok(Ok(()))
+                            }
+                            Err(err) if fired && !matches!(err, LimboError::OutOfMemory) => {
+                                Ok(Err(format!(
+                                    "query failed with '{err}' instead of running out of memory"
+                                )))
+                            }
+                            Err(err) => {
+                                // As for FaultyQuery, the failed statement is rolled back,
+                                // and the enclosing transaction only if the engine left it.
+                                tracing::error!("allocation failure produced error: {err}");
+                                if !env.conn_db_in_transaction(connection_index) {
+                                    env.rollback_conn(connection_index);
+                                }
+                                Ok(Ok(()))
                             }
                         }
                     },
-                    tables_dependencies.clone(),
+                    tables.clone(),
+                );
+                let mut interactions = vec![
+                    InteractionBuilder::with_interaction(InteractionType::Fault(
+                        Fault::AllocationFailure { nth },
+                    )),
+                    run,
+                    InteractionBuilder::with_interaction(InteractionType::Assertion(assert)),
+                    assert_integrity_check(&tables, connection_index, "an allocation failure"),
+                ];
+                interactions.extend(assert_all_table_values(&tables, connection_index));
+                interactions
+            }
+            Property::Interleaving {
+                writer,
+                readers,
+                schedule,
+            } => {
+                let mut tables = writer.iter().flat_map(Query::uses).collect::<Vec<_>>();
+                tables.sort();
+                tables.dedup();
+                // The next statement of each transaction
+                let mut next = vec![0; readers.len() + 1];
+                let mut interactions = Vec::new();
+                for &txn in schedule {
+                    let step = next[txn];
+                    next[txn] += 1;
+                    if txn == 0 {
+                        interactions.push(InteractionBuilder::with_interaction(
+                            InteractionType::Query(writer[step].clone()),
+                        ));
+                        continue;
+                    }
+                    let reader = readers[txn - 1].connection;
+                    match &readers[txn - 1].queries[step] {
+                        Query::Select(select) => {
+                            let read = select.dependencies().into_iter().collect::<Vec<_>>();
+                            interactions.extend(assert_all_table_values(&read, reader).map(
+                                |mut builder| {
+                                    builder.connection_index(reader);
+                                    builder
+                                },
+                            ));
+                        }
+                        query if query.is_write() => {
+                            let mut write = InteractionBuilder::with_interaction(
+                                InteractionType::Query(query.clone()),
+                            );
+                            write.connection_index(reader).ignore_error(true);
+                            let write_query = query.clone();
+                            let assertion = Assertion::new(
+                                format!(
+                                    "connection {reader} cannot write while connection {connection_index} holds the write lock"
+                                ),
+                                move |stack: &Vec<ResultSet>, _env: &mut SimulatorEnv| match stack
+                                    .last()
+                                    .unwrap()
+                                {
+                                    Err(LimboError::Busy | LimboError::BusySnapshot) => Ok(Ok(())),
+                                    Ok(_) => Ok(Err(format!(
+                                        "`{write_query}` succeeded while another connection held the write lock"
+                                    ))),
+                                    Err(err) => Ok(Err(format!(
+                                        "expected `{write_query}` to fail with Busy, but it failed with: {err}"
+                                    ))),
+                                },
+                                query.uses(),
+                            );
+                            let mut assert = InteractionBuilder::with_interaction(
+                                InteractionType::Assertion(assertion),
+                            );
+                            assert.connection_index(reader);
+                            interactions.extend([write, assert]);
+                        }
+                        query => {
+                            let mut run = InteractionBuilder::with_interaction(
+                                InteractionType::Query(query.clone()),
+                            );
+                            run.connection_index(reader);
+                            interactions.push(run);
+                        }
+                    }
+                }
+                interactions.extend(assert_all_table_values(&tables, connection_index));
+                interactions
+            }
+            Property::CheckpointInterleaving { steps, crash_point } => {
+                let mut tables = steps
+                    .iter()
+                    .filter(|step| step.query.is_write())
+                    .flat_map(|step| step.query.uses())
+                    .collect::<Vec<_>>();
+                tables.sort();
+                tables.dedup();
+                // The readers in a transaction, whose snapshots may keep a checkpoint
+                // from moving the whole WAL to the database file
+                let mut in_transaction = Vec::new();
+                let mut interactions = Vec::new();
+                for step in steps {
+                    let connection = step.connection;
+                    match &step.query {
+                        Query::Select(select) => {
+                            let read = select.dependencies().into_iter().collect::<Vec<_>>();
+                            interactions.extend(assert_all_table_values(&read, connection).map(
+                                |mut builder| {
+                                    builder.connection_index(connection);
+                                    builder
+                                },
+                            ));
+                        }
+                        Query::Pragma(Pragma::WalCheckpoint) => {
+                            interactions.extend(checkpoint(
+                                connection,
+                                !in_transaction.is_empty(),
+                                tables.clone(),
+                            ));
+                        }
+                        query => {
+                            match query {
+                                Query::Begin(_) => in_transaction.push(connection),
+                                Query::Commit(_) => {
+                                    in_transaction.retain(|&reader| reader != connection)
+                                }
+                                _ => {}
+                            }
+                            let mut run = InteractionBuilder::with_interaction(
+                                InteractionType::Query(query.clone()),
+                            );
+                            run.connection_index(connection);
+                            interactions.push(run);
+                        }
+                    }
+                }
+                if let Some(crash_point) = *crash_point {
+                    interactions.push(InteractionBuilder::with_interaction(
+                        InteractionType::Fault(Fault::PowerLoss { crash_point }),
+                    ));
+                    let mut run = InteractionBuilder::with_interaction(InteractionType::Query(
+                        Query::Pragma(Pragma::WalCheckpoint),
+                    ));
+                    run.ignore_error(true);
+                    interactions.push(run);
+                    let assert = Assertion::new(
+                        format!(
+                            "checkpoint fails if the power goes out after {crash_point} operations of it"
+                        ),
+                        move |stack: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            // A crash the statements did not reach must not happen later in the plan
+                            let crashed = env.io.clear_scheduled_fault();
+                            let result = stack.last().unwrap();
+                            if crashed && result.is_ok() {
+                                return Ok(Err(format!(
+                                    "checkpoint succeeded although the power went out after {crash_point} operations"
+                                )));
+                            }
+                            if let Err(err) = result {
+                                tracing::error!(
+                                    "power loss during checkpoint produced error: {err}"
+                                );
+                            }
+                            // Every write committed before the checkpoint, so the recovery
+                            // must find all of them, in the WAL or in the database file
+                            reopen_database(env);
+                            Ok(Ok(()))
+                        },
+                        tables.clone(),
+                    );
+                    interactions.push(InteractionBuilder::with_interaction(
+                        InteractionType::Assertion(assert),
+                    ));
+                }
+                interactions.push(assert_integrity_check(
+                    &tables,
+                    connection_index,
+                    "interleaved checkpoints",
                 ));
+                interactions.extend(assert_all_table_values(&tables, connection_index));
+                interactions
+            }
+            Property::TransactionAtomicity {
+                queries,
+                observer,
+                commit,
+            } => {
+                let observer = *observer;
+                let commit = *commit;
+                let mut tables = queries.iter().flat_map(Query::uses).collect::<Vec<_>>();
+                tables.sort();
+                tables.dedup();
+                let observe = || {
+                    tables.iter().map(move |table| {
+                        let mut read =
+                            InteractionBuilder::with_interaction(InteractionType::Query(
+                                Query::Select(Select::simple(table.clone(), Predicate::true_())),
+                            ));
+                        read.connection_index(observer);
+                        read
+                    })
+                };
 
-                let old_predicate = select.body.select.where_clause.clone();
-
-                let p_true = Predicate::and(vec![old_predicate.clone(), predicate.clone()]);
-                let p_false = Predicate::and(vec![
-                    old_predicate.clone(),
-                    Predicate::not(predicate.clone()),
-                ]);
+                let mut interactions = observe().collect::<Vec<_>>();
+                interactions.push(InteractionBuilder::with_interaction(
+                    InteractionType::Query(Query::Begin(Begin::Deferred)),
+                ));
+                for query in queries {
+                    interactions.push(InteractionBuilder::with_interaction(
+                        InteractionType::Query(query.clone()),
+                    ));
+                    interactions.extend(assert_all_table_values(&tables, connection_index));
+                    interactions.extend(observe());
+                }
+                interactions.push(InteractionBuilder::with_interaction(
+                    InteractionType::Query(if commit {
+                        Query::Commit(Commit)
+                    } else {
+                        Query::Rollback(Rollback)
+                    }),
+                ));
+                interactions.extend(observe());
+
+                let observed = tables.clone();
+                let read_count = tables.len() * (queries.len() + 2);
+                let end = if commit { "commits" } else { "rolls back" };
+                let mut assert = InteractionBuilder::with_interaction(InteractionType::Assertion(
+                    Assertion::new(
+                        format!(
+                            "connection {observer} sees all of the transaction on connection {connection_index} once it {end}, and none of it before"
+                        ),
+                        move |stack: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            // The observer read every table before the transaction, after
+                            // each of its queries, and after it ended
+                            let reads = stack[stack.len() - read_count..]
+                                .iter()
+                                .cloned()
+                                .collect::<turso_core::Result<Vec<_>>>()?;
+                            let mut reads = reads.chunks(observed.len());
+                            let before = reads.next().unwrap();
+                            let after = reads.next_back().unwrap();
+                            for (step, during) in reads.enumerate() {
+                                for ((table, before), during) in
+                                    observed.iter().zip(before).zip(during)
+                                {
+                                    if !same_result(before, during) {
+                                        print_diff(before, during, "before", "during");
+                                        return Ok(Err(format!(
+                                            "table {table} changed for connection {observer} after query {} of a transaction that had not ended",
+                                            step + 1
+                                        )));
+                                    }
+                                }
+                            }
+                            let model = env.get_conn_tables(observer);
+                            for ((name, before), after) in observed.iter().zip(before).zip(after) {
+                                let table = model
+                                    .iter()
+                                    .find(|table| &table.name == name)
+                                    .ok_or_else(|| {
+                                        LimboError::InternalError(format!(
+                                            "table {name} should exist in simulator env"
+                                        ))
+                                    })?;
+                                if !commit && !same_result(before, after) {
+                                    print_diff(before, after, "before", "after");
+                                    return Ok(Err(format!(
+                                        "table {name} kept changes of a transaction that rolled back"
+                                    )));
+                                }
+                                if !same_rows(table, after) {
+                                    return Ok(Err(format!(
+                                        "table {name} does not match the model after the transaction {end}"
+                                    )));
+                                }
+                            }
+                            Ok(Ok(()))
+                        },
+                        tables.clone(),
+                    ),
+                ));
+                assert.connection_index(observer);
+                interactions.push(assert);
+                interactions.extend(assert_all_table_values(&tables, connection_index));
+                interactions
+            }
+            Property::WhereTrueFalseNull { select, predicate } => {
+                let tables_dependencies = select.dependencies().into_iter().collect::<Vec<_>>();
+                let assumption = InteractionType::Assumption(Assertion::new(
+                    format!(
+                        "tables ({}) exists",
+                        select
+                            .dependencies()
+                            .into_iter()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    {
+                        let tables = select.dependencies();
+                        move |_: &Vec<ResultSet>, env: &mut SimulatorEnv| {
+                            let conn_tables = env.get_conn_tables(connection_index);
+                            if tables
+                                .iter()
+                                .all(|table| conn_tables.iter().any(|t| t.name == *table))
+                            {
+                                Ok(Ok(()))
+                            } else {
+                                let missing_tables = tables
+                                    .iter()
+                                    .filter(|t| !conn_tables.iter().any(|t2| t2.name == **t))
+                                    .collect::<Vec<&String>>();
+                                Ok(Err(format!("missing tables: {missing_tables:?}")))
+                            }
+                        }
+                    },
+                    tables_dependencies.clone(),
+                ));
+
+                let old_predicate = select.body.select.where_clause.clone();
+
+                let p_true = Predicate::and(vec![old_predicate.clone(), predicate.clone()]);
+                let p_false = Predicate::and(vec![
+                    old_predicate.clone(),
+                    Predicate::not(predicate.clone()),
+                ]);
                 let p_null = Predicate::and(vec![
                     old_predicate,
                     Predicate::is(predicate.clone(), Predicate::null()),
@@ -1090,6 +1817,7 @@ impl Property {
                             from: select.body.select.from.clone(),
                             where_clause: p_true,
                             order_by: None,
+                            group_by: None,
                         }),
                         compounds: vec![
                             CompoundSelect {
@@ -1100,6 +1828,7 @@ impl Property {
                                     from: select.body.select.from.clone(),
                                     where_clause: p_false,
                                     order_by: None,
+                                    group_by: None,
                                 }),
                             },
                             CompoundSelect {
@@ -1110,11 +1839,13 @@ impl Property {
                                     from: select.body.select.from.clone(),
                                     where_clause: p_null,
                                     order_by: None,
+                                    group_by: None,
                                 }),
                             },
                         ],
                     },
                     limit: None,
+                    offset: None,
                 };
 
                 let select = InteractionType::Query(Query::Select(select.clone()));
@@ -1278,7 +2009,11 @@ impl Property {
                     })),
                 ));
                 interactions.extend(assert_all_table_values(tables, connection_index));
-                interactions.push(assert_integrity_check(tables, connection_index));
+                interactions.push(assert_integrity_check(
+                    tables,
+                    connection_index,
+                    "savepoint rollback",
+                ));
                 interactions
             }
             Property::SequenceMonotonicity {
@@ -1400,7 +2135,10 @@ impl Property {
                 if !builder.has_property_meta() {
                     builder.property_meta(PropertyMetadata::new(self, false));
                 }
-                builder.connection_index(connection_index).id(id);
+                if !builder.has_connection_index() {
+                    builder.connection_index(connection_index);
+                }
+                builder.id(id);
                 builder.build().unwrap()
             })
             .collect()
@@ -1593,13 +2331,46 @@ fn random_main_table_delete<R: rand::Rng + ?Sized>(rng: &mut R, table: &Table) -
         } else {
             Predicate::false_()
         },
+        limit: None,
     })
 }
 
-fn assert_integrity_check(tables: &[String], connection_index: usize) -> InteractionBuilder {
+/// A `PRAGMA wal_checkpoint` on `connection`, and the assertion that it succeeded, or
+/// failed with Busy if `busy` is set because a reader is in a transaction.
+fn checkpoint(connection: usize, busy: bool, tables: Vec<String>) -> [InteractionBuilder; 2] {
+    let mut run = InteractionBuilder::with_interaction(InteractionType::Query(Query::Pragma(
+        Pragma::WalCheckpoint,
+    )));
+    run.connection_index(connection).ignore_error(true);
+    let assertion = Assertion::new(
+        format!(
+            "checkpoint on connection {connection} succeeds, unless a reader is in a transaction"
+        ),
+        move |stack: &Vec<ResultSet>, _env: &mut SimulatorEnv| match stack.last().unwrap() {
+            Ok(_) => Ok(Ok(())),
+            Err(LimboError::Busy) if busy => Ok(Ok(())),
+            Err(LimboError::Busy) => Ok(Err(format!(
+                "checkpoint on connection {connection} was busy although no reader was in a transaction"
+            ))),
+            Err(err) => Ok(Err(format!(
+                "checkpoint on connection {connection} failed with: {err}"
+            ))),
+        },
+        tables,
+    );
+    let mut assert = InteractionBuilder::with_interaction(InteractionType::Assertion(assertion));
+    assert.connection_index(connection);
+    [run, assert]
+}
+
+fn assert_integrity_check(
+    tables: &[String],
+    connection_index: usize,
+    after: &str,
+) -> InteractionBuilder {
     let tables = tables.to_vec();
     InteractionBuilder::with_interaction(InteractionType::Assertion(Assertion::new(
-        "PRAGMA integrity_check should be ok after savepoint rollback".to_string(),
+        format!("PRAGMA integrity_check should be ok after {after}"),
         move |_stack: &Vec<ResultSet>, env: &mut SimulatorEnv| {
             let result = run_integrity_check(env, connection_index)?;
             if result == "ok" {
@@ -1656,6 +2427,56 @@ fn run_integrity_check(
     }
 }
 
+/// The rows of `table`, read through the connection.
+fn read_table(
+    env: &mut SimulatorEnv,
+    connection_index: usize,
+    table: &str,
+) -> turso_core::Result<Vec<Vec<SimValue>>> {
+    match &mut env.connections[connection_index] {
+        crate::runner::env::SimConnection::LimboConnection(conn) => InteractionType::Query(
+            Query::Select(Select::simple(table.to_string(), Predicate::true_())),
+        )
+        .execute_query(conn),
+        crate::runner::env::SimConnection::SQLiteConnection(_)
+        | crate::runner::env::SimConnection::Disconnected => Err(LimboError::InternalError(
+            format!("cannot read table {table} from connection"),
+        )),
+    }
+}
+
+/// Whether `rows` are the rows of the model `table`, in any order.
+fn same_rows(table: &Table, rows: &[Vec<SimValue>]) -> bool {
+    let expected: Vec<Vec<SimValue>> = table
+        .rows
+        .iter()
+        .map(|r| strip_virtual_cols(table, r))
+        .collect();
+    let actual: Vec<Vec<SimValue>> = rows.iter().map(|r| strip_virtual_cols(table, r)).collect();
+    expected.iter().all(|row| actual.contains(row))
+        && actual.iter().all(|row| expected.contains(row))
+}
+
+/// Whether two reads of a table returned the same rows, in any order.
+fn same_result(a: &[Vec<SimValue>], b: &[Vec<SimValue>]) -> bool {
+    a.len() == b.len() && a.iter().all(|row| b.contains(row)) && b.iter().all(|row| a.contains(row))
+}
+
+/// Whether an aggregate `value` is the `expected` one of the model. REAL values only have
+/// to match up to rounding, as the database may add them up in another order.
+fn aggregate_value_matches(value: &SimValue, expected: &SimValue) -> bool {
+    match (&value.0, &expected.0) {
+        (
+            types::Value::Numeric(Numeric::Float(value)),
+            types::Value::Numeric(Numeric::Float(expected)),
+        ) => {
+            let (value, expected) = (f64::from(*value), f64::from(*expected));
+            (value - expected).abs() <= 1e-9 * value.abs().max(expected.abs()).max(1.0)
+        }
+        _ => value == expected,
+    }
+}
+
 fn strip_virtual_cols(table: &Table, row: &[SimValue]) -> Vec<SimValue> {
     table
         .columns
@@ -1959,16 +2780,162 @@ fn property_select_limit<R: rand::Rng + ?Sized>(
     // Get a random table
     let table = pick(ctx.tables(), rng);
     // Select the table
-    let select = Select::single(
+    let mut select = Select::single(
         table.name.clone(),
         vec![ResultColumn::Star],
         Predicate::arbitrary_from(rng, ctx, table),
         Some(rng.random_range(1..=5)),
         Distinctness::All,
     );
+    // The model doesn't always compute generated columns the way the database does, so
+    // don't order by them
+    let columns = table
+        .columns
+        .iter()
+        .filter(|c| !c.is_generated())
+        .collect::<Vec<_>>();
+    if !columns.is_empty() && rng.random_bool(0.5) {
+        let num_columns = rng.random_range(1..=columns.len().min(2));
+        let columns = pick_unique(&columns, num_columns, rng)
+            .map(|column| format!("{}.{}", table.name, column.name))
+            .collect::<Vec<_>>();
+        select.body.select.order_by = Some(OrderBy {
+            columns: columns
+                .into_iter()
+                .map(|column| {
+                    let order = if rng.random_bool(0.5) {
+                        ast::SortOrder::Asc
+                    } else {
+                        ast::SortOrder::Desc
+                    };
+                    (column, order)
+                })
+                .collect(),
+        });
+    }
+    if rng.random_bool(0.3) {
+        select.offset = Some(rng.random_range(1..=3));
+    }
     Property::SelectLimit { select }
 }
 
+fn property_select_aggregate<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    _query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    _mvcc: bool,
+) -> Property {
+    assert!(!ctx.tables().is_empty());
+    let table = pick(ctx.tables(), rng);
+    // The model doesn't always compute generated columns the way the database does, so
+    // don't group or aggregate them
+    let columns = table
+        .columns
+        .iter()
+        .filter(|c| !c.is_generated())
+        .collect::<Vec<_>>();
+    let qualified = |column: &Column| format!("{}.{}", table.name, column.name);
+
+    let mut result_columns = Vec::new();
+    let mut group_by = None;
+    if !columns.is_empty() && rng.random_bool(0.6) {
+        let num_columns = rng.random_range(1..=columns.len().min(2));
+        let group_columns = pick_unique(&columns, num_columns, rng)
+            .map(|column| qualified(column))
+            .collect::<Vec<_>>();
+        result_columns.extend(group_columns.iter().cloned().map(ResultColumn::Column));
+        group_by = Some(GroupBy {
+            columns: group_columns,
+            having: None,
+        });
+    }
+
+    // SUM and AVG only add up numbers
+    let numeric_columns = columns
+        .iter()
+        .filter(|c| matches!(c.column_type, ColumnType::Integer | ColumnType::Float))
+        .collect::<Vec<_>>();
+    for _ in 0..rng.random_range(1..=3) {
+        let func = if numeric_columns.is_empty() {
+            *pick(
+                &[AggregateFunc::Count, AggregateFunc::Min, AggregateFunc::Max],
+                rng,
+            )
+        } else {
+            *pick(
+                &[
+                    AggregateFunc::Count,
+                    AggregateFunc::Sum,
+                    AggregateFunc::Avg,
+                    AggregateFunc::Min,
+                    AggregateFunc::Max,
+                ],
+                rng,
+            )
+        };
+        let aggregate = match func {
+            AggregateFunc::Count if columns.is_empty() || rng.random_bool(0.5) => {
+                Aggregate::count_star()
+            }
+            AggregateFunc::Sum | AggregateFunc::Avg => Aggregate {
+                func,
+                column: Some(qualified(pick(&numeric_columns, rng))),
+            },
+            _ if columns.is_empty() => Aggregate::count_star(),
+            _ => Aggregate {
+                func,
+                column: Some(qualified(pick(&columns, rng))),
+            },
+        };
+        result_columns.push(ResultColumn::Aggregate(aggregate));
+    }
+
+    // Only filter on the aggregates that are exact, an average that the database rounds
+    // differently could land on the other side of the value
+    if let Some(group_by) = &mut group_by
+        && rng.random_bool(0.4)
+    {
+        let column_idx = rng.random_range(0..table.columns.len());
+        let column = &table.columns[column_idx];
+        let having = if table.rows.is_empty() || column.is_generated() || rng.random_bool(0.5) {
+            Having {
+                aggregate: Aggregate::count_star(),
+                operator: *pick(&[ast::Operator::Greater, ast::Operator::LessEquals], rng),
+                value: SimValue(types::Value::from_i64(rng.random_range(1..=3))),
+            }
+        } else {
+            Having {
+                aggregate: Aggregate {
+                    func: *pick(&[AggregateFunc::Min, AggregateFunc::Max], rng),
+                    column: Some(qualified(column)),
+                },
+                operator: *pick(
+                    &[
+                        ast::Operator::Equals,
+                        ast::Operator::Greater,
+                        ast::Operator::GreaterEquals,
+                        ast::Operator::Less,
+                        ast::Operator::LessEquals,
+                    ],
+                    rng,
+                ),
+                value: pick(&table.rows, rng)[column_idx].clone(),
+            }
+        };
+        group_by.having = Some(having);
+    }
+
+    let mut select = Select::single(
+        table.name.clone(),
+        result_columns,
+        Predicate::arbitrary_from(rng, ctx, table),
+        None,
+        Distinctness::All,
+    );
+    select.body.select.group_by = group_by;
+    Property::SelectAggregate { select }
+}
+
 fn property_double_create_failure<R: rand::Rng + ?Sized>(
     rng: &mut R,
     _query_distr: &QueryDistribution,
@@ -2061,6 +3028,27 @@ fn property_select_select_optimizer<R: rand::Rng + ?Sized>(
     }
 }
 
+fn property_index_consistency<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    _query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    _mvcc: bool,
+) -> Property {
+    let tables = ctx
+        .tables()
+        .iter()
+        .filter(|t| !t.indexes.is_empty())
+        .collect::<Vec<_>>();
+    assert!(!tables.is_empty());
+    let table = *pick(&tables, rng);
+    let index = pick(&table.indexes, rng);
+    Property::IndexConsistency {
+        table: table.name.clone(),
+        index: index.index_name.clone(),
+        predicate: Predicate::arbitrary_from(rng, ctx, table),
+    }
+}
+
 fn property_where_true_false_null<R: rand::Rng + ?Sized>(
     rng: &mut R,
     _query_distr: &QueryDistribution,
@@ -2133,6 +3121,370 @@ fn property_faulty_query<R: rand::Rng + ?Sized>(
     }
 }
 
+fn property_scheduled_io_fault<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    _mvcc: bool,
+) -> Property {
+    let write_kinds = query_distr
+        .positive_items()
+        .filter(|query| {
+            matches!(
+                query,
+                QueryDiscriminants::Insert
+                    | QueryDiscriminants::Update
+                    | QueryDiscriminants::Delete
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(!write_kinds.is_empty());
+    let query = random_main_table_write(rng, ctx, &write_kinds)
+        .expect("there should be a main database table to write to");
+    Property::ScheduledIoFault {
+        fault: *pick(&IoFault::ALL, rng),
+        query,
+    }
+}
+
+fn property_power_loss<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    _mvcc: bool,
+) -> Property {
+    let write_kinds = query_distr
+        .positive_items()
+        .filter(|query| {
+            matches!(
+                query,
+                QueryDiscriminants::Insert
+                    | QueryDiscriminants::Update
+                    | QueryDiscriminants::Delete
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(!write_kinds.is_empty());
+    let query = random_main_table_write(rng, ctx, &write_kinds)
+        .expect("there should be a main database table to write to");
+    Property::PowerLoss {
+        // a commit writes and syncs the WAL, and a checkpoint then writes, syncs and
+        // truncates it, so crash points past these are rarely reached
+        crash_point: rng.random_range(0..8),
+        query,
+        checkpoint: rng.random_bool(0.5),
+    }
+}
+
+fn property_allocation_failure<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    _mvcc: bool,
+) -> Property {
+    let write_kinds = query_distr
+        .positive_items()
+        .filter(|query| {
+            matches!(
+                query,
+                QueryDiscriminants::Insert
+                    | QueryDiscriminants::Update
+                    | QueryDiscriminants::Delete
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(!write_kinds.is_empty());
+    let query = random_main_table_write(rng, ctx, &write_kinds)
+        .expect("there should be a main database table to write to");
+    Property::AllocationFailure {
+        // writes make a few dozen allocations that can fail, most of them early on
+        nth: rng.random_range(0..32),
+        query,
+    }
+}
+
+/// The most readers that an [Property::Interleaving] runs next to its writer.
+const MAX_INTERLEAVED_READERS: usize = 3;
+
+/// An interleaving of a writer on `connection_index` with readers on up to
+/// [MAX_INTERLEAVED_READERS] of the other `connections`. Unlike the other
+/// properties, it needs to know the connections, so the plan generates it with
+/// this function rather than through a [PropertyDistribution].
+pub(super) fn property_interleaving<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    connection_index: usize,
+    connections: usize,
+) -> Property {
+    use rand::seq::IndexedRandom;
+
+    let write_kinds = query_distr
+        .positive_items()
+        .filter(|query| {
+            matches!(
+                query,
+                QueryDiscriminants::Insert
+                    | QueryDiscriminants::Update
+                    | QueryDiscriminants::Delete
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(!write_kinds.is_empty());
+    let mut writer = vec![Query::Begin(Begin::Deferred)];
+    for _ in 0..rng.random_range(1..=3) {
+        writer.push(
+            random_main_table_write(rng, ctx, &write_kinds)
+                .expect("there should be a main database table to write to"),
+        );
+    }
+    writer.push(Query::Commit(Commit));
+    let mut tables = writer.iter().flat_map(Query::uses).collect::<Vec<_>>();
+    tables.sort();
+    tables.dedup();
+
+    let others = (0..connections)
+        .filter(|&connection| connection != connection_index)
+        .collect::<Vec<_>>();
+    let count = rng.random_range(1..=others.len().min(MAX_INTERLEAVED_READERS));
+    let connections = others
+        .choose_multiple(rng, count)
+        .copied()
+        .collect::<Vec<_>>();
+    let mut readers = connections
+        .into_iter()
+        .map(|connection| {
+            let in_transaction = rng.random_bool(0.5);
+            let mut queries = Vec::new();
+            if in_transaction {
+                queries.push(Query::Begin(Begin::Deferred));
+            }
+            for _ in 0..rng.random_range(1..=3) {
+                let table = pick(&tables, rng).clone();
+                queries.push(Query::Select(Select::simple(table, Predicate::true_())));
+            }
+            if in_transaction {
+                queries.push(Query::Commit(Commit));
+            }
+            Reader {
+                connection,
+                queries,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Step a random transaction that has statements left, so that the statements
+    // of each transaction keep their order
+    let mut left = std::iter::once(writer.len())
+        .chain(readers.iter().map(|reader| reader.queries.len()))
+        .collect::<Vec<_>>();
+    let mut schedule = Vec::new();
+    loop {
+        let ready = (0..left.len())
+            .filter(|&txn| left[txn] > 0)
+            .collect::<Vec<_>>();
+        let Some(&txn) = ready.choose(rng) else {
+            break;
+        };
+        left[txn] -= 1;
+        schedule.push(txn);
+    }
+
+    if rng.random_bool(0.5) {
+        // The writer holds the write lock from its first write until it commits
+        let mut writer_steps = schedule
+            .iter()
+            .enumerate()
+            .filter(|(_, txn)| **txn == 0)
+            .map(|(pos, _)| pos);
+        let first_write = writer_steps.nth(1).unwrap();
+        let commit = writer_steps.last().unwrap();
+        let mut candidates = Vec::new();
+        for pos in first_write + 1..=commit {
+            for (idx, reader) in readers.iter().enumerate() {
+                let done = schedule[..pos]
+                    .iter()
+                    .filter(|&&txn| txn == idx + 1)
+                    .count();
+                // A write between the `BEGIN` of a reader and its first read would
+                // start its snapshot, which the model only does for statements
+                // that succeed
+                if !matches!(reader.queries.first(), Some(Query::Begin(_))) || done != 1 {
+                    candidates.push((pos, idx, done));
+                }
+            }
+        }
+        if let Some(&(pos, idx, done)) = candidates.choose(rng) {
+            let write = random_main_table_write(rng, ctx, &write_kinds)
+                .expect("there should be a main database table to write to");
+            readers[idx].queries.insert(done, write);
+            schedule.insert(pos, idx + 1);
+        }
+    }
+
+    Property::Interleaving {
+        writer,
+        readers,
+        schedule,
+    }
+}
+
+/// Autocommit writes on `connection_index`, interleaved with reads on up to
+/// [MAX_INTERLEAVED_READERS] of the other `connections` and checkpoints on any of
+/// them. Like [property_interleaving], the plan generates it rather than a
+/// [PropertyDistribution], as it needs to know the connections. A crash point is only
+/// drawn with `power_loss`.
+pub(super) fn property_checkpoint_interleaving<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    connection_index: usize,
+    connections: usize,
+    power_loss: bool,
+) -> Property {
+    use rand::seq::IndexedRandom;
+
+    let write_kinds = query_distr
+        .positive_items()
+        .filter(|query| {
+            matches!(
+                query,
+                QueryDiscriminants::Insert
+                    | QueryDiscriminants::Update
+                    | QueryDiscriminants::Delete
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(!write_kinds.is_empty());
+    let write = |rng: &mut R| {
+        let query = random_main_table_write(rng, ctx, &write_kinds)
+            .expect("there should be a main database table to write to");
+        CheckpointStep {
+            connection: connection_index,
+            query,
+        }
+    };
+
+    let others = (0..connections)
+        .filter(|&connection| connection != connection_index)
+        .collect::<Vec<_>>();
+    let count = rng.random_range(1..=others.len().min(MAX_INTERLEAVED_READERS));
+    let readers = others
+        .choose_multiple(rng, count)
+        .copied()
+        .collect::<Vec<_>>();
+
+    let mut steps = vec![write(rng)];
+    let mut tables = steps[0].query.uses();
+    let mut in_transaction = Vec::new();
+    for _ in 0..rng.random_range(3..=12) {
+        match rng.random_range(0..4) {
+            0 => {
+                let step = write(rng);
+                tables.extend(step.query.uses());
+                steps.push(step);
+            }
+            1 => {
+                // A checkpoint inside a transaction would not see the other
+                // connections, so only the connections outside of one checkpoint
+                let idle = std::iter::once(connection_index)
+                    .chain(readers.iter().copied())
+                    .filter(|connection| !in_transaction.contains(connection))
+                    .collect::<Vec<_>>();
+                steps.push(CheckpointStep {
+                    connection: *pick(&idle, rng),
+                    query: Query::Pragma(Pragma::WalCheckpoint),
+                });
+            }
+            2 => {
+                let connection = *pick(&readers, rng);
+                if !in_transaction.contains(&connection) && rng.random_bool(0.5) {
+                    in_transaction.push(connection);
+                    steps.push(CheckpointStep {
+                        connection,
+                        query: Query::Begin(Begin::Deferred),
+                    });
+                }
+                let table = pick(&tables, rng).clone();
+                steps.push(CheckpointStep {
+                    connection,
+                    query: Query::Select(Select::simple(table, Predicate::true_())),
+                });
+            }
+            _ => {
+                if let Some(&connection) = in_transaction.choose(rng) {
+                    in_transaction.retain(|&reader| reader != connection);
+                    steps.push(CheckpointStep {
+                        connection,
+                        query: Query::Commit(Commit),
+                    });
+                }
+            }
+        }
+    }
+    // The crash and the final checks run once every transaction ended
+    for connection in in_transaction {
+        steps.push(CheckpointStep {
+            connection,
+            query: Query::Commit(Commit),
+        });
+    }
+    if !steps
+        .iter()
+        .any(|step| matches!(step.query, Query::Pragma(Pragma::WalCheckpoint)))
+    {
+        steps.push(CheckpointStep {
+            connection: connection_index,
+            query: Query::Pragma(Pragma::WalCheckpoint),
+        });
+    }
+
+    Property::CheckpointInterleaving {
+        steps,
+        // as for a power loss, crash points past a checkpoint of a few pages are
+        // rarely reached
+        crash_point: (power_loss && rng.random_bool(0.5)).then(|| rng.random_range(0..8)),
+    }
+}
+
+/// A transaction on `connection_index`, watched by another of the `connections`.
+/// Like [property_interleaving], the plan generates it rather than a
+/// [PropertyDistribution], as it needs to know the connections.
+pub(super) fn property_transaction_atomicity<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    query_distr: &QueryDistribution,
+    ctx: &impl GenerationContext,
+    connection_index: usize,
+    connections: usize,
+) -> Property {
+    let write_kinds = query_distr
+        .positive_items()
+        .filter(|query| {
+            matches!(
+                query,
+                QueryDiscriminants::Insert
+                    | QueryDiscriminants::Update
+                    | QueryDiscriminants::Delete
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(!write_kinds.is_empty());
+    let queries = (0..rng.random_range(1..=4))
+        .map(|_| {
+            random_main_table_write(rng, ctx, &write_kinds)
+                .expect("there should be a main database table to write to")
+        })
+        .collect();
+    let others = (0..connections)
+        .filter(|&connection| connection != connection_index)
+        .collect::<Vec<_>>();
+    Property::TransactionAtomicity {
+        queries,
+        observer: *pick(&others, rng),
+        commit: rng.random_bool(0.5),
+    }
+}
+
 fn property_sequence_monotonicity<R: rand::Rng + ?Sized>(
     rng: &mut R,
     _query_distr: &QueryDistribution,
@@ -2209,16 +3561,34 @@ impl PropertyDiscriminants {
             }
             PropertyDiscriminants::DoubleCreateFailure => property_double_create_failure,
             PropertyDiscriminants::SelectLimit => property_select_limit,
+            PropertyDiscriminants::SelectAggregate => property_select_aggregate,
             PropertyDiscriminants::DeleteSelect => property_delete_select,
             PropertyDiscriminants::DropSelect => property_drop_select,
             PropertyDiscriminants::SelectSelectOptimizer => property_select_select_optimizer,
+            PropertyDiscriminants::IndexConsistency => property_index_consistency,
             PropertyDiscriminants::WhereTrueFalseNull => property_where_true_false_null,
             PropertyDiscriminants::UnionAllPreservesCardinality => {
                 property_union_all_preserves_cardinality
             }
             PropertyDiscriminants::FsyncNoWait => property_fsync_no_wait,
             PropertyDiscriminants::FaultyQuery => property_faulty_query,
+            PropertyDiscriminants::ScheduledIoFault => property_scheduled_io_fault,
+            PropertyDiscriminants::PowerLoss => property_power_loss,
+            PropertyDiscriminants::AllocationFailure => property_allocation_failure,
             PropertyDiscriminants::SequenceMonotonicity => property_sequence_monotonicity,
+            PropertyDiscriminants::Interleaving => {
+                unreachable!("interleavings are generated by the plan, see `property_interleaving`")
+            }
+            PropertyDiscriminants::CheckpointInterleaving => {
+                unreachable!(
+                    "checkpoint interleavings are generated by the plan, see `property_checkpoint_interleaving`"
+                )
+            }
+            PropertyDiscriminants::TransactionAtomicity => {
+                unreachable!(
+                    "transaction atomicity is generated by the plan, see `property_transaction_atomicity`"
+                )
+            }
             PropertyDiscriminants::Queries => {
                 unreachable!("should not try to generate queries property")
             }
@@ -2286,6 +3656,13 @@ impl PropertyDiscriminants {
                     0
                 }
             }
+            PropertyDiscriminants::SelectAggregate => {
+                if !env.opts.disable_select_aggregate && !ctx.tables().is_empty() {
+                    remaining.select
+                } else {
+                    0
+                }
+            }
             PropertyDiscriminants::DeleteSelect => {
                 if !env.opts.disable_delete_select && !ctx.tables().is_empty() {
                     u32::min(remaining.select, remaining.insert).min(remaining.delete)
@@ -2307,6 +3684,15 @@ impl PropertyDiscriminants {
                     0
                 }
             }
+            PropertyDiscriminants::IndexConsistency => {
+                if !env.opts.disable_index_consistency
+                    && ctx.tables().iter().any(|t| !t.indexes.is_empty())
+                {
+                    remaining.select / 2
+                } else {
+                    0
+                }
+            }
             PropertyDiscriminants::WhereTrueFalseNull => {
                 if !env.opts.disable_where_true_false_null && !ctx.tables().is_empty() {
                     remaining.select / 2
@@ -2338,6 +3724,43 @@ impl PropertyDiscriminants {
                     0
                 }
             }
+            PropertyDiscriminants::ScheduledIoFault => {
+                if env.profile.io.enable
+                    && env.profile.io.fault.enable
+                    && !env.opts.disable_scheduled_io_fault
+                    && !env.profile.mvcc
+                    && ctx.tables().iter().any(|table| !table.name.contains('.'))
+                    && remaining.insert + remaining.update + remaining.delete > 0
+                {
+                    10
+                } else {
+                    0
+                }
+            }
+            PropertyDiscriminants::PowerLoss => {
+                if env.opts.power_loss
+                    && !matches!(env.type_, SimulationType::Differential)
+                    && !env.profile.mvcc
+                    && ctx.tables().iter().any(|table| !table.name.contains('.'))
+                    && remaining.insert + remaining.update + remaining.delete > 0
+                {
+                    20
+                } else {
+                    0
+                }
+            }
+            PropertyDiscriminants::AllocationFailure => {
+                if env.opts.allocation_faults
+                    && !matches!(env.type_, SimulationType::Differential)
+                    && !env.profile.mvcc
+                    && ctx.tables().iter().any(|table| !table.name.contains('.'))
+                    && remaining.insert + remaining.update + remaining.delete > 0
+                {
+                    20
+                } else {
+                    0
+                }
+            }
             PropertyDiscriminants::SequenceMonotonicity => {
                 if !env.profile.mvcc && remaining.create_sequence > 0 {
                     5
@@ -2345,6 +3768,10 @@ impl PropertyDiscriminants {
                     0
                 }
             }
+            // Generated by the plan, which knows the other connections
+            PropertyDiscriminants::Interleaving
+            | PropertyDiscriminants::CheckpointInterleaving
+            | PropertyDiscriminants::TransactionAtomicity => 0,
             PropertyDiscriminants::Queries => {
                 unreachable!("queries property should not be generated")
             }
@@ -2375,6 +3802,7 @@ impl PropertyDiscriminants {
             PropertyDiscriminants::AllTableHaveExpectedContent => QueryCapabilities::SELECT,
             PropertyDiscriminants::DoubleCreateFailure => QueryCapabilities::CREATE,
             PropertyDiscriminants::SelectLimit => QueryCapabilities::SELECT,
+            PropertyDiscriminants::SelectAggregate => QueryCapabilities::SELECT,
             PropertyDiscriminants::DeleteSelect => {
                 QueryCapabilities::SELECT.union(QueryCapabilities::DELETE)
             }
@@ -2382,10 +3810,19 @@ impl PropertyDiscriminants {
                 QueryCapabilities::SELECT.union(QueryCapabilities::DROP)
             }
             PropertyDiscriminants::SelectSelectOptimizer => QueryCapabilities::SELECT,
+            PropertyDiscriminants::IndexConsistency => QueryCapabilities::SELECT,
             PropertyDiscriminants::WhereTrueFalseNull => QueryCapabilities::SELECT,
             PropertyDiscriminants::UnionAllPreservesCardinality => QueryCapabilities::SELECT,
             PropertyDiscriminants::FsyncNoWait => QueryCapabilities::all(),
             PropertyDiscriminants::FaultyQuery => QueryCapabilities::all(),
+            PropertyDiscriminants::ScheduledIoFault => QueryCapabilities::INSERT,
+            PropertyDiscriminants::PowerLoss => QueryCapabilities::INSERT,
+            PropertyDiscriminants::AllocationFailure => QueryCapabilities::INSERT,
+            PropertyDiscriminants::Interleaving
+            | PropertyDiscriminants::CheckpointInterleaving
+            | PropertyDiscriminants::TransactionAtomicity => {
+                QueryCapabilities::SELECT.union(QueryCapabilities::INSERT)
+            }
             PropertyDiscriminants::SequenceMonotonicity => QueryCapabilities::SEQUENCE,
             PropertyDiscriminants::Queries => panic!("queries property should not be generated"),
         }
@@ -2407,11 +3844,13 @@ impl<'a> PropertyDistribution<'a> {
         ctx: &impl GenerationContext,
     ) -> Result<Self, rand::distr::weighted::Error> {
         let properties = PropertyDiscriminants::can_generate(query_distr.items());
-        let weights = WeightedIndex::new(
-            properties
-                .iter()
-                .map(|property| property.weight(env, remaining, ctx)),
-        )?;
+        let weights = WeightedIndex::new(properties.iter().map(|property| {
+            let boost = env
+                .coverage
+                .as_ref()
+                .map_or(1, |coverage| coverage.boost(*property));
+            property.weight(env, remaining, ctx).saturating_mul(boost)
+        }))?;
 
         Ok(Self {
             properties,
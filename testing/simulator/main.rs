@@ -3,21 +3,28 @@ use anyhow::anyhow;
 use clap::Parser;
 use rand::prelude::*;
 use runner::bugbase::BugBase;
+use runner::bundle;
 use runner::cli::{SimulatorCLI, SimulatorCommand};
 use runner::differential;
 use runner::env::SimulatorEnv;
 use runner::execution::{Execution, ExecutionHistory, ExecutionResult, execute_interactions};
+use runner::parallel::{self, Job};
+use runner::replay;
+use runner::soak::{self, SoakOptions};
 use std::any::Any;
 use std::backtrace::Backtrace;
 use std::fs::OpenOptions;
 use std::io::{IsTerminal, Write};
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::field::MakeExt;
 use tracing_subscriber::fmt::format;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use turso_core::VerifyLevel;
 
 use crate::model::interactions::{
     ConnectionState, InteractionPlan, InteractionPlanIterator, InteractionPlanState,
@@ -37,6 +44,9 @@ fn main() -> anyhow::Result<()> {
     init_logger()?;
     let mut cli_opts = SimulatorCLI::parse();
     cli_opts.validate()?;
+    if cli_opts.allocation_faults {
+        runner::allocation_fault::install()?;
+    }
 
     let profile = Profile::parse_from_type(cli_opts.profile.clone())?;
     tracing::debug!(sim_profile = ?profile);
@@ -108,6 +118,63 @@ fn main() -> anyhow::Result<()> {
                 println!("\t{} failed runs", failures.len());
                 Ok(())
             }
+            SimulatorCommand::Soak {
+                duration,
+                check_every,
+                memory_ceiling_mb,
+                history_window,
+                quick_check,
+            } => {
+                banner();
+                let soak_opts = SoakOptions {
+                    duration: Duration::from_secs(duration),
+                    check_every,
+                    memory_ceiling_bytes: memory_ceiling_mb.map(|mb| mb << 20),
+                    history_window,
+                    verify_level: if quick_check {
+                        VerifyLevel::Quick
+                    } else {
+                        VerifyLevel::Full
+                    },
+                };
+                // A soak run only keeps the tail of its plan, so it can't be added to the bug base.
+                let (seed, env, mut plan) = setup_simulation(None, &mut cli_opts, &profile);
+                let paths = env.paths.clone();
+                let result = soak::run(env, &mut plan, &soak_opts);
+                println!("seed: {seed}");
+                println!("path: {}", paths.base.display());
+                if result.is_err() && !plan.is_compacted() && !cli_opts.disable_bugbase {
+                    // The whole plan is still in memory, so it can go through the shrinking
+                    // and the bug base of a regular run
+                    tracing::info!("running the plan of the soak again to shrink it");
+                    let mut bugbase = BugBase::load()?;
+                    let (_, mut env, _) =
+                        setup_simulation(Some(&mut bugbase), &mut cli_opts, &profile);
+                    // only run the plan of the soak, without generating more
+                    env.opts.max_interactions = 0;
+                    if run_simulator(Some(&mut bugbase), &cli_opts, env, plan).is_ok() {
+                        tracing::warn!("the plan of the soak did not reproduce its failure");
+                    } else {
+                        write_bundle(&bugbase.paths(seed), seed);
+                    }
+                }
+                if !cli_opts.keep_files && result.is_ok() {
+                    paths.delete_all_files();
+                }
+                result
+            }
+            SimulatorCommand::Replay { target } => {
+                banner();
+                replay_main(&target)
+            }
+            SimulatorCommand::Bundle { seed } => {
+                banner();
+                bundle_main(seed)
+            }
+            SimulatorCommand::Parallel { seeds, jobs } => {
+                banner();
+                parallel_main(&cli_opts, &profile, seeds, jobs)
+            }
             SimulatorCommand::PrintSchema => {
                 let schema = schemars::schema_for!(crate::Profile);
                 println!("{}", serde_json::to_string_pretty(&schema).unwrap());
@@ -143,6 +210,9 @@ fn testing_main(cli_opts: &mut SimulatorCLI, profile: &Profile) -> anyhow::Resul
     }
 
     let result = run_simulator(bugbase.as_mut(), cli_opts, env, plans);
+    if result.is_err() && bugbase.is_some() {
+        write_bundle(&paths, seed);
+    }
 
     // Print the seed, the locations of the database and the plan file at the end again for easily accessing them.
     println!("seed: {seed}");
@@ -155,6 +225,259 @@ fn testing_main(cli_opts: &mut SimulatorCLI, profile: &Profile) -> anyhow::Resul
     result
 }
 
+/// Replay the bug `target`, given by its seed or the path to its plan in the bug base,
+/// and print the first step at which the replay diverges from the recorded history.
+fn replay_main(target: &str) -> anyhow::Result<()> {
+    let mut bugbase = BugBase::load()?;
+    let seed = match target.parse::<u64>() {
+        Ok(seed) => seed,
+        Err(_) => BugBase::seed_of_plan(target)?,
+    };
+    let history_path = bugbase.paths(seed).history;
+    let bug = bugbase
+        .get_or_load_bug(seed)?
+        .ok_or_else(|| anyhow!("bug '{seed}' not found in bug base"))?;
+    if bug.runs.is_empty() {
+        anyhow::bail!("bug '{seed}' has no recorded runs to replay");
+    }
+
+    // run with the same CLI options as the bug, so that the plan, the IO schedule and
+    // the clock are all generated the same way from its seed
+    let mut cli_opts = bug.last_cli_opts();
+    cli_opts.seed = Some(seed);
+    cli_opts.load = None;
+    let profile = Profile::parse_from_type(cli_opts.profile.clone())?;
+    if cli_opts.allocation_faults {
+        runner::allocation_fault::install()?;
+    }
+    let recorded = if history_path.exists() {
+        Some(replay::read_history(&history_path)?)
+    } else {
+        None
+    };
+
+    // without a bug base the replay writes to `simulator-output`, keeping the bug's files
+    let (seed, mut env, plan) = setup_simulation(None, &mut cli_opts, &profile);
+    if cli_opts.differential {
+        env.type_ = SimulationType::Differential;
+    } else if cli_opts.doublecheck {
+        env.type_ = SimulationType::Doublecheck;
+    }
+    let paths = env.paths.clone();
+    let plan_path = env.get_plan_path();
+    let plan_json_path = env.get_plan_json_path();
+
+    let last_execution = Arc::new(Mutex::new(Execution::new(0, 0)));
+    let mut gen_rng = env.gen_rng();
+    let env = Arc::new(Mutex::new(env));
+    let plan = Rc::new(Mutex::new(plan));
+    let result = {
+        let sim_execution = last_execution.clone();
+        let sim_plan = plan.clone();
+        std::panic::catch_unwind(move || {
+            let mut sim_plan = sim_plan.lock().unwrap();
+            let plan = sim_plan.generator(&mut gen_rng);
+            run_simulation(env, plan, sim_execution)
+        })
+    };
+    plan.clear_poison();
+    last_execution.clear_poison();
+    let plan = plan.lock().unwrap();
+    std::fs::write(&plan_path, plan.to_string())?;
+    std::fs::write(&plan_json_path, plan.to_json()?)?;
+
+    println!("seed: {seed}");
+    println!("path: {}", paths.base.display());
+    let ExecutionResult { history, error } = match result {
+        Ok(result) => result,
+        Err(_) => {
+            // a panicking run takes its history with it, so only its last step is known
+            let last_execution = *last_execution.lock().unwrap();
+            let interaction = &plan.interactions_list()[last_execution.interaction_index];
+            println!(
+                "replay panicked at interaction {} on connection {}: {interaction}",
+                last_execution.interaction_index, last_execution.connection_index
+            );
+            if let Some(recorded) = &recorded {
+                println!(
+                    "the recorded run did not panic, it took {} steps",
+                    recorded.len()
+                );
+            }
+            return Err(anyhow!("replay of bug '{seed}' panicked"));
+        }
+    };
+    replay::write_history(&paths.history, &history)?;
+    match &error {
+        Some(error) => println!("replay failed with error: '{error:?}'"),
+        None => println!("replay succeeded"),
+    }
+
+    let Some(recorded) = recorded else {
+        println!(
+            "no history was recorded at {}, there is nothing to compare the replay with",
+            history_path.display()
+        );
+        return Ok(());
+    };
+    match replay::divergence(&recorded, &history.history) {
+        Some(divergence) => {
+            println!("{divergence}");
+            if let Some(execution) = divergence.replayed {
+                let interaction = &plan.interactions_list()[execution.interaction_index];
+                println!("replayed interaction: {interaction}");
+            }
+            Err(anyhow!(
+                "replay of bug '{seed}' diverged from its history at step {}",
+                divergence.step
+            ))
+        }
+        None => {
+            println!("replay followed all {} recorded steps", recorded.len());
+            Ok(())
+        }
+    }
+}
+
+/// Run the bug with `seed` again, so that its files are those of the current version
+/// of Turso, and bundle them.
+fn bundle_main(seed: u64) -> anyhow::Result<()> {
+    let mut bugbase = BugBase::load()?;
+    let bug = bugbase
+        .get_or_load_bug(seed)?
+        .ok_or_else(|| anyhow!("bug '{seed}' not found in bug base"))?;
+    if bug.runs.is_empty() {
+        anyhow::bail!("bug '{seed}' has no recorded runs to bundle");
+    }
+
+    let mut cli_opts = bug.last_cli_opts();
+    cli_opts.seed = Some(seed);
+    cli_opts.load = None;
+    let profile = Profile::parse_from_type(cli_opts.profile.clone())?;
+    if cli_opts.allocation_faults {
+        runner::allocation_fault::install()?;
+    }
+    let (seed, mut env, plan) = setup_simulation(Some(&mut bugbase), &mut cli_opts, &profile);
+    if cli_opts.differential {
+        env.type_ = SimulationType::Differential;
+    } else if cli_opts.doublecheck {
+        env.type_ = SimulationType::Doublecheck;
+    }
+    if run_simulator(Some(&mut bugbase), &cli_opts, env, plan).is_ok() {
+        tracing::warn!("bug '{seed}' did not reproduce, bundling the files of its passing run");
+    }
+
+    let bundle = bundle::write_bundle(&bugbase.paths(seed), seed)?;
+    println!("seed: {seed}");
+    println!("bundle: {}", bundle.display());
+    Ok(())
+}
+
+/// Run `seeds` seeds on a pool of `jobs` workers, see [parallel].
+fn parallel_main(
+    cli_opts: &SimulatorCLI,
+    profile: &Profile,
+    seeds: u64,
+    jobs: Option<NonZeroUsize>,
+) -> anyhow::Result<()> {
+    let jobs =
+        jobs.unwrap_or_else(|| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN));
+    let first_seed = cli_opts.seed.unwrap_or_else(|| rand::rng().next_u64());
+    let output = std::env::current_dir()?.join("simulator-output");
+    println!("running {seeds} seeds from {first_seed} on {jobs} workers");
+
+    let failures = parallel::run_pool(
+        (0..seeds).map(|i| first_seed.wrapping_add(i)),
+        jobs,
+        |job| {
+            let mut cli_opts = cli_opts.clone();
+            cli_opts.load = None;
+            match job {
+                Job::Explore { seed } => {
+                    cli_opts.seed = Some(seed);
+                    cli_opts.disable_heuristic_shrinking = true;
+                    cli_opts.enable_brute_force_shrinking = false;
+                    run_seed(None, &cli_opts, profile, &output)
+                }
+                Job::Shrink { seed } => {
+                    cli_opts.seed = Some(seed);
+                    let mut bugbase = if cli_opts.disable_bugbase {
+                        None
+                    } else {
+                        Some(BugBase::load()?)
+                    };
+                    let result = run_seed(bugbase.as_mut(), &cli_opts, profile, &output);
+                    if result.is_err()
+                        && let Some(bugbase) = &bugbase
+                    {
+                        write_bundle(&bugbase.paths(seed), seed);
+                    }
+                    result
+                }
+            }
+        },
+    );
+
+    println!("{} of {seeds} seeds failed", failures.len());
+    for failure in &failures {
+        let shrunk = match failure.reproduced {
+            Some(true) => "shrunk",
+            Some(false) => "did not fail again while shrinking",
+            None => "not shrunk",
+        };
+        println!("seed {}, {shrunk}: {}", failure.seed, failure.error);
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} of {seeds} seeds failed", failures.len()))
+    }
+}
+
+/// Run the seed of `cli_opts` on a worker of a parallel run. Without a bug base, its
+/// files go to a directory of its own in `output`.
+fn run_seed(
+    bugbase: Option<&mut BugBase>,
+    cli_opts: &SimulatorCLI,
+    profile: &Profile,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let seed = cli_opts.seed.expect("a parallel run should set the seed");
+    let paths = match &bugbase {
+        Some(bugbase) => bugbase.paths(seed),
+        None => Paths::new(&output.join(seed.to_string())),
+    };
+    std::fs::create_dir_all(&paths.base)?;
+
+    let mut env = SimulatorEnv::new(
+        seed,
+        cli_opts,
+        paths.clone(),
+        SimulationType::Default,
+        profile,
+    );
+    if cli_opts.differential {
+        env.type_ = SimulationType::Differential;
+    } else if cli_opts.doublecheck {
+        env.type_ = SimulationType::Doublecheck;
+    }
+    let plan = InteractionPlan::new(env.profile.mvcc);
+
+    let result = run_simulator(bugbase, cli_opts, env, plan);
+    if !cli_opts.keep_files && result.is_ok() {
+        paths.delete_all_files();
+    }
+    result
+}
+
+/// Bundle the files of a failed run in the bug base, see [bundle].
+fn write_bundle(paths: &Paths, seed: u64) {
+    match bundle::write_bundle(paths, seed) {
+        Ok(bundle) => println!("bundle: {}", bundle.display()),
+        Err(err) => tracing::error!("failed to write the bundle of bug '{seed}': {err:?}"),
+    }
+}
+
 fn run_simulator(
     mut bugbase: Option<&mut BugBase>,
     cli_opts: &SimulatorCLI,
@@ -205,6 +528,7 @@ fn run_simulator(
 
     tracing::info!("{}", plan.stats());
     std::fs::write(env.get_plan_path(), plan.to_string()).unwrap();
+    std::fs::write(env.get_plan_json_path(), plan.to_json()?).unwrap();
 
     // No doublecheck, run shrinking if panicking or found a bug.
     match &result {
@@ -224,16 +548,7 @@ fn run_simulator(
         } => {
             if let SandboxedResult::FoundBug { history, .. } = &result {
                 // No panic occurred, so write the history to a file
-                let f = std::fs::File::create(&env.paths.history).unwrap();
-                let mut f = std::io::BufWriter::new(f);
-                for execution in history.history.iter() {
-                    writeln!(
-                        f,
-                        "{} {}",
-                        execution.connection_index, execution.interaction_index,
-                    )
-                    .unwrap();
-                }
+                replay::write_history(&env.paths.history, history).unwrap();
             }
 
             tracing::error!("simulation failed: '{}'", error);
@@ -259,6 +574,10 @@ fn run_simulator(
                 let mut f = std::fs::File::create(&shrunk_plan_path).unwrap();
                 tracing::trace!("writing shrunk plan to {}", shrunk_plan_path.display());
                 f.write_all(shrunk_plan.to_string().as_bytes()).unwrap();
+                let shrunk_plan_json_path = env
+                    .paths
+                    .plan_json(&SimulationType::Default, &SimulationPhase::Shrink);
+                std::fs::write(&shrunk_plan_json_path, shrunk_plan.to_json()?).unwrap();
 
                 let last_execution = Arc::new(Mutex::new(*last_execution));
                 let env = env.clone_at_phase(SimulationPhase::Shrink);
@@ -310,8 +629,11 @@ fn run_simulator(
                         let env = Arc::new(Mutex::new(env));
 
                         let final_plan = if cli_opts.enable_brute_force_shrinking {
-                            let brute_shrunk_plan =
-                                shrunk_plan.brute_shrink_interaction_plan(&shrunk, env);
+                            let brute_shrunk_plan = shrunk_plan.brute_shrink_interaction_plan(
+                                &shrunk,
+                                env,
+                                cli_opts.shrink_budget,
+                            );
                             tracing::info!("Brute force shrinking completed");
                             brute_shrunk_plan
                         } else {
@@ -357,7 +679,6 @@ enum SandboxedResult {
         error: String,
         last_execution: Execution,
     },
-    #[expect(dead_code)]
     FoundBug {
         error: String,
         history: ExecutionHistory,
@@ -373,11 +694,15 @@ impl SandboxedResult {
     ) -> Self {
         match result {
             Ok(ExecutionResult { error: None, .. }) => SandboxedResult::Correct,
-            Ok(ExecutionResult { error: Some(e), .. }) => {
+            Ok(ExecutionResult {
+                error: Some(e),
+                history,
+            }) => {
                 let error = format!("{e:?}");
                 let last_execution = last_execution.lock().unwrap();
-                SandboxedResult::Panicked {
+                SandboxedResult::FoundBug {
                     error,
+                    history,
                     last_execution: *last_execution,
                 }
             }
@@ -524,6 +849,15 @@ fn run_simulation_default(
     env.io.print_stats();
 
     tracing::info!("Simulation completed");
+    if let Some(coverage) = &env.coverage {
+        tracing::info!("covered {} opcode edges", coverage.covered());
+    }
+    if env.opts.allocation_faults {
+        tracing::info!(
+            "failed {} allocations so far",
+            runner::allocation_fault::injected_faults()
+        );
+    }
 
     env.io.persist_files().unwrap();
 
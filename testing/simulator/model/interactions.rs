@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     fmt::{Debug, Display},
     marker::PhantomData,
     num::NonZeroUsize,
@@ -22,7 +23,10 @@ use crate::{
         metrics::InteractionStats,
         property::{Property, PropertyDiscriminants},
     },
-    runner::env::{ShadowTablesMut, SimConnection, SimulationType, SimulatorEnv},
+    runner::{
+        allocation_fault,
+        env::{ShadowTablesMut, SimConnection, SimulationType, SimulatorEnv},
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -32,12 +36,17 @@ pub(crate) struct InteractionPlan {
     // In the future, this should probably be a stack of interactions
     // so we can have nested properties
     last_interactions: Option<Interactions>,
+    /// The [Interactions] that the interactions with each id were generated from. Assertions
+    /// can't be serialized, so plans are saved with these to generate them again.
+    sources: BTreeMap<NonZeroUsize, Interactions>,
     pub mvcc: bool,
 
     /// Counts [Interactions]. Should not count transactions statements, just so we can generate more meaningful interactions per run
     /// This field is only necessary and valid when generating interactions. For static iteration, we do not care about this field
     len_properties: usize,
     next_interaction_id: NonZeroUsize,
+    /// Whether [InteractionPlan::compact] dropped interactions from the start of the plan
+    compacted: bool,
 }
 
 impl InteractionPlan {
@@ -46,9 +55,11 @@ impl InteractionPlan {
             plan: Vec::new(),
             stats: InteractionStats::default(),
             last_interactions: None,
+            sources: BTreeMap::new(),
             mvcc,
             len_properties: 0,
             next_interaction_id: NonZeroUsize::new(1).unwrap(),
+            compacted: false,
         }
     }
 
@@ -77,10 +88,25 @@ impl InteractionPlan {
         self.last_interactions.as_ref()
     }
 
-    pub fn push_interactions(&mut self, interactions: Interactions) {
+    /// The [Interactions] that the interactions with `id` were generated from.
+    pub fn source(&self, id: NonZeroUsize) -> Option<&Interactions> {
+        self.sources.get(&id)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = (NonZeroUsize, &Interactions)> {
+        self.sources
+            .iter()
+            .map(|(id, interactions)| (*id, interactions))
+    }
+
+    pub fn push_interactions(&mut self, id: NonZeroUsize, interactions: Interactions) {
         if !interactions.ignore() {
             self.len_properties += 1;
         }
+        if id >= self.next_interaction_id {
+            self.next_interaction_id = id.checked_add(1).unwrap();
+        }
+        self.sources.insert(id, interactions.clone());
         self.last_interactions = Some(interactions);
     }
 
@@ -138,11 +164,37 @@ impl InteractionPlan {
         self.plan.truncate(len);
     }
 
+    /// Drops the oldest interactions so that roughly `keep` remain, and returns them.
+    /// Never splits a property, so the retained plan still starts at a property boundary.
+    pub fn compact(&mut self, keep: usize) -> Vec<Interaction> {
+        let Some(mut split) = self.plan.len().checked_sub(keep) else {
+            return Vec::new();
+        };
+        while split > 0
+            && split < self.plan.len()
+            && self.plan[split - 1].id() == self.plan[split].id()
+        {
+            split -= 1;
+        }
+        if let Some(first) = self.plan.get(split) {
+            self.sources = self.sources.split_off(&first.id());
+        }
+        self.compacted |= split > 0;
+        self.plan.drain(..split).collect()
+    }
+
+    /// Whether interactions were dropped from the start of the plan, so that it can't be
+    /// run again from an empty database.
+    pub fn is_compacted(&self) -> bool {
+        self.compacted
+    }
+
     /// Used to remove a particular [Interactions]
     pub fn remove_property(&mut self, id: NonZeroUsize) {
         let range = self.find_interactions_range(id);
         // Consume the drain iterator just to be sure
         for _interaction in self.plan.drain(range) {}
+        self.sources.remove(&id);
     }
 
     pub fn retain_mut<F>(&mut self, f: F)
@@ -157,6 +209,11 @@ impl InteractionPlan {
         &self.plan
     }
 
+    #[inline]
+    pub fn interactions_list_mut(&mut self) -> &mut [Interaction] {
+        &mut self.plan
+    }
+
     pub fn iter_properties(
         &self,
     ) -> IterProperty<
@@ -436,6 +493,17 @@ impl Assertion {
 pub enum Fault {
     Disconnect,
     ReopenDatabase,
+    /// Schedule an IO fault on the database file
+    Io(IoFault),
+    /// Cut the power once `crash_point` more writes and syncs of the database files
+    /// went through
+    PowerLoss {
+        crash_point: u32,
+    },
+    /// Fail the allocation that comes after `nth` more allocations that can fail
+    AllocationFailure {
+        nth: u32,
+    },
 }
 
 impl Display for Fault {
@@ -443,6 +511,39 @@ impl Display for Fault {
         match self {
             Fault::Disconnect => write!(f, "DISCONNECT"),
             Fault::ReopenDatabase => write!(f, "REOPEN_DATABASE"),
+            Fault::Io(fault) => write!(f, "IO {fault}"),
+            Fault::PowerLoss { crash_point } => write!(f, "POWER_LOSS AFTER {crash_point}"),
+            Fault::AllocationFailure { nth } => write!(f, "ALLOCATION_FAILURE AFTER {nth}"),
+        }
+    }
+}
+
+/// A fault of a single IO operation, which fires on the next operation it applies to
+/// once scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IoFault {
+    /// The write fails without writing anything
+    FailedWrite,
+    /// The first half of a page reaches the file, then the write fails
+    PartialWrite,
+    /// The fsync fails
+    SyncError,
+}
+
+impl IoFault {
+    pub const ALL: [IoFault; 3] = [
+        IoFault::FailedWrite,
+        IoFault::PartialWrite,
+        IoFault::SyncError,
+    ];
+}
+
+impl Display for IoFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoFault::FailedWrite => write!(f, "FAILED_WRITE"),
+            IoFault::PartialWrite => write!(f, "PARTIAL_WRITE"),
+            IoFault::SyncError => write!(f, "SYNC_ERROR"),
         }
     }
 }
@@ -499,6 +600,11 @@ impl InteractionBuilder {
     pub fn has_property_meta(&self) -> bool {
         self.property_meta.is_some()
     }
+
+    /// Checks to see if the connection was already set
+    pub fn has_connection_index(&self) -> bool {
+        self.connection_index.is_some()
+    }
 }
 
 impl Deref for Interaction {
@@ -708,6 +814,17 @@ impl InteractionType {
                     Fault::ReopenDatabase => {
                         reopen_database(env);
                     }
+                    Fault::Io(fault) => {
+                        let path = env.get_db_path();
+                        env.io.schedule_fault(path.to_str().unwrap(), *fault);
+                    }
+                    Fault::PowerLoss { crash_point } => {
+                        let path = env.get_db_path();
+                        env.io.schedule_crash(path.to_str().unwrap(), *crash_point);
+                    }
+                    Fault::AllocationFailure { nth } => {
+                        allocation_fault::schedule(*nth);
+                    }
                 }
                 Ok(())
             }
@@ -884,7 +1001,7 @@ impl InteractionType {
     }
 }
 
-fn reopen_database(env: &mut SimulatorEnv) {
+pub(crate) fn reopen_database(env: &mut SimulatorEnv) {
     // 1. Close all connections without default checkpoint-on-close behavior
     // to expose bugs related to how we handle WAL
     let mvcc = env.profile.mvcc;
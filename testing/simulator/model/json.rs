@@ -0,0 +1,270 @@
+//! The versioned JSON form of an [InteractionPlan], saved next to its `.sql` form so that
+//! external tools can read plans and hand them back to the simulator.
+//!
+//! Assertions and assumptions are closures, so only their names are saved. The plan also
+//! saves the [Interactions] that every id was generated from, and loading it generates
+//! those again to pick each assertion by its name.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::num::NonZeroUsize;
+
+use anyhow::{Context, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::model::{
+    Query,
+    interactions::{
+        Fault, Interaction, InteractionBuilder, InteractionPlan, InteractionType, Interactions,
+        PropertyMetadata,
+    },
+    property::PropertyDiscriminants,
+};
+
+/// Bumped whenever the format changes in a way that older plans can't be read with.
+pub(crate) const PLAN_JSON_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct PlanJson {
+    version: u32,
+    mvcc: bool,
+    sources: Vec<Source>,
+    steps: Vec<Step>,
+    /// The faults of the plan, with the step they run at. Only saved for tools, loading a
+    /// plan reads the faults from its steps.
+    faults: Vec<ScheduledFault>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Source {
+    id: NonZeroUsize,
+    interactions: Interactions,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Step {
+    id: NonZeroUsize,
+    connection_index: usize,
+    ignore_error: bool,
+    property: Option<StepProperty>,
+    interaction: StepInteraction,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StepProperty {
+    name: String,
+    extension: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StepInteraction {
+    Query(Query),
+    Assumption { name: String },
+    Assertion { name: String },
+    Fault(Fault),
+    FsyncQuery(Query),
+    FaultyQuery(Query),
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScheduledFault {
+    step: usize,
+    connection_index: usize,
+    fault: Fault,
+}
+
+impl From<&Interaction> for Step {
+    fn from(interaction: &Interaction) -> Self {
+        let step_interaction = match &interaction.interaction {
+            InteractionType::Query(query) => StepInteraction::Query(query.clone()),
+            InteractionType::Assumption(assumption) => StepInteraction::Assumption {
+                name: assumption.name.clone(),
+            },
+            InteractionType::Assertion(assertion) => StepInteraction::Assertion {
+                name: assertion.name.clone(),
+            },
+            InteractionType::Fault(fault) => StepInteraction::Fault(*fault),
+            InteractionType::FsyncQuery(query) => StepInteraction::FsyncQuery(query.clone()),
+            InteractionType::FaultyQuery(query) => StepInteraction::FaultyQuery(query.clone()),
+        };
+        Step {
+            id: interaction.id(),
+            connection_index: interaction.connection_index,
+            ignore_error: interaction.ignore_error,
+            property: interaction.property_meta.map(|meta| StepProperty {
+                name: meta.property.name().to_string(),
+                extension: meta.extension,
+            }),
+            interaction: step_interaction,
+        }
+    }
+}
+
+impl InteractionPlan {
+    /// The plan as versioned JSON, with the sources of the interactions that are left.
+    pub(crate) fn to_json(&self) -> anyhow::Result<String> {
+        let interactions = self.interactions_list();
+        let mut sources = Vec::new();
+        for interaction in interactions {
+            let id = interaction.id();
+            if sources
+                .last()
+                .is_some_and(|source: &Source| source.id == id)
+            {
+                continue;
+            }
+            if let Some(interactions) = self.source(id) {
+                sources.push(Source {
+                    id,
+                    interactions: interactions.clone(),
+                });
+            }
+        }
+        let faults = interactions
+            .iter()
+            .enumerate()
+            .filter_map(|(step, interaction)| match &interaction.interaction {
+                InteractionType::Fault(fault) => Some(ScheduledFault {
+                    step,
+                    connection_index: interaction.connection_index,
+                    fault: *fault,
+                }),
+                _ => None,
+            })
+            .collect();
+        let plan = PlanJson {
+            version: PLAN_JSON_VERSION,
+            mvcc: self.mvcc,
+            sources,
+            steps: interactions.iter().map(Step::from).collect(),
+            faults,
+        };
+        serde_json::to_string_pretty(&plan).context("should be able to serialize the plan")
+    }
+
+    /// Load a plan saved with [InteractionPlan::to_json].
+    pub(crate) fn from_json(json: &str) -> anyhow::Result<Self> {
+        let saved: PlanJson = serde_json::from_str(json).context("failed to parse plan")?;
+        if saved.version != PLAN_JSON_VERSION {
+            bail!(
+                "plan has version {}, but only version {PLAN_JSON_VERSION} can be loaded",
+                saved.version
+            );
+        }
+
+        let mut plan = InteractionPlan::new(saved.mvcc);
+        for source in saved.sources {
+            plan.push_interactions(source.id, source.interactions);
+        }
+        // Assertions are taken in order from the interactions generated for their id
+        let mut generated: HashMap<NonZeroUsize, Vec<Interaction>> = HashMap::new();
+        for (idx, step) in saved.steps.into_iter().enumerate() {
+            let interaction = match step.interaction {
+                StepInteraction::Query(query) => InteractionType::Query(query),
+                StepInteraction::Fault(fault) => InteractionType::Fault(fault),
+                StepInteraction::FsyncQuery(query) => InteractionType::FsyncQuery(query),
+                StepInteraction::FaultyQuery(query) => InteractionType::FaultyQuery(query),
+                StepInteraction::Assumption { ref name }
+                | StepInteraction::Assertion { ref name } => {
+                    let assumption = matches!(step.interaction, StepInteraction::Assumption { .. });
+                    let generated = match generated.entry(step.id) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let source = plan.source(step.id).with_context(|| {
+                                format!("step {idx} has no source to generate its assertion from")
+                            })?;
+                            entry.insert(source.interactions(step.id))
+                        }
+                    };
+                    let position = generated
+                        .iter()
+                        .position(|interaction| match &interaction.interaction {
+                            InteractionType::Assumption(assertion) => {
+                                assumption && assertion.name == *name
+                            }
+                            InteractionType::Assertion(assertion) => {
+                                !assumption && assertion.name == *name
+                            }
+                            _ => false,
+                        })
+                        .with_context(|| {
+                            format!("step {idx} has no assertion named '{name}' in its source")
+                        })?;
+                    generated.remove(position).interaction
+                }
+            };
+
+            let mut builder = InteractionBuilder::with_interaction(interaction);
+            builder
+                .connection_index(step.connection_index)
+                .id(step.id)
+                .ignore_error(step.ignore_error);
+            if let Some(property) = step.property {
+                let discriminant = PropertyDiscriminants::iter()
+                    .find(|discriminant| discriminant.name() == property.name)
+                    .with_context(|| {
+                        format!("step {idx} has unknown property '{}'", property.name)
+                    })?;
+                builder.property_meta(PropertyMetadata {
+                    property: discriminant,
+                    extension: property.extension,
+                });
+            }
+            let interaction = builder.build().map_err(|e| anyhow!("step {idx}: {e}"))?;
+            if let Some(property_meta) = interaction.property_meta
+                && !property_meta.property.check_tables()
+            {
+                plan.stats_mut().update(&interaction);
+            }
+            plan.push(interaction);
+        }
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_generation::model::query::transaction::{Begin, Commit};
+
+    use crate::model::{interactions::InteractionsType, property::Property};
+
+    use super::*;
+
+    #[test]
+    fn plans_round_trip_through_json() {
+        let mut plan = InteractionPlan::new(false);
+        let sources = [
+            Interactions::new(0, InteractionsType::Query(Query::Begin(Begin::Deferred))),
+            Interactions::new(
+                1,
+                InteractionsType::Property(Property::AllTableHaveExpectedContent {
+                    tables: vec!["t".to_string()],
+                }),
+            ),
+            Interactions::new(0, InteractionsType::Fault(Fault::Disconnect)),
+            Interactions::new(0, InteractionsType::Query(Query::Commit(Commit))),
+        ];
+        for interactions in sources {
+            let id = plan.next_property_id();
+            for interaction in interactions.interactions(id) {
+                plan.push(interaction);
+            }
+            plan.push_interactions(id, interactions);
+        }
+
+        let json = plan.to_json().unwrap();
+        let loaded = InteractionPlan::from_json(&json).unwrap();
+        assert_eq!(loaded.len(), plan.len());
+        assert_eq!(loaded.to_string(), plan.to_string());
+        assert_eq!(loaded.to_json().unwrap(), json);
+
+        let newer = json.replacen(
+            &format!("\"version\": {PLAN_JSON_VERSION}"),
+            &format!("\"version\": {}", PLAN_JSON_VERSION + 1),
+            1,
+        );
+        assert!(InteractionPlan::from_json(&newer).is_err());
+    }
+}
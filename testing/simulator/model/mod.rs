@@ -12,7 +12,9 @@ use sql_generation::model::{
         Create, CreateIndex, Delete, Drop, DropIndex, Insert, Select,
         alter_table::{AlterTable, AlterTableType},
         pragma::Pragma,
-        select::{CompoundOperator, FromClause, ResultColumn, SelectInner},
+        select::{
+            CompoundOperator, FromClause, OrderBy, ResultColumn, SelectInner, column_position,
+        },
         transaction::{Begin, Commit, Rollback},
         update::{SetValue, Update},
     },
@@ -24,7 +26,7 @@ use turso_parser::ast::{ColumnConstraint, Distinctness};
 
 use crate::runner::env::TransactionMode;
 use crate::{generation::Shadow, runner::env::ShadowTablesMut};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 fn integer_pk_index(columns: &[Column]) -> Option<usize> {
     columns
@@ -237,6 +239,7 @@ fn prepare_insert_rows(
 }
 
 pub mod interactions;
+pub mod json;
 pub mod metrics;
 pub mod property;
 
@@ -580,7 +583,9 @@ impl Shadow for Query {
             Query::RollbackToSavepoint(rollback_to) => rollback_to.shadow(env),
             Query::ReleaseSavepoint(release) => release.shadow(env),
             Query::Placeholder => Ok(vec![]),
-            Query::Pragma(Pragma::AutoVacuumMode(_) | Pragma::ForeignKeyList(_)) => Ok(vec![]),
+            Query::Pragma(
+                Pragma::AutoVacuumMode(_) | Pragma::ForeignKeyList(_) | Pragma::WalCheckpoint,
+            ) => Ok(vec![]),
         }
     }
 }
@@ -704,16 +709,26 @@ impl Shadow for Delete {
 
     fn shadow(&self, tables: &mut ShadowTablesMut) -> Self::Result {
         // First pass: find deleted rows and collect them
-        let deleted_rows = {
+        let (deleted_row_indices, deleted_rows) = {
             let table = tables.iter().find(|t| t.name == self.table);
             if let Some(table) = table {
-                let t2 = table.clone();
-                table
-                    .rows
+                let mut matching = (0..table.rows.len())
+                    .filter(|&idx| self.predicate.test(&table.rows[idx], table))
+                    .collect::<Vec<_>>();
+                if let Some(limit) = self.limit {
+                    // A LIMIT deletes the first rows in rowid order, which is the order of
+                    // the INTEGER PRIMARY KEY if there is one, and of insertion otherwise
+                    if let Some(pk_idx) = integer_pk_index(&table.columns) {
+                        matching
+                            .sort_by(|&a, &b| table.rows[a][pk_idx].cmp(&table.rows[b][pk_idx]));
+                    }
+                    matching.truncate(limit);
+                }
+                let rows = matching
                     .iter()
-                    .filter(|r| self.predicate.test(r, &t2))
-                    .cloned()
-                    .collect::<Vec<_>>()
+                    .map(|&idx| table.rows[idx].clone())
+                    .collect::<Vec<_>>();
+                (matching, rows)
             } else {
                 return Err(anyhow::anyhow!(
                     "Table {} does not exist. DELETE statement ignored.",
@@ -728,9 +743,15 @@ impl Shadow for Delete {
         }
 
         // Second pass: actually remove the rows
+        let deleted_row_indices: std::collections::HashSet<usize> =
+            deleted_row_indices.into_iter().collect();
         if let Some(table) = tables.iter_mut().find(|t| t.name == self.table) {
-            let t2 = table.clone();
-            table.rows.retain_mut(|r| !self.predicate.test(r, &t2));
+            let mut idx = 0;
+            table.rows.retain(|_| {
+                let keep = !deleted_row_indices.contains(&idx);
+                idx += 1;
+                keep
+            });
         }
 
         Ok(vec![])
@@ -1138,6 +1159,13 @@ impl Shadow for SelectInner {
                 .rows
                 .retain(|row| self.where_clause.test(row, &join_clone));
 
+            if self.is_aggregate() {
+                join_table = JoinTable {
+                    tables: Vec::new(),
+                    rows: aggregate_rows(self, &join_table)?,
+                };
+            }
+
             if self.distinctness == Distinctness::Distinct {
                 join_table.rows.sort_unstable();
                 join_table.rows.dedup();
@@ -1186,13 +1214,63 @@ impl Shadow for SelectInner {
     }
 }
 
+/// The rows of an aggregate select, one per group of the rows of `table` that passes the
+/// `HAVING` clause. Without a `GROUP BY`, all the rows are a single group, even when there
+/// are none.
+fn aggregate_rows(select: &SelectInner, table: &JoinTable) -> anyhow::Result<Vec<Vec<SimValue>>> {
+    let key_positions = select
+        .group_by
+        .iter()
+        .flat_map(|group_by| group_by.columns.iter())
+        .map(|name| column_position(table, name).context("GROUP BY column not found"))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut groups: BTreeMap<Vec<SimValue>, Vec<&Vec<SimValue>>> = BTreeMap::new();
+    for row in &table.rows {
+        let key = key_positions.iter().map(|&idx| row[idx].clone()).collect();
+        groups.entry(key).or_default().push(row);
+    }
+    if select.group_by.is_none() {
+        groups.entry(Vec::new()).or_default();
+    }
+
+    let having = select
+        .group_by
+        .as_ref()
+        .and_then(|group_by| group_by.having.as_ref());
+    let mut rows = Vec::new();
+    for group in groups.values() {
+        if let Some(having) = having
+            && !having.test(group, table)?
+        {
+            continue;
+        }
+        let row = select
+            .columns
+            .iter()
+            .map(|column| match column {
+                ResultColumn::Aggregate(aggregate) => aggregate.eval(group, table),
+                // Only the GROUP BY columns are selected, so any row of the group has their value
+                ResultColumn::Column(name) => {
+                    let idx = column_position(table, name).context("column not found")?;
+                    Ok(group.first().map_or(SimValue::NULL, |row| row[idx].clone()))
+                }
+                ResultColumn::Expr(_) | ResultColumn::Star => Err(anyhow::anyhow!(
+                    "only columns and aggregates are modeled in aggregate selects"
+                )),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
 impl Shadow for Select {
     type Result = anyhow::Result<Vec<Vec<SimValue>>>;
 
     fn shadow(&self, env: &mut ShadowTablesMut) -> Self::Result {
-        let first_result = self.body.select.shadow(env)?;
+        let mut first_result = self.body.select.shadow(env)?;
 
-        let mut rows = first_result.rows;
+        let mut rows = std::mem::take(&mut first_result.rows);
 
         for compound in self.body.compounds.iter() {
             let compound_results = compound.select.shadow(env)?;
@@ -1213,6 +1291,20 @@ impl Shadow for Select {
             }
         }
 
+        if let Some(order_by) = &self.body.select.order_by {
+            let keys = order_by
+                .key_columns(&first_result)
+                .context("ORDER BY column not found")?;
+            // A stable sort, ties keep the order they had. The database may return them
+            // in any order, so only compare the ORDER BY columns of the results.
+            rows.sort_by(|a, b| OrderBy::compare(&keys, a, b));
+        }
+        if let Some(limit) = self.limit {
+            let offset = self.offset.unwrap_or(0).min(rows.len());
+            rows.drain(..offset);
+            rows.truncate(limit);
+        }
+
         Ok(rows)
     }
 }
@@ -1303,7 +1395,7 @@ impl Shadow for Update {
                 .map(|(row_idx, old_row)| {
                     let mut new_row = old_row.clone();
                     for (column, set_value) in &self.set_values {
-                        if let Some((idx, _)) =
+                        if let Some((idx, col)) =
                             columns.iter().enumerate().find(|(_, c)| &c.name == column)
                         {
                             match set_value {
@@ -1321,6 +1413,16 @@ impl Shadow for Update {
                                         new_row[idx] = then_value.clone();
                                     }
                                 }
+                                SetValue::Column(source) => {
+                                    // Read from the old row, assignments don't see each other
+                                    if let Some(source_idx) =
+                                        columns.iter().position(|c| &c.name == source)
+                                    {
+                                        new_row[idx] = old_row[source_idx]
+                                            .clone()
+                                            .apply_affinity(col.column_type);
+                                    }
+                                }
                             }
                         }
                     }
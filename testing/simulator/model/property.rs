@@ -1,12 +1,14 @@
 use serde::{Deserialize, Serialize};
 use sql_generation::model::query::{Create, Insert, Select, predicate::Predicate, update::Update};
 
-use crate::model::{CreateSequence, DropSequence, Query, QueryDiscriminants};
+use crate::model::{
+    CreateSequence, DropSequence, Query, QueryDiscriminants, interactions::IoFault,
+};
 
 /// Properties are representations of executable specifications
 /// about the database behavior.
 #[derive(Debug, Clone, Serialize, Deserialize, strum::EnumDiscriminants, strum::IntoStaticStr)]
-#[strum_discriminants(derive(strum::EnumIter, strum::IntoStaticStr))]
+#[strum_discriminants(derive(Hash, strum::EnumIter, strum::IntoStaticStr))]
 #[strum(serialize_all = "Train-Case")]
 pub enum Property {
     /// Insert-Select is a property in which the inserted row
@@ -93,14 +95,29 @@ pub enum Property {
     /// Select Limit is a property in which the select query
     /// has a limit clause that is respected by the query.
     /// The execution of the property is as follows
-    ///     SELECT * FROM <t> WHERE <predicate> LIMIT <n>
+    ///     SELECT * FROM <t> WHERE <predicate> [ORDER BY <cols>] LIMIT <n> [OFFSET <m>]
     /// This property is a single-interaction property.
     /// The interaction has the following constraints;
     /// - The select query will respect the limit clause.
+    /// - It returns as many rows as the model, all of them matching the predicate.
+    /// - With an ORDER BY, the ORDER BY columns of its rows are the ones of the model's
+    ///   rows, in the same order.
     SelectLimit {
         /// The select query
         select: Select,
     },
+    /// Select Aggregate is a property in which an aggregate select query
+    /// returns the groups and aggregates that the model computes.
+    /// The execution of the property is as follows
+    ///     SELECT [<cols>,] <aggregates> FROM <t> WHERE <predicate> [GROUP BY <cols> [HAVING <aggregate> <op> <value>]]
+    /// This property is a single-interaction property.
+    /// The interaction has the following constraints;
+    /// - It returns the rows of the model, in any order.
+    /// - REAL results, like averages, only have to match the model's up to rounding.
+    SelectAggregate {
+        /// The select query
+        select: Select,
+    },
     /// Delete-Select is a property in which the deleted row
     /// must not be in the resulting rows of a select query that has a
     /// where clause that matches the deleted row. In practice, `p1` of
@@ -150,6 +167,19 @@ pub enum Property {
         table: String,
         predicate: Predicate,
     },
+    /// Index-Consistency is a property in which the same select query is run twice,
+    /// once forced through an index of the table and once with a full scan of the
+    /// table. An index that misses rows of the table, or kept rows that were deleted or
+    /// updated, makes the two queries return different rows.
+    /// The execution of the property is as follows
+    ///     SELECT * FROM <t> INDEXED BY <index> WHERE <predicate>
+    ///     SELECT * FROM <t> NOT INDEXED WHERE <predicate>
+    /// The property is successful if both queries return the same rows, in any order.
+    IndexConsistency {
+        table: String,
+        index: String,
+        predicate: Predicate,
+    },
     /// Where-True-False-Null is a property that tests the boolean logic implementation
     /// in the database. It relies on the fact that `P == true || P == false || P == null` should return true,
     /// as SQLite uses a ternary logic system. This property is invented in "Finding Bugs in Database Systems via Query Partitioning"
@@ -182,6 +212,109 @@ pub enum Property {
     FaultyQuery {
         query: Query,
     },
+    /// ScheduledIoFault is a property which tests that a failed IO operation on the
+    /// database file is reported, and leaves the database intact.
+    ///
+    /// # Interactions
+    /// - Schedule `fault` on the next write or fsync of the database file
+    /// - Execute the `query`
+    /// - Assert that the query failed if the fault fired
+    /// - Check the integrity of the database
+    /// - Query the tables the `query` uses to assert that they match the model
+    ScheduledIoFault {
+        fault: IoFault,
+        query: Query,
+    },
+    /// PowerLoss is a property which tests that a commit is atomic across a power
+    /// loss: after recovery, the database holds either the state before the commit or
+    /// the state after it, never a mix of the two.
+    ///
+    /// # Interactions
+    /// - Cut the power after `crash_point` more writes and syncs of the database files
+    /// - Execute the `query`, and checkpoint the WAL if `checkpoint` is set
+    /// - Assert that a statement failed if the power went out
+    /// - Reopen the database, and assert that the tables the `query` uses hold the
+    ///   state from before the `query` or after it
+    /// - Check the integrity of the database
+    /// - Query the tables the `query` uses to assert that they match the model
+    PowerLoss {
+        crash_point: u32,
+        query: Query,
+        checkpoint: bool,
+    },
+    /// AllocationFailure is a property which tests that running out of memory fails
+    /// the statement that needed the memory, and leaves the database intact.
+    ///
+    /// # Interactions
+    /// - Fail the allocation after `nth` more allocations that can fail
+    /// - Execute the `query`
+    /// - Assert that the query failed with an out of memory error if the allocation failed
+    /// - Check the integrity of the database
+    /// - Query the tables the `query` uses to assert that they match the model
+    AllocationFailure {
+        nth: u32,
+        query: Query,
+    },
+    /// Interleaving is a property which tests how a writer and its readers on
+    /// other connections see each other: the writer's transaction runs on the
+    /// property's connection, each reader runs on a connection of its own, and
+    /// their statements are interleaved in the order of a seeded `schedule`.
+    ///
+    /// # Interactions
+    /// - Each step runs the next statement of the connection the `schedule` names
+    /// - After each read, assert that the reader sees the rows of its snapshot in
+    ///   the model, never a part of the writer's uncommitted transaction
+    /// - A reader only writes while the writer holds the write lock, so assert
+    ///   that the write fails with Busy
+    /// - Query the tables the writer uses to assert that they match the model
+    Interleaving {
+        /// `BEGIN`, the writes, and `COMMIT` of the writer
+        writer: Vec<Query>,
+        readers: Vec<Reader>,
+        /// The transaction that takes each step: 0 for the writer, and `i` for
+        /// the reader at `readers[i - 1]`
+        schedule: Vec<usize>,
+    },
+    /// CheckpointInterleaving is a property which tests the checkpointer against the
+    /// model: autocommit writes on the property's connection, reads on other
+    /// connections, some of them in transactions of their own, and `PRAGMA
+    /// wal_checkpoint` calls on any connection that is not in a transaction run in
+    /// the order of the `steps`.
+    ///
+    /// # Interactions
+    /// - Each step runs its statement on its connection
+    /// - After each read, assert that the reader sees the rows of its snapshot in
+    ///   the model, whatever the checkpoints before it moved out of the WAL
+    /// - Assert that each checkpoint succeeds, or fails with Busy while a reader
+    ///   is in a transaction
+    /// - If `crash_point` is set, cut the power after `crash_point` more writes and
+    ///   syncs of the database files, checkpoint once more, assert that the
+    ///   checkpoint failed if the power went out, and reopen the database
+    /// - Check the integrity of the database
+    /// - Query the tables the writes use to assert that they match the model
+    CheckpointInterleaving {
+        steps: Vec<CheckpointStep>,
+        crash_point: Option<u32>,
+    },
+    /// TransactionAtomicity is a property which tests that a transaction is all or
+    /// nothing: once it commits all of its writes are visible, once it rolls back
+    /// none of them are, and until then another connection sees none of them.
+    ///
+    /// # Interactions
+    /// - The `observer` reads the tables the `queries` use
+    /// - Begin a transaction, and execute the `queries`
+    /// - After each query, assert that the transaction sees the tables of the model,
+    ///   and read the tables again from the `observer`
+    /// - Commit the transaction if `commit` is set, and roll it back otherwise
+    /// - Read the tables from the `observer` once more, and assert that
+    ///   - every read before the end of the transaction saw the tables before it
+    ///   - the last read saw the tables of the model, which are the tables before
+    ///     the transaction if it rolled back
+    TransactionAtomicity {
+        queries: Vec<Query>,
+        observer: usize,
+        commit: bool,
+    },
     /// SavepointRollback wraps random write interactions in a named savepoint,
     /// rolls them back, then checks that the database still matches the shadow
     /// model. This targets pager/WAL/cache-spill bugs where rolled-back page
@@ -211,6 +344,22 @@ pub enum Property {
     },
 }
 
+/// A connection reading the tables while the writer of an [Property::Interleaving]
+/// runs. Its selects read whole tables, and run in a transaction if they are
+/// between a `BEGIN` and a `COMMIT`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reader {
+    pub connection: usize,
+    pub queries: Vec<Query>,
+}
+
+/// A statement of a [Property::CheckpointInterleaving], with the connection it runs on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointStep {
+    pub connection: usize,
+    pub query: Query,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InteractiveQueryInfo {
     pub start_with_immediate: bool,
@@ -222,7 +371,11 @@ impl Property {
     pub fn check_tables(&self) -> bool {
         matches!(
             self,
-            Property::FsyncNoWait { .. } | Property::FaultyQuery { .. }
+            Property::FsyncNoWait { .. }
+                | Property::FaultyQuery { .. }
+                | Property::ScheduledIoFault { .. }
+                | Property::PowerLoss { .. }
+                | Property::AllocationFailure { .. }
         )
     }
 
@@ -246,15 +399,24 @@ impl Property {
             | Property::DropSelect { queries, .. }
             | Property::SavepointRollback { queries, .. }
             | Property::Queries { queries } => Some(queries),
-            Property::FsyncNoWait { .. } | Property::FaultyQuery { .. } => None,
+            Property::FsyncNoWait { .. }
+            | Property::FaultyQuery { .. }
+            | Property::ScheduledIoFault { .. }
+            | Property::PowerLoss { .. }
+            | Property::AllocationFailure { .. } => None,
             Property::SequenceMonotonicity { .. } => None,
             Property::SelectLimit { .. }
+            | Property::SelectAggregate { .. }
             | Property::SelectSelectOptimizer { .. }
+            | Property::IndexConsistency { .. }
             | Property::WhereTrueFalseNull { .. }
             | Property::UnionAllPreservesCardinality { .. }
             | Property::ReadYourUpdatesBack { .. }
             | Property::TableHasExpectedContent { .. }
-            | Property::AllTableHaveExpectedContent { .. } => None,
+            | Property::AllTableHaveExpectedContent { .. }
+            | Property::Interleaving { .. }
+            | Property::CheckpointInterleaving { .. }
+            | Property::TransactionAtomicity { .. } => None,
         }
     }
 }
@@ -0,0 +1,147 @@
+//! Allocation failures scheduled by the plan. With `--allocation-faults`, Turso's
+//! allocations go through [AllocationFaultInjector], which can fail one of the
+//! allocations of the next query, so that the paths that run out of memory are tested.
+//!
+//! Only the allocations that Turso makes with its own allocator, at a site that allows
+//! fault injection, can fail. The countdown is kept per thread, so that the other
+//! threads of the process never see an injected failure.
+
+use std::cell::Cell;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use turso_core::alloc::{
+    AllocError, AllocationSite, ApiAllocator, Global, Layout, SetAllocatorError, TursoAllocBackend,
+};
+
+thread_local! {
+    /// The number of injectable allocations left before the one that fails.
+    static COUNTDOWN: Cell<Option<u32>> = const { Cell::new(None) };
+    static FIRED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) struct AllocationFaultInjector {
+    injected_faults: AtomicU64,
+}
+
+static INJECTOR: AllocationFaultInjector = AllocationFaultInjector {
+    injected_faults: AtomicU64::new(0),
+};
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the injector as Turso's allocator. It has to be installed before the first
+/// database is opened, and stays installed for the rest of the process.
+pub(crate) fn install() -> anyhow::Result<()> {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    if let Err(err) = unsafe { turso_core::alloc::set_allocator(&INJECTOR) } {
+        INSTALLED.store(false, Ordering::Release);
+        return Err(match err {
+            SetAllocatorError::AlreadyInitialized => anyhow::anyhow!(
+                "allocation faults require installing the simulator's allocator before any other Turso allocator"
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Fail the allocation that comes after `nth` more injectable allocations on this
+/// thread. A failure that did not fire yet is replaced.
+pub(crate) fn schedule(nth: u32) {
+    COUNTDOWN.with(|countdown| countdown.set(Some(nth)));
+    FIRED.with(|fired| fired.set(false));
+}
+
+/// Cancel the scheduled failure, returning whether it fired.
+pub(crate) fn clear() -> bool {
+    COUNTDOWN.with(|countdown| countdown.set(None));
+    FIRED.with(|fired| fired.replace(false))
+}
+
+/// Number of allocations failed since the process started.
+pub(crate) fn injected_faults() -> u64 {
+    INJECTOR.injected_faults.load(Ordering::Relaxed)
+}
+
+impl AllocationFaultInjector {
+    fn should_fail(&self) -> bool {
+        if !matches!(
+            turso_core::alloc::current_allocation_site(),
+            Some(site) if site != AllocationSite::NoFaultInjection
+        ) {
+            return false;
+        }
+        let fail = COUNTDOWN.with(|countdown| match countdown.get() {
+            Some(0) => {
+                countdown.set(None);
+                true
+            }
+            Some(n) => {
+                countdown.set(Some(n - 1));
+                false
+            }
+            None => false,
+        });
+        if fail {
+            FIRED.with(|fired| fired.set(true));
+            self.injected_faults.fetch_add(1, Ordering::Relaxed);
+        }
+        fail
+    }
+}
+
+unsafe impl TursoAllocBackend for AllocationFaultInjector {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if self.should_fail() {
+            return Err(AllocError);
+        }
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe {
+            Global.deallocate(ptr, layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use turso_core::alloc::BTreeAllocationSite;
+
+    use super::*;
+
+    #[test]
+    fn fails_the_nth_injectable_allocation_once() {
+        let injector = AllocationFaultInjector {
+            injected_faults: AtomicU64::new(0),
+        };
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        let allocate = |injector: &AllocationFaultInjector| {
+            let result = injector.allocate(layout);
+            if let Ok(ptr) = result {
+                unsafe { injector.deallocate(ptr.cast(), layout) };
+            }
+            result.is_ok()
+        };
+
+        schedule(1);
+        // Allocations outside of an injectable site don't count
+        assert!(allocate(&injector));
+        {
+            let _site = turso_core::alloc::enter_allocation_site(BTreeAllocationSite::Balance);
+            assert!(allocate(&injector));
+            assert!(!allocate(&injector));
+            assert!(allocate(&injector));
+        }
+        assert_eq!(injector.injected_faults.load(Ordering::Relaxed), 1);
+        assert!(clear());
+        assert!(!clear());
+
+        schedule(0);
+        let _site = turso_core::alloc::enter_allocation_site(AllocationSite::NoFaultInjection);
+        assert!(allocate(&injector));
+        assert!(!clear());
+    }
+}
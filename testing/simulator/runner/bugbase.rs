@@ -17,6 +17,8 @@ use super::cli::SimulatorCLI;
 
 const READABLE_PLAN_PATH: &str = "plan.sql";
 const SHRUNK_READABLE_PLAN_PATH: &str = "shrunk.sql";
+const PLAN_PATH: &str = "plan.json";
+const SHRUNK_PLAN_PATH: &str = "shrunk.json";
 const SEED_PATH: &str = "seed.txt";
 const RUNS_PATH: &str = "runs.json";
 
@@ -26,9 +28,7 @@ pub struct Bug {
     /// The seed of the bug.
     pub seed: u64,
 
-    /// The plan of the bug.
-    /// TODO: currently plan is only saved to the .sql file, and that is not deserializable yet
-    /// so we cannot always store an interaction plan here
+    /// The plan of the bug, if it was saved as JSON.
     pub plan: Option<InteractionPlan>,
 
     /// The shrunk plan of the bug, if any.
@@ -68,12 +68,16 @@ impl Bug {
             let readable_plan_path = bug_path.join(READABLE_PLAN_PATH);
             std::fs::write(&readable_plan_path, plan.to_string())
                 .with_context(|| "should be able to write readable plan file")?;
+            std::fs::write(bug_path.join(PLAN_PATH), plan.to_json()?)
+                .with_context(|| "should be able to write plan file")?;
         }
 
         if let Some(shrunk_plan) = &self.shrunk_plan {
             let readable_shrunk_plan_path = bug_path.join(SHRUNK_READABLE_PLAN_PATH);
             std::fs::write(&readable_shrunk_plan_path, shrunk_plan.to_string())
                 .with_context(|| "should be able to write readable shrunk plan file")?;
+            std::fs::write(bug_path.join(SHRUNK_PLAN_PATH), shrunk_plan.to_json()?)
+                .with_context(|| "should be able to write shrunk plan file")?;
         }
 
         let runs_path = bug_path.join(RUNS_PATH);
@@ -168,10 +172,11 @@ impl BugBase {
                 .and_then(|runs| serde_json::from_str(&runs).map_err(|e| anyhow!("{}", e)))?
         };
 
+        let bug_path = self.path.join(seed.to_string());
         let bug = Bug {
             seed,
-            plan: None,
-            shrunk_plan: None,
+            plan: load_plan(&bug_path.join(PLAN_PATH)),
+            shrunk_plan: load_plan(&bug_path.join(SHRUNK_PLAN_PATH)),
             runs,
         };
         Ok(bug)
@@ -315,6 +320,19 @@ impl BugBase {
         let base = self.path.join(format!("{seed}/"));
         Paths::new(&base)
     }
+
+    /// Get the seed of the bug whose plan file is at `plan`, from the seed file next to it.
+    pub(crate) fn seed_of_plan(plan: impl AsRef<Path>) -> anyhow::Result<u64> {
+        let plan = plan.as_ref();
+        let seed_path = plan
+            .parent()
+            .with_context(|| format!("{} is not in a bug directory", plan.display()))?
+            .join(SEED_PATH);
+        read_to_string(&seed_path)
+            .with_context(|| format!("failed to read {}", seed_path.display()))?
+            .parse::<u64>()
+            .with_context(|| format!("failed to parse seed from {}", seed_path.display()))
+    }
 }
 
 impl BugBase {
@@ -366,6 +384,23 @@ fn resolve_head(git_dir: impl AsRef<Path>) -> anyhow::Result<String> {
     }
 }
 
+/// Load the plan saved at `path`, if there is one that this version of the simulator can read.
+fn load_plan(path: &Path) -> Option<InteractionPlan> {
+    if !path.exists() {
+        return None;
+    }
+    let plan = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| InteractionPlan::from_json(&json));
+    match plan {
+        Ok(plan) => Some(plan),
+        Err(err) => {
+            tracing::warn!("failed to load plan from {}: {err:?}", path.display());
+            None
+        }
+    }
+}
+
 fn read_to_string(path: impl AsRef<Path>) -> anyhow::Result<String> {
     let mut file = File::open(path)?;
     let mut contents = String::new();
@@ -0,0 +1,255 @@
+//! Bug bundles: a single tarball with everything needed to reproduce a failing run
+//! somewhere else.
+//!
+//! A bundle is written to `bundle.tar` in the directory of the bug, and holds every file
+//! of that directory: the seed, the plan and the shrunk plan, the history of the run,
+//! the database files and the recorded CLI options. It adds the commit of Turso that
+//! the bundle was made at, and a script that puts the bug back in a bug base and
+//! loads it with the same options.
+//!
+//! The entries are stored under `<seed>/`, so extracting the bundle into `.bugbase`
+//! gives back the directory of the bug.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, bail};
+
+use crate::runner::bugbase::BugBase;
+use crate::runner::env::Paths;
+
+const BUNDLE_PATH: &str = "bundle.tar";
+const HASH_PATH: &str = "hash.txt";
+const REPRODUCE_PATH: &str = "reproduce.sh";
+
+const BLOCK_SIZE: usize = 512;
+
+/// Bundle the files of the bug with `seed` in `paths.base`, returning the path of the
+/// tarball.
+pub(crate) fn write_bundle(paths: &Paths, seed: u64) -> anyhow::Result<PathBuf> {
+    let bundle_path = paths.base.join(BUNDLE_PATH);
+    let mut files = std::fs::read_dir(&paths.base)
+        .with_context(|| format!("should be able to read {}", paths.base.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|path| path.is_file() && *path != bundle_path);
+    // sorted, so that the same files always give the same bundle
+    files.sort();
+
+    let hash = BugBase::get_current_commit_hash().unwrap_or_else(|err| {
+        tracing::warn!("failed to get the commit hash for the bundle: {err:?}");
+        "unknown".to_string()
+    });
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    let f = std::fs::File::create(&bundle_path)
+        .with_context(|| format!("should be able to create {}", bundle_path.display()))?;
+    let mut tar = TarWriter::new(std::io::BufWriter::new(f), mtime);
+    for path in files {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            tracing::warn!("skipping {} in the bundle", path.display());
+            continue;
+        };
+        if name == HASH_PATH || name == REPRODUCE_PATH {
+            continue;
+        }
+        let contents = std::fs::read(&path)
+            .with_context(|| format!("should be able to read {}", path.display()))?;
+        tar.append(&format!("{seed}/{name}"), &contents, false)?;
+    }
+    tar.append(
+        &format!("{seed}/{HASH_PATH}"),
+        format!("{hash}\n").as_bytes(),
+        false,
+    )?;
+    tar.append(
+        &format!("{seed}/{REPRODUCE_PATH}"),
+        reproduce_script(seed, &hash).as_bytes(),
+        true,
+    )?;
+    tar.finish()?;
+
+    tracing::info!("bundle written to {}", bundle_path.display());
+    Ok(bundle_path)
+}
+
+/// A script that adds the extracted bug to the bug base of the checkout it's run from,
+/// and runs it again with the options of its last run.
+fn reproduce_script(seed: u64, hash: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+# Reproduces simulator bug {seed}, found at Turso commit {hash}.
+#
+# From the root of a Turso checkout at that commit:
+#   tar -xf bundle.tar && sh {seed}/reproduce.sh
+set -e
+mkdir -p .bugbase
+cp -R "$(dirname "$0")" .bugbase/
+cargo run --bin limbo_sim -- --load {seed}
+"#
+    )
+}
+
+/// Writes an uncompressed tarball in the ustar format, with regular files only.
+struct TarWriter<W: Write> {
+    out: W,
+    mtime: u64,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn new(out: W, mtime: u64) -> Self {
+        Self { out, mtime }
+    }
+
+    fn append(&mut self, name: &str, contents: &[u8], executable: bool) -> anyhow::Result<()> {
+        let mode = if executable { 0o755 } else { 0o644 };
+        let header = header(name, contents.len() as u64, mode, self.mtime)?;
+        self.out.write_all(&header)?;
+        self.out.write_all(contents)?;
+        let padding = contents.len().next_multiple_of(BLOCK_SIZE) - contents.len();
+        self.out.write_all(&[0; BLOCK_SIZE][..padding])?;
+        Ok(())
+    }
+
+    /// Write the two empty blocks that end the archive.
+    fn finish(mut self) -> anyhow::Result<W> {
+        self.out.write_all(&[0; 2 * BLOCK_SIZE])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn header(name: &str, size: u64, mode: u32, mtime: u64) -> anyhow::Result<[u8; BLOCK_SIZE]> {
+    if name.len() > 100 {
+        bail!("'{name}' is too long for a tar entry");
+    }
+    if size >= 8u64.pow(11) {
+        bail!("'{name}' is too large for a tar entry");
+    }
+    let mut header = [0; BLOCK_SIZE];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(0, name.as_bytes());
+    field(100, format!("{mode:07o}\0").as_bytes());
+    // uid and gid
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{size:011o}\0").as_bytes());
+    field(
+        136,
+        format!("{:011o}\0", mtime.min(8u64.pow(11) - 1)).as_bytes(),
+    );
+    // regular file
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+
+    // the checksum is computed with its own field filled with spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn octal(field: &[u8]) -> u64 {
+        let digits = std::str::from_utf8(field)
+            .unwrap()
+            .trim_end_matches(['\0', ' ']);
+        u64::from_str_radix(digits, 8).unwrap()
+    }
+
+    /// The name, mode and contents of every entry of `tar`.
+    fn read_tar(tar: &[u8]) -> Vec<(String, u64, Vec<u8>)> {
+        assert_eq!(tar.len() % BLOCK_SIZE, 0);
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        loop {
+            let header = &tar[offset..offset + BLOCK_SIZE];
+            if header.iter().all(|&byte| byte == 0) {
+                assert!(tar[offset..].iter().all(|&byte| byte == 0));
+                assert_eq!(tar.len() - offset, 2 * BLOCK_SIZE);
+                return entries;
+            }
+            let mut unsummed = header.to_vec();
+            unsummed[148..156].fill(b' ');
+            let checksum: u64 = unsummed.iter().map(|&byte| u64::from(byte)).sum();
+            assert_eq!(octal(&header[148..156]), checksum);
+            assert_eq!(&header[257..263], b"ustar\0");
+
+            let name_len = header[..100].iter().position(|&byte| byte == 0).unwrap();
+            let name = String::from_utf8(header[..name_len].to_vec()).unwrap();
+            let size = octal(&header[124..136]) as usize;
+            let contents = tar[offset + BLOCK_SIZE..offset + BLOCK_SIZE + size].to_vec();
+            entries.push((name, octal(&header[100..108]), contents));
+            offset += BLOCK_SIZE + size.next_multiple_of(BLOCK_SIZE);
+        }
+    }
+
+    #[test]
+    fn tarballs_keep_their_entries() {
+        let mut tar = TarWriter::new(Vec::new(), 1_700_000_000);
+        let db = (0..1500).map(|i| i as u8).collect::<Vec<_>>();
+        tar.append("42/plan.sql", b"SELECT 1;\n", false).unwrap();
+        tar.append("42/test.db", &db, false).unwrap();
+        tar.append("42/empty.txt", b"", false).unwrap();
+        tar.append("42/reproduce.sh", b"#!/bin/sh\n", true).unwrap();
+        let tar = tar.finish().unwrap();
+
+        assert_eq!(
+            read_tar(&tar),
+            vec![
+                ("42/plan.sql".to_string(), 0o644, b"SELECT 1;\n".to_vec()),
+                ("42/test.db".to_string(), 0o644, db),
+                ("42/empty.txt".to_string(), 0o644, Vec::new()),
+                (
+                    "42/reproduce.sh".to_string(),
+                    0o755,
+                    b"#!/bin/sh\n".to_vec()
+                ),
+            ]
+        );
+
+        let mut tar = TarWriter::new(Vec::new(), 0);
+        assert!(tar.append(&"a".repeat(101), b"", false).is_err());
+    }
+
+    #[test]
+    fn bundles_hold_the_files_of_the_bug() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = Paths::new(dir.path());
+        std::fs::write(dir.path().join("seed.txt"), "7").unwrap();
+        std::fs::write(dir.path().join("shrunk.sql"), "SELECT 1;\n").unwrap();
+        std::fs::write(dir.path().join("test.db"), [1, 2, 3]).unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+
+        let bundle = write_bundle(&paths, 7).unwrap();
+        // bundling again doesn't put the previous bundle in the new one
+        let bundle = write_bundle(&paths, 7).unwrap();
+        let entries = read_tar(&std::fs::read(bundle).unwrap());
+        let names = entries
+            .iter()
+            .map(|(name, ..)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "7/seed.txt",
+                "7/shrunk.sql",
+                "7/test.db",
+                "7/hash.txt",
+                "7/reproduce.sh"
+            ]
+        );
+        assert_eq!(entries[2].2, [1, 2, 3]);
+        let script = String::from_utf8(entries[4].2.clone()).unwrap();
+        assert!(script.contains("limbo_sim -- --load 7"));
+    }
+}
@@ -5,6 +5,7 @@ use clap::{
     error::{ContextKind, ContextValue, ErrorKind},
 };
 use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
 
 use crate::profiles::ProfileType;
 
@@ -88,11 +89,29 @@ pub struct SimulatorCLI {
         conflicts_with = "doublecheck"
     )]
     pub differential: bool,
+    #[clap(
+        long,
+        help = "simulate power losses at random crash points during commits and checkpoints, and check that the database recovers atomically",
+        conflicts_with = "differential"
+    )]
+    pub power_loss: bool,
+    #[clap(
+        long,
+        help = "fail allocations during write queries, and check that they fail cleanly and leave the database intact",
+        conflicts_with = "differential"
+    )]
+    pub allocation_faults: bool,
     #[clap(
         long,
         help = "enable brute force shrink (warning: it might take a long time)"
     )]
     pub enable_brute_force_shrinking: bool,
+    #[clap(
+        long,
+        help = "maximum number of times brute force shrinking re-runs the plan",
+        default_value_t = 1000
+    )]
+    pub shrink_budget: usize,
     #[clap(subcommand)]
     pub subcommand: Option<SimulatorCommand>,
     #[clap(long, help = "disable BugBase")]
@@ -123,6 +142,8 @@ pub struct SimulatorCLI {
     pub disable_double_create_failure: bool,
     #[clap(long, help = "disable Select-Limit Property")]
     pub disable_select_limit: bool,
+    #[clap(long, help = "disable Select-Aggregate Property")]
+    pub disable_select_aggregate: bool,
     #[clap(long, help = "disable Delete-Select Property")]
     pub disable_delete_select: bool,
     #[clap(long, help = "disable Drop-Select Property")]
@@ -133,6 +154,8 @@ pub struct SimulatorCLI {
         default_value_t = false
     )]
     pub disable_select_optimizer: bool,
+    #[clap(long, help = "disable Index-Consistency Property")]
+    pub disable_index_consistency: bool,
     #[clap(
         long,
         help = "disable Where-True-False-Null Property",
@@ -155,6 +178,19 @@ pub struct SimulatorCLI {
     pub disable_fsync_no_wait: bool,
     #[clap(long, help = "disable FaultyQuery Property")]
     pub disable_faulty_query: bool,
+    #[clap(long, help = "disable ScheduledIoFault Property")]
+    pub disable_scheduled_io_fault: bool,
+    #[clap(long, help = "disable Interleaving Property")]
+    pub disable_interleaving: bool,
+    #[clap(long, help = "disable Checkpoint-Interleaving Property")]
+    pub disable_checkpoint_interleaving: bool,
+    #[clap(long, help = "disable Transaction-Atomicity Property")]
+    pub disable_transaction_atomicity: bool,
+    #[clap(
+        long,
+        help = "favor the properties that reach bytecode not covered before, making plans depend on the database's behavior"
+    )]
+    pub coverage_guided: bool,
     #[clap(long, help = "disable Reopen-Database fault")]
     pub disable_reopen_database: bool,
     #[clap(long = "latency-prob", help = "added IO latency probability", value_parser = clap::value_parser!(u8).range(0..=100))]
@@ -221,6 +257,70 @@ pub enum SimulatorCommand {
     },
     /// Print profile Json Schema
     PrintSchema,
+    #[clap(about = "run a single seed for a long time, periodically checking invariants")]
+    Soak {
+        #[clap(
+            long,
+            help = "how long to run the simulation (in seconds)",
+            default_value_t = 4 * 60 * 60
+        )]
+        duration: u64,
+        #[clap(
+            long,
+            help = "run the invariant checks every this many interactions",
+            default_value_t = 10_000,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        check_every: u64,
+        #[clap(
+            long,
+            help = "fail when the resident memory of the simulator exceeds this many MiB"
+        )]
+        memory_ceiling_mb: Option<u64>,
+        #[clap(
+            long,
+            help = "number of recent interactions to keep in memory, older ones are moved to rotating plan files",
+            default_value_t = 100_000
+        )]
+        history_window: usize,
+        #[clap(
+            long,
+            help = "run PRAGMA quick_check instead of the full integrity check, skipping index contents"
+        )]
+        quick_check: bool,
+    },
+    #[clap(
+        about = "replay a bug from the bug base, printing the step at which it diverges from its recorded history"
+    )]
+    Replay {
+        #[clap(help = "the seed of the bug, or the path to its plan.sql in the bug base")]
+        target: String,
+    },
+    #[clap(
+        about = "run a bug from the bug base again, and bundle its files into a tarball that reproduces it"
+    )]
+    Bundle {
+        #[clap(help = "the seed of the bug")]
+        seed: u64,
+    },
+    #[clap(
+        about = "run many seeds at once on a pool of worker threads, shrinking the ones that fail"
+    )]
+    Parallel {
+        #[clap(
+            short = 'n',
+            long,
+            help = "number of seeds to run, counting up from --seed when it is set",
+            default_value_t = 16
+        )]
+        seeds: u64,
+        #[clap(
+            short = 'j',
+            long,
+            help = "number of worker threads, defaults to the number of CPUs"
+        )]
+        jobs: Option<NonZeroUsize>,
+    },
 }
 
 impl SimulatorCLI {
@@ -4,6 +4,10 @@ use chrono::{DateTime, Utc};
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
 
+/// The instant every simulation starts at, so that `'now'` and `CURRENT_TIMESTAMP` only
+/// depend on the seed.
+const START_TIME: i64 = 1_700_000_000;
+
 #[derive(Debug)]
 pub struct SimulatorClock {
     curr_time: RefCell<DateTime<Utc>>,
@@ -15,7 +19,7 @@ pub struct SimulatorClock {
 impl SimulatorClock {
     pub fn new(rng: ChaCha8Rng, min_tick: u64, max_tick: u64) -> Self {
         Self {
-            curr_time: RefCell::new(Utc::now()),
+            curr_time: RefCell::new(DateTime::from_timestamp(START_TIME, 0).unwrap()),
             rng: RefCell::new(rng),
             min_tick,
             max_tick,
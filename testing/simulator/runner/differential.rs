@@ -5,15 +5,28 @@ use std::{
 
 use itertools::Itertools;
 use similar_asserts::SimpleDiff;
-use sql_generation::model::table::SimValue;
+use sql_generation::model::{
+    query::{
+        Select,
+        select::{ResultColumn, SelectTable},
+    },
+    table::{SimValue, Table},
+};
+use turso_core::ErrorClass;
 
 use crate::{
-    model::interactions::{ConnectionState, InteractionPlanIterator, InteractionPlanState},
+    model::{
+        Query,
+        interactions::{
+            ConnectionState, Interaction, InteractionPlanIterator, InteractionPlanState,
+            InteractionType,
+        },
+    },
     runner::execution::ExecutionContinuation,
 };
 
 use super::{
-    env::SimulatorEnv,
+    env::{SimConnection, SimulatorEnv},
     execution::{Execution, ExecutionHistory, ExecutionResult},
 };
 
@@ -98,14 +111,43 @@ pub(crate) fn execute_interactions(
             super::execution::execute_plan(&mut rusqlite_env, &interaction, rusqlite_conn_state);
 
         // Compare results
-        let next = match compare_results(
+        let order_by = match &interaction.interaction {
+            InteractionType::Query(Query::Select(select)) => {
+                order_by_positions(select, &rusqlite_env.get_conn_tables(connection_index))
+            }
+            _ => None,
+        };
+        // The class is only that of this interaction's error if it is a query
+        let rusqlite_error_class = match &interaction.interaction {
+            InteractionType::Query(_) => rusqlite_env.last_sqlite_error,
+            _ => None,
+        };
+        let compared = compare_results(
             turso_res,
             turso_conn_state,
             rusqlite_res,
             rusqlite_conn_state,
-        ) {
+            order_by.as_deref(),
+            rusqlite_error_class,
+        )
+        .and_then(|next| {
+            if next != ExecutionContinuation::Stay {
+                compare_changes(
+                    &env,
+                    &rusqlite_env,
+                    &interaction,
+                    turso_conn_state,
+                    rusqlite_conn_state,
+                )?;
+            }
+            Ok(next)
+        });
+        let next = match compared {
             Ok(next) => next,
-            Err(err) => return ExecutionResult::new(history, Some(err)),
+            Err(err) => {
+                tracing::error!("first divergence between limbo and rusqlite: {interaction}");
+                return ExecutionResult::new(history, Some(err));
+            }
         };
 
         match next {
@@ -149,11 +191,16 @@ pub(crate) fn execute_interactions(
     ExecutionResult::new(history, None)
 }
 
+/// Compares the results of an interaction. Rows are compared regardless of their order,
+/// except for the sort keys at `order_by`, whose order must match too. Errors must be
+/// of the same class when rusqlite reported one.
 fn compare_results(
     turso_res: turso_core::Result<ExecutionContinuation>,
     turso_conn_state: &mut ConnectionState,
     rusqlite_res: turso_core::Result<ExecutionContinuation>,
     rusqlite_conn_state: &mut ConnectionState,
+    order_by: Option<&[usize]>,
+    rusqlite_error_class: Option<ErrorClass>,
 ) -> turso_core::Result<ExecutionContinuation> {
     let next = match (turso_res, rusqlite_res) {
         (Ok(v1), Ok(v2)) => {
@@ -210,8 +257,30 @@ fn compare_results(
                                         .into(),
                                 ));
                             }
+                            if let Some(order_by) = order_by
+                                && let (Some(turso_keys), Some(rusqlite_keys)) = (
+                                    sort_keys(turso_values, order_by),
+                                    sort_keys(rusqlite_values, order_by),
+                                )
+                                && turso_keys != rusqlite_keys
+                            {
+                                tracing::error!(
+                                    "rows from limbo and rusqlite are not in the same order"
+                                );
+                                let diff = SimpleDiff::from_str(
+                                    &format!("{turso_keys:#?}"),
+                                    &format!("{rusqlite_keys:#?}"),
+                                    "turso",
+                                    "rusqlite",
+                                );
+                                tracing::error!(%diff);
+                                return Err(turso_core::LimboError::InternalError(
+                                    "row order of limbo and rusqlite results does not match".into(),
+                                ));
+                            }
                         }
                         (Err(turso_err), Err(rusqlite_err)) => {
+                            compare_error_classes(turso_err, rusqlite_err, rusqlite_error_class)?;
                             tracing::warn!("limbo and rusqlite both fail, requires manual check");
                             tracing::warn!("limbo error {}", turso_err);
                             tracing::warn!("rusqlite error {}", rusqlite_err);
@@ -259,6 +328,7 @@ fn compare_results(
             return Err(err);
         }
         (Err(err), Err(err_rusqlite)) => {
+            compare_error_classes(&err, &err_rusqlite, rusqlite_error_class)?;
             tracing::error!("limbo and rusqlite both fail, requires manual check");
             tracing::error!("limbo error {}", err);
             tracing::error!("rusqlite error {}", err_rusqlite);
@@ -272,6 +342,114 @@ fn compare_results(
     Ok(next)
 }
 
+/// Fails if limbo's error is not of the class of the error rusqlite reported.
+fn compare_error_classes(
+    turso_err: &turso_core::LimboError,
+    rusqlite_err: &turso_core::LimboError,
+    rusqlite_error_class: Option<ErrorClass>,
+) -> turso_core::Result<()> {
+    let Some(rusqlite_class) = rusqlite_error_class else {
+        return Ok(());
+    };
+    if turso_err.class() != rusqlite_class {
+        tracing::error!("limbo and rusqlite fail with errors of different classes");
+        tracing::error!("limbo error {} ({:?})", turso_err, turso_err.class());
+        tracing::error!("rusqlite error {} ({:?})", rusqlite_err, rusqlite_class);
+        return Err(turso_core::LimboError::InternalError(
+            "limbo and rusqlite fail with errors of different classes".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Compares the number of rows a write changed, as `changes()` reports it.
+fn compare_changes(
+    env: &SimulatorEnv,
+    rusqlite_env: &SimulatorEnv,
+    interaction: &Interaction,
+    turso_conn_state: &ConnectionState,
+    rusqlite_conn_state: &ConnectionState,
+) -> turso_core::Result<()> {
+    let is_write = matches!(
+        &interaction.interaction,
+        InteractionType::Query(Query::Insert(_) | Query::Update(_) | Query::Delete(_))
+    );
+    let succeeded = matches!(
+        (
+            turso_conn_state.stack.last(),
+            rusqlite_conn_state.stack.last()
+        ),
+        (Some(Ok(_)), Some(Ok(_)))
+    );
+    if !is_write || !succeeded {
+        return Ok(());
+    }
+    let connection_index = interaction.connection_index;
+    let (
+        SimConnection::LimboConnection(turso_conn),
+        SimConnection::SQLiteConnection(rusqlite_conn),
+    ) = (
+        &env.connections[connection_index],
+        &rusqlite_env.connections[connection_index],
+    )
+    else {
+        return Ok(());
+    };
+    let turso_changes = turso_conn.changes();
+    let rusqlite_changes = rusqlite_conn.changes() as i64;
+    if turso_changes != rusqlite_changes {
+        tracing::error!(
+            "limbo changed {turso_changes} rows but rusqlite changed {rusqlite_changes} rows"
+        );
+        return Err(turso_core::LimboError::InternalError(
+            "changes() of limbo and rusqlite do not match".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The positions, in the rows `select` returns, of the columns its `ORDER BY` sorts on.
+/// Rows that sort equal may come in any order, but their sort keys come in the same
+/// order from both databases. `None` when the columns cannot be located in the rows.
+fn order_by_positions(select: &Select, tables: &[Table]) -> Option<Vec<usize>> {
+    let inner = &select.body.select;
+    let order_by = inner.order_by.as_ref()?;
+    if !select.body.compounds.is_empty()
+        || !matches!(inner.columns.as_slice(), [ResultColumn::Star])
+    {
+        return None;
+    }
+    let from = inner.from.as_ref()?;
+    let SelectTable::Table(first) = &from.table else {
+        return None;
+    };
+    // `*` lists the columns of every table, in the order they are joined
+    let mut offset = 0;
+    let mut offsets = Vec::new();
+    for name in std::iter::once(first).chain(from.joins.iter().map(|join| &join.table)) {
+        let table = tables.iter().find(|table| &table.name == name)?;
+        offsets.push((offset, table));
+        offset += table.columns.len();
+    }
+    order_by
+        .columns
+        .iter()
+        .map(|(column, _)| {
+            let (table_name, column) = column.rsplit_once('.')?;
+            let (offset, table) = offsets.iter().find(|(_, table)| table.name == table_name)?;
+            let position = table.columns.iter().position(|c| c.name == column)?;
+            Some(offset + position)
+        })
+        .collect()
+}
+
+/// The values at `positions` of every row, or `None` if a row is too short.
+fn sort_keys<'a>(rows: &'a [Vec<SimValue>], positions: &[usize]) -> Option<Vec<Vec<&'a SimValue>>> {
+    rows.iter()
+        .map(|row| positions.iter().map(|&i| row.get(i)).collect())
+        .collect()
+}
+
 fn count_rows(values: &[Vec<SimValue>]) -> BTreeMap<&Vec<SimValue>, i32> {
     let mut counter = BTreeMap::new();
     for row in values.iter() {
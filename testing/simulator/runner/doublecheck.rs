@@ -3,6 +3,8 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use turso_core::{Connection, Database, Value};
+
 use crate::{
     model::interactions::{ConnectionState, InteractionPlanIterator, InteractionPlanState},
     runner::execution::ExecutionContinuation,
@@ -53,6 +55,10 @@ pub fn run_simulation(
         doublecheck_env.clear_poison();
         let doublecheck_env = doublecheck_env.lock().unwrap();
 
+        if result.error.is_none() {
+            result.error = compare_table_digests(&env, &doublecheck_env).err();
+        }
+
         // Check if the database files are the same
         let db = fs::read(env.get_db_path()).expect("should be able to read default database file");
         let doublecheck_db = fs::read(doublecheck_env.get_db_path())
@@ -78,6 +84,65 @@ pub fn run_simulation(
     result
 }
 
+/// Compare the `hash_table()` digest of every committed table in both databases, so
+/// that a difference in their content is reported with the table it is in.
+fn compare_table_digests(
+    env: &SimulatorEnv,
+    doublecheck_env: &SimulatorEnv,
+) -> turso_core::Result<()> {
+    let (Some(db), Some(doublecheck_db)) = (&env.db, &doublecheck_env.db) else {
+        return Ok(());
+    };
+    let conn = connect_to_compare(env, db)?;
+    let doublecheck_conn = connect_to_compare(doublecheck_env, doublecheck_db)?;
+    for table in &env.committed_tables {
+        let digest = table_digest(&conn, &table.name)?;
+        let doublecheck_digest = table_digest(&doublecheck_conn, &table.name)?;
+        if digest != doublecheck_digest {
+            tracing::error!(
+                "table {} has different contents: limbo digest {:?}, doublecheck digest {:?}",
+                table.name,
+                digest,
+                doublecheck_digest
+            );
+            return Err(turso_core::LimboError::InternalError(format!(
+                "table {} has different contents in limbo and doublecheck",
+                table.name
+            )));
+        }
+    }
+    conn.close()?;
+    doublecheck_conn.close()?;
+    Ok(())
+}
+
+/// A new connection to `db`, with the databases of the simulation attached. Unlike the
+/// connections of the plan, it is never in a transaction, so it only sees what was
+/// committed.
+fn connect_to_compare(
+    env: &SimulatorEnv,
+    db: &Arc<Database>,
+) -> turso_core::Result<Arc<Connection>> {
+    let conn = db.connect()?;
+    for name in &env.attached_dbs {
+        let aux_path = env.get_aux_db_path(name);
+        conn.execute(format!("ATTACH '{}' AS {name}", aux_path.display()))?;
+    }
+    Ok(conn)
+}
+
+fn table_digest(conn: &Arc<Connection>, table: &str) -> turso_core::Result<Value> {
+    let mut rows = conn
+        .query(format!("SELECT hash_table('{table}')"))?
+        .expect("SELECT should return rows");
+    let mut digest = Value::Null;
+    rows.run_with_row_callback(|row| {
+        digest = row.get_value(0).clone();
+        Ok(())
+    })?;
+    Ok(digest)
+}
+
 pub(crate) fn execute_plans(
     env: Arc<Mutex<SimulatorEnv>>,
     doublecheck_env: Arc<Mutex<SimulatorEnv>>,
@@ -5,7 +5,7 @@ use std::ops::{Deref, DerefMut};
 use std::panic::UnwindSafe;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use turso_core::SqliteDialect;
+use turso_core::{ErrorClass, SqliteDialect};
 
 use bitmaps::Bitmap;
 use garde::Validate;
@@ -20,10 +20,12 @@ use turso_core::Database;
 use turso_parser::ast::ColumnConstraint;
 
 use crate::generation::Shadow;
+use crate::generation::coverage::CoverageGuide;
 use crate::model::Query;
 use crate::profiles::Profile;
 use crate::runner::SimIO;
 use crate::runner::cli::IoBackend;
+use crate::runner::fault::FaultSimIO;
 use crate::runner::io::SimulatorIO;
 use crate::runner::memory::io::MemorySimIO;
 const DEFAULT_CACHE_SIZE: usize = 2000;
@@ -31,7 +33,7 @@ use super::cli::SimulatorCLI;
 
 /// Pre-create attached DB files with MVCC journal mode so that journal modes
 /// are compatible when ATTACH happens later during simulation.
-fn enable_mvcc_on_attached_dbs(io: &Arc<dyn SimIO>, aux_paths: impl Iterator<Item = PathBuf>) {
+fn enable_mvcc_on_attached_dbs(io: &Arc<FaultSimIO>, aux_paths: impl Iterator<Item = PathBuf>) {
     for aux_path in aux_paths {
         let aux_db = Database::open_file_with_flags(
             io.clone(),
@@ -1123,6 +1125,196 @@ mod tests {
         );
     }
 
+    #[test]
+    fn update_set_columns_read_the_old_row() {
+        use sql_generation::model::query::predicate::Predicate;
+        use sql_generation::model::query::update::{SetValue, Update};
+
+        // SET a = b, b = a swaps the two columns, since both assignments read the row
+        // as it was before the update. g = a + b is recomputed, and stays the same.
+        let mut commited_tables = vec![table_with_unique_generated_column(&[(1, 5), (2, 1)])];
+        let mut transaction_tables = None;
+        let mut sequences = Vec::new();
+        let mut tables = shadow_tables_mut(
+            &mut commited_tables,
+            &mut transaction_tables,
+            &mut sequences,
+        );
+
+        let update = Update {
+            table: "t".to_string(),
+            set_values: vec![
+                ("a".to_string(), SetValue::Column("b".to_string())),
+                ("b".to_string(), SetValue::Column("a".to_string())),
+            ],
+            predicate: Predicate::true_(),
+        };
+        assert_eq!(update.to_string(), "UPDATE t SET a = b, b = a WHERE TRUE");
+        update.shadow(&mut tables).unwrap();
+        let int = |v: i64| SimValue(turso_core::Value::from_i64(v));
+        assert_eq!(
+            commited_tables[0].rows,
+            vec![vec![int(5), int(6), int(1)], vec![int(1), int(3), int(2)]]
+        );
+    }
+
+    #[test]
+    fn delete_limit_removes_the_first_rows_in_rowid_order() {
+        use sql_generation::model::query::Delete;
+        use sql_generation::model::query::predicate::Predicate;
+        use sql_generation::model::table::{Column, ColumnType};
+
+        // id is the rowid, so the rows inserted as 3, 1, 2 are scanned as 1, 2, 3
+        let int = |v: i64| SimValue(turso_core::Value::from_i64(v));
+        let mut commited_tables = vec![Table {
+            name: "t".to_string(),
+            columns: vec![Column {
+                name: "id".to_string(),
+                column_type: ColumnType::Integer,
+                constraints: vec![ColumnConstraint::PrimaryKey {
+                    order: None,
+                    conflict_clause: None,
+                    auto_increment: false,
+                }],
+            }],
+            rows: vec![vec![int(3)], vec![int(1)], vec![int(2)]],
+            indexes: vec![],
+        }];
+        let mut transaction_tables = None;
+        let mut sequences = Vec::new();
+        let mut tables = shadow_tables_mut(
+            &mut commited_tables,
+            &mut transaction_tables,
+            &mut sequences,
+        );
+
+        let delete = Delete {
+            table: "t".to_string(),
+            predicate: Predicate::true_(),
+            limit: Some(2),
+        };
+        assert_eq!(delete.to_string(), "DELETE FROM t WHERE TRUE LIMIT 2");
+        delete.shadow(&mut tables).unwrap();
+        assert_eq!(commited_tables[0].rows, vec![vec![int(3)]]);
+    }
+
+    #[test]
+    fn select_orders_then_applies_offset_and_limit() {
+        use sql_generation::model::query::Select;
+        use sql_generation::model::query::predicate::Predicate;
+        use sql_generation::model::query::select::OrderBy;
+        use turso_parser::ast::SortOrder;
+
+        let mut commited_tables = vec![table_with_unique_generated_column(&[
+            (1, 5),
+            (2, 1),
+            (3, 3),
+        ])];
+        let mut transaction_tables = None;
+        let mut sequences = Vec::new();
+        let mut tables = shadow_tables_mut(
+            &mut commited_tables,
+            &mut transaction_tables,
+            &mut sequences,
+        );
+
+        let mut select = Select::simple("t".to_string(), Predicate::true_());
+        select.body.select.order_by = Some(OrderBy {
+            columns: vec![("t.b".to_string(), SortOrder::Desc)],
+        });
+        select.limit = Some(2);
+        select.offset = Some(1);
+        assert_eq!(
+            select.to_string(),
+            "SELECT * FROM t WHERE TRUE ORDER BY t.b DESC LIMIT 2 OFFSET 1"
+        );
+        let int = |v: i64| SimValue(turso_core::Value::from_i64(v));
+        assert_eq!(
+            select.shadow(&mut tables).unwrap(),
+            vec![vec![int(3), int(6), int(3)], vec![int(2), int(3), int(1)]]
+        );
+    }
+
+    #[test]
+    fn select_groups_rows_and_keeps_the_groups_passing_having() {
+        use sql_generation::model::query::Select;
+        use sql_generation::model::query::predicate::Predicate;
+        use sql_generation::model::query::select::{
+            Aggregate, AggregateFunc, GroupBy, Having, ResultColumn,
+        };
+        use turso_parser::ast::{Distinctness, Operator};
+
+        let mut commited_tables = vec![table_with_unique_generated_column(&[
+            (1, 5),
+            (2, 1),
+            (3, 5),
+            (4, 1),
+            (5, 5),
+        ])];
+        let mut transaction_tables = None;
+        let mut sequences = Vec::new();
+        let mut tables = shadow_tables_mut(
+            &mut commited_tables,
+            &mut transaction_tables,
+            &mut sequences,
+        );
+
+        let int = |v: i64| SimValue(turso_core::Value::from_i64(v));
+        let aggregate = |func, column: &str| {
+            ResultColumn::Aggregate(Aggregate {
+                func,
+                column: Some(column.to_string()),
+            })
+        };
+        let mut select = Select::single(
+            "t".to_string(),
+            vec![
+                ResultColumn::Column("t.b".to_string()),
+                ResultColumn::Aggregate(Aggregate::count_star()),
+                aggregate(AggregateFunc::Sum, "t.a"),
+                aggregate(AggregateFunc::Avg, "t.a"),
+                aggregate(AggregateFunc::Max, "t.a"),
+            ],
+            Predicate::true_(),
+            None,
+            Distinctness::All,
+        );
+        select.body.select.group_by = Some(GroupBy {
+            columns: vec!["t.b".to_string()],
+            having: Some(Having {
+                aggregate: Aggregate::count_star(),
+                operator: Operator::Greater,
+                value: int(2),
+            }),
+        });
+        assert_eq!(
+            select.shadow(&mut tables).unwrap(),
+            vec![vec![
+                int(5),
+                int(3),
+                int(9),
+                SimValue(turso_core::Value::from_f64(3.0)),
+                int(5)
+            ]]
+        );
+
+        // Without a GROUP BY, an aggregate returns a row even when nothing matches
+        let select = Select::single(
+            "t".to_string(),
+            vec![
+                ResultColumn::Aggregate(Aggregate::count_star()),
+                aggregate(AggregateFunc::Sum, "t.a"),
+            ],
+            Predicate::false_(),
+            None,
+            Distinctness::All,
+        );
+        assert_eq!(
+            select.shadow(&mut tables).unwrap(),
+            vec![vec![int(0), SimValue::NULL]]
+        );
+    }
+
     #[test]
     fn savepoint_inside_deferred_transaction_stays_open_on_release() {
         let mut commited_tables = Vec::new();
@@ -1171,7 +1363,7 @@ pub(crate) struct SimulatorEnv {
     pub(crate) opts: SimulatorOpts,
     pub profile: Profile,
     pub(crate) connections: Vec<SimConnection>,
-    pub(crate) io: Arc<dyn SimIO>,
+    pub(crate) io: Arc<FaultSimIO>,
     pub(crate) db: Option<Arc<Database>>,
     pub(crate) rng: ChaCha8Rng,
 
@@ -1195,6 +1387,11 @@ pub(crate) struct SimulatorEnv {
     pub(crate) attached_dbs: Vec<String>,
     /// Sequences are global objects, not affected by transactions/savepoints
     pub sequences: Vec<ShadowSequence>,
+    /// Class of the SQLite error the last query on a SQLite connection failed with, as
+    /// the error itself is turned into a [turso_core::LimboError]
+    pub(crate) last_sqlite_error: Option<ErrorClass>,
+    /// Bytecode coverage fed back to generation, with `--coverage-guided`
+    pub(crate) coverage: Option<CoverageGuide>,
 }
 
 impl UnwindSafe for SimulatorEnv {}
@@ -1221,6 +1418,8 @@ impl SimulatorEnv {
             committed_tables: self.committed_tables.clone(),
             attached_dbs: self.attached_dbs.clone(),
             sequences: self.sequences.clone(),
+            last_sqlite_error: None,
+            coverage: self.coverage.clone(),
         }
     }
 
@@ -1231,7 +1430,7 @@ impl SimulatorEnv {
 
         let latency_prof = &self.profile.io.latency;
 
-        let io: Arc<dyn SimIO> = match self.io_backend {
+        let sim: Arc<dyn SimIO> = match self.io_backend {
             IoBackend::Memory => Arc::new(MemorySimIO::new(
                 self.opts.seed,
                 self.opts.page_size,
@@ -1251,6 +1450,7 @@ impl SimulatorEnv {
                 .unwrap(),
             ),
         };
+        let io = Arc::new(FaultSimIO::new(sim, self.opts.page_size));
 
         // Remove existing database file
         let db_path = self.get_db_path();
@@ -1337,6 +1537,10 @@ impl SimulatorEnv {
         self.paths.plan(&self.type_, &self.phase)
     }
 
+    pub(crate) fn get_plan_json_path(&self) -> PathBuf {
+        self.paths.plan_json(&self.type_, &self.phase)
+    }
+
     pub(crate) fn clone_as(&self, simulation_type: SimulationType) -> Self {
         let mut env = self.clone_without_connections();
         env.type_ = simulation_type;
@@ -1396,9 +1600,11 @@ impl SimulatorEnv {
             seed,
             ticks: usize::MAX,
             disable_select_optimizer: cli_opts.disable_select_optimizer,
+            disable_index_consistency: cli_opts.disable_index_consistency,
             disable_insert_values_select: cli_opts.disable_insert_values_select,
             disable_double_create_failure: cli_opts.disable_double_create_failure,
             disable_select_limit: cli_opts.disable_select_limit,
+            disable_select_aggregate: cli_opts.disable_select_aggregate,
             disable_delete_select: cli_opts.disable_delete_select,
             disable_drop_select: cli_opts.disable_drop_select,
             disable_where_true_false_null: cli_opts.disable_where_true_false_null,
@@ -1407,6 +1613,13 @@ impl SimulatorEnv {
             disable_savepoint_rollback: cli_opts.disable_savepoint_rollback,
             disable_fsync_no_wait: cli_opts.disable_fsync_no_wait,
             disable_faulty_query: cli_opts.disable_faulty_query,
+            disable_scheduled_io_fault: cli_opts.disable_scheduled_io_fault,
+            disable_interleaving: cli_opts.disable_interleaving,
+            disable_checkpoint_interleaving: cli_opts.disable_checkpoint_interleaving,
+            disable_transaction_atomicity: cli_opts.disable_transaction_atomicity,
+            coverage_guided: cli_opts.coverage_guided,
+            power_loss: cli_opts.power_loss,
+            allocation_faults: cli_opts.allocation_faults,
             page_size: 4096, // TODO: randomize this too
             max_interactions: rng.random_range(cli_opts.minimum_tests..=cli_opts.maximum_tests),
             max_time_simulation: cli_opts.maximum_time,
@@ -1488,7 +1701,7 @@ impl SimulatorEnv {
         let latency_prof = &profile.io.latency;
 
         let io_backend = cli_opts.io_backend;
-        let io: Arc<dyn SimIO> = match io_backend {
+        let sim: Arc<dyn SimIO> = match io_backend {
             IoBackend::Memory => Arc::new(MemorySimIO::new(
                 opts.seed,
                 opts.page_size,
@@ -1508,6 +1721,7 @@ impl SimulatorEnv {
                 .unwrap(),
             ),
         };
+        let io = Arc::new(FaultSimIO::new(sim, opts.page_size));
 
         let db = match Database::open_file_with_flags(
             io.clone(),
@@ -1545,6 +1759,7 @@ impl SimulatorEnv {
         let connections = (0..profile.max_connections)
             .map(|_| SimConnection::Disconnected)
             .collect::<Vec<_>>();
+        let coverage = opts.coverage_guided.then(CoverageGuide::start);
 
         SimulatorEnv {
             opts,
@@ -1563,6 +1778,8 @@ impl SimulatorEnv {
             connection_last_query: Bitmap::new(),
             attached_dbs,
             sequences: Vec::new(),
+            last_sqlite_error: None,
+            coverage,
         }
     }
 
@@ -1759,9 +1976,11 @@ pub(crate) struct SimulatorOpts {
     pub(crate) ticks: usize,
 
     pub(crate) disable_select_optimizer: bool,
+    pub(crate) disable_index_consistency: bool,
     pub(crate) disable_insert_values_select: bool,
     pub(crate) disable_double_create_failure: bool,
     pub(crate) disable_select_limit: bool,
+    pub(crate) disable_select_aggregate: bool,
     pub(crate) disable_delete_select: bool,
     pub(crate) disable_drop_select: bool,
     pub(crate) disable_where_true_false_null: bool,
@@ -1769,6 +1988,13 @@ pub(crate) struct SimulatorOpts {
     pub(crate) disable_savepoint_rollback: bool,
     pub(crate) disable_fsync_no_wait: bool,
     pub(crate) disable_faulty_query: bool,
+    pub(crate) disable_scheduled_io_fault: bool,
+    pub(crate) disable_interleaving: bool,
+    pub(crate) disable_checkpoint_interleaving: bool,
+    pub(crate) disable_transaction_atomicity: bool,
+    pub(crate) coverage_guided: bool,
+    pub(crate) power_loss: bool,
+    pub(crate) allocation_faults: bool,
     pub(crate) disable_reopen_database: bool,
     pub(crate) disable_integrity_check: bool,
 
@@ -1831,6 +2057,16 @@ impl Paths {
         self.path_(type_, phase).with_extension("sql")
     }
 
+    /// The plan saved as versioned JSON, next to [Paths::plan].
+    pub(crate) fn plan_json(&self, type_: &SimulationType, phase: &SimulationPhase) -> PathBuf {
+        self.path_(type_, phase).with_extension("json")
+    }
+
+    /// One of the rotating files a soak run moves compacted plan history to.
+    pub(crate) fn soak_segment(&self, segment: usize) -> PathBuf {
+        self.base.join(format!("soak.{segment}.sql"))
+    }
+
     pub fn delete_all_files(&self) {
         if self.base.exists() {
             let res = std::fs::remove_dir_all(&self.base);
@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex};
 use rand::Rng;
 use sql_generation::model::table::SimValue;
 use tracing::instrument;
-use turso_core::{Connection, LimboError, Result, Value};
+use turso_core::{Connection, ErrorClass, LimboError, Result, Value};
 
 /// Checks if an error is a recoverable error that should not fail the simulation.
 /// These errors indicate expected behavior, not bugs.
@@ -56,7 +56,7 @@ use crate::{
 
 use super::env::{SimConnection, SimulatorEnv};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Execution {
     pub connection_index: usize,
     pub interaction_index: usize,
@@ -85,7 +85,6 @@ impl ExecutionHistory {
 }
 
 pub struct ExecutionResult {
-    #[expect(dead_code)]
     pub history: ExecutionHistory,
     pub error: Option<LimboError>,
 }
@@ -127,7 +126,14 @@ pub(crate) fn execute_interactions(
         last_execution.connection_index = connection_index;
         last_execution.interaction_index = state.interaction_pointer;
         // Execute the interaction for the selected connection
-        match execute_plan(&mut env, &interaction, conn_state) {
+        let result = execute_plan(&mut env, &interaction, conn_state);
+        if let Some(coverage) = env.coverage.as_mut() {
+            coverage.observe(
+                interaction.id(),
+                interaction.property_meta.map(|meta| meta.property),
+            );
+        }
+        match result {
             Ok(ExecutionContinuation::NextInteraction) => {
                 state.interaction_pointer += 1;
                 let Some(new_interaction) = plan.next(&mut env) else {
@@ -249,7 +255,7 @@ pub fn execute_interaction_turso(
                         }
                         ExecutionContinuation::NextInteractionOutsideThisProperty
                     }
-                    LimboError::Constraint(_) => {
+                    err if err.class() == ErrorClass::Constraint => {
                         let shadow_result =
                             interaction.shadow(&mut env.get_conn_tables_mut(connection_index));
                         if shadow_result.is_ok() {
@@ -391,11 +397,13 @@ fn execute_interaction_rusqlite(
             tracing::debug!("{}", interaction);
             let raw_result = execute_query_rusqlite(conn, query, &attached_dbs);
 
-            let is_constraint_error = matches!(
-                &raw_result,
-                Err(rusqlite::Error::SqliteFailure(err, _))
-                    if err.code == rusqlite::ErrorCode::ConstraintViolation
-            );
+            env.last_sqlite_error = match &raw_result {
+                Err(rusqlite::Error::SqliteFailure(err, _)) => {
+                    ErrorClass::from_sqlite_code(err.extended_code)
+                }
+                _ => None,
+            };
+            let is_constraint_error = env.last_sqlite_error == Some(ErrorClass::Constraint);
 
             let results = raw_result.map_err(|e| {
                 turso_core::LimboError::InternalError(format!("error executing query: {e}"))
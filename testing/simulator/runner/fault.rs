@@ -0,0 +1,135 @@
+//! The IO of a simulation. Every backend is wrapped in the [FaultIO] of Turso, which
+//! remembers what the writes overwrite until the files are synced, so that the
+//! plan can inject faults and cut the power in the same way for every backend.
+
+use std::sync::Arc;
+
+use turso_core::{
+    Clock, Fault, FaultIO, FaultOp, FaultSchedule, File, IO, MonotonicInstant, OpenFlags, Result,
+    WallClockInstant,
+};
+
+use crate::model::interactions::IoFault;
+use crate::runner::SimIO;
+
+pub(crate) struct FaultSimIO {
+    /// The backend, for what only the simulator knows how to do.
+    sim: Arc<dyn SimIO>,
+    io: FaultIO,
+    page_size: usize,
+}
+
+impl FaultSimIO {
+    pub(crate) fn new(sim: Arc<dyn SimIO>, page_size: usize) -> Self {
+        let io = FaultIO::new(sim.clone(), FaultSchedule::new());
+        Self { sim, io, page_size }
+    }
+
+    pub(crate) fn inject_fault(&self, fault: bool) {
+        self.sim.inject_fault(fault);
+    }
+
+    pub(crate) fn inject_fault_selective(&self, faults: &[(&str, bool)]) {
+        self.sim.inject_fault_selective(faults);
+    }
+
+    /// Schedule `fault` on the database at `db_path`, its WAL and its log. It fires
+    /// once, on the next operation of one of them that it applies to. A partial write
+    /// writes the first half of a page.
+    pub(crate) fn schedule_fault(&self, db_path: &str, fault: IoFault) {
+        let (op, fault) = match fault {
+            IoFault::FailedWrite => (FaultOp::Write, Fault::Error),
+            IoFault::PartialWrite => (FaultOp::Write, Fault::TornWrite(self.page_size / 2)),
+            IoFault::SyncError => (FaultOp::Sync, Fault::Error),
+        };
+        let files = database_files(db_path);
+        self.io.set_schedule(FaultSchedule::new().on_files(
+            &files.each_ref().map(String::as_str),
+            &[op],
+            1,
+            fault,
+        ));
+    }
+
+    /// Cut the power at the write or sync of the database at `db_path`, its WAL or its
+    /// log that comes after `ops` more of them. The writes that were not synced are
+    /// lost, except for the first half page of the last one to each file, and the
+    /// files opened before fail until they are opened again.
+    pub(crate) fn schedule_crash(&self, db_path: &str, ops: u32) {
+        let files = database_files(db_path);
+        self.io.set_schedule(FaultSchedule::new().on_files(
+            &files.each_ref().map(String::as_str),
+            &[FaultOp::Write, FaultOp::Sync],
+            u64::from(ops) + 1,
+            Fault::PowerLoss {
+                torn_bytes: self.page_size / 2,
+            },
+        ));
+    }
+
+    /// Cancel the scheduled fault or crash, returning whether it fired.
+    pub(crate) fn clear_scheduled_fault(&self) -> bool {
+        self.io.set_schedule(FaultSchedule::new()).fired()
+    }
+
+    pub(crate) fn print_stats(&self) {
+        self.sim.print_stats();
+    }
+
+    pub(crate) fn syncing(&self) -> bool {
+        self.sim.syncing()
+    }
+
+    pub(crate) fn close_files(&self) {
+        self.sim.close_files();
+    }
+
+    pub(crate) fn persist_files(&self) -> anyhow::Result<()> {
+        self.sim.persist_files()
+    }
+}
+
+/// The paths of the files of the database at `db_path`.
+fn database_files(db_path: &str) -> [String; 3] {
+    [
+        db_path.to_string(),
+        format!("{db_path}-wal"),
+        format!("{db_path}-log"),
+    ]
+}
+
+impl Clock for FaultSimIO {
+    fn current_time_monotonic(&self) -> MonotonicInstant {
+        self.io.current_time_monotonic()
+    }
+
+    fn current_time_wall_clock(&self) -> WallClockInstant {
+        self.io.current_time_wall_clock()
+    }
+}
+
+impl IO for FaultSimIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        self.io.open_file(path, flags, direct)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.io.remove_file(path)
+    }
+
+    fn file_id(&self, path: &str) -> Result<turso_core::io::FileId> {
+        self.io.file_id(path)
+    }
+
+    fn step(&self) -> Result<()> {
+        self.io.step()
+    }
+
+    fn generate_random_number(&self) -> i64 {
+        self.io.generate_random_number()
+    }
+
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.io.fill_bytes(dest);
+    }
+}
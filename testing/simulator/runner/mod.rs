@@ -1,14 +1,20 @@
+pub mod allocation_fault;
 pub mod bugbase;
+pub mod bundle;
 pub mod cli;
 pub mod clock;
 pub mod differential;
 pub mod doublecheck;
 pub mod env;
 pub mod execution;
+pub mod fault;
 #[expect(dead_code)]
 pub mod file;
 pub mod io;
 pub mod memory;
+pub mod parallel;
+pub mod replay;
+pub mod soak;
 
 pub const FAULT_ERROR_MSG: &str = "Injected Fault";
 
@@ -0,0 +1,239 @@
+//! Parallel mode: run many seeds at once on a pool of worker threads.
+//!
+//! Every seed first runs with shrinking off, so that the pool keeps exploring new
+//! seeds instead of spending its time on one bug. A seed that fails is promoted to
+//! the shrink queue, where it waits to run again with the regular shrinking and bug
+//! base. A worker that becomes free takes the next shrink before any new seed, so
+//! bugs are shrunk while the rest of the seeds are still running.
+//!
+//! Each seed writes its files to a directory of its own, and a line of progress is
+//! printed every time a worker finishes a run.
+
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Condvar, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Job {
+    /// Run a new seed, without shrinking.
+    Explore { seed: u64 },
+    /// Run a seed that failed again, shrinking its plan.
+    Shrink { seed: u64 },
+}
+
+impl Job {
+    fn seed(&self) -> u64 {
+        match self {
+            Job::Explore { seed } | Job::Shrink { seed } => *seed,
+        }
+    }
+}
+
+/// A seed that failed, with the error of its first run.
+#[derive(Debug, Clone)]
+pub(crate) struct Failure {
+    pub(crate) seed: u64,
+    pub(crate) error: String,
+    /// Whether the shrinking run failed too, `None` until it finished.
+    pub(crate) reproduced: Option<bool>,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    seeds: VecDeque<u64>,
+    shrink: VecDeque<u64>,
+    total: usize,
+    /// Jobs taken that did not finish yet. While an exploration runs, it may still
+    /// promote its seed, so the queue is not done.
+    running: usize,
+    explored: usize,
+    failures: Vec<Failure>,
+}
+
+/// The jobs of the pool, shared by its workers.
+#[derive(Debug)]
+pub(crate) struct JobQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+impl JobQueue {
+    pub(crate) fn new(seeds: impl IntoIterator<Item = u64>) -> Self {
+        let seeds: VecDeque<u64> = seeds.into_iter().collect();
+        Self {
+            state: Mutex::new(QueueState {
+                total: seeds.len(),
+                seeds,
+                ..Default::default()
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Take the next job, shrinks first. Waits while the queue is empty but some job
+    /// is still running, and returns `None` once there is nothing left to run.
+    pub(crate) fn take(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let job = match state.shrink.pop_front() {
+                Some(seed) => Some(Job::Shrink { seed }),
+                None => state.seeds.pop_front().map(|seed| Job::Explore { seed }),
+            };
+            if job.is_some() {
+                state.running += 1;
+                return job;
+            }
+            if state.running == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Record the result of `job`, promoting a failed exploration to the shrink queue,
+    /// and print the progress of the pool.
+    pub(crate) fn finish(&self, job: Job, result: anyhow::Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        let seed = job.seed();
+        let status = match (job, result) {
+            (Job::Explore { .. }, Ok(())) => {
+                state.explored += 1;
+                "passed".to_string()
+            }
+            (Job::Explore { .. }, Err(err)) => {
+                state.explored += 1;
+                state.failures.push(Failure {
+                    seed,
+                    error: format!("{err:?}"),
+                    reproduced: None,
+                });
+                state.shrink.push_back(seed);
+                format!("failed, queued for shrinking: {err}")
+            }
+            (Job::Shrink { .. }, result) => {
+                let reproduced = result.is_err();
+                if let Some(failure) = state
+                    .failures
+                    .iter_mut()
+                    .find(|failure| failure.seed == seed)
+                {
+                    failure.reproduced = Some(reproduced);
+                }
+                if reproduced {
+                    "shrunk".to_string()
+                } else {
+                    "did not fail again while shrinking".to_string()
+                }
+            }
+        };
+        println!(
+            "[{}/{}] seed {seed} {status} ({} failed, {} waiting to shrink, {} running)",
+            state.explored,
+            state.total,
+            state.failures.len(),
+            state.shrink.len(),
+            state.running,
+        );
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    /// The seeds that failed, once every job finished.
+    pub(crate) fn into_failures(self) -> Vec<Failure> {
+        self.state.into_inner().unwrap().failures
+    }
+}
+
+/// Run `seeds` on `jobs` worker threads, calling `run` for every job, and return the
+/// seeds that failed.
+pub(crate) fn run_pool(
+    seeds: impl IntoIterator<Item = u64>,
+    jobs: NonZeroUsize,
+    run: impl Fn(Job) -> anyhow::Result<()> + Sync,
+) -> Vec<Failure> {
+    let queue = JobQueue::new(seeds);
+    std::thread::scope(|scope| {
+        for worker in 0..jobs.get() {
+            let queue = &queue;
+            let run = &run;
+            std::thread::Builder::new()
+                .name(format!("simulator-worker-{worker}"))
+                .spawn_scoped(scope, move || {
+                    while let Some(job) = queue.take() {
+                        // a panic that escapes the run still fails its seed, and must not
+                        // take the worker down with it
+                        let result = std::panic::catch_unwind(AssertUnwindSafe(|| run(job)))
+                            .unwrap_or_else(|_| {
+                                Err(anyhow::anyhow!("seed {} panicked", job.seed()))
+                            });
+                        queue.finish(job, result);
+                    }
+                })
+                .expect("should be able to spawn a simulator worker");
+        }
+    });
+    queue.into_failures()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_seeds_are_shrunk_before_new_ones_run() {
+        let queue = JobQueue::new([1, 2, 3]);
+        assert_eq!(queue.take(), Some(Job::Explore { seed: 1 }));
+        assert_eq!(queue.take(), Some(Job::Explore { seed: 2 }));
+        queue.finish(Job::Explore { seed: 2 }, Err(anyhow::anyhow!("boom")));
+        assert_eq!(queue.take(), Some(Job::Shrink { seed: 2 }));
+        assert_eq!(queue.take(), Some(Job::Explore { seed: 3 }));
+        queue.finish(Job::Explore { seed: 1 }, Ok(()));
+        queue.finish(Job::Shrink { seed: 2 }, Err(anyhow::anyhow!("boom")));
+        queue.finish(Job::Explore { seed: 3 }, Ok(()));
+        assert_eq!(queue.take(), None);
+
+        let failures = queue.into_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].seed, 2);
+        assert_eq!(failures[0].reproduced, Some(true));
+    }
+
+    #[test]
+    fn pools_run_every_seed_and_shrink_the_failing_ones() {
+        let ran = Mutex::new(Vec::new());
+        let failures = run_pool(0..20, NonZeroUsize::new(4).unwrap(), |job| {
+            ran.lock().unwrap().push(job);
+            match job {
+                Job::Explore { seed } if seed % 5 == 0 => Err(anyhow::anyhow!("seed {seed}")),
+                Job::Shrink { seed: 0 } => Err(anyhow::anyhow!("seed 0")),
+                Job::Shrink { seed: 10 } => panic!("shrinking panicked"),
+                _ => Ok(()),
+            }
+        });
+
+        let mut ran = ran.into_inner().unwrap();
+        ran.sort_by_key(|job| (matches!(job, Job::Shrink { .. }), job.seed()));
+        let mut expected = (0..20)
+            .map(|seed| Job::Explore { seed })
+            .collect::<Vec<_>>();
+        expected.extend([0, 5, 10, 15].map(|seed| Job::Shrink { seed }));
+        assert_eq!(ran, expected);
+
+        let mut failures = failures
+            .into_iter()
+            .map(|failure| (failure.seed, failure.reproduced))
+            .collect::<Vec<_>>();
+        failures.sort();
+        assert_eq!(
+            failures,
+            [
+                (0, Some(true)),
+                (5, Some(false)),
+                (10, Some(true)),
+                (15, Some(false))
+            ]
+        );
+    }
+}
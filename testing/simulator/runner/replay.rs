@@ -0,0 +1,168 @@
+//! Replay mode: re-run a bug from the bug base and report where it stops following
+//! its recorded history.
+//!
+//! A failing run writes the `(connection, interaction)` pair of every step it took to
+//! `history.txt`, one step per line. Replaying the bug regenerates its plan from the
+//! same seed and CLI options, so the RNG, the IO schedule and the simulated clock all
+//! start from the same state. The steps of the replay are then compared with the
+//! recorded ones, and the first step at which they differ is where the behavior of the
+//! database, or of the simulator itself, changed since the bug was recorded.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, anyhow};
+
+use crate::runner::execution::{Execution, ExecutionHistory};
+
+/// Write the steps of `history` to `path`.
+pub(crate) fn write_history(path: &Path, history: &ExecutionHistory) -> anyhow::Result<()> {
+    let f = std::fs::File::create(path)
+        .with_context(|| format!("should be able to create {}", path.display()))?;
+    let mut f = std::io::BufWriter::new(f);
+    for execution in history.history.iter() {
+        writeln!(
+            f,
+            "{} {}",
+            execution.connection_index, execution.interaction_index,
+        )?;
+    }
+    f.flush()?;
+    Ok(())
+}
+
+/// Read the steps written by [write_history].
+pub(crate) fn read_history(path: &Path) -> anyhow::Result<Vec<Execution>> {
+    let history = std::fs::read_to_string(path)
+        .with_context(|| format!("should be able to read {}", path.display()))?;
+    history
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            let parse = |field: Option<&str>| field.and_then(|field| field.parse::<usize>().ok());
+            let mut fields = line.split_whitespace();
+            match (parse(fields.next()), parse(fields.next()), fields.next()) {
+                (Some(connection_index), Some(interaction_index), None) => {
+                    Ok(Execution::new(connection_index, interaction_index))
+                }
+                _ => Err(anyhow!(
+                    "malformed step on line {} of {}: '{line}'",
+                    idx + 1,
+                    path.display()
+                )),
+            }
+        })
+        .collect()
+}
+
+/// The first step at which a replay stopped following the recorded history. A missing
+/// step means that one of the runs ended there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Divergence {
+    pub(crate) step: usize,
+    pub(crate) recorded: Option<Execution>,
+    pub(crate) replayed: Option<Execution>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |execution: Option<Execution>| match execution {
+            Some(execution) => format!(
+                "interaction {} on connection {}",
+                execution.interaction_index, execution.connection_index
+            ),
+            None => "the end of the run".to_string(),
+        };
+        write!(
+            f,
+            "step {} diverged: recorded {}, replayed {}",
+            self.step,
+            describe(self.recorded),
+            describe(self.replayed)
+        )
+    }
+}
+
+/// The first step at which `replayed` differs from `recorded`, if any.
+pub(crate) fn divergence(recorded: &[Execution], replayed: &[Execution]) -> Option<Divergence> {
+    let step = recorded
+        .iter()
+        .zip(replayed)
+        .position(|(recorded, replayed)| recorded != replayed)
+        .unwrap_or(recorded.len().min(replayed.len()));
+    if step == recorded.len() && step == replayed.len() {
+        return None;
+    }
+    Some(Divergence {
+        step,
+        recorded: recorded.get(step).copied(),
+        replayed: replayed.get(step).copied(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(steps: &[(usize, usize)]) -> Vec<Execution> {
+        steps
+            .iter()
+            .map(|&(connection, interaction)| Execution::new(connection, interaction))
+            .collect()
+    }
+
+    #[test]
+    fn identical_histories_do_not_diverge() {
+        let history = steps(&[(0, 0), (1, 1), (0, 2)]);
+        assert_eq!(divergence(&history, &history), None);
+        assert_eq!(divergence(&[], &[]), None);
+    }
+
+    #[test]
+    fn reports_the_first_differing_step() {
+        let recorded = steps(&[(0, 0), (1, 1), (0, 2), (1, 3)]);
+        let replayed = steps(&[(0, 0), (1, 1), (1, 2), (1, 3)]);
+        assert_eq!(
+            divergence(&recorded, &replayed),
+            Some(Divergence {
+                step: 2,
+                recorded: Some(Execution::new(0, 2)),
+                replayed: Some(Execution::new(1, 2)),
+            })
+        );
+    }
+
+    #[test]
+    fn a_shorter_run_diverges_where_it_ends() {
+        let recorded = steps(&[(0, 0), (1, 1), (0, 2)]);
+        let replayed = steps(&[(0, 0)]);
+        assert_eq!(
+            divergence(&recorded, &replayed),
+            Some(Divergence {
+                step: 1,
+                recorded: Some(Execution::new(1, 1)),
+                replayed: None,
+            })
+        );
+        assert_eq!(
+            divergence(&replayed, &recorded).map(|divergence| divergence.step),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn histories_round_trip_through_their_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.txt");
+        let history = ExecutionHistory {
+            history: steps(&[(0, 0), (2, 1), (1, 7)]),
+        };
+        write_history(&path, &history).unwrap();
+        assert_eq!(read_history(&path).unwrap(), history.history);
+
+        std::fs::write(&path, "0 0\n1\n").unwrap();
+        assert!(read_history(&path).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Soak mode: run a single seed for hours, sampling invariants along the way.
+//!
+//! A regular run stops after a few thousand interactions, which catches crashes
+//! but rarely slow leaks or drift between the database and the model. A soak run
+//! keeps generating interactions until its time budget is spent. Every
+//! `check_every` interactions, once no connection is inside a transaction, it
+//! pauses to:
+//!
+//! - verify the database file, with `PRAGMA integrity_check` or the cheaper
+//!   `PRAGMA quick_check`,
+//! - compare every table with the model, with an `AllTableHaveExpectedContent`
+//!   property that is added to the plan,
+//! - checkpoint the WAL, and check that the database file holds every page of the
+//!   database and that the freelist is smaller than the database,
+//! - compare the resident memory of the process with the configured ceiling.
+//!
+//! To keep the simulator's own footprint flat, only the last `history_window`
+//! interactions of the plan are kept in memory. Older ones are moved to a
+//! small ring of plan files next to the database, so a failure still comes with
+//! recent context even though the full plan can't be replayed or shrunk. A run that
+//! fails before its plan was ever compacted still has the full plan, which is then
+//! shrunk and added to the bug base like the plan of a regular run.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, anyhow};
+use turso_core::{Connection, VerifyLevel};
+
+use crate::generation::plan::PlanGenerator;
+use crate::model::interactions::{
+    ConnectionState, InteractionPlan, InteractionPlanIterator, Interactions, InteractionsType,
+};
+use crate::model::property::Property;
+use crate::runner::env::SimulatorEnv;
+use crate::runner::execution::{ExecutionContinuation, execute_plan};
+
+/// Number of plan files compacted history rotates through.
+const SOAK_PLAN_SEGMENTS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct SoakOptions {
+    pub duration: Duration,
+    pub check_every: u64,
+    pub memory_ceiling_bytes: Option<u64>,
+    pub history_window: usize,
+    pub verify_level: VerifyLevel,
+}
+
+#[derive(Debug, Default)]
+struct SoakStats {
+    interactions: u64,
+    checks: u64,
+    peak_rss: Option<u64>,
+    segments_written: usize,
+    peak_pages: Option<u64>,
+}
+
+pub fn run(
+    mut env: SimulatorEnv,
+    plan: &mut InteractionPlan,
+    opts: &SoakOptions,
+) -> anyhow::Result<()> {
+    env.opts.max_interactions = u32::MAX;
+    env.opts.max_time_simulation = usize::MAX;
+    env.clear();
+
+    let mut rng = env.gen_rng();
+    let mut generator = plan.generator(&mut rng);
+    let mut stats = SoakStats::default();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        soak(&mut env, &mut generator, opts, &mut stats)
+    }));
+
+    let plan_path = env.get_plan_path();
+    std::fs::write(&plan_path, generator.plan().to_string())?;
+    std::fs::write(env.get_plan_json_path(), generator.plan().to_json()?)?;
+    tracing::info!(
+        "soak ran {} interactions and {} invariant checks, peak rss {}, peak database size {}",
+        stats.interactions,
+        stats.checks,
+        stats
+            .peak_rss
+            .map_or("unknown".to_string(), |rss| format!("{} MiB", rss >> 20)),
+        stats
+            .peak_pages
+            .map_or("unknown".to_string(), |pages| format!("{pages} pages"))
+    );
+    tracing::info!(
+        "last {} interactions written to {}",
+        generator.plan().len(),
+        plan_path.display()
+    );
+
+    match result {
+        Ok(Ok(())) => {
+            println!("soak succeeded after {} interactions", stats.interactions);
+            Ok(())
+        }
+        Ok(Err(err)) => {
+            tracing::error!("soak failed: {err:#}");
+            Err(err)
+        }
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+}
+
+fn soak<R: rand::Rng>(
+    env: &mut SimulatorEnv,
+    generator: &mut PlanGenerator<'_, R>,
+    opts: &SoakOptions,
+    stats: &mut SoakStats,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let mut conn_states = vec![ConnectionState::default(); env.connections.len()];
+    let mut next_check = opts.check_every;
+
+    let mut pending = None;
+    while start.elapsed() < opts.duration {
+        let interaction = match pending.take() {
+            Some(interaction) => interaction,
+            None => {
+                let quiescent = (0..env.connections.len()).all(|idx| !env.conn_in_transaction(idx));
+                if stats.interactions >= next_check && generator.at_property_boundary() && quiescent
+                {
+                    check_invariants(env, generator, opts, stats)
+                        .with_context(|| format!("after {} interactions", stats.interactions))?;
+                    compact_history(env, generator, opts, stats)?;
+                    next_check = stats.interactions + opts.check_every;
+                }
+                let Some(next) = generator.next(env) else {
+                    break;
+                };
+                next
+            }
+        };
+        let conn_state = &mut conn_states[interaction.connection_index];
+        let continuation = execute_plan(env, &interaction, conn_state)
+            .with_context(|| format!("interaction {} failed: {interaction}", stats.interactions))?;
+        match continuation {
+            ExecutionContinuation::Stay => pending = Some(interaction),
+            ExecutionContinuation::NextInteraction => stats.interactions += 1,
+            ExecutionContinuation::NextInteractionOutsideThisProperty => loop {
+                stats.interactions += 1;
+                let Some(next) = generator.next(env) else {
+                    return Ok(());
+                };
+                if next.id() != interaction.id() {
+                    pending = Some(next);
+                    break;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+fn check_invariants<R: rand::Rng>(
+    env: &mut SimulatorEnv,
+    generator: &mut PlanGenerator<'_, R>,
+    opts: &SoakOptions,
+    stats: &mut SoakStats,
+) -> anyhow::Result<()> {
+    stats.checks += 1;
+    tracing::info!(
+        "soak check {}: {} interactions",
+        stats.checks,
+        stats.interactions
+    );
+
+    // Make sure the checks run on a live connection. The check is added to the plan, so
+    // that running the plan again reproduces a divergence it finds.
+    let mut conn_state = ConnectionState::default();
+    let tables = env
+        .connection_context(0)
+        .tables()
+        .iter()
+        .map(|table| table.name.clone())
+        .collect::<Vec<_>>();
+    let property = Property::AllTableHaveExpectedContent { tables };
+    let checks =
+        generator.push_interactions(Interactions::new(0, InteractionsType::Property(property)));
+    for check in checks {
+        loop {
+            match execute_plan(env, &check, &mut conn_state)
+                .context("table contents diverged from the model")?
+            {
+                ExecutionContinuation::Stay => continue,
+                ExecutionContinuation::NextInteraction => break,
+                ExecutionContinuation::NextInteractionOutsideThisProperty => {
+                    return Err(anyhow!("table content check was skipped: {check}"));
+                }
+            }
+        }
+    }
+
+    // TODO: skip integrity check with mvcc
+    if !env.profile.mvcc {
+        let db = env
+            .db
+            .as_ref()
+            .expect("the database is open during a soak run");
+        let problems = db.verify(opts.verify_level)?;
+        if !problems.is_empty() {
+            return Err(anyhow!(
+                "{:?} verification failed: {}",
+                opts.verify_level,
+                problems.join("\n")
+            ));
+        }
+        check_file_accounting(env, stats)?;
+    }
+
+    if let Some(rss) = resident_memory_bytes() {
+        stats.peak_rss = Some(stats.peak_rss.unwrap_or(0).max(rss));
+        tracing::info!("soak check {}: rss {} MiB", stats.checks, rss >> 20);
+        if let Some(ceiling) = opts.memory_ceiling_bytes
+            && rss > ceiling
+        {
+            return Err(anyhow!(
+                "resident memory {} MiB exceeds the ceiling of {} MiB",
+                rss >> 20,
+                ceiling >> 20
+            ));
+        }
+    } else if opts.memory_ceiling_bytes.is_some() && stats.checks == 1 {
+        tracing::warn!("resident memory is not available on this platform, ignoring the ceiling");
+    }
+    Ok(())
+}
+
+/// Checkpoints the WAL into the database file, and checks the size of the file and of
+/// the freelist against the page count in the database header.
+fn check_file_accounting(env: &SimulatorEnv, stats: &mut SoakStats) -> anyhow::Result<()> {
+    let db = env
+        .db
+        .as_ref()
+        .expect("the database is open during a soak run");
+    let conn = db.connect()?;
+    let accounting = read_accounting(&conn);
+    conn.close()?;
+    let (busy, page_count, freelist_count, page_size) = accounting?;
+
+    stats.peak_pages = Some(stats.peak_pages.unwrap_or(0).max(page_count));
+    tracing::info!(
+        "soak check {}: {page_count} pages, {freelist_count} on the freelist",
+        stats.checks
+    );
+    if freelist_count >= page_count.max(1) {
+        return Err(anyhow!(
+            "freelist holds {freelist_count} pages of a database of {page_count} pages"
+        ));
+    }
+    if busy {
+        // Pages may still be in the WAL only
+        tracing::warn!("checkpoint was busy, skipping the file size check");
+        return Ok(());
+    }
+    let file_size = std::fs::metadata(env.get_db_path())?.len();
+    if file_size < page_count * page_size {
+        return Err(anyhow!(
+            "database file has {file_size} bytes, but holds {page_count} pages of {page_size} bytes"
+        ));
+    }
+    Ok(())
+}
+
+/// Whether a `TRUNCATE` checkpoint was busy, and the page count, freelist count and page
+/// size of the database after it.
+fn read_accounting(conn: &Arc<Connection>) -> anyhow::Result<(bool, u64, u64, u64)> {
+    let first_int = |pragma: &str| -> anyhow::Result<i64> {
+        conn.pragma_query(pragma)?
+            .first()
+            .and_then(|row| row.first())
+            .and_then(|value| value.as_int())
+            .ok_or_else(|| anyhow!("PRAGMA {pragma} did not return an integer"))
+    };
+    let count = |pragma: &str| -> anyhow::Result<u64> {
+        u64::try_from(first_int(pragma)?)
+            .with_context(|| format!("PRAGMA {pragma} returned a negative count"))
+    };
+    let busy = first_int("wal_checkpoint(TRUNCATE)")? != 0;
+    Ok((
+        busy,
+        count("page_count")?,
+        count("freelist_count")?,
+        count("page_size")?,
+    ))
+}
+
+/// Moves everything but the last `history_window` interactions of the plan to
+/// the next plan segment, overwriting the oldest one once the ring is full.
+fn compact_history<R: rand::Rng>(
+    env: &SimulatorEnv,
+    generator: &mut PlanGenerator<'_, R>,
+    opts: &SoakOptions,
+    stats: &mut SoakStats,
+) -> anyhow::Result<()> {
+    let dropped = generator.compact_plan(opts.history_window);
+    if dropped.is_empty() {
+        return Ok(());
+    }
+    let segment = env
+        .paths
+        .soak_segment(stats.segments_written % SOAK_PLAN_SEGMENTS);
+    let contents = dropped
+        .iter()
+        .map(|interaction| format!("{interaction}\n"))
+        .collect::<String>();
+    std::fs::write(&segment, contents)?;
+    tracing::debug!(
+        "moved {} interactions to {}",
+        dropped.len(),
+        segment.display()
+    );
+    stats.segments_written += 1;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory_bytes() -> Option<u64> {
+    None
+}
@@ -1 +1,2 @@
 pub mod plan;
+mod reduce;
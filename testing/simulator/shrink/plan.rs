@@ -11,6 +11,7 @@ use crate::{
     },
     run_simulation,
     runner::execution::Execution,
+    shrink::reduce::reductions,
 };
 use std::{
     collections::HashMap,
@@ -99,11 +100,14 @@ impl InteractionPlan {
         plan
     }
 
-    /// Create a smaller interaction plan by deleting a property
+    /// Create a smaller interaction plan by removing chunks of properties with delta
+    /// debugging, and then reducing the queries that are left. Each candidate plan is
+    /// re-run to check that it still fails with the same error, at most `budget` times.
     pub(crate) fn brute_shrink_interaction_plan(
         &self,
         result: &SandboxedResult,
         env: Arc<Mutex<SimulatorEnv>>,
+        budget: usize,
     ) -> InteractionPlan {
         let failing_execution = match result {
             SandboxedResult::Panicked {
@@ -124,52 +128,111 @@ impl InteractionPlan {
         let all_interactions = self.interactions_list();
         let property_id = all_interactions[failing_execution.interaction_index].id();
 
-        let before = self.len_properties();
+        let before = plan.len();
 
         plan.truncate(failing_execution.interaction_index + 1);
 
+        let mut verifier = Verifier {
+            failing_execution,
+            result,
+            env,
+            runs_left: budget,
+        };
         // phase 2: shrink the entire plan
-        plan = Self::iterative_shrink(&plan, failing_execution, result, env, property_id);
-
-        let after = plan.len_properties();
+        plan = Self::ddmin_shrink(&plan, &mut verifier, property_id);
+        // phase 3: shrink the queries that are left
+        plan = Self::reduce_queries(&plan, &mut verifier);
 
         tracing::info!(
-            "Shrinking interaction plan from {} to {} properties",
+            "Shrinking interaction plan from {} to {} interactions with {} runs",
             before,
-            after
+            plan.len(),
+            budget - verifier.runs_left
         );
 
         plan
     }
 
-    /// shrink a plan by removing one interaction at a time (and its deps) while preserving the error
-    fn iterative_shrink(
+    /// Remove chunks of properties while preserving the error, in the manner of ddmin:
+    /// start with halves of the plan, and split the chunks further whenever none of
+    /// them can be removed, until single properties are tried.
+    fn ddmin_shrink(
         plan: &InteractionPlan,
-        failing_execution: &Execution,
-        old_result: &SandboxedResult,
-        env: Arc<Mutex<SimulatorEnv>>,
+        verifier: &mut Verifier,
         failing_property_id: NonZeroUsize,
     ) -> InteractionPlan {
-        let mut iter_properties = plan.rev_iter_properties();
-
-        let mut ret_plan = plan.clone();
-
-        while let Some(property_interactions) = iter_properties.next_property() {
-            // get the overall property id and try to remove it
-            // need to consume the iterator, to advance outer iterator
-            if let Some((_, interaction)) = property_interactions.last()
-                && interaction.id() != failing_property_id
-            {
-                // try to remove the property
-                let mut test_plan = ret_plan.clone();
-                test_plan.remove_property(interaction.id());
-                if Self::test_shrunk_plan(&test_plan, failing_execution, old_result, env.clone()) {
-                    ret_plan = test_plan;
+        let mut plan = plan.clone();
+        let mut granularity = 2;
+        loop {
+            let mut ids = plan
+                .interactions_list()
+                .iter()
+                .map(|interaction| interaction.id())
+                .filter(|id| *id != failing_property_id)
+                .collect::<Vec<_>>();
+            ids.dedup();
+            if ids.is_empty() {
+                break;
+            }
+            granularity = granularity.min(ids.len());
+            let chunk_size = ids.len().div_ceil(granularity);
+            let mut removed = false;
+            for chunk in ids.chunks(chunk_size) {
+                if verifier.exhausted() {
+                    return plan;
+                }
+                let mut test_plan = plan.clone();
+                for id in chunk {
+                    test_plan.remove_property(*id);
                 }
+                if verifier.reproduces(&test_plan) {
+                    plan = test_plan;
+                    removed = true;
+                    break;
+                }
+            }
+            if removed {
+                granularity = (granularity - 1).max(2);
+            } else if granularity == ids.len() {
+                break;
+            } else {
+                granularity = (granularity * 2).min(ids.len());
             }
         }
+        plan
+    }
 
-        ret_plan
+    /// Replace queries with smaller variants, such as ones with simpler predicates,
+    /// fewer columns or rows, or smaller values, while preserving the error
+    fn reduce_queries(plan: &InteractionPlan, verifier: &mut Verifier) -> InteractionPlan {
+        let mut plan = plan.clone();
+        for idx in 0..plan.len() {
+            // Each reduction is smaller than the query it replaces, so this ends
+            loop {
+                let InteractionType::Query(query) = &plan.interactions_list()[idx].interaction
+                else {
+                    break;
+                };
+                let mut reduced = false;
+                for candidate in reductions(query) {
+                    if verifier.exhausted() {
+                        return plan;
+                    }
+                    let mut test_plan = plan.clone();
+                    test_plan.interactions_list_mut()[idx].interaction =
+                        InteractionType::Query(candidate);
+                    if verifier.reproduces(&test_plan) {
+                        plan = test_plan;
+                        reduced = true;
+                        break;
+                    }
+                }
+                if !reduced {
+                    break;
+                }
+            }
+        }
+        plan
     }
 
     fn test_shrunk_plan(
@@ -241,7 +304,9 @@ impl InteractionPlan {
                             property_meta.property,
                             PropertyDiscriminants::AllTableHaveExpectedContent
                                 | PropertyDiscriminants::SelectLimit
+                                | PropertyDiscriminants::SelectAggregate
                                 | PropertyDiscriminants::SelectSelectOptimizer
+                                | PropertyDiscriminants::IndexConsistency
                                 | PropertyDiscriminants::TableHasExpectedContent
                                 | PropertyDiscriminants::UnionAllPreservesCardinality
                                 | PropertyDiscriminants::WhereTrueFalseNull
@@ -338,3 +403,31 @@ impl InteractionPlan {
         });
     }
 }
+
+/// Re-runs shrunk plans to check that they still fail with the same error, until
+/// its budget of runs is spent.
+struct Verifier<'a> {
+    failing_execution: &'a Execution,
+    result: &'a SandboxedResult,
+    env: Arc<Mutex<SimulatorEnv>>,
+    runs_left: usize,
+}
+
+impl Verifier<'_> {
+    fn exhausted(&self) -> bool {
+        self.runs_left == 0
+    }
+
+    fn reproduces(&mut self, plan: &InteractionPlan) -> bool {
+        if self.exhausted() {
+            return false;
+        }
+        self.runs_left -= 1;
+        InteractionPlan::test_shrunk_plan(
+            plan,
+            self.failing_execution,
+            self.result,
+            self.env.clone(),
+        )
+    }
+}
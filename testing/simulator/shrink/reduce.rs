@@ -0,0 +1,282 @@
+//! Semantic reductions of the queries of a failing plan. Each reduction is a
+//! strictly smaller variant of a query, so repeatedly applying the ones that still
+//! reproduce a failure always terminates.
+
+use sql_generation::model::{
+    query::{
+        Delete, Insert, Select,
+        predicate::Predicate,
+        select::ResultColumn,
+        update::{SetValue, Update},
+    },
+    table::SimValue,
+};
+use turso_core::{Numeric, Value};
+use turso_parser::ast;
+
+use crate::model::Query;
+
+/// Smaller variants of `query`, the simplest first.
+pub(crate) fn reductions(query: &Query) -> Vec<Query> {
+    match query {
+        Query::Select(select) => select_reductions(select)
+            .into_iter()
+            .map(Query::Select)
+            .collect(),
+        Query::Insert(insert) => insert_reductions(insert)
+            .into_iter()
+            .map(Query::Insert)
+            .collect(),
+        Query::Update(update) => update_reductions(update)
+            .into_iter()
+            .map(Query::Update)
+            .collect(),
+        Query::Delete(delete) => predicate_reductions(&delete.predicate)
+            .into_iter()
+            .map(|predicate| {
+                Query::Delete(Delete {
+                    predicate,
+                    ..delete.clone()
+                })
+            })
+            .chain(delete.limit.is_some().then(|| {
+                Query::Delete(Delete {
+                    limit: None,
+                    ..delete.clone()
+                })
+            }))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn select_reductions(select: &Select) -> Vec<Select> {
+    let mut reductions = Vec::new();
+    let inner = &select.body.select;
+    for where_clause in predicate_reductions(&inner.where_clause) {
+        let mut reduced = select.clone();
+        reduced.body.select.where_clause = where_clause;
+        reductions.push(reduced);
+    }
+    for idx in 0..select.body.compounds.len() {
+        let mut reduced = select.clone();
+        reduced.body.compounds.remove(idx);
+        reductions.push(reduced);
+    }
+    if select.body.compounds.is_empty() && inner.columns.len() > 1 {
+        // Every select of a compound must have the same number of columns
+        for idx in 0..inner.columns.len() {
+            let mut reduced = select.clone();
+            reduced.body.select.columns.remove(idx);
+            reductions.push(reduced);
+        }
+    }
+    for (idx, column) in inner.columns.iter().enumerate() {
+        if let ResultColumn::Expr(expr) = column {
+            for expr in predicate_reductions(expr) {
+                let mut reduced = select.clone();
+                reduced.body.select.columns[idx] = ResultColumn::Expr(expr);
+                reductions.push(reduced);
+            }
+        }
+    }
+    if inner.order_by.is_some() {
+        let mut reduced = select.clone();
+        reduced.body.select.order_by = None;
+        reductions.push(reduced);
+    }
+    if select.limit.is_some() {
+        let mut reduced = select.clone();
+        reduced.limit = None;
+        reductions.push(reduced);
+    }
+    reductions
+}
+
+fn insert_reductions(insert: &Insert) -> Vec<Insert> {
+    let mut reductions = Vec::new();
+    match insert {
+        Insert::Values { values, .. } => {
+            for values in values_reductions(values) {
+                let mut reduced = insert.clone();
+                let Insert::Values { values: v, .. } = &mut reduced else {
+                    unreachable!()
+                };
+                *v = values;
+                reductions.push(reduced);
+            }
+        }
+        Insert::ValuesWithColumns {
+            table,
+            columns,
+            values,
+        } => {
+            if columns.len() > 1 {
+                for idx in 0..columns.len() {
+                    let mut columns = columns.clone();
+                    columns.remove(idx);
+                    let values = values
+                        .iter()
+                        .map(|row| {
+                            let mut row = row.clone();
+                            row.remove(idx);
+                            row
+                        })
+                        .collect();
+                    reductions.push(Insert::ValuesWithColumns {
+                        table: table.clone(),
+                        columns,
+                        values,
+                    });
+                }
+            }
+            for values in values_reductions(values) {
+                reductions.push(Insert::ValuesWithColumns {
+                    table: table.clone(),
+                    columns: columns.clone(),
+                    values,
+                });
+            }
+        }
+        Insert::Select { select, .. } => {
+            for select in select_reductions(select) {
+                let mut reduced = insert.clone();
+                let Insert::Select { select: s, .. } = &mut reduced else {
+                    unreachable!()
+                };
+                **s = select;
+                reductions.push(reduced);
+            }
+        }
+    }
+    reductions
+}
+
+/// The rows of an `INSERT` with one row less, or with a smaller value.
+fn values_reductions(values: &[Vec<SimValue>]) -> Vec<Vec<Vec<SimValue>>> {
+    let mut reductions = Vec::new();
+    if values.len() > 1 {
+        for idx in 0..values.len() {
+            let mut reduced = values.to_vec();
+            reduced.remove(idx);
+            reductions.push(reduced);
+        }
+    }
+    for (row_idx, row) in values.iter().enumerate() {
+        for (idx, value) in row.iter().enumerate() {
+            for value in value_reductions(value) {
+                let mut reduced = values.to_vec();
+                reduced[row_idx][idx] = value;
+                reductions.push(reduced);
+            }
+        }
+    }
+    reductions
+}
+
+fn update_reductions(update: &Update) -> Vec<Update> {
+    let mut reductions = Vec::new();
+    for predicate in predicate_reductions(&update.predicate) {
+        reductions.push(Update {
+            predicate,
+            ..update.clone()
+        });
+    }
+    if update.set_values.len() > 1 {
+        for idx in 0..update.set_values.len() {
+            let mut reduced = update.clone();
+            reduced.set_values.remove(idx);
+            reductions.push(reduced);
+        }
+    }
+    for (idx, (_, set_value)) in update.set_values.iter().enumerate() {
+        let values = match set_value {
+            SetValue::Simple(value) => value_reductions(value),
+            SetValue::CaseWhen { then_value, .. } => {
+                // A plain value is simpler than a case
+                std::iter::once(then_value.clone())
+                    .chain(value_reductions(then_value))
+                    .collect()
+            }
+            SetValue::Column(_) => Vec::new(),
+        };
+        for value in values {
+            let mut reduced = update.clone();
+            reduced.set_values[idx].1 = SetValue::Simple(value);
+            reductions.push(reduced);
+        }
+    }
+    reductions
+}
+
+/// `TRUE`, `FALSE`, and the operands of the logical operators of `predicate`,
+/// unless it is already as simple.
+fn predicate_reductions(predicate: &Predicate) -> Vec<Predicate> {
+    let true_ = Predicate::true_();
+    if *predicate == true_ {
+        return Vec::new();
+    }
+    let mut reductions = vec![true_];
+    if *predicate != Predicate::false_() {
+        reductions.push(Predicate::false_());
+    }
+    match &predicate.0 {
+        ast::Expr::Parenthesized(exprs) if exprs.len() == 1 => {
+            reductions.push(Predicate((*exprs[0]).clone()));
+        }
+        ast::Expr::Binary(lhs, ast::Operator::And | ast::Operator::Or, rhs) => {
+            reductions.push(Predicate((**lhs).clone()));
+            reductions.push(Predicate((**rhs).clone()));
+        }
+        ast::Expr::Unary(ast::UnaryOperator::Not, expr) => {
+            reductions.push(Predicate((**expr).clone()));
+        }
+        _ => {}
+    }
+    reductions.dedup();
+    reductions
+}
+
+/// Zero, or a value halfway there.
+fn value_reductions(value: &SimValue) -> Vec<SimValue> {
+    let values = match &value.0 {
+        Value::Numeric(Numeric::Integer(i)) if *i != 0 => {
+            let mut values = vec![Value::from_i64(0)];
+            if i / 2 != 0 {
+                values.push(Value::from_i64(i / 2));
+            }
+            values
+        }
+        Value::Numeric(Numeric::Float(f)) if f64::from(*f) != 0.0 => {
+            let f = f64::from(*f);
+            let mut values = vec![Value::from_f64(0.0)];
+            if f.trunc() != f {
+                values.push(Value::from_f64(f.trunc()));
+            }
+            values
+        }
+        value @ Value::Text(..) => {
+            let text = value.to_string();
+            let len = text.chars().count();
+            if len == 0 {
+                Vec::new()
+            } else {
+                vec![
+                    Value::build_text(String::new()),
+                    Value::build_text(text.chars().take(len / 2).collect::<String>()),
+                ]
+            }
+        }
+        Value::Blob(b) if !b.is_empty() => {
+            let mut half = b.clone();
+            half.truncate(b.len() / 2);
+            let mut empty = b.clone();
+            empty.clear();
+            vec![Value::Blob(empty), Value::Blob(half)]
+        }
+        _ => Vec::new(),
+    };
+    let mut values = values.into_iter().map(SimValue).collect::<Vec<_>>();
+    values.dedup();
+    values
+}
@@ -331,3 +331,196 @@ fn test_do_open_skips_registry() {
         "open() must still return the registered instance; do_open must not replace it"
     );
 }
+
+#[cfg(unix)]
+#[test]
+fn test_database_with_direct_io() {
+    use crate::common::{limbo_exec_rows, TempDatabase};
+    use rusqlite::types::Value as RValue;
+
+    let opts = turso_core::DatabaseOpts::new().with_direct_io(true);
+    let db = TempDatabase::builder().with_opts(opts).build();
+    let conn = db.connect_limbo();
+    conn.execute("CREATE TABLE t (x)").unwrap();
+    conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 500)")
+        .unwrap();
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+
+    // A fresh connection reads the checkpointed pages back from the database
+    // file rather than from the WAL.
+    let conn2 = db.connect_limbo();
+    let rows = limbo_exec_rows(&conn2, "SELECT count(*), sum(length(x)) FROM t");
+    assert_eq!(
+        rows,
+        vec![vec![RValue::Integer(500), RValue::Integer(500_000)]]
+    );
+    let rows = limbo_exec_rows(&conn2, "PRAGMA integrity_check");
+    assert_eq!(rows, vec![vec![RValue::Text("ok".to_string())]]);
+}
+
+/// Connection::serialize yields the checkpointed file image, including
+/// frames still in the WAL, and Database::deserialize opens a private copy
+/// of it.
+#[test]
+fn test_serialize_deserialize_roundtrip() {
+    use crate::common::{limbo_exec_rows, TempDatabase};
+    use rusqlite::types::Value as RValue;
+
+    let db = TempDatabase::new_empty();
+    let conn = db.connect_limbo();
+    conn.execute("CREATE TABLE t (x INTEGER PRIMARY KEY, y)")
+        .unwrap();
+    conn.execute("INSERT INTO t SELECT value, randomblob(300) FROM generate_series(1, 200)")
+        .unwrap();
+
+    // Nothing is checkpointed yet, the image is rebuilt from the WAL.
+    let image = conn.serialize().unwrap();
+    assert_eq!(image.len() % 4096, 0);
+
+    let copy = Database::deserialize(
+        &image,
+        turso_core::OpenOptions::new(Arc::new(SqliteDialect)),
+    )
+    .unwrap();
+    let copy_conn = copy.connect().unwrap();
+    assert_eq!(
+        limbo_exec_rows(&copy_conn, "SELECT count(*), max(x) FROM t"),
+        vec![vec![RValue::Integer(200), RValue::Integer(200)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&copy_conn, "PRAGMA integrity_check"),
+        vec![vec![RValue::Text("ok".to_string())]]
+    );
+
+    // Writes to the copy stay in the copy.
+    copy_conn.execute("DELETE FROM t WHERE x > 10").unwrap();
+    assert_eq!(
+        limbo_exec_rows(&copy_conn, "SELECT count(*) FROM t"),
+        vec![vec![RValue::Integer(10)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&conn, "SELECT count(*) FROM t"),
+        vec![vec![RValue::Integer(200)]]
+    );
+
+    // Once checkpointed, the image is byte for byte the database file.
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    assert_eq!(conn.serialize().unwrap(), std::fs::read(&db.path).unwrap());
+}
+
+/// Inside a transaction the image includes the transaction's own changes.
+#[test]
+fn test_serialize_sees_uncommitted_changes() {
+    use crate::common::{limbo_exec_rows, TempDatabase};
+    use rusqlite::types::Value as RValue;
+
+    let db = TempDatabase::new_empty();
+    let conn = db.connect_limbo();
+    conn.execute("CREATE TABLE t (x)").unwrap();
+    conn.execute("BEGIN").unwrap();
+    conn.execute("INSERT INTO t VALUES (1), (2), (3)").unwrap();
+    let image = conn.serialize().unwrap();
+    conn.execute("ROLLBACK").unwrap();
+
+    let copy = Database::deserialize(
+        &image,
+        turso_core::OpenOptions::new(Arc::new(SqliteDialect)),
+    )
+    .unwrap();
+    let copy_conn = copy.connect().unwrap();
+    assert_eq!(
+        limbo_exec_rows(&copy_conn, "SELECT sum(x) FROM t"),
+        vec![vec![RValue::Integer(6)]]
+    );
+}
+
+#[test]
+fn test_deserialize_read_only() {
+    use crate::common::{limbo_exec_rows, TempDatabase};
+    use rusqlite::types::Value as RValue;
+
+    let db = TempDatabase::new_empty();
+    let conn = db.connect_limbo();
+    conn.execute("CREATE TABLE t (x)").unwrap();
+    conn.execute("INSERT INTO t VALUES ('a')").unwrap();
+    let image = conn.serialize().unwrap();
+
+    let copy = Database::deserialize(
+        &image,
+        turso_core::OpenOptions::new(Arc::new(SqliteDialect)).flags(OpenFlags::ReadOnly),
+    )
+    .unwrap();
+    let copy_conn = copy.connect().unwrap();
+    assert_eq!(
+        limbo_exec_rows(&copy_conn, "SELECT x FROM t"),
+        vec![vec![RValue::Text("a".to_string())]]
+    );
+    let err = copy_conn
+        .execute("INSERT INTO t VALUES ('b')")
+        .expect_err("read-only image must reject writes");
+    assert!(
+        matches!(err, turso_core::LimboError::ReadOnly),
+        "expected ReadOnly, got {err:?}"
+    );
+}
+
+#[test]
+fn test_deserialize_rejects_partial_page() {
+    let err = Database::deserialize(
+        &[0u8; 1000],
+        turso_core::OpenOptions::new(Arc::new(SqliteDialect)),
+    )
+    .expect_err("image must be whole pages");
+    assert!(
+        matches!(err, turso_core::LimboError::InvalidArgument(_)),
+        "expected InvalidArgument, got {err:?}"
+    );
+}
+
+fn open_uri(uri: &str) -> Arc<turso_core::Connection> {
+    let (_io, conn) = turso_core::Connection::from_uri(
+        uri,
+        turso_core::DatabaseOpts::default(),
+        Arc::new(SqliteDialect),
+    )
+    .unwrap();
+    conn
+}
+
+#[test]
+fn test_shared_memory_uri() {
+    use crate::common::limbo_exec_rows;
+    use rusqlite::types::Value as RValue;
+
+    for uri in [
+        "file:shared_uri_test?mode=memory&cache=shared",
+        "file:memdb_uri_test?vfs=memdb&cache=shared",
+        "file:/memdb_slash_uri_test?vfs=memdb",
+    ] {
+        let conn1 = open_uri(uri);
+        conn1.execute("CREATE TABLE t (x)").unwrap();
+        conn1.execute("INSERT INTO t VALUES (1)").unwrap();
+        let conn2 = open_uri(uri);
+        assert_eq!(
+            limbo_exec_rows(&conn2, "SELECT x FROM t"),
+            vec![vec![RValue::Integer(1)]],
+            "{uri} should be shared between connections"
+        );
+    }
+}
+
+#[test]
+fn test_private_memory_uri() {
+    for uri in [
+        "file:private_uri_test?mode=memory",
+        "file:memdb_private_uri_test?vfs=memdb",
+    ] {
+        let conn1 = open_uri(uri);
+        conn1.execute("CREATE TABLE t (x)").unwrap();
+        let conn2 = open_uri(uri);
+        assert!(
+            conn2.execute("SELECT x FROM t").is_err(),
+            "{uri} should be private to its connection"
+        );
+    }
+}
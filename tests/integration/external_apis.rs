@@ -6,15 +6,17 @@ use std::{
     ffi::CString,
     os::raw::c_void,
     sync::{
-        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
         Arc,
     },
 };
-use turso_core::{Connection, LimboError, StepResult};
+use turso_core::{Connection, ExtensionCapabilities, LimboError, StepResult};
 use turso_ext::{
-    AggCtx, ContextDestructor, FinalizeFunction, InitAggFunction, ResultCode, ScalarDerive,
-    ScalarFunc, ScalarFunction, StepFunction, Value as ExtValue, ValueDestructor,
-    ValueType as ExtValueType,
+    AggCtx, AggFunc, AggregateDerive, BufferRef, Callback, Connection as ExtConnection,
+    ContextDestructor, ExtResult, FinalizeFunction, InitAggFunction, ResultCode, ScalarDerive,
+    ScalarFunc, ScalarFunction, StepFunction, StepResult as ExtStepResult, VTabCursor, VTabKind,
+    VTabModule, VTabModuleDerive, VTable, Value as ExtValue, ValueDestructor,
+    ValueType as ExtValueType, VfsDerive, VfsExtension, VfsFile, WindowFunc,
 };
 
 static CTX_CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
@@ -83,6 +85,87 @@ fn scalar_derive_state_is_shared_across_calls_and_dropped_once(
     Ok(())
 }
 
+#[derive(AggregateDerive)]
+#[window]
+struct RunningSum;
+
+impl AggFunc for RunningSum {
+    type State = i64;
+    type Error = &'static str;
+    const NAME: &'static str = "running_sum";
+    const ARGS: i32 = 1;
+
+    fn step(state: &mut Self::State, args: &[ExtValue]) {
+        *state += args[0].to_integer().unwrap_or_default();
+    }
+
+    fn finalize(state: Self::State) -> Result<ExtValue, Self::Error> {
+        Ok(ExtValue::from_integer(state))
+    }
+}
+
+impl WindowFunc for RunningSum {
+    fn value(state: &Self::State) -> Result<ExtValue, Self::Error> {
+        Ok(ExtValue::from_integer(*state))
+    }
+
+    fn inverse(state: &mut Self::State, args: &[ExtValue]) {
+        *state -= args[0].to_integer().unwrap_or_default();
+    }
+}
+
+#[turso_macros::test]
+#[serial]
+fn window_aggregate_derive_runs_over_partitions(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    let api = unsafe { conn._build_turso_ext() };
+    let rc = unsafe { RunningSum::register_RunningSum(&api as *const _) };
+    unsafe { conn._free_extension_ctx(api) };
+    assert_eq!(rc, ResultCode::OK);
+
+    conn.execute("CREATE TABLE runs(grp TEXT, x INTEGER)")?;
+    conn.execute("INSERT INTO runs VALUES ('a', 1), ('a', 2), ('a', 3), ('b', 10), ('b', 20)")?;
+
+    let running: Vec<(String, i64, i64)> = conn.exec_rows(
+        "SELECT grp, x, running_sum(x) OVER (PARTITION BY grp ORDER BY x) FROM runs ORDER BY grp, x",
+    );
+    assert_eq!(
+        running,
+        vec![
+            ("a".to_string(), 1, 1),
+            ("a".to_string(), 2, 3),
+            ("a".to_string(), 3, 6),
+            ("b".to_string(), 10, 10),
+            ("b".to_string(), 20, 30),
+        ]
+    );
+
+    // Still usable as a plain aggregate.
+    let total: Vec<(i64,)> = conn.exec_rows("SELECT running_sum(x) FROM runs");
+    assert_eq!(total, vec![(36,)]);
+
+    // Aggregates registered without window callbacks are rejected.
+    register_context_aggregate(
+        &conn,
+        "managed_sum",
+        1,
+        boxed_aggregate_context(Arc::new(CallbackCounters::default())),
+        managed_sum_init,
+        managed_sum_step,
+        managed_sum_final,
+        Some(drop_aggregate_context),
+        Some(drop_sum_state),
+        None,
+    )?;
+    let err = conn
+        .prepare("SELECT managed_sum(x) OVER (ORDER BY x) FROM runs")
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("may not be used as a window function"));
+    Ok(())
+}
+
 #[turso_macros::test]
 fn sql_extension_loading_is_disabled_by_default(tmp_db: TempDatabase) -> anyhow::Result<()> {
     let conn = tmp_db.connect_limbo();
@@ -504,6 +587,635 @@ fn register_context_aggregate(
     Ok(())
 }
 
+#[derive(ScalarDerive)]
+struct BlobProbe;
+
+impl ScalarFunc for BlobProbe {
+    /// Largest blob accepted, in bytes.
+    type State = usize;
+    const NAME: &'static str = "blob_probe";
+
+    fn init() -> Self::State {
+        1024
+    }
+
+    fn call(max_len: &Self::State, args: &[ExtValue]) -> ExtValue {
+        let arg = &args[0];
+        if arg.is_null() {
+            return ExtValue::null();
+        }
+        match arg.blob_ref() {
+            Some(bytes) if bytes.len() <= *max_len => {
+                ExtValue::from_blob(bytes.iter().rev().copied().collect())
+            }
+            _ => ExtValue::error_with_message(format!(
+                "blob_probe expects a blob of at most {max_len} bytes"
+            )),
+        }
+    }
+}
+
+#[turso_macros::test]
+fn ext_value_round_trips_blobs_nulls_and_exact_numbers(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    let api = unsafe { conn._build_turso_ext() };
+    let rc = unsafe { register_BlobProbe(&api as *const _) };
+    unsafe { conn._free_extension_ctx(api) };
+    assert_eq!(rc, ResultCode::OK);
+
+    let rows = limbo_exec_rows(
+        &conn,
+        "SELECT blob_probe(x'000102ff'), blob_probe(x''), blob_probe(NULL)",
+    );
+    assert_eq!(
+        rows,
+        vec![vec![
+            SqliteValue::Blob(vec![0xff, 0x02, 0x01, 0x00]),
+            SqliteValue::Blob(vec![]),
+            SqliteValue::Null,
+        ]]
+    );
+
+    assert!(ExtValue::null().is_null());
+    assert!(!ExtValue::from_integer(0).is_null());
+    assert_eq!(ExtValue::from_float(3.0).to_integer_exact(), Some(3));
+    assert_eq!(ExtValue::from_float(3.5).to_integer_exact(), None);
+    assert_eq!(ExtValue::from_float(9.3e18).to_integer_exact(), None);
+    assert_eq!(
+        ExtValue::from_float(i64::MIN as f64).to_integer_exact(),
+        Some(i64::MIN)
+    );
+    assert_eq!(
+        ExtValue::from_text("abc".to_string()).to_integer_exact(),
+        None
+    );
+    assert_eq!(
+        ExtValue::from_integer(1 << 53).to_float_exact(),
+        Some(9007199254740992.0)
+    );
+    assert_eq!(ExtValue::from_integer((1 << 53) + 1).to_float_exact(), None);
+    assert_eq!(ExtValue::from_integer(i64::MAX).to_float_exact(), None);
+    assert_eq!(
+        ExtValue::from_text("héllo".to_string()).text_bytes(),
+        Some("héllo".as_bytes())
+    );
+    Ok(())
+}
+
+#[derive(ScalarDerive)]
+struct DoubleIt;
+
+impl ScalarFunc for DoubleIt {
+    type State = i64;
+    const NAME: &'static str = "double_it";
+    const DETERMINISTIC: bool = true;
+
+    fn init() -> Self::State {
+        2
+    }
+
+    fn call(factor: &Self::State, args: &[ExtValue]) -> ExtValue {
+        let n = args
+            .first()
+            .and_then(ExtValue::to_integer)
+            .unwrap_or_default();
+        ExtValue::from_integer(n * factor)
+    }
+}
+
+#[derive(ScalarDerive)]
+struct Jitter;
+
+impl ScalarFunc for Jitter {
+    type State = AtomicUsize;
+    const NAME: &'static str = "jitter";
+
+    fn init() -> Self::State {
+        AtomicUsize::new(0)
+    }
+
+    fn call(calls: &Self::State, _args: &[ExtValue]) -> ExtValue {
+        ExtValue::from_integer(calls.fetch_add(1, AtomicOrdering::SeqCst) as i64)
+    }
+}
+
+#[turso_macros::test]
+fn only_deterministic_extension_functions_are_allowed_in_indexes(
+    tmp_db: TempDatabase,
+) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    let api = unsafe { conn._build_turso_ext() };
+    let rc = unsafe { register_DoubleIt(&api as *const _) };
+    assert_eq!(rc, ResultCode::OK);
+    let rc = unsafe { register_Jitter(&api as *const _) };
+    assert_eq!(rc, ResultCode::OK);
+    unsafe { conn._free_extension_ctx(api) };
+
+    conn.execute("CREATE TABLE t(x INTEGER)")?;
+    conn.execute("CREATE INDEX t_double ON t(double_it(x))")?;
+    conn.execute("CREATE INDEX t_partial ON t(x) WHERE double_it(x) > 2")?;
+    let err = conn
+        .execute("CREATE INDEX t_jitter ON t(jitter(x))")
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("invalid expression in CREATE INDEX"));
+    assert!(conn
+        .execute("CREATE INDEX t_jitter_partial ON t(x) WHERE jitter(x) > 2")
+        .is_err());
+
+    conn.execute("INSERT INTO t VALUES (1), (2), (3)")?;
+    let rows: Vec<(i64,)> = conn.exec_rows("SELECT x FROM t WHERE double_it(x) = 4");
+    assert_eq!(rows, vec![(2,)]);
+    Ok(())
+}
+
+#[derive(Default)]
+struct TxnHookState {
+    commits: AtomicUsize,
+    rollbacks: AtomicUsize,
+    veto: AtomicBool,
+}
+
+unsafe extern "C" fn counting_commit_hook(context: usize) -> ResultCode {
+    let state = unsafe { &*(context as *const TxnHookState) };
+    state.commits.fetch_add(1, AtomicOrdering::SeqCst);
+    if state.veto.load(AtomicOrdering::SeqCst) {
+        ResultCode::Error
+    } else {
+        ResultCode::OK
+    }
+}
+
+unsafe extern "C" fn counting_rollback_hook(context: usize) {
+    let state = unsafe { &*(context as *const TxnHookState) };
+    state.rollbacks.fetch_add(1, AtomicOrdering::SeqCst);
+}
+
+unsafe extern "C" fn drop_txn_hook_state(context: usize) {
+    drop(unsafe { Arc::from_raw(context as *const TxnHookState) });
+}
+
+#[turso_macros::test]
+fn commit_and_rollback_hooks_follow_write_transactions(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    conn.execute("CREATE TABLE t(x INTEGER)")?;
+
+    let state = Arc::new(TxnHookState::default());
+    let api = unsafe { conn._build_turso_ext() };
+    let rc = unsafe {
+        (api.register_commit_hook)(
+            api.ctx,
+            Arc::into_raw(state.clone()) as usize,
+            counting_commit_hook,
+            Some(drop_txn_hook_state),
+        )
+    };
+    assert_eq!(rc, ResultCode::OK);
+    let rc = unsafe {
+        (api.register_rollback_hook)(
+            api.ctx,
+            Arc::into_raw(state.clone()) as usize,
+            counting_rollback_hook,
+            Some(drop_txn_hook_state),
+        )
+    };
+    assert_eq!(rc, ResultCode::OK);
+    unsafe { conn._free_extension_ctx(api) };
+    let counts = || {
+        (
+            state.commits.load(AtomicOrdering::SeqCst),
+            state.rollbacks.load(AtomicOrdering::SeqCst),
+        )
+    };
+
+    conn.execute("INSERT INTO t VALUES (1)")?;
+    assert_eq!(counts(), (1, 0));
+
+    // Read-only transactions do not fire either hook.
+    let _ = limbo_exec_rows(&conn, "SELECT * FROM t");
+    assert_eq!(counts(), (1, 0));
+
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO t VALUES (2)")?;
+    conn.execute("ROLLBACK")?;
+    assert_eq!(counts(), (1, 1));
+
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO t VALUES (3)")?;
+    conn.execute("COMMIT")?;
+    assert_eq!(counts(), (2, 1));
+
+    // A vetoed commit rolls the transaction back.
+    state.veto.store(true, AtomicOrdering::SeqCst);
+    let err = conn.execute("INSERT INTO t VALUES (4)").unwrap_err();
+    assert!(err.to_string().contains("commit hook vetoed"));
+    assert_eq!(counts(), (3, 2));
+    state.veto.store(false, AtomicOrdering::SeqCst);
+
+    let rows = limbo_exec_rows(&conn, "SELECT x FROM t ORDER BY x");
+    assert_eq!(
+        rows,
+        vec![vec![SqliteValue::Integer(1)], vec![SqliteValue::Integer(3)]]
+    );
+
+    // Closing the connection releases both hook contexts.
+    drop(conn);
+    assert_eq!(Arc::strong_count(&state), 1);
+    Ok(())
+}
+
+unsafe extern "C" fn register_running_sum_auto(api: &mut turso_ext::ExtensionApi) -> ResultCode {
+    RunningSum::register_RunningSum(api as *const _)
+}
+
+#[test]
+#[serial]
+fn auto_extension_is_loaded_into_databases_opened_afterwards() -> anyhow::Result<()> {
+    let before = TempDatabase::new_empty();
+    turso_core::register_auto_extension(register_running_sum_auto);
+    // Registering the same entry point twice is a no-op.
+    turso_core::register_auto_extension(register_running_sum_auto);
+
+    let tmp_db = TempDatabase::new_empty();
+    for conn in [tmp_db.connect_limbo(), tmp_db.connect_limbo()] {
+        let rows: Vec<(i64,)> =
+            conn.exec_rows("SELECT running_sum(x) FROM (SELECT 1 AS x UNION ALL SELECT 2)");
+        assert_eq!(rows, vec![(3,)]);
+    }
+
+    // Databases opened before the registration do not pick it up.
+    assert!(before
+        .connect_limbo()
+        .prepare("SELECT running_sum(1)")
+        .is_err());
+
+    assert!(turso_core::cancel_auto_extension(register_running_sum_auto));
+    assert!(!turso_core::cancel_auto_extension(
+        register_running_sum_auto
+    ));
+    turso_core::reset_auto_extension();
+
+    let after = TempDatabase::new_empty();
+    assert!(after
+        .connect_limbo()
+        .prepare("SELECT running_sum(1)")
+        .is_err());
+    Ok(())
+}
+
+#[derive(VTabModuleDerive)]
+struct ShadowKv;
+
+/// Keeps its rows in the `kv_shadow` table of the database it belongs to.
+struct ShadowKvTable {
+    conn: Option<Arc<ExtConnection>>,
+}
+
+struct ShadowKvCursor {
+    conn: Option<Arc<ExtConnection>>,
+    rows: Vec<(i64, String, String)>,
+    pos: usize,
+}
+
+impl VTabModule for ShadowKv {
+    type Table = ShadowKvTable;
+    const VTAB_KIND: VTabKind = VTabKind::VirtualTable;
+    const NAME: &'static str = "shadow_kv";
+    const READONLY: bool = false;
+
+    fn create(_args: &[ExtValue]) -> Result<(String, Self::Table), ResultCode> {
+        let schema = "CREATE TABLE x (key TEXT, value TEXT)".to_string();
+        Ok((schema, ShadowKvTable { conn: None }))
+    }
+}
+
+impl VTable for ShadowKvTable {
+    type Cursor = ShadowKvCursor;
+    type Error = String;
+
+    fn open(&self, conn: Option<Arc<ExtConnection>>) -> Result<Self::Cursor, Self::Error> {
+        Ok(ShadowKvCursor {
+            conn,
+            rows: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    fn set_connection(&mut self, conn: Option<Arc<ExtConnection>>) {
+        self.conn = conn;
+    }
+
+    fn insert(&mut self, args: &[ExtValue]) -> Result<i64, Self::Error> {
+        let conn = self.conn.as_ref().ok_or("no connection")?;
+        let text = |idx: usize| {
+            ExtValue::from_text(
+                args.get(idx)
+                    .and_then(ExtValue::to_text)
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        };
+        let rowid = conn
+            .execute(
+                "INSERT INTO kv_shadow (key, value) VALUES (?, ?)",
+                &[text(0), text(1)],
+            )
+            .map_err(|rc| rc.to_string())?;
+        Ok(rowid.unwrap_or_default() as i64)
+    }
+
+    fn destroy(&mut self) -> Result<(), Self::Error> {
+        let conn = self.conn.as_ref().ok_or("no connection")?;
+        conn.execute("DROP TABLE kv_shadow", &[])
+            .map_err(|rc| rc.to_string())?;
+        Ok(())
+    }
+}
+
+impl VTabCursor for ShadowKvCursor {
+    type Error = String;
+
+    fn filter(&mut self, _args: &[ExtValue], _idx_info: Option<(&str, i32)>) -> ResultCode {
+        let Some(conn) = &self.conn else {
+            return ResultCode::Error;
+        };
+        let Ok(mut stmt) = conn.prepare("SELECT rowid, key, value FROM kv_shadow ORDER BY rowid")
+        else {
+            return ResultCode::Error;
+        };
+        self.rows.clear();
+        self.pos = 0;
+        while let ExtStepResult::Row = stmt.step() {
+            let row = stmt.get_row();
+            let text = |idx: usize| row[idx].to_text().unwrap_or_default().to_string();
+            self.rows
+                .push((row[0].to_integer().unwrap_or_default(), text(1), text(2)));
+        }
+        stmt.close();
+        if self.rows.is_empty() {
+            ResultCode::EOF
+        } else {
+            ResultCode::OK
+        }
+    }
+
+    fn rowid(&self) -> i64 {
+        self.rows[self.pos].0
+    }
+
+    fn column(&self, idx: u32) -> Result<ExtValue, Self::Error> {
+        let (_, key, value) = &self.rows[self.pos];
+        match idx {
+            0 => Ok(ExtValue::from_text(key.clone())),
+            1 => Ok(ExtValue::from_text(value.clone())),
+            _ => Err("invalid column".to_string()),
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.rows.len()
+    }
+
+    fn next(&mut self) -> ResultCode {
+        self.pos += 1;
+        if self.eof() {
+            ResultCode::EOF
+        } else {
+            ResultCode::OK
+        }
+    }
+}
+
+#[turso_macros::test]
+fn virtual_tables_keep_their_rows_in_shadow_tables(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    let api = unsafe { conn._build_turso_ext() };
+    let rc = unsafe { ShadowKv::register_ShadowKv(&api as *const _) };
+    assert_eq!(rc, ResultCode::OK);
+    unsafe { conn._free_extension_ctx(api) };
+
+    conn.execute("CREATE TABLE kv_shadow (key TEXT, value TEXT)")?;
+    conn.execute("CREATE VIRTUAL TABLE kv USING shadow_kv")?;
+    conn.execute("INSERT INTO kv VALUES ('a', '1'), ('b', '2')")?;
+
+    // Writes made through the connection belong to the statement's transaction.
+    conn.execute("BEGIN")?;
+    conn.execute("INSERT INTO kv VALUES ('c', '3')")?;
+    conn.execute("ROLLBACK")?;
+
+    let rows: Vec<(String, String)> = conn.exec_rows("SELECT key, value FROM kv_shadow");
+    assert_eq!(
+        rows,
+        vec![
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "2".to_string())
+        ]
+    );
+    let rows: Vec<(String,)> = conn.exec_rows("SELECT value FROM kv WHERE key = 'b'");
+    assert_eq!(rows, vec![("2".to_string(),)]);
+
+    conn.execute("DROP TABLE kv")?;
+    let rows: Vec<(String,)> =
+        conn.exec_rows("SELECT name FROM sqlite_schema WHERE name = 'kv_shadow'");
+    assert!(rows.is_empty());
+    Ok(())
+}
+
+/// Statements [CapabilityProbe] tries to run, each of which modifies the database.
+const PROBE_WRITES: [&str; 3] = [
+    "INSERT INTO probe_target VALUES (1)",
+    "PRAGMA user_version = 7",
+    "PRAGMA hexrekey = '000102030405060708090a0b0c0d0e0f000102030405060708090a0b0c0d0e0f'",
+];
+
+/// Reports what its connection was allowed to do: one row with `none` if it got no
+/// connection, else the row count of `probe_target` and the results of [PROBE_WRITES].
+#[derive(VTabModuleDerive)]
+struct CapabilityProbe;
+
+struct CapabilityProbeTable;
+
+struct CapabilityProbeCursor {
+    conn: Option<Arc<ExtConnection>>,
+    /// The row count and the results of the writes, `Some(None)` without a connection.
+    row: Option<Option<(i64, String)>>,
+}
+
+impl VTabModule for CapabilityProbe {
+    type Table = CapabilityProbeTable;
+    const VTAB_KIND: VTabKind = VTabKind::VirtualTable;
+    const NAME: &'static str = "capability_probe";
+
+    fn create(_args: &[ExtValue]) -> Result<(String, Self::Table), ResultCode> {
+        let schema = "CREATE TABLE x (conn TEXT, rows INTEGER, write TEXT)".to_string();
+        Ok((schema, CapabilityProbeTable))
+    }
+}
+
+impl VTable for CapabilityProbeTable {
+    type Cursor = CapabilityProbeCursor;
+    type Error = String;
+
+    fn open(&self, conn: Option<Arc<ExtConnection>>) -> Result<Self::Cursor, Self::Error> {
+        Ok(CapabilityProbeCursor { conn, row: None })
+    }
+}
+
+impl VTabCursor for CapabilityProbeCursor {
+    type Error = String;
+
+    fn filter(&mut self, _args: &[ExtValue], _idx_info: Option<(&str, i32)>) -> ResultCode {
+        let Some(conn) = &self.conn else {
+            self.row = Some(None);
+            return ResultCode::OK;
+        };
+        let Ok(mut stmt) = conn.prepare("SELECT count(*) FROM probe_target") else {
+            return ResultCode::Error;
+        };
+        let rows = match stmt.step() {
+            ExtStepResult::Row => stmt.get_row()[0].to_integer().unwrap_or_default(),
+            _ => return ResultCode::Error,
+        };
+        stmt.close();
+        let write = PROBE_WRITES
+            .iter()
+            .map(|sql| match conn.execute(sql, &[]) {
+                Ok(_) => "ok".to_string(),
+                Err(rc) => rc.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        self.row = Some(Some((rows, write)));
+        ResultCode::OK
+    }
+
+    fn rowid(&self) -> i64 {
+        1
+    }
+
+    fn column(&self, idx: u32) -> Result<ExtValue, Self::Error> {
+        let row = self.row.as_ref().ok_or("no row")?;
+        match (idx, row) {
+            (0, None) => Ok(ExtValue::from_text("none".to_string())),
+            (0, Some(_)) => Ok(ExtValue::from_text("some".to_string())),
+            (1 | 2, None) => Ok(ExtValue::null()),
+            (1, Some((rows, _))) => Ok(ExtValue::from_integer(*rows)),
+            (2, Some((_, write))) => Ok(ExtValue::from_text(write.clone())),
+            _ => Err("invalid column".to_string()),
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.row.is_none()
+    }
+
+    fn next(&mut self) -> ResultCode {
+        self.row = None;
+        ResultCode::EOF
+    }
+}
+
+fn register_capability_probe(conn: &Arc<Connection>, capabilities: ExtensionCapabilities) {
+    let api = unsafe { conn._build_turso_ext_with_capabilities(capabilities) };
+    let rc = unsafe { CapabilityProbe::register_CapabilityProbe(&api as *const _) };
+    assert_eq!(rc, ResultCode::OK);
+    unsafe { conn._free_extension_ctx(api) };
+}
+
+#[turso_macros::test]
+fn virtual_tables_get_no_connection_without_database_read(
+    tmp_db: TempDatabase,
+) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    register_capability_probe(&conn, ExtensionCapabilities::FILESYSTEM);
+    conn.execute("CREATE TABLE probe_target (x INTEGER)")?;
+    conn.execute("CREATE VIRTUAL TABLE probe USING capability_probe")?;
+
+    let rows: Vec<(String,)> = conn.exec_rows("SELECT conn FROM probe");
+    assert_eq!(rows, vec![("none".to_string(),)]);
+    Ok(())
+}
+
+#[turso_macros::test]
+fn virtual_tables_cannot_write_without_database_write(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    register_capability_probe(&conn, ExtensionCapabilities::DATABASE_READ);
+    conn.execute("CREATE TABLE probe_target (x INTEGER)")?;
+    conn.execute("INSERT INTO probe_target VALUES (1), (2)")?;
+    conn.execute("CREATE VIRTUAL TABLE probe USING capability_probe")?;
+
+    let rows: Vec<(String, i64, String)> = conn.exec_rows("SELECT conn, rows, write FROM probe");
+    let read_only = vec![ResultCode::ReadOnly.to_string(); PROBE_WRITES.len()].join(",");
+    assert_eq!(rows, vec![("some".to_string(), 2, read_only)]);
+    let rows: Vec<(i64,)> = conn.exec_rows("SELECT count(*) FROM probe_target");
+    assert_eq!(rows, vec![(2,)]);
+    let rows: Vec<(i64,)> = conn.exec_rows("PRAGMA user_version");
+    assert_eq!(rows, vec![(0,)]);
+
+    // The rekey was refused before it could re-encrypt anything: a new connection
+    // still reads the database without a key.
+    let conn = tmp_db.connect_limbo();
+    let rows: Vec<(i64,)> = conn.exec_rows("SELECT count(*) FROM probe_target");
+    assert_eq!(rows, vec![(2,)]);
+    Ok(())
+}
+
+#[derive(VfsDerive, Default)]
+struct DeniedVfs;
+
+struct DeniedFile;
+
+impl VfsExtension for DeniedVfs {
+    const NAME: &'static str = "denied_vfs";
+    type File = DeniedFile;
+
+    fn open_file(&self, _path: &str, _flags: i32, _direct: bool) -> ExtResult<Self::File> {
+        Ok(DeniedFile)
+    }
+
+    fn remove_file(&self, _path: &str) -> ExtResult<()> {
+        Ok(())
+    }
+}
+
+impl VfsFile for DeniedFile {
+    fn read(&mut self, _buf: BufferRef, _offset: i64, _cb: Callback) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+
+    fn write(&mut self, _buf: BufferRef, _offset: i64, _cb: Callback) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+
+    fn sync(&self, _cb: Callback) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+
+    fn truncate(&self, _len: i64, _cb: Callback) -> ExtResult<()> {
+        Err(ResultCode::Unimplemented)
+    }
+
+    fn size(&self) -> i64 {
+        0
+    }
+}
+
+#[turso_macros::test]
+fn vfs_registration_is_denied_without_filesystem(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    let api = unsafe {
+        conn._build_turso_ext_with_capabilities(
+            ExtensionCapabilities::DATABASE_READ | ExtensionCapabilities::DATABASE_WRITE,
+        )
+    };
+    let rc = unsafe { register_DeniedVfs(&api) };
+    unsafe { conn._free_extension_ctx(api) };
+    assert_eq!(rc, ResultCode::PermissionDenied);
+    assert!(!conn.list_vfs().contains(&DeniedVfs::NAME.to_string()));
+    Ok(())
+}
+
 #[turso_macros::test]
 #[serial]
 fn managed_scalar_callbacks_cover_fixed_args_metadata_and_invalidation(
@@ -166,6 +166,25 @@ fn test_quick_check_valid_database(db: TempDatabase) {
     assert_eq!(result, "ok", "Valid database should pass quick_check");
 }
 
+/// Test that Database::verify reports no problems at either level for a valid database
+#[turso_macros::test]
+fn test_verify_valid_database(db: TempDatabase) {
+    let conn = db.connect_limbo();
+
+    conn.execute("CREATE TABLE t1(id INTEGER PRIMARY KEY, name TEXT);")
+        .unwrap();
+    conn.execute("CREATE INDEX idx_name ON t1(name);").unwrap();
+    conn.execute("INSERT INTO t1 VALUES (1, 'test');").unwrap();
+
+    let database = db.limbo_database();
+    for level in [
+        turso_core::VerifyLevel::Quick,
+        turso_core::VerifyLevel::Full,
+    ] {
+        assert_eq!(database.verify(level).unwrap(), Vec::<String>::new());
+    }
+}
+
 /// Test that database with multiple indexes validates correctly
 #[turso_macros::test]
 fn test_integrity_check_multiple_indexes(db: TempDatabase) {
@@ -2029,3 +2048,41 @@ fn test_blob_read_corrupt_spilled_header_overflow_no_panic(db: TempDatabase) {
         }
     }
 }
+
+#[test]
+fn test_integrity_check_reports_page_checksum_mismatch() {
+    let opts = turso_core::DatabaseOpts::new().with_page_checksums(true);
+    let db = TempDatabase::builder().with_opts(opts).build();
+    let conn = db.connect_limbo();
+    conn.execute("CREATE TABLE t1(id INTEGER PRIMARY KEY, data TEXT);")
+        .unwrap();
+    conn.execute("INSERT INTO t1 SELECT value, 'row' FROM generate_series(1, 100);")
+        .unwrap();
+    checkpoint_database(&conn);
+    assert_eq!(run_integrity_check(&conn), "ok");
+    drop(conn);
+
+    // Flip a byte of page 2 without updating its checksum.
+    {
+        use std::io::{Read, Seek, SeekFrom, Write};
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&db.path)
+            .unwrap();
+        let mut byte = [0u8; 1];
+        file.seek(SeekFrom::Start(4096 + 1000)).unwrap();
+        file.read_exact(&mut byte).unwrap();
+        byte[0] ^= 0xff;
+        file.seek(SeekFrom::Start(4096 + 1000)).unwrap();
+        file.write_all(&byte).unwrap();
+        file.sync_all().unwrap();
+    }
+
+    let db = TempDatabase::new_with_existent_with_opts(&db.path, opts);
+    let conn = db.connect_limbo();
+    let mut stmt = conn.prepare("SELECT count(*) FROM t1").unwrap();
+    assert!(stmt.run_collect_rows().is_err());
+    drop(stmt);
+    assert_eq!(run_integrity_check(&conn), "Page 2: checksum mismatch");
+}
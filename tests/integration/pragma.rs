@@ -1,6 +1,6 @@
 use crate::common::{limbo_exec_rows, TempDatabase};
 use rusqlite::types::Value as RValue;
-use turso_core::{Numeric, StepResult, Value};
+use turso_core::{DatabaseOpts, Numeric, PageCachePolicy, StepResult, Value};
 
 #[turso_macros::test(mvcc)]
 fn test_pragma_module_list_returns_list(db: TempDatabase) {
@@ -412,6 +412,74 @@ fn test_pragma_cache_size_i32_min_order_by(db: TempDatabase) {
     );
 }
 
+#[test]
+fn test_page_cache_policies_evict_and_count() {
+    for policy in [
+        PageCachePolicy::Sieve,
+        PageCachePolicy::Lru,
+        PageCachePolicy::TwoQueue,
+        PageCachePolicy::Arc,
+    ] {
+        let opts = DatabaseOpts::new().with_page_cache_policy(policy);
+        let db = TempDatabase::builder().with_opts(opts).build();
+        let conn = db.connect_limbo();
+        conn.execute("PRAGMA cache_size = 200").unwrap();
+        conn.execute("CREATE TABLE t (x)").unwrap();
+        conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 1000)")
+            .unwrap();
+
+        for _ in 0..2 {
+            let rows = limbo_exec_rows(&conn, "SELECT count(*), sum(length(x)) FROM t");
+            assert_eq!(
+                rows,
+                vec![vec![RValue::Integer(1000), RValue::Integer(1_000_000)]]
+            );
+        }
+        let stats = conn.page_cache_stats();
+        assert!(stats.hits > 0, "{policy:?}: {stats:?}");
+        assert!(stats.misses > 0, "{policy:?}: {stats:?}");
+        assert!(stats.evictions > 0, "{policy:?}: {stats:?}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pragma_mmap_size_reads_through_mapping() {
+    let db = TempDatabase::new_empty();
+    let conn = db.connect_limbo();
+    conn.execute("CREATE TABLE t (x)").unwrap();
+    conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 500)")
+        .unwrap();
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+
+    conn.execute("PRAGMA mmap_size = 1048576").unwrap();
+    let rows = limbo_exec_rows(&conn, "PRAGMA mmap_size");
+    assert_eq!(rows, vec![vec![RValue::Integer(1048576)]]);
+
+    // Pages come from the mapping, then from pread past its end, and the
+    // mapping grows after checkpoints extend the file.
+    let conn2 = db.connect_limbo();
+    let rows = limbo_exec_rows(&conn2, "SELECT count(*), sum(length(x)) FROM t");
+    assert_eq!(
+        rows,
+        vec![vec![RValue::Integer(500), RValue::Integer(500_000)]]
+    );
+    conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 2000)")
+        .unwrap();
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    let rows = limbo_exec_rows(&conn2, "SELECT count(*), sum(length(x)) FROM t");
+    assert_eq!(
+        rows,
+        vec![vec![RValue::Integer(2500), RValue::Integer(2_500_000)]]
+    );
+    let rows = limbo_exec_rows(&conn2, "PRAGMA integrity_check");
+    assert_eq!(rows, vec![vec![RValue::Text("ok".to_string())]]);
+
+    conn.execute("PRAGMA mmap_size = -1").unwrap();
+    let rows = limbo_exec_rows(&conn, "PRAGMA mmap_size");
+    assert_eq!(rows, vec![vec![RValue::Integer(0)]]);
+}
+
 #[turso_macros::test]
 fn test_pragma_wal_checkpoint_targets_attached_database(db: TempDatabase) {
     let conn = db.connect_limbo();
@@ -573,3 +641,108 @@ fn test_pragma_vtab_query_with_limit_then_write(db: TempDatabase) {
         "insert after LIMITed pragma vtab query must be committed"
     );
 }
+
+#[turso_macros::test]
+fn test_pragma_temp_store_directory(db: TempDatabase) {
+    let conn = db.connect_limbo();
+    let dir = tempfile::tempdir().unwrap();
+    let dir_str = dir.path().to_str().unwrap().to_string();
+
+    // Unset by default: no row, like SQLite.
+    assert!(limbo_exec_rows(&conn, "PRAGMA temp_store_directory").is_empty());
+
+    conn.execute(format!("PRAGMA temp_store_directory = '{dir_str}'"))
+        .unwrap();
+    assert_eq!(
+        limbo_exec_rows(&conn, "PRAGMA temp_store_directory"),
+        vec![vec![RValue::Text(dir_str.clone())]]
+    );
+
+    // The setting is per connection.
+    let other = db.connect_limbo();
+    assert!(limbo_exec_rows(&other, "PRAGMA temp_store_directory").is_empty());
+
+    let missing = dir.path().join("missing");
+    assert!(conn
+        .execute(format!(
+            "PRAGMA temp_store_directory = '{}'",
+            missing.to_str().unwrap()
+        ))
+        .is_err());
+    assert_eq!(conn.get_temp_store_directory(), Some(dir_str.clone()));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let read_only = dir.path().join("read_only");
+        std::fs::create_dir(&read_only).unwrap();
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o555)).unwrap();
+        // root can write anywhere, so only check when the directory really is read-only
+        if std::fs::File::create(read_only.join("probe")).is_err() {
+            assert!(conn
+                .execute(format!(
+                    "PRAGMA temp_store_directory = '{}'",
+                    read_only.to_str().unwrap()
+                ))
+                .is_err());
+            assert_eq!(conn.get_temp_store_directory(), Some(dir_str));
+        }
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    // An empty string resets to the default location.
+    conn.execute("PRAGMA temp_store_directory = ''").unwrap();
+    assert!(limbo_exec_rows(&conn, "PRAGMA temp_store_directory").is_empty());
+}
+
+#[turso_macros::test]
+fn test_pragma_temp_store_directory_sorter_spill(db: TempDatabase) {
+    let conn = db.connect_limbo();
+    let dir = tempfile::tempdir().unwrap();
+    conn.execute(format!(
+        "PRAGMA temp_store_directory = '{}'",
+        dir.path().to_str().unwrap()
+    ))
+    .unwrap();
+    // The sorter buffer is sized like the page cache; shrinking it to the
+    // minimum makes the ORDER BY below spill.
+    conn.execute("PRAGMA cache_size = -1").unwrap();
+    conn.execute("CREATE TABLE t (x)").unwrap();
+    conn.execute("INSERT INTO t SELECT randomblob(1000) FROM generate_series(1, 2000)")
+        .unwrap();
+
+    let temp_entries = || {
+        std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x").unwrap();
+    let mut rows = 0;
+    stmt.run_with_row_callback(|_| {
+        if rows == 0 {
+            // The spill file is unlinked as soon as it is opened, so a crash
+            // can't leak its contents. It is still open, in the directory.
+            #[cfg(unix)]
+            assert!(temp_entries().is_empty());
+            #[cfg(target_os = "linux")]
+            assert!(std::fs::read_dir("/proc/self/fd")
+                .unwrap()
+                .filter_map(|fd| std::fs::read_link(fd.unwrap().path()).ok())
+                .any(|target| target.starts_with(dir.path())));
+            #[cfg(not(unix))]
+            {
+                let entries = temp_entries();
+                assert_eq!(entries.len(), 1, "{entries:?}");
+                assert!(entries[0].starts_with("tursodb_temp_file-"), "{entries:?}");
+            }
+        }
+        rows += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(rows, 2000);
+    drop(stmt);
+    assert!(temp_entries().is_empty());
+}
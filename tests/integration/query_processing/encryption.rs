@@ -1176,6 +1176,54 @@ fn test_vacuum_into_unencrypts(tmp_db: TempDatabase) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[turso_macros::test]
+fn test_rekey_encrypted_database(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let _ = env_logger::try_init();
+    let old_key = "b1bbfda4f589dc9daaf004fe21111e00dc00c98237102f5c7002a5669fc76327";
+    let new_key = "6f1e9d5b0a3c7e2f4d8b1a6c9e0f3b7d2a5c8e1f4b7d0a3c6e9f2b5d8a1c4e7f";
+    let opts = tmp_db.db_opts;
+    let db_path = tmp_db.path.to_str().unwrap().to_string();
+
+    {
+        let conn = tmp_db.connect_limbo();
+        conn.execute(format!("PRAGMA hexkey = '{old_key}'"))?;
+        conn.execute("PRAGMA cipher = 'aegis256'")?;
+        conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, data BLOB)")?;
+        conn.execute("INSERT INTO t SELECT value, randomblob(500) FROM generate_series(1, 200)")?;
+
+        // The rekey happens when the statement runs, not when it is prepared.
+        let mut rekey = conn.prepare(format!("PRAGMA hexrekey = '{new_key}'"))?;
+        let other = tmp_db.connect_limbo();
+        assert!(rekey.run_ignore_rows().is_err());
+        drop(other);
+        rekey.reset()?;
+        rekey.run_ignore_rows()?;
+        drop(rekey);
+        let rows: Vec<(i64,)> = conn.exec_rows("SELECT count(*) FROM t");
+        assert_eq!(rows, vec![(200,)]);
+        conn.execute("INSERT INTO t VALUES (201, randomblob(500))")?;
+        do_flush(&conn, &tmp_db)?;
+    }
+
+    {
+        let uri = format!("file:{db_path}?cipher=aegis256&hexkey={new_key}");
+        let (_io, conn) = turso_core::Connection::from_uri(&uri, opts, Arc::new(SqliteDialect))?;
+        let rows: Vec<(i64,)> = conn.exec_rows("SELECT count(*) FROM t");
+        assert_eq!(rows, vec![(201,)]);
+        let rows: Vec<(String,)> = conn.exec_rows("PRAGMA integrity_check");
+        assert_eq!(rows, vec![("ok".to_string(),)]);
+    }
+
+    {
+        let uri = format!("file:{db_path}?cipher=aegis256&hexkey={old_key}");
+        let (_io, conn) = turso_core::Connection::from_uri(&uri, opts, Arc::new(SqliteDialect))?;
+        let result = run_query_on_row(&tmp_db, &conn, "SELECT * FROM t", |_row: &Row| {});
+        assert!(result.is_err(), "the old key should no longer work");
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_encrypted_db_then_enable_mvcc_large_payload_chunked() -> anyhow::Result<()> {
     let _ = env_logger::try_init();
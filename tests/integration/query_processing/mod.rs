@@ -7,6 +7,7 @@ mod test_in_seek;
 mod test_materialized_subquery;
 mod test_read_path;
 mod test_vacuum;
+mod test_vtab_order_by;
 mod test_write_path;
 
 mod encryption;
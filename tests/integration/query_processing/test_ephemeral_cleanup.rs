@@ -13,11 +13,15 @@ fn snapshot_temp_dir() -> HashSet<PathBuf> {
         .collect()
 }
 
-/// Find new directories that contain a `tursodb_temp_file` — these are leaked TempFiles.
+/// Find new `tursodb_temp_file-*` files — these are leaked TempFiles.
 fn find_leaked_temp_files(before: &HashSet<PathBuf>, after: &HashSet<PathBuf>) -> Vec<PathBuf> {
     after
         .difference(before)
-        .filter(|p| p.is_dir() && p.join("tursodb_temp_file").exists())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("tursodb_temp_file-"))
+        })
         .cloned()
         .collect()
 }
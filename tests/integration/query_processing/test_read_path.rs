@@ -1037,3 +1037,71 @@ fn test_parameter_column_names(tmp_db: TempDatabase) {
         assert_eq!(names, expected, "Turso column names mismatch for: {sql}");
     }
 }
+
+/// Columns on either side of a value that spills into overflow pages, and
+/// index keys that overflow, read back intact whether or not they fit in the
+/// part of the row kept on the leaf page.
+#[turso_macros::test(
+    init_sql = "create table t (a integer, b text, c blob, d text); create index t_d on t(d);"
+)]
+fn test_read_columns_around_overflowing_value(tmp_db: TempDatabase) {
+    let conn = tmp_db.connect_limbo();
+    conn.execute(
+        "insert into t select value, 'row' || value, zeroblob(20000 * value), replace(hex(zeroblob(1500)), '0', char(96 + value)) || value from generate_series(1, 3)",
+    )
+    .unwrap();
+    let sqlite_conn = SqliteConnection::open_in_memory().unwrap();
+
+    let rows = limbo_exec_rows(&conn, "select a, b, length(c), length(d) from t order by a");
+    let expected = sqlite_exec_rows(
+        &sqlite_conn,
+        "values (1, 'row1', 20000, 3001), (2, 'row2', 40000, 3001), (3, 'row3', 60000, 3001)",
+    );
+    assert_eq!(rows, expected);
+
+    let rows = limbo_exec_rows(
+        &conn,
+        "select a, substr(d, 1, 2), substr(d, -1) from t indexed by t_d where d > 'b' order by d",
+    );
+    let expected = sqlite_exec_rows(&sqlite_conn, "values (2, 'bb', '2'), (3, 'cc', '3')");
+    assert_eq!(rows, expected);
+}
+
+#[turso_macros::test(init_sql = "create table t (a integer, b text); create index t_b on t(b);")]
+fn test_conditions_with_poisoned_temp_registers(tmp_db: TempDatabase) {
+    let conn = tmp_db.connect_limbo();
+    conn.set_poison_temp_registers(true);
+    let rows = (1..=20)
+        .map(|i| match i % 3 {
+            0 => format!("({i}, null)"),
+            _ => format!("({i}, 'row{i}')"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert = format!("insert into t values {rows}");
+    conn.execute(&insert).unwrap();
+    let sqlite_conn = SqliteConnection::open_in_memory().unwrap();
+    sqlite_conn
+        .execute_batch("create table t (a integer, b text); create index t_b on t(b);")
+        .unwrap();
+    sqlite_conn.execute(&insert, []).unwrap();
+
+    // Every condition below evaluates into a temporary register that is
+    // released, and so poisoned, before the next row is read.
+    for query in [
+        "select a from t where a between 3 and 7 order by a",
+        "select a from t where a not between 3 and 17 order by a",
+        "select a from t where b is null order by a",
+        "select a from t where b is not null and a < 5 order by a",
+        "select a from t where b like 'row1%' order by a",
+        "select a from t where (a + 1) % 2 = 0 or b glob '*2' order by a",
+        "select count(*) from t where a in (select a from t where b is null)",
+        "select t1.a, t2.a from t t1 join t t2 on t1.a = t2.a + 1 where t1.b is null order by 1",
+    ] {
+        assert_eq!(
+            limbo_exec_rows(&conn, query),
+            sqlite_exec_rows(&sqlite_conn, query),
+            "{query}"
+        );
+    }
+}
@@ -5578,47 +5578,13 @@ fn test_mvcc_vacuum_into_preserves_full_autovacuum_after_reopen() -> anyhow::Res
 }
 
 #[test]
-fn test_plain_vacuum_incremental_autovacuum_still_unsupported() -> anyhow::Result<()> {
-    let opts = DatabaseOpts::new()
-        .with_encryption(true)
-        .with_autovacuum(true);
-    let (_temp_dir, tmp_db) = open_sqlite_autovacuum_db("incremental", opts)?;
-    let conn = tmp_db.connect_limbo();
-
-    let err = conn.execute("VACUUM").unwrap_err();
-    assert_eq!(
-        err.to_string(),
-        "Internal error: Incremental auto-vacuum is not supported",
-        "unexpected error: {err}"
-    );
-
-    Ok(())
+fn test_plain_vacuum_preserves_incremental_autovacuum() -> anyhow::Result<()> {
+    assert_plain_vacuum_preserves_autovacuum_mode("incremental", 2)
 }
 
 #[test]
-fn test_vacuum_into_incremental_autovacuum_still_unsupported() -> anyhow::Result<()> {
-    let opts = DatabaseOpts::new()
-        .with_encryption(true)
-        .with_autovacuum(true);
-    let (_temp_dir, tmp_db) = open_sqlite_autovacuum_db("incremental", opts)?;
-    let conn = tmp_db.connect_limbo();
-    let dest_dir = TempDir::new()?;
-    let dest_path = dest_dir.path().join("vacuum-into.db");
-
-    let err = conn
-        .execute(format!("VACUUM INTO '{}'", dest_path.to_str().unwrap()))
-        .unwrap_err();
-    assert_eq!(
-        err.to_string(),
-        "Internal error: Incremental auto-vacuum is not supported",
-        "unexpected error: {err}"
-    );
-    assert!(
-        !dest_path.exists(),
-        "unsupported VACUUM INTO should not leave an output file behind"
-    );
-
-    Ok(())
+fn test_vacuum_into_preserves_incremental_autovacuum() -> anyhow::Result<()> {
+    assert_vacuum_into_preserves_autovacuum_mode("incremental", 2)
 }
 
 /// Keep one non-ignored plain VACUUM test under the checksum feature so
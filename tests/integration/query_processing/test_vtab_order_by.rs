@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+use turso_ext::{
+    ConstraintInfo, ConstraintOp, ConstraintUsage, IndexInfo, OrderByInfo, ResultCode, VTabCursor,
+    VTabKind, VTabModule, VTabModuleDerive, VTable, Value as ExtValue,
+};
+
+use crate::common::{ExecRows, TempDatabase};
+
+/// The numbers 1 to 5, in ascending order. A `k = ...` constraint is required when
+/// it is offered, and only echoed back. The `seen` column reports the LIMIT and
+/// OFFSET the table applied itself.
+#[derive(VTabModuleDerive)]
+struct OrderedNums;
+
+struct OrderedNumsTable;
+
+struct OrderedNumsCursor {
+    k: Option<i64>,
+    seen: Option<String>,
+    rows: Vec<i64>,
+    pos: usize,
+}
+
+const N_COLUMN: u32 = 0;
+const K_COLUMN: u32 = 2;
+const K_BIT: i32 = 1;
+const LIMIT_BIT: i32 = 2;
+const OFFSET_BIT: i32 = 4;
+
+impl VTabModule for OrderedNums {
+    type Table = OrderedNumsTable;
+    const VTAB_KIND: VTabKind = VTabKind::VirtualTable;
+    const NAME: &'static str = "ordered_nums";
+
+    fn create(_args: &[ExtValue]) -> Result<(String, Self::Table), ResultCode> {
+        let schema = "CREATE TABLE x (n INTEGER, seen TEXT, k HIDDEN)".to_string();
+        Ok((schema, OrderedNumsTable))
+    }
+}
+
+impl VTable for OrderedNumsTable {
+    type Cursor = OrderedNumsCursor;
+    type Error = String;
+
+    fn open(&self, _conn: Option<Arc<turso_ext::Connection>>) -> Result<Self::Cursor, Self::Error> {
+        Ok(OrderedNumsCursor {
+            k: None,
+            seen: None,
+            rows: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    fn best_index(
+        constraints: &[ConstraintInfo],
+        order_by: &[OrderByInfo],
+    ) -> Result<IndexInfo, ResultCode> {
+        let k = constraints
+            .iter()
+            .position(|c| c.column_index == K_COLUMN && c.op == ConstraintOp::Eq);
+        if k.is_some_and(|k| !constraints[k].usable) {
+            return Err(ResultCode::ConstraintViolation);
+        }
+        let order_by_consumed = matches!(
+            order_by,
+            [OrderByInfo {
+                column_index: N_COLUMN,
+                desc: false
+            }]
+        );
+        let position = |op: ConstraintOp| constraints.iter().position(|c| c.op == op);
+        // the rows can only be cut short if nothing else filters or sorts them
+        let applies_limit = (order_by.is_empty() || order_by_consumed)
+            && constraints.iter().enumerate().all(|(idx, c)| {
+                Some(idx) == k || matches!(c.op, ConstraintOp::Limit | ConstraintOp::Offset)
+            });
+        let (limit, offset) = match applies_limit {
+            true => (
+                position(ConstraintOp::Limit),
+                position(ConstraintOp::Offset),
+            ),
+            false => (None, None),
+        };
+        let used = [k, limit, offset];
+        let constraint_usages = (0..constraints.len())
+            .map(|idx| {
+                let argv_index = used
+                    .iter()
+                    .flatten()
+                    .position(|used| *used == idx)
+                    .map(|pos| pos as u32 + 1);
+                ConstraintUsage {
+                    argv_index,
+                    omit: argv_index.is_some(),
+                }
+            })
+            .collect();
+        let idx_num = [K_BIT, LIMIT_BIT, OFFSET_BIT]
+            .into_iter()
+            .zip(used)
+            .filter_map(|(bit, used)| used.map(|_| bit))
+            .sum();
+        Ok(IndexInfo {
+            idx_num,
+            idx_str: Some("ordered_nums".to_string()),
+            order_by_consumed,
+            estimated_cost: 10.0,
+            estimated_rows: 5,
+            constraint_usages,
+        })
+    }
+}
+
+impl VTabCursor for OrderedNumsCursor {
+    type Error = String;
+
+    fn filter(&mut self, args: &[ExtValue], idx_info: Option<(&str, i32)>) -> ResultCode {
+        let idx_num = idx_info.map_or(0, |(_, idx_num)| idx_num);
+        let mut args = args.iter().map(|arg| arg.to_integer().unwrap_or_default());
+        let mut arg = |bit: i32| {
+            if idx_num & bit != 0 {
+                args.next()
+            } else {
+                None
+            }
+        };
+        self.k = arg(K_BIT);
+        let limit = arg(LIMIT_BIT);
+        let offset = arg(OFFSET_BIT);
+        self.seen = limit.map(|limit| format!("{limit}/{}", offset.unwrap_or(0)));
+        self.rows = (1..=5)
+            .skip(offset.unwrap_or(0).max(0) as usize)
+            .take(
+                limit
+                    .and_then(|limit| usize::try_from(limit).ok())
+                    .unwrap_or(usize::MAX),
+            )
+            .collect();
+        self.pos = 0;
+        if self.eof() {
+            ResultCode::EOF
+        } else {
+            ResultCode::OK
+        }
+    }
+
+    fn rowid(&self) -> i64 {
+        self.rows[self.pos]
+    }
+
+    fn column(&self, idx: u32) -> Result<ExtValue, Self::Error> {
+        Ok(match idx {
+            N_COLUMN => ExtValue::from_integer(self.rows[self.pos]),
+            K_COLUMN => self.k.map_or_else(ExtValue::null, ExtValue::from_integer),
+            _ => self
+                .seen
+                .clone()
+                .map_or_else(ExtValue::null, ExtValue::from_text),
+        })
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.rows.len()
+    }
+
+    fn next(&mut self) -> ResultCode {
+        self.pos += 1;
+        if self.eof() {
+            ResultCode::EOF
+        } else {
+            ResultCode::OK
+        }
+    }
+}
+
+fn connect_with_ordered_nums(tmp_db: &TempDatabase) -> anyhow::Result<Arc<turso_core::Connection>> {
+    let conn = tmp_db.connect_limbo();
+    let api = unsafe { conn._build_turso_ext() };
+    let rc = unsafe { OrderedNums::register_OrderedNums(&api as *const _) };
+    assert_eq!(rc, ResultCode::OK);
+    unsafe { conn._free_extension_ctx(api) };
+    conn.execute("CREATE VIRTUAL TABLE nums USING ordered_nums")?;
+    Ok(conn)
+}
+
+fn sorts(conn: &Arc<turso_core::Connection>, sql: &str) -> anyhow::Result<bool> {
+    let mut stmt = conn.prepare(format!("EXPLAIN QUERY PLAN {sql}"))?;
+    let mut plans = Vec::new();
+    stmt.run_with_row_callback(|row| {
+        plans.push(row.get::<String>(3)?);
+        Ok(())
+    })?;
+    Ok(plans.iter().any(|p| p == "USE TEMP B-TREE FOR ORDER BY"))
+}
+
+#[turso_macros::test]
+fn vtab_order_by_consumed_drops_the_sort(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = connect_with_ordered_nums(&tmp_db)?;
+
+    let sql = "SELECT n FROM nums ORDER BY n";
+    assert!(!sorts(&conn, sql)?);
+    let rows: Vec<(i64,)> = conn.exec_rows(sql);
+    assert_eq!(rows, vec![(1,), (2,), (3,), (4,), (5,)]);
+
+    // an order the table does not consume is still sorted
+    let sql = "SELECT n FROM nums ORDER BY n DESC";
+    assert!(sorts(&conn, sql)?);
+    let rows: Vec<(i64,)> = conn.exec_rows(sql);
+    assert_eq!(rows, vec![(5,), (4,), (3,), (2,), (1,)]);
+    Ok(())
+}
+
+#[turso_macros::test]
+fn vtab_order_by_consumed_is_sorted_in_an_inner_loop(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = connect_with_ordered_nums(&tmp_db)?;
+    conn.execute("CREATE TABLE t (x INTEGER)")?;
+    conn.execute("INSERT INTO t VALUES (1), (2)")?;
+
+    // nums needs t.x, so it is the inner loop and its order repeats for every row of t
+    let sql = "SELECT nums.n FROM t JOIN nums ON nums.k = t.x ORDER BY nums.n";
+    assert!(sorts(&conn, sql)?);
+    let rows: Vec<(i64,)> = conn.exec_rows(sql);
+    assert_eq!(
+        rows,
+        vec![(1,), (1,), (2,), (2,), (3,), (3,), (4,), (4,), (5,), (5,)]
+    );
+    Ok(())
+}
+
+#[turso_macros::test]
+fn vtab_is_offered_the_limit_and_offset(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = connect_with_ordered_nums(&tmp_db)?;
+
+    let rows: Vec<(i64, String)> =
+        conn.exec_rows("SELECT n, seen FROM nums ORDER BY n LIMIT 2 OFFSET 1");
+    assert_eq!(rows, vec![(2, "2/1".to_string()), (3, "2/1".to_string())]);
+    let rows: Vec<(i64, String)> = conn.exec_rows("SELECT n, seen FROM nums LIMIT 1 + 2");
+    assert_eq!(
+        rows,
+        vec![
+            (1, "3/0".to_string()),
+            (2, "3/0".to_string()),
+            (3, "3/0".to_string())
+        ]
+    );
+
+    // A sort or a filter the table does not apply comes first, so it gets no
+    // LIMIT, or does not use it.
+    for (sql, expected) in [
+        (
+            "SELECT n, seen IS NULL FROM nums ORDER BY n DESC LIMIT 2 OFFSET 1",
+            vec![(4, 1), (3, 1)],
+        ),
+        (
+            "SELECT n, seen IS NULL FROM nums WHERE n > 2 ORDER BY n LIMIT 2",
+            vec![(3, 1), (4, 1)],
+        ),
+        (
+            "SELECT n, seen IS NULL FROM nums WHERE n % 2 = 1 LIMIT 2 OFFSET 1",
+            vec![(3, 1), (5, 1)],
+        ),
+    ] {
+        let rows: Vec<(i64, i64)> = conn.exec_rows(sql);
+        assert_eq!(rows, expected, "{sql}");
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::common::TempDatabase;
+use turso_core::vdbe::StepResult;
+use turso_core::{LimboError, StatementStatusCounter};
+
+fn run_to_completion(stmt: &mut turso_core::Statement) -> turso_core::Result<()> {
+    loop {
+        match stmt.step()? {
+            StepResult::IO => stmt._io().step()?,
+            StepResult::Row | StepResult::Yield => continue,
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[turso_macros::test]
+fn sorter_memory_is_reported_as_high_water(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    conn.execute("CREATE TABLE t(x TEXT);")?;
+    conn.execute("INSERT INTO t SELECT printf('%0100d', value) FROM generate_series(1, 500);")?;
+
+    let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x DESC;")?;
+    run_to_completion(&mut stmt)?;
+    let high_water = stmt.stmt_status(StatementStatusCounter::MemoryHighWater);
+    assert!(high_water >= 500 * 100, "high water {high_water}");
+
+    stmt.reset_stmt_status(StatementStatusCounter::MemoryHighWater);
+    assert!(stmt.stmt_status(StatementStatusCounter::MemoryHighWater) < high_water);
+    Ok(())
+}
+
+#[turso_macros::test]
+fn statement_memory_limit_fails_large_sorts(tmp_db: TempDatabase) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    conn.execute("CREATE TABLE t(x TEXT);")?;
+    conn.execute("INSERT INTO t SELECT printf('%0100d', value) FROM generate_series(1, 500);")?;
+    conn.set_statement_memory_limit(4096);
+
+    let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x DESC;")?;
+    let result = run_to_completion(&mut stmt);
+    assert!(
+        matches!(result, Err(LimboError::OutOfMemory)),
+        "expected out of memory, got {result:?}"
+    );
+
+    conn.set_statement_memory_limit(0);
+    let mut stmt = conn.prepare("SELECT x FROM t ORDER BY x DESC;")?;
+    run_to_completion(&mut stmt)?;
+    Ok(())
+}
@@ -40,3 +40,21 @@ fn create_table_if_not_exists_existing_is_not_readonly(tmp_db: TempDatabase) ->
     assert!(!is_stmt_readonly(&conn, "CREATE TABLE IF NOT EXISTS t(x)"));
     Ok(())
 }
+
+#[turso_macros::test(init_sql = "CREATE TABLE t(x)")]
+fn write_prepared_before_begin_readonly_fails_at_runtime(
+    tmp_db: TempDatabase,
+) -> anyhow::Result<()> {
+    let conn = tmp_db.connect_limbo();
+    let mut insert = conn.prepare("INSERT INTO t VALUES (1)")?;
+    conn.execute("BEGIN READONLY")?;
+    assert!(matches!(
+        insert.run_ignore_rows(),
+        Err(turso_core::LimboError::ReadOnly)
+    ));
+    conn.execute("COMMIT")?;
+
+    insert.reset()?;
+    insert.run_ignore_rows()?;
+    Ok(())
+}
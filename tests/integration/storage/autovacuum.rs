@@ -1,7 +1,8 @@
+use crate::common::{rusqlite_integrity_check, ExecRows, TempDatabase};
 use std::sync::Arc;
 use tempfile::TempDir;
 use turso_core::SqliteDialect;
-use turso_core::{Database, DatabaseOpts, OpenFlags, PlatformIO};
+use turso_core::{Connection, Database, DatabaseOpts, OpenFlags, PlatformIO};
 
 #[test]
 fn test_autovacuum_readonly_behavior() {
@@ -45,3 +46,131 @@ fn test_autovacuum_readonly_behavior() {
         );
     }
 }
+
+fn open_autovacuum_db(temp_dir: &TempDir, pragma_value: &str) -> TempDatabase {
+    let db_path = temp_dir.path().join("autovacuum.db");
+    {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.pragma_update(None, "page_size", 1024).unwrap();
+        conn.pragma_update(None, "auto_vacuum", pragma_value)
+            .unwrap();
+        conn.execute_batch("VACUUM").unwrap();
+        conn.execute("CREATE TABLE t(id INTEGER PRIMARY KEY, payload TEXT)", ())
+            .unwrap();
+        conn.execute("CREATE INDEX idx_t_payload ON t(payload)", ())
+            .unwrap();
+        for i in 0..200 {
+            conn.execute(
+                "INSERT INTO t VALUES(?1, ?2)",
+                rusqlite::params![i, format!("{i}-{}", "payload".repeat(40))],
+            )
+            .unwrap();
+        }
+    }
+    TempDatabase::new_with_existent_with_opts(&db_path, DatabaseOpts::new().with_autovacuum(true))
+}
+
+fn scalar_i64(conn: &Arc<Connection>, sql: &str) -> i64 {
+    let rows: Vec<(i64,)> = conn.exec_rows(sql);
+    assert_eq!(rows.len(), 1, "expected one row for {sql}");
+    rows[0].0
+}
+
+#[test]
+fn test_full_autovacuum_truncates_on_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let tmp_db = open_autovacuum_db(&temp_dir, "FULL");
+    let conn = tmp_db.connect_limbo();
+    let pages_before = scalar_i64(&conn, "PRAGMA page_count");
+
+    conn.execute("DELETE FROM t WHERE id >= 20").unwrap();
+    assert_eq!(scalar_i64(&conn, "PRAGMA freelist_count"), 0);
+    let pages_after = scalar_i64(&conn, "PRAGMA page_count");
+    assert!(
+        pages_after < pages_before,
+        "expected the file to shrink from {pages_before} pages, got {pages_after}"
+    );
+    assert_eq!(scalar_i64(&conn, "SELECT COUNT(*) FROM t"), 20);
+
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    let file_size = std::fs::metadata(&tmp_db.path).unwrap().len();
+    assert_eq!(file_size, pages_after as u64 * 1024);
+    rusqlite_integrity_check(&tmp_db.path).unwrap();
+}
+
+#[test]
+fn test_incremental_vacuum_frees_requested_pages() {
+    let temp_dir = TempDir::new().unwrap();
+    let tmp_db = open_autovacuum_db(&temp_dir, "INCREMENTAL");
+    let conn = tmp_db.connect_limbo();
+
+    conn.execute("DELETE FROM t WHERE id >= 20").unwrap();
+    let free_pages = scalar_i64(&conn, "PRAGMA freelist_count");
+    let pages = scalar_i64(&conn, "PRAGMA page_count");
+    assert!(free_pages > 2, "expected free pages, got {free_pages}");
+
+    conn.execute("PRAGMA incremental_vacuum(2)").unwrap();
+    assert_eq!(scalar_i64(&conn, "PRAGMA freelist_count"), free_pages - 2);
+    assert_eq!(scalar_i64(&conn, "PRAGMA page_count"), pages - 2);
+
+    conn.execute("PRAGMA incremental_vacuum").unwrap();
+    assert_eq!(scalar_i64(&conn, "PRAGMA freelist_count"), 0);
+    assert!(scalar_i64(&conn, "PRAGMA page_count") < pages - 2);
+    assert_eq!(scalar_i64(&conn, "SELECT COUNT(*) FROM t"), 20);
+
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    rusqlite_integrity_check(&tmp_db.path).unwrap();
+}
+
+#[test]
+fn test_incremental_vacuum_rolls_back() {
+    let temp_dir = TempDir::new().unwrap();
+    let tmp_db = open_autovacuum_db(&temp_dir, "INCREMENTAL");
+    let conn = tmp_db.connect_limbo();
+
+    conn.execute("DELETE FROM t WHERE id >= 20").unwrap();
+    let free_pages = scalar_i64(&conn, "PRAGMA freelist_count");
+    let pages = scalar_i64(&conn, "PRAGMA page_count");
+
+    conn.execute("BEGIN").unwrap();
+    conn.execute("PRAGMA incremental_vacuum").unwrap();
+    assert_eq!(scalar_i64(&conn, "PRAGMA freelist_count"), 0);
+    conn.execute("ROLLBACK").unwrap();
+
+    assert_eq!(scalar_i64(&conn, "PRAGMA freelist_count"), free_pages);
+    assert_eq!(scalar_i64(&conn, "PRAGMA page_count"), pages);
+    assert_eq!(scalar_i64(&conn, "SELECT COUNT(*) FROM t"), 20);
+
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    rusqlite_integrity_check(&tmp_db.path).unwrap();
+}
+
+#[test]
+fn test_full_autovacuum_on_new_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("autovacuum.db");
+    let tmp_db = TempDatabase::new_with_existent_with_opts(
+        &db_path,
+        DatabaseOpts::new().with_autovacuum(true),
+    );
+    let conn = tmp_db.connect_limbo();
+
+    conn.execute("PRAGMA auto_vacuum = full").unwrap();
+    conn.execute("CREATE TABLE a(id INTEGER PRIMARY KEY, payload BLOB)")
+        .unwrap();
+    conn.execute("CREATE TABLE b(id INTEGER PRIMARY KEY, payload BLOB)")
+        .unwrap();
+    for i in 0..50 {
+        conn.execute(&format!("INSERT INTO a VALUES({i}, zeroblob(3000))"))
+            .unwrap();
+        conn.execute(&format!("INSERT INTO b VALUES({i}, zeroblob(300))"))
+            .unwrap();
+    }
+    conn.execute("DELETE FROM a WHERE id % 2 = 0").unwrap();
+    conn.execute("DELETE FROM b WHERE id >= 10").unwrap();
+
+    assert_eq!(scalar_i64(&conn, "PRAGMA auto_vacuum"), 1);
+    assert_eq!(scalar_i64(&conn, "PRAGMA freelist_count"), 0);
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    rusqlite_integrity_check(&tmp_db.path).unwrap();
+}
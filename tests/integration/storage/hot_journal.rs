@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use turso_core::{Database, DatabaseOpts, OpenFlags, SqliteDialect};
+
+use crate::common::{rusqlite_integrity_check, ExecRows};
+
+fn journal_path(db_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}-journal", db_path.display()))
+}
+
+fn open_with_limbo(db_path: &Path, flags: OpenFlags) -> turso_core::Result<Arc<Database>> {
+    let io = Arc::new(turso_core::PlatformIO::new().unwrap());
+    Database::open_file_with_flags(
+        io,
+        db_path.to_str().unwrap(),
+        flags,
+        DatabaseOpts::new(),
+        None,
+        Arc::new(SqliteDialect),
+    )
+}
+
+/// Leave `crashed.db` in `dir` as SQLite would after crashing in the middle of
+/// a write transaction in DELETE journal mode: the transaction spilled some of
+/// its pages to the database file, and the hot journal holds their originals.
+fn create_crashed_db(dir: &TempDir) -> PathBuf {
+    let live_path = dir.path().join("live.db");
+    let conn = rusqlite::Connection::open(&live_path).unwrap();
+    conn.pragma_update(None, "journal_mode", "delete").unwrap();
+    conn.pragma_update(None, "cache_size", 10).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, val BLOB);
+         WITH RECURSIVE s(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM s WHERE i < 100)
+         INSERT INTO t SELECT i, randomblob(500) FROM s;",
+    )
+    .unwrap();
+    conn.execute_batch(
+        "BEGIN;
+         UPDATE t SET val = randomblob(600);
+         WITH RECURSIVE s(i) AS (SELECT 101 UNION ALL SELECT i + 1 FROM s WHERE i < 2000)
+         INSERT INTO t SELECT i, randomblob(1000) FROM s;",
+    )
+    .unwrap();
+
+    let crashed_path = dir.path().join("crashed.db");
+    std::fs::copy(&live_path, &crashed_path).unwrap();
+    std::fs::copy(journal_path(&live_path), journal_path(&crashed_path)).unwrap();
+    conn.execute_batch("ROLLBACK").unwrap();
+    crashed_path
+}
+
+#[test]
+fn test_hot_journal_is_rolled_back_on_open() {
+    let dir = TempDir::new().unwrap();
+    let db_path = create_crashed_db(&dir);
+
+    let db = open_with_limbo(&db_path, OpenFlags::default()).unwrap();
+    assert!(!journal_path(&db_path).exists());
+    let conn = db.connect().unwrap();
+    let rows: Vec<(i64, i64)> = conn.exec_rows("SELECT count(*), max(length(val)) FROM t");
+    assert_eq!(rows, vec![(100, 500)]);
+    conn.close().unwrap();
+    drop(db);
+
+    rusqlite_integrity_check(&db_path).unwrap();
+}
+
+#[test]
+fn test_hot_journal_refuses_read_only_open() {
+    let dir = TempDir::new().unwrap();
+    let db_path = create_crashed_db(&dir);
+
+    assert!(matches!(
+        open_with_limbo(&db_path, OpenFlags::ReadOnly),
+        Err(turso_core::LimboError::ReadOnly)
+    ));
+    assert!(journal_path(&db_path).exists());
+}
+
+#[test]
+fn test_committed_persist_journal_is_ignored() {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("persist.db");
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.pragma_update(None, "journal_mode", "persist").unwrap();
+    conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")
+        .unwrap();
+    drop(conn);
+    assert!(journal_path(&db_path).exists());
+
+    let db = open_with_limbo(&db_path, OpenFlags::default()).unwrap();
+    let conn = db.connect().unwrap();
+    let rows: Vec<(i64,)> = conn.exec_rows("SELECT x FROM t");
+    assert_eq!(rows, vec![(1,)]);
+    assert!(journal_path(&db_path).exists());
+}
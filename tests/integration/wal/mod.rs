@@ -1,2 +1,3 @@
+mod test_sqlite_wal;
 mod test_wal;
 mod test_wal_publish_backfill_race;
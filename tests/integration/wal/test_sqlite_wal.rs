@@ -0,0 +1,118 @@
+use crate::common::{limbo_exec_rows, rusqlite_integrity_check, TempDatabase};
+use rusqlite::types::Value as RValue;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Open `path` with SQLite in WAL mode without automatic checkpoints, so
+/// every commit stays in the `-wal` file.
+fn open_sqlite_wal(path: &Path) -> rusqlite::Connection {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.pragma_update(None, "journal_mode", "wal").unwrap();
+    conn.pragma_update(None, "wal_autocheckpoint", 0).unwrap();
+    conn
+}
+
+/// Copy the database and its WAL while SQLite still has them open: this is
+/// what turso finds on disk if the SQLite process dies at this point. SQLite
+/// checkpoints and deletes the WAL when its last connection closes, so a
+/// copy is the only way to keep a non-empty one around.
+fn snapshot_sqlite_files(path: &Path, dir: &Path) -> PathBuf {
+    let copy = dir.join("copy.db");
+    std::fs::copy(path, &copy).unwrap();
+    let wal = PathBuf::from(format!("{}-wal", path.display()));
+    let wal_len = std::fs::metadata(&wal).unwrap().len();
+    assert!(wal_len > 0, "SQLite should have left frames in the WAL");
+    std::fs::copy(&wal, format!("{}-wal", copy.display())).unwrap();
+    copy
+}
+
+/// Insert `n` rows of 1000 random bytes. SQLite's library build has no
+/// `generate_series`, hence the recursive CTE.
+fn insert_blobs(conn: &rusqlite::Connection, n: usize) {
+    conn.execute(
+        &format!(
+            "WITH RECURSIVE s(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM s WHERE i < {n}) \
+             INSERT INTO t (v) SELECT randomblob(1000) FROM s"
+        ),
+        (),
+    )
+    .unwrap();
+}
+
+fn count_rows(conn: &std::sync::Arc<turso_core::Connection>) -> Vec<Vec<RValue>> {
+    limbo_exec_rows(conn, "SELECT count(*), sum(length(v)) FROM t")
+}
+
+/// Committed SQLite frames are read from the WAL, frames of a transaction
+/// that had spilled to the WAL but never committed are ignored.
+#[test]
+fn test_open_sqlite_wal_with_uncommitted_tail() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sqlite.db");
+    let sqlite = open_sqlite_wal(&path);
+    sqlite
+        .execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v BLOB)", ())
+        .unwrap();
+    insert_blobs(&sqlite, 100);
+    // A tiny cache makes SQLite spill the open transaction's dirty pages to
+    // the WAL before it commits.
+    sqlite.pragma_update(None, "cache_size", 10).unwrap();
+    sqlite.execute("BEGIN", ()).unwrap();
+    insert_blobs(&sqlite, 500);
+
+    let copy = snapshot_sqlite_files(&path, dir.path());
+    sqlite.execute("ROLLBACK", ()).unwrap();
+
+    let db = TempDatabase::new_with_existent(&copy);
+    let conn = db.connect_limbo();
+    assert_eq!(
+        count_rows(&conn),
+        vec![vec![RValue::Integer(100), RValue::Integer(100_000)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&conn, "PRAGMA integrity_check"),
+        vec![vec![RValue::Text("ok".to_string())]]
+    );
+}
+
+/// After a full checkpoint SQLite restarts the WAL: new frames overwrite it
+/// from the start with fresh salts and the older, longer generation is left
+/// behind them. Those stale frames must not be replayed.
+#[test]
+fn test_open_sqlite_wal_after_restart() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sqlite.db");
+    let sqlite = open_sqlite_wal(&path);
+    sqlite
+        .execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v BLOB)", ())
+        .unwrap();
+    insert_blobs(&sqlite, 300);
+    sqlite
+        .query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(()))
+        .unwrap();
+    sqlite.execute("DELETE FROM t WHERE id > 10", ()).unwrap();
+
+    let copy = snapshot_sqlite_files(&path, dir.path());
+    drop(sqlite);
+
+    let db = TempDatabase::new_with_existent(&copy);
+    let conn = db.connect_limbo();
+    assert_eq!(
+        count_rows(&conn),
+        vec![vec![RValue::Integer(10), RValue::Integer(10_000)]]
+    );
+
+    // turso keeps appending to the recovered WAL, and SQLite can read the
+    // result back once it is checkpointed.
+    conn.execute("INSERT INTO t (v) VALUES (randomblob(1000))")
+        .unwrap();
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+    drop(conn);
+    drop(db);
+    rusqlite_integrity_check(&copy).unwrap();
+    let sqlite = rusqlite::Connection::open(&copy).unwrap();
+    let count: i64 = sqlite
+        .query_row("SELECT count(*) FROM t", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 11);
+}
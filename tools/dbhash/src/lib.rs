@@ -39,6 +39,34 @@ pub struct DbHashResult {
     pub rows_hashed: usize,
 }
 
+/// Order-independent digest of a table's rows.
+///
+/// Each row is hashed with [`Value::row_hash`] (the same function exposed to
+/// SQL as `row_hash(...)`) and the row hashes are summed, so two tables with
+/// the same multiset of rows have the same digest regardless of rowid
+/// assignment or scan order. `digest` is also what `hash_table('t')` returns
+/// in SQL, as a signed integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TableDigest {
+    /// Number of rows folded into the digest.
+    pub rows: usize,
+    /// Wrapping sum of the row hashes.
+    pub digest: u64,
+}
+
+impl TableDigest {
+    pub fn add_row<'a>(&mut self, values: impl Iterator<Item = &'a Value>) {
+        self.rows += 1;
+        self.digest = self.digest.wrapping_add(Value::row_hash(values));
+    }
+}
+
+impl std::fmt::Display for TableDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.digest)
+    }
+}
+
 /// Compute content hash of a database.
 ///
 /// The hash is computed over the logical content of the database:
@@ -100,6 +128,61 @@ pub fn hash_database_with_database_opts(
     })
 }
 
+/// Compute an order-independent [`TableDigest`] for every user table matching
+/// `options.table_filter`, sorted by table name.
+pub fn digest_tables(
+    path: &str,
+    options: &DbHashOptions,
+) -> Result<Vec<(String, TableDigest)>, LimboError> {
+    let io: Arc<dyn IO> = Arc::new(PlatformIO::new()?);
+    let db = Database::open_file_with_flags(
+        io.clone(),
+        path,
+        OpenFlags::default(),
+        DatabaseOpts::new(),
+        None,
+        Arc::new(SqliteDialect),
+    )?;
+    let conn = db.connect()?;
+
+    let filter = options.table_filter.as_deref().unwrap_or("%");
+    get_table_names(&conn, &io, filter)?
+        .into_iter()
+        .map(|table| {
+            let digest = table_digest(&conn, &io, &table)?;
+            Ok((table, digest))
+        })
+        .collect()
+}
+
+/// Compute an order-independent [`TableDigest`] of a table on an open connection.
+pub fn table_digest(
+    conn: &Arc<turso_core::Connection>,
+    io: &Arc<dyn IO>,
+    table_name: &str,
+) -> Result<TableDigest, LimboError> {
+    let sql = format!("SELECT * FROM \"{}\"", table_name.replace('"', "\"\""));
+    let mut stmt = conn.prepare(&sql)?;
+    let mut digest = TableDigest::default();
+
+    loop {
+        match stmt.step()? {
+            StepResult::Row => {
+                let row = stmt.row().unwrap();
+                digest.add_row(row.get_values());
+            }
+            StepResult::IO => io.step()?,
+            StepResult::Yield => continue,
+            StepResult::Done => break,
+            StepResult::Busy | StepResult::Interrupt => {
+                return Err(LimboError::Busy);
+            }
+        }
+    }
+
+    Ok(digest)
+}
+
 /// Get list of user tables (excludes sqlite_%, virtual tables).
 fn get_table_names(
     conn: &Arc<turso_core::Connection>,
@@ -1,7 +1,7 @@
 //! turso-dbhash CLI - Compute SHA1 hash of SQLite database content.
 
 use clap::Parser;
-use turso_dbhash::{hash_database, DbHashOptions};
+use turso_dbhash::{digest_tables, hash_database, DbHashOptions};
 
 #[derive(Parser)]
 #[command(name = "turso-dbhash")]
@@ -26,6 +26,10 @@ struct Args {
     /// Trace hash inputs to stderr
     #[arg(long)]
     debug: bool,
+
+    /// Print an order-independent digest per table instead of the database hash
+    #[arg(long)]
+    table_digests: bool,
 }
 
 fn main() {
@@ -46,6 +50,20 @@ fn main() {
     let mut exit_code = 0;
 
     for file in &args.files {
+        if args.table_digests {
+            match digest_tables(file, &options) {
+                Ok(digests) => {
+                    for (table, digest) in digests {
+                        println!("{digest} {} {file}:{table}", digest.rows);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error hashing '{file}': {e}");
+                    exit_code = 1;
+                }
+            }
+            continue;
+        }
         match hash_database(file, &options) {
             Ok(result) => {
                 println!("{} {}", result.hash, file);
@@ -0,0 +1,126 @@
+//! Tests for order-independent table digests.
+
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use turso_core::{
+    Database, DatabaseOpts, Numeric, OpenFlags, PlatformIO, SqliteDialect, Value, IO,
+};
+use turso_dbhash::{digest_tables, table_digest, DbHashOptions};
+
+fn open_db(file: &NamedTempFile) -> (Arc<dyn IO>, Arc<Database>) {
+    let io: Arc<dyn IO> = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file_with_flags(
+        io.clone(),
+        file.path().to_str().unwrap(),
+        OpenFlags::default(),
+        DatabaseOpts::new().with_generated_columns(true),
+        None,
+        Arc::new(SqliteDialect),
+    )
+    .unwrap();
+    (io, db)
+}
+
+fn create_db(statements: &[&str]) -> NamedTempFile {
+    let file = NamedTempFile::new().expect("Failed to create temp file");
+    let (_io, db) = open_db(&file);
+    let conn = db.connect().unwrap();
+    for sql in statements {
+        conn.execute(sql).unwrap();
+    }
+    conn.close().unwrap();
+    file
+}
+
+fn digests(file: &NamedTempFile) -> Vec<(String, turso_dbhash::TableDigest)> {
+    digest_tables(file.path().to_str().unwrap(), &DbHashOptions::default()).unwrap()
+}
+
+#[test]
+fn test_digest_ignores_row_order_and_rowids() {
+    let a = create_db(&[
+        "CREATE TABLE t (x, y)",
+        "INSERT INTO t VALUES (1, 'one'), (2, 'two'), (NULL, x'ff')",
+    ]);
+    let b = create_db(&[
+        "CREATE TABLE t (x, y)",
+        "INSERT INTO t VALUES (NULL, x'ff'), (2, 'two'), (1, 'one')",
+    ]);
+
+    let (a, b) = (digests(&a), digests(&b));
+    assert_eq!(a.len(), 1);
+    assert_eq!(a[0].0, "t");
+    assert_eq!(a[0].1.rows, 3);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn test_digest_detects_changed_values() {
+    let a = create_db(&["CREATE TABLE t (x)", "INSERT INTO t VALUES (1), (2)"]);
+    let b = create_db(&["CREATE TABLE t (x)", "INSERT INTO t VALUES (1), ('2')"]);
+    let c = create_db(&["CREATE TABLE t (x)", "INSERT INTO t VALUES (1), (2), (2)"]);
+
+    let (a, b, c) = (digests(&a), digests(&b), digests(&c));
+    assert_ne!(a[0].1.digest, b[0].1.digest);
+    assert_ne!(a[0].1.digest, c[0].1.digest);
+    assert_eq!(c[0].1.rows, 3);
+}
+
+#[test]
+fn test_digest_matches_sql_row_hash() {
+    let file = create_db(&[
+        "CREATE TABLE t (x, y)",
+        "INSERT INTO t VALUES (1, 'a'), (2.5, NULL), (x'00', -7)",
+    ]);
+    let (io, db) = open_db(&file);
+    let conn = db.connect().unwrap();
+
+    let mut expected = 0u64;
+    let mut stmt = conn.prepare("SELECT row_hash(x, y) FROM t").unwrap();
+    stmt.run_with_row_callback(|row| {
+        let Value::Numeric(Numeric::Integer(hash)) = row.get_value(0) else {
+            panic!("row_hash must return an integer");
+        };
+        expected = expected.wrapping_add(*hash as u64);
+        Ok(())
+    })
+    .unwrap();
+
+    let digest = table_digest(&conn, &io, "t").unwrap();
+    assert_eq!(digest.rows, 3);
+    assert_eq!(digest.digest, expected);
+}
+
+#[test]
+fn test_digest_matches_sql_hash_table() {
+    let file = create_db(&[
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, x, doubled AS (x * 2))",
+        "INSERT INTO t (x) VALUES (1), (2.5), ('a'), (NULL)",
+        "ALTER TABLE t ADD COLUMN added DEFAULT 'default'",
+        "CREATE TABLE empty (x)",
+    ]);
+    let (io, db) = open_db(&file);
+    let conn = db.connect().unwrap();
+
+    let hash_table = |sql: &str| {
+        let mut hash = None;
+        let mut stmt = conn.prepare(sql).unwrap();
+        stmt.run_with_row_callback(|row| {
+            hash = Some(row.get_value(0).clone());
+            Ok(())
+        })
+        .unwrap();
+        hash.unwrap()
+    };
+    // the rowid alias, the generated column and the added column are all hashed
+    let digest = table_digest(&conn, &io, "t").unwrap();
+    assert_eq!(digest.rows, 4);
+    for sql in ["SELECT hash_table('t')", "SELECT hash_table('main.t')"] {
+        assert_eq!(
+            hash_table(sql),
+            Value::from_i64(digest.digest as i64),
+            "{sql}"
+        );
+    }
+    assert_eq!(hash_table("SELECT hash_table('empty')"), Value::from_i64(0));
+}
@@ -0,0 +1,177 @@
+//! turso-replay: Re-execute a captured workload against a copy of a database.
+//!
+//! Workloads are recorded with `Connection::set_workload_capture` in
+//! `turso_core`, one JSON object per executed statement. Replaying never
+//! touches the original database: it is copied (together with its WAL) to a
+//! target path first, and statements run against the copy.
+
+use std::num::NonZero;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::Value as Json;
+use turso_core::{
+    Database, DatabaseOpts, LimboError, OpenFlags, PlatformIO, SqliteDialect, Value, IO,
+};
+
+/// A statement read from a capture file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedStatement {
+    /// Time since the first captured statement.
+    pub elapsed: Duration,
+    pub sql: String,
+    /// Bound parameters by 1-based index. Redacted parameters hold a
+    /// placeholder of their original type (0, 0.0, '' or x'').
+    pub params: Vec<(NonZero<usize>, Value)>,
+}
+
+/// Parse a capture file's contents. Empty lines are skipped.
+pub fn parse_workload(contents: &str) -> Result<Vec<CapturedStatement>, String> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_statement(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+fn parse_statement(line: &str) -> Result<CapturedStatement, String> {
+    let json: Json = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let elapsed_us = json["elapsed_us"]
+        .as_u64()
+        .ok_or("missing \"elapsed_us\"")?;
+    let sql = json["sql"].as_str().ok_or("missing \"sql\"")?.to_string();
+    let params = json["params"]
+        .as_array()
+        .ok_or("missing \"params\"")?
+        .iter()
+        .map(parse_param)
+        .collect::<Result<_, _>>()?;
+    Ok(CapturedStatement {
+        elapsed: Duration::from_micros(elapsed_us),
+        sql,
+        params,
+    })
+}
+
+fn parse_param(param: &Json) -> Result<(NonZero<usize>, Value), String> {
+    let index = param["index"]
+        .as_u64()
+        .and_then(|i| NonZero::new(i as usize))
+        .ok_or("parameter without a valid \"index\"")?;
+    let type_name = param["type"].as_str().ok_or("parameter without \"type\"")?;
+    let redacted = param["redacted"].as_bool().unwrap_or(false);
+    let value = &param["value"];
+    let value = match type_name {
+        "null" => Value::Null,
+        "integer" if redacted => Value::from_i64(0),
+        "integer" => Value::from_i64(value.as_i64().ok_or("integer parameter without value")?),
+        "real" if redacted => Value::from_f64(0.0),
+        "real" => match value {
+            Json::Number(n) => Value::from_f64(n.as_f64().ok_or("real parameter out of range")?),
+            // Infinities are captured as text.
+            Json::String(s) => Value::from_f64(s.parse().map_err(|_| format!("bad real {s:?}"))?),
+            _ => return Err("real parameter without value".to_string()),
+        },
+        "text" if redacted => Value::build_text(""),
+        "text" => Value::build_text(
+            value
+                .as_str()
+                .ok_or("text parameter without value")?
+                .to_string(),
+        ),
+        "blob" if redacted => Value::from_blob(Vec::new()),
+        "blob" => Value::from_blob(
+            hex::decode(value.as_str().ok_or("blob parameter without value")?)
+                .map_err(|e| e.to_string())?,
+        ),
+        other => return Err(format!("unknown parameter type {other:?}")),
+    };
+    Ok((index, value))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Run statements back to back.
+    AsFastAsPossible,
+    /// Keep the captured gaps between statements, divided by this factor
+    /// (1.0 reproduces the original pacing).
+    Scaled(f64),
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Statements that ran to completion.
+    pub executed: usize,
+    /// Statements that failed, with their position in the workload and error.
+    pub errors: Vec<(usize, String)>,
+    /// Total time spent preparing and running statements, excluding pacing sleeps.
+    pub busy_time: Duration,
+    /// The slowest statement's latency.
+    pub max_latency: Duration,
+}
+
+/// Copy `source` (and its WAL, if any) to `target`, overwriting it.
+pub fn copy_database(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::fs::copy(source, target)?;
+    let wal = |path: &Path| {
+        let mut wal = path.as_os_str().to_owned();
+        wal.push("-wal");
+        std::path::PathBuf::from(wal)
+    };
+    let (source_wal, target_wal) = (wal(source), wal(target));
+    if source_wal.exists() {
+        std::fs::copy(source_wal, target_wal)?;
+    } else if target_wal.exists() {
+        std::fs::remove_file(target_wal)?;
+    }
+    Ok(())
+}
+
+/// Run `workload` against the database at `path`. Errors of individual
+/// statements are collected in the report; only failing to open the
+/// database aborts the replay.
+pub fn replay(
+    path: &str,
+    workload: &[CapturedStatement],
+    pacing: Pacing,
+) -> Result<ReplayReport, LimboError> {
+    let io: Arc<dyn IO> = Arc::new(PlatformIO::new()?);
+    let db = Database::open_file_with_flags(
+        io,
+        path,
+        OpenFlags::default(),
+        DatabaseOpts::new(),
+        None,
+        Arc::new(SqliteDialect),
+    )?;
+    let conn = db.connect()?;
+
+    let mut report = ReplayReport::default();
+    let start = Instant::now();
+    for (i, statement) in workload.iter().enumerate() {
+        if let Pacing::Scaled(speed) = pacing {
+            let due = statement.elapsed.div_f64(speed);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        let began = Instant::now();
+        let result = conn.prepare(&statement.sql).and_then(|mut stmt| {
+            for (index, value) in &statement.params {
+                stmt.bind_at(*index, value.clone())?;
+            }
+            stmt.run_ignore_rows()
+        });
+        let latency = began.elapsed();
+        report.busy_time += latency;
+        report.max_latency = report.max_latency.max(latency);
+        match result {
+            Ok(()) => report.executed += 1,
+            Err(e) => report.errors.push((i, e.to_string())),
+        }
+    }
+    conn.close()?;
+    Ok(report)
+}
@@ -0,0 +1,95 @@
+//! turso-replay CLI - Replay a captured workload against a copy of a database.
+
+use clap::Parser;
+use std::path::PathBuf;
+use turso_replay::{copy_database, parse_workload, replay, Pacing};
+
+#[derive(Parser)]
+#[command(name = "turso-replay")]
+#[command(
+    version,
+    about = "Replay a captured workload against a copy of a database"
+)]
+struct Args {
+    /// Capture file written by a connection's workload capture
+    workload: PathBuf,
+
+    /// Database the workload was captured against; it is not modified
+    database: PathBuf,
+
+    /// Where to put the copy the workload runs against
+    #[arg(long, value_name = "PATH")]
+    target: PathBuf,
+
+    /// Pacing relative to the capture: 1 keeps the original gaps between
+    /// statements, 10 replays ten times faster, 0 runs back to back
+    #[arg(long, default_value_t = 0.0)]
+    speed: f64,
+
+    /// Print every failing statement
+    #[arg(long)]
+    verbose: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if args.speed < 0.0 || !args.speed.is_finite() {
+        eprintln!("Error: --speed must be a non-negative number");
+        std::process::exit(1);
+    }
+    if args.target == args.database {
+        eprintln!("Error: --target must differ from the captured database");
+        std::process::exit(1);
+    }
+
+    let workload = match std::fs::read_to_string(&args.workload)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| parse_workload(&contents))
+    {
+        Ok(workload) => workload,
+        Err(e) => {
+            eprintln!("Error reading '{}': {e}", args.workload.display());
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = copy_database(&args.database, &args.target) {
+        eprintln!("Error copying '{}': {e}", args.database.display());
+        std::process::exit(1);
+    }
+
+    let pacing = if args.speed == 0.0 {
+        Pacing::AsFastAsPossible
+    } else {
+        Pacing::Scaled(args.speed)
+    };
+    let report = match replay(&args.target.to_string_lossy(), &workload, pacing) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error opening '{}': {e}", args.target.display());
+            std::process::exit(1);
+        }
+    };
+
+    if args.verbose {
+        for (i, error) in &report.errors {
+            eprintln!("statement {i}: {error}: {}", workload[*i].sql);
+        }
+    }
+    let mean = report
+        .busy_time
+        .checked_div(workload.len().max(1) as u32)
+        .unwrap_or_default();
+    println!(
+        "{} statements, {} executed, {} failed",
+        workload.len(),
+        report.executed,
+        report.errors.len()
+    );
+    println!(
+        "busy {:?}, mean latency {mean:?}, max latency {:?}",
+        report.busy_time, report.max_latency
+    );
+
+    std::process::exit(if report.errors.is_empty() { 0 } else { 2 });
+}
@@ -0,0 +1,127 @@
+//! Capture a workload with turso_core and replay it with turso-replay.
+
+use std::sync::Arc;
+use tempfile::{NamedTempFile, TempDir};
+use turso_core::{
+    Connection, Database, DatabaseOpts, OpenFlags, PlatformIO, RedactionRule, SqliteDialect, Value,
+    WorkloadCapture, IO,
+};
+use turso_replay::{copy_database, parse_workload, replay, Pacing};
+
+fn open_db(path: &str) -> (Arc<Database>, Arc<Connection>) {
+    let io: Arc<dyn IO> = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file_with_flags(
+        io,
+        path,
+        OpenFlags::default(),
+        DatabaseOpts::new(),
+        None,
+        Arc::new(SqliteDialect),
+    )
+    .unwrap();
+    let conn = db.connect().unwrap();
+    (db, conn)
+}
+
+fn count(conn: &Arc<Connection>, sql: &str) -> i64 {
+    let mut stmt = conn.prepare(sql).unwrap();
+    let rows = stmt.run_collect_rows().unwrap();
+    match rows[0][0] {
+        Value::Numeric(turso_core::Numeric::Integer(n)) => n,
+        ref other => panic!("expected an integer, got {other:?}"),
+    }
+}
+
+/// Creates `t` in a fresh database, then captures a few writes against it.
+/// Returns the database directory and the capture file; the database itself
+/// is left as it was before the captured writes by restoring a copy.
+fn capture(rules: Vec<RedactionRule>) -> (TempDir, NamedTempFile) {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("original.db");
+    let pristine = dir.path().join("pristine.db");
+    let capture_file = NamedTempFile::new().unwrap();
+    {
+        let (_db, conn) = open_db(db_path.to_str().unwrap());
+        conn.execute("CREATE TABLE t(id INTEGER PRIMARY KEY, name TEXT, data BLOB)")
+            .unwrap();
+        conn.close().unwrap();
+    }
+    copy_database(&db_path, &pristine).unwrap();
+    {
+        let (_db, conn) = open_db(db_path.to_str().unwrap());
+        let capture = WorkloadCapture::create_file(capture_file.path(), rules).unwrap();
+        conn.set_workload_capture(Some(Arc::new(capture)));
+        for i in 1..=3 {
+            let mut stmt = conn.prepare("INSERT INTO t VALUES (?, :name, ?)").unwrap();
+            stmt.bind_at(1.try_into().unwrap(), Value::from_i64(i))
+                .unwrap();
+            stmt.bind_at(2.try_into().unwrap(), Value::build_text(format!("n{i}")))
+                .unwrap();
+            stmt.bind_at(3.try_into().unwrap(), Value::from_blob(vec![i as u8]))
+                .unwrap();
+            stmt.run_ignore_rows().unwrap();
+        }
+        conn.execute("DELETE FROM t WHERE id = 2").unwrap();
+        conn.set_workload_capture(None);
+        conn.close().unwrap();
+    }
+    copy_database(&pristine, &db_path).unwrap();
+    (dir, capture_file)
+}
+
+#[test]
+fn test_replay_reproduces_captured_writes() {
+    let (dir, capture_file) = capture(vec![]);
+    let workload = parse_workload(&std::fs::read_to_string(capture_file.path()).unwrap()).unwrap();
+    assert_eq!(workload.len(), 4);
+    assert_eq!(workload[0].params.len(), 3);
+
+    let target = dir.path().join("replay.db");
+    copy_database(&dir.path().join("original.db"), &target).unwrap();
+    let report = replay(
+        target.to_str().unwrap(),
+        &workload,
+        Pacing::AsFastAsPossible,
+    )
+    .unwrap();
+    assert_eq!(report.executed, 4, "{:?}", report.errors);
+
+    let (_db, conn) = open_db(target.to_str().unwrap());
+    assert_eq!(count(&conn, "SELECT count(*) FROM t"), 2);
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT count(*) FROM t WHERE name = 'n3' AND data = x'03'"
+        ),
+        1
+    );
+}
+
+#[test]
+fn test_redacted_parameters_replay_as_placeholders() {
+    let (dir, capture_file) = capture(vec![RedactionRule::TextAndBlobParameters]);
+    let contents = std::fs::read_to_string(capture_file.path()).unwrap();
+    assert!(!contents.contains("n1"), "{contents}");
+    let workload = parse_workload(&contents).unwrap();
+
+    let target = dir.path().join("replay.db");
+    copy_database(&dir.path().join("original.db"), &target).unwrap();
+    let report = replay(target.to_str().unwrap(), &workload, Pacing::Scaled(1000.0)).unwrap();
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+
+    let (_db, conn) = open_db(target.to_str().unwrap());
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT count(*) FROM t WHERE name = '' AND data = x''"
+        ),
+        2
+    );
+}
+
+#[test]
+fn test_parse_rejects_malformed_lines() {
+    let err = parse_workload("{\"elapsed_us\":0,\"sql\":\"SELECT 1\",\"params\":[]}\nnot json")
+        .unwrap_err();
+    assert!(err.starts_with("line 2:"), "{err}");
+}